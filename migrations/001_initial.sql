@@ -8,23 +8,23 @@ CREATE TABLE shopify_tokens (
     scope TEXT NOT NULL,
     created_at TIMESTAMPTZ DEFAULT NOW(),
     updated_at TIMESTAMPTZ DEFAULT NOW(),
-    expires_at TIMESTAMPTZ, -- For future token expiration support
-    
-    INDEX idx_shopify_tokens_shop (shop_domain),
-    INDEX idx_shopify_tokens_created (created_at)
+    expires_at TIMESTAMPTZ -- For future token expiration support
 );
 
+CREATE INDEX idx_shopify_tokens_shop ON shopify_tokens (shop_domain);
+CREATE INDEX idx_shopify_tokens_created ON shopify_tokens (created_at);
+
 -- Table for CSRF state management
 CREATE TABLE oauth_states (
     id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
     state_token VARCHAR(255) NOT NULL UNIQUE,
     created_at TIMESTAMPTZ DEFAULT NOW(),
-    expires_at TIMESTAMPTZ NOT NULL,
-    
-    INDEX idx_oauth_states_token (state_token),
-    INDEX idx_oauth_states_expires (expires_at)
+    expires_at TIMESTAMPTZ NOT NULL
 );
 
+CREATE INDEX idx_oauth_states_token ON oauth_states (state_token);
+CREATE INDEX idx_oauth_states_expires ON oauth_states (expires_at);
+
 -- Table for rate limiting (optional - can use Redis instead)
 CREATE TABLE rate_limit_buckets (
     id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
@@ -33,11 +33,12 @@ CREATE TABLE rate_limit_buckets (
     token_count INTEGER NOT NULL DEFAULT 0,
     last_refill TIMESTAMPTZ DEFAULT NOW(),
     
-    UNIQUE(identifier, bucket_type),
-    INDEX idx_rate_limit_identifier (identifier),
-    INDEX idx_rate_limit_last_refill (last_refill)
+    UNIQUE(identifier, bucket_type)
 );
 
+CREATE INDEX idx_rate_limit_identifier ON rate_limit_buckets (identifier);
+CREATE INDEX idx_rate_limit_last_refill ON rate_limit_buckets (last_refill);
+
 -- Function to automatically update updated_at timestamp
 CREATE OR REPLACE FUNCTION update_updated_at_column()
 RETURNS TRIGGER AS $$
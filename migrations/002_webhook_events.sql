@@ -0,0 +1,14 @@
+-- Locally captured webhook deliveries, used to reconstruct resource history
+
+CREATE TABLE webhook_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    shop_domain VARCHAR(255) NOT NULL,
+    topic VARCHAR(100) NOT NULL,
+    resource_id BIGINT, -- Order ID for order and refund topics
+    webhook_id VARCHAR(255), -- X-Shopify-Webhook-Id
+    payload JSONB NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_webhook_events_resource ON webhook_events (shop_domain, resource_id);
+CREATE INDEX idx_webhook_events_received ON webhook_events (received_at);
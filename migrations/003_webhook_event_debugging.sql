@@ -0,0 +1,10 @@
+-- Keep the exact delivery Shopify sent, plus verification and processing outcome
+
+ALTER TABLE webhook_events
+    ALTER COLUMN payload DROP NOT NULL, -- NULL when the body isn't valid JSON
+    ADD COLUMN raw_body BYTEA,
+    ADD COLUMN headers JSONB NOT NULL DEFAULT '{}',
+    ADD COLUMN verified BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN processing_attempts INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN processed_at TIMESTAMPTZ,
+    ADD COLUMN last_error TEXT;
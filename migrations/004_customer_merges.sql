@@ -0,0 +1,34 @@
+-- Local mirror of Shopify customers, used for duplicate detection and merges
+
+CREATE TABLE customers (
+    shop_domain VARCHAR(255) NOT NULL,
+    customer_id BIGINT NOT NULL,
+    email VARCHAR(255),
+    phone VARCHAR(64),
+    first_name VARCHAR(255),
+    last_name VARCHAR(255),
+    orders_count INTEGER NOT NULL DEFAULT 0,
+    tags TEXT NOT NULL DEFAULT '',
+    note TEXT,
+    created_at TIMESTAMPTZ,
+    merged_into BIGINT, -- Primary customer ID once this record has been merged
+    synced_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (shop_domain, customer_id)
+);
+
+CREATE INDEX idx_customers_email ON customers (shop_domain, LOWER(email)) WHERE merged_into IS NULL;
+
+-- Audit trail of duplicate customer records merged into a primary record
+
+CREATE TABLE customer_merges (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    shop_domain VARCHAR(255) NOT NULL,
+    primary_customer_id BIGINT NOT NULL,
+    merged_customer_ids BIGINT[] NOT NULL,
+    reason TEXT,
+    shopify_updated BOOLEAN NOT NULL DEFAULT FALSE,
+    shopify_errors JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_customer_merges_shop ON customer_merges (shop_domain, created_at);
@@ -0,0 +1,15 @@
+-- Hourly outbound Shopify API usage per shop and feature
+
+CREATE TABLE api_usage (
+    shop_domain VARCHAR(255) NOT NULL,
+    period_start TIMESTAMPTZ NOT NULL, -- Start of the hour
+    feature VARCHAR(100) NOT NULL, -- First path segment, e.g. "orders" or "customers"
+    calls BIGINT NOT NULL DEFAULT 0,
+    throttled BIGINT NOT NULL DEFAULT 0, -- 429 responses
+    errors BIGINT NOT NULL DEFAULT 0, -- Other failed calls
+    utilization_sum DOUBLE PRECISION NOT NULL DEFAULT 0, -- Sum of used/max call-limit ratios
+    utilization_samples BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (shop_domain, period_start, feature)
+);
+
+CREATE INDEX idx_api_usage_period ON api_usage (period_start);
@@ -0,0 +1,19 @@
+-- Encrypted per-shop credentials for third-party integrations (Klaviyo, 3PL, SMTP)
+
+CREATE TABLE shop_secrets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    shop_domain VARCHAR(255) NOT NULL,
+    integration VARCHAR(64) NOT NULL,
+    encrypted_value TEXT NOT NULL, -- JSON credentials encrypted with TokenEncryption
+    key_id VARCHAR(16) NOT NULL, -- Fingerprint of the encryption key used
+    version INTEGER NOT NULL DEFAULT 1, -- Incremented every time the credentials are rotated
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    rotated_at TIMESTAMPTZ,
+    UNIQUE (shop_domain, integration)
+);
+
+CREATE TRIGGER update_shop_secrets_updated_at
+    BEFORE UPDATE ON shop_secrets
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
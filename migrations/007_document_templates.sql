@@ -0,0 +1,16 @@
+-- Per-shop templates for generated order documents (packing slips, invoices)
+
+CREATE TABLE document_templates (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    shop_domain VARCHAR(255) NOT NULL,
+    kind VARCHAR(32) NOT NULL, -- packing-slip or invoice
+    template JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (shop_domain, kind)
+);
+
+CREATE TRIGGER update_document_templates_updated_at
+    BEFORE UPDATE ON document_templates
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,13 @@
+-- Per-shop rules for choosing the fulfillment location by destination and stock
+
+CREATE TABLE fulfillment_routing (
+    shop_domain VARCHAR(255) PRIMARY KEY,
+    config JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_fulfillment_routing_updated_at
+    BEFORE UPDATE ON fulfillment_routing
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,8 @@
+-- Shops whose data lives in a regional database instead of the home database.
+-- Only kept in the home database; shops without a row stay in the home database.
+
+CREATE TABLE shop_regions (
+    shop_domain VARCHAR(255) PRIMARY KEY,
+    region VARCHAR(32) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,4 @@
+-- Hash of the mirrored fields, so syncs can skip rows that haven't changed.
+-- synced_at now records the last sync that changed the row.
+
+ALTER TABLE customers ADD COLUMN content_hash VARCHAR(64);
@@ -0,0 +1,21 @@
+-- Local mirror of order line items, one row per line item, for product-level
+-- reporting. Cancelled and test orders are not mirrored.
+
+CREATE TABLE order_line_items (
+    shop_domain VARCHAR(255) NOT NULL,
+    line_item_id BIGINT NOT NULL,
+    order_id BIGINT NOT NULL,
+    order_created_at TIMESTAMPTZ NOT NULL,
+    product_id BIGINT,
+    variant_id BIGINT,
+    title TEXT NOT NULL,
+    sku VARCHAR(255),
+    quantity INTEGER NOT NULL,
+    price NUMERIC(14, 2) NOT NULL,
+    total_discount NUMERIC(14, 2) NOT NULL DEFAULT 0,
+    synced_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (shop_domain, line_item_id)
+);
+
+CREATE INDEX idx_order_line_items_order ON order_line_items (shop_domain, order_id);
+CREATE INDEX idx_order_line_items_created ON order_line_items (shop_domain, order_created_at);
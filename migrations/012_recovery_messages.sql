@@ -0,0 +1,22 @@
+-- Abandoned checkout recovery messages and what happened to each one:
+-- opens (tracking pixel), clicks (signed redirect), bounces, and conversions.
+
+CREATE TABLE recovery_messages (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    shop_domain VARCHAR(255) NOT NULL,
+    checkout_id BIGINT NOT NULL,
+    recipient VARCHAR(255) NOT NULL,
+    subject TEXT,
+    sent_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    first_opened_at TIMESTAMPTZ,
+    open_count INTEGER NOT NULL DEFAULT 0,
+    first_clicked_at TIMESTAMPTZ,
+    click_count INTEGER NOT NULL DEFAULT 0,
+    bounced_at TIMESTAMPTZ,
+    bounce_reason TEXT,
+    converted_at TIMESTAMPTZ,
+    order_id BIGINT
+);
+
+CREATE INDEX idx_recovery_messages_sent ON recovery_messages (shop_domain, sent_at);
+CREATE INDEX idx_recovery_messages_checkout ON recovery_messages (shop_domain, checkout_id);
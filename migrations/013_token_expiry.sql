@@ -0,0 +1,4 @@
+-- Online tokens carry an expiry; the cleanup job deletes them once it passes
+CREATE INDEX IF NOT EXISTS idx_shopify_tokens_expires
+    ON shopify_tokens (expires_at)
+    WHERE expires_at IS NOT NULL;
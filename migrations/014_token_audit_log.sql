@@ -0,0 +1,16 @@
+-- Every store, read, rotation and deletion of a shop access token, for
+-- credential-handling reviews. Rows live in the shop's own database.
+
+CREATE TABLE token_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    shop_domain VARCHAR(255), -- NULL for sweeps that span shops
+    action VARCHAR(32) NOT NULL, -- store, rotate, read, delete, expire, rekey
+    outcome VARCHAR(32) NOT NULL, -- success, not_found, failure
+    actor TEXT NOT NULL, -- "GET /api/orders" for requests, the job name for background work
+    source_ip VARCHAR(64),
+    detail TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_token_audit_log_shop_created ON token_audit_log (shop_domain, created_at DESC);
+CREATE INDEX idx_token_audit_log_created ON token_audit_log (created_at DESC);
@@ -0,0 +1,40 @@
+-- Local copy of Shopify orders, kept current by a backfill, periodic
+-- incremental syncs and order webhooks, so /api/orders can filter and page
+-- without calling Shopify. `data` is the order as /api/orders returns it;
+-- the other columns exist to filter on.
+
+CREATE TABLE orders (
+    shop_domain VARCHAR(255) NOT NULL,
+    order_id BIGINT NOT NULL,
+    order_number BIGINT NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    email VARCHAR(255),
+    financial_status VARCHAR(64),
+    fulfillment_status VARCHAR(64),
+    source_name VARCHAR(255),
+    total_price NUMERIC(14, 2) NOT NULL,
+    currency VARCHAR(8),
+    test BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    processed_at TIMESTAMPTZ,
+    closed_at TIMESTAMPTZ,
+    cancelled_at TIMESTAMPTZ,
+    data JSONB NOT NULL,
+    synced_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (shop_domain, order_id)
+);
+
+CREATE INDEX idx_orders_created ON orders (shop_domain, created_at);
+CREATE INDEX idx_orders_updated ON orders (shop_domain, updated_at);
+
+-- Where each shop's order sync left off. Orders are only served locally
+-- once backfilled_at is set.
+
+CREATE TABLE order_sync_state (
+    shop_domain VARCHAR(255) PRIMARY KEY,
+    backfilled_at TIMESTAMPTZ,
+    updated_at_cursor TIMESTAMPTZ, -- next incremental sync asks for orders updated since this
+    last_synced_at TIMESTAMPTZ,
+    last_error TEXT
+);
@@ -0,0 +1,56 @@
+-- Local mirror of each shop's products and variants, kept fresh by
+-- products/* webhooks and a periodic reconciliation pass, so /api/catalog
+-- can search without calling Shopify. `data` is the product as Shopify sent
+-- it; the other columns exist to filter and search on.
+
+CREATE TABLE catalog_products (
+    shop_domain VARCHAR(255) NOT NULL,
+    product_id BIGINT NOT NULL,
+    title TEXT NOT NULL,
+    handle VARCHAR(255) NOT NULL,
+    vendor VARCHAR(255) NOT NULL,
+    product_type VARCHAR(255) NOT NULL,
+    status VARCHAR(32) NOT NULL,
+    tags TEXT NOT NULL,
+    skus TEXT NOT NULL, -- variant SKUs, space-separated, so they can be searched with the product
+    updated_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL,
+    synced_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', title), 'A') ||
+        setweight(to_tsvector('simple', skus), 'A') ||
+        setweight(to_tsvector('simple', vendor), 'B') ||
+        setweight(to_tsvector('simple', tags), 'B')
+    ) STORED,
+    PRIMARY KEY (shop_domain, product_id)
+);
+
+CREATE INDEX idx_catalog_products_search ON catalog_products USING GIN (search_vector);
+CREATE INDEX idx_catalog_products_vendor ON catalog_products (shop_domain, vendor);
+
+CREATE TABLE catalog_variants (
+    shop_domain VARCHAR(255) NOT NULL,
+    variant_id BIGINT NOT NULL,
+    product_id BIGINT NOT NULL,
+    title TEXT NOT NULL,
+    sku VARCHAR(255),
+    barcode VARCHAR(255),
+    price NUMERIC(14, 2) NOT NULL,
+    inventory_quantity INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (shop_domain, variant_id),
+    FOREIGN KEY (shop_domain, product_id)
+        REFERENCES catalog_products (shop_domain, product_id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_catalog_variants_product ON catalog_variants (shop_domain, product_id);
+CREATE INDEX idx_catalog_variants_sku ON catalog_variants (shop_domain, sku);
+
+-- When each shop's catalog was last reconciled against Shopify. The catalog
+-- is only served once reconciled_at is set.
+
+CREATE TABLE catalog_sync_state (
+    shop_domain VARCHAR(255) PRIMARY KEY,
+    reconciled_at TIMESTAMPTZ,
+    last_synced_at TIMESTAMPTZ,
+    last_error TEXT
+);
@@ -0,0 +1,17 @@
+-- Background jobs shared by every instance. A job runs on whichever instance
+-- claims it first: the claim takes a lease (renewed while the job runs) and
+-- moves next_run_at past the slot, so other instances skip that slot.
+
+CREATE TABLE scheduled_jobs (
+    name VARCHAR(100) PRIMARY KEY,
+    next_run_at TIMESTAMPTZ,
+    locked_by VARCHAR(255),
+    locked_until TIMESTAMPTZ,
+    last_started_at TIMESTAMPTZ,
+    last_finished_at TIMESTAMPTZ,
+    last_status VARCHAR(16), -- 'ok' or 'failed'
+    last_summary TEXT,
+    last_error TEXT,
+    last_duration_ms BIGINT,
+    last_instance VARCHAR(255)
+);
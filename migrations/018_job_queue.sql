@@ -0,0 +1,29 @@
+-- Durable work queue shared by every instance. Workers claim ready rows with
+-- SELECT ... FOR UPDATE SKIP LOCKED, so each job runs on one worker at a time.
+-- Failed jobs are retried with exponential backoff until max_attempts, then
+-- left in 'dead' for an operator to inspect or retry.
+
+CREATE TABLE jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    seq BIGSERIAL NOT NULL,
+    kind VARCHAR(100) NOT NULL,
+    shop_domain VARCHAR(255),
+    -- Jobs sharing a key run one at a time, in the order they were enqueued
+    queue_key VARCHAR(255),
+    payload JSONB NOT NULL DEFAULT '{}',
+    status VARCHAR(16) NOT NULL DEFAULT 'queued', -- queued, running, succeeded, dead
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 5,
+    run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    locked_by VARCHAR(255),
+    locked_until TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_jobs_ready ON jobs (run_at, seq) WHERE status IN ('queued', 'running');
+CREATE INDEX idx_jobs_queue_key ON jobs (queue_key, seq) WHERE status IN ('queued', 'running');
+CREATE INDEX idx_jobs_status ON jobs (status, updated_at);
+CREATE INDEX idx_jobs_kind ON jobs (kind, seq);
@@ -0,0 +1,6 @@
+-- Bind each state to the shop, scopes and browser that started the install
+ALTER TABLE oauth_states
+    ADD COLUMN IF NOT EXISTS shop_domain TEXT,
+    ADD COLUMN IF NOT EXISTS scopes TEXT,
+    ADD COLUMN IF NOT EXISTS client_ip TEXT,
+    ADD COLUMN IF NOT EXISTS user_agent TEXT;
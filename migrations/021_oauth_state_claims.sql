@@ -0,0 +1,5 @@
+-- A callback claims its state before exchanging the code, so repeats of it
+-- get the stored outcome instead of a second exchange
+ALTER TABLE oauth_states
+    ADD COLUMN IF NOT EXISTS code_hash TEXT,
+    ADD COLUMN IF NOT EXISTS exchange_result TEXT;
@@ -0,0 +1,28 @@
+-- Search over the customer mirror for /api/customers/search, kept fresh by
+-- customers/* webhooks and a periodic reconciliation pass. Emails are split
+-- into words for the text search and matched whole through
+-- idx_customers_email; phones are compared on their digits alone.
+
+ALTER TABLE customers
+    ADD COLUMN updated_at TIMESTAMPTZ,
+    ADD COLUMN phone_digits TEXT GENERATED ALWAYS AS (
+        regexp_replace(COALESCE(phone, ''), '[^0-9]', '', 'g')
+    ) STORED,
+    ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', COALESCE(first_name, '') || ' ' || COALESCE(last_name, '')), 'A') ||
+        setweight(to_tsvector('simple', regexp_replace(COALESCE(email, ''), '[^[:alnum:]]+', ' ', 'g')), 'B') ||
+        setweight(to_tsvector('simple', tags), 'C')
+    ) STORED;
+
+CREATE INDEX idx_customers_search ON customers USING GIN (search_vector);
+CREATE INDEX idx_customers_phone ON customers (shop_domain, phone_digits) WHERE phone_digits <> '';
+
+-- When each shop's customers were last reconciled against Shopify. Search is
+-- only served once reconciled_at is set.
+
+CREATE TABLE customer_index_sync_state (
+    shop_domain VARCHAR(255) PRIMARY KEY,
+    reconciled_at TIMESTAMPTZ,
+    last_synced_at TIMESTAMPTZ,
+    last_error TEXT
+);
@@ -0,0 +1,4 @@
+-- Open orders for /api/orders/summary, which dashboards poll every minute.
+
+CREATE INDEX idx_orders_open ON orders (shop_domain, created_at)
+    WHERE closed_at IS NULL AND cancelled_at IS NULL AND NOT test;
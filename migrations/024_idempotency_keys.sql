@@ -0,0 +1,21 @@
+-- Responses to write requests sent with an Idempotency-Key, replayed when a
+-- client retries so the Shopify resource is only created once. A row without
+-- a status_code is a request still in progress. Keys are per shop and per
+-- API client, and purged after IDEMPOTENCY_KEY_TTL_HOURS.
+
+CREATE TABLE idempotency_keys (
+    shop_domain VARCHAR(255) NOT NULL,
+    client VARCHAR(255) NOT NULL,
+    idempotency_key VARCHAR(255) NOT NULL,
+    method VARCHAR(16) NOT NULL,
+    path TEXT NOT NULL,
+    request_hash VARCHAR(64) NOT NULL,
+    status_code INTEGER,
+    content_type VARCHAR(255),
+    response_body BYTEA,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ,
+    PRIMARY KEY (shop_domain, client, idempotency_key)
+);
+
+CREATE INDEX idx_idempotency_keys_created ON idempotency_keys (created_at);
@@ -0,0 +1,9 @@
+-- When personal data was scrubbed from a row, by the PII_RETENTION_DAYS
+-- policy or a customers/redact request. An order refreshed from Shopify
+-- afterwards is unmarked, so the next retention pass scrubs it again.
+
+ALTER TABLE webhook_events ADD COLUMN pii_redacted_at TIMESTAMPTZ;
+ALTER TABLE orders ADD COLUMN pii_redacted_at TIMESTAMPTZ;
+ALTER TABLE recovery_messages ADD COLUMN pii_redacted_at TIMESTAMPTZ;
+
+CREATE INDEX idx_webhook_events_unredacted ON webhook_events (received_at) WHERE pii_redacted_at IS NULL;
@@ -0,0 +1,15 @@
+-- Latest fraud risk level of each order, from orders/risk_assessment_changed
+-- webhooks and /api/orders/{id}/risks lookups, for /api/orders?risk=. Kept
+-- apart from the orders table so an assessment that arrives before its
+-- order is mirrored still counts once the order shows up.
+
+CREATE TABLE order_risks (
+    shop_domain VARCHAR(255) NOT NULL,
+    order_id BIGINT NOT NULL,
+    -- low, medium or high
+    risk_level VARCHAR(16) NOT NULL,
+    assessed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (shop_domain, order_id)
+);
+
+CREATE INDEX idx_order_risks_level ON order_risks (shop_domain, risk_level);
@@ -0,0 +1,32 @@
+-- Per-shop low-stock thresholds by SKU (and optionally location), and the
+-- stock levels currently at or below them. An alert stays open until stock
+-- is back above its threshold, so each drop is notified once.
+
+CREATE TABLE low_stock_rules (
+    shop_domain VARCHAR(255) PRIMARY KEY,
+    config JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_low_stock_rules_updated_at
+    BEFORE UPDATE ON low_stock_rules
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
+
+CREATE TABLE low_stock_alerts (
+    shop_domain VARCHAR(255) NOT NULL,
+    inventory_item_id BIGINT NOT NULL,
+    location_id BIGINT NOT NULL,
+    sku VARCHAR(255) NOT NULL,
+    available INTEGER NOT NULL,
+    threshold INTEGER NOT NULL,
+    triggered_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Set once stock is back above the threshold; cleared if it drops again
+    resolved_at TIMESTAMPTZ,
+    notified_at TIMESTAMPTZ,
+    PRIMARY KEY (shop_domain, inventory_item_id, location_id)
+);
+
+CREATE INDEX idx_low_stock_alerts_open ON low_stock_alerts (shop_domain, triggered_at) WHERE resolved_at IS NULL;
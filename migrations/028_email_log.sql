@@ -0,0 +1,20 @@
+-- Every email handed to the mail provider, for recovery messages and
+-- notifications alike, and whether the provider accepted it.
+
+CREATE TABLE email_log (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    shop_domain VARCHAR(255) NOT NULL,
+    -- What sent it, e.g. recovery or notification
+    kind VARCHAR(50) NOT NULL,
+    provider VARCHAR(50) NOT NULL,
+    recipient VARCHAR(255) NOT NULL,
+    subject TEXT NOT NULL,
+    -- sent or failed
+    status VARCHAR(20) NOT NULL,
+    provider_message_id TEXT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    pii_redacted_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_email_log_shop ON email_log (shop_domain, created_at);
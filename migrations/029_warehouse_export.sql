@@ -0,0 +1,20 @@
+-- How far each shop's local copies have been exported to the data warehouse.
+-- Rows go out in (synced_at, id) order, and the last one written is the
+-- watermark the next export starts after.
+
+CREATE TABLE warehouse_export_state (
+    shop_domain VARCHAR(255) NOT NULL,
+    -- orders, customers or products
+    table_name VARCHAR(50) NOT NULL,
+    synced_until TIMESTAMPTZ,
+    last_id BIGINT,
+    rows_exported BIGINT NOT NULL DEFAULT 0,
+    last_exported_at TIMESTAMPTZ,
+    last_error TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (shop_domain, table_name)
+);
+
+CREATE INDEX idx_orders_export ON orders (shop_domain, synced_at, order_id);
+CREATE INDEX idx_customers_export ON customers (shop_domain, synced_at, customer_id);
+CREATE INDEX idx_catalog_products_export ON catalog_products (shop_domain, synced_at, product_id);
@@ -0,0 +1,16 @@
+-- Token and OAuth state storage for a mysql:// (or mariadb://) DATABASE_URL
+
+CREATE TABLE IF NOT EXISTS shopify_tokens (
+    shop_domain VARCHAR(255) NOT NULL PRIMARY KEY,
+    encrypted_access_token TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    created_at TIMESTAMP(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+    updated_at TIMESTAMP(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6)
+);
+
+CREATE TABLE IF NOT EXISTS oauth_states (
+    state_token VARCHAR(255) NOT NULL PRIMARY KEY,
+    -- Unix seconds
+    expires_at BIGINT NOT NULL,
+    INDEX idx_oauth_states_expires (expires_at)
+);
@@ -0,0 +1,4 @@
+-- Unix seconds; NULL for offline tokens, which never expire
+ALTER TABLE shopify_tokens
+    ADD COLUMN expires_at BIGINT NULL,
+    ADD INDEX idx_shopify_tokens_expires (expires_at);
@@ -0,0 +1,2 @@
+-- Where to send the merchant after the install this state belongs to
+ALTER TABLE oauth_states ADD COLUMN return_to TEXT NULL;
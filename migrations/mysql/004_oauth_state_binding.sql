@@ -0,0 +1,6 @@
+-- Bind each state to the shop, scopes and browser that started the install
+ALTER TABLE oauth_states
+    ADD COLUMN shop_domain VARCHAR(255) NULL,
+    ADD COLUMN scopes TEXT NULL,
+    ADD COLUMN client_ip VARCHAR(64) NULL,
+    ADD COLUMN user_agent TEXT NULL;
@@ -0,0 +1,5 @@
+-- A callback claims its state before exchanging the code, so repeats of it
+-- get the stored outcome instead of a second exchange
+ALTER TABLE oauth_states
+    ADD COLUMN code_hash CHAR(64) NULL,
+    ADD COLUMN exchange_result TEXT NULL;
@@ -0,0 +1,17 @@
+-- Token and OAuth state storage for TOKEN_STORE_BACKEND=sqlite
+
+CREATE TABLE IF NOT EXISTS shopify_tokens (
+    shop_domain TEXT PRIMARY KEY,
+    encrypted_access_token TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+    updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE TABLE IF NOT EXISTS oauth_states (
+    state_token TEXT PRIMARY KEY,
+    -- Unix seconds
+    expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_oauth_states_expires ON oauth_states (expires_at);
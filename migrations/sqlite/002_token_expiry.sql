@@ -0,0 +1,4 @@
+-- Unix seconds; NULL for offline tokens, which never expire
+ALTER TABLE shopify_tokens ADD COLUMN expires_at INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_shopify_tokens_expires ON shopify_tokens (expires_at);
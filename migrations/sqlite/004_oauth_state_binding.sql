@@ -0,0 +1,5 @@
+-- Bind each state to the shop, scopes and browser that started the install
+ALTER TABLE oauth_states ADD COLUMN shop_domain TEXT;
+ALTER TABLE oauth_states ADD COLUMN scopes TEXT;
+ALTER TABLE oauth_states ADD COLUMN client_ip TEXT;
+ALTER TABLE oauth_states ADD COLUMN user_agent TEXT;
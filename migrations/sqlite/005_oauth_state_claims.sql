@@ -0,0 +1,4 @@
+-- A callback claims its state before exchanging the code, so repeats of it
+-- get the stored outcome instead of a second exchange
+ALTER TABLE oauth_states ADD COLUMN code_hash TEXT;
+ALTER TABLE oauth_states ADD COLUMN exchange_result TEXT;
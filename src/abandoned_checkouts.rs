@@ -1,16 +1,26 @@
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
-use tracing::{info, warn, error};
+use tracing::{info, error};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::{AppState, get_token};
+use crate::{
+    AppState,
+    require_token,
+    csv_response::{full_name, list_format, optional, CsvRecord, ListFormat},
+    downloads::list_export,
+    error::{AppResult, ShopifyError},
+    http_client::ShopifyClient,
+    shopify_api::{export_pages, export_params, BILLING_ADDRESS_CSV_HEADERS, SHIPPING_ADDRESS_CSV_HEADERS},
+};
 
 // Shopify Address structure
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = CheckoutAddress)]
 pub struct Address {
     pub address1: Option<String>,
     pub address2: Option<String>,
@@ -31,7 +41,8 @@ pub struct Address {
 }
 
 // Shopify Customer structure
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = CheckoutCustomer)]
 pub struct Customer {
     pub id: Option<u64>,
     pub email: Option<String>,
@@ -45,7 +56,8 @@ pub struct Customer {
 }
 
 // Shopify Line Item structure
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
+#[schema(as = CheckoutLineItem)]
 pub struct LineItem {
     pub id: Option<u64>,
     pub product_id: Option<u64>,
@@ -60,7 +72,7 @@ pub struct LineItem {
 }
 
 // Shopify Abandoned Checkout structure (comprehensive)
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct AbandonedCheckout {
     pub id: u64,
     pub token: String,
@@ -99,7 +111,8 @@ pub struct AbandonedCheckoutsResponse {
 }
 
 // Query parameters for abandoned checkouts
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct AbandonedCheckoutParams {
     pub limit: Option<i32>,
     pub since_id: Option<u64>,
@@ -108,215 +121,260 @@ pub struct AbandonedCheckoutParams {
     pub updated_at_min: Option<String>,
     pub updated_at_max: Option<String>,
     pub status: Option<String>,
+    /// `csv` (one row per line item) or `ndjson` streams every matching checkout
+    pub format: Option<String>,
+    /// With an export format, write the export to a file and return a signed
+    /// `/downloads` link to it instead
+    pub link: Option<bool>,
+}
+
+// =============================================================================
+// CSV Layout
+// =============================================================================
+
+const LINE_ITEM_CSV_HEADERS: [&str; 7] = [
+    "line_item_sku", "line_item_title", "line_item_variant_title", "line_item_vendor",
+    "line_item_quantity", "line_item_price", "line_item_discount",
+];
+
+// Same columns as the order export's addresses
+fn address_csv_fields(address: Option<&Address>) -> Vec<String> {
+    let Some(address) = address else {
+        return vec![String::new(); BILLING_ADDRESS_CSV_HEADERS.len()];
+    };
+    let name = address.name.clone().unwrap_or_else(|| full_name(&address.first_name, &address.last_name));
+
+    vec![
+        name,
+        optional(&address.company),
+        optional(&address.address1),
+        optional(&address.address2),
+        optional(&address.city),
+        optional(&address.province_code.clone().or_else(|| address.province.clone())),
+        optional(&address.zip),
+        optional(&address.country_code.clone().or_else(|| address.country.clone())),
+    ]
 }
 
+impl CsvRecord for AbandonedCheckout {
+    fn csv_headers() -> Vec<&'static str> {
+        let mut headers = vec![
+            "checkout_id", "created_at", "updated_at", "completed_at", "email", "phone", "customer_id",
+            "customer_name", "buyer_accepts_marketing", "currency", "subtotal_price", "total_discounts",
+            "total_tax", "total_price", "recovery_url", "source_name",
+        ];
+        headers.extend(BILLING_ADDRESS_CSV_HEADERS);
+        headers.extend(SHIPPING_ADDRESS_CSV_HEADERS);
+        headers.extend(LINE_ITEM_CSV_HEADERS);
+        headers
+    }
+
+    /// The checkout's own columns, with the line item columns left empty.
+    fn csv_row(&self) -> Vec<String> {
+        let customer_name = self.customer.as_ref().map(|customer| full_name(&customer.first_name, &customer.last_name));
+
+        let mut row = vec![
+            self.id.to_string(),
+            self.created_at.clone(),
+            self.updated_at.clone(),
+            optional(&self.completed_at),
+            optional(&self.email),
+            optional(&self.phone),
+            optional(&self.customer.as_ref().and_then(|customer| customer.id)),
+            optional(&customer_name),
+            optional(&self.buyer_accepts_marketing),
+            optional(&self.currency),
+            optional(&self.subtotal_price),
+            optional(&self.total_discounts),
+            optional(&self.total_tax),
+            optional(&self.total_price),
+            optional(&self.abandoned_checkout_url),
+            optional(&self.source_name),
+        ];
+        row.extend(address_csv_fields(self.billing_address.as_ref()));
+        row.extend(address_csv_fields(self.shipping_address.as_ref()));
+        row.extend(vec![String::new(); LINE_ITEM_CSV_HEADERS.len()]);
+        row
+    }
+
+    /// One row per line item, repeating the checkout's columns.
+    fn csv_rows(&self) -> Vec<Vec<String>> {
+        let checkout = self.csv_row();
+        let line_items = self.line_items.as_deref().unwrap_or_default();
+        if line_items.is_empty() {
+            return vec![checkout];
+        }
+        let checkout = &checkout[..checkout.len() - LINE_ITEM_CSV_HEADERS.len()];
+
+        line_items
+            .iter()
+            .map(|item| {
+                let mut row = checkout.to_vec();
+                row.extend([
+                    optional(&item.sku),
+                    optional(&item.title),
+                    optional(&item.variant_title),
+                    optional(&item.vendor),
+                    optional(&item.quantity),
+                    optional(&item.price),
+                    optional(&item.total_discount),
+                ]);
+                row
+            })
+            .collect()
+    }
+}
+
+/// Lists abandoned checkouts. `format=csv|ndjson` or a matching `Accept`
+/// header streams every matching checkout instead, or with `link=true`
+/// writes them to a file behind a signed download link.
+#[utoipa::path(
+    get,
+    path = "/api/abandoned-checkouts",
+    tag = "abandoned checkouts",
+    params(AbandonedCheckoutParams),
+    responses(
+        (status = 200, description = "Matching checkouts, or the full export", content(
+            (crate::openapi::AbandonedCheckoutList = "application/json"),
+            (String = "text/csv"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 201, description = "With `link`, a signed link to the export file", body = crate::openapi::ExportLink),
+    ),
+)]
 pub async fn abandoned_checkouts_handler(
     Query(params): Query<AbandonedCheckoutParams>,
     State(state): State<AppState>,
-) -> impl IntoResponse {
+    headers: HeaderMap,
+) -> AppResult<Response> {
     let shop = &state.config.shop;
     
     // Get stored access token
-    let token = match get_token(&state.token_store, shop).await {
-        Some(token) => token,
-        None => {
-            warn!("No access token found for shop: {}", shop);
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({
-                    "error": "No access token found. Please complete OAuth flow first.",
-                    "auth_url": "/auth"
-                })),
-            );
-        }
-    };
+    let token = require_token(&state.token_store, shop).await?;
     
-    // Fetch abandoned checkouts from Shopify
-    match fetch_abandoned_checkouts(&token, shop, &params).await {
-        Ok(checkouts) => {
-            info!("Successfully fetched {} abandoned checkouts", checkouts.len());
-            (StatusCode::OK, Json(serde_json::json!({
-                "shop": shop,
-                "checkouts_count": checkouts.len(),
-                "abandoned_checkouts": checkouts
-            })))
-        }
-        Err(e) => {
-            error!("Failed to fetch abandoned checkouts: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": "Failed to fetch abandoned checkouts",
-                    "details": e.to_string()
-                })),
-            )
-        }
+    // Exports: every matching checkout, streamed from Shopify
+    let format = list_format(params.format.as_deref(), &headers)?;
+    if format != ListFormat::Json {
+        let query_params = export_params(filter_params(&params), None);
+        let pages = export_pages(
+            state.shopify.clone(),
+            token,
+            "checkouts.json",
+            query_params,
+            |page: AbandonedCheckoutsResponse| page.checkouts,
+        );
+        return list_export(&state.config.downloads, params.link.unwrap_or(false), format, "abandoned-checkouts", pages).await;
     }
+    
+    // Fetch abandoned checkouts from Shopify
+    let checkouts = fetch_abandoned_checkouts(&state.shopify, &token, &params).await.map_err(|e| {
+        error!("Failed to fetch abandoned checkouts: {}", e);
+        e
+    })?;
+    info!("Successfully fetched {} abandoned checkouts", checkouts.len());
+    
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "checkouts_count": checkouts.len(),
+        "abandoned_checkouts": checkouts
+    }))).into_response())
 }
 
-async fn fetch_abandoned_checkouts(
-    token: &str,
-    shop: &str,
-    params: &AbandonedCheckoutParams,
-) -> Result<Vec<AbandonedCheckout>, Box<dyn std::error::Error>> {
-    let client = reqwest::Client::new();
-    
-    // Build query parameters
+// Filters shared by the list and count endpoints
+fn filter_params(params: &AbandonedCheckoutParams) -> Vec<(&'static str, String)> {
     let mut query_params = Vec::new();
     
-    // Set default limit if not provided
-    let limit = params.limit.unwrap_or(50);
-    query_params.push(format!("limit={}", limit));
-    
     if let Some(since_id) = params.since_id {
-        query_params.push(format!("since_id={}", since_id));
+        query_params.push(("since_id", since_id.to_string()));
     }
     
     if let Some(ref created_at_min) = params.created_at_min {
-        query_params.push(format!("created_at_min={}", urlencoding::encode(created_at_min)));
+        query_params.push(("created_at_min", created_at_min.clone()));
     }
     
     if let Some(ref created_at_max) = params.created_at_max {
-        query_params.push(format!("created_at_max={}", urlencoding::encode(created_at_max)));
+        query_params.push(("created_at_max", created_at_max.clone()));
     }
     
     if let Some(ref updated_at_min) = params.updated_at_min {
-        query_params.push(format!("updated_at_min={}", urlencoding::encode(updated_at_min)));
+        query_params.push(("updated_at_min", updated_at_min.clone()));
     }
     
     if let Some(ref updated_at_max) = params.updated_at_max {
-        query_params.push(format!("updated_at_max={}", urlencoding::encode(updated_at_max)));
+        query_params.push(("updated_at_max", updated_at_max.clone()));
     }
     
     if let Some(ref status) = params.status {
-        query_params.push(format!("status={}", status));
+        query_params.push(("status", status.clone()));
     }
     
-    let query_string = if query_params.is_empty() {
-        String::new()
-    } else {
-        format!("?{}", query_params.join("&"))
-    };
-    
-    let url = format!("https://{}/admin/api/2025-04/checkouts.json{}", shop, query_string);
+    query_params
+}
+
+async fn fetch_abandoned_checkouts(
+    client: &ShopifyClient,
+    token: &str,
+    params: &AbandonedCheckoutParams,
+) -> Result<Vec<AbandonedCheckout>, ShopifyError> {
+    // Set default limit if not provided
+    let limit = params.limit.unwrap_or(50);
+    let mut query_params = vec![("limit", limit.to_string())];
+    query_params.extend(filter_params(params));
     
-    let response = client
-        .get(&url)
-        .header("X-Shopify-Access-Token", token)
-        .header("Content-Type", "application/json")
-        .send()
-        .await?;
+    let query_params_ref: Vec<(&str, &str)> = query_params.iter()
+        .map(|(k, v)| (*k, v.as_str()))
+        .collect();
     
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await?;
-        return Err(format!("Shopify API Error {}: {}", status, error_text).into());
-    }
+    let checkouts_response: AbandonedCheckoutsResponse = client
+        .get_with_auth("checkouts.json", token, Some(&query_params_ref))
+        .await?
+        .data;
     
-    let checkouts_response: AbandonedCheckoutsResponse = response.json().await?;
     Ok(checkouts_response.checkouts)
 }
 
 // New endpoint to get abandoned checkouts count
+#[utoipa::path(
+    get,
+    path = "/api/abandoned-checkouts/count",
+    tag = "abandoned checkouts",
+    params(AbandonedCheckoutParams),
+    responses(
+        (status = 200, description = "Number of matching checkouts", body = crate::openapi::ResourceCount),
+    ),
+)]
 pub async fn abandoned_checkouts_count_handler(
     Query(params): Query<AbandonedCheckoutParams>,
     State(state): State<AppState>,
-) -> impl IntoResponse {
+) -> AppResult<impl IntoResponse> {
     let shop = &state.config.shop;
     
     // Get stored access token
-    let token = match get_token(&state.token_store, shop).await {
-        Some(token) => token,
-        None => {
-            warn!("No access token found for shop: {}", shop);
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({
-                    "error": "No access token found. Please complete OAuth flow first.",
-                    "auth_url": "/auth"
-                })),
-            );
-        }
-    };
+    let token = require_token(&state.token_store, shop).await?;
     
     // Fetch count of abandoned checkouts from Shopify
-    match fetch_abandoned_checkouts_count(&token, shop, &params).await {
-        Ok(count) => {
-            info!("Successfully fetched abandoned checkouts count: {}", count);
-            (StatusCode::OK, Json(serde_json::json!({
-                "shop": shop,
-                "count": count
-            })))
-        }
-        Err(e) => {
-            error!("Failed to fetch abandoned checkouts count: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": "Failed to fetch abandoned checkouts count",
-                    "details": e.to_string()
-                })),
-            )
-        }
-    }
+    let count = fetch_abandoned_checkouts_count(&state.shopify, &token, &params).await.map_err(|e| {
+        error!("Failed to fetch abandoned checkouts count: {}", e);
+        e
+    })?;
+    info!("Successfully fetched abandoned checkouts count: {}", count);
+    
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "count": count
+    }))))
 }
 
 async fn fetch_abandoned_checkouts_count(
+    client: &ShopifyClient,
     token: &str,
-    shop: &str,
     params: &AbandonedCheckoutParams,
-) -> Result<u64, Box<dyn std::error::Error>> {
-    let client = reqwest::Client::new();
-    
-    // Build query parameters (same as regular fetch but for count endpoint)
-    let mut query_params = Vec::new();
-    
-    if let Some(since_id) = params.since_id {
-        query_params.push(format!("since_id={}", since_id));
-    }
-    
-    if let Some(ref created_at_min) = params.created_at_min {
-        query_params.push(format!("created_at_min={}", urlencoding::encode(created_at_min)));
-    }
-    
-    if let Some(ref created_at_max) = params.created_at_max {
-        query_params.push(format!("created_at_max={}", urlencoding::encode(created_at_max)));
-    }
-    
-    if let Some(ref updated_at_min) = params.updated_at_min {
-        query_params.push(format!("updated_at_min={}", urlencoding::encode(updated_at_min)));
-    }
-    
-    if let Some(ref updated_at_max) = params.updated_at_max {
-        query_params.push(format!("updated_at_max={}", urlencoding::encode(updated_at_max)));
-    }
-    
-    if let Some(ref status) = params.status {
-        query_params.push(format!("status={}", status));
-    }
-    
-    let query_string = if query_params.is_empty() {
-        String::new()
-    } else {
-        format!("?{}", query_params.join("&"))
-    };
-    
-    let url = format!("https://{}/admin/api/2025-04/checkouts/count.json{}", shop, query_string);
-    
-    let response = client
-        .get(&url)
-        .header("X-Shopify-Access-Token", token)
-        .header("Content-Type", "application/json")
-        .send()
-        .await?;
-    
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await?;
-        return Err(format!("Shopify API Error {}: {}", status, error_text).into());
-    }
+) -> Result<u64, ShopifyError> {
+    // Same filters as the regular fetch, without a limit
+    let query_params = filter_params(params);
+    let query_params_ref: Vec<(&str, &str)> = query_params.iter()
+        .map(|(k, v)| (*k, v.as_str()))
+        .collect();
     
-    let count_response: serde_json::Value = response.json().await?;
-    let count = count_response["count"].as_u64().unwrap_or(0);
-    Ok(count)
+    client.get_count("checkouts/count.json", token, &query_params_ref).await
 }
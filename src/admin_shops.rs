@@ -0,0 +1,115 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::{
+    AppState,
+    error::{AppError, AppResult},
+    shop_info::ShopResponse,
+};
+
+// =============================================================================
+// Installed Shop Handlers
+// =============================================================================
+
+/// `GET /admin/shops` — shops with a live token, most recently authorized first.
+#[utoipa::path(
+    get,
+    path = "/admin/shops",
+    tag = "shops",
+    responses(
+        (status = 200, description = "Installed shops", body = crate::openapi::InstalledShopList),
+    ),
+)]
+pub async fn list_installed_shops_handler(
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shops = state.token_store.list_shops().await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "count": shops.len(),
+        "shops": shops
+    }))))
+}
+
+/// `DELETE /admin/shops/{shop}` — revokes the app's access on Shopify, then
+/// drops the stored token.
+#[utoipa::path(
+    delete,
+    path = "/admin/shops/{shop}",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 200, description = "Access revoked", body = crate::openapi::ShopRevoked),
+        (status = 404, description = "Shop not installed", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn revoke_shop_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let token = state.token_store.get_token(&shop).await?
+        .ok_or_else(|| AppError::NotFound(format!("Shop {} is not installed", shop)))?;
+
+    state.shopify.for_shop(&shop).revoke_access(&token).await?;
+    state.token_store.delete_token(&shop).await?;
+    info!("🗑️ Revoked access for shop: {}", shop);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "revoked": true
+    }))))
+}
+
+/// `GET /admin/shops/{shop}/health` — checks the stored token still works by
+/// fetching `/shop.json` with it.
+#[utoipa::path(
+    get,
+    path = "/admin/shops/{shop}/health",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 200, description = "Whether the stored token still works", body = crate::openapi::ShopHealth),
+        (status = 404, description = "Shop not installed", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn shop_health_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let token = state.token_store.get_token(&shop).await?
+        .ok_or_else(|| AppError::NotFound(format!("Shop {} is not installed", shop)))?;
+
+    let started = Instant::now();
+    let result = state.shopify
+        .for_shop(&shop)
+        .get_with_auth::<ShopResponse>("shop.json", &token, None)
+        .await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let body = match result {
+        Ok(_) => serde_json::json!({
+            "shop": shop,
+            "healthy": true,
+            "status": StatusCode::OK.as_u16(),
+            "latency_ms": latency_ms
+        }),
+        Err(e) => {
+            warn!("Health check failed for shop {}: {}", shop, e);
+            serde_json::json!({
+                "shop": shop,
+                "healthy": false,
+                "status": e.status_code().as_u16(),
+                "latency_ms": latency_ms,
+                "error": e.to_string()
+            })
+        }
+    };
+
+    Ok((StatusCode::OK, Json(body)))
+}
@@ -0,0 +1,354 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::error::{AppError, AppResult};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header carrying a static API key, as an alternative to `Authorization: Bearer`.
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+// =============================================================================
+// Scopes
+// =============================================================================
+
+/// What an API key or JWT may do. These are this server's own scopes, not
+/// the Shopify access scopes granted to the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiScope {
+    /// `GET` requests under `/api`
+    Read,
+    /// Every other method under `/api`
+    Write,
+    /// Anything under `/admin`
+    Admin,
+}
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Admin => "admin",
+        }
+    }
+
+    /// The scope a request needs in the given area.
+    pub fn required_for(area: ApiArea, method: &Method) -> Self {
+        match area {
+            ApiArea::Admin => Self::Admin,
+            ApiArea::Api if method == Method::GET || method == Method::HEAD => Self::Read,
+            ApiArea::Api => Self::Write,
+        }
+    }
+}
+
+impl FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "read" => Ok(Self::Read),
+            "write" => Ok(Self::Write),
+            "admin" => Ok(Self::Admin),
+            other => Err(format!("Unknown API scope: {}", other)),
+        }
+    }
+}
+
+/// Which group of routes a guard protects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiArea {
+    Api,
+    Admin,
+}
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+/// A static key from `API_KEYS`. Only its digest is kept in memory.
+#[derive(Clone)]
+pub struct ApiKey {
+    pub name: String,
+    digest: [u8; 32],
+    pub scopes: Vec<ApiScope>,
+}
+
+impl ApiKey {
+    pub fn new(name: &str, key: &str, scopes: Vec<ApiScope>) -> Self {
+        Self { name: name.to_string(), digest: Sha256::digest(key.as_bytes()).into(), scopes }
+    }
+
+    // Comparing digests keeps the time taken independent of where keys differ
+    fn matches(&self, presented: &str) -> bool {
+        let presented: [u8; 32] = Sha256::digest(presented.as_bytes()).into();
+        presented.iter().zip(self.digest.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+}
+
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKey").field("name", &self.name).field("scopes", &self.scopes).finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ApiAuthConfig {
+    pub keys: Vec<ApiKey>,
+    /// HS256 secret for bearer JWTs; `None` accepts static keys only
+    pub jwt_secret: Option<Secret<String>>,
+    /// Dev-only: serve `/api` and `/admin` without credentials
+    pub disabled: bool,
+}
+
+impl ApiAuthConfig {
+    /// Reads `API_KEYS`, `ADMIN_API_TOKEN`, `JWT_SECRET` and
+    /// `DANGEROUSLY_DISABLE_API_AUTH`.
+    pub fn from_env(environment: &str) -> AppResult<Self> {
+        let mut keys = parse_api_keys(&std::env::var("API_KEYS").unwrap_or_default())?;
+        // The admin token predates API_KEYS and keeps working as a key with every scope
+        if let Some(token) = std::env::var("ADMIN_API_TOKEN").ok().filter(|token| !token.is_empty()) {
+            keys.push(ApiKey::new("admin", &token, vec![ApiScope::Read, ApiScope::Write, ApiScope::Admin]));
+        }
+
+        let jwt_secret = match std::env::var("JWT_SECRET").ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) {
+            None => None,
+            Some(secret) if secret.len() < 32 => {
+                return Err(AppError::Config("JWT_SECRET must be at least 32 characters".to_string()));
+            }
+            Some(secret) => Some(Secret::new(secret)),
+        };
+
+        let disabled = std::env::var("DANGEROUSLY_DISABLE_API_AUTH")
+            .map(|v| v.trim().eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        if disabled && environment == "production" {
+            return Err(AppError::Config(
+                "DANGEROUSLY_DISABLE_API_AUTH cannot be enabled in production".to_string(),
+            ));
+        }
+
+        Ok(Self { keys, jwt_secret, disabled })
+    }
+
+    /// Whether any credential could be accepted.
+    pub fn is_configured(&self) -> bool {
+        !self.keys.is_empty() || self.jwt_secret.is_some()
+    }
+
+    /// Identifies the caller from `X-API-Key` or `Authorization: Bearer`.
+    /// Bearer values shaped like a JWT are checked as one when `JWT_SECRET`
+    /// is set; anything else is looked up as a static key.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Option<ApiPrincipal> {
+        let presented = headers
+            .get(API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| {
+                headers
+                    .get(header::AUTHORIZATION)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("Bearer "))
+            })
+            .map(str::trim)
+            .filter(|v| !v.is_empty())?;
+
+        if let Some(secret) = &self.jwt_secret {
+            if presented.split('.').count() == 3 {
+                return verify_jwt(presented, secret.expose_secret());
+            }
+        }
+
+        // Check every key so timing doesn't reveal which one matched
+        self.keys
+            .iter()
+            .fold(None, |found, key| if key.matches(presented) { Some(key) } else { found })
+            .map(|key| ApiPrincipal { name: key.name.clone(), scopes: key.scopes.clone() })
+    }
+}
+
+/// Parses `API_KEYS`: `name:key:scope,scope` entries separated by `;`.
+pub fn parse_api_keys(value: &str) -> AppResult<Vec<ApiKey>> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let mut parts = entry.splitn(3, ':');
+            let (name, key, scopes) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(name), Some(key), Some(scopes)) if !name.is_empty() && !key.is_empty() => (name, key, scopes),
+                _ => return Err(AppError::Config("API_KEYS entries must be name:key:scopes".to_string())),
+            };
+            if key.len() < 16 {
+                return Err(AppError::Config(format!("API key {} must be at least 16 characters", name)));
+            }
+            let scopes = scopes
+                .split(',')
+                .filter(|scope| !scope.trim().is_empty())
+                .map(str::parse)
+                .collect::<Result<Vec<ApiScope>, _>>()
+                .map_err(|e| AppError::Config(format!("API key {}: {}", name, e)))?;
+            Ok(ApiKey::new(name, key, scopes))
+        })
+        .collect()
+}
+
+// =============================================================================
+// JWT Bearer Tokens
+// =============================================================================
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JwtClaims {
+    sub: String,
+    exp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nbf: Option<i64>,
+    /// Space-separated, as in OAuth
+    #[serde(default)]
+    scope: String,
+}
+
+/// Claims of an HS256 JWT signed with `secret`. Only the signature is
+/// checked; lifetime and audience are up to the caller.
+pub fn decode_hs256<T: DeserializeOwned>(token: &str, secret: &str) -> Option<T> {
+    let mut parts = token.split('.');
+    let (header, claims, signature) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+
+    // Pinning the algorithm rules out "none" and key-confusion tricks
+    let jwt_header: JwtHeader = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header).ok()?).ok()?;
+    if jwt_header.alg != "HS256" {
+        return None;
+    }
+
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).ok()?;
+    mac.update(format!("{}.{}", header, claims).as_bytes());
+    mac.verify_slice(&URL_SAFE_NO_PAD.decode(signature).ok()?).ok()?;
+
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).ok()?).ok()
+}
+
+/// Checks an HS256 JWT's signature and lifetime. Unknown scopes in the
+/// `scope` claim are ignored.
+pub fn verify_jwt(token: &str, secret: &str) -> Option<ApiPrincipal> {
+    let claims: JwtClaims = decode_hs256(token, secret)?;
+    let now = Utc::now().timestamp();
+    if claims.exp <= now || claims.nbf.is_some_and(|nbf| nbf > now) {
+        return None;
+    }
+
+    Some(ApiPrincipal {
+        name: claims.sub,
+        scopes: claims.scope.split_whitespace().filter_map(|scope| scope.parse().ok()).collect(),
+    })
+}
+
+/// An HS256 JWT that `verify_jwt` accepts, for issuing tokens to internal callers.
+pub fn sign_jwt(secret: &str, subject: &str, scopes: &[ApiScope], expires_at: i64) -> String {
+    let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+    let claims = JwtClaims {
+        sub: subject.to_string(),
+        exp: expires_at,
+        nbf: None,
+        scope: scopes.iter().map(ApiScope::as_str).collect::<Vec<_>>().join(" "),
+    };
+    let claims = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).expect("claims serialize"));
+
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(format!("{}.{}", header, claims).as_bytes());
+    format!("{}.{}.{}", header, claims, URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+}
+
+// =============================================================================
+// Auth Middleware
+// =============================================================================
+
+/// The authenticated caller, added to request extensions for handlers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiPrincipal {
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+}
+
+/// Credentials a group of routes requires. Attach with `route_layer` on the
+/// nested router.
+#[derive(Clone)]
+pub struct ApiGuard {
+    config: Arc<ApiAuthConfig>,
+    area: ApiArea,
+}
+
+impl ApiGuard {
+    pub fn new(config: &ApiAuthConfig, area: ApiArea) -> Self {
+        Self { config: Arc::new(config.clone()), area }
+    }
+
+    /// The caller a request comes from, `None` when auth is disabled, or the
+    /// status and message to reject it with.
+    pub fn check(&self, method: &Method, headers: &HeaderMap) -> Result<Option<ApiPrincipal>, (StatusCode, String)> {
+        if self.config.disabled {
+            return Ok(None);
+        }
+        if !self.config.is_configured() {
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "API authentication is not configured; set API_KEYS or JWT_SECRET".to_string(),
+            ));
+        }
+
+        let principal = self.config.authenticate(headers)
+            .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Invalid or missing API credentials".to_string()))?;
+
+        let required = ApiScope::required_for(self.area, method);
+        if !principal.scopes.contains(&required) {
+            warn!("🔐 {} lacks the {} scope", principal.name, required.as_str());
+            return Err((StatusCode::FORBIDDEN, format!("Credentials lack the {} scope", required.as_str())));
+        }
+
+        Ok(Some(principal))
+    }
+}
+
+pub async fn api_auth_middleware(
+    State(guard): State<ApiGuard>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match guard.check(request.method(), request.headers()) {
+        Ok(principal) => {
+            if let Some(principal) = principal {
+                request.extensions_mut().insert(principal);
+            }
+            next.run(request).await
+        }
+        Err((status, message)) => {
+            warn!("🔐 Rejected {} {}: {}", request.method(), request.uri().path(), status);
+            let mut response = (status, Json(serde_json::json!({ "error": message }))).into_response();
+            if status == StatusCode::UNAUTHORIZED {
+                response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            }
+            response
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, DurationRound, Utc};
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::{info, error};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    csv_response::{csv_response, export_file_name, optional, wants_csv, CsvRecord},
+    database::{ApiUsageRow, ApiUsageStore},
+    error::{AppError, AppResult},
+    http_client::parse_call_limit,
+    scheduler::{Job, JobScope, Schedule},
+};
+
+// Hourly usage rows are kept this long
+pub const API_USAGE_RETENTION_DAYS: i64 = 90;
+
+// =============================================================================
+// Usage Recording
+// =============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct UsageKey {
+    shop_domain: String,
+    period_start: DateTime<Utc>,
+    feature: String,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct UsageCounters {
+    calls: i64,
+    throttled: i64,
+    errors: i64,
+    utilization_sum: f64,
+    utilization_samples: i64,
+}
+
+/// In-memory hourly counters for outbound Shopify calls, drained into
+/// `api_usage` periodically. Shared process-wide like the call-limit tracker.
+#[derive(Clone, Default)]
+pub struct ApiUsageRecorder {
+    counters: Arc<Mutex<HashMap<UsageKey, UsageCounters>>>,
+}
+
+/// Groups Admin API paths by resource, e.g.
+/// `/admin/api/2025-04/orders/123.json` becomes `orders`.
+pub fn feature_for_path(path: &str) -> String {
+    let resource = path
+        .trim_start_matches('/')
+        .strip_prefix("admin/")
+        .map(|rest| match rest.strip_prefix("api/") {
+            Some(versioned) => versioned.split_once('/').map(|(_, r)| r).unwrap_or(versioned),
+            None => rest,
+        })
+        .unwrap_or(path);
+
+    let segment = resource.split('/').next().unwrap_or_default();
+    let segment = segment.trim_end_matches(".json");
+    if segment.is_empty() {
+        "other".to_string()
+    } else {
+        segment.to_string()
+    }
+}
+
+impl ApiUsageRecorder {
+    pub fn shared() -> Self {
+        static SHARED: OnceLock<ApiUsageRecorder> = OnceLock::new();
+        SHARED.get_or_init(ApiUsageRecorder::default).clone()
+    }
+
+    /// Counts one call attempt. `status` is `None` when the request never got a response.
+    pub fn record(&self, shop_domain: &str, path: &str, status: Option<u16>, call_limit: Option<(u32, u32)>) {
+        self.record_at(Utc::now(), shop_domain, path, status, call_limit);
+    }
+
+    pub fn record_at(
+        &self,
+        at: DateTime<Utc>,
+        shop_domain: &str,
+        path: &str,
+        status: Option<u16>,
+        call_limit: Option<(u32, u32)>,
+    ) {
+        let key = UsageKey {
+            shop_domain: shop_domain.to_string(),
+            period_start: at.duration_trunc(chrono::Duration::hours(1)).unwrap_or(at),
+            feature: feature_for_path(path),
+        };
+
+        let mut counters = self.counters.lock().unwrap();
+        let entry = counters.entry(key).or_default();
+        entry.calls += 1;
+
+        match status {
+            Some(429) => entry.throttled += 1,
+            Some(status) if status >= 400 => entry.errors += 1,
+            None => entry.errors += 1,
+            Some(_) => {}
+        }
+
+        if let Some((used, max)) = call_limit.filter(|(_, max)| *max > 0) {
+            entry.utilization_sum += used as f64 / max as f64;
+            entry.utilization_samples += 1;
+        }
+    }
+
+    /// Takes every counter recorded since the last drain.
+    pub fn drain(&self) -> Vec<ApiUsageRow> {
+        let mut counters = self.counters.lock().unwrap();
+        counters
+            .drain()
+            .map(|(key, c)| ApiUsageRow {
+                shop_domain: key.shop_domain,
+                period_start: key.period_start,
+                feature: key.feature,
+                calls: c.calls,
+                throttled: c.throttled,
+                errors: c.errors,
+                utilization_sum: c.utilization_sum,
+                utilization_samples: c.utilization_samples,
+            })
+            .collect()
+    }
+
+    /// Persists drained counters, putting them back if the write fails.
+    pub async fn flush(&self, store: &ApiUsageStore) -> AppResult<()> {
+        let rows = self.drain();
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        if let Err(e) = store.add_usage(&rows).await {
+            let mut counters = self.counters.lock().unwrap();
+            for row in rows {
+                let entry = counters
+                    .entry(UsageKey {
+                        shop_domain: row.shop_domain,
+                        period_start: row.period_start,
+                        feature: row.feature,
+                    })
+                    .or_default();
+                entry.calls += row.calls;
+                entry.throttled += row.throttled;
+                entry.errors += row.errors;
+                entry.utilization_sum += row.utilization_sum;
+                entry.utilization_samples += row.utilization_samples;
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+}
+
+/// Records every attempt that reaches Shopify, including ones the retry
+/// middleware later retries, so throttled calls show up in the usage data.
+pub struct ApiUsageMiddleware {
+    pub recorder: ApiUsageRecorder,
+}
+
+#[async_trait::async_trait]
+impl Middleware for ApiUsageMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut task_local_extensions::Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let shop_domain = req.url().host_str().unwrap_or_default().to_string();
+        let path = req.url().path().to_string();
+
+        let result = next.run(req, extensions).await;
+
+        let (status, call_limit) = match &result {
+            Ok(response) => (
+                Some(response.status().as_u16()),
+                response.headers()
+                    .get("x-shopify-shop-api-call-limit")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_call_limit),
+            ),
+            Err(_) => (None, None),
+        };
+        self.recorder.record(&shop_domain, &path, status, call_limit);
+
+        result
+    }
+}
+
+// =============================================================================
+// Scheduled Jobs
+// =============================================================================
+
+/// Writes this instance's counters to `api_usage`. Every instance keeps its
+/// own counters, so every instance flushes.
+pub fn api_usage_flush_job() -> Job {
+    Job::new("api-usage-flush", Schedule::Every(std::time::Duration::from_secs(60)), JobScope::Instance, |state: AppState| async move {
+        ApiUsageRecorder::shared().flush(&state.api_usage).await.map_err(|e| e.to_string())?;
+        Ok("usage counters flushed".to_string())
+    })
+}
+
+/// Drops hourly usage rows older than `API_USAGE_RETENTION_DAYS`.
+pub fn api_usage_purge_job() -> Job {
+    Job::new("api-usage-purge", Schedule::Every(std::time::Duration::from_secs(3600)), JobScope::Cluster, |state: AppState| async move {
+        let purged = state.api_usage.purge_older_than(API_USAGE_RETENTION_DAYS).await.map_err(|e| e.to_string())?;
+        Ok(format!("{} usage rows purged", purged))
+    })
+}
+
+// =============================================================================
+// Usage Summaries
+// =============================================================================
+
+#[derive(Debug, Default, Serialize, PartialEq, ToSchema)]
+pub struct UsageSummary {
+    pub calls: i64,
+    pub throttled: i64,
+    pub errors: i64,
+    /// Mean of used/max across responses that carried a call-limit header
+    pub avg_utilization: Option<f64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeatureUsage {
+    pub feature: String,
+    #[serde(flatten)]
+    pub usage: UsageSummary,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HourlyUsage {
+    pub period_start: DateTime<Utc>,
+    #[serde(flatten)]
+    pub usage: UsageSummary,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    pub totals: UsageSummary,
+    /// Busiest feature first
+    pub by_feature: Vec<FeatureUsage>,
+    pub timeline: Vec<HourlyUsage>,
+}
+
+#[derive(Default)]
+struct UsageTotals {
+    calls: i64,
+    throttled: i64,
+    errors: i64,
+    utilization_sum: f64,
+    utilization_samples: i64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, row: &ApiUsageRow) {
+        self.calls += row.calls;
+        self.throttled += row.throttled;
+        self.errors += row.errors;
+        self.utilization_sum += row.utilization_sum;
+        self.utilization_samples += row.utilization_samples;
+    }
+
+    fn summary(&self) -> UsageSummary {
+        UsageSummary {
+            calls: self.calls,
+            throttled: self.throttled,
+            errors: self.errors,
+            avg_utilization: (self.utilization_samples > 0)
+                .then(|| self.utilization_sum / self.utilization_samples as f64),
+        }
+    }
+}
+
+/// The recorded rows, one per hour and feature, as the CSV usage report.
+impl CsvRecord for ApiUsageRow {
+    fn csv_headers() -> Vec<&'static str> {
+        vec!["period_start", "feature", "calls", "throttled", "errors", "avg_utilization"]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        let mut totals = UsageTotals::default();
+        totals.add(self);
+        let usage = totals.summary();
+        vec![
+            self.period_start.to_rfc3339(),
+            self.feature.clone(),
+            usage.calls.to_string(),
+            usage.throttled.to_string(),
+            usage.errors.to_string(),
+            optional(&usage.avg_utilization),
+        ]
+    }
+}
+
+pub fn summarize_usage(rows: &[ApiUsageRow]) -> UsageReport {
+    let mut total = UsageTotals::default();
+    let mut by_feature: BTreeMap<&str, UsageTotals> = BTreeMap::new();
+    let mut by_hour: BTreeMap<DateTime<Utc>, UsageTotals> = BTreeMap::new();
+
+    for row in rows {
+        total.add(row);
+        by_feature.entry(&row.feature).or_default().add(row);
+        by_hour.entry(row.period_start).or_default().add(row);
+    }
+
+    let mut features: Vec<FeatureUsage> = by_feature
+        .into_iter()
+        .map(|(feature, totals)| FeatureUsage { feature: feature.to_string(), usage: totals.summary() })
+        .collect();
+    features.sort_by_key(|f| std::cmp::Reverse(f.usage.calls));
+
+    UsageReport {
+        totals: total.summary(),
+        by_feature: features,
+        timeline: by_hour
+            .into_iter()
+            .map(|(period_start, totals)| HourlyUsage { period_start, usage: totals.summary() })
+            .collect(),
+    }
+}
+
+// =============================================================================
+// Usage Handler
+// =============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ApiUsageParams {
+    /// How far back to report, in hours (default 24, max 30 days)
+    pub hours: Option<i64>,
+}
+
+/// Reports the Shopify API calls made for a shop. `Accept: text/csv` streams
+/// one row per hour and feature instead of the summaries.
+#[utoipa::path(
+    get,
+    path = "/api/shops/{shop}/api-usage",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com"), ApiUsageParams),
+    responses(
+        (status = 200, description = "Shopify API calls made for the shop, by feature and hour", content(
+            (crate::openapi::ApiUsage = "application/json"),
+            (String = "text/csv"),
+        )),
+        (status = 400, description = "`hours` out of range", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn api_usage_handler(
+    Path(shop): Path<String>,
+    Query(params): Query<ApiUsageParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<axum::response::Response> {
+    let hours = params.hours.unwrap_or(24);
+    if !(1..=24 * 30).contains(&hours) {
+        return Err(AppError::BadRequest("hours must be between 1 and 720".to_string()));
+    }
+
+    // Include calls made since the last background flush
+    if let Err(e) = ApiUsageRecorder::shared().flush(&state.api_usage).await {
+        error!("Failed to flush API usage before reporting: {}", e);
+    }
+
+    let since = Utc::now() - chrono::Duration::hours(hours);
+    let rows = state.api_usage.usage_since(&shop, since).await?;
+    info!("📊 Reporting API usage for {} over {} hours", shop, hours);
+
+    if wants_csv(&headers) {
+        return Ok(csv_response(&export_file_name("api-usage"), rows));
+    }
+    let report = summarize_usage(&rows);
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "hours": hours,
+        "totals": report.totals,
+        "by_feature": report.by_feature,
+        "timeline": report.timeline
+    }))).into_response())
+}
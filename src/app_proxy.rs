@@ -0,0 +1,186 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use tracing::warn;
+
+use crate::{AppState, embedded::is_shop_domain, error::AppResult, shop_secrets::WebhookSecret};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signed proxy requests older (or further in the future) than this are
+/// refused, so a captured URL can't be replayed for long.
+const PROXY_SIGNATURE_MAX_AGE_SECS: i64 = 300;
+
+// =============================================================================
+// Signatures
+// =============================================================================
+
+/// Signature Shopify puts in an app proxy request's `signature` parameter:
+/// every other parameter as `key=value`, values of a repeated key joined
+/// with commas, sorted and concatenated without separators, then
+/// HMAC-SHA256'd with the app secret.
+pub fn app_proxy_signature<'a>(params: impl IntoIterator<Item = (&'a str, &'a str)>, secret: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(canonical_params(params).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn canonical_params<'a>(params: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let mut grouped: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (key, value) in params.into_iter().filter(|(key, _)| *key != "signature") {
+        grouped.entry(key).or_default().push(value);
+    }
+    grouped.iter().map(|(key, values)| format!("{}={}", key, values.join(","))).collect()
+}
+
+/// A verified app proxy request: the shop whose storefront it came through
+/// and the customer logged in there, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppProxyRequest {
+    pub shop: String,
+    /// Storefront path the proxy is mounted at, e.g. `/apps/rewards`
+    pub path_prefix: Option<String>,
+    /// Empty when nobody is logged in
+    pub logged_in_customer_id: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl AppProxyRequest {
+    /// Checks the `signature` on a proxied request's query string against
+    /// `secret`, and that its `timestamp` is recent.
+    pub fn verify(query: &str, secret: &str, now: DateTime<Utc>) -> Result<Self, String> {
+        let params: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+        let param = |name: &str| params.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+
+        let signature = param("signature").ok_or_else(|| "Missing app proxy signature".to_string())?;
+        let signature = hex::decode(signature).map_err(|_| "App proxy signature is not hex".to_string())?;
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+        mac.update(canonical_params(params.iter().map(|(key, value)| (key.as_str(), value.as_str()))).as_bytes());
+        // Constant-time comparison
+        mac.verify_slice(&signature).map_err(|_| "App proxy signature is invalid".to_string())?;
+
+        let timestamp = param("timestamp")
+            .and_then(|t| t.parse::<i64>().ok())
+            .and_then(|t| DateTime::from_timestamp(t, 0))
+            .ok_or_else(|| "Missing app proxy timestamp".to_string())?;
+        if (now - timestamp).num_seconds().abs() > PROXY_SIGNATURE_MAX_AGE_SECS {
+            return Err("App proxy request has expired".to_string());
+        }
+        let shop = param("shop")
+            .filter(|shop| is_shop_domain(shop))
+            .ok_or_else(|| "App proxy request does not name a shop".to_string())?;
+
+        Ok(Self {
+            shop: shop.to_string(),
+            path_prefix: param("path_prefix").map(str::to_string),
+            logged_in_customer_id: param("logged_in_customer_id").filter(|id| !id.is_empty()).map(str::to_string),
+            timestamp,
+        })
+    }
+}
+
+// =============================================================================
+// Middleware
+// =============================================================================
+
+/// Rejection for an unsigned or badly signed app proxy request.
+#[derive(Debug)]
+pub struct AppProxyRejection(pub String);
+
+impl IntoResponse for AppProxyRejection {
+    fn into_response(self) -> Response {
+        (StatusCode::UNAUTHORIZED, Json(serde_json::json!({ "error": self.0 }))).into_response()
+    }
+}
+
+/// Lets through only requests Shopify signed on its way from a storefront,
+/// verified with the shop's own secret when it has one (custom apps) and
+/// `API_SECRET` otherwise. Handlers behind it take an `AppProxyRequest`.
+pub async fn app_proxy_middleware(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let query = request.uri().query().unwrap_or_default().to_string();
+    let secret = proxy_secret(&state, &query).await;
+
+    match AppProxyRequest::verify(&query, &secret, Utc::now()) {
+        Ok(proxy) => {
+            request.extensions_mut().insert(proxy);
+            next.run(request).await
+        }
+        Err(reason) => {
+            warn!("🔐 Rejected app proxy request to {}: {}", request.uri().path(), reason);
+            AppProxyRejection(reason).into_response()
+        }
+    }
+}
+
+// The named shop's own secret, if it has one stored, else the app's. Failed
+// lookups fall back to the app secret, as for webhooks.
+async fn proxy_secret(state: &AppState, query: &str) -> String {
+    let shop = url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == "shop")
+        .map(|(_, shop)| shop.into_owned())
+        .filter(|shop| is_shop_domain(shop));
+    if let (Some(shop), Some(_)) = (shop, state.config.database.database_url.as_ref()) {
+        match state.shop_secrets.get::<WebhookSecret>(&shop).await {
+            Ok(Some(secret)) => return secret.api_secret,
+            Ok(None) => {}
+            Err(e) => warn!("Failed to look up the secret for {}, using the app secret: {}", shop, e),
+        }
+    }
+    state.config.api_secret.clone()
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AppProxyRequest {
+    type Rejection = AppProxyRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AppProxyRequest>()
+            .cloned()
+            .ok_or_else(|| AppProxyRejection("Not an app proxy request".to_string()))
+    }
+}
+
+// =============================================================================
+// Proxy Handlers
+// =============================================================================
+
+/// `GET /proxy/session` — the shop and customer a storefront request came
+/// through the app proxy for, and whether the shop has installed the app.
+#[utoipa::path(
+    get,
+    path = "/proxy/session",
+    tag = "app proxy",
+    params(
+        ("shop" = String, Query, description = "Set by Shopify"),
+        ("timestamp" = i64, Query, description = "Set by Shopify"),
+        ("signature" = String, Query, description = "HMAC-SHA256 of the other parameters, set by Shopify"),
+    ),
+    responses(
+        (status = 200, description = "The proxied request's shop and customer", body = crate::openapi::AppProxySession),
+        (status = 401, description = "Missing, invalid or expired signature", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn app_proxy_session_handler(
+    proxy: AppProxyRequest,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let installed = state.token_store.get_scope(&proxy.shop).await?.is_some();
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": proxy.shop,
+        "path_prefix": proxy.path_prefix,
+        "logged_in_customer_id": proxy.logged_in_customer_id,
+        "installed": installed
+    }))))
+}
@@ -0,0 +1,449 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::{pin_mut, TryStreamExt};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::{error, info, warn};
+use utoipa::IntoParams;
+
+use crate::{
+    AppState,
+    database::{CatalogProduct, CatalogQuery, CatalogStore, CatalogVariant, QueuedJob},
+    error::{AppError, AppResult},
+    http_client::{PageInfo, ShopifyClient},
+    job_queue::{JobHandlerResult, JobKind, ShopOrdering},
+    scheduler::{Job, JobResult, JobScope, Schedule},
+    shopify_api::{Product, ProductsResponse},
+};
+
+/// Prefix of `page_info` cursors for catalog pages.
+const CATALOG_CURSOR_PREFIX: &str = "catalog_";
+
+const CATALOG_DEFAULT_LIMIT: i64 = 50;
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct CatalogConfig {
+    /// Mirror products locally and serve `/api/catalog`
+    pub enabled: bool,
+    /// Time between reconciliation passes over every installed shop
+    pub reconcile_interval: Duration,
+}
+
+impl Default for CatalogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            reconcile_interval: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl CatalogConfig {
+    /// `CATALOG_RECONCILE_INTERVAL_SECS`; 0 turns the mirror off.
+    pub fn from_env() -> Self {
+        match std::env::var("CATALOG_RECONCILE_INTERVAL_SECS").ok().and_then(|v| v.parse::<u64>().ok()) {
+            Some(0) => Self { enabled: false, ..Self::default() },
+            Some(secs) => Self { enabled: true, reconcile_interval: Duration::from_secs(secs) },
+            None => Self::default(),
+        }
+    }
+}
+
+// =============================================================================
+// Conversion
+// =============================================================================
+
+/// The rows kept for `product`, or `None` if it lacks an ID or a readable
+/// `updated_at`.
+pub fn catalog_product(product: &Product) -> Option<CatalogProduct> {
+    let updated_at = DateTime::parse_from_rfc3339(&product.updated_at).ok()?.with_timezone(&Utc);
+
+    Some(CatalogProduct {
+        product_id: i64::try_from(product.id).ok().filter(|id| *id > 0)?,
+        title: product.title.clone(),
+        handle: product.handle.clone(),
+        vendor: product.vendor.clone(),
+        product_type: product.product_type.clone(),
+        status: product.status.clone(),
+        tags: product.tags.clone(),
+        updated_at,
+        variants: product
+            .variants
+            .iter()
+            .filter_map(|variant| {
+                Some(CatalogVariant {
+                    variant_id: i64::try_from(variant.id).ok()?,
+                    title: variant.title.clone(),
+                    sku: variant.sku.clone().filter(|sku| !sku.trim().is_empty()),
+                    barcode: variant.barcode.clone().filter(|barcode| !barcode.trim().is_empty()),
+                    price: variant.price.parse().unwrap_or(Decimal::ZERO),
+                    inventory_quantity: variant.inventory_quantity,
+                })
+            })
+            .collect(),
+        data: serde_json::to_value(product).ok()?,
+    })
+}
+
+/// A search box query as a prefix-matching `to_tsquery` expression: every
+/// word must match the start of a title, SKU, vendor or tag word. Splitting
+/// on punctuation keeps user input out of the tsquery syntax, and matches
+/// how Postgres splits SKUs like `TEE-BLK-M`.
+pub fn search_tsquery(q: &str) -> Option<String> {
+    let terms: Vec<String> = q
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("{}:*", term.to_lowercase()))
+        .collect();
+
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
+// =============================================================================
+// Sync
+// =============================================================================
+
+/// What one reconciliation pass did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CatalogSyncReport {
+    pub fetched: usize,
+    /// Products that were new or had changed
+    pub written: u64,
+    /// Products gone from Shopify whose delete webhook was missed
+    pub removed: u64,
+}
+
+// Shops with a reconciliation in progress in this process
+fn reconciling() -> &'static Mutex<HashSet<String>> {
+    static RECONCILING: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    RECONCILING.get_or_init(Default::default)
+}
+
+struct ReconcileGuard(String);
+
+impl ReconcileGuard {
+    fn acquire(shop: &str) -> Option<Self> {
+        reconciling().lock().unwrap().insert(shop.to_string()).then(|| Self(shop.to_string()))
+    }
+}
+
+impl Drop for ReconcileGuard {
+    fn drop(&mut self) {
+        reconciling().lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Compares every product in Shopify with the mirror: writes only the ones
+/// that changed and drops the ones Shopify no longer has. Webhooks keep the
+/// mirror fresh in between; this catches whatever they missed. One pass per
+/// shop at a time; a second caller gets `Conflict`.
+pub async fn reconcile_catalog(
+    shopify: &ShopifyClient,
+    store: &CatalogStore,
+    shop: &str,
+    token: &str,
+) -> AppResult<CatalogSyncReport> {
+    let _guard = ReconcileGuard::acquire(shop)
+        .ok_or_else(|| AppError::Conflict(format!("A catalog sync is already running for {}", shop)))?;
+
+    let started_at = Utc::now();
+    let mut report = CatalogSyncReport::default();
+    let mut seen = Vec::new();
+    let client = shopify.for_shop(shop);
+    let result: AppResult<()> = async {
+        let pages = client.get_all_pages::<ProductsResponse>(
+            "products.json",
+            token,
+            vec![("limit".to_string(), "250".to_string())],
+        );
+        pin_mut!(pages);
+
+        while let Some(page) = pages.try_next().await? {
+            let products: Vec<CatalogProduct> = page.products.iter().filter_map(catalog_product).collect();
+            report.fetched += page.products.len();
+            seen.extend(products.iter().map(|product| product.product_id));
+            report.written += store.upsert_products(shop, &products).await?;
+        }
+        report.removed = store.delete_products_except(shop, &seen, started_at).await?;
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            store.record_sync(shop, None).await?;
+            info!(
+                "🛍️ Reconciled catalog for {}: {} fetched, {} written, {} removed",
+                shop, report.fetched, report.written, report.removed
+            );
+            Ok(report)
+        }
+        Err(e) => {
+            if let Err(record_error) = store.record_sync(shop, Some(&e.to_string())).await {
+                error!("Failed to record catalog sync failure for {}: {}", shop, record_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Queue job kind reconciling one shop's catalog, retried with backoff.
+pub const SHOP_CATALOG_SYNC_JOB: &str = "shop-catalog-sync";
+
+pub fn shop_catalog_sync_job_kind() -> JobKind {
+    JobKind::new(SHOP_CATALOG_SYNC_JOB, ShopOrdering::Single, |state: AppState, job: QueuedJob| async move {
+        run_shop_catalog_sync(&state, job.shop_domain.as_deref()).await
+    })
+}
+
+async fn run_shop_catalog_sync(state: &AppState, shop: Option<&str>) -> JobHandlerResult {
+    let shop = shop.ok_or("Catalog sync job has no shop")?;
+    let token = state.token_store.get_token(shop).await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Shop {} is not installed", shop))?;
+
+    reconcile_catalog(&state.shopify, &state.catalog, shop, &token)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Queues a reconciliation of every installed shop's catalog each
+/// `CATALOG_RECONCILE_INTERVAL_SECS`, starting at startup so new shops are
+/// mirrored right away.
+pub fn catalog_reconcile_job(config: &CatalogConfig) -> Job {
+    Job::new("catalog-reconcile", Schedule::Every(config.reconcile_interval), JobScope::Cluster, |state: AppState| async move {
+        queue_installed_shops(&state).await
+    })
+    .run_on_start()
+}
+
+async fn queue_installed_shops(state: &AppState) -> JobResult {
+    let shops = state.token_store.list_shops().await.map_err(|e| e.to_string())?;
+
+    let (mut queued, mut pending, mut failed) = (0, 0, Vec::new());
+    for shop in shops {
+        match state.job_queue.enqueue(SHOP_CATALOG_SYNC_JOB, Some(&shop.shop_domain), serde_json::json!({})).await {
+            Ok(Some(_)) => queued += 1,
+            Ok(None) => pending += 1,
+            Err(e) => {
+                error!("Failed to queue catalog sync for {}: {}", shop.shop_domain, e);
+                failed.push(shop.shop_domain);
+            }
+        }
+    }
+
+    let summary = format!("{} shops queued, {} already pending", queued, pending);
+    if failed.is_empty() {
+        Ok(summary)
+    } else {
+        Err(format!("{}, failed for {}", summary, failed.join(", ")))
+    }
+}
+
+/// Applies a `products/*` webhook to the mirror.
+pub async fn apply_product_webhook(
+    store: &CatalogStore,
+    shop: &str,
+    topic: &str,
+    payload: &serde_json::Value,
+) -> AppResult<()> {
+    if topic == "products/delete" {
+        if let Some(product_id) = payload["id"].as_i64() {
+            store.delete_product(shop, product_id).await?;
+        }
+        return Ok(());
+    }
+
+    let product: Product = serde_json::from_value(payload.clone())
+        .map_err(|e| AppError::BadRequest(format!("Unreadable {} payload: {}", topic, e)))?;
+    match catalog_product(&product) {
+        Some(product) => store.upsert_products(shop, &[product]).await.map(|_| ()),
+        None => {
+            warn!("Skipping {} webhook for {} without a product ID or updated_at", topic, shop);
+            Ok(())
+        }
+    }
+}
+
+// =============================================================================
+// Catalog Search
+// =============================================================================
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CatalogParams {
+    /// Words to find in the title, SKUs, vendor or tags
+    pub q: Option<String>,
+    pub vendor: Option<String>,
+    pub product_type: Option<String>,
+    /// active, archived or draft
+    pub status: Option<String>,
+    pub tag: Option<String>,
+    pub limit: Option<u32>,
+    /// Cursor from the previous page; repeat the same filters with it
+    pub page_info: Option<String>,
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+/// The `/api/catalog` query as a catalog search.
+pub fn catalog_query(params: &CatalogParams) -> Result<CatalogQuery, String> {
+    let status = match non_empty(&params.status) {
+        None => None,
+        Some(status) if ["active", "archived", "draft"].contains(&status.as_str()) => Some(status),
+        Some(other) => return Err(format!("Unknown status: {}", other)),
+    };
+    let offset = match params.page_info.as_deref() {
+        None => 0,
+        Some(page_info) => page_info
+            .strip_prefix(CATALOG_CURSOR_PREFIX)
+            .and_then(|offset| offset.parse::<i64>().ok())
+            .filter(|offset| *offset >= 0)
+            .ok_or_else(|| "page_info is not a catalog cursor".to_string())?,
+    };
+
+    Ok(CatalogQuery {
+        text: params.q.as_deref().and_then(search_tsquery),
+        vendor: non_empty(&params.vendor),
+        product_type: non_empty(&params.product_type),
+        status,
+        tag: non_empty(&params.tag),
+        limit: params.limit.map(i64::from).unwrap_or(CATALOG_DEFAULT_LIMIT).clamp(1, 250),
+        offset,
+    })
+}
+
+/// `GET /api/catalog` — searches the local product mirror.
+#[utoipa::path(
+    get,
+    path = "/api/catalog",
+    tag = "products",
+    params(CatalogParams),
+    responses(
+        (status = 200, description = "Matching products from the local catalog", body = crate::openapi::CatalogSearch),
+        (status = 400, description = "Catalog disabled or bad filters", body = crate::openapi::ErrorResponse),
+        (status = 409, description = "The catalog is still being mirrored", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn catalog_handler(
+    Query(params): Query<CatalogParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    if !state.config.catalog.enabled || state.config.database.database_url.is_none() {
+        return Err(AppError::BadRequest(
+            "The catalog needs a Postgres DATABASE_URL and CATALOG_RECONCILE_INTERVAL_SECS above 0".to_string(),
+        ));
+    }
+    let sync = state.catalog.sync_state(shop).await?;
+    if sync.as_ref().is_none_or(|s| s.reconciled_at.is_none()) {
+        return Err(AppError::Conflict(format!(
+            "The catalog for {} is still being mirrored; use /api/products until it is",
+            shop
+        )));
+    }
+
+    let query = catalog_query(&params).map_err(AppError::BadRequest)?;
+    let (products, total) = state.catalog.search_products(shop, &query).await?;
+    let products: Vec<Product> = products
+        .into_iter()
+        .filter_map(|data| serde_json::from_value(data).ok())
+        .collect();
+
+    let next_offset = query.offset + query.limit;
+    let page_info = PageInfo {
+        has_next_page: next_offset < total,
+        has_previous_page: query.offset > 0,
+        next_page_info: (next_offset < total).then(|| format!("{}{}", CATALOG_CURSOR_PREFIX, next_offset)),
+        previous_page_info: (query.offset > 0)
+            .then(|| format!("{}{}", CATALOG_CURSOR_PREFIX, (query.offset - query.limit).max(0))),
+    };
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "total": total,
+        "products_count": products.len(),
+        "products": products,
+        "page_info": page_info,
+        "synced_at": sync.and_then(|s| s.last_synced_at)
+    }))))
+}
+
+// =============================================================================
+// Admin Handlers
+// =============================================================================
+
+/// `GET /admin/shops/{shop}/catalog/sync` — when the shop's catalog was last
+/// reconciled.
+#[utoipa::path(
+    get,
+    path = "/admin/shops/{shop}/catalog/sync",
+    tag = "sync",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 200, description = "Catalog sync progress", body = crate::openapi::CatalogSyncStatus),
+    ),
+)]
+pub async fn catalog_sync_status_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let sync = state.catalog.sync_state(&shop).await?;
+    let running = reconciling().lock().unwrap().contains(&shop);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "enabled": state.config.catalog.enabled,
+        "running": running,
+        "state": sync
+    }))))
+}
+
+/// `POST /admin/shops/{shop}/catalog/sync` — queues a reconciliation pass
+/// instead of waiting for the next one.
+#[utoipa::path(
+    post,
+    path = "/admin/shops/{shop}/catalog/sync",
+    tag = "sync",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 202, description = "Sync queued", body = crate::openapi::SyncQueued),
+        (status = 404, description = "Shop not installed", body = crate::openapi::ErrorResponse),
+        (status = 409, description = "A sync is already queued or running", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn start_catalog_sync_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    if !state.config.catalog.enabled || state.config.database.database_url.is_none() {
+        return Err(AppError::BadRequest(
+            "The catalog needs a Postgres DATABASE_URL and CATALOG_RECONCILE_INTERVAL_SECS above 0".to_string(),
+        ));
+    }
+    state.token_store.get_token(&shop).await?
+        .ok_or_else(|| AppError::NotFound(format!("Shop {} is not installed", shop)))?;
+    let job = state.job_queue.enqueue(SHOP_CATALOG_SYNC_JOB, Some(&shop), serde_json::json!({})).await?
+        .ok_or_else(|| AppError::Conflict(format!("A catalog sync is already queued or running for {}", shop)))?;
+
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({
+        "shop": shop,
+        "queued": true,
+        "job_id": job.id
+    }))))
+}
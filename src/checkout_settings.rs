@@ -0,0 +1,128 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tracing::info;
+
+use crate::{
+    AppState,
+    require_token,
+    error::{AppError, AppResult, ShopifyError},
+    http_client::ShopifyClient,
+    metafields::{Metafield, MetafieldResponse, MetafieldsResponse},
+};
+
+// Shop metafield the checkout UI extension reads its settings from
+pub const CHECKOUT_SETTINGS_NAMESPACE: &str = "checkout_extension";
+pub const CHECKOUT_SETTINGS_KEY: &str = "settings";
+
+// Keeps the payload well under Shopify's metafield value limit
+pub const MAX_CHECKOUT_SETTINGS_BYTES: usize = 64 * 1024;
+
+// =============================================================================
+// Settings Validation
+// =============================================================================
+
+/// Checks settings before they are written to the shop: they must be a JSON
+/// object and fit in a single metafield.
+pub fn validate_checkout_settings(settings: &serde_json::Value) -> AppResult<String> {
+    if !settings.is_object() {
+        return Err(AppError::BadRequest("Checkout settings must be a JSON object".to_string()));
+    }
+
+    let value = settings.to_string();
+    if value.len() > MAX_CHECKOUT_SETTINGS_BYTES {
+        return Err(AppError::BadRequest(format!(
+            "Checkout settings are {} bytes; the limit is {} bytes",
+            value.len(),
+            MAX_CHECKOUT_SETTINGS_BYTES
+        )));
+    }
+
+    Ok(value)
+}
+
+// =============================================================================
+// Checkout Settings Handlers
+// =============================================================================
+
+/// Reads the checkout UI extension's settings from the shop metafield.
+#[utoipa::path(
+    get,
+    path = "/api/checkout-settings",
+    tag = "checkout settings",
+    responses(
+        (status = 200, description = "Settings the checkout UI extension reads", body = crate::openapi::CheckoutSettings),
+    ),
+)]
+pub async fn get_checkout_settings_handler(
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let metafield = fetch_settings_metafield(&state.shopify, &token).await?;
+    let settings = match metafield {
+        Some(ref metafield) => serde_json::from_str(&metafield.value).map_err(ShopifyError::from)?,
+        None => serde_json::json!({}),
+    };
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "configured": metafield.is_some(),
+        "settings": settings,
+        "updated_at": metafield.and_then(|m| m.updated_at)
+    }))))
+}
+
+/// Replaces the checkout UI extension's settings.
+#[utoipa::path(
+    put,
+    path = "/api/checkout-settings",
+    tag = "checkout settings",
+    request_body(content = Object, description = "Any JSON object up to 64 KiB"),
+    responses(
+        (status = 200, description = "The stored settings", body = crate::openapi::CheckoutSettingsUpdated),
+        (status = 400, description = "Not an object, or too large", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn put_checkout_settings_handler(
+    State(state): State<AppState>,
+    Json(settings): Json<serde_json::Value>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let value = validate_checkout_settings(&settings)?;
+    let token = require_token(&state.token_store, shop).await?;
+
+    // Shopify updates the existing shop metafield for the same namespace and key
+    let body = serde_json::json!({
+        "metafield": {
+            "namespace": CHECKOUT_SETTINGS_NAMESPACE,
+            "key": CHECKOUT_SETTINGS_KEY,
+            "type": "json",
+            "value": value
+        }
+    });
+    let response: MetafieldResponse = state.shopify.post_with_auth("metafields.json", &token, &body).await?;
+    info!("🛒 Updated checkout extension settings for {}", shop);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "shop": shop,
+        "settings": settings,
+        "updated_at": response.metafield.updated_at
+    }))))
+}
+
+async fn fetch_settings_metafield(client: &ShopifyClient, token: &str) -> Result<Option<Metafield>, ShopifyError> {
+    let params = [("namespace", CHECKOUT_SETTINGS_NAMESPACE), ("key", CHECKOUT_SETTINGS_KEY)];
+    let response = client
+        .get_with_auth::<MetafieldsResponse>("metafields.json", token, Some(&params))
+        .await?;
+
+    Ok(response.data.metafields.into_iter().find(|m| {
+        m.namespace == CHECKOUT_SETTINGS_NAMESPACE && m.key == CHECKOUT_SETTINGS_KEY
+    }))
+}
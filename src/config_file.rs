@@ -0,0 +1,116 @@
+use config::{Config, File, Map, Value, ValueKind};
+use std::path::{Path, PathBuf};
+
+use crate::error::{AppError, AppResult};
+
+// =============================================================================
+// Config File
+// =============================================================================
+
+/// Looked for in the working directory, in order, when `CONFIG_FILE` is unset.
+const DEFAULT_CONFIG_FILES: [&str; 3] = ["config.toml", "config.yaml", "config.yml"];
+
+/// Loads `CONFIG_FILE`, or the first default config file present, underneath
+/// the environment: a setting from the file only applies when neither the
+/// environment nor `.env` sets the same variable. Returns the file used.
+///
+/// Call before anything reads the environment, including logging setup.
+pub fn load_config_file() -> AppResult<Option<PathBuf>> {
+    dotenv::dotenv().ok();
+
+    let path = match std::env::var("CONFIG_FILE").ok().filter(|v| !v.trim().is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => match DEFAULT_CONFIG_FILES.iter().map(PathBuf::from).find(|p| p.is_file()) {
+            Some(path) => path,
+            None => return Ok(None),
+        },
+    };
+
+    for (name, value) in read_config_file(&path)? {
+        if std::env::var_os(&name).is_none() {
+            std::env::set_var(name, value);
+        }
+    }
+    Ok(Some(path))
+}
+
+/// Reads a TOML or YAML config file (by extension) as environment variables.
+/// Keys are the variable names in any case; nested tables join with `_`, so
+/// `[rate_limit] oauth_per_minute = 10` sets `RATE_LIMIT_OAUTH_PER_MINUTE`,
+/// and arrays join with `,`.
+pub fn read_config_file(path: &Path) -> AppResult<Vec<(String, String)>> {
+    let values = Config::builder()
+        .add_source(File::from(path))
+        .build()
+        .and_then(|settings| settings.try_deserialize::<Map<String, Value>>())
+        .map_err(|e| AppError::Config(format!("{}: {}", path.display(), e)))?;
+
+    let mut variables = Vec::new();
+    flatten("", values, &mut variables);
+    variables.sort();
+    Ok(variables)
+}
+
+fn flatten(prefix: &str, table: Map<String, Value>, variables: &mut Vec<(String, String)>) {
+    for (key, value) in table {
+        let name = if prefix.is_empty() {
+            key.to_ascii_uppercase()
+        } else {
+            format!("{}_{}", prefix, key.to_ascii_uppercase())
+        };
+
+        match value.kind {
+            ValueKind::Nil => {}
+            ValueKind::Table(table) => flatten(&name, table, variables),
+            ValueKind::Array(items) => {
+                let joined = items.iter().map(ToString::to_string).collect::<Vec<_>>().join(",");
+                variables.push((name, joined));
+            }
+            kind => variables.push((name, kind.to_string())),
+        }
+    }
+}
+
+// =============================================================================
+// Validation
+// =============================================================================
+
+/// A variable that must be set to a non-empty value.
+pub fn required_var(name: &str) -> AppResult<String> {
+    std::env::var(name)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .ok_or_else(|| AppError::Config(format!("{} is not set", name)))
+}
+
+/// Collects every configuration problem so startup reports them together
+/// instead of one per restart.
+#[derive(Debug, Default)]
+pub struct ConfigErrors(Vec<String>);
+
+impl ConfigErrors {
+    /// Records `result`'s error, if any, and hands the result back.
+    pub fn check<T>(&mut self, result: AppResult<T>) -> AppResult<T> {
+        if let Err(ref e) = result {
+            self.0.push(match e {
+                AppError::Config(message) => message.clone(),
+                other => other.to_string(),
+            });
+        }
+        result
+    }
+
+    /// Records a problem found by comparing settings.
+    pub fn add(&mut self, message: impl Into<String>) {
+        self.0.push(message.into());
+    }
+
+    /// `Err` listing every recorded problem, or `Ok` if there were none.
+    pub fn finish(self) -> AppResult<()> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Config(self.0.join("; ")))
+        }
+    }
+}
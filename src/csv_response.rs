@@ -0,0 +1,272 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use futures::{Stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::fmt::Display;
+use tracing::{error, info};
+
+use crate::error::{AppError, AppResult};
+
+// =============================================================================
+// Content Negotiation
+// =============================================================================
+
+// Quality value the Accept header assigns to a media type, honoring wildcards
+fn quality_for(accept: &str, media_type: &str) -> f32 {
+    let (kind, _) = media_type.split_once('/').unwrap_or((media_type, ""));
+
+    accept
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let range_type = parts.next()?.trim().to_ascii_lowercase();
+            let q = parts
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let matches = range_type == media_type
+                || range_type == format!("{}/*", kind)
+                || range_type == "*/*";
+            matches.then_some(q)
+        })
+        .fold(0.0, f32::max)
+}
+
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// How a list endpoint answers: one JSON page, or every matching record
+/// streamed as CSV or newline-delimited JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    Json,
+    Csv,
+    Ndjson,
+}
+
+impl ListFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+        }
+    }
+}
+
+/// The format the `Accept` header prefers. JSON stays the default for ties,
+/// so `Accept: */*` and missing headers keep today's behaviour.
+pub fn negotiated_format(headers: &HeaderMap) -> ListFormat {
+    let accept = match headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) {
+        Some(accept) => accept,
+        None => return ListFormat::Json,
+    };
+
+    [
+        (ListFormat::Json, "application/json"),
+        (ListFormat::Csv, "text/csv"),
+        (ListFormat::Ndjson, NDJSON_CONTENT_TYPE),
+    ]
+    .into_iter()
+    .map(|(format, media_type)| (format, quality_for(accept, media_type)))
+    .fold((ListFormat::Json, 0.0), |best, candidate| if candidate.1 > best.1 { candidate } else { best })
+    .0
+}
+
+/// True when `Accept` ranks JSON above HTML, for endpoints that answer
+/// browsers with pages. Ties, `*/*` and a missing header go to HTML.
+pub fn prefers_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| quality_for(accept, "application/json") > quality_for(accept, "text/html"))
+}
+
+/// True when the client prefers `text/csv` over JSON.
+pub fn wants_csv(headers: &HeaderMap) -> bool {
+    negotiated_format(headers) == ListFormat::Csv
+}
+
+/// The format a list endpoint should answer in: `?format=json|csv|ndjson`
+/// when given, otherwise the `Accept` header.
+pub fn list_format(format: Option<&str>, headers: &HeaderMap) -> AppResult<ListFormat> {
+    match format.map(|f| f.trim().to_ascii_lowercase()).as_deref() {
+        Some("json") => Ok(ListFormat::Json),
+        Some("csv") => Ok(ListFormat::Csv),
+        Some("ndjson") => Ok(ListFormat::Ndjson),
+        Some(other) => Err(AppError::BadRequest(format!("format must be json, csv or ndjson, got {}", other))),
+        None => Ok(negotiated_format(headers)),
+    }
+}
+
+// =============================================================================
+// CSV Encoding
+// =============================================================================
+
+/// A row type that report endpoints can render as CSV.
+pub trait CsvRecord {
+    fn csv_headers() -> Vec<&'static str>;
+    fn csv_row(&self) -> Vec<String>;
+
+    /// Rows for one record. Records with nested lists (an order's line items,
+    /// a product's variants) repeat their own columns on one row per entry.
+    fn csv_rows(&self) -> Vec<Vec<String>> {
+        vec![self.csv_row()]
+    }
+}
+
+/// An optional value as a CSV field, empty when missing.
+pub fn optional<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(ToString::to_string).unwrap_or_default()
+}
+
+/// First and last name joined, skipping whichever is missing.
+pub fn full_name(first_name: &Option<String>, last_name: &Option<String>) -> String {
+    [first_name, last_name].into_iter().flatten().map(String::as_str).collect::<Vec<_>>().join(" ")
+}
+
+/// `orders-20250307-101500.csv`
+pub fn export_file_name(resource: &str) -> String {
+    dated_file_name(resource, "csv")
+}
+
+/// `orders-20250307-101500.ndjson`
+pub fn dated_file_name(resource: &str, extension: &str) -> String {
+    format!("{}-{}.{}", resource, chrono::Utc::now().format("%Y%m%d-%H%M%S"), extension)
+}
+
+/// Quotes a field when needed and neutralizes leading formula characters so
+/// spreadsheets don't evaluate values like `=HYPERLINK(...)`.
+pub fn escape_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) && value.parse::<f64>().is_err() {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+pub fn encode_row<S: AsRef<str>>(fields: &[S]) -> String {
+    let mut line = fields
+        .iter()
+        .map(|f| escape_field(f.as_ref()))
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+    line
+}
+
+/// Streams records as a CSV download, one chunk per row.
+pub fn csv_response<T>(file_name: &str, records: Vec<T>) -> Response
+where
+    T: CsvRecord + Send + 'static,
+{
+    let header_row = encode_row(&T::csv_headers());
+    let rows = std::iter::once(header_row)
+        .chain(records.into_iter().flat_map(|record| record.csv_rows()).map(|row| encode_row(&row)))
+        .map(|line| Ok::<_, Infallible>(Bytes::from(line)));
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        Body::from_stream(futures::stream::iter(rows)),
+    ).into_response()
+}
+
+/// Streams every page of records as a CSV download while later pages are
+/// still being fetched. The first page is fetched up front so a failure
+/// there (no access, bad filter) is still an ordinary error response; a
+/// later failure aborts the download rather than leaving it silently short.
+pub async fn csv_download<T, S, E>(file_name: String, pages: S) -> AppResult<Response>
+where
+    T: CsvRecord + Send + 'static,
+    S: Stream<Item = Result<Vec<T>, E>> + Send + 'static,
+    E: Into<AppError> + Display + Send + 'static,
+{
+    let mut pages = Box::pin(pages);
+    let first = pages.try_next().await.map_err(Into::into)?.unwrap_or_default();
+
+    let encode = |records: Vec<T>| {
+        Bytes::from(records.iter().flat_map(CsvRecord::csv_rows).map(|row| encode_row(&row)).collect::<String>())
+    };
+    // The byte order mark makes Excel read the file as UTF-8
+    let header_row = Bytes::from(format!("\u{feff}{}", encode_row(&T::csv_headers())));
+    let export = file_name.clone();
+    let rows = futures::stream::iter([Ok(header_row), Ok(encode(first))])
+        .chain(pages.map(move |page| {
+            page.map(encode).map_err(|e| {
+                error!("CSV export {} failed part-way: {}", export, e);
+                std::io::Error::other(e.to_string())
+            })
+        }));
+
+    info!("📄 Streaming CSV export {}", file_name);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        Body::from_stream(rows),
+    ).into_response())
+}
+
+/// Streams every page of records as newline-delimited JSON, one record per
+/// line, holding only the page being written in memory. Failures behave as
+/// in `csv_download`.
+pub async fn ndjson_download<T, S, E>(resource: &str, pages: S) -> AppResult<Response>
+where
+    T: Serialize + Send + 'static,
+    S: Stream<Item = Result<Vec<T>, E>> + Send + 'static,
+    E: Into<AppError> + Display + Send + 'static,
+{
+    let mut pages = Box::pin(pages);
+    let first = pages.try_next().await.map_err(Into::into)?.unwrap_or_default();
+
+    let encode = |records: Vec<T>| -> std::io::Result<Bytes> {
+        let mut lines = Vec::new();
+        for record in &records {
+            serde_json::to_writer(&mut lines, record)?;
+            lines.push(b'\n');
+        }
+        Ok(Bytes::from(lines))
+    };
+    let export = resource.to_string();
+    let lines = futures::stream::iter([encode(first)])
+        .chain(pages.map(move |page| {
+            page.map_err(|e| {
+                error!("NDJSON export of {} failed part-way: {}", export, e);
+                std::io::Error::other(e.to_string())
+            })
+            .and_then(encode)
+        }));
+
+    info!("📄 Streaming {} as NDJSON", resource);
+    Ok((
+        [(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)],
+        Body::from_stream(lines),
+    ).into_response())
+}
+
+/// Streams a list endpoint's records in `format`, CSV as a dated download.
+pub async fn list_download<T, S, E>(format: ListFormat, resource: &str, pages: S) -> AppResult<Response>
+where
+    T: CsvRecord + Serialize + Send + 'static,
+    S: Stream<Item = Result<Vec<T>, E>> + Send + 'static,
+    E: Into<AppError> + Display + Send + 'static,
+{
+    match format {
+        ListFormat::Csv => csv_download(export_file_name(resource), pages).await,
+        ListFormat::Json | ListFormat::Ndjson => ndjson_download(resource, pages).await,
+    }
+}
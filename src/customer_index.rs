@@ -0,0 +1,415 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use futures::{pin_mut, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::{error, info};
+use utoipa::IntoParams;
+
+use crate::{
+    AppState,
+    catalog::search_tsquery,
+    customer_merge::{MirrorCustomer, MirrorCustomersResponse, MIRROR_CUSTOMER_FIELDS},
+    database::{CustomerMirrorStore, CustomerQuery, MirroredCustomer, QueuedJob},
+    error::{AppError, AppResult},
+    http_client::{PageInfo, ShopifyClient},
+    job_queue::{JobHandlerResult, JobKind, ShopOrdering},
+    scheduler::{Job, JobResult, JobScope, Schedule},
+};
+
+/// Prefix of `page_info` cursors for customer search pages.
+const CUSTOMER_CURSOR_PREFIX: &str = "customers_";
+
+const CUSTOMER_SEARCH_DEFAULT_LIMIT: i64 = 50;
+
+/// Fewer digits than this match too many phone numbers to be useful.
+const MIN_PHONE_DIGITS: usize = 4;
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct CustomerIndexConfig {
+    /// Keep the customer mirror searchable and serve `/api/customers/search`
+    pub enabled: bool,
+    /// Time between reconciliation passes over every installed shop
+    pub reconcile_interval: Duration,
+}
+
+impl Default for CustomerIndexConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reconcile_interval: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl CustomerIndexConfig {
+    /// `CUSTOMER_INDEX_RECONCILE_INTERVAL_SECS`; unset or 0 leaves the index
+    /// off, since it copies customer PII into the database.
+    pub fn from_env() -> Self {
+        match std::env::var("CUSTOMER_INDEX_RECONCILE_INTERVAL_SECS").ok().and_then(|v| v.parse::<u64>().ok()) {
+            None | Some(0) => Self::default(),
+            Some(secs) => Self { enabled: true, reconcile_interval: Duration::from_secs(secs) },
+        }
+    }
+}
+
+// =============================================================================
+// Sync
+// =============================================================================
+
+/// What one reconciliation pass did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CustomerIndexSyncReport {
+    pub fetched: usize,
+    /// Customers that were new or had changed
+    pub written: u64,
+    /// Customers gone from Shopify whose delete webhook was missed
+    pub removed: u64,
+}
+
+// Shops with a reconciliation in progress in this process
+fn reconciling() -> &'static Mutex<HashSet<String>> {
+    static RECONCILING: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    RECONCILING.get_or_init(Default::default)
+}
+
+struct ReconcileGuard(String);
+
+impl ReconcileGuard {
+    fn acquire(shop: &str) -> Option<Self> {
+        reconciling().lock().unwrap().insert(shop.to_string()).then(|| Self(shop.to_string()))
+    }
+}
+
+impl Drop for ReconcileGuard {
+    fn drop(&mut self) {
+        reconciling().lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Compares every customer in Shopify with the mirror, writing the ones that
+/// changed and dropping the ones Shopify no longer has. Merged records are
+/// left alone. One pass per shop at a time; a second caller gets `Conflict`.
+pub async fn reconcile_customers(
+    shopify: &ShopifyClient,
+    store: &CustomerMirrorStore,
+    shop: &str,
+    token: &str,
+) -> AppResult<CustomerIndexSyncReport> {
+    let _guard = ReconcileGuard::acquire(shop)
+        .ok_or_else(|| AppError::Conflict(format!("A customer index sync is already running for {}", shop)))?;
+
+    let started_at = Utc::now();
+    let mut report = CustomerIndexSyncReport::default();
+    let mut seen = Vec::new();
+    let client = shopify.for_shop(shop);
+    let result: AppResult<()> = async {
+        let pages = client.get_all_pages::<MirrorCustomersResponse>(
+            "customers.json",
+            token,
+            vec![
+                ("limit".to_string(), "250".to_string()),
+                ("fields".to_string(), MIRROR_CUSTOMER_FIELDS.to_string()),
+            ],
+        );
+        pin_mut!(pages);
+
+        while let Some(page) = pages.try_next().await? {
+            let customers: Vec<MirroredCustomer> = page.customers.into_iter().map(MirroredCustomer::from).collect();
+            report.fetched += customers.len();
+            seen.extend(customers.iter().map(|customer| customer.customer_id));
+            let stats = store.upsert_customers(shop, &customers).await?;
+            report.written += stats.changed as u64;
+        }
+        report.removed = store.delete_customers_except(shop, &seen, started_at).await?;
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            store.record_sync(shop, None).await?;
+            info!(
+                "👥 Reconciled customer index for {}: {} fetched, {} written, {} removed",
+                shop, report.fetched, report.written, report.removed
+            );
+            Ok(report)
+        }
+        Err(e) => {
+            if let Err(record_error) = store.record_sync(shop, Some(&e.to_string())).await {
+                error!("Failed to record customer index sync failure for {}: {}", shop, record_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Queue job kind reconciling one shop's customers, retried with backoff.
+pub const SHOP_CUSTOMER_INDEX_SYNC_JOB: &str = "shop-customer-index-sync";
+
+pub fn shop_customer_index_sync_job_kind() -> JobKind {
+    JobKind::new(SHOP_CUSTOMER_INDEX_SYNC_JOB, ShopOrdering::Single, |state: AppState, job: QueuedJob| async move {
+        run_shop_customer_index_sync(&state, job.shop_domain.as_deref()).await
+    })
+}
+
+async fn run_shop_customer_index_sync(state: &AppState, shop: Option<&str>) -> JobHandlerResult {
+    let shop = shop.ok_or("Customer index sync job has no shop")?;
+    let token = state.token_store.get_token(shop).await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Shop {} is not installed", shop))?;
+
+    reconcile_customers(&state.shopify, &state.customer_mirror, shop, &token)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Queues a reconciliation of every installed shop's customers each
+/// `CUSTOMER_INDEX_RECONCILE_INTERVAL_SECS`, starting at startup.
+pub fn customer_index_reconcile_job(config: &CustomerIndexConfig) -> Job {
+    Job::new("customer-index-reconcile", Schedule::Every(config.reconcile_interval), JobScope::Cluster, |state: AppState| async move {
+        queue_installed_shops(&state).await
+    })
+    .run_on_start()
+}
+
+async fn queue_installed_shops(state: &AppState) -> JobResult {
+    let shops = state.token_store.list_shops().await.map_err(|e| e.to_string())?;
+
+    let (mut queued, mut pending, mut failed) = (0, 0, Vec::new());
+    for shop in shops {
+        match state.job_queue.enqueue(SHOP_CUSTOMER_INDEX_SYNC_JOB, Some(&shop.shop_domain), serde_json::json!({})).await {
+            Ok(Some(_)) => queued += 1,
+            Ok(None) => pending += 1,
+            Err(e) => {
+                error!("Failed to queue customer index sync for {}: {}", shop.shop_domain, e);
+                failed.push(shop.shop_domain);
+            }
+        }
+    }
+
+    let summary = format!("{} shops queued, {} already pending", queued, pending);
+    if failed.is_empty() {
+        Ok(summary)
+    } else {
+        Err(format!("{}, failed for {}", summary, failed.join(", ")))
+    }
+}
+
+/// Applies a `customers/*` webhook to the mirror.
+pub async fn apply_customer_webhook(
+    store: &CustomerMirrorStore,
+    shop: &str,
+    topic: &str,
+    payload: &serde_json::Value,
+) -> AppResult<()> {
+    if topic == "customers/delete" {
+        if let Some(customer_id) = payload["id"].as_i64() {
+            store.delete_customer(shop, customer_id).await?;
+        }
+        return Ok(());
+    }
+
+    let customer: MirrorCustomer = serde_json::from_value(payload.clone())
+        .map_err(|e| AppError::BadRequest(format!("Unreadable {} payload: {}", topic, e)))?;
+    store.upsert_customers(shop, &[customer.into()]).await.map(|_| ())
+}
+
+// =============================================================================
+// Customer Search
+// =============================================================================
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CustomerSearchParams {
+    /// Words to find in names, email addresses or tags
+    pub q: Option<String>,
+    /// Exact email address, ignoring case
+    pub email: Option<String>,
+    /// Phone number or its last digits, in any format
+    pub phone: Option<String>,
+    /// Words to find in first or last names
+    pub name: Option<String>,
+    pub tag: Option<String>,
+    pub limit: Option<u32>,
+    /// Cursor from the previous page; repeat the same filters with it
+    pub page_info: Option<String>,
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+/// `search_tsquery` restricted to the name words of the index.
+fn name_tsquery(name: &str) -> Option<String> {
+    search_tsquery(name).map(|query| query.replace(":*", ":*A"))
+}
+
+/// The `/api/customers/search` query as a mirror search. At least one
+/// criterion is required, so the endpoint can't be used to page through
+/// every customer.
+pub fn customer_query(params: &CustomerSearchParams) -> Result<CustomerQuery, String> {
+    let phone_digits = match non_empty(&params.phone) {
+        None => None,
+        Some(phone) => {
+            let digits: String = phone.chars().filter(|c| c.is_ascii_digit()).collect();
+            if digits.len() < MIN_PHONE_DIGITS {
+                return Err(format!("phone needs at least {} digits", MIN_PHONE_DIGITS));
+            }
+            Some(digits)
+        }
+    };
+    let offset = match params.page_info.as_deref() {
+        None => 0,
+        Some(page_info) => page_info
+            .strip_prefix(CUSTOMER_CURSOR_PREFIX)
+            .and_then(|offset| offset.parse::<i64>().ok())
+            .filter(|offset| *offset >= 0)
+            .ok_or_else(|| "page_info is not a customer search cursor".to_string())?,
+    };
+
+    let query = CustomerQuery {
+        text: params.q.as_deref().and_then(search_tsquery),
+        name: params.name.as_deref().and_then(name_tsquery),
+        email: non_empty(&params.email),
+        phone_digits,
+        tag: non_empty(&params.tag),
+        limit: params.limit.map(i64::from).unwrap_or(CUSTOMER_SEARCH_DEFAULT_LIMIT).clamp(1, 250),
+        offset,
+    };
+    if query.text.is_none() && query.name.is_none() && query.email.is_none() && query.phone_digits.is_none() && query.tag.is_none() {
+        return Err("Search by at least one of q, email, phone, name or tag".to_string());
+    }
+
+    Ok(query)
+}
+
+fn require_customer_index(state: &AppState) -> AppResult<()> {
+    if !state.config.customer_index.enabled || state.config.database.database_url.is_none() {
+        return Err(AppError::BadRequest(
+            "Customer search needs a Postgres DATABASE_URL and CUSTOMER_INDEX_RECONCILE_INTERVAL_SECS above 0".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// `GET /api/customers/search` — searches the local customer index.
+#[utoipa::path(
+    get,
+    path = "/api/customers/search",
+    tag = "customers",
+    params(CustomerSearchParams),
+    responses(
+        (status = 200, description = "Matching customers from the local index", body = crate::openapi::CustomerSearch),
+        (status = 400, description = "Index disabled, no criteria or bad filters", body = crate::openapi::ErrorResponse),
+        (status = 409, description = "The index is still being built", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn customer_search_handler(
+    Query(params): Query<CustomerSearchParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    require_customer_index(&state)?;
+    let query = customer_query(&params).map_err(AppError::BadRequest)?;
+
+    let sync = state.customer_mirror.sync_state(shop).await?;
+    if sync.as_ref().is_none_or(|s| s.reconciled_at.is_none()) {
+        return Err(AppError::Conflict(format!(
+            "The customer index for {} is still being built",
+            shop
+        )));
+    }
+
+    let (customers, total) = state.customer_mirror.search_customers(shop, &query).await?;
+
+    let next_offset = query.offset + query.limit;
+    let page_info = PageInfo {
+        has_next_page: next_offset < total,
+        has_previous_page: query.offset > 0,
+        next_page_info: (next_offset < total).then(|| format!("{}{}", CUSTOMER_CURSOR_PREFIX, next_offset)),
+        previous_page_info: (query.offset > 0)
+            .then(|| format!("{}{}", CUSTOMER_CURSOR_PREFIX, (query.offset - query.limit).max(0))),
+    };
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "total": total,
+        "customers_count": customers.len(),
+        "customers": customers,
+        "page_info": page_info,
+        "synced_at": sync.and_then(|s| s.last_synced_at)
+    }))))
+}
+
+// =============================================================================
+// Admin Handlers
+// =============================================================================
+
+/// `GET /admin/shops/{shop}/customers/sync` — when the shop's customer index
+/// was last reconciled.
+#[utoipa::path(
+    get,
+    path = "/admin/shops/{shop}/customers/sync",
+    tag = "sync",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 200, description = "Customer index sync progress", body = crate::openapi::CustomerIndexSyncStatus),
+    ),
+)]
+pub async fn customer_index_sync_status_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let sync = state.customer_mirror.sync_state(&shop).await?;
+    let running = reconciling().lock().unwrap().contains(&shop);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "enabled": state.config.customer_index.enabled,
+        "running": running,
+        "state": sync
+    }))))
+}
+
+/// `POST /admin/shops/{shop}/customers/sync` — queues a reconciliation pass
+/// instead of waiting for the next one.
+#[utoipa::path(
+    post,
+    path = "/admin/shops/{shop}/customers/sync",
+    tag = "sync",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 202, description = "Sync queued", body = crate::openapi::SyncQueued),
+        (status = 404, description = "Shop not installed", body = crate::openapi::ErrorResponse),
+        (status = 409, description = "A sync is already queued or running", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn start_customer_index_sync_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    require_customer_index(&state)?;
+    state.token_store.get_token(&shop).await?
+        .ok_or_else(|| AppError::NotFound(format!("Shop {} is not installed", shop)))?;
+    let job = state.job_queue.enqueue(SHOP_CUSTOMER_INDEX_SYNC_JOB, Some(&shop), serde_json::json!({})).await?
+        .ok_or_else(|| AppError::Conflict(format!("A customer index sync is already queued or running for {}", shop)))?;
+
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({
+        "shop": shop,
+        "queued": true,
+        "job_id": job.id
+    }))))
+}
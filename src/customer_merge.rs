@@ -0,0 +1,355 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::{pin_mut, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    require_token,
+    error::{AppError, AppResult, ShopifyError},
+    database::{MirroredCustomer, MirrorSyncStats},
+    http_client::ShopifyClient,
+};
+
+// =============================================================================
+// Merge Structures
+// =============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DuplicateParams {
+    /// Re-sync the customer mirror from Shopify before looking for duplicates
+    pub refresh: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MergeRequest {
+    pub primary_id: i64,
+    pub duplicate_ids: Vec<i64>,
+    pub reason: Option<String>,
+    /// Tag and annotate the duplicates in Shopify as well
+    #[serde(default)]
+    pub update_shopify: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DuplicateGroup {
+    /// What the records have in common, e.g. `email` or `email,phone`
+    pub matched_on: Vec<String>,
+    /// Suggested record to keep: most orders, then the oldest
+    pub suggested_primary_id: i64,
+    pub customers: Vec<MirroredCustomer>,
+}
+
+/// The `fields` a mirror sync asks Shopify for
+pub(crate) const MIRROR_CUSTOMER_FIELDS: &str =
+    "id,email,phone,first_name,last_name,orders_count,tags,note,created_at,updated_at";
+
+// Light customer shape, only the fields needed for the mirror
+#[derive(Deserialize)]
+pub(crate) struct MirrorCustomersResponse {
+    pub customers: Vec<MirrorCustomer>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct MirrorCustomer {
+    id: i64,
+    email: Option<String>,
+    phone: Option<String>,
+    first_name: Option<String>,
+    last_name: Option<String>,
+    orders_count: Option<i32>,
+    tags: Option<String>,
+    note: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<MirrorCustomer> for MirroredCustomer {
+    fn from(customer: MirrorCustomer) -> Self {
+        Self {
+            customer_id: customer.id,
+            email: customer.email,
+            phone: customer.phone,
+            first_name: customer.first_name,
+            last_name: customer.last_name,
+            orders_count: customer.orders_count.unwrap_or(0),
+            tags: customer.tags.unwrap_or_default(),
+            note: customer.note,
+            created_at: customer.created_at,
+            updated_at: customer.updated_at,
+        }
+    }
+}
+
+// =============================================================================
+// Duplicate Detection
+// =============================================================================
+
+pub fn normalize_email(email: &str) -> Option<String> {
+    let email = email.trim().to_lowercase();
+    email.contains('@').then_some(email)
+}
+
+/// Digits only, so `+1 (555) 010-0000` and `15550100000` match. Very short
+/// numbers are ignored since they are more likely placeholders than real phones.
+pub fn normalize_phone(phone: &str) -> Option<String> {
+    let digits: String = phone.chars().filter(|c| c.is_ascii_digit()).collect();
+    (digits.len() >= 7).then_some(digits)
+}
+
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+/// Groups customers that share a normalized email or phone. Matches are
+/// transitive: A shares an email with B and B a phone with C puts all three together.
+pub fn find_duplicate_groups(customers: &[MirroredCustomer]) -> Vec<DuplicateGroup> {
+    let mut parents: Vec<usize> = (0..customers.len()).collect();
+    let mut matched_on: Vec<Vec<String>> = vec![Vec::new(); customers.len()];
+    let mut seen: HashMap<(&str, String), usize> = HashMap::new();
+
+    for (i, customer) in customers.iter().enumerate() {
+        let keys = [
+            ("email", customer.email.as_deref().and_then(normalize_email)),
+            ("phone", customer.phone.as_deref().and_then(normalize_phone)),
+        ];
+
+        for (kind, key) in keys {
+            let Some(key) = key else { continue };
+            match seen.get(&(kind, key.clone())) {
+                Some(&first) => {
+                    let (a, b) = (find_root(&mut parents, first), find_root(&mut parents, i));
+                    parents[b] = a;
+                    for index in [first, i] {
+                        if !matched_on[index].iter().any(|k| k == kind) {
+                            matched_on[index].push(kind.to_string());
+                        }
+                    }
+                }
+                None => {
+                    seen.insert((kind, key), i);
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..customers.len() {
+        let root = find_root(&mut parents, i);
+        groups.entry(root).or_default().push(i);
+    }
+
+    let mut duplicate_groups: Vec<DuplicateGroup> = groups
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            let mut matched: Vec<String> = members.iter().flat_map(|&i| matched_on[i].clone()).collect();
+            matched.sort();
+            matched.dedup();
+
+            let group: Vec<MirroredCustomer> = members.iter().map(|&i| customers[i].clone()).collect();
+            let primary = group
+                .iter()
+                .max_by(|a, b| {
+                    a.orders_count
+                        .cmp(&b.orders_count)
+                        .then_with(|| b.created_at.cmp(&a.created_at))
+                        .then_with(|| b.customer_id.cmp(&a.customer_id))
+                })
+                .map(|c| c.customer_id)
+                .unwrap_or_default();
+
+            DuplicateGroup {
+                matched_on: matched,
+                suggested_primary_id: primary,
+                customers: group,
+            }
+        })
+        .collect();
+
+    duplicate_groups.sort_by_key(|group| group.suggested_primary_id);
+    duplicate_groups
+}
+
+// =============================================================================
+// Customer Merge Handlers
+// =============================================================================
+
+/// Finds likely duplicate customers in the local customer mirror.
+#[utoipa::path(
+    get,
+    path = "/api/customers/duplicates",
+    tag = "customers",
+    params(DuplicateParams),
+    responses(
+        (status = 200, description = "Groups of mirrored customers that look like the same person", body = crate::openapi::DuplicateGroupList),
+    ),
+)]
+pub async fn customer_duplicates_handler(
+    Query(params): Query<DuplicateParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+
+    let sync = if params.refresh.unwrap_or(false) {
+        let token = require_token(&state.token_store, shop).await?;
+        Some(sync_customer_mirror(&state, &token, shop).await?)
+    } else {
+        None
+    };
+
+    let customers = state.customer_mirror.active_customers(shop).await?;
+    let groups = find_duplicate_groups(&customers);
+    info!("Found {} duplicate customer groups among {} customers", groups.len(), customers.len());
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "customers_scanned": customers.len(),
+        "sync": sync,
+        "groups_count": groups.len(),
+        "groups": groups
+    }))))
+}
+
+/// Merges duplicate customers into a primary record.
+#[utoipa::path(
+    post,
+    path = "/api/customers/merge",
+    tag = "customers",
+    request_body = MergeRequest,
+    responses(
+        (status = 200, description = "The recorded merge", body = crate::openapi::CustomerMerged),
+        (status = 400, description = "No duplicates, or the primary among them", body = crate::openapi::ErrorResponse),
+        (status = 404, description = "Customers missing from the mirror or already merged", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn customer_merge_handler(
+    State(state): State<AppState>,
+    Json(request): Json<MergeRequest>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+
+    if request.duplicate_ids.is_empty() || request.duplicate_ids.contains(&request.primary_id) {
+        return Err(AppError::BadRequest(
+            "duplicate_ids must be non-empty and must not include primary_id".to_string(),
+        ));
+    }
+
+    // Check the mirror before touching Shopify so a bad request changes nothing
+    let customers = state.customer_mirror.active_customers(shop).await?;
+
+    let unknown: Vec<i64> = std::iter::once(request.primary_id)
+        .chain(request.duplicate_ids.iter().copied())
+        .filter(|id| !customers.iter().any(|c| c.customer_id == *id))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(AppError::NotFound(format!(
+            "Customers not found in the mirror or already merged: {:?}",
+            unknown
+        )));
+    }
+
+    let mut shopify_errors = Vec::new();
+    if request.update_shopify {
+        let token = require_token(&state.token_store, shop).await?;
+
+        for duplicate in customers.iter().filter(|c| request.duplicate_ids.contains(&c.customer_id)) {
+            if let Err(e) = mark_merged_in_shopify(&state.shopify, &token, duplicate, request.primary_id).await {
+                warn!("Failed to update customer {} in Shopify: {}", duplicate.customer_id, e);
+                shopify_errors.push(serde_json::json!({
+                    "customer_id": duplicate.customer_id,
+                    "error": e.to_string()
+                }));
+            }
+        }
+    }
+
+    let merge = state.customer_mirror.merge_customers(
+        shop,
+        request.primary_id,
+        &request.duplicate_ids,
+        request.reason.as_deref(),
+        request.update_shopify && shopify_errors.is_empty(),
+        &serde_json::Value::Array(shopify_errors),
+    ).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "merge": merge
+    }))))
+}
+
+// =============================================================================
+// Shopify Sync
+// =============================================================================
+
+async fn sync_customer_mirror(
+    state: &AppState,
+    token: &str,
+    shop: &str,
+) -> AppResult<MirrorSyncStats> {
+    let query_params = vec![
+        ("limit".to_string(), "250".to_string()),
+        ("fields".to_string(), MIRROR_CUSTOMER_FIELDS.to_string()),
+    ];
+
+    let pages = state.shopify.get_all_pages::<MirrorCustomersResponse>("customers.json", token, query_params);
+    pin_mut!(pages);
+
+    let mut customers = Vec::new();
+    while let Some(page) = pages.try_next().await? {
+        customers.extend(page.customers.into_iter().map(MirroredCustomer::from));
+    }
+
+    state.customer_mirror.upsert_customers(shop, &customers).await
+}
+
+pub fn merged_tags(tags: &str, primary_id: i64) -> String {
+    let merged_tag = format!("merged-into-{}", primary_id);
+    let mut tags: Vec<&str> = tags.split(',').map(str::trim).filter(|t| !t.is_empty()).collect();
+    if !tags.contains(&merged_tag.as_str()) {
+        tags.push(&merged_tag);
+    }
+    tags.join(", ")
+}
+
+async fn mark_merged_in_shopify(
+    client: &ShopifyClient,
+    token: &str,
+    customer: &MirroredCustomer,
+    primary_id: i64,
+) -> Result<(), ShopifyError> {
+    let merge_note = format!("Merged into customer {} on {}", primary_id, Utc::now().format("%Y-%m-%d"));
+    let note = match customer.note.as_deref() {
+        Some(existing) if !existing.is_empty() => format!("{}\n{}", existing, merge_note),
+        _ => merge_note,
+    };
+
+    let body = serde_json::json!({
+        "customer": {
+            "id": customer.customer_id,
+            "tags": merged_tags(&customer.tags, primary_id),
+            "note": note
+        }
+    });
+
+    client
+        .put_with_auth::<_, serde_json::Value>(&format!("customers/{}.json", customer.customer_id), token, &body)
+        .await?;
+
+    Ok(())
+}
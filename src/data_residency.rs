@@ -0,0 +1,74 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::{
+    AppState,
+    error::AppResult,
+};
+
+// =============================================================================
+// Data Residency Handlers
+// =============================================================================
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AssignRegionRequest {
+    pub region: String,
+}
+
+/// Which regional database holds a shop's data.
+#[utoipa::path(
+    get,
+    path = "/admin/shops/{shop}/region",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 200, description = "The shop's database region", body = crate::openapi::ShopRegion),
+    ),
+)]
+pub async fn get_shop_region_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let region = state.db.region_for(&shop).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "region": region,
+        "home": region.is_none(),
+        "available_regions": state.db.regions()
+    }))))
+}
+
+/// Pins a shop's data to a regional database. Only allowed before the shop
+/// has installed the app, since existing rows are not migrated.
+#[utoipa::path(
+    put,
+    path = "/admin/shops/{shop}/region",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    request_body = AssignRegionRequest,
+    responses(
+        (status = 200, description = "Region assigned", body = crate::openapi::ShopRegionAssigned),
+        (status = 400, description = "Unknown region", body = crate::openapi::ErrorResponse),
+        (status = 409, description = "The shop already has data", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn put_shop_region_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+    Json(request): Json<AssignRegionRequest>,
+) -> AppResult<impl IntoResponse> {
+    state.db.assign_region(&shop, &request.region).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "shop": shop,
+        "region": request.region.to_lowercase()
+    }))))
+}
@@ -0,0 +1,99 @@
+use chrono::Utc;
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::info;
+
+use crate::{
+    AppState,
+    database::{CustomerRedaction, PersonalDataStore, RedactionCounts},
+    error::{AppError, AppResult},
+    scheduler::{Job, JobScope, Schedule},
+    webhooks::CustomerRedactWebhook,
+};
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+#[derive(Clone, Debug, Default)]
+pub struct DataRetentionConfig {
+    /// Days personal data is kept in webhook deliveries, order copies and
+    /// recovery messages before it's scrubbed; `None` keeps it
+    pub pii_retention_days: Option<i64>,
+}
+
+impl DataRetentionConfig {
+    /// `PII_RETENTION_DAYS`, unset (or 0) by default.
+    pub fn from_env() -> AppResult<Self> {
+        Self::parse(std::env::var("PII_RETENTION_DAYS").ok().as_deref())
+    }
+
+    pub fn parse(days: Option<&str>) -> AppResult<Self> {
+        let days = match days.map(str::trim).filter(|v| !v.is_empty()) {
+            Some(days) => days
+                .parse::<i64>()
+                .ok()
+                .filter(|days| *days >= 0)
+                .ok_or_else(|| AppError::Config(format!("PII_RETENTION_DAYS must be a number of days, got {}", days)))?,
+            None => 0,
+        };
+        Ok(Self { pii_retention_days: (days > 0).then_some(days) })
+    }
+}
+
+// =============================================================================
+// Customer Redaction
+// =============================================================================
+
+/// Applies a `customers/redact` delivery, erasing the customer from every
+/// local table of the shop. Safe to repeat.
+pub async fn apply_customer_redaction(
+    store: &PersonalDataStore,
+    shop_domain: &str,
+    payload: &serde_json::Value,
+) -> AppResult<RedactionCounts> {
+    let payload = CustomerRedactWebhook::deserialize(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid customers/redact payload: {}", e)))?;
+    let redaction = CustomerRedaction {
+        customer_id: payload.customer.id as i64,
+        email: payload.customer.email,
+        order_ids: payload.orders_to_redact.iter().map(|&id| id as i64).collect(),
+    };
+
+    let counts = store.redact_customer(shop_domain, &redaction).await?;
+    info!(
+        "🧽 Redacted customer {} on {}: {} webhook events, {} orders, {} mirrored records, {} recovery messages, {} emails, {} jobs, {} exports",
+        redaction.customer_id,
+        shop_domain,
+        counts.webhook_events,
+        counts.orders,
+        counts.customers,
+        counts.recovery_messages,
+        counts.emails,
+        counts.jobs,
+        counts.exports
+    );
+    Ok(counts)
+}
+
+// =============================================================================
+// Scheduled Jobs
+// =============================================================================
+
+/// Scrubs personal data older than `PII_RETENTION_DAYS`.
+pub fn pii_retention_job() -> Job {
+    Job::new("pii-retention", Schedule::Every(Duration::from_secs(3600)), JobScope::Cluster, |state: AppState| async move {
+        let Some(days) = state.config.data_retention.pii_retention_days else {
+            return Ok("PII_RETENTION_DAYS is not set".to_string());
+        };
+        let counts = state
+            .personal_data
+            .redact_older_than(Utc::now() - chrono::Duration::days(days))
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(format!(
+            "{} webhook events, {} orders, {} recovery messages, {} emails, {} jobs and {} exports redacted",
+            counts.webhook_events, counts.orders, counts.recovery_messages, counts.emails, counts.jobs, counts.exports
+        ))
+    })
+}
@@ -1,16 +1,35 @@
 use sqlx::PgPool;
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
+use rust_decimal::Decimal;
 use aes_gcm::{
     aead::{Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm,
 };
 use base64::{Engine as _, engine::general_purpose};
+use hkdf::Hkdf;
 use secrecy::{Secret, ExposeSecret};
+use sha2::{Digest, Sha256};
 use tracing::{info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 
-// Export aliases for convenience
-pub use TokenStore as DbTokenStore;
+use crate::downloads::purge_exports;
+use crate::error::{AppError, AppResult};
+use crate::key_provider::WrappedKey;
+use crate::token_audit::{AuditSink, TokenAuditEntry};
+use crate::token_store::{
+    exchange_json, CompletedExchange, InstalledShop, OAuthStateRecord, OAuthStateRow, StateClaim, StateStore,
+    TokenStore, STATE_CLAIM_COLUMNS, STATE_RECORD_COLUMNS,
+};
+use crate::fulfillment_routing::FulfillmentRoutingConfig;
+use crate::low_stock::LowStockRules;
+use crate::order_documents::{DocumentKind, DocumentTemplate};
+use crate::response_shaping::redact_pii;
+use crate::shop_secrets::IntegrationSecret;
+use crate::warehouse_export::ExportTable;
 
 // =============================================================================
 // Database Models
@@ -28,6 +47,469 @@ pub struct ShopifyToken {
     pub expires_at: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, sqlx::FromRow)]
+pub struct WebhookEvent {
+    pub id: Uuid,
+    pub shop_domain: String,
+    pub topic: String,
+    pub resource_id: Option<i64>,
+    pub webhook_id: Option<String>,
+    pub payload: Option<serde_json::Value>,
+    pub received_at: DateTime<Utc>,
+    pub raw_body: Option<Vec<u8>>,
+    pub headers: serde_json::Value,
+    pub verified: bool,
+    pub processing_attempts: i32,
+    pub processed_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct MirroredCustomer {
+    pub customer_id: i64,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub orders_count: i32,
+    pub tags: String,
+    pub note: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl MirroredCustomer {
+    /// SHA-256 over the mirrored fields, used to tell whether a fetched
+    /// customer differs from the stored row.
+    pub fn content_hash(&self) -> String {
+        let content = serde_json::to_vec(self).unwrap_or_default();
+        hex::encode(Sha256::digest(&content))
+    }
+}
+
+/// Outcome of writing a sync batch to a mirror table.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, utoipa::ToSchema)]
+pub struct MirrorSyncStats {
+    pub fetched: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+}
+
+/// One mirrored order line item.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct MirroredLineItem {
+    pub order_id: i64,
+    pub line_item_id: i64,
+    pub order_created_at: DateTime<Utc>,
+    pub product_id: Option<i64>,
+    pub variant_id: Option<i64>,
+    pub title: String,
+    pub sku: Option<String>,
+    pub quantity: i32,
+    pub price: Decimal,
+    pub total_discount: Decimal,
+}
+
+impl MirroredLineItem {
+    /// Line revenue after line-level discounts.
+    pub fn revenue(&self) -> Decimal {
+        self.price * Decimal::from(self.quantity) - self.total_discount
+    }
+}
+
+/// An order in the local `orders` table. `data` is the whole order; the
+/// other fields are copied out of it to filter on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredOrder {
+    pub order_id: i64,
+    pub order_number: i64,
+    pub name: String,
+    pub email: Option<String>,
+    pub financial_status: Option<String>,
+    pub fulfillment_status: Option<String>,
+    pub source_name: Option<String>,
+    pub total_price: Decimal,
+    pub currency: Option<String>,
+    pub test: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub processed_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub data: serde_json::Value,
+}
+
+/// Narrows `OrderStore::list_orders`; unset fields match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoredOrderFilter {
+    pub ids: Option<Vec<i64>>,
+    pub since_id: Option<i64>,
+    /// Only orders with a lower ID, for paging newest first
+    pub before_id: Option<i64>,
+    /// open, closed or cancelled
+    pub status: Option<String>,
+    pub financial_statuses: Option<Vec<String>>,
+    /// Stored values, with `unfulfilled` standing for a null status
+    pub fulfillment_statuses: Option<Vec<String>>,
+    pub created_at_min: Option<DateTime<Utc>>,
+    pub created_at_max: Option<DateTime<Utc>>,
+    pub updated_at_min: Option<DateTime<Utc>>,
+    pub updated_at_max: Option<DateTime<Utc>>,
+    pub processed_at_min: Option<DateTime<Utc>>,
+    pub processed_at_max: Option<DateTime<Utc>>,
+    /// low, medium or high, as last assessed
+    pub risk_level: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Open, non-test orders in the local copy, for `/api/orders/summary`.
+#[derive(Debug, Clone, Default, PartialEq, sqlx::FromRow)]
+pub struct OrderSummary {
+    pub open_orders: i64,
+    pub unfulfilled_orders: i64,
+    pub partially_fulfilled_orders: i64,
+    /// When the oldest unfulfilled or partially fulfilled order was placed
+    pub oldest_unfulfilled_at: Option<DateTime<Utc>>,
+    #[sqlx(skip)]
+    pub by_financial_status: Vec<FinancialStatusTotal>,
+}
+
+/// Open orders with one financial status, in one currency.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct FinancialStatusTotal {
+    pub financial_status: Option<String>,
+    pub currency: Option<String>,
+    pub orders: i64,
+    pub total_price: Decimal,
+}
+
+/// Orders placed in one period, in one currency.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct PeriodSales {
+    pub currency: Option<String>,
+    pub period_start: DateTime<Utc>,
+    pub orders: i64,
+    pub revenue: Decimal,
+}
+
+/// Refunds issued in one period, in one currency.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct PeriodRefunds {
+    pub currency: Option<String>,
+    pub period_start: DateTime<Utc>,
+    pub refunds: i64,
+    pub refunded: Decimal,
+}
+
+/// Progress of a shop's order sync.
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct OrderSyncState {
+    pub shop_domain: String,
+    pub backfilled_at: Option<DateTime<Utc>>,
+    pub updated_at_cursor: Option<DateTime<Utc>>,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// A product in the local catalog. `data` is the whole product; the other
+/// fields are copied out of it to filter and search on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogProduct {
+    pub product_id: i64,
+    pub title: String,
+    pub handle: String,
+    pub vendor: String,
+    pub product_type: String,
+    pub status: String,
+    pub tags: String,
+    pub updated_at: DateTime<Utc>,
+    pub variants: Vec<CatalogVariant>,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogVariant {
+    pub variant_id: i64,
+    pub title: String,
+    pub sku: Option<String>,
+    pub barcode: Option<String>,
+    pub price: Decimal,
+    pub inventory_quantity: i32,
+}
+
+/// Narrows `CatalogStore::search_products`; unset fields match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CatalogQuery {
+    /// Prefix-matched full-text query over title, SKUs, vendor and tags, in
+    /// `to_tsquery` syntax
+    pub text: Option<String>,
+    pub vendor: Option<String>,
+    pub product_type: Option<String>,
+    pub status: Option<String>,
+    pub tag: Option<String>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Narrows `CustomerMirrorStore::search_customers`; unset fields match
+/// everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CustomerQuery {
+    /// Prefix-matched full-text query over names, email and tags, in
+    /// `to_tsquery` syntax
+    pub text: Option<String>,
+    /// As `text`, over first and last names only
+    pub name: Option<String>,
+    /// Matched whole, ignoring case
+    pub email: Option<String>,
+    /// Digits the phone number ends with
+    pub phone_digits: Option<String>,
+    pub tag: Option<String>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Progress of a shop's customer index.
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct CustomerIndexSyncState {
+    pub shop_domain: String,
+    pub reconciled_at: Option<DateTime<Utc>>,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// Progress of a shop's catalog mirror.
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct CatalogSyncState {
+    pub shop_domain: String,
+    pub reconciled_at: Option<DateTime<Utc>>,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// A stock level at or below its low-stock threshold, or one that was until
+/// `resolved_at`.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct LowStockAlert {
+    pub inventory_item_id: i64,
+    pub location_id: i64,
+    pub sku: String,
+    pub available: i32,
+    pub threshold: i32,
+    pub triggered_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub notified_at: Option<DateTime<Utc>>,
+}
+
+/// What recording a stock level did to its alert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LowStockChange {
+    /// Low, and nobody has been told yet
+    Triggered,
+    /// Still low, already notified
+    StillLow,
+    /// Back above the threshold
+    Resolved,
+    /// Not low, and wasn't
+    Unchanged,
+}
+
+/// A scheduled job's shared state: who holds it and how its last run went.
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct ScheduledJobRecord {
+    pub name: String,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub locked_by: Option<String>,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_status: Option<String>,
+    pub last_summary: Option<String>,
+    pub last_error: Option<String>,
+    pub last_duration_ms: Option<i64>,
+    pub last_instance: Option<String>,
+}
+
+/// A unit of work in the durable job queue.
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct QueuedJob {
+    pub id: Uuid,
+    pub kind: String,
+    pub shop_domain: Option<String>,
+    pub queue_key: Option<String>,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub locked_by: Option<String>,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// A job to add to the queue.
+#[derive(Debug, Clone)]
+pub struct NewJob {
+    pub kind: String,
+    pub shop_domain: Option<String>,
+    /// Jobs sharing a key run one at a time, in enqueue order
+    pub queue_key: Option<String>,
+    pub payload: serde_json::Value,
+    pub max_attempts: i32,
+    /// Skip the insert if a job with the same key is already queued or running
+    pub unique: bool,
+}
+
+/// Filters for listing queued jobs.
+#[derive(Debug, Clone, Default)]
+pub struct JobQueueFilter {
+    pub status: Option<String>,
+    pub kind: Option<String>,
+    pub shop_domain: Option<String>,
+    pub limit: i64,
+}
+
+/// Number of jobs of one kind in one status.
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct JobQueueCount {
+    pub kind: String,
+    pub status: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct RecoveryMessage {
+    pub id: Uuid,
+    pub shop_domain: String,
+    pub checkout_id: i64,
+    pub recipient: String,
+    pub subject: Option<String>,
+    pub sent_at: DateTime<Utc>,
+    pub first_opened_at: Option<DateTime<Utc>>,
+    pub open_count: i32,
+    pub first_clicked_at: Option<DateTime<Utc>>,
+    pub click_count: i32,
+    pub bounced_at: Option<DateTime<Utc>>,
+    pub bounce_reason: Option<String>,
+    pub converted_at: Option<DateTime<Utc>>,
+    pub order_id: Option<i64>,
+}
+
+/// An email handed to the mail provider, and whether it took it.
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct EmailLogEntry {
+    pub id: Uuid,
+    pub shop_domain: String,
+    pub kind: String,
+    pub provider: String,
+    pub recipient: String,
+    pub subject: String,
+    /// `sent` or `failed`
+    pub status: String,
+    pub provider_message_id: Option<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How far one of a shop's tables has been exported to the data warehouse.
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct WarehouseExportState {
+    /// `orders`, `customers` or `products`
+    pub table_name: String,
+    /// `synced_at` of the last row written; the watermark together with
+    /// `last_id`
+    pub synced_until: Option<DateTime<Utc>>,
+    pub last_id: Option<i64>,
+    pub rows_exported: i64,
+    pub last_exported_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A row of a local copy, as exported to the data warehouse.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ExportRow {
+    pub id: i64,
+    /// When Shopify last changed it; unknown for some customers
+    pub updated_at: Option<DateTime<Utc>>,
+    pub synced_at: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+/// How many recovery messages sent in a window reached each stage.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, sqlx::FromRow)]
+pub struct RecoveryCounts {
+    pub sent: i64,
+    pub opened: i64,
+    pub clicked: i64,
+    pub bounced: i64,
+    pub converted: i64,
+}
+
+#[derive(Debug, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct CustomerMerge {
+    pub id: Uuid,
+    pub shop_domain: String,
+    pub primary_customer_id: i64,
+    pub merged_customer_ids: Vec<i64>,
+    pub reason: Option<String>,
+    pub shopify_updated: bool,
+    pub shopify_errors: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ApiUsageRow {
+    pub shop_domain: String,
+    pub period_start: DateTime<Utc>,
+    pub feature: String,
+    pub calls: i64,
+    pub throttled: i64,
+    pub errors: i64,
+    pub utilization_sum: f64,
+    pub utilization_samples: i64,
+}
+
+/// Everything about a stored integration secret except its value.
+#[derive(Debug, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct ShopSecretMetadata {
+    pub integration: String,
+    pub key_id: String,
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub rotated_at: Option<DateTime<Utc>>,
+}
+
+/// One row of `token_audit_log`.
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct TokenAuditEvent {
+    pub id: i64,
+    pub shop_domain: Option<String>,
+    pub action: String,
+    pub outcome: String,
+    pub actor: String,
+    pub source_ip: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Narrows `TokenAuditStore::query`; unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct TokenAuditFilter {
+    pub shop_domain: Option<String>,
+    pub action: Option<String>,
+    pub outcome: Option<String>,
+    pub actor: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: i64,
+}
+
 #[derive(Debug, sqlx::FromRow)]
 #[allow(dead_code)]
 pub struct OAuthState {
@@ -43,187 +525,575 @@ pub struct OAuthState {
 
 #[derive(Clone)]
 pub struct DatabaseConfig {
-    pub database_url: String,
+    /// Only optional when tokens and OAuth states are kept outside Postgres
+    pub database_url: Option<String>,
     pub max_connections: u32,
     pub min_connections: u32,
     pub encryption_key: Secret<String>,
+    /// When set, `encryption_key` is replaced at startup by unwrapping this
+    /// with an external key service
+    pub wrapped_key: Option<WrappedKey>,
+    /// Retired keys still accepted for decryption while secrets are re-encrypted
+    pub previous_encryption_keys: Vec<Secret<String>>,
+    /// Extra databases by region, from `DATABASE_URL_<REGION>` (e.g. `DATABASE_URL_EU`)
+    pub regional_database_urls: BTreeMap<String, String>,
 }
 
 impl DatabaseConfig {
-    pub fn from_env() -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    pub fn from_env() -> AppResult<Self> {
+        let wrapped_key = WrappedKey::from_env()?;
         Ok(DatabaseConfig {
-            database_url: std::env::var("DATABASE_URL")?,
+            database_url: std::env::var("DATABASE_URL").ok().filter(|url| !url.is_empty()),
             max_connections: std::env::var("DB_MAX_CONNECTIONS")
                 .unwrap_or_else(|_| "10".to_string())
-                .parse()?,
+                .parse()
+                .map_err(|e| AppError::Config(format!("DB_MAX_CONNECTIONS: {}", e)))?,
             min_connections: std::env::var("DB_MIN_CONNECTIONS")
                 .unwrap_or_else(|_| "5".to_string())
-                .parse()?,
+                .parse()
+                .map_err(|e| AppError::Config(format!("DB_MIN_CONNECTIONS: {}", e)))?,
             encryption_key: Secret::new(
                 std::env::var("ENCRYPTION_KEY")
                     .unwrap_or_else(|_| {
-                        warn!("ENCRYPTION_KEY not set, using default (NOT SECURE for production)");
+                        if wrapped_key.is_none() {
+                            warn!("ENCRYPTION_KEY not set, using default (NOT SECURE for production)");
+                        }
                         "your-32-byte-encryption-key-here-change-this-in-production!".to_string()
                     })
             ),
+            wrapped_key,
+            previous_encryption_keys: std::env::var("ENCRYPTION_KEY_PREVIOUS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .map(|k| Secret::new(k.to_string()))
+                .collect(),
+            regional_database_urls: regional_database_urls(std::env::vars()),
         })
     }
+    
+    /// Swaps in the unwrapped master key when a key service is configured.
+    pub async fn resolve_encryption_key(&mut self, http: &reqwest::Client) -> AppResult<()> {
+        if let Some(wrapped_key) = &self.wrapped_key {
+            self.encryption_key = wrapped_key.fetch(http).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Picks `DATABASE_URL_<REGION>` entries out of the environment, keyed by
+/// lowercase region name.
+pub fn regional_database_urls(vars: impl IntoIterator<Item = (String, String)>) -> BTreeMap<String, String> {
+    vars.into_iter()
+        .filter_map(|(name, url)| {
+            let region = name.strip_prefix("DATABASE_URL_")?.to_lowercase();
+            (!region.is_empty() && !url.is_empty()).then_some((region, url))
+        })
+        .collect()
 }
 
 // =============================================================================
 // Database Connection Pool
 // =============================================================================
 
-pub async fn create_connection_pool(config: &DatabaseConfig) -> Result<PgPool, Box<dyn std::error::Error + Send + Sync>> {
+pub async fn create_connection_pool(config: &DatabaseConfig, database_url: &str) -> AppResult<PgPool> {
     info!("🔄 Connecting to database...");
     
     let pool = sqlx::postgres::PgPoolOptions::new()
         .max_connections(config.max_connections)
         .min_connections(config.min_connections)
-        .connect(&config.database_url)
+        .connect(database_url)
         .await?;
     
     info!("✅ Database connection pool created successfully");
     Ok(pool)
 }
 
-pub async fn run_migrations(pool: &PgPool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub async fn run_migrations(pool: &PgPool) -> AppResult<()> {
     info!("🔄 Running database migrations...");
     sqlx::migrate!("./migrations").run(pool).await?;
     info!("✅ Database migrations completed");
     Ok(())
 }
 
+// =============================================================================
+// Regional Database Routing
+// =============================================================================
+
+/// Resolves which database holds a shop's data, for data residency.
+///
+/// Shops listed in the home database's `shop_regions` table live in that
+/// region's database (`DATABASE_URL_EU` for `eu`); every other shop uses the
+/// home `DATABASE_URL`. Only the shop domain and region name are kept in the
+/// home database for assigned shops.
+#[derive(Clone)]
+pub struct DatabaseRouter {
+    home: PgPool,
+    regions: Arc<BTreeMap<String, PgPool>>,
+    // Only positive assignments are cached: a shop assigned by another
+    // instance must never be served from the home database
+    assignments: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl DatabaseRouter {
+    /// Connects to the home and every regional database and migrates them all.
+    pub async fn connect(config: &DatabaseConfig) -> AppResult<Self> {
+        let database_url = config.database_url.as_deref()
+            .ok_or_else(|| AppError::Config("DATABASE_URL is required".to_string()))?;
+        let home = create_connection_pool(config, database_url).await?;
+        run_migrations(&home).await?;
+        
+        let mut regions = BTreeMap::new();
+        for (region, url) in &config.regional_database_urls {
+            info!("🌍 Connecting to {} regional database", region);
+            let pool = create_connection_pool(config, url).await?;
+            run_migrations(&pool).await?;
+            regions.insert(region.clone(), pool);
+        }
+        
+        Ok(Self::from_pools(home, regions))
+    }
+    
+    /// Router for deployments without Postgres. Nothing connects until a
+    /// Postgres-backed feature is used, and then the query fails.
+    pub fn unavailable() -> Self {
+        let home = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy_with(sqlx::postgres::PgConnectOptions::new());
+        Self::from_pools(home, BTreeMap::new())
+    }
+    
+    pub fn from_pools(home: PgPool, regions: BTreeMap<String, PgPool>) -> Self {
+        Self {
+            home,
+            regions: Arc::new(regions),
+            assignments: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+    
+    /// The home database, for data that isn't tied to a shop.
+    pub fn home(&self) -> &PgPool {
+        &self.home
+    }
+    
+    pub fn regions(&self) -> Vec<String> {
+        self.regions.keys().cloned().collect()
+    }
+    
+    /// Home first, then each region, for maintenance that spans every shop.
+    pub fn all_pools(&self) -> Vec<PgPool> {
+        std::iter::once(self.home.clone())
+            .chain(self.regions.values().cloned())
+            .collect()
+    }
+    
+    /// Waits for checked-out connections to be returned, then closes every pool.
+    pub async fn close(&self) {
+        for pool in self.all_pools() {
+            pool.close().await;
+        }
+    }
+    
+    pub async fn region_for(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        if self.regions.is_empty() {
+            return Ok(None);
+        }
+        if let Some(region) = self.assignments.read().unwrap().get(shop_domain) {
+            return Ok(Some(region.clone()));
+        }
+        
+        let row = sqlx::query_as::<_, (String,)>("SELECT region FROM shop_regions WHERE shop_domain = $1")
+            .bind(shop_domain)
+            .fetch_optional(&self.home)
+            .await?;
+        
+        if let Some((ref region,)) = row {
+            self.assignments.write().unwrap().insert(shop_domain.to_string(), region.clone());
+        }
+        Ok(row.map(|(region,)| region))
+    }
+    
+    pub async fn pool_for(&self, shop_domain: &str) -> AppResult<PgPool> {
+        match self.region_for(shop_domain).await? {
+            None => Ok(self.home.clone()),
+            // Never fall back to the home database for a regional shop
+            Some(region) => self.regions.get(&region).cloned().ok_or_else(|| {
+                AppError::Config(format!(
+                    "Shop {} is assigned to region {} but DATABASE_URL_{} is not configured",
+                    shop_domain,
+                    region,
+                    region.to_uppercase()
+                ))
+            }),
+        }
+    }
+    
+    /// Pins a shop to a region. Must happen before the shop has any data,
+    /// since existing rows are not moved between databases.
+    pub async fn assign_region(&self, shop_domain: &str, region: &str) -> AppResult<()> {
+        let region = region.to_lowercase();
+        if !self.regions.contains_key(&region) {
+            return Err(AppError::BadRequest(format!(
+                "Unknown region {}; configured regions: {:?}",
+                region,
+                self.regions()
+            )));
+        }
+        
+        match self.region_for(shop_domain).await? {
+            Some(existing) if existing == region => return Ok(()),
+            Some(existing) => {
+                return Err(AppError::Conflict(format!(
+                    "Shop {} is already assigned to region {}",
+                    shop_domain, existing
+                )));
+            }
+            None => {}
+        }
+        
+        let (has_home_data,) = sqlx::query_as::<_, (bool,)>(
+            "SELECT EXISTS (SELECT 1 FROM shopify_tokens WHERE shop_domain = $1)"
+        )
+        .bind(shop_domain)
+        .fetch_one(&self.home)
+        .await?;
+        if has_home_data {
+            return Err(AppError::Conflict(format!(
+                "Shop {} already has data in the home database and cannot be moved to {}",
+                shop_domain, region
+            )));
+        }
+        
+        sqlx::query("INSERT INTO shop_regions (shop_domain, region) VALUES ($1, $2)")
+            .bind(shop_domain)
+            .bind(&region)
+            .execute(&self.home)
+            .await?;
+        
+        self.assignments.write().unwrap().insert(shop_domain.to_string(), region.clone());
+        info!("🌍 Assigned shop {} to the {} database", shop_domain, region);
+        Ok(())
+    }
+}
+
 // =============================================================================
 // Token Encryption/Decryption
 // =============================================================================
 
+/// Prefix marking ciphertext encrypted with a shop's derived key rather than
+/// the master key.
+const SHOP_KEY_PREFIX: &str = "v2:";
+
+/// Prefix marking envelope ciphertext: `v3:<data key>:<value>`, where a fresh
+/// data key per row is sealed with the shop's derived key and the value is
+/// sealed with the data key.
+const ENVELOPE_PREFIX: &str = "v3:";
+
+/// Fixed HKDF salt; changing it would orphan every per-shop ciphertext.
+const SHOP_KEY_SALT: &[u8] = b"shopify-oauth-rust/shop-key/v1";
+
+/// AES-256-GCM encryption keyed by the master `ENCRYPTION_KEY`.
+///
+/// Shop data goes through `encrypt_for_shop`, which uses a key derived from
+/// the master key with HKDF-SHA256 and the shop domain as context, so one
+/// shop's key decrypts nothing belonging to another shop. Each value gets its
+/// own random data key, wrapped with the shop's key and stored alongside it.
+/// Ciphertext written before per-shop keys existed has no prefix and still
+/// decrypts with the master key until it is rewritten, as does `v2:`
+/// ciphertext sealed directly with the shop key.
 #[derive(Clone)]
 pub struct TokenEncryption {
     cipher: Aes256Gcm,
+    hkdf: Hkdf<Sha256>,
+    key_id: String,
 }
 
 impl TokenEncryption {
-    pub fn new(key: &Secret<String>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    pub fn new(key: &Secret<String>) -> AppResult<Self> {
         let key_bytes = key.expose_secret().as_bytes();
         if key_bytes.len() != 32 {
-            return Err("Encryption key must be exactly 32 bytes".into());
+            return Err(AppError::Encryption("Encryption key must be exactly 32 bytes".to_string()));
         }
         
         let cipher = Aes256Gcm::new_from_slice(key_bytes)
-            .map_err(|e| format!("Failed to create cipher: {}", e))?;
-        Ok(Self { cipher })
-    }
-    
-    pub fn encrypt(&self, plaintext: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-        let ciphertext = self.cipher.encrypt(&nonce, plaintext.as_bytes())
-            .map_err(|e| format!("Encryption failed: {}", e))?;
+            .map_err(|e| AppError::Encryption(format!("Failed to create cipher: {}", e)))?;
+        let hkdf = Hkdf::<sha2::Sha256>::new(Some(SHOP_KEY_SALT), key_bytes);
         
-        // Combine nonce + ciphertext and encode as base64
-        let mut combined = nonce.to_vec();
-        combined.extend_from_slice(&ciphertext);
+        // Short fingerprint so stored ciphertext records which key produced it
+        let digest = <sha2::Sha256 as sha2::Digest>::digest(key_bytes);
+        let key_id = hex::encode(&digest[..8]);
         
-        Ok(general_purpose::STANDARD.encode(combined))
+        Ok(Self { cipher, hkdf, key_id })
+    }
+    
+    pub fn key_id(&self) -> &str {
+        &self.key_id
     }
     
-    pub fn decrypt(&self, encrypted: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let combined = general_purpose::STANDARD.decode(encrypted)?;
+    fn shop_cipher(&self, shop_domain: &str) -> AppResult<Aes256Gcm> {
+        let mut key = [0u8; 32];
+        self.hkdf
+            .expand(format!("shop:{}", shop_domain.to_ascii_lowercase()).as_bytes(), &mut key)
+            .map_err(|e| AppError::Encryption(format!("Failed to derive shop key: {}", e)))?;
         
-        if combined.len() < 12 {
-            return Err("Invalid encrypted data".into());
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|e| AppError::Encryption(format!("Failed to create cipher: {}", e)));
+        key.fill(0);
+        cipher
+    }
+    
+    /// Master-key encryption, the format written before per-shop keys.
+    #[allow(dead_code)]
+    pub fn encrypt(&self, plaintext: &str) -> AppResult<String> {
+        seal(&self.cipher, plaintext)
+    }
+    
+    pub fn decrypt(&self, encrypted: &str) -> AppResult<String> {
+        open(&self.cipher, encrypted)
+    }
+    
+    /// Encrypts with a fresh data key, itself wrapped with the key derived
+    /// for `shop_domain`.
+    pub fn encrypt_for_shop(&self, shop_domain: &str, plaintext: &str) -> AppResult<String> {
+        let data_key = Aes256Gcm::generate_key(&mut OsRng);
+        let wrapped_key = seal_bytes(&self.shop_cipher(shop_domain)?, &data_key)?;
+        let sealed = seal(&Aes256Gcm::new(&data_key), plaintext)?;
+        Ok(format!("{}{}:{}", ENVELOPE_PREFIX, wrapped_key, sealed))
+    }
+    
+    /// Decrypts per-shop ciphertext for `shop_domain`, or legacy ciphertext
+    /// written with the master key.
+    pub fn decrypt_for_shop(&self, shop_domain: &str, encrypted: &str) -> AppResult<String> {
+        if let Some(envelope) = encrypted.strip_prefix(ENVELOPE_PREFIX) {
+            let (wrapped_key, sealed) = envelope.split_once(':')
+                .ok_or_else(|| AppError::Encryption("Invalid encrypted data".to_string()))?;
+            let mut data_key = open_bytes(&self.shop_cipher(shop_domain)?, wrapped_key)?;
+            let cipher = Aes256Gcm::new_from_slice(&data_key)
+                .map_err(|e| AppError::Encryption(format!("Invalid data key: {}", e)));
+            data_key.fill(0);
+            return open(&cipher?, sealed);
+        }
+        match encrypted.strip_prefix(SHOP_KEY_PREFIX) {
+            Some(encrypted) => open(&self.shop_cipher(shop_domain)?, encrypted),
+            None => self.decrypt(encrypted),
         }
-        
-        let (nonce_bytes, ciphertext) = combined.split_at(12);
-        let nonce = aes_gcm::Nonce::from_slice(nonce_bytes);
-        
-        let plaintext = self.cipher.decrypt(nonce, ciphertext)
-            .map_err(|e| format!("Decryption failed: {}", e))?;
-        Ok(String::from_utf8(plaintext)?)
     }
 }
 
+fn seal(cipher: &Aes256Gcm, plaintext: &str) -> AppResult<String> {
+    seal_bytes(cipher, plaintext.as_bytes())
+}
+
+fn seal_bytes(cipher: &Aes256Gcm, plaintext: &[u8]) -> AppResult<String> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, plaintext)
+        .map_err(|e| AppError::Encryption(format!("Encryption failed: {}", e)))?;
+    
+    // Combine nonce + ciphertext and encode as base64
+    let mut combined = nonce.to_vec();
+    combined.extend_from_slice(&ciphertext);
+    
+    Ok(general_purpose::STANDARD.encode(combined))
+}
+
+fn open(cipher: &Aes256Gcm, encrypted: &str) -> AppResult<String> {
+    String::from_utf8(open_bytes(cipher, encrypted)?)
+        .map_err(|e| AppError::Encryption(format!("Decrypted token is not UTF-8: {}", e)))
+}
+
+fn open_bytes(cipher: &Aes256Gcm, encrypted: &str) -> AppResult<Vec<u8>> {
+    let combined = general_purpose::STANDARD.decode(encrypted)
+        .map_err(|e| AppError::Encryption(format!("Invalid encrypted data: {}", e)))?;
+    
+    if combined.len() < 12 {
+        return Err(AppError::Encryption("Invalid encrypted data".to_string()));
+    }
+    
+    let (nonce_bytes, ciphertext) = combined.split_at(12);
+    let nonce = aes_gcm::Nonce::from_slice(nonce_bytes);
+    
+    cipher.decrypt(nonce, ciphertext)
+        .map_err(|e| AppError::Encryption(format!("Decryption failed: {}", e)))
+}
+
 // =============================================================================
 // Database Operations for Tokens
 // =============================================================================
 
 #[derive(Clone)]
-pub struct TokenStore {
-    pool: PgPool,
+pub struct PgTokenStore {
+    db: DatabaseRouter,
     encryption: TokenEncryption,
 }
 
-impl TokenStore {
-    pub fn new(pool: PgPool, encryption_key: &Secret<String>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+impl PgTokenStore {
+    pub fn new(db: DatabaseRouter, encryption_key: &Secret<String>) -> AppResult<Self> {
         let encryption = TokenEncryption::new(encryption_key)?;
-        Ok(Self { pool, encryption })
+        Ok(Self { db, encryption })
     }
-    
-    pub async fn store_token(
+}
+
+#[async_trait::async_trait]
+impl TokenStore for PgTokenStore {
+    async fn store_token(
         &self,
         shop_domain: &str,
         access_token: &str,
         scope: &str,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let encrypted_token = self.encryption.encrypt(access_token)?;
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        let encrypted_token = self.encryption.encrypt_for_shop(shop_domain, access_token)?;
         
         sqlx::query(
             r#"
-            INSERT INTO shopify_tokens (shop_domain, encrypted_access_token, scope)
-            VALUES ($1, $2, $3)
+            INSERT INTO shopify_tokens (shop_domain, encrypted_access_token, scope, expires_at)
+            VALUES ($1, $2, $3, $4)
             ON CONFLICT (shop_domain)
             DO UPDATE SET
                 encrypted_access_token = EXCLUDED.encrypted_access_token,
                 scope = EXCLUDED.scope,
+                expires_at = EXCLUDED.expires_at,
                 updated_at = NOW()
             "#,
         )
         .bind(shop_domain)
         .bind(encrypted_token)
         .bind(scope)
-        .execute(&self.pool)
+        .bind(expires_at)
+        .execute(&self.db.pool_for(shop_domain).await?)
         .await?;
         
         info!("✅ Token stored for shop: {}", shop_domain);
         Ok(())
     }
     
-    pub async fn get_token(&self, shop_domain: &str) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    async fn get_token(&self, shop_domain: &str) -> AppResult<Option<String>> {
         let row = sqlx::query_as::<_, (String,)>(
-            "SELECT encrypted_access_token FROM shopify_tokens WHERE shop_domain = $1"
+            "SELECT encrypted_access_token FROM shopify_tokens
+             WHERE shop_domain = $1 AND (expires_at IS NULL OR expires_at > NOW())"
         )
         .bind(shop_domain)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
         .await?;
         
         match row {
             Some((encrypted_token,)) => {
-                let decrypted_token = self.encryption.decrypt(&encrypted_token)?;
+                let decrypted_token = self.encryption.decrypt_for_shop(shop_domain, &encrypted_token)?;
                 Ok(Some(decrypted_token))
             }
             None => Ok(None),
         }
     }
     
-    pub async fn delete_token(&self, shop_domain: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    async fn get_scope(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        let row = sqlx::query_as::<_, (String,)>(
+            "SELECT scope FROM shopify_tokens
+             WHERE shop_domain = $1 AND (expires_at IS NULL OR expires_at > NOW())"
+        )
+        .bind(shop_domain)
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(row.map(|(scope,)| scope))
+    }
+    
+    async fn get_expiry(&self, shop_domain: &str) -> AppResult<Option<DateTime<Utc>>> {
+        let row = sqlx::query_as::<_, (Option<DateTime<Utc>>,)>(
+            "SELECT expires_at FROM shopify_tokens WHERE shop_domain = $1"
+        )
+        .bind(shop_domain)
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(row.and_then(|(expires_at,)| expires_at))
+    }
+    
+    async fn delete_token(&self, shop_domain: &str) -> AppResult<bool> {
         let result = sqlx::query(
             "DELETE FROM shopify_tokens WHERE shop_domain = $1"
         )
         .bind(shop_domain)
-        .execute(&self.pool)
+        .execute(&self.db.pool_for(shop_domain).await?)
         .await?;
         
         Ok(result.rows_affected() > 0)
     }
     
-    pub async fn list_shops(&self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
-        let rows = sqlx::query_as::<_, (String,)>(
-            "SELECT shop_domain FROM shopify_tokens ORDER BY updated_at DESC"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+    async fn delete_expired_tokens(&self) -> AppResult<u64> {
+        let mut deleted = 0;
+        for pool in self.db.all_pools() {
+            deleted += sqlx::query("DELETE FROM shopify_tokens WHERE expires_at <= NOW()")
+                .execute(&pool)
+                .await?
+                .rows_affected();
+        }
+        Ok(deleted)
+    }
+    
+    /// Rewrites tokens still encrypted with the master key under their
+    /// shop's derived key, in every database.
+    async fn rekey_legacy_tokens(&self) -> AppResult<u64> {
+        let mut rekeyed = 0;
+        for pool in self.db.all_pools() {
+            let legacy = sqlx::query_as::<_, (String, String)>(
+                "SELECT shop_domain, encrypted_access_token FROM shopify_tokens WHERE encrypted_access_token NOT LIKE $1"
+            )
+            .bind(format!("{}%", ENVELOPE_PREFIX))
+            .fetch_all(&pool)
+            .await?;
+            
+            for (shop_domain, encrypted_token) in legacy {
+                let token = match self.encryption.decrypt_for_shop(&shop_domain, &encrypted_token) {
+                    Ok(token) => token,
+                    Err(e) => {
+                        warn!("Cannot re-key token for shop {}: {}", shop_domain, e);
+                        continue;
+                    }
+                };
+                
+                // Compare against the old value so a token refreshed meanwhile isn't overwritten
+                let result = sqlx::query(
+                    "UPDATE shopify_tokens SET encrypted_access_token = $2 WHERE shop_domain = $1 AND encrypted_access_token = $3"
+                )
+                .bind(&shop_domain)
+                .bind(self.encryption.encrypt_for_shop(&shop_domain, &token)?)
+                .bind(&encrypted_token)
+                .execute(&pool)
+                .await?;
+                rekeyed += result.rows_affected();
+            }
+        }
+        
+        if rekeyed > 0 {
+            info!("🔑 Moved {} access tokens onto per-token data keys", rekeyed);
+        }
+        Ok(rekeyed)
+    }
+    
+    async fn list_shops(&self) -> AppResult<Vec<InstalledShop>> {
+        let mut shops = Vec::new();
+        for pool in self.db.all_pools() {
+            let rows = sqlx::query_as::<_, (String, String, DateTime<Utc>, DateTime<Utc>, Option<DateTime<Utc>>)>(
+                r#"
+                SELECT shop_domain, scope, COALESCE(created_at, NOW()), COALESCE(updated_at, created_at, NOW()), expires_at
+                FROM shopify_tokens
+                WHERE expires_at IS NULL OR expires_at > NOW()
+                "#,
+            )
+            .fetch_all(&pool)
+            .await?;
+            
+            shops.extend(rows.into_iter().map(|(shop_domain, scope, installed_at, updated_at, expires_at)| InstalledShop {
+                shop_domain,
+                scope,
+                installed_at,
+                updated_at,
+                expires_at,
+            }));
+        }
         
-        Ok(rows.into_iter().map(|(shop_domain,)| shop_domain).collect())
+        shops.sort_by_key(|shop| std::cmp::Reverse(shop.updated_at));
+        Ok(shops)
     }
 }
 
@@ -232,26 +1102,35 @@ impl TokenStore {
 // =============================================================================
 
 #[derive(Clone)]
-pub struct StateStore {
+pub struct PgStateStore {
     pool: PgPool,
 }
 
-impl StateStore {
+impl PgStateStore {
     pub fn new(pool: PgPool) -> Self {
         Self { pool }
     }
-    
-    pub async fn store_state(&self, state_token: &str, ttl_seconds: i64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+}
+
+#[async_trait::async_trait]
+impl StateStore for PgStateStore {
+    async fn store_state(&self, state_token: &str, ttl_seconds: i64, record: &OAuthStateRecord) -> AppResult<()> {
         let expires_at = Utc::now() + chrono::Duration::seconds(ttl_seconds);
         
-        sqlx::query(
+        sqlx::query(&format!(
             r#"
-            INSERT INTO oauth_states (state_token, expires_at)
-            VALUES ($1, $2)
+            INSERT INTO oauth_states (state_token, expires_at, {})
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
             "#,
-        )
+            STATE_RECORD_COLUMNS
+        ))
         .bind(state_token)
         .bind(expires_at)
+        .bind(&record.shop)
+        .bind(&record.scopes)
+        .bind(&record.return_to)
+        .bind(&record.client_ip)
+        .bind(&record.user_agent)
         .execute(&self.pool)
         .await?;
         
@@ -259,30 +1138,61 @@ impl StateStore {
         Ok(())
     }
     
-    pub async fn validate_and_remove_state(&self, state_token: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
-        let result = sqlx::query(
+    async fn claim_state(&self, state_token: &str, code_hash: &str) -> AppResult<StateClaim> {
+        let claimed = sqlx::query_as::<_, OAuthStateRow>(&format!(
             r#"
-            DELETE FROM oauth_states 
-            WHERE state_token = $1 AND expires_at > NOW()
+            UPDATE oauth_states SET code_hash = $2
+            WHERE state_token = $1 AND expires_at > NOW() AND code_hash IS NULL
+            RETURNING {}
             "#,
-        )
+            STATE_RECORD_COLUMNS
+        ))
         .bind(state_token)
-        .execute(&self.pool)
+        .bind(code_hash)
+        .fetch_optional(&self.pool)
         .await?;
         
-        let is_valid = result.rows_affected() > 0;
+        if let Some(row) = claimed {
+            info!("✅ CSRF state validated and claimed: {}", &state_token[..8]);
+            return Ok(StateClaim::Claimed(row.into()));
+        }
+        
+        let existing = sqlx::query_as::<_, OAuthStateRow>(&format!(
+            "SELECT {}, {} FROM oauth_states WHERE state_token = $1 AND expires_at > NOW()",
+            STATE_RECORD_COLUMNS, STATE_CLAIM_COLUMNS
+        ))
+        .bind(state_token)
+        .fetch_optional(&self.pool)
+        .await?;
         
-        if is_valid {
-            info!("✅ CSRF state validated and removed: {}", &state_token[..8]);
-        } else {
+        let claim = existing.map_or(StateClaim::Invalid, |row| row.into_claim(code_hash));
+        if claim == StateClaim::Invalid {
             warn!("⚠️ CSRF state invalid or expired: {}", &state_token[..8]);
         }
+        Ok(claim)
+    }
+    
+    async fn complete_state(&self, state_token: &str, exchange: &CompletedExchange) -> AppResult<()> {
+        sqlx::query("UPDATE oauth_states SET exchange_result = $2 WHERE state_token = $1")
+            .bind(state_token)
+            .bind(exchange_json(exchange))
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    async fn release_state(&self, state_token: &str) -> AppResult<()> {
+        sqlx::query("DELETE FROM oauth_states WHERE state_token = $1")
+            .bind(state_token)
+            .execute(&self.pool)
+            .await?;
         
-        Ok(is_valid)
+        Ok(())
     }
     
-    pub async fn cleanup_expired_states(&self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
-        let result = sqlx::query_as::<_, (Option<i64>,)>(
+    async fn cleanup_expired_states(&self) -> AppResult<u64> {
+        let result = sqlx::query_as::<_, (Option<i32>,)>(
             "SELECT cleanup_expired_oauth_states() as deleted_count"
         )
         .fetch_one(&self.pool)
@@ -297,3 +1207,2809 @@ impl StateStore {
         Ok(deleted_count)
     }
 }
+
+// =============================================================================
+// Database Operations for Webhook Events
+// =============================================================================
+
+#[derive(Clone)]
+pub struct WebhookEventStore {
+    db: DatabaseRouter,
+}
+
+impl WebhookEventStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    pub async fn record_event(
+        &self,
+        shop_domain: &str,
+        topic: &str,
+        resource_id: Option<i64>,
+        webhook_id: Option<&str>,
+        payload: &serde_json::Value,
+    ) -> AppResult<Uuid> {
+        let (id,) = sqlx::query_as::<_, (Uuid,)>(
+            r#"
+            INSERT INTO webhook_events (shop_domain, topic, resource_id, webhook_id, payload)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(topic)
+        .bind(resource_id)
+        .bind(webhook_id)
+        .bind(payload)
+        .fetch_one(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(id)
+    }
+    
+    /// Stores a delivery exactly as received, before it is verified or processed.
+    pub async fn capture_delivery(
+        &self,
+        shop_domain: &str,
+        topic: &str,
+        webhook_id: Option<&str>,
+        headers: &serde_json::Value,
+        raw_body: &[u8],
+        verified: bool,
+    ) -> AppResult<Uuid> {
+        let payload = serde_json::from_slice::<serde_json::Value>(raw_body).ok();
+        
+        let (id,) = sqlx::query_as::<_, (Uuid,)>(
+            r#"
+            INSERT INTO webhook_events (shop_domain, topic, webhook_id, payload, raw_body, headers, verified)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(topic)
+        .bind(webhook_id)
+        .bind(payload)
+        .bind(raw_body)
+        .bind(headers)
+        .bind(verified)
+        .fetch_one(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(id)
+    }
+    
+    /// Records one processing attempt for a captured delivery.
+    pub async fn mark_processed(
+        &self,
+        shop_domain: &str,
+        id: Uuid,
+        resource_id: Option<i64>,
+        error: Option<&str>,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_events
+            SET processing_attempts = processing_attempts + 1,
+                resource_id = COALESCE($2, resource_id),
+                processed_at = CASE WHEN $3::TEXT IS NULL THEN NOW() ELSE processed_at END,
+                last_error = $3
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(resource_id)
+        .bind(error)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Looks the event up in every database, since only its ID is known.
+    pub async fn get_event(&self, id: Uuid) -> AppResult<Option<WebhookEvent>> {
+        for pool in self.db.all_pools() {
+            let event = sqlx::query_as::<_, WebhookEvent>(
+                "SELECT * FROM webhook_events WHERE id = $1"
+            )
+            .bind(id)
+            .fetch_optional(&pool)
+            .await?;
+            
+            if event.is_some() {
+                return Ok(event);
+            }
+        }
+        
+        Ok(None)
+    }
+    
+    pub async fn events_for_resource(
+        &self,
+        shop_domain: &str,
+        resource_id: i64,
+    ) -> AppResult<Vec<WebhookEvent>> {
+        let events = sqlx::query_as::<_, WebhookEvent>(
+            r#"
+            SELECT * FROM webhook_events
+            WHERE shop_domain = $1 AND resource_id = $2 AND verified
+            ORDER BY received_at ASC
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(resource_id)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(events)
+    }
+    
+    pub async fn purge_older_than(&self, retention_days: i64) -> AppResult<u64> {
+        let cutoff = Utc::now() - chrono::Duration::days(retention_days);
+        
+        let mut deleted_count = 0;
+        for pool in self.db.all_pools() {
+            let result = sqlx::query("DELETE FROM webhook_events WHERE received_at < $1")
+                .bind(cutoff)
+                .execute(&pool)
+                .await?;
+            deleted_count += result.rows_affected();
+        }
+        
+        if deleted_count > 0 {
+            info!("🧹 Purged {} webhook events older than {} days", deleted_count, retention_days);
+        }
+        
+        Ok(deleted_count)
+    }
+}
+
+// =============================================================================
+// Database Operations for the Customer Mirror
+// =============================================================================
+
+#[derive(Clone)]
+pub struct CustomerMirrorStore {
+    db: DatabaseRouter,
+}
+
+impl CustomerMirrorStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    /// Refreshes mirrored customers from Shopify, leaving merge state untouched.
+    /// Writes fetched customers to the mirror, skipping any whose content
+    /// hash matches the stored row.
+    pub async fn upsert_customers(
+        &self,
+        shop_domain: &str,
+        customers: &[MirroredCustomer],
+    ) -> AppResult<MirrorSyncStats> {
+        let pool = self.db.pool_for(shop_domain).await?;
+        let ids: Vec<i64> = customers.iter().map(|customer| customer.customer_id).collect();
+        let stored: HashMap<i64, String> = sqlx::query_as::<_, (i64, String)>(
+            "SELECT customer_id, content_hash FROM customers WHERE shop_domain = $1 AND customer_id = ANY($2) AND content_hash IS NOT NULL"
+        )
+        .bind(shop_domain)
+        .bind(&ids)
+        .fetch_all(&pool)
+        .await?
+        .into_iter()
+        .collect();
+        
+        let changed: Vec<(&MirroredCustomer, String)> = customers
+            .iter()
+            .map(|customer| (customer, customer.content_hash()))
+            .filter(|(customer, hash)| stored.get(&customer.customer_id) != Some(hash))
+            .collect();
+        let stats = MirrorSyncStats {
+            fetched: customers.len(),
+            changed: changed.len(),
+            unchanged: customers.len() - changed.len(),
+        };
+        
+        let mut tx = pool.begin().await?;
+        
+        for (customer, hash) in changed {
+            // The WHERE keeps a concurrent sync of the same data from rewriting
+            // the row, and a late webhook with an older copy from rolling it back
+            sqlx::query(
+                r#"
+                INSERT INTO customers (shop_domain, customer_id, email, phone, first_name, last_name, orders_count, tags, note, created_at, updated_at, content_hash)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                ON CONFLICT (shop_domain, customer_id)
+                DO UPDATE SET
+                    email = EXCLUDED.email,
+                    phone = EXCLUDED.phone,
+                    first_name = EXCLUDED.first_name,
+                    last_name = EXCLUDED.last_name,
+                    orders_count = EXCLUDED.orders_count,
+                    tags = EXCLUDED.tags,
+                    note = EXCLUDED.note,
+                    created_at = EXCLUDED.created_at,
+                    updated_at = EXCLUDED.updated_at,
+                    content_hash = EXCLUDED.content_hash,
+                    synced_at = NOW()
+                WHERE customers.content_hash IS DISTINCT FROM EXCLUDED.content_hash
+                  AND (customers.updated_at IS NULL OR EXCLUDED.updated_at IS NULL OR EXCLUDED.updated_at >= customers.updated_at)
+                "#,
+            )
+            .bind(shop_domain)
+            .bind(customer.customer_id)
+            .bind(&customer.email)
+            .bind(&customer.phone)
+            .bind(&customer.first_name)
+            .bind(&customer.last_name)
+            .bind(customer.orders_count)
+            .bind(&customer.tags)
+            .bind(&customer.note)
+            .bind(customer.created_at)
+            .bind(customer.updated_at)
+            .bind(hash)
+            .execute(&mut *tx)
+            .await?;
+        }
+        
+        tx.commit().await?;
+        info!(
+            "🪞 Mirrored {} customers for shop {}: {} changed, {} unchanged",
+            stats.fetched, shop_domain, stats.changed, stats.unchanged
+        );
+        Ok(stats)
+    }
+    
+    /// Customers that have not been merged into another record.
+    pub async fn active_customers(
+        &self,
+        shop_domain: &str,
+    ) -> AppResult<Vec<MirroredCustomer>> {
+        let customers = sqlx::query_as::<_, MirroredCustomer>(
+            r#"
+            SELECT customer_id, email, phone, first_name, last_name, orders_count, tags, note, created_at, updated_at
+            FROM customers
+            WHERE shop_domain = $1 AND merged_into IS NULL
+            ORDER BY customer_id ASC
+            "#,
+        )
+        .bind(shop_domain)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(customers)
+    }
+    
+    /// Marks duplicates as merged into the primary and records the audit row
+    /// in one transaction. Fails if any customer is unknown or already merged.
+    pub async fn merge_customers(
+        &self,
+        shop_domain: &str,
+        primary_id: i64,
+        duplicate_ids: &[i64],
+        reason: Option<&str>,
+        shopify_updated: bool,
+        shopify_errors: &serde_json::Value,
+    ) -> AppResult<CustomerMerge> {
+        let mut tx = self.db.pool_for(shop_domain).await?.begin().await?;
+        
+        let (active,) = sqlx::query_as::<_, (i64,)>(
+            r#"
+            SELECT COUNT(*) FROM customers
+            WHERE shop_domain = $1 AND customer_id = ANY($2) AND merged_into IS NULL
+            "#,
+        )
+        .bind(shop_domain)
+        .bind([&[primary_id], duplicate_ids].concat())
+        .fetch_one(&mut *tx)
+        .await?;
+        
+        if active as usize != duplicate_ids.len() + 1 {
+            return Err(AppError::Conflict("Customers must exist in the mirror and not already be merged".to_string()));
+        }
+        
+        sqlx::query(
+            r#"
+            UPDATE customers
+            SET merged_into = $2
+            WHERE shop_domain = $1 AND customer_id = ANY($3)
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(primary_id)
+        .bind(duplicate_ids)
+        .execute(&mut *tx)
+        .await?;
+        
+        let merge = sqlx::query_as::<_, CustomerMerge>(
+            r#"
+            INSERT INTO customer_merges (shop_domain, primary_customer_id, merged_customer_ids, reason, shopify_updated, shopify_errors)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(primary_id)
+        .bind(duplicate_ids)
+        .bind(reason)
+        .bind(shopify_updated)
+        .bind(shopify_errors)
+        .fetch_one(&mut *tx)
+        .await?;
+        
+        tx.commit().await?;
+        info!("🔗 Merged customers {:?} into {} for shop: {}", duplicate_ids, primary_id, shop_domain);
+        Ok(merge)
+    }
+}
+
+impl CustomerMirrorStore {
+    /// Removes a customer deleted in Shopify. Merged records stay, since the
+    /// merge audit refers to them.
+    pub async fn delete_customer(&self, shop_domain: &str, customer_id: i64) -> AppResult<bool> {
+        let result = sqlx::query(
+            "DELETE FROM customers WHERE shop_domain = $1 AND customer_id = $2 AND merged_into IS NULL"
+        )
+        .bind(shop_domain)
+        .bind(customer_id)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    /// Removes unmerged customers missing from a reconciliation pass, except
+    /// those a webhook wrote while it ran.
+    pub async fn delete_customers_except(
+        &self,
+        shop_domain: &str,
+        seen: &[i64],
+        pass_started_at: DateTime<Utc>,
+    ) -> AppResult<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM customers
+            WHERE shop_domain = $1 AND customer_id <> ALL($2) AND synced_at < $3 AND merged_into IS NULL
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(seen)
+        .bind(pass_started_at)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(result.rows_affected())
+    }
+    
+    /// One page of unmerged customers matching `query`, best matches first,
+    /// with the total number of matches.
+    pub async fn search_customers(
+        &self,
+        shop_domain: &str,
+        query: &CustomerQuery,
+    ) -> AppResult<(Vec<MirroredCustomer>, i64)> {
+        #[derive(sqlx::FromRow)]
+        struct SearchRow {
+            #[sqlx(flatten)]
+            customer: MirroredCustomer,
+            total: i64,
+        }
+        
+        let rows = sqlx::query_as::<_, SearchRow>(
+            r#"
+            SELECT customer_id, email, phone, first_name, last_name, orders_count, tags, note, created_at, updated_at,
+                COUNT(*) OVER () AS total
+            FROM customers
+            WHERE shop_domain = $1
+              AND merged_into IS NULL
+              AND ($2::text IS NULL OR search_vector @@ to_tsquery('simple', $2))
+              AND ($3::text IS NULL OR search_vector @@ to_tsquery('simple', $3))
+              AND ($4::text IS NULL OR LOWER(email) = LOWER($4))
+              AND ($5::text IS NULL OR phone_digits LIKE '%' || $5)
+              AND ($6::text IS NULL OR LOWER($6) = ANY(string_to_array(LOWER(REPLACE(tags, ', ', ',')), ',')))
+            ORDER BY
+                CASE WHEN $2::text IS NULL THEN 0 ELSE ts_rank(search_vector, to_tsquery('simple', $2)) END DESC,
+                customer_id DESC
+            LIMIT $7 OFFSET $8
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(&query.text)
+        .bind(&query.name)
+        .bind(&query.email)
+        .bind(&query.phone_digits)
+        .bind(&query.tag)
+        .bind(query.limit)
+        .bind(query.offset)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        let total = rows.first().map(|row| row.total).unwrap_or_default();
+        Ok((rows.into_iter().map(|row| row.customer).collect(), total))
+    }
+    
+    pub async fn sync_state(&self, shop_domain: &str) -> AppResult<Option<CustomerIndexSyncState>> {
+        let state = sqlx::query_as::<_, CustomerIndexSyncState>(
+            "SELECT shop_domain, reconciled_at, last_synced_at, last_error FROM customer_index_sync_state WHERE shop_domain = $1"
+        )
+        .bind(shop_domain)
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(state)
+    }
+    
+    /// Records a reconciliation pass; a successful one makes the index searchable.
+    pub async fn record_sync(&self, shop_domain: &str, error: Option<&str>) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO customer_index_sync_state (shop_domain, reconciled_at, last_synced_at, last_error)
+            VALUES ($1, CASE WHEN $2::text IS NULL THEN NOW() END, NOW(), $2)
+            ON CONFLICT (shop_domain)
+            DO UPDATE SET
+                reconciled_at = COALESCE(EXCLUDED.reconciled_at, customer_index_sync_state.reconciled_at),
+                last_synced_at = NOW(),
+                last_error = EXCLUDED.last_error
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(error)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Database Operations for Idempotency Keys
+// =============================================================================
+
+/// A write request sent with an `Idempotency-Key`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdempotentRequest {
+    pub shop_domain: String,
+    /// The API client that sent it, so clients can't see each other's keys
+    pub client: String,
+    pub key: String,
+    pub method: String,
+    pub path: String,
+    /// SHA-256 of the body, so a key reused for a different request is caught
+    pub request_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct StoredResponse {
+    pub status_code: i32,
+    pub content_type: Option<String>,
+    pub response_body: Vec<u8>,
+}
+
+/// What claiming a key found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdempotencyClaim {
+    /// First use of the key; run the request and then `complete` or `release` it
+    Claimed,
+    /// The same request is still being handled
+    InProgress,
+    /// The same request already finished with this response
+    Completed(StoredResponse),
+    /// The key was used for a different request
+    Mismatch,
+}
+
+#[derive(Clone)]
+pub struct IdempotencyStore {
+    db: DatabaseRouter,
+}
+
+impl IdempotencyStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    /// Claims `request.key`. An in-progress claim made before `stale_before`
+    /// is taken over, as its request can no longer be running.
+    pub async fn claim(&self, request: &IdempotentRequest, stale_before: DateTime<Utc>) -> AppResult<IdempotencyClaim> {
+        let pool = self.db.pool_for(&request.shop_domain).await?;
+        
+        let claimed = sqlx::query(
+            r#"
+            INSERT INTO idempotency_keys (shop_domain, client, idempotency_key, method, path, request_hash)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (shop_domain, client, idempotency_key)
+            DO UPDATE SET created_at = NOW()
+            WHERE idempotency_keys.status_code IS NULL
+              AND idempotency_keys.created_at < $7
+              AND idempotency_keys.method = EXCLUDED.method
+              AND idempotency_keys.path = EXCLUDED.path
+              AND idempotency_keys.request_hash = EXCLUDED.request_hash
+            "#,
+        )
+        .bind(&request.shop_domain)
+        .bind(&request.client)
+        .bind(&request.key)
+        .bind(&request.method)
+        .bind(&request.path)
+        .bind(&request.request_hash)
+        .bind(stale_before)
+        .execute(&pool)
+        .await?
+        .rows_affected();
+        if claimed > 0 {
+            return Ok(IdempotencyClaim::Claimed);
+        }
+        
+        let row = sqlx::query_as::<_, (String, String, String, Option<i32>, Option<String>, Option<Vec<u8>>)>(
+            r#"
+            SELECT method, path, request_hash, status_code, content_type, response_body
+            FROM idempotency_keys
+            WHERE shop_domain = $1 AND client = $2 AND idempotency_key = $3
+            "#,
+        )
+        .bind(&request.shop_domain)
+        .bind(&request.client)
+        .bind(&request.key)
+        .fetch_optional(&pool)
+        .await?;
+        
+        Ok(match row {
+            // Released between the two statements; the client can retry
+            None => IdempotencyClaim::InProgress,
+            Some((method, path, hash, _, _, _))
+                if method != request.method || path != request.path || hash != request.request_hash =>
+            {
+                IdempotencyClaim::Mismatch
+            }
+            Some((_, _, _, Some(status_code), content_type, response_body)) => IdempotencyClaim::Completed(StoredResponse {
+                status_code,
+                content_type,
+                response_body: response_body.unwrap_or_default(),
+            }),
+            Some(_) => IdempotencyClaim::InProgress,
+        })
+    }
+    
+    /// Stores the response to a claimed request for replay.
+    pub async fn complete(&self, request: &IdempotentRequest, response: &StoredResponse) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE idempotency_keys
+            SET status_code = $4, content_type = $5, response_body = $6, completed_at = NOW()
+            WHERE shop_domain = $1 AND client = $2 AND idempotency_key = $3
+            "#,
+        )
+        .bind(&request.shop_domain)
+        .bind(&request.client)
+        .bind(&request.key)
+        .bind(response.status_code)
+        .bind(&response.content_type)
+        .bind(&response.response_body)
+        .execute(&self.db.pool_for(&request.shop_domain).await?)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Gives up a claim without a response, so the key can be used again.
+    pub async fn release(&self, request: &IdempotentRequest) -> AppResult<()> {
+        sqlx::query(
+            "DELETE FROM idempotency_keys WHERE shop_domain = $1 AND client = $2 AND idempotency_key = $3 AND status_code IS NULL"
+        )
+        .bind(&request.shop_domain)
+        .bind(&request.client)
+        .bind(&request.key)
+        .execute(&self.db.pool_for(&request.shop_domain).await?)
+        .await?;
+        
+        Ok(())
+    }
+    
+    pub async fn purge_older_than(&self, cutoff: DateTime<Utc>) -> AppResult<u64> {
+        let mut deleted_count = 0;
+        for pool in self.db.all_pools() {
+            let result = sqlx::query("DELETE FROM idempotency_keys WHERE created_at < $1")
+                .bind(cutoff)
+                .execute(&pool)
+                .await?;
+            deleted_count += result.rows_affected();
+        }
+        
+        Ok(deleted_count)
+    }
+}
+
+// =============================================================================
+// Database Operations for Personal Data
+// =============================================================================
+
+/// What `customers/redact` asks to erase: the customer, found by ID or
+/// email, and orders Shopify lists for them.
+#[derive(Debug, Clone, Default)]
+pub struct CustomerRedaction {
+    pub customer_id: i64,
+    pub email: Option<String>,
+    pub order_ids: Vec<i64>,
+}
+
+/// Rows scrubbed of personal data by one redaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RedactionCounts {
+    pub webhook_events: u64,
+    pub orders: u64,
+    pub customers: u64,
+    pub recovery_messages: u64,
+    pub emails: u64,
+    pub jobs: u64,
+    /// List exports deleted from disk
+    pub exports: u64,
+}
+
+/// Stands in for the address of a scrubbed recovery message.
+const REDACTED_RECIPIENT: &str = "redacted";
+
+const REDACTION_BATCH_SIZE: i64 = 500;
+
+/// Job kind whose payload is a whole webhook delivery.
+const WEBHOOK_JOB_KIND: &str = "webhook";
+
+// Drops the personal fields from each delivery's payload and its raw body
+// with them, since that can't be scrubbed field by field.
+async fn scrub_webhook_events(
+    conn: &mut sqlx::PgConnection,
+    events: Vec<(Uuid, Option<serde_json::Value>)>,
+) -> AppResult<u64> {
+    let count = events.len() as u64;
+    for (id, mut payload) in events {
+        if let Some(ref mut payload) = payload {
+            redact_pii(payload);
+        }
+        sqlx::query("UPDATE webhook_events SET payload = $2, raw_body = NULL, pii_redacted_at = NOW() WHERE id = $1")
+            .bind(id)
+            .bind(payload)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(count)
+}
+
+async fn scrub_orders(
+    conn: &mut sqlx::PgConnection,
+    orders: Vec<(String, i64, serde_json::Value)>,
+) -> AppResult<u64> {
+    let count = orders.len() as u64;
+    for (shop_domain, order_id, mut data) in orders {
+        redact_pii(&mut data);
+        sqlx::query(
+            "UPDATE orders SET email = NULL, data = $3, pii_redacted_at = NOW() WHERE shop_domain = $1 AND order_id = $2"
+        )
+        .bind(shop_domain)
+        .bind(order_id)
+        .bind(data)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(count)
+}
+
+#[derive(Clone)]
+pub struct PersonalDataStore {
+    db: DatabaseRouter,
+    export_dir: Option<PathBuf>,
+}
+
+impl PersonalDataStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db, export_dir: None }
+    }
+
+    /// Also deletes list exports written to `export_dir` when redacting.
+    pub fn with_export_dir(mut self, export_dir: PathBuf) -> Self {
+        self.export_dir = Some(export_dir);
+        self
+    }
+
+    // Exports are whole files with no index of who's in them, so any that
+    // could hold the data are deleted rather than scrubbed
+    async fn purge_exports(&self, cutoff: Option<DateTime<Utc>>) -> AppResult<u64> {
+        let Some(ref export_dir) = self.export_dir else {
+            return Ok(0);
+        };
+        let removed = purge_exports(export_dir, cutoff.map(SystemTime::from))
+            .await
+            .map_err(|e| AppError::Export(format!("Failed to delete exports: {}", e)))?;
+        Ok(removed as u64)
+    }
+    
+    /// Erases one customer from the shop's local tables: their mirrored
+    /// record and finished webhook jobs are deleted, and webhook deliveries,
+    /// order copies, recovery messages and logged emails that name them by
+    /// ID, email or listed order are scrubbed. Every list export on this
+    /// instance's disk is deleted too; other instances' expire with their links.
+    pub async fn redact_customer(&self, shop_domain: &str, redaction: &CustomerRedaction) -> AppResult<RedactionCounts> {
+        let customer_id = redaction.customer_id.to_string();
+        let email = redaction.email.as_deref().map(str::trim).filter(|email| !email.is_empty());
+        let mut tx = self.db.pool_for(shop_domain).await?.begin().await?;
+        
+        let events = sqlx::query_as::<_, (Uuid, Option<serde_json::Value>)>(
+            r#"
+            SELECT id, payload FROM webhook_events
+            WHERE shop_domain = $1
+              AND ((topic LIKE 'customers/%' AND resource_id = $2)
+                OR ((topic LIKE 'orders/%' OR topic LIKE 'refunds/%') AND resource_id = ANY($3))
+                OR payload->'customer'->>'id' = $4
+                OR LOWER(payload->>'email') = LOWER($5))
+            FOR UPDATE
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(redaction.customer_id)
+        .bind(&redaction.order_ids)
+        .bind(&customer_id)
+        .bind(email)
+        .fetch_all(&mut *tx)
+        .await?;
+        let webhook_events = scrub_webhook_events(&mut tx, events).await?;
+        
+        let orders = sqlx::query_as::<_, (String, i64, serde_json::Value)>(
+            r#"
+            SELECT shop_domain, order_id, data FROM orders
+            WHERE shop_domain = $1
+              AND (order_id = ANY($2) OR data->'customer'->>'id' = $3 OR LOWER(email) = LOWER($4))
+            FOR UPDATE
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(&redaction.order_ids)
+        .bind(&customer_id)
+        .bind(email)
+        .fetch_all(&mut *tx)
+        .await?;
+        let orders = scrub_orders(&mut tx, orders).await?;
+        
+        // Merged records go too; the merge audit keeps only their IDs
+        let customers = sqlx::query("DELETE FROM customers WHERE shop_domain = $1 AND customer_id = $2")
+            .bind(shop_domain)
+            .bind(redaction.customer_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        
+        let recovery_messages = sqlx::query(
+            "UPDATE recovery_messages SET recipient = $3, pii_redacted_at = NOW() WHERE shop_domain = $1 AND LOWER(recipient) = LOWER($2)"
+        )
+        .bind(shop_domain)
+        .bind(email)
+        .bind(REDACTED_RECIPIENT)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        
+        let emails = sqlx::query(
+            "UPDATE email_log SET recipient = $3, pii_redacted_at = NOW() WHERE shop_domain = $1 AND LOWER(recipient) = LOWER($2)"
+        )
+        .bind(shop_domain)
+        .bind(email)
+        .bind(REDACTED_RECIPIENT)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        
+        tx.commit().await?;
+        
+        // Jobs live in the home database. Earlier deliveries for the shop have
+        // all run by now, as its webhook jobs run in order
+        let jobs = sqlx::query(
+            r#"
+            DELETE FROM jobs
+            WHERE kind = $1 AND shop_domain = $2 AND status IN ('succeeded', 'dead')
+              AND ((payload->>'topic' LIKE 'customers/%' AND (payload->>'resource_id')::BIGINT = $3)
+                OR ((payload->>'topic' LIKE 'orders/%' OR payload->>'topic' LIKE 'refunds/%')
+                    AND (payload->>'resource_id')::BIGINT = ANY($4))
+                OR payload->'payload'->'customer'->>'id' = $5
+                OR LOWER(payload->'payload'->>'email') = LOWER($6))
+            "#,
+        )
+        .bind(WEBHOOK_JOB_KIND)
+        .bind(shop_domain)
+        .bind(redaction.customer_id)
+        .bind(&redaction.order_ids)
+        .bind(&customer_id)
+        .bind(email)
+        .execute(self.db.home())
+        .await?
+        .rows_affected();
+        
+        let exports = self.purge_exports(None).await?;
+        
+        Ok(RedactionCounts { webhook_events, orders, customers, recovery_messages, emails, jobs, exports })
+    }
+    
+    /// Scrubs personal data stored before `cutoff` in every database:
+    /// webhook deliveries received, orders placed and recovery messages and
+    /// other emails sent before it. The customer mirror is left alone, as it only holds
+    /// current customers. Finished webhook jobs queued and list exports
+    /// written before it are deleted. Works in batches, so a first run over a large
+    /// backlog doesn't hold one long transaction.
+    pub async fn redact_older_than(&self, cutoff: DateTime<Utc>) -> AppResult<RedactionCounts> {
+        let mut counts = RedactionCounts::default();
+        for pool in self.db.all_pools() {
+            loop {
+                let mut tx = pool.begin().await?;
+                let events = sqlx::query_as::<_, (Uuid, Option<serde_json::Value>)>(
+                    r#"
+                    SELECT id, payload FROM webhook_events
+                    WHERE received_at < $1 AND pii_redacted_at IS NULL
+                    ORDER BY received_at
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                    "#,
+                )
+                .bind(cutoff)
+                .bind(REDACTION_BATCH_SIZE)
+                .fetch_all(&mut *tx)
+                .await?;
+                if events.is_empty() {
+                    break;
+                }
+                counts.webhook_events += scrub_webhook_events(&mut tx, events).await?;
+                tx.commit().await?;
+            }
+            
+            loop {
+                let mut tx = pool.begin().await?;
+                let orders = sqlx::query_as::<_, (String, i64, serde_json::Value)>(
+                    r#"
+                    SELECT shop_domain, order_id, data FROM orders
+                    WHERE created_at < $1 AND pii_redacted_at IS NULL
+                    ORDER BY created_at
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                    "#,
+                )
+                .bind(cutoff)
+                .bind(REDACTION_BATCH_SIZE)
+                .fetch_all(&mut *tx)
+                .await?;
+                if orders.is_empty() {
+                    break;
+                }
+                counts.orders += scrub_orders(&mut tx, orders).await?;
+                tx.commit().await?;
+            }
+            
+            counts.recovery_messages += sqlx::query(
+                "UPDATE recovery_messages SET recipient = $2, pii_redacted_at = NOW() WHERE sent_at < $1 AND pii_redacted_at IS NULL"
+            )
+            .bind(cutoff)
+            .bind(REDACTED_RECIPIENT)
+            .execute(&pool)
+            .await?
+            .rows_affected();
+            
+            counts.emails += sqlx::query(
+                "UPDATE email_log SET recipient = $2, pii_redacted_at = NOW() WHERE created_at < $1 AND pii_redacted_at IS NULL"
+            )
+            .bind(cutoff)
+            .bind(REDACTED_RECIPIENT)
+            .execute(&pool)
+            .await?
+            .rows_affected();
+        }
+        
+        counts.jobs = sqlx::query(
+            "DELETE FROM jobs WHERE kind = $1 AND created_at < $2 AND status IN ('succeeded', 'dead')"
+        )
+        .bind(WEBHOOK_JOB_KIND)
+        .bind(cutoff)
+        .execute(self.db.home())
+        .await?
+        .rows_affected();
+        
+        counts.exports = self.purge_exports(Some(cutoff)).await?;
+        
+        Ok(counts)
+    }
+}
+
+// =============================================================================
+// Database Operations for API Usage
+// =============================================================================
+
+#[derive(Clone)]
+pub struct ApiUsageStore {
+    db: DatabaseRouter,
+}
+
+impl ApiUsageStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    /// Adds drained counters onto the stored hourly totals, in each shop's database.
+    pub async fn add_usage(&self, rows: &[ApiUsageRow]) -> AppResult<()> {
+        let mut by_shop: BTreeMap<&str, Vec<&ApiUsageRow>> = BTreeMap::new();
+        for row in rows {
+            by_shop.entry(&row.shop_domain).or_default().push(row);
+        }
+        
+        for (shop_domain, rows) in by_shop {
+            self.add_shop_usage(shop_domain, &rows).await?;
+        }
+        Ok(())
+    }
+    
+    async fn add_shop_usage(&self, shop_domain: &str, rows: &[&ApiUsageRow]) -> AppResult<()> {
+        let mut tx = self.db.pool_for(shop_domain).await?.begin().await?;
+        
+        for row in rows {
+            sqlx::query(
+                r#"
+                INSERT INTO api_usage (shop_domain, period_start, feature, calls, throttled, errors, utilization_sum, utilization_samples)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (shop_domain, period_start, feature)
+                DO UPDATE SET
+                    calls = api_usage.calls + EXCLUDED.calls,
+                    throttled = api_usage.throttled + EXCLUDED.throttled,
+                    errors = api_usage.errors + EXCLUDED.errors,
+                    utilization_sum = api_usage.utilization_sum + EXCLUDED.utilization_sum,
+                    utilization_samples = api_usage.utilization_samples + EXCLUDED.utilization_samples
+                "#,
+            )
+            .bind(&row.shop_domain)
+            .bind(row.period_start)
+            .bind(&row.feature)
+            .bind(row.calls)
+            .bind(row.throttled)
+            .bind(row.errors)
+            .bind(row.utilization_sum)
+            .bind(row.utilization_samples)
+            .execute(&mut *tx)
+            .await?;
+        }
+        
+        tx.commit().await?;
+        Ok(())
+    }
+    
+    pub async fn usage_since(&self, shop_domain: &str, since: DateTime<Utc>) -> AppResult<Vec<ApiUsageRow>> {
+        let rows = sqlx::query_as::<_, ApiUsageRow>(
+            r#"
+            SELECT * FROM api_usage
+            WHERE shop_domain = $1 AND period_start >= $2
+            ORDER BY period_start ASC, feature ASC
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(since)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(rows)
+    }
+    
+    pub async fn purge_older_than(&self, retention_days: i64) -> AppResult<u64> {
+        let cutoff = Utc::now() - chrono::Duration::days(retention_days);
+        
+        let mut deleted_count = 0;
+        for pool in self.db.all_pools() {
+            let result = sqlx::query("DELETE FROM api_usage WHERE period_start < $1")
+                .bind(cutoff)
+                .execute(&pool)
+                .await?;
+            deleted_count += result.rows_affected();
+        }
+        
+        Ok(deleted_count)
+    }
+}
+
+// =============================================================================
+// Database Operations for the Token Audit Log
+// =============================================================================
+
+#[derive(Clone)]
+pub struct TokenAuditStore {
+    db: DatabaseRouter,
+}
+
+impl TokenAuditStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    /// Appends an entry to the shop's database, or the home database for
+    /// entries not tied to one shop.
+    pub async fn record(&self, entry: &TokenAuditEntry) -> AppResult<()> {
+        let pool = match &entry.shop_domain {
+            Some(shop_domain) => self.db.pool_for(shop_domain).await?,
+            None => self.db.home().clone(),
+        };
+        
+        sqlx::query(
+            r#"
+            INSERT INTO token_audit_log (shop_domain, action, outcome, actor, source_ip, detail)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(&entry.shop_domain)
+        .bind(entry.action.as_str())
+        .bind(entry.outcome.as_str())
+        .bind(&entry.actor)
+        .bind(&entry.source_ip)
+        .bind(&entry.detail)
+        .execute(&pool)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Matching entries, newest first. A shop filter reads only that shop's
+    /// database; otherwise every database is searched.
+    pub async fn query(&self, filter: &TokenAuditFilter) -> AppResult<Vec<TokenAuditEvent>> {
+        let pools = match &filter.shop_domain {
+            Some(shop_domain) => vec![self.db.pool_for(shop_domain).await?],
+            None => self.db.all_pools(),
+        };
+        
+        let mut events = Vec::new();
+        for pool in pools {
+            events.extend(
+                sqlx::query_as::<_, TokenAuditEvent>(
+                    r#"
+                    SELECT * FROM token_audit_log
+                    WHERE ($1::TEXT IS NULL OR shop_domain = $1)
+                      AND ($2::TEXT IS NULL OR action = $2)
+                      AND ($3::TEXT IS NULL OR outcome = $3)
+                      AND ($4::TEXT IS NULL OR actor = $4)
+                      AND ($5::TIMESTAMPTZ IS NULL OR created_at >= $5)
+                      AND ($6::TIMESTAMPTZ IS NULL OR created_at < $6)
+                    ORDER BY created_at DESC, id DESC
+                    LIMIT $7
+                    "#,
+                )
+                .bind(&filter.shop_domain)
+                .bind(&filter.action)
+                .bind(&filter.outcome)
+                .bind(&filter.actor)
+                .bind(filter.since)
+                .bind(filter.until)
+                .bind(filter.limit)
+                .fetch_all(&pool)
+                .await?,
+            );
+        }
+        
+        events.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        events.truncate(filter.limit.max(0) as usize);
+        Ok(events)
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditSink for TokenAuditStore {
+    async fn record(&self, entry: TokenAuditEntry) -> AppResult<()> {
+        TokenAuditStore::record(self, &entry).await
+    }
+}
+
+// =============================================================================
+// Database Operations for the Order Line Item Mirror
+// =============================================================================
+
+#[derive(Clone)]
+pub struct OrderMirrorStore {
+    db: DatabaseRouter,
+}
+
+impl OrderMirrorStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    /// Replaces the mirrored line items of every order in `order_ids` with
+    /// `line_items`, so edited orders lose removed lines and orders that were
+    /// since cancelled drop out. Returns the number of rows written.
+    pub async fn replace_orders(
+        &self,
+        shop_domain: &str,
+        order_ids: &[i64],
+        line_items: &[MirroredLineItem],
+    ) -> AppResult<u64> {
+        let mut tx = self.db.pool_for(shop_domain).await?.begin().await?;
+        
+        sqlx::query("DELETE FROM order_line_items WHERE shop_domain = $1 AND order_id = ANY($2)")
+            .bind(shop_domain)
+            .bind(order_ids)
+            .execute(&mut *tx)
+            .await?;
+        
+        for item in line_items {
+            sqlx::query(
+                r#"
+                INSERT INTO order_line_items
+                    (shop_domain, line_item_id, order_id, order_created_at, product_id, variant_id, title, sku, quantity, price, total_discount)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                ON CONFLICT (shop_domain, line_item_id) DO NOTHING
+                "#,
+            )
+            .bind(shop_domain)
+            .bind(item.line_item_id)
+            .bind(item.order_id)
+            .bind(item.order_created_at)
+            .bind(item.product_id)
+            .bind(item.variant_id)
+            .bind(&item.title)
+            .bind(&item.sku)
+            .bind(item.quantity)
+            .bind(item.price)
+            .bind(item.total_discount)
+            .execute(&mut *tx)
+            .await?;
+        }
+        
+        tx.commit().await?;
+        info!(
+            "🪞 Mirrored {} line items from {} orders for shop {}",
+            line_items.len(), order_ids.len(), shop_domain
+        );
+        Ok(line_items.len() as u64)
+    }
+    
+    /// Line items of orders created in `[created_at_min, created_at_max)`.
+    pub async fn line_items_between(
+        &self,
+        shop_domain: &str,
+        created_at_min: DateTime<Utc>,
+        created_at_max: Option<DateTime<Utc>>,
+    ) -> AppResult<Vec<MirroredLineItem>> {
+        let line_items = sqlx::query_as::<_, MirroredLineItem>(
+            r#"
+            SELECT order_id, line_item_id, order_created_at, product_id, variant_id, title, sku, quantity, price, total_discount
+            FROM order_line_items
+            WHERE shop_domain = $1
+              AND order_created_at >= $2
+              AND ($3::timestamptz IS NULL OR order_created_at < $3)
+            ORDER BY order_id ASC, line_item_id ASC
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(created_at_min)
+        .bind(created_at_max)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(line_items)
+    }
+}
+
+// =============================================================================
+// Database Operations for Local Orders
+// =============================================================================
+
+#[derive(Clone)]
+pub struct OrderStore {
+    db: DatabaseRouter,
+}
+
+impl OrderStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    /// Inserts or refreshes `orders`. A row is only replaced by a copy with
+    /// the same or a later `updated_at`, so a late webhook can't roll an
+    /// order back. Returns the number of rows written.
+    pub async fn upsert_orders(&self, shop_domain: &str, orders: &[StoredOrder]) -> AppResult<u64> {
+        let mut tx = self.db.pool_for(shop_domain).await?.begin().await?;
+        let mut written = 0;
+        
+        for order in orders {
+            written += sqlx::query(
+                r#"
+                INSERT INTO orders
+                    (shop_domain, order_id, order_number, name, email, financial_status, fulfillment_status,
+                     source_name, total_price, currency, test, created_at, updated_at, processed_at, closed_at,
+                     cancelled_at, data)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+                ON CONFLICT (shop_domain, order_id)
+                DO UPDATE SET
+                    order_number = EXCLUDED.order_number,
+                    name = EXCLUDED.name,
+                    email = EXCLUDED.email,
+                    financial_status = EXCLUDED.financial_status,
+                    fulfillment_status = EXCLUDED.fulfillment_status,
+                    source_name = EXCLUDED.source_name,
+                    total_price = EXCLUDED.total_price,
+                    currency = EXCLUDED.currency,
+                    test = EXCLUDED.test,
+                    created_at = EXCLUDED.created_at,
+                    updated_at = EXCLUDED.updated_at,
+                    processed_at = EXCLUDED.processed_at,
+                    closed_at = EXCLUDED.closed_at,
+                    cancelled_at = EXCLUDED.cancelled_at,
+                    data = EXCLUDED.data,
+                    synced_at = NOW(),
+                    pii_redacted_at = NULL
+                WHERE orders.updated_at <= EXCLUDED.updated_at
+                "#,
+            )
+            .bind(shop_domain)
+            .bind(order.order_id)
+            .bind(order.order_number)
+            .bind(&order.name)
+            .bind(&order.email)
+            .bind(&order.financial_status)
+            .bind(&order.fulfillment_status)
+            .bind(&order.source_name)
+            .bind(order.total_price)
+            .bind(&order.currency)
+            .bind(order.test)
+            .bind(order.created_at)
+            .bind(order.updated_at)
+            .bind(order.processed_at)
+            .bind(order.closed_at)
+            .bind(order.cancelled_at)
+            .bind(&order.data)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        
+        tx.commit().await?;
+        Ok(written)
+    }
+    
+    pub async fn delete_order(&self, shop_domain: &str, order_id: i64) -> AppResult<bool> {
+        let pool = self.db.pool_for(shop_domain).await?;
+        let result = sqlx::query("DELETE FROM orders WHERE shop_domain = $1 AND order_id = $2")
+            .bind(shop_domain)
+            .bind(order_id)
+            .execute(&pool)
+            .await?;
+        sqlx::query("DELETE FROM order_risks WHERE shop_domain = $1 AND order_id = $2")
+            .bind(shop_domain)
+            .bind(order_id)
+            .execute(&pool)
+            .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    /// Records the latest risk level assessed for an order, which needn't be
+    /// in the local copy yet.
+    pub async fn record_risk_level(&self, shop_domain: &str, order_id: i64, risk_level: &str) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO order_risks (shop_domain, order_id, risk_level)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (shop_domain, order_id)
+            DO UPDATE SET risk_level = EXCLUDED.risk_level, assessed_at = NOW()
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(order_id)
+        .bind(risk_level)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Counts of open, non-test orders by fulfillment and financial status.
+    pub async fn summary(&self, shop_domain: &str) -> AppResult<OrderSummary> {
+        let pool = self.db.pool_for(shop_domain).await?;
+        
+        let mut summary = sqlx::query_as::<_, OrderSummary>(
+            r#"
+            SELECT
+                COUNT(*) AS open_orders,
+                COUNT(*) FILTER (WHERE COALESCE(fulfillment_status, 'unfulfilled') = 'unfulfilled') AS unfulfilled_orders,
+                COUNT(*) FILTER (WHERE fulfillment_status = 'partial') AS partially_fulfilled_orders,
+                MIN(created_at) FILTER (WHERE COALESCE(fulfillment_status, 'unfulfilled') IN ('unfulfilled', 'partial')) AS oldest_unfulfilled_at
+            FROM orders
+            WHERE shop_domain = $1 AND closed_at IS NULL AND cancelled_at IS NULL AND NOT test
+            "#,
+        )
+        .bind(shop_domain)
+        .fetch_one(&pool)
+        .await?;
+        
+        summary.by_financial_status = sqlx::query_as::<_, FinancialStatusTotal>(
+            r#"
+            SELECT financial_status, currency, COUNT(*) AS orders, SUM(total_price) AS total_price
+            FROM orders
+            WHERE shop_domain = $1 AND closed_at IS NULL AND cancelled_at IS NULL AND NOT test
+            GROUP BY financial_status, currency
+            ORDER BY orders DESC, financial_status, currency
+            "#,
+        )
+        .bind(shop_domain)
+        .fetch_all(&pool)
+        .await?;
+        
+        Ok(summary)
+    }
+    
+    /// Non-test, uncancelled orders placed in `[since, until)`, summed per
+    /// currency and UTC `period` (`day` or `week`, which starts on Monday).
+    pub async fn sales_by_period(
+        &self,
+        shop_domain: &str,
+        period: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> AppResult<Vec<PeriodSales>> {
+        let sales = sqlx::query_as::<_, PeriodSales>(
+            r#"
+            SELECT
+                currency,
+                date_trunc($2, created_at AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS period_start,
+                COUNT(*) AS orders,
+                SUM(total_price) AS revenue
+            FROM orders
+            WHERE shop_domain = $1 AND NOT test AND cancelled_at IS NULL
+              AND created_at >= $3 AND created_at < $4
+            GROUP BY 1, 2
+            ORDER BY 1, 2
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(period)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(sales)
+    }
+    
+    /// Successful refund transactions issued in `[since, until)` on the
+    /// orders [`Self::sales_by_period`] counts, by when the refund was made
+    /// rather than when the order was placed.
+    pub async fn refunds_by_period(
+        &self,
+        shop_domain: &str,
+        period: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> AppResult<Vec<PeriodRefunds>> {
+        // A refund updates its order, so older orders can't have new refunds
+        let refunds = sqlx::query_as::<_, PeriodRefunds>(
+            r#"
+            SELECT
+                COALESCE(NULLIF(tx.value->>'currency', ''), orders.currency) AS currency,
+                date_trunc($2, refund.refunded_at AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS period_start,
+                COUNT(DISTINCT refund.id) AS refunds,
+                SUM((tx.value->>'amount')::numeric) AS refunded
+            FROM orders
+            CROSS JOIN LATERAL jsonb_array_elements(COALESCE(orders.data->'refunds', '[]'::jsonb)) AS r(value)
+            CROSS JOIN LATERAL (
+                SELECT r.value->>'id' AS id, COALESCE((r.value->>'created_at')::timestamptz, orders.created_at) AS refunded_at
+            ) AS refund
+            CROSS JOIN LATERAL jsonb_array_elements(COALESCE(r.value->'transactions', '[]'::jsonb)) AS tx(value)
+            WHERE orders.shop_domain = $1 AND NOT orders.test AND orders.cancelled_at IS NULL
+              AND orders.updated_at >= $3
+              AND tx.value->>'kind' = 'refund' AND tx.value->>'status' = 'success'
+              AND refund.refunded_at >= $3 AND refund.refunded_at < $4
+            GROUP BY 1, 2
+            ORDER BY 1, 2
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(period)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(refunds)
+    }
+    
+    /// Stored orders matching `filter`, newest first.
+    pub async fn list_orders(
+        &self,
+        shop_domain: &str,
+        filter: &StoredOrderFilter,
+    ) -> AppResult<Vec<serde_json::Value>> {
+        let rows = sqlx::query_as::<_, (serde_json::Value,)>(
+            r#"
+            SELECT data
+            FROM orders
+            WHERE shop_domain = $1
+              AND ($2::bigint[] IS NULL OR order_id = ANY($2))
+              AND ($3::bigint IS NULL OR order_id > $3)
+              AND ($4::bigint IS NULL OR order_id < $4)
+              AND ($5::text IS NULL
+                   OR ($5 = 'open' AND closed_at IS NULL)
+                   OR ($5 = 'closed' AND closed_at IS NOT NULL)
+                   OR ($5 = 'cancelled' AND cancelled_at IS NOT NULL))
+              AND ($6::text[] IS NULL OR financial_status = ANY($6))
+              AND ($7::text[] IS NULL OR COALESCE(fulfillment_status, 'unfulfilled') = ANY($7))
+              AND ($8::timestamptz IS NULL OR created_at >= $8)
+              AND ($9::timestamptz IS NULL OR created_at <= $9)
+              AND ($10::timestamptz IS NULL OR updated_at >= $10)
+              AND ($11::timestamptz IS NULL OR updated_at <= $11)
+              AND ($12::timestamptz IS NULL OR processed_at >= $12)
+              AND ($13::timestamptz IS NULL OR processed_at <= $13)
+              AND ($14::text IS NULL OR EXISTS (
+                   SELECT 1 FROM order_risks
+                   WHERE order_risks.shop_domain = orders.shop_domain
+                     AND order_risks.order_id = orders.order_id
+                     AND order_risks.risk_level = $14))
+            ORDER BY order_id DESC
+            LIMIT $15
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(&filter.ids)
+        .bind(filter.since_id)
+        .bind(filter.before_id)
+        .bind(&filter.status)
+        .bind(&filter.financial_statuses)
+        .bind(&filter.fulfillment_statuses)
+        .bind(filter.created_at_min)
+        .bind(filter.created_at_max)
+        .bind(filter.updated_at_min)
+        .bind(filter.updated_at_max)
+        .bind(filter.processed_at_min)
+        .bind(filter.processed_at_max)
+        .bind(&filter.risk_level)
+        .bind(filter.limit)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(rows.into_iter().map(|(data,)| data).collect())
+    }
+    
+    pub async fn sync_state(&self, shop_domain: &str) -> AppResult<Option<OrderSyncState>> {
+        let state = sqlx::query_as::<_, OrderSyncState>(
+            "SELECT shop_domain, backfilled_at, updated_at_cursor, last_synced_at, last_error FROM order_sync_state WHERE shop_domain = $1"
+        )
+        .bind(shop_domain)
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(state)
+    }
+    
+    /// Records a finished sync run. The cursor only moves forward, and the
+    /// backfill timestamp is set by the first run that completes one.
+    pub async fn record_sync(
+        &self,
+        shop_domain: &str,
+        backfilled: bool,
+        updated_at_cursor: Option<DateTime<Utc>>,
+        error: Option<&str>,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO order_sync_state (shop_domain, backfilled_at, updated_at_cursor, last_synced_at, last_error)
+            VALUES ($1, CASE WHEN $2 THEN NOW() END, $3, NOW(), $4)
+            ON CONFLICT (shop_domain)
+            DO UPDATE SET
+                backfilled_at = COALESCE(order_sync_state.backfilled_at, EXCLUDED.backfilled_at),
+                updated_at_cursor = GREATEST(order_sync_state.updated_at_cursor, EXCLUDED.updated_at_cursor),
+                last_synced_at = NOW(),
+                last_error = EXCLUDED.last_error
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(backfilled)
+        .bind(updated_at_cursor)
+        .bind(error)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Database Operations for the Catalog Mirror
+// =============================================================================
+
+#[derive(Clone)]
+pub struct CatalogStore {
+    db: DatabaseRouter,
+}
+
+impl CatalogStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    /// Inserts or refreshes products with their variants. A product is only
+    /// rewritten when its content changed and the copy is at least as recent
+    /// as the stored one, so a late webhook can't roll it back. Returns the
+    /// number of products written.
+    pub async fn upsert_products(&self, shop_domain: &str, products: &[CatalogProduct]) -> AppResult<u64> {
+        let mut tx = self.db.pool_for(shop_domain).await?.begin().await?;
+        let mut written = 0;
+        
+        for product in products {
+            let skus: Vec<&str> = product.variants.iter().filter_map(|v| v.sku.as_deref()).collect();
+            let changed = sqlx::query(
+                r#"
+                INSERT INTO catalog_products
+                    (shop_domain, product_id, title, handle, vendor, product_type, status, tags, skus, updated_at, data)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                ON CONFLICT (shop_domain, product_id)
+                DO UPDATE SET
+                    title = EXCLUDED.title,
+                    handle = EXCLUDED.handle,
+                    vendor = EXCLUDED.vendor,
+                    product_type = EXCLUDED.product_type,
+                    status = EXCLUDED.status,
+                    tags = EXCLUDED.tags,
+                    skus = EXCLUDED.skus,
+                    updated_at = EXCLUDED.updated_at,
+                    data = EXCLUDED.data,
+                    synced_at = NOW()
+                WHERE catalog_products.updated_at <= EXCLUDED.updated_at
+                  AND catalog_products.data IS DISTINCT FROM EXCLUDED.data
+                "#,
+            )
+            .bind(shop_domain)
+            .bind(product.product_id)
+            .bind(&product.title)
+            .bind(&product.handle)
+            .bind(&product.vendor)
+            .bind(&product.product_type)
+            .bind(&product.status)
+            .bind(&product.tags)
+            .bind(skus.join(" "))
+            .bind(product.updated_at)
+            .bind(&product.data)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            if changed == 0 {
+                continue;
+            }
+            written += changed;
+            
+            sqlx::query("DELETE FROM catalog_variants WHERE shop_domain = $1 AND product_id = $2")
+                .bind(shop_domain)
+                .bind(product.product_id)
+                .execute(&mut *tx)
+                .await?;
+            for variant in &product.variants {
+                sqlx::query(
+                    r#"
+                    INSERT INTO catalog_variants
+                        (shop_domain, variant_id, product_id, title, sku, barcode, price, inventory_quantity)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                    "#,
+                )
+                .bind(shop_domain)
+                .bind(variant.variant_id)
+                .bind(product.product_id)
+                .bind(&variant.title)
+                .bind(&variant.sku)
+                .bind(&variant.barcode)
+                .bind(variant.price)
+                .bind(variant.inventory_quantity)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        
+        tx.commit().await?;
+        Ok(written)
+    }
+    
+    /// Removes a product and its variants.
+    pub async fn delete_product(&self, shop_domain: &str, product_id: i64) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM catalog_products WHERE shop_domain = $1 AND product_id = $2")
+            .bind(shop_domain)
+            .bind(product_id)
+            .execute(&self.db.pool_for(shop_domain).await?)
+            .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    /// Removes products a reconciliation pass didn't see: deleted in Shopify
+    /// while a webhook was missed. Products synced since `pass_started_at`
+    /// (created by a webhook during the pass) are kept.
+    pub async fn delete_products_except(
+        &self,
+        shop_domain: &str,
+        seen: &[i64],
+        pass_started_at: DateTime<Utc>,
+    ) -> AppResult<u64> {
+        let result = sqlx::query(
+            "DELETE FROM catalog_products WHERE shop_domain = $1 AND product_id <> ALL($2) AND synced_at < $3"
+        )
+        .bind(shop_domain)
+        .bind(seen)
+        .bind(pass_started_at)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(result.rows_affected())
+    }
+    
+    /// Products matching `query`, best matches first when searching and
+    /// newest first otherwise, with the total number of matches.
+    pub async fn search_products(
+        &self,
+        shop_domain: &str,
+        query: &CatalogQuery,
+    ) -> AppResult<(Vec<serde_json::Value>, i64)> {
+        let rows = sqlx::query_as::<_, (serde_json::Value, i64)>(
+            r#"
+            SELECT data, COUNT(*) OVER () AS total
+            FROM catalog_products
+            WHERE shop_domain = $1
+              AND ($2::text IS NULL OR search_vector @@ to_tsquery('simple', $2))
+              AND ($3::text IS NULL OR LOWER(vendor) = LOWER($3))
+              AND ($4::text IS NULL OR LOWER(product_type) = LOWER($4))
+              AND ($5::text IS NULL OR status = $5)
+              AND ($6::text IS NULL OR LOWER($6) = ANY(string_to_array(LOWER(REPLACE(tags, ', ', ',')), ',')))
+            ORDER BY
+                CASE WHEN $2::text IS NULL THEN 0 ELSE ts_rank(search_vector, to_tsquery('simple', $2)) END DESC,
+                product_id DESC
+            LIMIT $7 OFFSET $8
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(&query.text)
+        .bind(&query.vendor)
+        .bind(&query.product_type)
+        .bind(&query.status)
+        .bind(&query.tag)
+        .bind(query.limit)
+        .bind(query.offset)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        let total = rows.first().map(|(_, total)| *total).unwrap_or_default();
+        Ok((rows.into_iter().map(|(data, _)| data).collect(), total))
+    }
+    
+    pub async fn sync_state(&self, shop_domain: &str) -> AppResult<Option<CatalogSyncState>> {
+        let state = sqlx::query_as::<_, CatalogSyncState>(
+            "SELECT shop_domain, reconciled_at, last_synced_at, last_error FROM catalog_sync_state WHERE shop_domain = $1"
+        )
+        .bind(shop_domain)
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(state)
+    }
+    
+    /// Records a finished reconciliation pass, successful or not.
+    pub async fn record_sync(&self, shop_domain: &str, error: Option<&str>) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO catalog_sync_state (shop_domain, reconciled_at, last_synced_at, last_error)
+            VALUES ($1, CASE WHEN $2::text IS NULL THEN NOW() END, NOW(), $2)
+            ON CONFLICT (shop_domain)
+            DO UPDATE SET
+                reconciled_at = COALESCE(EXCLUDED.reconciled_at, catalog_sync_state.reconciled_at),
+                last_synced_at = NOW(),
+                last_error = EXCLUDED.last_error
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(error)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Database Operations for Scheduled Jobs
+// =============================================================================
+
+#[derive(Clone)]
+pub struct JobStore {
+    db: DatabaseRouter,
+}
+
+impl JobStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    /// Claims the run of `name` due at `due_at` for `instance`. Fails if
+    /// another instance holds an unexpired lease or already claimed that
+    /// slot; on success the next slot becomes `next_run_at`.
+    pub async fn claim(
+        &self,
+        name: &str,
+        instance: &str,
+        due_at: DateTime<Utc>,
+        next_run_at: Option<DateTime<Utc>>,
+        lease: std::time::Duration,
+    ) -> AppResult<bool> {
+        let pool = self.db.home();
+        sqlx::query("INSERT INTO scheduled_jobs (name) VALUES ($1) ON CONFLICT (name) DO NOTHING")
+            .bind(name)
+            .execute(pool)
+            .await?;
+        
+        let result = sqlx::query(
+            r#"
+            UPDATE scheduled_jobs
+            SET locked_by = $2,
+                locked_until = NOW() + make_interval(secs => $5),
+                next_run_at = $4,
+                last_started_at = NOW()
+            WHERE name = $1
+              AND (locked_until IS NULL OR locked_until < NOW())
+              AND (next_run_at IS NULL OR next_run_at <= $3)
+            "#,
+        )
+        .bind(name)
+        .bind(instance)
+        .bind(due_at)
+        .bind(next_run_at)
+        .bind(lease.as_secs_f64())
+        .execute(pool)
+        .await?;
+        
+        Ok(result.rows_affected() == 1)
+    }
+    
+    /// Keeps a running job's lease from expiring. Returns false once another
+    /// instance has taken the job over, after the lease ran out.
+    pub async fn extend_lease(&self, name: &str, instance: &str, lease: std::time::Duration) -> AppResult<bool> {
+        let result = sqlx::query(
+            "UPDATE scheduled_jobs SET locked_until = NOW() + make_interval(secs => $3) WHERE name = $1 AND locked_by = $2"
+        )
+        .bind(name)
+        .bind(instance)
+        .bind(lease.as_secs_f64())
+        .execute(self.db.home())
+        .await?;
+        
+        Ok(result.rows_affected() == 1)
+    }
+    
+    /// Releases the lease and records how the run went.
+    pub async fn finish(
+        &self,
+        name: &str,
+        instance: &str,
+        summary: Option<&str>,
+        error: Option<&str>,
+        duration_ms: i64,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE scheduled_jobs
+            SET locked_by = NULL,
+                locked_until = NULL,
+                last_finished_at = NOW(),
+                last_status = CASE WHEN $4::text IS NULL THEN 'ok' ELSE 'failed' END,
+                last_summary = $3,
+                last_error = $4,
+                last_duration_ms = $5,
+                last_instance = $2
+            WHERE name = $1 AND locked_by = $2
+            "#,
+        )
+        .bind(name)
+        .bind(instance)
+        .bind(summary)
+        .bind(error)
+        .bind(duration_ms)
+        .execute(self.db.home())
+        .await?;
+        
+        Ok(())
+    }
+    
+    pub async fn list_jobs(&self) -> AppResult<Vec<ScheduledJobRecord>> {
+        let jobs = sqlx::query_as::<_, ScheduledJobRecord>(
+            r#"
+            SELECT name, next_run_at, locked_by, locked_until, last_started_at, last_finished_at,
+                   last_status, last_summary, last_error, last_duration_ms, last_instance
+            FROM scheduled_jobs
+            ORDER BY name
+            "#,
+        )
+        .fetch_all(self.db.home())
+        .await?;
+        
+        Ok(jobs)
+    }
+}
+
+// =============================================================================
+// Database Operations for the Job Queue
+// =============================================================================
+
+const QUEUED_JOB_COLUMNS: &str = "id, kind, shop_domain, queue_key, payload, status, attempts, max_attempts, \
+    run_at, locked_by, locked_until, last_error, created_at, updated_at, finished_at";
+
+#[derive(Clone)]
+pub struct JobQueueStore {
+    db: DatabaseRouter,
+}
+
+impl JobQueueStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    /// Adds a job, ready to run now. Returns `None` when `unique` is set and
+    /// a job with the same key is already waiting or running.
+    pub async fn enqueue(&self, job: &NewJob) -> AppResult<Option<QueuedJob>> {
+        let job = sqlx::query_as::<_, QueuedJob>(&format!(
+            r#"
+            INSERT INTO jobs (kind, shop_domain, queue_key, payload, max_attempts)
+            SELECT $1, $2, $3, $4, $5
+            WHERE NOT $6 OR NOT EXISTS (
+                SELECT 1 FROM jobs WHERE queue_key = $3 AND status IN ('queued', 'running')
+            )
+            RETURNING {}
+            "#,
+            QUEUED_JOB_COLUMNS
+        ))
+        .bind(&job.kind)
+        .bind(&job.shop_domain)
+        .bind(&job.queue_key)
+        .bind(&job.payload)
+        .bind(job.max_attempts)
+        .bind(job.unique)
+        .fetch_optional(self.db.home())
+        .await?;
+        
+        Ok(job)
+    }
+    
+    /// Claims the next ready job of one of `kinds` for `worker`, skipping rows
+    /// other workers hold and jobs queued behind an unfinished job with the
+    /// same key. Jobs whose worker stopped renewing the lease are reclaimed.
+    pub async fn claim(&self, kinds: &[String], worker: &str, lease: std::time::Duration) -> AppResult<Option<QueuedJob>> {
+        let job = sqlx::query_as::<_, QueuedJob>(&format!(
+            r#"
+            UPDATE jobs
+            SET status = 'running',
+                attempts = attempts + 1,
+                locked_by = $2,
+                locked_until = NOW() + make_interval(secs => $3),
+                updated_at = NOW()
+            WHERE id = (
+                SELECT id FROM jobs j
+                WHERE j.kind = ANY($1)
+                  AND ((j.status = 'queued' AND j.run_at <= NOW())
+                    OR (j.status = 'running' AND j.locked_until < NOW()))
+                  AND (j.queue_key IS NULL OR NOT EXISTS (
+                      SELECT 1 FROM jobs earlier
+                      WHERE earlier.queue_key = j.queue_key
+                        AND earlier.seq < j.seq
+                        AND earlier.status IN ('queued', 'running')
+                  ))
+                ORDER BY j.run_at, j.seq
+                FOR UPDATE SKIP LOCKED
+                LIMIT 1
+            )
+            RETURNING {}
+            "#,
+            QUEUED_JOB_COLUMNS
+        ))
+        .bind(kinds)
+        .bind(worker)
+        .bind(lease.as_secs_f64())
+        .fetch_optional(self.db.home())
+        .await?;
+        
+        Ok(job)
+    }
+    
+    /// Keeps a running job's lease from expiring.
+    pub async fn extend_lease(&self, id: Uuid, worker: &str, lease: std::time::Duration) -> AppResult<()> {
+        sqlx::query(
+            "UPDATE jobs SET locked_until = NOW() + make_interval(secs => $3) WHERE id = $1 AND locked_by = $2 AND status = 'running'"
+        )
+        .bind(id)
+        .bind(worker)
+        .bind(lease.as_secs_f64())
+        .execute(self.db.home())
+        .await?;
+        
+        Ok(())
+    }
+    
+    pub async fn complete(&self, id: Uuid, worker: &str) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE jobs
+            SET status = 'succeeded', locked_by = NULL, locked_until = NULL,
+                last_error = NULL, finished_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND locked_by = $2 AND status = 'running'
+            "#,
+        )
+        .bind(id)
+        .bind(worker)
+        .execute(self.db.home())
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Records a failed attempt: the job runs again after `retry_in`, or is
+    /// dead-lettered once it has used all its attempts. Returns the new status.
+    pub async fn fail(&self, id: Uuid, worker: &str, error: &str, retry_in: std::time::Duration) -> AppResult<Option<String>> {
+        let status: Option<String> = sqlx::query_scalar(
+            r#"
+            UPDATE jobs
+            SET status = CASE WHEN attempts >= max_attempts THEN 'dead' ELSE 'queued' END,
+                run_at = NOW() + make_interval(secs => $4),
+                finished_at = CASE WHEN attempts >= max_attempts THEN NOW() END,
+                locked_by = NULL, locked_until = NULL,
+                last_error = $3, updated_at = NOW()
+            WHERE id = $1 AND locked_by = $2 AND status = 'running'
+            RETURNING status
+            "#,
+        )
+        .bind(id)
+        .bind(worker)
+        .bind(error)
+        .bind(retry_in.as_secs_f64())
+        .fetch_optional(self.db.home())
+        .await?;
+        
+        Ok(status)
+    }
+    
+    /// Puts a dead job (or one waiting out its backoff) back in line to run
+    /// now with a fresh set of attempts.
+    pub async fn retry(&self, id: Uuid) -> AppResult<Option<QueuedJob>> {
+        let job = sqlx::query_as::<_, QueuedJob>(&format!(
+            r#"
+            UPDATE jobs
+            SET status = 'queued', attempts = 0, run_at = NOW(), finished_at = NULL, updated_at = NOW()
+            WHERE id = $1 AND status IN ('dead', 'queued')
+            RETURNING {}
+            "#,
+            QUEUED_JOB_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(self.db.home())
+        .await?;
+        
+        Ok(job)
+    }
+    
+    pub async fn get_job(&self, id: Uuid) -> AppResult<Option<QueuedJob>> {
+        let job = sqlx::query_as::<_, QueuedJob>(&format!("SELECT {} FROM jobs WHERE id = $1", QUEUED_JOB_COLUMNS))
+            .bind(id)
+            .fetch_optional(self.db.home())
+            .await?;
+        
+        Ok(job)
+    }
+    
+    /// Most recently enqueued jobs first.
+    pub async fn list_jobs(&self, filter: &JobQueueFilter) -> AppResult<Vec<QueuedJob>> {
+        let jobs = sqlx::query_as::<_, QueuedJob>(&format!(
+            r#"
+            SELECT {} FROM jobs
+            WHERE ($1::TEXT IS NULL OR status = $1)
+              AND ($2::TEXT IS NULL OR kind = $2)
+              AND ($3::TEXT IS NULL OR shop_domain = $3)
+            ORDER BY seq DESC
+            LIMIT $4
+            "#,
+            QUEUED_JOB_COLUMNS
+        ))
+        .bind(&filter.status)
+        .bind(&filter.kind)
+        .bind(&filter.shop_domain)
+        .bind(filter.limit)
+        .fetch_all(self.db.home())
+        .await?;
+        
+        Ok(jobs)
+    }
+    
+    pub async fn counts(&self) -> AppResult<Vec<JobQueueCount>> {
+        let counts = sqlx::query_as::<_, JobQueueCount>(
+            "SELECT kind, status, COUNT(*) AS count FROM jobs GROUP BY kind, status ORDER BY kind, status"
+        )
+        .fetch_all(self.db.home())
+        .await?;
+        
+        Ok(counts)
+    }
+    
+    /// Deletes succeeded jobs finished before `succeeded_before` and dead
+    /// ones before `dead_before`.
+    pub async fn purge_finished(&self, succeeded_before: DateTime<Utc>, dead_before: DateTime<Utc>) -> AppResult<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM jobs
+            WHERE (status = 'succeeded' AND finished_at < $1)
+               OR (status = 'dead' AND finished_at < $2)
+            "#,
+        )
+        .bind(succeeded_before)
+        .bind(dead_before)
+        .execute(self.db.home())
+        .await?;
+        
+        Ok(result.rows_affected())
+    }
+}
+
+// =============================================================================
+// Database Operations for Recovery Messages
+// =============================================================================
+
+const RECOVERY_MESSAGE_COLUMNS: &str = "id, shop_domain, checkout_id, recipient, subject, sent_at, \
+    first_opened_at, open_count, first_clicked_at, click_count, bounced_at, bounce_reason, converted_at, order_id";
+
+#[derive(Clone)]
+pub struct RecoveryMessageStore {
+    db: DatabaseRouter,
+}
+
+impl RecoveryMessageStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    pub async fn record_send(
+        &self,
+        shop_domain: &str,
+        checkout_id: i64,
+        recipient: &str,
+        subject: Option<&str>,
+    ) -> AppResult<RecoveryMessage> {
+        let message = sqlx::query_as::<_, RecoveryMessage>(&format!(
+            "INSERT INTO recovery_messages (shop_domain, checkout_id, recipient, subject) VALUES ($1, $2, $3, $4) RETURNING {}",
+            RECOVERY_MESSAGE_COLUMNS
+        ))
+        .bind(shop_domain)
+        .bind(checkout_id)
+        .bind(recipient)
+        .bind(subject)
+        .fetch_one(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        info!("📧 Recorded recovery message {} for checkout {} on shop {}", message.id, checkout_id, shop_domain);
+        Ok(message)
+    }
+    
+    /// Removes a message that never went out. Returns false if it doesn't exist.
+    pub async fn delete_message(&self, shop_domain: &str, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM recovery_messages WHERE shop_domain = $1 AND id = $2")
+            .bind(shop_domain)
+            .bind(id)
+            .execute(&self.db.pool_for(shop_domain).await?)
+            .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    /// Counts an open. Returns false if the message doesn't exist.
+    pub async fn record_open(&self, shop_domain: &str, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE recovery_messages
+            SET open_count = open_count + 1, first_opened_at = COALESCE(first_opened_at, NOW())
+            WHERE shop_domain = $1 AND id = $2
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(id)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    /// Counts a click, which also means the message was opened even if the
+    /// pixel was blocked. Returns false if the message doesn't exist.
+    pub async fn record_click(&self, shop_domain: &str, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE recovery_messages
+            SET click_count = click_count + 1,
+                first_clicked_at = COALESCE(first_clicked_at, NOW()),
+                first_opened_at = COALESCE(first_opened_at, NOW())
+            WHERE shop_domain = $1 AND id = $2
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(id)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    pub async fn record_bounce(&self, shop_domain: &str, id: Uuid, reason: Option<&str>) -> AppResult<Option<RecoveryMessage>> {
+        let message = sqlx::query_as::<_, RecoveryMessage>(&format!(
+            r#"
+            UPDATE recovery_messages
+            SET bounced_at = COALESCE(bounced_at, NOW()), bounce_reason = COALESCE($3, bounce_reason)
+            WHERE shop_domain = $1 AND id = $2
+            RETURNING {}
+            "#,
+            RECOVERY_MESSAGE_COLUMNS
+        ))
+        .bind(shop_domain)
+        .bind(id)
+        .bind(reason)
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(message)
+    }
+    
+    /// Credits an order to every unconverted message sent for its checkout.
+    pub async fn record_conversion(&self, shop_domain: &str, checkout_id: i64, order_id: i64) -> AppResult<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE recovery_messages
+            SET converted_at = NOW(), order_id = $3
+            WHERE shop_domain = $1 AND checkout_id = $2 AND converted_at IS NULL
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(checkout_id)
+        .bind(order_id)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        if result.rows_affected() > 0 {
+            info!("💸 Order {} converted {} recovery messages for checkout {}", order_id, result.rows_affected(), checkout_id);
+        }
+        Ok(result.rows_affected())
+    }
+    
+    /// Stage counts for messages sent in `[since, until)`.
+    pub async fn counts(&self, shop_domain: &str, since: DateTime<Utc>, until: DateTime<Utc>) -> AppResult<RecoveryCounts> {
+        let counts = sqlx::query_as::<_, RecoveryCounts>(
+            r#"
+            SELECT
+                COUNT(*) AS sent,
+                COUNT(first_opened_at) AS opened,
+                COUNT(first_clicked_at) AS clicked,
+                COUNT(bounced_at) AS bounced,
+                COUNT(converted_at) AS converted
+            FROM recovery_messages
+            WHERE shop_domain = $1 AND sent_at >= $2 AND sent_at < $3
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(since)
+        .bind(until)
+        .fetch_one(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(counts)
+    }
+}
+
+// =============================================================================
+// Database Operations for the Email Log
+// =============================================================================
+
+const EMAIL_LOG_COLUMNS: &str =
+    "id, shop_domain, kind, provider, recipient, subject, status, provider_message_id, error, created_at";
+
+#[derive(Clone)]
+pub struct EmailLogStore {
+    db: DatabaseRouter,
+}
+
+impl EmailLogStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    /// Records a send: `Ok` with the provider's message ID, or what went wrong.
+    pub async fn record(
+        &self,
+        shop_domain: &str,
+        kind: &str,
+        provider: &str,
+        recipient: &str,
+        subject: &str,
+        outcome: Result<Option<&str>, &str>,
+    ) -> AppResult<EmailLogEntry> {
+        let (status, provider_message_id, error) = match outcome {
+            Ok(message_id) => ("sent", message_id, None),
+            Err(error) => ("failed", None, Some(error)),
+        };
+        let entry = sqlx::query_as::<_, EmailLogEntry>(&format!(
+            r#"
+            INSERT INTO email_log (shop_domain, kind, provider, recipient, subject, status, provider_message_id, error)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING {}
+            "#,
+            EMAIL_LOG_COLUMNS
+        ))
+        .bind(shop_domain)
+        .bind(kind)
+        .bind(provider)
+        .bind(recipient)
+        .bind(subject)
+        .bind(status)
+        .bind(provider_message_id)
+        .bind(error)
+        .fetch_one(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(entry)
+    }
+    
+    /// The shop's most recent sends, newest first.
+    pub async fn list_recent(&self, shop_domain: &str, limit: i64) -> AppResult<Vec<EmailLogEntry>> {
+        let entries = sqlx::query_as::<_, EmailLogEntry>(&format!(
+            "SELECT {} FROM email_log WHERE shop_domain = $1 ORDER BY created_at DESC LIMIT $2",
+            EMAIL_LOG_COLUMNS
+        ))
+        .bind(shop_domain)
+        .bind(limit)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(entries)
+    }
+}
+
+// =============================================================================
+// Database Operations for Shop Secrets
+// =============================================================================
+
+/// Encrypted per-shop integration credentials, one row per shop and integration.
+///
+/// Values are encrypted with the same per-shop keys used for access tokens.
+/// Each row records the master key that encrypted it, so after `ENCRYPTION_KEY` changes
+/// the old key can stay in `ENCRYPTION_KEY_PREVIOUS` until `reencrypt_all` has
+/// moved every row onto the new one.
+#[derive(Clone)]
+pub struct ShopSecretStore {
+    db: DatabaseRouter,
+    current: TokenEncryption,
+    previous: Vec<TokenEncryption>,
+}
+
+impl ShopSecretStore {
+    pub fn new(db: DatabaseRouter, config: &DatabaseConfig) -> AppResult<Self> {
+        Ok(Self {
+            db,
+            current: TokenEncryption::new(&config.encryption_key)?,
+            previous: config.previous_encryption_keys
+                .iter()
+                .map(TokenEncryption::new)
+                .collect::<AppResult<_>>()?,
+        })
+    }
+    
+    fn encryption_for(&self, key_id: &str) -> AppResult<&TokenEncryption> {
+        std::iter::once(&self.current)
+            .chain(self.previous.iter())
+            .find(|e| e.key_id() == key_id)
+            .ok_or_else(|| AppError::Encryption(format!("No encryption key available for key id {}", key_id)))
+    }
+    
+    /// Stores or rotates a shop's credentials for an integration.
+    pub async fn put<T: IntegrationSecret>(&self, shop_domain: &str, value: &T) -> AppResult<()> {
+        let json = serde_json::to_string(value)
+            .map_err(|e| AppError::Encryption(format!("Failed to serialize secret: {}", e)))?;
+        let encrypted = self.current.encrypt_for_shop(shop_domain, &json)?;
+        
+        sqlx::query(
+            r#"
+            INSERT INTO shop_secrets (shop_domain, integration, encrypted_value, key_id)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (shop_domain, integration)
+            DO UPDATE SET
+                encrypted_value = EXCLUDED.encrypted_value,
+                key_id = EXCLUDED.key_id,
+                version = shop_secrets.version + 1,
+                rotated_at = NOW()
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(T::INTEGRATION)
+        .bind(&encrypted)
+        .bind(self.current.key_id())
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        info!("🔐 Stored {} credentials for shop: {}", T::INTEGRATION, shop_domain);
+        Ok(())
+    }
+    
+    pub async fn get<T: IntegrationSecret>(&self, shop_domain: &str) -> AppResult<Option<T>> {
+        let row = sqlx::query_as::<_, (String, String)>(
+            "SELECT encrypted_value, key_id FROM shop_secrets WHERE shop_domain = $1 AND integration = $2"
+        )
+        .bind(shop_domain)
+        .bind(T::INTEGRATION)
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        match row {
+            Some((encrypted_value, key_id)) => {
+                let json = self.encryption_for(&key_id)?.decrypt_for_shop(shop_domain, &encrypted_value)?;
+                let value = serde_json::from_str(&json)
+                    .map_err(|e| AppError::Encryption(format!("Stored {} secret is malformed: {}", T::INTEGRATION, e)))?;
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+    
+    pub async fn delete<T: IntegrationSecret>(&self, shop_domain: &str) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM shop_secrets WHERE shop_domain = $1 AND integration = $2")
+            .bind(shop_domain)
+            .bind(T::INTEGRATION)
+            .execute(&self.db.pool_for(shop_domain).await?)
+            .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    pub async fn list(&self, shop_domain: &str) -> AppResult<Vec<ShopSecretMetadata>> {
+        let secrets = sqlx::query_as::<_, ShopSecretMetadata>(
+            r#"
+            SELECT integration, key_id, version, created_at, updated_at, rotated_at
+            FROM shop_secrets
+            WHERE shop_domain = $1
+            ORDER BY integration ASC
+            "#,
+        )
+        .bind(shop_domain)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(secrets)
+    }
+    
+    /// Re-encrypts rows written with a previous key, or in a format older
+    /// than per-row data keys, under the current key, in every database.
+    pub async fn reencrypt_all(&self) -> AppResult<u64> {
+        let mut reencrypted = 0;
+        for pool in self.db.all_pools() {
+            reencrypted += self.reencrypt_pool(&pool).await?;
+        }
+        
+        if reencrypted > 0 {
+            info!("🔑 Re-encrypted {} shop secrets with the current key", reencrypted);
+        }
+        Ok(reencrypted)
+    }
+    
+    async fn reencrypt_pool(&self, pool: &PgPool) -> AppResult<u64> {
+        let stale = sqlx::query_as::<_, (Uuid, String, String, String)>(
+            "SELECT id, shop_domain, encrypted_value, key_id FROM shop_secrets WHERE key_id <> $1 OR encrypted_value NOT LIKE $2"
+        )
+        .bind(self.current.key_id())
+        .bind(format!("{}%", ENVELOPE_PREFIX))
+        .fetch_all(pool)
+        .await?;
+        
+        let mut reencrypted = 0;
+        for (id, shop_domain, encrypted_value, key_id) in stale {
+            let plaintext = match self.encryption_for(&key_id).and_then(|e| e.decrypt_for_shop(&shop_domain, &encrypted_value)) {
+                Ok(plaintext) => plaintext,
+                Err(e) => {
+                    warn!("Cannot re-encrypt shop secret {}: {}", id, e);
+                    continue;
+                }
+            };
+            
+            sqlx::query("UPDATE shop_secrets SET encrypted_value = $2, key_id = $3 WHERE id = $1")
+                .bind(id)
+                .bind(self.current.encrypt_for_shop(&shop_domain, &plaintext)?)
+                .bind(self.current.key_id())
+                .execute(pool)
+                .await?;
+            reencrypted += 1;
+        }
+        
+        Ok(reencrypted)
+    }
+}
+
+// =============================================================================
+// Document Template Storage
+// =============================================================================
+
+#[derive(Clone)]
+pub struct DocumentTemplateStore {
+    db: DatabaseRouter,
+}
+
+impl DocumentTemplateStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    pub async fn get(&self, shop_domain: &str, kind: DocumentKind) -> AppResult<Option<DocumentTemplate>> {
+        let row = sqlx::query_as::<_, (sqlx::types::Json<DocumentTemplate>,)>(
+            "SELECT template FROM document_templates WHERE shop_domain = $1 AND kind = $2"
+        )
+        .bind(shop_domain)
+        .bind(kind.slug())
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(row.map(|(template,)| template.0))
+    }
+    
+    pub async fn put(&self, shop_domain: &str, kind: DocumentKind, template: &DocumentTemplate) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO document_templates (shop_domain, kind, template)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (shop_domain, kind)
+            DO UPDATE SET template = EXCLUDED.template
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(kind.slug())
+        .bind(sqlx::types::Json(template))
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        info!("🧾 Saved {} template for shop: {}", kind.slug(), shop_domain);
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Fulfillment Routing Storage
+// =============================================================================
+
+#[derive(Clone)]
+pub struct FulfillmentRoutingStore {
+    db: DatabaseRouter,
+}
+
+impl FulfillmentRoutingStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    pub async fn get(&self, shop_domain: &str) -> AppResult<Option<FulfillmentRoutingConfig>> {
+        let row = sqlx::query_as::<_, (sqlx::types::Json<FulfillmentRoutingConfig>,)>(
+            "SELECT config FROM fulfillment_routing WHERE shop_domain = $1"
+        )
+        .bind(shop_domain)
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(row.map(|(config,)| config.0))
+    }
+    
+    pub async fn put(&self, shop_domain: &str, config: &FulfillmentRoutingConfig) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO fulfillment_routing (shop_domain, config)
+            VALUES ($1, $2)
+            ON CONFLICT (shop_domain)
+            DO UPDATE SET config = EXCLUDED.config
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(sqlx::types::Json(config))
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        info!("🚚 Saved fulfillment routing ({} rules) for shop: {}", config.rules.len(), shop_domain);
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Low-Stock Alerts
+// =============================================================================
+
+#[derive(Clone)]
+pub struct LowStockStore {
+    db: DatabaseRouter,
+}
+
+impl LowStockStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    pub async fn get_rules(&self, shop_domain: &str) -> AppResult<Option<LowStockRules>> {
+        let row = sqlx::query_as::<_, (sqlx::types::Json<LowStockRules>,)>(
+            "SELECT config FROM low_stock_rules WHERE shop_domain = $1"
+        )
+        .bind(shop_domain)
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(row.map(|(config,)| config.0))
+    }
+    
+    pub async fn put_rules(&self, shop_domain: &str, rules: &LowStockRules) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO low_stock_rules (shop_domain, config)
+            VALUES ($1, $2)
+            ON CONFLICT (shop_domain)
+            DO UPDATE SET config = EXCLUDED.config
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(sqlx::types::Json(rules))
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        info!("📉 Saved low-stock rules ({} rules) for shop: {}", rules.rules.len(), shop_domain);
+        Ok(())
+    }
+    
+    /// Records a stock level against `threshold`, the one its rules set or
+    /// `None` if no rule covers it any more, opening, updating or resolving
+    /// its alert.
+    pub async fn record_level(
+        &self,
+        shop_domain: &str,
+        inventory_item_id: i64,
+        location_id: i64,
+        sku: &str,
+        available: i32,
+        threshold: Option<i32>,
+    ) -> AppResult<LowStockChange> {
+        let pool = self.db.pool_for(shop_domain).await?;
+        
+        if let Some(threshold) = threshold.filter(|threshold| available <= *threshold) {
+            // A resolved alert that drops again starts over, to be notified anew
+            let (notified,) = sqlx::query_as::<_, (bool,)>(
+                r#"
+                INSERT INTO low_stock_alerts (shop_domain, inventory_item_id, location_id, sku, available, threshold)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (shop_domain, inventory_item_id, location_id)
+                DO UPDATE SET
+                    sku = EXCLUDED.sku,
+                    available = EXCLUDED.available,
+                    threshold = EXCLUDED.threshold,
+                    updated_at = NOW(),
+                    triggered_at = CASE WHEN low_stock_alerts.resolved_at IS NULL THEN low_stock_alerts.triggered_at ELSE NOW() END,
+                    notified_at = CASE WHEN low_stock_alerts.resolved_at IS NULL THEN low_stock_alerts.notified_at END,
+                    resolved_at = NULL
+                RETURNING notified_at IS NOT NULL
+                "#,
+            )
+            .bind(shop_domain)
+            .bind(inventory_item_id)
+            .bind(location_id)
+            .bind(sku)
+            .bind(available)
+            .bind(threshold)
+            .fetch_one(&pool)
+            .await?;
+            
+            return Ok(if notified { LowStockChange::StillLow } else { LowStockChange::Triggered });
+        }
+        
+        let result = sqlx::query(
+            r#"
+            UPDATE low_stock_alerts
+            SET available = $4, updated_at = NOW(), resolved_at = NOW()
+            WHERE shop_domain = $1 AND inventory_item_id = $2 AND location_id = $3 AND resolved_at IS NULL
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(inventory_item_id)
+        .bind(location_id)
+        .bind(available)
+        .execute(&pool)
+        .await?;
+        
+        Ok(if result.rows_affected() > 0 { LowStockChange::Resolved } else { LowStockChange::Unchanged })
+    }
+    
+    pub async fn mark_notified(&self, shop_domain: &str, inventory_item_id: i64, location_id: i64) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE low_stock_alerts SET notified_at = NOW()
+            WHERE shop_domain = $1 AND inventory_item_id = $2 AND location_id = $3
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(inventory_item_id)
+        .bind(location_id)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Open alerts, or with `include_resolved` every alert, most recently
+    /// triggered first.
+    pub async fn list_alerts(&self, shop_domain: &str, include_resolved: bool, limit: i64) -> AppResult<Vec<LowStockAlert>> {
+        let alerts = sqlx::query_as::<_, LowStockAlert>(
+            r#"
+            SELECT inventory_item_id, location_id, sku, available, threshold,
+                   triggered_at, updated_at, resolved_at, notified_at
+            FROM low_stock_alerts
+            WHERE shop_domain = $1 AND ($2 OR resolved_at IS NULL)
+            ORDER BY triggered_at DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(include_resolved)
+        .bind(limit)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(alerts)
+    }
+}
+
+// =============================================================================
+// Warehouse Export Watermarks
+// =============================================================================
+
+const WAREHOUSE_EXPORT_STATE_COLUMNS: &str =
+    "table_name, synced_until, last_id, rows_exported, last_exported_at, last_error, updated_at";
+
+#[derive(Clone)]
+pub struct WarehouseExportStore {
+    db: DatabaseRouter,
+}
+
+impl WarehouseExportStore {
+    pub fn new(db: DatabaseRouter) -> Self {
+        Self { db }
+    }
+    
+    /// Every table of the shop exported or tried so far.
+    pub async fn states(&self, shop_domain: &str) -> AppResult<Vec<WarehouseExportState>> {
+        let states = sqlx::query_as::<_, WarehouseExportState>(&format!(
+            "SELECT {} FROM warehouse_export_state WHERE shop_domain = $1 ORDER BY table_name",
+            WAREHOUSE_EXPORT_STATE_COLUMNS
+        ))
+        .bind(shop_domain)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(states)
+    }
+    
+    pub async fn state(&self, shop_domain: &str, table: ExportTable) -> AppResult<Option<WarehouseExportState>> {
+        let state = sqlx::query_as::<_, WarehouseExportState>(&format!(
+            "SELECT {} FROM warehouse_export_state WHERE shop_domain = $1 AND table_name = $2",
+            WAREHOUSE_EXPORT_STATE_COLUMNS
+        ))
+        .bind(shop_domain)
+        .bind(table.as_str())
+        .fetch_optional(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(state)
+    }
+    
+    /// Up to `limit` rows past the `(synced_at, id)` watermark `after`, in
+    /// that order. Rows synced at or after `settled_before` are left for the
+    /// next export, since a sync still running may yet commit rows stamped
+    /// earlier.
+    pub async fn rows_after(
+        &self,
+        shop_domain: &str,
+        table: ExportTable,
+        after: Option<(DateTime<Utc>, i64)>,
+        settled_before: DateTime<Utc>,
+        limit: i64,
+    ) -> AppResult<Vec<ExportRow>> {
+        let (source, id) = match table {
+            ExportTable::Orders => ("SELECT order_id AS id, updated_at, synced_at, data FROM orders", "order_id"),
+            ExportTable::Products => {
+                ("SELECT product_id AS id, updated_at, synced_at, data FROM catalog_products", "product_id")
+            }
+            // Customers are stored as columns rather than Shopify's JSON
+            ExportTable::Customers => (
+                r#"
+                SELECT customer_id AS id, updated_at, synced_at,
+                       jsonb_build_object(
+                           'id', customer_id, 'email', email, 'phone', phone,
+                           'first_name', first_name, 'last_name', last_name,
+                           'orders_count', orders_count, 'tags', tags, 'note', note,
+                           'created_at', created_at, 'updated_at', updated_at,
+                           'merged_into', merged_into
+                       ) AS data
+                FROM customers
+                "#,
+                "customer_id",
+            ),
+        };
+        let (synced_after, id_after) = after.unzip();
+        let rows = sqlx::query_as::<_, ExportRow>(&format!(
+            r#"
+            {}
+            WHERE shop_domain = $1
+              AND synced_at < $2
+              AND ($3::TIMESTAMPTZ IS NULL OR (synced_at, {}) > ($3, $4))
+            ORDER BY synced_at, {}
+            LIMIT $5
+            "#,
+            source, id, id
+        ))
+        .bind(shop_domain)
+        .bind(settled_before)
+        .bind(synced_after)
+        .bind(id_after)
+        .bind(limit)
+        .fetch_all(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(rows)
+    }
+    
+    /// Moves the table's watermark to a batch of `rows` just written, ending
+    /// with the row `(synced_until, last_id)`.
+    pub async fn advance(
+        &self,
+        shop_domain: &str,
+        table: ExportTable,
+        synced_until: DateTime<Utc>,
+        last_id: i64,
+        rows: i64,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO warehouse_export_state
+                (shop_domain, table_name, synced_until, last_id, rows_exported, last_exported_at, last_error)
+            VALUES ($1, $2, $3, $4, $5, NOW(), NULL)
+            ON CONFLICT (shop_domain, table_name)
+            DO UPDATE SET
+                synced_until = EXCLUDED.synced_until,
+                last_id = EXCLUDED.last_id,
+                rows_exported = warehouse_export_state.rows_exported + EXCLUDED.rows_exported,
+                last_exported_at = NOW(),
+                last_error = NULL,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(table.as_str())
+        .bind(synced_until)
+        .bind(last_id)
+        .bind(rows)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Notes a failed export, leaving the watermark where it was.
+    pub async fn record_error(&self, shop_domain: &str, table: ExportTable, error: &str) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO warehouse_export_state (shop_domain, table_name, last_error)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (shop_domain, table_name)
+            DO UPDATE SET last_error = EXCLUDED.last_error, updated_at = NOW()
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(table.as_str())
+        .bind(error)
+        .execute(&self.db.pool_for(shop_domain).await?)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Forgets the shop's watermarks, so the next export starts over.
+    pub async fn reset(&self, shop_domain: &str) -> AppResult<u64> {
+        let result = sqlx::query("DELETE FROM warehouse_export_state WHERE shop_domain = $1")
+            .bind(shop_domain)
+            .execute(&self.db.pool_for(shop_domain).await?)
+            .await?;
+        
+        Ok(result.rows_affected())
+    }
+}
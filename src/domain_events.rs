@@ -0,0 +1,268 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::{Arc, RwLock};
+use tracing::{debug, error};
+
+use crate::{
+    error::AppResult,
+    low_stock::INVENTORY_LEVEL_TOPIC,
+    order_risks::{RiskAssessmentWebhook, RiskLevel, RISK_ASSESSMENT_TOPIC},
+};
+
+// =============================================================================
+// Domain Events
+// =============================================================================
+
+/// Something that happened in a shop, read from a webhook delivery, for
+/// business logic that shouldn't care about topics and payload layouts.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DomainEvent {
+    OrderCreated {
+        order_id: u64,
+        customer_id: Option<u64>,
+        /// The checkout the order was placed from
+        checkout_id: Option<u64>,
+        total_price: Option<String>,
+        currency: Option<String>,
+    },
+    OrderUpdated { order_id: u64 },
+    OrderPaid {
+        order_id: u64,
+        total_price: Option<String>,
+        currency: Option<String>,
+    },
+    OrderCancelled {
+        order_id: u64,
+        cancel_reason: Option<String>,
+    },
+    OrderFulfilled { order_id: u64 },
+    /// A new assessment moved the order's fraud risk to `risk_level`
+    OrderRiskChanged {
+        order_id: u64,
+        risk_level: RiskLevel,
+    },
+    RefundCreated {
+        refund_id: u64,
+        order_id: Option<u64>,
+    },
+    /// `available` is unset for items whose stock isn't tracked
+    InventoryLevelUpdated {
+        inventory_item_id: u64,
+        location_id: u64,
+        available: Option<i32>,
+    },
+    ProductCreated { product_id: u64 },
+    ProductUpdated { product_id: u64 },
+    ProductDeleted { product_id: u64 },
+    CustomerCreated {
+        customer_id: u64,
+        email: Option<String>,
+    },
+    CustomerUpdated { customer_id: u64 },
+    CustomerDeleted { customer_id: u64 },
+    /// GDPR erasure request; the customer's data is gone by the time
+    /// subscribers hear of it
+    CustomerRedacted { customer_id: Option<u64> },
+    /// A checkout with contact details and no order, as Shopify lists under
+    /// abandoned checkouts. Raised again on every update until it completes.
+    CheckoutAbandoned {
+        checkout_id: u64,
+        token: Option<String>,
+        email: Option<String>,
+        total_price: Option<String>,
+        currency: Option<String>,
+    },
+    CheckoutCompleted {
+        checkout_id: u64,
+        token: Option<String>,
+    },
+    /// The shop removed the app; its access token no longer works
+    AppUninstalled {
+        shop_id: u64,
+        shop_name: Option<String>,
+    },
+}
+
+/// Every [`DomainEvent::name`], for settings that refer to events by name.
+pub const EVENT_NAMES: &[&str] = &[
+    "order_created",
+    "order_updated",
+    "order_paid",
+    "order_cancelled",
+    "order_fulfilled",
+    "order_risk_changed",
+    "refund_created",
+    "inventory_level_updated",
+    "product_created",
+    "product_updated",
+    "product_deleted",
+    "customer_created",
+    "customer_updated",
+    "customer_deleted",
+    "customer_redacted",
+    "checkout_abandoned",
+    "checkout_completed",
+    "app_uninstalled",
+];
+
+impl DomainEvent {
+    /// The event a webhook delivery stands for, if any. Checkouts without
+    /// contact details yet, and payloads missing their ID, raise nothing.
+    pub fn from_webhook(topic: &str, payload: &Value) -> Option<Self> {
+        let id = payload["id"].as_u64();
+        let text = |field: &str| payload[field].as_str().filter(|s| !s.is_empty()).map(str::to_string);
+
+        let event = match topic {
+            "orders/create" => Self::OrderCreated {
+                order_id: id?,
+                customer_id: payload["customer"]["id"].as_u64(),
+                checkout_id: payload["checkout_id"].as_u64(),
+                total_price: text("total_price"),
+                currency: text("currency"),
+            },
+            "orders/updated" => Self::OrderUpdated { order_id: id? },
+            "orders/paid" => Self::OrderPaid {
+                order_id: id?,
+                total_price: text("total_price"),
+                currency: text("currency"),
+            },
+            "orders/cancelled" => Self::OrderCancelled {
+                order_id: id?,
+                cancel_reason: text("cancel_reason"),
+            },
+            "orders/fulfilled" => Self::OrderFulfilled { order_id: id? },
+            RISK_ASSESSMENT_TOPIC => {
+                let assessment: RiskAssessmentWebhook = serde_json::from_value(payload.clone()).ok()?;
+                Self::OrderRiskChanged {
+                    order_id: assessment.order_id()?,
+                    // Pending assessments haven't changed anything yet
+                    risk_level: assessment.level()?,
+                }
+            }
+            "refunds/create" => Self::RefundCreated {
+                refund_id: id?,
+                order_id: payload["order_id"].as_u64(),
+            },
+            INVENTORY_LEVEL_TOPIC => Self::InventoryLevelUpdated {
+                inventory_item_id: payload["inventory_item_id"].as_u64()?,
+                location_id: payload["location_id"].as_u64()?,
+                available: payload["available"].as_i64().and_then(|available| i32::try_from(available).ok()),
+            },
+            "products/create" => Self::ProductCreated { product_id: id? },
+            "products/update" => Self::ProductUpdated { product_id: id? },
+            "products/delete" => Self::ProductDeleted { product_id: id? },
+            "customers/create" => Self::CustomerCreated {
+                customer_id: id?,
+                email: text("email"),
+            },
+            "customers/update" => Self::CustomerUpdated { customer_id: id? },
+            "customers/delete" => Self::CustomerDeleted { customer_id: id? },
+            "customers/redact" => Self::CustomerRedacted {
+                customer_id: payload["customer"]["id"].as_u64(),
+            },
+            "checkouts/create" | "checkouts/update" => {
+                let checkout_id = id?;
+                if !payload["completed_at"].is_null() {
+                    Self::CheckoutCompleted { checkout_id, token: text("token") }
+                } else if text("email").is_some() || text("phone").is_some() {
+                    Self::CheckoutAbandoned {
+                        checkout_id,
+                        token: text("token"),
+                        email: text("email"),
+                        total_price: text("total_price"),
+                        currency: text("currency"),
+                    }
+                } else {
+                    return None;
+                }
+            }
+            "app/uninstalled" => Self::AppUninstalled {
+                shop_id: id?,
+                shop_name: text("name"),
+            },
+            _ => return None,
+        };
+        Some(event)
+    }
+
+    /// `snake_case` name, as in the serialized `type`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::OrderCreated { .. } => "order_created",
+            Self::OrderUpdated { .. } => "order_updated",
+            Self::OrderPaid { .. } => "order_paid",
+            Self::OrderCancelled { .. } => "order_cancelled",
+            Self::OrderFulfilled { .. } => "order_fulfilled",
+            Self::OrderRiskChanged { .. } => "order_risk_changed",
+            Self::RefundCreated { .. } => "refund_created",
+            Self::InventoryLevelUpdated { .. } => "inventory_level_updated",
+            Self::ProductCreated { .. } => "product_created",
+            Self::ProductUpdated { .. } => "product_updated",
+            Self::ProductDeleted { .. } => "product_deleted",
+            Self::CustomerCreated { .. } => "customer_created",
+            Self::CustomerUpdated { .. } => "customer_updated",
+            Self::CustomerDeleted { .. } => "customer_deleted",
+            Self::CustomerRedacted { .. } => "customer_redacted",
+            Self::CheckoutAbandoned { .. } => "checkout_abandoned",
+            Self::CheckoutCompleted { .. } => "checkout_completed",
+            Self::AppUninstalled { .. } => "app_uninstalled",
+        }
+    }
+}
+
+// =============================================================================
+// Subscribers
+// =============================================================================
+
+/// Business logic run for domain events, after the webhook behind them has
+/// been applied to the local copies. A retried webhook raises its event
+/// again, so handling must be safe to repeat.
+#[async_trait]
+pub trait DomainEventSubscriber: Send + Sync {
+    /// Shown in logs and failure messages.
+    fn name(&self) -> &str;
+
+    async fn handle(&self, shop_domain: &str, event: &DomainEvent) -> AppResult<()>;
+}
+
+/// Subscribers domain events are handed to, in the order they subscribed.
+#[derive(Clone, Default)]
+pub struct DomainEventRegistry {
+    subscribers: Arc<RwLock<Vec<Arc<dyn DomainEventSubscriber>>>>,
+}
+
+impl DomainEventRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self, subscriber: Arc<dyn DomainEventSubscriber>) {
+        self.subscribers.write().unwrap().push(subscriber);
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.read().unwrap().len()
+    }
+
+    /// Hands `event` to every subscriber. One failing doesn't keep the rest
+    /// from running; the `Err` names each that failed.
+    pub async fn publish(&self, shop_domain: &str, event: &DomainEvent) -> Result<(), String> {
+        let subscribers = self.subscribers.read().unwrap().clone();
+        debug!("Publishing {} for {} to {} subscribers", event.name(), shop_domain, subscribers.len());
+
+        let mut failures = Vec::new();
+        for subscriber in subscribers {
+            if let Err(e) = subscriber.handle(shop_domain, event).await {
+                error!("{} failed to handle {} for {}: {}", subscriber.name(), event.name(), shop_domain, e);
+                failures.push(format!("{}: {}", subscriber.name(), e));
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("; "))
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{Engine as _, engine::general_purpose};
+use futures::{Stream, StreamExt};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use serde::Serialize;
+use sha2::Sha256;
+use std::fmt::Display;
+use std::path::{Component, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    csv_response::{dated_file_name, list_download, CsvRecord, ListFormat},
+    error::{AppError, AppResult},
+    key_provider::derive_purpose_key,
+    scheduler::{Job, JobScope, Schedule},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// =============================================================================
+// Download Configuration
+// =============================================================================
+
+#[derive(Clone)]
+pub struct DownloadConfig {
+    pub export_dir: PathBuf,
+    pub signing_secret: Secret<String>,
+    pub url_ttl_seconds: i64,
+}
+
+impl DownloadConfig {
+    /// `DOWNLOAD_SIGNING_SECRET` defaults to a key derived from `fallback_secret`.
+    pub fn from_env(fallback_secret: &str) -> Self {
+        Self {
+            export_dir: std::env::var("EXPORT_DIR")
+                .unwrap_or_else(|_| "./exports".to_string())
+                .into(),
+            signing_secret: std::env::var("DOWNLOAD_SIGNING_SECRET")
+                .map(Secret::new)
+                .unwrap_or_else(|_| derive_purpose_key(fallback_secret, "downloads")),
+            url_ttl_seconds: std::env::var("DOWNLOAD_URL_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+        }
+    }
+}
+
+// =============================================================================
+// URL Signing
+// =============================================================================
+
+/// Issues and verifies `/downloads/:token` links for files in the export directory.
+///
+/// A token is `base64url(path).expires_at.hex(hmac(path|expires_at))`, so links
+/// can be shared freely until they expire without exposing anything else on disk.
+#[derive(Clone)]
+pub struct DownloadSigner {
+    secret: Secret<String>,
+    ttl_seconds: i64,
+}
+
+impl DownloadSigner {
+    pub fn new(config: &DownloadConfig) -> Self {
+        Self {
+            secret: config.signing_secret.clone(),
+            ttl_seconds: config.url_ttl_seconds,
+        }
+    }
+
+    fn mac(&self, path: &str, expires_at: i64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(path.as_bytes());
+        mac.update(b"|");
+        mac.update(expires_at.to_string().as_bytes());
+        mac
+    }
+
+    pub fn sign(&self, path: &str) -> String {
+        let expires_at = chrono::Utc::now().timestamp() + self.ttl_seconds;
+        self.sign_until(path, expires_at)
+    }
+
+    pub fn sign_until(&self, path: &str, expires_at: i64) -> String {
+        let signature = hex::encode(self.mac(path, expires_at).finalize().into_bytes());
+        format!(
+            "{}.{}.{}",
+            general_purpose::URL_SAFE_NO_PAD.encode(path),
+            expires_at,
+            signature
+        )
+    }
+
+    /// Relative URL clients can use to fetch `path` until the link expires.
+    pub fn signed_url(&self, path: &str) -> String {
+        format!("/downloads/{}", self.sign(path))
+    }
+
+    /// Returns the signed path if the token is authentic and not expired.
+    pub fn verify(&self, token: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let mut parts = token.splitn(3, '.');
+        let (encoded_path, expires_at, signature) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(p), Some(e), Some(s)) => (p, e, s),
+            _ => return Err("Malformed download token".into()),
+        };
+
+        let path = String::from_utf8(general_purpose::URL_SAFE_NO_PAD.decode(encoded_path)?)?;
+        let expires_at: i64 = expires_at.parse()?;
+        let signature = hex::decode(signature)?;
+
+        // Constant-time comparison
+        self.mac(&path, expires_at)
+            .verify_slice(&signature)
+            .map_err(|_| "Invalid download signature")?;
+
+        if expires_at < chrono::Utc::now().timestamp() {
+            return Err("Download link has expired".into());
+        }
+
+        Ok(path)
+    }
+}
+
+// Only plain relative paths inside the export directory may be served
+fn resolve_export_path(export_dir: &std::path::Path, path: &str) -> Option<PathBuf> {
+    let relative = std::path::Path::new(path);
+    if relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        Some(export_dir.join(relative))
+    } else {
+        None
+    }
+}
+
+fn content_type_for(path: &str) -> &'static str {
+    match path.rsplit('.').next() {
+        Some("csv") => "text/csv; charset=utf-8",
+        Some("json") => "application/json",
+        Some("ndjson") => "application/x-ndjson",
+        Some("gz") => "application/gzip",
+        Some("zip") => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+// =============================================================================
+// Export Links
+// =============================================================================
+
+/// Answers a list export in `format`: streamed in the response, or with
+/// `link` written to a file and handed out as a signed link.
+pub async fn list_export<T, S, E>(
+    config: &DownloadConfig,
+    link: bool,
+    format: ListFormat,
+    resource: &str,
+    pages: S,
+) -> AppResult<Response>
+where
+    T: CsvRecord + Serialize + Send + 'static,
+    S: Stream<Item = Result<Vec<T>, E>> + Send + 'static,
+    E: Into<AppError> + Display + Send + 'static,
+{
+    let export = list_download(format, resource, pages).await?;
+    if link {
+        export_link(config, resource, format, export).await
+    } else {
+        Ok(export)
+    }
+}
+
+/// Writes a streamed list export into the export directory and answers with
+/// a signed `/downloads` link to it instead of the file itself. Each export
+/// gets its own directory, so file names never clash.
+pub async fn export_link(config: &DownloadConfig, resource: &str, format: ListFormat, export: Response) -> AppResult<Response> {
+    let file_name = dated_file_name(resource, format.extension());
+    let id = Uuid::new_v4().to_string();
+    let path = format!("{}/{}", id, file_name);
+    let dir = config.export_dir.join(&id);
+
+    let bytes = match write_export(&dir, &file_name, export).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to write {} export: {}", resource, e);
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            return Err(AppError::Export(e.to_string()));
+        }
+    };
+
+    info!("📦 Wrote {} export {} ({} bytes)", resource, path, bytes);
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "download_url": DownloadSigner::new(config).signed_url(&path),
+            "file_name": file_name,
+            "bytes": bytes,
+            "expires_in": config.url_ttl_seconds
+        })),
+    ).into_response())
+}
+
+async fn write_export(dir: &std::path::Path, file_name: &str, export: Response) -> std::io::Result<u64> {
+    tokio::fs::create_dir_all(dir).await?;
+    let mut file = tokio::fs::File::create(dir.join(file_name)).await?;
+    let mut body = export.into_body().into_data_stream();
+    let mut bytes = 0;
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(std::io::Error::other)?;
+        file.write_all(&chunk).await?;
+        bytes += chunk.len() as u64;
+    }
+    file.flush().await?;
+    Ok(bytes)
+}
+
+/// Deletes the export directories `export_link` created before `cutoff`, or
+/// all of them without one. Anything else in the export directory is left
+/// alone. Returns how many exports were removed.
+pub async fn purge_exports(export_dir: &std::path::Path, cutoff: Option<SystemTime>) -> std::io::Result<usize> {
+    let mut entries = match tokio::fs::read_dir(export_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut removed = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        let is_export = entry.file_name().to_str().is_some_and(|name| Uuid::parse_str(name).is_ok());
+        let metadata = entry.metadata().await?;
+        if !is_export || !metadata.is_dir() {
+            continue;
+        }
+        if let Some(cutoff) = cutoff {
+            if metadata.modified()? >= cutoff {
+                continue;
+            }
+        }
+        tokio::fs::remove_dir_all(entry.path()).await?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Removes exports once their links have expired; a link can't be signed
+/// for longer than `DOWNLOAD_URL_TTL_SECS`, so nothing older is reachable.
+/// Runs on every instance since each writes to its own disk.
+pub fn export_purge_job() -> Job {
+    Job::new("export-purge", Schedule::Every(Duration::from_secs(300)), JobScope::Instance, |state: AppState| async move {
+        let config = &state.config.downloads;
+        let ttl = Duration::from_secs(config.url_ttl_seconds.max(0) as u64);
+        let removed = purge_exports(&config.export_dir, Some(SystemTime::now() - ttl))
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(format!("{} expired exports removed", removed))
+    })
+}
+
+// =============================================================================
+// Download Handler
+// =============================================================================
+
+/// Streams an export file named by a signed, expiring download token.
+#[utoipa::path(
+    get,
+    path = "/downloads/{token}",
+    tag = "downloads",
+    params(("token" = String, Path, description = "Signed download token")),
+    responses(
+        (status = 200, description = "The export file", content(
+            ("text/csv"),
+            ("application/json"),
+            ("application/x-ndjson"),
+            ("application/gzip"),
+            ("application/zip"),
+            ("application/octet-stream"),
+        )),
+        (status = 403, description = "Invalid or expired link", body = crate::openapi::ErrorResponse),
+        (status = 404, description = "Export not found", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn download_handler(
+    Path(token): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    let signer = DownloadSigner::new(&state.config.downloads);
+
+    let path = match signer.verify(&token) {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Rejected download token: {}", e);
+            return (
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({ "error": "Invalid or expired download link" })),
+            ).into_response();
+        }
+    };
+
+    let file_path = match resolve_export_path(&state.config.downloads.export_dir, &path) {
+        Some(file_path) => file_path,
+        None => {
+            warn!("Rejected download path outside export directory: {}", path);
+            return (
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({ "error": "Invalid download path" })),
+            ).into_response();
+        }
+    };
+
+    let file = match tokio::fs::File::open(&file_path).await {
+        Ok(file) => file,
+        Err(_) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "error": "Export not found" })),
+            ).into_response();
+        }
+    };
+
+    info!("📥 Serving export download: {}", path);
+
+    let file_name = path.rsplit('/').next().unwrap_or(&path).to_string();
+    (
+        [
+            (header::CONTENT_TYPE, content_type_for(&path).to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    ).into_response()
+}
@@ -0,0 +1,200 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{AppState, api_auth::decode_hs256, error::AppResult};
+
+/// Allowed clock skew between Shopify and this server when checking
+/// `exp`/`nbf`.
+const SESSION_TOKEN_LEEWAY_SECS: i64 = 5;
+
+/// Header App Bridge looks for to fetch a fresh session token and retry.
+pub const RETRY_INVALID_SESSION_HEADER: &str = "X-Shopify-Retry-Invalid-Session-Request";
+
+/// Where Shopify's admin is served from, besides the shop's own domain.
+const SHOPIFY_ADMIN_ORIGIN: &str = "https://admin.shopify.com";
+
+// =============================================================================
+// Session Tokens
+// =============================================================================
+
+#[derive(Deserialize)]
+struct SessionTokenClaims {
+    iss: String,
+    dest: String,
+    aud: String,
+    #[serde(default)]
+    sub: Option<String>,
+    exp: i64,
+    nbf: i64,
+    #[serde(default)]
+    sid: Option<String>,
+}
+
+/// A verified App Bridge session token: the shop and staff member the
+/// embedded admin is open for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionToken {
+    pub shop: String,
+    /// Staff member's user ID; absent for tokens not tied to a user
+    pub user_id: Option<String>,
+    pub session_id: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl SessionToken {
+    /// Checks a session token's HS256 signature against the app's API secret,
+    /// its lifetime, that it was issued for this app, and that `iss` and `dest`
+    /// name the same shop.
+    pub fn verify(token: &str, api_key: &str, api_secret: &str) -> Result<Self, String> {
+        let claims: SessionTokenClaims =
+            decode_hs256(token, api_secret).ok_or_else(|| "Session token signature is invalid".to_string())?;
+
+        let now = Utc::now().timestamp();
+        if claims.exp + SESSION_TOKEN_LEEWAY_SECS <= now {
+            return Err("Session token has expired".to_string());
+        }
+        if claims.nbf - SESSION_TOKEN_LEEWAY_SECS > now {
+            return Err("Session token is not valid yet".to_string());
+        }
+        if claims.aud != api_key {
+            return Err("Session token was issued for another app".to_string());
+        }
+
+        let shop = claims
+            .dest
+            .strip_prefix("https://")
+            .filter(|shop| is_shop_domain(shop))
+            .ok_or_else(|| "Session token destination is not a shop".to_string())?;
+        if claims.iss != format!("https://{}/admin", shop) {
+            return Err("Session token issuer does not match its destination".to_string());
+        }
+
+        Ok(Self {
+            shop: shop.to_string(),
+            user_id: claims.sub,
+            session_id: claims.sid,
+            expires_at: DateTime::from_timestamp(claims.exp, 0).unwrap_or_default(),
+        })
+    }
+}
+
+/// Rejection for a missing or invalid session token. Carries the retry
+/// header so App Bridge refreshes the token and tries once more.
+#[derive(Debug)]
+pub struct SessionTokenRejection(pub String);
+
+impl IntoResponse for SessionTokenRejection {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(RETRY_INVALID_SESSION_HEADER, "1")],
+            Json(serde_json::json!({ "error": self.0 })),
+        )
+            .into_response()
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for SessionToken {
+    type Rejection = SessionTokenRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or_else(|| SessionTokenRejection("Missing session token".to_string()))?;
+
+        SessionToken::verify(token.trim(), &state.config.api_key, &state.config.api_secret).map_err(|reason| {
+            warn!("🔐 Rejected session token: {}", reason);
+            SessionTokenRejection(reason)
+        })
+    }
+}
+
+/// `<name>.myshopify.com`, the only hosts session tokens and embed requests
+/// may name.
+pub fn is_shop_domain(host: &str) -> bool {
+    host.strip_suffix(".myshopify.com").is_some_and(|name| {
+        !name.is_empty()
+            && !name.starts_with('-')
+            && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    })
+}
+
+/// `GET /embedded/session` — who the embedded admin is open for, and whether
+/// the shop has installed the app.
+#[utoipa::path(
+    get,
+    path = "/embedded/session",
+    tag = "embedded",
+    security(("session_token" = [])),
+    responses(
+        (status = 200, description = "The session's shop and user", body = crate::openapi::EmbeddedSession),
+        (status = 401, description = "Missing or invalid session token", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn embedded_session_handler(
+    session: SessionToken,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let installed = state.token_store.get_scope(&session.shop).await?.is_some();
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": session.shop,
+        "user_id": session.user_id,
+        "expires_at": session.expires_at,
+        "installed": installed
+    }))))
+}
+
+// =============================================================================
+// Frame Ancestors
+// =============================================================================
+
+/// Content-Security-Policy for a page Shopify's admin embeds for `shop`.
+pub fn embedded_content_security_policy(shop: &str) -> String {
+    format!("default-src 'self'; frame-ancestors https://{} {}", shop, SHOPIFY_ADMIN_ORIGIN)
+}
+
+/// The shop an embedded page is loaded for, from the `shop` query parameter
+/// Shopify adds when it frames the app.
+fn embedding_shop(request: &Request) -> Option<String> {
+    url::form_urlencoded::parse(request.uri().query()?.as_bytes())
+        .find(|(key, _)| key == "shop")
+        .map(|(_, shop)| shop.into_owned())
+        .filter(|shop| is_shop_domain(shop))
+}
+
+/// With `SHOPIFY_EMBEDDED_APP` on, lets the shop's admin frame responses by
+/// swapping `X-Frame-Options: DENY` for a `frame-ancestors` policy. Requests
+/// without a valid `shop` keep the standalone headers. Layer it outside
+/// `security_headers_middleware` so it has the last word.
+pub async fn frame_ancestors_middleware(
+    State(embedded): State<bool>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let shop = if embedded { embedding_shop(&request) } else { None };
+    let mut response = next.run(request).await;
+
+    if let Some(shop) = shop {
+        let headers = response.headers_mut();
+        headers.remove(header::X_FRAME_OPTIONS);
+        if let Ok(policy) = HeaderValue::from_str(&embedded_content_security_policy(&shop)) {
+            headers.insert(header::CONTENT_SECURITY_POLICY, policy);
+        }
+    }
+
+    response
+}
@@ -0,0 +1,191 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::time::Duration;
+use tracing::error;
+
+// =============================================================================
+// Shopify API Errors
+// =============================================================================
+
+/// Failures talking to the Shopify Admin API, split by what a caller can do
+/// about them: re-authenticate, back off, or give up.
+#[derive(Debug, thiserror::Error)]
+pub enum ShopifyError {
+    #[error("Invalid or expired access token. Please re-authenticate.")]
+    Unauthorized,
+
+    #[error("Insufficient permissions. Check your app's scopes.")]
+    Forbidden,
+
+    #[error("Resource not found or API endpoint unavailable.")]
+    NotFound,
+
+    #[error("Rate limit exceeded. Please try again later.")]
+    RateLimited { retry_after: Option<Duration> },
+
+    #[error("Shopify API Error {status}: {body}")]
+    Api { status: reqwest::StatusCode, body: String },
+
+    /// A GraphQL response's `errors`, which Shopify sends with a 200
+    #[error("Shopify GraphQL Error: {}", .0.join("; "))]
+    GraphQl(Vec<String>),
+
+    #[error("Failed to reach Shopify: {0}")]
+    Transport(#[from] reqwest_middleware::Error),
+
+    #[error("Failed to read Shopify response: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Failed to parse Shopify response: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+impl ShopifyError {
+    /// Maps a non-success response to the matching variant.
+    pub fn from_response(status: reqwest::StatusCode, body: String, retry_after: Option<Duration>) -> Self {
+        match status.as_u16() {
+            401 => Self::Unauthorized,
+            403 => Self::Forbidden,
+            404 => Self::NotFound,
+            429 => Self::RateLimited { retry_after },
+            _ => Self::Api { status, body },
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            // Shopify rejected the data we sent, which traces back to the caller's input
+            Self::Api { status, .. } if status.as_u16() == 422 => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Api { .. } | Self::GraphQl(_) | Self::Parse(_) => StatusCode::BAD_GATEWAY,
+            Self::Transport(e) if is_timeout(e) => StatusCode::GATEWAY_TIMEOUT,
+            Self::Transport(_) | Self::Http(_) => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+
+fn is_timeout(error: &reqwest_middleware::Error) -> bool {
+    matches!(error, reqwest_middleware::Error::Reqwest(e) if e.is_timeout())
+}
+
+// =============================================================================
+// Application Errors
+// =============================================================================
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error(transparent)]
+    Shopify(#[from] ShopifyError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Database migration failed: {0}")]
+    Migration(#[from] sqlx::migrate::MigrateError),
+
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+
+    #[error("Token encryption error: {0}")]
+    Encryption(String),
+
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    #[error("Failed to generate document: {0}")]
+    Document(String),
+
+    #[error("Failed to write export: {0}")]
+    Export(String),
+
+    /// The mail provider didn't take an email
+    #[error("Failed to send email: {0}")]
+    Email(String),
+
+    #[error("No access token found. Please complete OAuth flow first.")]
+    MissingToken,
+
+    #[error("Missing access scopes: {}. Reauthorize to grant them.", missing.join(", "))]
+    MissingScopes { missing: Vec<String>, reauthorize_url: String },
+
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Conflict(String),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Shopify(e) => e.status_code(),
+            Self::MissingToken => StatusCode::UNAUTHORIZED,
+            Self::MissingScopes { .. } => StatusCode::FORBIDDEN,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Email(_) => StatusCode::BAD_GATEWAY,
+            Self::Database(_) | Self::Migration(_) | Self::Redis(_) | Self::Encryption(_) | Self::Config(_) | Self::Document(_)
+            | Self::Export(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+impl From<std::env::VarError> for AppError {
+    fn from(e: std::env::VarError) -> Self {
+        Self::Config(e.to_string())
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        if status.is_server_error() {
+            error!("Request failed: {}", self);
+        }
+
+        let mut body = serde_json::json!({ "error": self.to_string() });
+
+        match &self {
+            Self::MissingToken | Self::Shopify(ShopifyError::Unauthorized) => {
+                body["auth_url"] = "/auth".into();
+            }
+            Self::MissingScopes { missing, reauthorize_url } => {
+                body["missing_scopes"] = missing.clone().into();
+                body["auth_url"] = reauthorize_url.clone().into();
+            }
+            Self::Shopify(ShopifyError::Api { body: details, .. }) => {
+                body["details"] = details.clone().into();
+            }
+            _ => {}
+        }
+
+        let retry_after = match &self {
+            Self::Shopify(ShopifyError::RateLimited { retry_after: Some(delay) }) => {
+                Some(delay.as_secs_f64().ceil() as u64)
+            }
+            _ => None,
+        };
+
+        match retry_after {
+            Some(seconds) => {
+                body["retry_after"] = seconds.into();
+                (status, [(header::RETRY_AFTER, seconds.to_string())], Json(body)).into_response()
+            }
+            None => (status, Json(body)).into_response(),
+        }
+    }
+}
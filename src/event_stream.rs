@@ -0,0 +1,478 @@
+use async_stream::stream;
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{interval_at, timeout, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    api_auth::{sign_jwt, verify_jwt, ApiArea, ApiGuard, ApiPrincipal, ApiScope},
+    error::{AppError, AppResult},
+    key_provider::derive_purpose_key,
+    webhook_queue::QueuedWebhook,
+    webhooks::SUPPORTED_WEBHOOKS,
+};
+
+/// Events kept for a subscriber that falls behind before it misses some.
+const EVENT_BUFFER: usize = 1024;
+
+/// How often a WebSocket client is pinged; one silent for two of these is
+/// dropped.
+const SOCKET_HEARTBEAT: Duration = Duration::from_secs(30);
+
+/// How long a WebSocket client gets to take a message before it's dropped
+/// as too slow.
+const SOCKET_SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+const MAX_SOCKET_SUBSCRIPTIONS: usize = 32;
+
+/// Largest message a WebSocket client may send; subscriptions are small.
+const MAX_SOCKET_MESSAGE: usize = 64 * 1024;
+
+/// How long a `/ws/token` ticket can be used to open a socket.
+const SOCKET_TICKET_TTL_SECS: i64 = 60;
+
+// =============================================================================
+// Broadcasting
+// =============================================================================
+
+/// A verified webhook delivery as pushed to live subscribers.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StoreEvent {
+    /// Captured delivery in `webhook_events`, if storing it worked
+    pub id: Option<Uuid>,
+    pub shop_domain: String,
+    pub topic: String,
+    pub resource_id: Option<i64>,
+    pub webhook_id: Option<String>,
+    pub received_at: DateTime<Utc>,
+    pub payload: serde_json::Value,
+}
+
+/// Fans verified webhook deliveries out to every connected subscriber on
+/// this instance. Publishing never waits: a subscriber that falls more than
+/// `EVENT_BUFFER` events behind skips ahead and is told how many it missed.
+#[derive(Clone)]
+pub struct EventBroadcaster {
+    sender: broadcast::Sender<Arc<StoreEvent>>,
+    /// Ends every stream, so graceful shutdown isn't held up by open ones
+    shutdown: CancellationToken,
+}
+
+impl Default for EventBroadcaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBroadcaster {
+    pub fn new() -> Self {
+        Self { sender: broadcast::channel(EVENT_BUFFER).0, shutdown: CancellationToken::new() }
+    }
+
+    pub fn publish(&self, webhook: &QueuedWebhook) {
+        // Nobody listening isn't an error
+        let _ = self.sender.send(Arc::new(StoreEvent {
+            id: webhook.event_id,
+            shop_domain: webhook.shop_domain.clone(),
+            topic: webhook.topic.clone(),
+            resource_id: webhook.resource_id,
+            webhook_id: webhook.webhook_id.clone(),
+            received_at: Utc::now(),
+            payload: webhook.payload.clone(),
+        }));
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<StoreEvent>> {
+        self.sender.subscribe()
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Ends every open event stream and socket, and any opened later.
+    pub fn close(&self) {
+        self.shutdown.cancel();
+    }
+}
+
+// =============================================================================
+// Filters
+// =============================================================================
+
+/// Which events a subscriber wants. Topic patterns are exact (`orders/create`),
+/// a resource (`orders/*`) or everything (`*`); no topics or shops means all.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventFilter {
+    pub topics: Vec<String>,
+    pub shops: Vec<String>,
+}
+
+fn topic_matches(pattern: &str, topic: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(resource) => topic.split('/').next() == Some(resource),
+        None => pattern == "*" || pattern == topic,
+    }
+}
+
+fn split_list(list: Option<&str>) -> Vec<String> {
+    list.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl EventFilter {
+    /// Builds a filter from comma-separated topic patterns and shop domains.
+    /// A pattern that matches none of the supported topics is refused.
+    pub fn parse(topics: Option<&str>, shops: Option<&str>) -> Result<Self, String> {
+        Self::new(split_list(topics), split_list(shops))
+    }
+
+    pub fn new(topics: Vec<String>, shops: Vec<String>) -> Result<Self, String> {
+        if let Some(unknown) = topics
+            .iter()
+            .find(|pattern| !SUPPORTED_WEBHOOKS.iter().any(|webhook| topic_matches(pattern, webhook.topic)))
+        {
+            return Err(format!("No supported webhook topic matches {}", unknown));
+        }
+        Ok(Self { topics, shops })
+    }
+
+    pub fn matches(&self, event: &StoreEvent) -> bool {
+        (self.topics.is_empty() || self.topics.iter().any(|pattern| topic_matches(pattern, &event.topic)))
+            && (self.shops.is_empty() || self.shops.iter().any(|shop| shop.eq_ignore_ascii_case(&event.shop_domain)))
+    }
+}
+
+// =============================================================================
+// Server-Sent Events
+// =============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct EventStreamParams {
+    /// Comma-separated topics, e.g. `orders/create,customers/*`
+    pub topic: Option<String>,
+    /// Comma-separated shop domains
+    pub shop: Option<String>,
+}
+
+/// `GET /events/stream` — verified webhook deliveries as Server-Sent Events,
+/// one per delivery, named after its topic with the captured event ID as
+/// the SSE `id`. A client too slow to keep up gets a `lagged` event with
+/// the number it missed. Only deliveries received by this instance are
+/// streamed.
+#[utoipa::path(
+    get,
+    path = "/events/stream",
+    tag = "webhooks",
+    params(EventStreamParams),
+    responses(
+        (status = 200, description = "A stream of `StoreEvent`s", content_type = "text/event-stream", body = StoreEvent),
+        (status = 400, description = "Unknown topic", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn event_stream_handler(
+    State(state): State<AppState>,
+    Query(params): Query<EventStreamParams>,
+) -> AppResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let filter = EventFilter::parse(params.topic.as_deref(), params.shop.as_deref()).map_err(AppError::BadRequest)?;
+    let mut events = state.events.subscribe();
+    let shutdown = state.events.shutdown.clone();
+    debug!("Event stream opened ({} subscribers)", state.events.subscribers());
+
+    let stream = stream! {
+        loop {
+            let received = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                received = events.recv() => received,
+            };
+            match received {
+                Ok(event) if filter.matches(&event) => match Event::default().event(&event.topic).json_data(&*event) {
+                    Ok(sse) => yield Ok(match event.id {
+                        Some(id) => sse.id(id.to_string()),
+                        None => sse,
+                    }),
+                    Err(e) => warn!("Failed to encode {} event for streaming: {}", event.topic, e),
+                },
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    yield Ok(Event::default().event("lagged").data(format!("{{\"skipped\":{}}}", skipped)));
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// =============================================================================
+// Socket Tickets
+// =============================================================================
+
+/// A short-lived token that opens one `/ws` connection, for browsers, which
+/// can't set headers on a WebSocket handshake.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SocketTicket {
+    /// Pass as `/ws?token=...`
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// Tickets are JWTs under a key of their own, so no other token opens a socket
+fn ticket_key(state: &AppState) -> Secret<String> {
+    derive_purpose_key(&state.config.api_secret, "event-socket")
+}
+
+/// `GET /ws/token` — a ticket that opens `/ws` within the next minute.
+#[utoipa::path(
+    get,
+    path = "/ws/token",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "A ticket for `/ws`", body = SocketTicket),
+        (status = 401, description = "Invalid or missing API credentials", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn event_socket_ticket_handler(
+    State(state): State<AppState>,
+    principal: Option<Extension<ApiPrincipal>>,
+) -> Json<SocketTicket> {
+    let subject = principal.map_or_else(|| "anonymous".to_string(), |Extension(principal)| principal.name);
+    let expires_at = Utc::now() + chrono::Duration::seconds(SOCKET_TICKET_TTL_SECS);
+    let token = sign_jwt(ticket_key(&state).expose_secret(), &subject, &[ApiScope::Read], expires_at.timestamp());
+    Json(SocketTicket { token, expires_at })
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct EventSocketParams {
+    /// A ticket from `/ws/token`, in place of API credentials
+    pub token: Option<String>,
+}
+
+/// Refuses cross-site handshakes, then checks the ticket if there is one and
+/// the request's API credentials if not.
+fn authorize_socket(state: &AppState, headers: &HeaderMap, ticket: Option<&str>) -> Result<(), (StatusCode, String)> {
+    // Browsers always send Origin; other clients may leave it out
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        let app_origin = reqwest::Url::parse(&state.config.redirect_uri).map(|url| url.origin().ascii_serialization());
+        if app_origin.as_deref().ok() != origin.to_str().ok() {
+            return Err((StatusCode::FORBIDDEN, "Origin not allowed".to_string()));
+        }
+    }
+
+    match ticket {
+        Some(ticket) => verify_jwt(ticket, ticket_key(state).expose_secret())
+            .filter(|principal| principal.scopes.contains(&ApiScope::Read))
+            .map(|_| ())
+            .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Invalid or expired socket token".to_string())),
+        None => ApiGuard::new(&state.config.api_auth, ApiArea::Api).check(&Method::GET, headers).map(|_| ()),
+    }
+}
+
+// =============================================================================
+// WebSocket
+// =============================================================================
+
+/// A message from a WebSocket client, tagged by `type`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe {
+        #[serde(default)]
+        topics: Vec<String>,
+        #[serde(default)]
+        shops: Vec<String>,
+    },
+    Unsubscribe {
+        id: u64,
+    },
+    Ping,
+}
+
+fn socket_error(message: impl Into<String>) -> Value {
+    json!({ "type": "error", "message": message.into() })
+}
+
+/// The subscriptions of one WebSocket connection, each with an ID the
+/// client uses to unsubscribe and to tell which one an event matched.
+#[derive(Debug, Default)]
+pub struct SocketSubscriptions {
+    filters: Vec<(u64, EventFilter)>,
+    last_id: u64,
+}
+
+impl SocketSubscriptions {
+    /// Applies a client message and returns the reply to send back.
+    pub fn handle(&mut self, message: &str) -> Value {
+        let message = match serde_json::from_str::<ClientMessage>(message) {
+            Ok(message) => message,
+            Err(e) => return socket_error(format!("Invalid message: {}", e)),
+        };
+        match message {
+            ClientMessage::Subscribe { topics, shops } => {
+                if self.filters.len() >= MAX_SOCKET_SUBSCRIPTIONS {
+                    return socket_error(format!("At most {} subscriptions per connection", MAX_SOCKET_SUBSCRIPTIONS));
+                }
+                match EventFilter::new(topics, shops) {
+                    Ok(filter) => {
+                        self.last_id += 1;
+                        let reply = json!({
+                            "type": "subscribed",
+                            "id": self.last_id,
+                            "topics": filter.topics,
+                            "shops": filter.shops,
+                        });
+                        self.filters.push((self.last_id, filter));
+                        reply
+                    }
+                    Err(e) => socket_error(e),
+                }
+            }
+            ClientMessage::Unsubscribe { id } => {
+                let before = self.filters.len();
+                self.filters.retain(|(subscription, _)| *subscription != id);
+                if self.filters.len() < before {
+                    json!({ "type": "unsubscribed", "id": id })
+                } else {
+                    socket_error(format!("No subscription {}", id))
+                }
+            }
+            ClientMessage::Ping => json!({ "type": "pong" }),
+        }
+    }
+
+    /// IDs of the subscriptions that want an event, empty if none does.
+    pub fn matching(&self, event: &StoreEvent) -> Vec<u64> {
+        self.filters.iter().filter(|(_, filter)| filter.matches(event)).map(|(id, _)| *id).collect()
+    }
+}
+
+/// `GET /ws` — verified webhook deliveries pushed over a WebSocket. Clients
+/// send `{"type": "subscribe", "topics": ["orders/*"], "shops": [...]}` and
+/// get each matching delivery as `{"type": "event", "subscriptions": [...],
+/// "event": {...}}`. The server pings every 30 seconds and drops clients
+/// that stay silent or can't take messages fast enough. Handshakes from
+/// browsers must come from the app's own origin.
+#[utoipa::path(
+    get,
+    path = "/ws",
+    tag = "webhooks",
+    params(EventSocketParams),
+    responses(
+        (status = 101, description = "Switched to a WebSocket carrying `StoreEvent`s"),
+        (status = 400, description = "Not a WebSocket upgrade request", body = crate::openapi::ErrorResponse),
+        (status = 401, description = "Invalid or missing credentials or token", body = crate::openapi::ErrorResponse),
+        (status = 403, description = "Cross-site handshake", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn event_socket_handler(
+    State(state): State<AppState>,
+    Query(params): Query<EventSocketParams>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if let Err((status, message)) = authorize_socket(&state, &headers, params.token.as_deref()) {
+        warn!("🔐 Rejected event socket: {}", message);
+        return (status, Json(json!({ "error": message }))).into_response();
+    }
+    let events = state.events.clone();
+    ws.max_message_size(MAX_SOCKET_MESSAGE)
+        .on_upgrade(move |socket| serve_event_socket(socket, events))
+}
+
+async fn serve_event_socket(mut socket: WebSocket, events: EventBroadcaster) {
+    let mut receiver = events.subscribe();
+    let mut subscriptions = SocketSubscriptions::default();
+    let mut heartbeat = interval_at(Instant::now() + SOCKET_HEARTBEAT, SOCKET_HEARTBEAT);
+    let mut last_heard = Instant::now();
+    debug!("Event socket opened ({} subscribers)", events.subscribers());
+
+    loop {
+        let outgoing = tokio::select! {
+            _ = events.shutdown.cancelled() => {
+                let close = Message::Close(Some(CloseFrame {
+                    code: close_code::AWAY,
+                    reason: "Server shutting down".into(),
+                }));
+                let _ = timeout(Duration::from_secs(1), socket.send(close)).await;
+                break;
+            }
+            message = socket.recv() => {
+                last_heard = Instant::now();
+                match message {
+                    Some(Ok(Message::Text(text))) => Message::Text(subscriptions.handle(&text).to_string()),
+                    Some(Ok(Message::Binary(_))) => Message::Text(socket_error("Messages must be JSON text").to_string()),
+                    // Pongs only count as a sign of life; pings are answered for us
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Err(e)) => {
+                        debug!("Event socket failed: {}", e);
+                        break;
+                    }
+                }
+            }
+            event = receiver.recv() => match event {
+                Ok(event) => {
+                    let matching = subscriptions.matching(&event);
+                    if matching.is_empty() {
+                        continue;
+                    }
+                    Message::Text(json!({ "type": "event", "subscriptions": matching, "event": &*event }).to_string())
+                }
+                Err(RecvError::Lagged(skipped)) => Message::Text(json!({ "type": "lagged", "skipped": skipped }).to_string()),
+                Err(RecvError::Closed) => break,
+            },
+            _ = heartbeat.tick() => {
+                if last_heard.elapsed() > SOCKET_HEARTBEAT * 2 {
+                    debug!("Closing silent event socket");
+                    break;
+                }
+                Message::Ping(Vec::new())
+            }
+        };
+
+        match timeout(SOCKET_SEND_TIMEOUT, socket.send(outgoing)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                debug!("Event socket failed: {}", e);
+                break;
+            }
+            Err(_) => {
+                warn!("Closing an event socket that stopped taking messages");
+                let close = Message::Close(Some(CloseFrame {
+                    code: close_code::AGAIN,
+                    reason: "Too slow to keep up".into(),
+                }));
+                let _ = timeout(Duration::from_secs(1), socket.send(close)).await;
+                break;
+            }
+        }
+    }
+}
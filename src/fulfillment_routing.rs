@@ -0,0 +1,359 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    AppState,
+    require_token,
+    error::{AppError, AppResult, ShopifyError},
+    http_client::ShopifyClient,
+    shopify_api::{fetch_fulfillment_orders, fetch_inventory_levels, FulfillmentOrder, InventoryParams},
+};
+
+// =============================================================================
+// Routing Configuration
+// =============================================================================
+
+/// Location preferences for destinations matching `countries` (and
+/// `provinces`, when given). Codes are ISO, e.g. `CA` and `ON`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct RoutingRule {
+    pub name: String,
+    pub countries: Vec<String>,
+    pub provinces: Vec<String>,
+    /// Locations to try in order
+    pub location_priority: Vec<u64>,
+}
+
+impl RoutingRule {
+    fn matches(&self, destination: &Destination) -> bool {
+        let country = destination.country_code.as_deref().unwrap_or_default();
+        let province = destination.province_code.as_deref().unwrap_or_default();
+
+        self.countries.iter().any(|c| c.eq_ignore_ascii_case(country))
+            && (self.provinces.is_empty() || self.provinces.iter().any(|p| p.eq_ignore_ascii_case(province)))
+    }
+}
+
+/// Per-shop fulfillment routing. Rules are checked in order; the first that
+/// matches the destination supplies the location priority list.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct FulfillmentRoutingConfig {
+    pub rules: Vec<RoutingRule>,
+    /// Used when no rule matches the destination
+    pub default_priority: Vec<u64>,
+    /// When no location has everything, pick the one with the most units
+    /// instead of leaving the order where Shopify assigned it
+    pub fallback_to_best_coverage: bool,
+}
+
+// =============================================================================
+// Routing Decisions
+// =============================================================================
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Destination {
+    pub country_code: Option<String>,
+    pub province_code: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingReason {
+    /// The first location in priority order with every item in stock
+    InStock,
+    /// No location had everything; this one had the most units
+    BestCoverage,
+    /// Nothing better was found, so the assigned location is kept
+    Unchanged,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RoutingDecision {
+    pub location_id: u64,
+    pub rule: Option<String>,
+    pub reason: RoutingReason,
+}
+
+/// Available units keyed by `(location_id, inventory_item_id)`.
+pub type Availability = HashMap<(u64, u64), i32>;
+
+/// Chooses where a set of items should ship from. `items` are
+/// `(inventory_item_id, quantity)` pairs and `assigned_location` is where
+/// Shopify put the fulfillment order.
+pub fn choose_location(
+    config: &FulfillmentRoutingConfig,
+    destination: &Destination,
+    items: &[(u64, i32)],
+    availability: &Availability,
+    assigned_location: u64,
+) -> RoutingDecision {
+    let rule = config.rules.iter().find(|rule| rule.matches(destination));
+    let priority = rule.map(|r| &r.location_priority).unwrap_or(&config.default_priority);
+    let rule_name = rule.map(|r| r.name.clone());
+
+    let available = |location: u64, item: u64| availability.get(&(location, item)).copied().unwrap_or(0);
+
+    if let Some(&location_id) = priority
+        .iter()
+        .find(|&&location| items.iter().all(|&(item, quantity)| available(location, item) >= quantity))
+    {
+        return RoutingDecision { location_id, rule: rule_name, reason: RoutingReason::InStock };
+    }
+
+    if config.fallback_to_best_coverage {
+        let coverage = |location: u64| -> i32 {
+            items.iter().map(|&(item, quantity)| available(location, item).clamp(0, quantity)).sum()
+        };
+        // max_by_key keeps the last maximum, so walk the list backwards to prefer higher priority
+        if let Some(&location_id) = priority.iter().rev().filter(|&&l| coverage(l) > 0).max_by_key(|&&l| coverage(l)) {
+            return RoutingDecision { location_id, rule: rule_name, reason: RoutingReason::BestCoverage };
+        }
+    }
+
+    RoutingDecision { location_id: assigned_location, rule: rule_name, reason: RoutingReason::Unchanged }
+}
+
+// =============================================================================
+// Fulfillment Orders
+// =============================================================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RoutedFulfillmentOrder {
+    fulfillment_order_id: u64,
+    assigned_location_id: u64,
+    decision: RoutingDecision,
+    moved: bool,
+    fulfillment_requested: bool,
+    error: Option<String>,
+}
+
+async fn plan_routes(
+    client: &ShopifyClient,
+    token: &str,
+    config: &FulfillmentRoutingConfig,
+    order_id: u64,
+) -> AppResult<Vec<(FulfillmentOrder, RoutingDecision)>> {
+    // Only orders still waiting on a location can be moved
+    let open: Vec<FulfillmentOrder> = fetch_fulfillment_orders(client, token, order_id, false)
+        .await?
+        .into_iter()
+        .filter(|fo| fo.status == "open")
+        .collect();
+
+    let mut item_ids: Vec<u64> = open.iter().flat_map(|fo| fo.line_items.iter().map(|li| li.inventory_item_id)).collect();
+    item_ids.sort_unstable();
+    item_ids.dedup();
+
+    let availability = if item_ids.is_empty() {
+        Availability::new()
+    } else {
+        let params = InventoryParams {
+            limit: Some(250),
+            inventory_item_ids: Some(item_ids.iter().map(u64::to_string).collect::<Vec<_>>().join(",")),
+            location_ids: None,
+            updated_at_min: None,
+        };
+        fetch_inventory_levels(client, token, &params)
+            .await?
+            .into_iter()
+            .map(|level| ((level.location_id, level.inventory_item_id), level.available.unwrap_or(0)))
+            .collect()
+    };
+
+    Ok(open
+        .into_iter()
+        .map(|fo| {
+            let items: Vec<(u64, i32)> = fo.line_items.iter().map(|li| (li.inventory_item_id, li.fulfillable_quantity)).collect();
+            let destination = fo.destination.as_ref().map(|d| Destination {
+                country_code: d.country_code.clone(),
+                province_code: d.province_code.clone(),
+            }).unwrap_or_default();
+            let decision = choose_location(config, &destination, &items, &availability, fo.assigned_location_id);
+            (fo, decision)
+        })
+        .collect())
+}
+
+async fn move_and_request(
+    client: &ShopifyClient,
+    token: &str,
+    fulfillment_order: &FulfillmentOrder,
+    decision: &RoutingDecision,
+) -> Result<bool, ShopifyError> {
+    let mut fulfillment_order_id = fulfillment_order.id;
+    let moved = decision.location_id != fulfillment_order.assigned_location_id;
+
+    if moved {
+        let body = serde_json::json!({ "fulfillment_order": { "new_location_id": decision.location_id } });
+        let response: serde_json::Value = client
+            .post_with_auth(&format!("fulfillment_orders/{}/move.json", fulfillment_order.id), token, &body)
+            .await?;
+        // A partial move splits the order; the moved part has its own ID
+        if let Some(id) = response["moved_fulfillment_order"]["id"].as_u64() {
+            fulfillment_order_id = id;
+        }
+    }
+
+    let body = serde_json::json!({ "fulfillment_request": { "message": "Routed by fulfillment rules" } });
+    client
+        .post_with_auth::<_, serde_json::Value>(
+            &format!("fulfillment_orders/{}/fulfillment_request.json", fulfillment_order_id),
+            token,
+            &body,
+        )
+        .await?;
+
+    Ok(moved)
+}
+
+// =============================================================================
+// Fulfillment Routing Handlers
+// =============================================================================
+
+/// Shows where each open fulfillment order would be routed, without changing anything.
+#[utoipa::path(
+    get,
+    path = "/api/orders/{id}/fulfillment-route",
+    tag = "fulfillments",
+    params(("id" = u64, Path, description = "Order ID")),
+    responses(
+        (status = 200, description = "Where each open fulfillment order would go", body = crate::openapi::FulfillmentRoutes),
+    ),
+)]
+pub async fn preview_fulfillment_route_handler(
+    Path(order_id): Path<u64>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+    let config = state.fulfillment_routing.get(shop).await?.unwrap_or_default();
+
+    let routes: Vec<RoutedFulfillmentOrder> = plan_routes(&state.shopify, &token, &config, order_id)
+        .await?
+        .into_iter()
+        .map(|(fo, decision)| RoutedFulfillmentOrder {
+            fulfillment_order_id: fo.id,
+            assigned_location_id: fo.assigned_location_id,
+            moved: decision.location_id != fo.assigned_location_id,
+            decision,
+            fulfillment_requested: false,
+            error: None,
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "order_id": order_id,
+        "dry_run": true,
+        "fulfillment_orders": routes
+    }))))
+}
+
+/// Moves each open fulfillment order to its routed location and requests fulfillment there.
+#[utoipa::path(
+    post,
+    path = "/api/orders/{id}/fulfillment-route",
+    tag = "fulfillments",
+    params(("id" = u64, Path, description = "Order ID"), ("Idempotency-Key" = Option<String>, Header, description = "Makes retries with the same key and body return the first response")),
+    responses(
+        (status = 200, description = "What happened to each open fulfillment order", body = crate::openapi::FulfillmentRoutes),
+    ),
+)]
+pub async fn route_fulfillment_handler(
+    Path(order_id): Path<u64>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+    let config = state.fulfillment_routing.get(shop).await?.unwrap_or_default();
+
+    let mut routes = Vec::new();
+    for (fo, decision) in plan_routes(&state.shopify, &token, &config, order_id).await? {
+        let result = move_and_request(&state.shopify, &token, &fo, &decision).await;
+        if let Err(ref e) = result {
+            warn!("Failed to route fulfillment order {} for order {}: {}", fo.id, order_id, e);
+        }
+
+        routes.push(RoutedFulfillmentOrder {
+            fulfillment_order_id: fo.id,
+            assigned_location_id: fo.assigned_location_id,
+            moved: *result.as_ref().unwrap_or(&false),
+            fulfillment_requested: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+            decision,
+        });
+    }
+    info!("🚚 Routed {} fulfillment orders for order {}", routes.len(), order_id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "order_id": order_id,
+        "dry_run": false,
+        "fulfillment_orders": routes
+    }))))
+}
+
+/// The shop's fulfillment routing rules, or the defaults when none are stored.
+#[utoipa::path(
+    get,
+    path = "/admin/shops/{shop}/fulfillment-routing",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 200, description = "The shop's routing rules", body = crate::openapi::FulfillmentRouting),
+    ),
+)]
+pub async fn get_fulfillment_routing_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let config = state.fulfillment_routing.get(&shop).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "configured": config.is_some(),
+        "routing": config.unwrap_or_default()
+    }))))
+}
+
+/// Replaces the shop's fulfillment routing rules.
+#[utoipa::path(
+    put,
+    path = "/admin/shops/{shop}/fulfillment-routing",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    request_body = FulfillmentRoutingConfig,
+    responses(
+        (status = 200, description = "The stored rules", body = crate::openapi::FulfillmentRoutingUpdated),
+        (status = 400, description = "A rule without countries or locations", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn put_fulfillment_routing_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+    Json(config): Json<FulfillmentRoutingConfig>,
+) -> AppResult<impl IntoResponse> {
+    if let Some(rule) = config.rules.iter().find(|r| r.countries.is_empty() || r.location_priority.is_empty()) {
+        return Err(AppError::BadRequest(format!(
+            "Routing rule \"{}\" needs at least one country and one location",
+            rule.name
+        )));
+    }
+
+    state.fulfillment_routing.put(&shop, &config).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "shop": shop,
+        "routing": config
+    }))))
+}
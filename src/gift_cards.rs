@@ -0,0 +1,295 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{info, error};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    require_token,
+    error::{AppError, AppResult, ShopifyError},
+    http_client::{next_page_url, PaginatedResponse, ShopifyClient},
+    shopify_api::apply_page_info,
+};
+
+// =============================================================================
+// Gift Card Structures
+// =============================================================================
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct GiftCard {
+    pub id: u64,
+    pub balance: Decimal,
+    pub initial_value: Decimal,
+    pub currency: String,
+    /// Full code, only returned when the card is created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub last_characters: String,
+    pub customer_id: Option<u64>,
+    pub order_id: Option<u64>,
+    pub line_item_id: Option<u64>,
+    pub note: Option<String>,
+    pub expires_on: Option<String>,
+    pub template_suffix: Option<String>,
+    pub disabled_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct GiftCardsResponse {
+    pub gift_cards: Vec<GiftCard>,
+}
+
+#[derive(Deserialize)]
+pub struct GiftCardResponse {
+    pub gift_card: GiftCard,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GiftCardParams {
+    pub limit: Option<u32>,
+    pub since_id: Option<u64>,
+    /// enabled or disabled
+    pub status: Option<String>,
+    pub fields: Option<String>,
+    pub page_info: Option<String>,
+}
+
+/// Body for `POST /api/gift-cards`
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateGiftCardRequest {
+    pub initial_value: Decimal,
+    /// 8-20 letters and digits; Shopify generates one when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// `YYYY-MM-DD`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_suffix: Option<String>,
+}
+
+impl CreateGiftCardRequest {
+    pub fn validate(&self) -> AppResult<()> {
+        if self.initial_value <= Decimal::ZERO {
+            return Err(AppError::BadRequest("initial_value must be greater than zero".to_string()));
+        }
+
+        if let Some(ref code) = self.code {
+            if !(8..=20).contains(&code.len()) || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(AppError::BadRequest(
+                    "Gift card codes must be 8-20 letters and digits".to_string(),
+                ));
+            }
+        }
+
+        if let Some(ref expires_on) = self.expires_on {
+            chrono::NaiveDate::parse_from_str(expires_on, "%Y-%m-%d")
+                .map_err(|_| AppError::BadRequest("expires_on must be a YYYY-MM-DD date".to_string()))?;
+        }
+
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Gift Card Handlers
+// =============================================================================
+
+/// Lists gift cards.
+#[utoipa::path(
+    get,
+    path = "/api/gift-cards",
+    tag = "gift cards",
+    params(GiftCardParams),
+    responses(
+        (status = 200, description = "A page of gift cards", body = crate::openapi::GiftCardList),
+    ),
+)]
+pub async fn gift_cards_handler(
+    Query(params): Query<GiftCardParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let page = fetch_gift_cards(&state.shopify, &token, &params).await.map_err(|e| {
+        error!("Failed to fetch gift cards: {}", e);
+        e
+    })?;
+    let gift_cards = page.data;
+    info!("Successfully fetched {} gift cards", gift_cards.len());
+    let next_page = next_page_url("/api/gift-cards", page.page_info.as_ref(), params.limit.unwrap_or(50));
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "gift_cards_count": gift_cards.len(),
+        "gift_cards": gift_cards,
+        "page_info": page.page_info,
+        "next_page": next_page
+    }))))
+}
+
+/// Fetches one gift card.
+#[utoipa::path(
+    get,
+    path = "/api/gift-cards/{id}",
+    tag = "gift cards",
+    params(("id" = u64, Path, description = "Gift card ID")),
+    responses(
+        (status = 200, description = "The gift card", body = crate::openapi::GiftCardDetail),
+        (status = 404, description = "No such gift card", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn gift_card_handler(
+    Path(gift_card_id): Path<u64>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let response = state.shopify
+        .get_with_auth::<GiftCardResponse>(&format!("gift_cards/{}.json", gift_card_id), &token, None)
+        .await
+        .map_err(|e| gift_card_not_found(e, gift_card_id))?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "gift_card": response.data.gift_card
+    }))))
+}
+
+/// Issues a gift card.
+#[utoipa::path(
+    post,
+    path = "/api/gift-cards",
+    tag = "gift cards",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Makes retries with the same key and body return the first response")),
+    request_body = CreateGiftCardRequest,
+    responses(
+        (status = 201, description = "The new gift card, with its full code", body = crate::openapi::GiftCardChange),
+        (status = 400, description = "Invalid value, code or expiry", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn create_gift_card_handler(
+    State(state): State<AppState>,
+    Json(request): Json<CreateGiftCardRequest>,
+) -> AppResult<impl IntoResponse> {
+    request.validate()?;
+    let token = require_token(&state.token_store, &state.config.shop).await?;
+
+    let body = serde_json::json!({ "gift_card": request });
+    let response: GiftCardResponse = state.shopify
+        .post_with_auth("gift_cards.json", &token, &body)
+        .await
+        .map_err(|e| {
+            error!("Failed to create gift card: {}", e);
+            e
+        })?;
+    info!(
+        "🎁 Created gift card {} ending {} worth {} {}",
+        response.gift_card.id,
+        response.gift_card.last_characters,
+        response.gift_card.initial_value,
+        response.gift_card.currency
+    );
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({
+        "success": true,
+        "gift_card": response.gift_card
+    }))))
+}
+
+/// Disables a gift card permanently; Shopify has no way to re-enable one.
+#[utoipa::path(
+    post,
+    path = "/api/gift-cards/{id}/disable",
+    tag = "gift cards",
+    params(("id" = u64, Path, description = "Gift card ID")),
+    responses(
+        (status = 200, description = "The disabled gift card", body = crate::openapi::GiftCardChange),
+        (status = 404, description = "No such gift card", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn disable_gift_card_handler(
+    Path(gift_card_id): Path<u64>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let token = require_token(&state.token_store, &state.config.shop).await?;
+
+    let body = serde_json::json!({ "gift_card": { "id": gift_card_id } });
+    let response: GiftCardResponse = state.shopify
+        .post_with_auth(&format!("gift_cards/{}/disable.json", gift_card_id), &token, &body)
+        .await
+        .map_err(|e| gift_card_not_found(e, gift_card_id))?;
+    info!("🎁 Disabled gift card {}", gift_card_id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "gift_card": response.gift_card
+    }))))
+}
+
+// =============================================================================
+// Gift Card Fetch Functions
+// =============================================================================
+
+async fn fetch_gift_cards(
+    client: &ShopifyClient,
+    token: &str,
+    params: &GiftCardParams,
+) -> Result<PaginatedResponse<Vec<GiftCard>>, ShopifyError> {
+    let mut query_params = vec![("limit", params.limit.unwrap_or(50).to_string())];
+
+    if let Some(since_id) = params.since_id {
+        query_params.push(("since_id", since_id.to_string()));
+    }
+
+    if let Some(ref status) = params.status {
+        query_params.push(("status", status.clone()));
+    }
+
+    if let Some(ref fields) = params.fields {
+        query_params.push(("fields", fields.clone()));
+    }
+
+    apply_page_info(&mut query_params, params.page_info.as_deref());
+
+    let query_params_ref: Vec<(&str, &str)> = query_params.iter()
+        .map(|(k, v)| (k as &str, v as &str))
+        .collect();
+
+    let page = client
+        .get_with_auth::<GiftCardsResponse>("gift_cards.json", token, Some(&query_params_ref))
+        .await?
+        .into_paginated();
+
+    Ok(PaginatedResponse {
+        data: page.data.gift_cards,
+        page_info: page.page_info,
+    })
+}
+
+fn gift_card_not_found(error: ShopifyError, gift_card_id: u64) -> AppError {
+    match error {
+        ShopifyError::NotFound => AppError::NotFound(format!("Gift card {} not found", gift_card_id)),
+        e => {
+            error!("Gift card request for {} failed: {}", gift_card_id, e);
+            e.into()
+        }
+    }
+}
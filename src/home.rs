@@ -0,0 +1,19 @@
+use axum::{extract::State, response::IntoResponse};
+
+use crate::pages::{render, BrandingConfig, HomePage};
+
+// =============================================================================
+// Home Page
+// =============================================================================
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "docs",
+    responses(
+        (status = 200, description = "Setup instructions and an endpoint overview", content_type = "text/html"),
+    ),
+)]
+pub async fn home_handler(State(brand): State<BrandingConfig>) -> impl IntoResponse {
+    render(&HomePage { brand: &brand })
+}
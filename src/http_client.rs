@@ -1,44 +1,632 @@
-use reqwest::Client;
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::{RetryTransientMiddleware, policies::ExponentialBackoff};
+use futures::Stream;
+use reqwest::{header::HeaderMap, Client, Request, Response};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Middleware, Next};
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
-use tracing::{info, error};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn, error};
+
+use crate::api_usage::{ApiUsageMiddleware, ApiUsageRecorder};
+use crate::error::ShopifyError;
+
+// =============================================================================
+// Call Limit Tracking
+// =============================================================================
+
+#[derive(Debug, Clone, Copy)]
+struct BucketState {
+    used: u32,
+    max: u32,
+    observed_at: Instant,
+    blocked_until: Option<Instant>,
+    // Calls queued or in flight that Shopify hasn't reported back on yet
+    pending: u32,
+}
+
+impl BucketState {
+    // Shopify's standard REST bucket until a response says otherwise
+    fn new(now: Instant) -> Self {
+        Self {
+            used: 0,
+            max: 40,
+            observed_at: now,
+            blocked_until: None,
+            pending: 0,
+        }
+    }
+
+    // Shopify's leaky bucket drains at max/20 calls per second (2/s for the standard 40)
+    fn leak_per_second(&self) -> f64 {
+        (self.max as f64 / 20.0).max(1.0)
+    }
+
+    fn estimated_used(&self, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.observed_at).as_secs_f64();
+        (self.used as f64 - elapsed * self.leak_per_second()).max(0.0)
+    }
+
+    fn delay_at(&self, now: Instant) -> Option<Duration> {
+        if let Some(until) = self.blocked_until {
+            if until > now {
+                return Some(until - now);
+            }
+        }
+
+        // Keep 10% of the bucket as headroom for other callers
+        let threshold = self.max as f64 - (self.max as f64 / 10.0).max(1.0);
+        let estimated = self.estimated_used(now) + self.pending as f64;
+        if estimated >= threshold {
+            Some(Duration::from_secs_f64((estimated - threshold + 1.0) / self.leak_per_second()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Per-shop view of Shopify's API call bucket, shared by every client in the
+/// process so concurrent handlers back off together instead of collecting 429s.
+///
+/// Calls reserve their place in the bucket before they are sent, so a burst
+/// of handlers and background jobs hitting one shop is queued and released
+/// at the bucket's leak rate rather than all going out at once.
+#[derive(Clone, Default)]
+pub struct CallLimitTracker {
+    buckets: Arc<Mutex<HashMap<String, BucketState>>>,
+}
+
+impl CallLimitTracker {
+    pub fn shared() -> Self {
+        static SHARED: OnceLock<CallLimitTracker> = OnceLock::new();
+        SHARED.get_or_init(CallLimitTracker::default).clone()
+    }
+
+    /// How long a request to `shop` should wait before being sent, if at all.
+    pub fn throttle_delay(&self, shop: &str) -> Option<Duration> {
+        let buckets = self.buckets.lock().unwrap();
+        buckets.get(shop).and_then(|bucket| bucket.delay_at(Instant::now()))
+    }
+
+    /// Reserves a slot in the shop's bucket behind any calls already queued
+    /// and waits for its turn. A call that would wait longer than `max_wait`
+    /// is shed with `RateLimited` instead. Hold the reservation until the
+    /// response has been recorded.
+    pub async fn wait_for_capacity(&self, shop: &str, max_wait: Duration) -> Result<CallReservation, ShopifyError> {
+        let delay = {
+            let mut buckets = self.buckets.lock().unwrap();
+            let now = Instant::now();
+            let bucket = buckets.entry(shop.to_string()).or_insert_with(|| BucketState::new(now));
+
+            let delay = bucket.delay_at(now).unwrap_or_default();
+            if delay > max_wait {
+                warn!("🚦 Shedding Shopify request for {}: bucket needs {:?}, limit is {:?}", shop, delay, max_wait);
+                return Err(ShopifyError::RateLimited { retry_after: Some(delay) });
+            }
+            bucket.pending += 1;
+            delay
+        };
+        let reservation = CallReservation { tracker: self.clone(), shop: shop.to_string() };
+
+        if !delay.is_zero() {
+            info!("⏳ Throttling Shopify request for {} by {:?}", shop, delay);
+            tokio::time::sleep(delay).await;
+        }
+
+        // A 429 that came back while this call was queued holds it back too
+        while let Some(delay) = self.blocked_delay(shop) {
+            tokio::time::sleep(delay).await;
+        }
+
+        Ok(reservation)
+    }
+
+    fn blocked_delay(&self, shop: &str) -> Option<Duration> {
+        let buckets = self.buckets.lock().unwrap();
+        let now = Instant::now();
+        buckets
+            .get(shop)
+            .and_then(|bucket| bucket.blocked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+
+    /// Updates the shop's bucket from the headers of a completed call.
+    pub fn record(&self, shop: &str, call_limit: Option<(u32, u32)>, retry_after: Option<Duration>) {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let bucket = buckets.entry(shop.to_string()).or_insert_with(|| BucketState::new(now));
+
+        if let Some((used, max)) = call_limit {
+            bucket.used = used;
+            bucket.max = max;
+            bucket.observed_at = now;
+        }
+
+        if let Some(retry_after) = retry_after {
+            warn!("Shopify asked us to retry {} after {:?}", shop, retry_after);
+            bucket.blocked_until = Some(now + retry_after);
+        }
+    }
+}
+
+/// A call's place in its shop's bucket, released when dropped.
+pub struct CallReservation {
+    tracker: CallLimitTracker,
+    shop: String,
+}
+
+impl Drop for CallReservation {
+    fn drop(&mut self) {
+        let mut buckets = self.tracker.buckets.lock().unwrap();
+        if let Some(bucket) = buckets.get_mut(&self.shop) {
+            bucket.pending = bucket.pending.saturating_sub(1);
+        }
+    }
+}
+
+// =============================================================================
+// Retry Middleware
+// =============================================================================
+
+/// Which retry policy a request falls under. Reads are safe to repeat;
+/// writes may already have been applied when a response goes missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointClass {
+    Read,
+    Write,
+}
+
+impl EndpointClass {
+    pub fn for_method(method: &reqwest::Method) -> Self {
+        if method.is_safe() {
+            Self::Read
+        } else {
+            Self::Write
+        }
+    }
+}
+
+/// Retry behavior for one endpoint class.
+///
+/// When Shopify sends `Retry-After` the wait follows it exactly; otherwise the
+/// delay backs off exponentially between `min_backoff` and `max_backoff`,
+/// shortened at random by up to `jitter` of itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts including the first; 1 disables retries
+    pub max_attempts: u32,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Response statuses worth another attempt
+    pub retry_statuses: Vec<u16>,
+    /// Retry requests that timed out. Connection failures are always retried,
+    /// since the request never reached Shopify.
+    pub retry_timeouts: bool,
+    /// Share of each backoff delay that is randomized, from 0 (none) to 1, so
+    /// requests that failed together don't all retry together
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    /// 429 and 5xx, with timeouts, up to 4 attempts.
+    pub fn read_default() -> Self {
+        Self {
+            max_attempts: 4,
+            min_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            retry_statuses: parse_retry_statuses("429,5xx").unwrap_or_default(),
+            retry_timeouts: true,
+            jitter: 0.2,
+        }
+    }
+
+    /// Only 429, which Shopify returns before doing any work, up to 3 attempts.
+    /// A 5xx or timeout may hide a write that went through.
+    pub fn write_default() -> Self {
+        Self {
+            max_attempts: 3,
+            min_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            retry_statuses: vec![429],
+            retry_timeouts: false,
+            jitter: 0.2,
+        }
+    }
+
+    /// `<prefix>_MIN_BACKOFF_MS`, `_MAX_BACKOFF_MS` and `_JITTER` on top of `self`.
+    fn with_backoff_vars(self, var: &impl Fn(&str) -> Option<String>, prefix: &str) -> Self {
+        let var = |name: &str| var(&format!("{}_{}", prefix, name));
+        let millis = |name: &str, default: Duration| {
+            var(name)
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(default)
+        };
+
+        Self {
+            min_backoff: millis("MIN_BACKOFF_MS", self.min_backoff),
+            max_backoff: millis("MAX_BACKOFF_MS", self.max_backoff),
+            jitter: var("JITTER")
+                .and_then(|v| v.parse().ok())
+                .filter(|j| (0.0..=1.0).contains(j))
+                .unwrap_or(self.jitter),
+            ..self
+        }
+    }
+
+    /// Every `<prefix>_*` setting on top of `self`.
+    fn with_vars(self, var: &impl Fn(&str) -> Option<String>, prefix: &str) -> Self {
+        let policy = self.with_backoff_vars(var, prefix);
+        let var = |name: &str| var(&format!("{}_{}", prefix, name));
+
+        Self {
+            max_attempts: var("MAX_ATTEMPTS")
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(policy.max_attempts),
+            retry_statuses: var("STATUSES")
+                .and_then(|v| parse_retry_statuses(&v))
+                .unwrap_or_else(|| policy.retry_statuses.clone()),
+            retry_timeouts: var("TIMEOUTS")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(policy.retry_timeouts),
+            ..policy
+        }
+    }
+
+    /// The longest wait before retry `attempt`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.min_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    /// `backoff` shortened by `roll` (in `0..1`) of its jitter.
+    pub fn jittered_backoff(&self, attempt: u32, roll: f64) -> Duration {
+        self.backoff(attempt).mul_f64(1.0 - self.jitter * roll.clamp(0.0, 1.0))
+    }
+
+    /// How long to wait before retrying, or `None` if the outcome is final.
+    pub fn retry_delay(&self, result: &reqwest_middleware::Result<Response>, attempt: u32) -> Option<Duration> {
+        match result {
+            Ok(response) => {
+                if !self.retry_statuses.contains(&response.status().as_u16()) {
+                    return None;
+                }
+                let retry_after = response.headers()
+                    .get("retry-after")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_retry_after);
+                Some(retry_after.unwrap_or_else(|| self.jittered_backoff(attempt, fastrand::f64())))
+            }
+            Err(reqwest_middleware::Error::Reqwest(e))
+                if e.is_connect() || (self.retry_timeouts && e.is_timeout()) =>
+            {
+                Some(self.jittered_backoff(attempt, fastrand::f64()))
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+/// Parses a status list such as `429,502,503` or `429,5xx`. `None` if any
+/// entry is invalid.
+pub fn parse_retry_statuses(value: &str) -> Option<Vec<u16>> {
+    let mut statuses = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.strip_suffix("xx").and_then(|class| class.parse::<u16>().ok()) {
+            Some(class @ 1..=5) => statuses.extend(class * 100..class * 100 + 100),
+            Some(_) => return None,
+            None => statuses.push(entry.parse().ok().filter(|s| (100..600).contains(s))?),
+        }
+    }
+    Some(statuses)
+}
+
+/// Per-class retry policies for the Shopify client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryConfig {
+    pub read: RetryPolicy,
+    pub write: RetryPolicy,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            read: RetryPolicy::read_default(),
+            write: RetryPolicy::write_default(),
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// `SHOPIFY_RETRY_*` settings apply to reads, and their backoff and
+    /// jitter to writes too; `SHOPIFY_RETRY_READ_*` and `SHOPIFY_RETRY_WRITE_*`
+    /// override them per class. More write attempts or statuses risk
+    /// duplicates, so they have to be asked for by name, and
+    /// `SHOPIFY_RETRY_WRITES=false` sends every write exactly once.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let read = RetryPolicy::read_default()
+            .with_vars(&var, "SHOPIFY_RETRY")
+            .with_vars(&var, "SHOPIFY_RETRY_READ");
+        let mut write = RetryPolicy::write_default()
+            .with_backoff_vars(&var, "SHOPIFY_RETRY")
+            .with_vars(&var, "SHOPIFY_RETRY_WRITE");
+        if var("SHOPIFY_RETRY_WRITES").is_some_and(|v| v.trim().eq_ignore_ascii_case("false")) {
+            write.max_attempts = 1;
+        }
+
+        Self { read, write }
+    }
+
+    pub fn policy(&self, class: EndpointClass) -> &RetryPolicy {
+        match class {
+            EndpointClass::Read => &self.read,
+            EndpointClass::Write => &self.write,
+        }
+    }
+}
+
+/// Retries failed Shopify requests under the policy for their endpoint class.
+#[derive(Clone, Debug)]
+pub struct ShopifyRetryMiddleware {
+    pub config: RetryConfig,
+}
+
+#[async_trait::async_trait]
+impl Middleware for ShopifyRetryMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut task_local_extensions::Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let policy = self.config.policy(EndpointClass::for_method(req.method()));
+        let mut attempt = 1;
+
+        loop {
+            // Streaming bodies can't be replayed, so they get a single attempt
+            let request = match req.try_clone() {
+                Some(request) => request,
+                None => return next.run(req, extensions).await,
+            };
+
+            let result = next.clone().run(request, extensions).await;
+
+            match policy.retry_delay(&result, attempt) {
+                Some(delay) if attempt < policy.max_attempts => {
+                    warn!(
+                        "🔁 Retrying {} {} in {:?} (attempt {}/{})",
+                        req.method(), req.url().path(), delay, attempt + 1, policy.max_attempts
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+}
+
+// =============================================================================
+// HTTP Client Configuration
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct HttpClientConfig {
+    pub api_version: String,
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub pool_idle_timeout: Duration,
+    pub pool_max_idle_per_host: usize,
+    /// HTTPS proxy for outbound Shopify traffic; hosts in `NO_PROXY` bypass it
+    pub proxy_url: Option<String>,
+    pub retry: RetryConfig,
+    /// Longest a call may queue for room in the shop's bucket before it is
+    /// rejected as rate limited
+    pub throttle_max_wait: Duration,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            api_version: "2025-04".to_string(),
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            pool_idle_timeout: Duration::from_secs(90),
+            pool_max_idle_per_host: 10,
+            proxy_url: None,
+            retry: RetryConfig::default(),
+            throttle_max_wait: Duration::from_secs(10),
+        }
+    }
+}
+
+impl HttpClientConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let secs = |name: &str, default: Duration| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default)
+        };
+
+        Self {
+            api_version: std::env::var("SHOPIFY_API_VERSION").unwrap_or(defaults.api_version),
+            timeout: secs("HTTP_TIMEOUT_SECS", defaults.timeout),
+            connect_timeout: secs("HTTP_CONNECT_TIMEOUT_SECS", defaults.connect_timeout),
+            pool_idle_timeout: secs("SHOPIFY_POOL_IDLE_TIMEOUT_SECS", defaults.pool_idle_timeout),
+            pool_max_idle_per_host: std::env::var("SHOPIFY_POOL_MAX_IDLE_PER_HOST")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.pool_max_idle_per_host),
+            proxy_url: std::env::var("HTTPS_PROXY")
+                .or_else(|_| std::env::var("https_proxy"))
+                .ok()
+                .filter(|v| !v.is_empty()),
+            retry: RetryConfig::from_env(),
+            throttle_max_wait: secs("SHOPIFY_THROTTLE_MAX_WAIT_SECS", defaults.throttle_max_wait),
+        }
+    }
+}
+
+// Hides credentials embedded in proxy URLs before logging them
+fn redact_proxy_url(proxy_url: &str) -> String {
+    match url::Url::parse(proxy_url) {
+        Ok(mut url) if !url.username().is_empty() || url.password().is_some() => {
+            let _ = url.set_username("***");
+            let _ = url.set_password(None);
+            url.to_string()
+        }
+        Ok(url) => url.to_string(),
+        Err(_) => "<invalid proxy url>".to_string(),
+    }
+}
 
 // =============================================================================
 // HTTP Client with Retry Logic
 // =============================================================================
 
+/// Shopify Admin API client. Built once at startup and stored in `AppState`;
+/// clones share the same connection pool and call-limit tracker.
 #[derive(Clone)]
 pub struct ShopifyClient {
     client: ClientWithMiddleware,
+    http: Client,
+    shop_domain: String,
     base_url: String,
+    /// Sends every shop's requests here instead of `https://{shop}`
+    origin: Option<String>,
     api_version: String,
+    call_limits: CallLimitTracker,
+    throttle_max_wait: Duration,
 }
 
 impl ShopifyClient {
-    pub fn new(shop_domain: &str, api_version: Option<&str>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let retry_policy = ExponentialBackoff::builder()
-            .retry_bounds(Duration::from_millis(100), Duration::from_secs(10))
-            .build_with_max_retries(3);
+    pub fn new(shop_domain: &str, config: &HttpClientConfig) -> Result<Self, ShopifyError> {
+        let mut builder = Client::builder()
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .user_agent("Shopify OAuth Rust App/1.0");
+
+        if let Some(ref proxy_url) = config.proxy_url {
+            let proxy = reqwest::Proxy::https(proxy_url)?.no_proxy(reqwest::NoProxy::from_env());
+            builder = builder.proxy(proxy);
+            info!("🌐 Routing Shopify requests through proxy {}", redact_proxy_url(proxy_url));
+        }
 
-        let client = ClientBuilder::new(Client::new())
-            .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+        let http = builder.build()?;
+        let client = ClientBuilder::new(http.clone())
+            .with(ShopifyRetryMiddleware { config: config.retry.clone() })
+            .with(ApiUsageMiddleware { recorder: ApiUsageRecorder::shared() })
             .build();
 
         Ok(Self {
             client,
+            http,
+            shop_domain: shop_domain.to_string(),
             base_url: format!("https://{}", shop_domain),
-            api_version: api_version.unwrap_or("2025-04").to_string(),
+            origin: None,
+            api_version: config.api_version.clone(),
+            call_limits: CallLimitTracker::shared(),
+            throttle_max_wait: config.throttle_max_wait,
         })
     }
 
+    /// The same client pointed at another shop, sharing the connection pool
+    /// and call-limit tracker.
+    pub fn for_shop(&self, shop_domain: &str) -> Self {
+        Self {
+            shop_domain: shop_domain.to_string(),
+            base_url: self.shop_url(shop_domain),
+            ..self.clone()
+        }
+    }
+
+    /// The same client sending every shop's requests to `origin`, such as a
+    /// mock Shopify in tests.
+    pub fn with_origin(&self, origin: &str) -> Self {
+        let origin = origin.trim_end_matches('/').to_string();
+        Self {
+            base_url: origin.clone(),
+            origin: Some(origin),
+            ..self.clone()
+        }
+    }
+
+    /// Where `shop_domain`'s Admin API and OAuth endpoints live.
+    pub fn shop_url(&self, shop_domain: &str) -> String {
+        match self.origin {
+            Some(ref origin) => origin.clone(),
+            None => format!("https://{}", shop_domain),
+        }
+    }
+
+    /// The underlying pooled client, for calls outside the Admin API such as
+    /// the OAuth token exchange. Requests made with it are not retried.
+    pub fn http(&self) -> &Client {
+        &self.http
+    }
+
+    pub fn api_version(&self) -> &str {
+        &self.api_version
+    }
+
+    /// Admin API versions the shop currently knows about, from `/admin/api.json`.
+    pub async fn fetch_api_versions(&self, token: &str) -> Result<Vec<ApiVersionInfo>, ShopifyError> {
+        let url = format!("{}/admin/api.json", self.base_url);
+
+        let response = self.client
+            .get(&url)
+            .header("X-Shopify-Access-Token", token)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_for_response(response, "").await);
+        }
+
+        let apis: ApiListResponse = serde_json::from_slice(&response.bytes().await?)?;
+        Ok(apis.apis
+            .into_iter()
+            .find(|api| api.handle == "admin")
+            .map(|api| api.versions)
+            .unwrap_or_default())
+    }
+
+    /// Scopes Shopify actually granted to `token`, from
+    /// `/admin/oauth/access_scopes.json`. The endpoint is not versioned.
+    pub async fn fetch_access_scopes(&self, token: &str) -> Result<Vec<String>, ShopifyError> {
+        let url = format!("{}/admin/oauth/access_scopes.json", self.base_url);
+
+        let response = self.client
+            .get(&url)
+            .header("X-Shopify-Access-Token", token)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_for_response(response, "").await);
+        }
+
+        let scopes: AccessScopesResponse = serde_json::from_slice(&response.bytes().await?)?;
+        Ok(scopes.access_scopes.into_iter().map(|scope| scope.handle).collect())
+    }
+
     pub async fn get_with_auth<T: for<'de> Deserialize<'de>>(
         &self,
         endpoint: &str,
         token: &str,
         query_params: Option<&[(&str, &str)]>,
-    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<ShopifyResponse<T>, ShopifyError> {
         let mut url = format!("{}/admin/api/{}/{}", self.base_url, self.api_version, endpoint);
         
         if let Some(params) = query_params {
@@ -53,64 +641,406 @@ impl ShopifyClient {
 
         info!("🔄 Making Shopify API request to: {}", url);
 
+        let _reservation = self.call_limits.wait_for_capacity(&self.shop_domain, self.throttle_max_wait).await?;
+
         let response = self.client
             .get(&url)
             .header("X-Shopify-Access-Token", token)
             .header("Content-Type", "application/json")
-            .header("User-Agent", "Shopify OAuth Rust App/1.0")
             .send()
             .await?;
 
         let status = response.status();
+        self.record_call_limits(response.headers());
         
         if !status.is_success() {
-            let error_text = response.text().await?;
-            error!("Shopify API Error {}: {}", status, error_text);
-            
-            match status.as_u16() {
-                401 => return Err("Invalid or expired access token. Please re-authenticate.".into()),
-                403 => return Err("Insufficient permissions. Check your app's scopes.".into()),
-                404 => return Err("Resource not found or API endpoint unavailable.".into()),
-                429 => return Err("Rate limit exceeded. Please try again later.".into()),
-                _ => return Err(format!("Shopify API Error {}: {}", status, error_text).into()),
-            }
+            return Err(error_for_response(response, "").await);
         }
 
-        let response_json: T = response.json().await?;
-        Ok(response_json)
+        let headers = response.headers().clone();
+        let data: T = serde_json::from_slice(&response.bytes().await?)?;
+        Ok(ShopifyResponse { data, headers })
+    }
+
+    /// Fetches a `{resource}/count.json` endpoint with the given filters.
+    pub async fn get_count(
+        &self,
+        endpoint: &str,
+        token: &str,
+        query_params: &[(&str, &str)],
+    ) -> Result<u64, ShopifyError> {
+        let response = self.get_with_auth::<CountResponse>(endpoint, token, Some(query_params)).await?;
+        Ok(response.data.count)
+    }
+
+    fn record_call_limits(&self, headers: &HeaderMap) {
+        let call_limit = headers
+            .get("x-shopify-shop-api-call-limit")
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_call_limit);
+        let retry_after = headers
+            .get("retry-after")
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after);
+
+        self.call_limits.record(&self.shop_domain, call_limit, retry_after);
+    }
+
+    /// Streams every page of a list endpoint, following `page_info` cursors
+    /// until Shopify stops returning a `rel="next"` link.
+    ///
+    /// Each page goes through the shared call-limit tracker, so long exports
+    /// slow down as the shop's bucket fills instead of tripping Shopify's limiter.
+    pub fn get_all_pages<'a, T>(
+        &'a self,
+        endpoint: &'a str,
+        token: &'a str,
+        query_params: Vec<(String, String)>,
+    ) -> impl Stream<Item = Result<T, ShopifyError>> + Send + 'a
+    where
+        T: for<'de> Deserialize<'de> + Send + 'a,
+    {
+        async_stream::try_stream! {
+            let mut params = query_params;
+            let mut pages = 0u32;
+
+            loop {
+                let response = {
+                    let params_ref: Vec<(&str, &str)> = params.iter()
+                        .map(|(k, v)| (k.as_str(), v.as_str()))
+                        .collect();
+                    self.get_with_auth::<T>(endpoint, token, Some(&params_ref)).await?
+                };
+
+                let next_cursor = response.page_info().and_then(|p| p.next_page_info);
+                pages += 1;
+
+                yield response.data;
+
+                let cursor = match next_cursor {
+                    Some(cursor) => cursor,
+                    None => break,
+                };
+
+                // Shopify rejects filters on cursor requests
+                params.retain(|(k, _)| k == "limit" || k == "fields");
+                params.push(("page_info".to_string(), cursor));
+
+            }
+
+            info!("✅ Fetched {} pages from {}", pages, endpoint);
+        }
     }
 
-    #[allow(dead_code)]
     pub async fn post_with_auth<T: Serialize, R: for<'de> Deserialize<'de>>(
         &self,
         endpoint: &str,
         token: &str,
         body: &T,
-    ) -> Result<R, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<R, ShopifyError> {
         let url = format!("{}/admin/api/{}/{}", self.base_url, self.api_version, endpoint);
         
         info!("🔄 Making Shopify API POST request to: {}", url);
 
+        let _reservation = self.call_limits.wait_for_capacity(&self.shop_domain, self.throttle_max_wait).await?;
+
         let response = self.client
             .post(&url)
             .header("X-Shopify-Access-Token", token)
             .header("Content-Type", "application/json")
-            .header("User-Agent", "Shopify OAuth Rust App/1.0")
             .json(body)
             .send()
             .await?;
 
         let status = response.status();
+        self.record_call_limits(response.headers());
         
         if !status.is_success() {
-            let error_text = response.text().await?;
-            error!("Shopify API POST Error {}: {}", status, error_text);
-            return Err(format!("Shopify API POST Error {}: {}", status, error_text).into());
+            return Err(error_for_response(response, "POST ").await);
         }
 
-        let response_json: R = response.json().await?;
+        let response_json: R = serde_json::from_slice(&response.bytes().await?)?;
         Ok(response_json)
     }
+
+    pub async fn put_with_auth<T: Serialize, R: for<'de> Deserialize<'de>>(
+        &self,
+        endpoint: &str,
+        token: &str,
+        body: &T,
+    ) -> Result<R, ShopifyError> {
+        let url = format!("{}/admin/api/{}/{}", self.base_url, self.api_version, endpoint);
+        
+        info!("🔄 Making Shopify API PUT request to: {}", url);
+
+        let _reservation = self.call_limits.wait_for_capacity(&self.shop_domain, self.throttle_max_wait).await?;
+
+        let response = self.client
+            .put(&url)
+            .header("X-Shopify-Access-Token", token)
+            .header("Content-Type", "application/json")
+            .json(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        self.record_call_limits(response.headers());
+        
+        if !status.is_success() {
+            return Err(error_for_response(response, "PUT ").await);
+        }
+
+        let response_json: R = serde_json::from_slice(&response.bytes().await?)?;
+        Ok(response_json)
+    }
+
+    /// Runs a GraphQL Admin API query, for what the REST endpoints can't
+    /// express. Throttled and access-denied responses map to the REST
+    /// variants; other `errors` fail the whole query.
+    pub async fn graphql_with_auth<R: for<'de> Deserialize<'de>>(
+        &self,
+        token: &str,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<R, ShopifyError> {
+        let url = format!("{}/admin/api/{}/graphql.json", self.base_url, self.api_version);
+
+        info!("🔄 Making Shopify GraphQL request to: {}", url);
+
+        let _reservation = self.call_limits.wait_for_capacity(&self.shop_domain, self.throttle_max_wait).await?;
+
+        let response = self.client
+            .post(&url)
+            .header("X-Shopify-Access-Token", token)
+            .header("Content-Type", "application/json")
+            .json(&serde_json::json!({ "query": query, "variables": variables }))
+            .send()
+            .await?;
+
+        let status = response.status();
+        self.record_call_limits(response.headers());
+
+        if !status.is_success() {
+            return Err(error_for_response(response, "GraphQL ").await);
+        }
+
+        let response: GraphQlResponse<R> = serde_json::from_slice(&response.bytes().await?)?;
+        if !response.errors.is_empty() {
+            let code = |code: &str| response.errors.iter().any(|e| e.extensions.as_ref().and_then(|x| x.code.as_deref()) == Some(code));
+            if code("THROTTLED") {
+                return Err(ShopifyError::RateLimited { retry_after: None });
+            }
+            if code("ACCESS_DENIED") {
+                return Err(ShopifyError::Forbidden);
+            }
+            let messages: Vec<String> = response.errors.into_iter().map(|e| e.message).collect();
+            error!("Shopify GraphQL Error: {}", messages.join("; "));
+            return Err(ShopifyError::GraphQl(messages));
+        }
+        response.data.ok_or_else(|| ShopifyError::GraphQl(vec!["Response has no data".to_string()]))
+    }
+
+    pub async fn delete_with_auth(&self, endpoint: &str, token: &str) -> Result<(), ShopifyError> {
+        let url = format!("{}/admin/api/{}/{}", self.base_url, self.api_version, endpoint);
+        
+        info!("🔄 Making Shopify API DELETE request to: {}", url);
+
+        let _reservation = self.call_limits.wait_for_capacity(&self.shop_domain, self.throttle_max_wait).await?;
+
+        let response = self.client
+            .delete(&url)
+            .header("X-Shopify-Access-Token", token)
+            .send()
+            .await?;
+
+        let status = response.status();
+        self.record_call_limits(response.headers());
+        
+        if !status.is_success() {
+            return Err(error_for_response(response, "DELETE ").await);
+        }
+
+        Ok(())
+    }
+
+    /// Uninstalls the app from the shop by revoking `token`. The endpoint is
+    /// not versioned; a token Shopify already rejects counts as revoked.
+    pub async fn revoke_access(&self, token: &str) -> Result<(), ShopifyError> {
+        let url = format!("{}/admin/api_permissions/current.json", self.base_url);
+
+        info!("🔄 Revoking Shopify access for {}", self.shop_domain);
+
+        let response = self.client
+            .delete(&url)
+            .header("X-Shopify-Access-Token", token)
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            reqwest::StatusCode::UNAUTHORIZED => Ok(()),
+            _ => Err(error_for_response(response, "DELETE ").await),
+        }
+    }
+}
+
+// Logs a failed response and converts it into the matching ShopifyError
+#[derive(Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlError {
+    message: String,
+    extensions: Option<GraphQlErrorExtensions>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlErrorExtensions {
+    code: Option<String>,
+}
+
+async fn error_for_response(response: Response, method: &str) -> ShopifyError {
+    let status = response.status();
+    let retry_after = response.headers()
+        .get("retry-after")
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+
+    let error_text = match response.text().await {
+        Ok(text) => text,
+        Err(e) => return e.into(),
+    };
+    error!("Shopify API {}Error {}: {}", method, status, error_text);
+
+    ShopifyError::from_response(status, error_text, retry_after)
+}
+
+// =============================================================================
+// API Version Negotiation
+// =============================================================================
+
+// Shopify supports each stable version for at least 12 months after release
+const VERSION_SUPPORT_MONTHS: u32 = 12;
+// Start warning this long before a version's expected sunset
+const SUNSET_WARNING_DAYS: i64 = 90;
+
+#[derive(Deserialize)]
+struct ApiListResponse {
+    apis: Vec<ApiListing>,
+}
+
+#[derive(Deserialize)]
+pub struct CountResponse {
+    pub count: u64,
+}
+
+#[derive(Deserialize)]
+struct AccessScopesResponse {
+    access_scopes: Vec<AccessScope>,
+}
+
+#[derive(Deserialize)]
+struct AccessScope {
+    handle: String,
+}
+
+#[derive(Deserialize)]
+struct ApiListing {
+    handle: String,
+    versions: Vec<ApiVersionInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiVersionInfo {
+    pub handle: String,
+    #[serde(default)]
+    pub supported: bool,
+    #[serde(default)]
+    pub latest_supported: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiVersionCheck {
+    Supported { sunset: Option<chrono::NaiveDate> },
+    NearSunset { sunset: chrono::NaiveDate, latest: Option<String> },
+    Unsupported { latest: Option<String> },
+}
+
+/// True for stable `YYYY-MM` handles (quarterly releases) and `unstable`.
+pub fn is_valid_api_version(version: &str) -> bool {
+    if version == "unstable" {
+        return true;
+    }
+    match version.split_once('-') {
+        Some((year, month)) => {
+            year.len() == 4
+                && year.parse::<u32>().is_ok()
+                && matches!(month, "01" | "04" | "07" | "10")
+        }
+        None => false,
+    }
+}
+
+/// Expected end of support for a stable version handle.
+pub fn version_sunset(version: &str) -> Option<chrono::NaiveDate> {
+    let (year, month) = version.split_once('-')?;
+    chrono::NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)?
+        .checked_add_months(chrono::Months::new(VERSION_SUPPORT_MONTHS))
+}
+
+pub fn evaluate_api_version(
+    configured: &str,
+    versions: &[ApiVersionInfo],
+    today: chrono::NaiveDate,
+) -> ApiVersionCheck {
+    let latest = versions.iter().find(|v| v.latest_supported).map(|v| v.handle.clone());
+    let supported = versions.iter().any(|v| v.handle == configured && v.supported);
+
+    if !supported {
+        return ApiVersionCheck::Unsupported { latest };
+    }
+
+    match version_sunset(configured) {
+        Some(sunset) if (sunset - today).num_days() <= SUNSET_WARNING_DAYS => {
+            ApiVersionCheck::NearSunset { sunset, latest }
+        }
+        sunset => ApiVersionCheck::Supported { sunset },
+    }
+}
+
+/// Logs a warning at startup when the configured version is unsupported or
+/// close to sunset. Problems here never stop the server.
+pub async fn check_api_version(client: &ShopifyClient, token: &str) {
+    let versions = match client.fetch_api_versions(token).await {
+        Ok(versions) => versions,
+        Err(e) => {
+            warn!("Could not check Shopify API version support: {}", e);
+            return;
+        }
+    };
+
+    let configured = client.api_version();
+    match evaluate_api_version(configured, &versions, chrono::Utc::now().date_naive()) {
+        ApiVersionCheck::Supported { sunset } => {
+            info!("✅ Shopify API version {} is supported (expected sunset: {:?})", configured, sunset);
+        }
+        ApiVersionCheck::NearSunset { sunset, latest } => {
+            warn!(
+                "⚠️ Shopify API version {} reaches end of support around {}; latest is {}",
+                configured, sunset, latest.as_deref().unwrap_or("unknown")
+            );
+        }
+        ApiVersionCheck::Unsupported { latest } => {
+            warn!(
+                "⚠️ Shopify API version {} is not supported by this shop; latest is {}",
+                configured, latest.as_deref().unwrap_or("unknown")
+            );
+        }
+    }
 }
 
 // =============================================================================
@@ -164,18 +1094,108 @@ impl PaginationOptions {
     }
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
+// =============================================================================
+// Response Wrapper
+// =============================================================================
+
+/// A decoded Shopify response together with the headers it was returned with.
+pub struct ShopifyResponse<T> {
+    pub data: T,
+    pub headers: HeaderMap,
+}
+
+impl<T> ShopifyResponse<T> {
+    /// Cursor information from the `Link` header, if Shopify sent one.
+    pub fn page_info(&self) -> Option<PageInfo> {
+        self.headers
+            .get("link")
+            .and_then(|v| v.to_str().ok())
+            .map(parse_link_header)
+    }
+
+    pub fn into_paginated(self) -> PaginatedResponse<T> {
+        let page_info = self.page_info();
+        PaginatedResponse {
+            data: self.data,
+            page_info,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct PaginatedResponse<T> {
     pub data: T,
     pub page_info: Option<PageInfo>,
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, utoipa::ToSchema)]
 pub struct PageInfo {
     pub has_next_page: bool,
     pub has_previous_page: bool,
-    pub start_cursor: Option<String>,
-    pub end_cursor: Option<String>,
+    pub next_page_info: Option<String>,
+    pub previous_page_info: Option<String>,
+}
+
+// =============================================================================
+// Link Header Parsing
+// =============================================================================
+
+/// Parses Shopify's REST pagination header, e.g.
+/// `<https://shop/admin/api/2025-04/products.json?limit=50&page_info=abc>; rel="next"`.
+pub fn parse_link_header(header: &str) -> PageInfo {
+    let mut page_info = PageInfo::default();
+
+    for link in header.split(',') {
+        let mut parts = link.split(';');
+
+        let url = match parts.next() {
+            Some(url) => url.trim().trim_start_matches('<').trim_end_matches('>'),
+            None => continue,
+        };
+
+        let rel = parts
+            .filter_map(|p| p.trim().strip_prefix("rel="))
+            .map(|r| r.trim_matches('"'))
+            .next();
+
+        let cursor = url::Url::parse(url).ok().and_then(|u| {
+            u.query_pairs()
+                .find(|(k, _)| k == "page_info")
+                .map(|(_, v)| v.into_owned())
+        });
+
+        match rel {
+            Some("next") => {
+                page_info.has_next_page = cursor.is_some();
+                page_info.next_page_info = cursor;
+            }
+            Some("previous") => {
+                page_info.has_previous_page = cursor.is_some();
+                page_info.previous_page_info = cursor;
+            }
+            _ => {}
+        }
+    }
+
+    page_info
+}
+
+/// `(used, max)` from `X-Shopify-Shop-Api-Call-Limit`, e.g. `32/40`.
+pub fn parse_call_limit(header: &str) -> Option<(u32, u32)> {
+    let (used, max) = header.split_once('/')?;
+    Some((used.trim().parse().ok()?, max.trim().parse().ok()?))
+}
+
+/// `Retry-After` as sent by Shopify, in (possibly fractional) seconds.
+pub fn parse_retry_after(header: &str) -> Option<Duration> {
+    header.trim().parse::<f64>().ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+/// Builds the proxy URL a client should call to fetch the next page.
+pub fn next_page_url(path: &str, page_info: Option<&PageInfo>, limit: u32) -> Option<String> {
+    page_info
+        .and_then(|p| p.next_page_info.as_ref())
+        .map(|cursor| format!("{}?limit={}&page_info={}", path, limit, urlencoding::encode(cursor)))
 }
@@ -0,0 +1,193 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::{
+    AppState,
+    api_auth::ApiPrincipal,
+    database::{IdempotencyClaim, IdempotentRequest, StoredResponse},
+    error::AppError,
+    scheduler::{Job, JobScope, Schedule},
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Set on responses replayed from an earlier request with the same key.
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// How long a request may hold its key before a retry takes it over; well
+/// past the longest a Shopify write can take, retries included.
+const IN_PROGRESS_TIMEOUT: chrono::Duration = chrono::Duration::minutes(5);
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct IdempotencyConfig {
+    /// How long responses are kept for replay
+    pub ttl: Duration,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl IdempotencyConfig {
+    /// `IDEMPOTENCY_KEY_TTL_HOURS`, 24 by default.
+    pub fn from_env() -> Self {
+        match std::env::var("IDEMPOTENCY_KEY_TTL_HOURS").ok().and_then(|v| v.parse::<u64>().ok()) {
+            Some(hours) if hours > 0 => Self { ttl: Duration::from_secs(hours * 60 * 60) },
+            _ => Self::default(),
+        }
+    }
+}
+
+// =============================================================================
+// Middleware
+// =============================================================================
+
+/// Whether `key` can be used as an `Idempotency-Key`: 1 to 255 visible ASCII
+/// characters, like the UUIDs clients usually send.
+pub fn valid_idempotency_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= MAX_KEY_LENGTH && key.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Whether a response is kept for replay. Throttling and server errors
+/// aren't: the write most likely didn't happen, so a retry should run it.
+pub fn replayable(status: StatusCode) -> bool {
+    !status.is_server_error() && status != StatusCode::TOO_MANY_REQUESTS
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = u16::try_from(stored.status_code)
+        .ok()
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .unwrap_or(StatusCode::OK);
+    let mut response = (status, stored.response_body).into_response();
+    if let Some(content_type) = stored.content_type.and_then(|v| HeaderValue::from_str(&v).ok()) {
+        response.headers_mut().insert(header::CONTENT_TYPE, content_type);
+    }
+    response.headers_mut().insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+/// Makes writes sent with an `Idempotency-Key` header happen once: the first
+/// request runs and its response is stored, and retries with the same key
+/// and body get that response back instead of creating another Shopify
+/// resource. Requests without the header, and reads, pass straight through.
+pub async fn idempotency_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if request.method().is_safe() {
+        return next.run(request).await;
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) if valid_idempotency_key(key) => key.to_string(),
+        _ => return error_response(StatusCode::BAD_REQUEST, "Idempotency-Key must be 1 to 255 visible ASCII characters"),
+    };
+    if state.config.database.database_url.is_none() {
+        return error_response(StatusCode::BAD_REQUEST, "Idempotency-Key needs a Postgres DATABASE_URL");
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => return error_response(StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large"),
+    };
+    let idempotent = IdempotentRequest {
+        shop_domain: state.config.shop.clone(),
+        client: parts.extensions.get::<ApiPrincipal>().map(|p| p.name.clone()).unwrap_or_default(),
+        key,
+        method: parts.method.to_string(),
+        path: parts.uri.path_and_query().map_or_else(|| parts.uri.path().to_string(), |p| p.to_string()),
+        request_hash: hex::encode(Sha256::digest(&body)),
+    };
+
+    match state.idempotency_keys.claim(&idempotent, Utc::now() - IN_PROGRESS_TIMEOUT).await {
+        Ok(IdempotencyClaim::Claimed) => {}
+        Ok(IdempotencyClaim::Completed(stored)) => {
+            info!("🔂 Replaying {} {} for Idempotency-Key {}", idempotent.method, idempotent.path, idempotent.key);
+            return replay(stored);
+        }
+        Ok(IdempotencyClaim::InProgress) => {
+            return AppError::Conflict("A request with this Idempotency-Key is still in progress".to_string()).into_response();
+        }
+        Ok(IdempotencyClaim::Mismatch) => {
+            return error_response(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "This Idempotency-Key was already used for a different request",
+            );
+        }
+        Err(e) => return e.into_response(),
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Failed to read the response for Idempotency-Key {}: {}", idempotent.key, e);
+            if let Err(e) = state.idempotency_keys.release(&idempotent).await {
+                error!("Failed to release Idempotency-Key {}: {}", idempotent.key, e);
+            }
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read the response");
+        }
+    };
+
+    let result = if replayable(parts.status) {
+        let stored = StoredResponse {
+            status_code: i32::from(parts.status.as_u16()),
+            content_type: parts.headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(String::from),
+            response_body: body.to_vec(),
+        };
+        state.idempotency_keys.complete(&idempotent, &stored).await
+    } else {
+        state.idempotency_keys.release(&idempotent).await
+    };
+    // The write went through either way; a retry waits out IN_PROGRESS_TIMEOUT
+    if let Err(e) = result {
+        error!("Failed to record the response for Idempotency-Key {}: {}", idempotent.key, e);
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+// =============================================================================
+// Scheduled Jobs
+// =============================================================================
+
+/// Drops stored responses older than `IDEMPOTENCY_KEY_TTL_HOURS`.
+pub fn idempotency_key_purge_job() -> Job {
+    Job::new("idempotency-key-purge", Schedule::Every(Duration::from_secs(3600)), JobScope::Cluster, |state: AppState| async move {
+        let ttl = chrono::Duration::from_std(state.config.idempotency.ttl).map_err(|e| e.to_string())?;
+        let purged = state
+            .idempotency_keys
+            .purge_older_than(Utc::now() - ttl)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(format!("{} idempotency keys purged", purged))
+    })
+}
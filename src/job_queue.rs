@@ -0,0 +1,504 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    database::{JobQueueFilter, JobQueueStore, NewJob, QueuedJob},
+    error::{AppError, AppResult},
+    scheduler::{Job, JobScope, Schedule},
+    token_audit::with_actor,
+};
+
+/// How long a claimed job stays locked without a heartbeat. A crashed
+/// worker's job is claimed again once its lease runs out.
+const JOB_LEASE: Duration = Duration::from_secs(300);
+
+/// How long finished jobs are kept for inspection.
+const SUCCEEDED_RETENTION: chrono::Duration = chrono::Duration::days(7);
+const DEAD_RETENTION: chrono::Duration = chrono::Duration::days(30);
+
+const JOB_LIST_DEFAULT_LIMIT: i64 = 50;
+const JOB_LIST_MAX_LIMIT: i64 = 500;
+
+pub const JOB_STATUSES: [&str; 4] = ["queued", "running", "succeeded", "dead"];
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct JobQueueConfig {
+    /// Jobs run concurrently on this instance
+    pub workers: usize,
+    /// Attempts before a job is dead-lettered, unless its kind says otherwise
+    pub max_attempts: i32,
+    /// Delay before the first retry; doubles with each further attempt
+    pub retry_base: Duration,
+    pub retry_max: Duration,
+    /// How often idle workers look for jobs enqueued by other instances
+    pub poll_interval: Duration,
+    /// How long shutdown waits for running jobs to finish
+    pub drain_timeout: Duration,
+}
+
+impl Default for JobQueueConfig {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            max_attempts: 5,
+            retry_base: Duration::from_secs(30),
+            retry_max: Duration::from_secs(3600),
+            poll_interval: Duration::from_secs(2),
+            drain_timeout: Duration::from_secs(25),
+        }
+    }
+}
+
+impl JobQueueConfig {
+    /// `JOB_QUEUE_WORKERS`, `JOB_MAX_ATTEMPTS`, `JOB_RETRY_BASE_SECS` and
+    /// `JOB_RETRY_MAX_SECS`.
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).filter(|n| *n > 0);
+        let defaults = Self::default();
+
+        Self {
+            workers: var("JOB_QUEUE_WORKERS").map(|n| n as usize).unwrap_or(defaults.workers),
+            max_attempts: var("JOB_MAX_ATTEMPTS").map(|n| n.min(i32::MAX as u64) as i32).unwrap_or(defaults.max_attempts),
+            retry_base: var("JOB_RETRY_BASE_SECS").map(Duration::from_secs).unwrap_or(defaults.retry_base),
+            retry_max: var("JOB_RETRY_MAX_SECS").map(Duration::from_secs).unwrap_or(defaults.retry_max),
+            ..defaults
+        }
+    }
+
+    /// Backoff after `attempt` (1-based) failed: `retry_base`, doubling per
+    /// attempt, capped at `retry_max`.
+    pub fn retry_delay(&self, attempt: i32) -> Duration {
+        let doublings = attempt.saturating_sub(1).clamp(0, 31) as u32;
+        self.retry_base.saturating_mul(2u32.saturating_pow(doublings)).min(self.retry_max)
+    }
+}
+
+// =============================================================================
+// Job Kinds
+// =============================================================================
+
+/// How jobs of one kind for the same shop relate to each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ShopOrdering {
+    /// Run in parallel
+    Unordered,
+    /// One at a time, in the order they were enqueued
+    Serial,
+    /// One at a time, and enqueuing while one is waiting is a no-op
+    Single,
+}
+
+/// A job's outcome: retried with backoff on `Err`.
+pub type JobHandlerResult = Result<(), String>;
+
+type JobHandler = Arc<dyn Fn(AppState, QueuedJob) -> BoxFuture<'static, JobHandlerResult> + Send + Sync>;
+
+pub struct JobKind {
+    pub name: &'static str,
+    pub ordering: ShopOrdering,
+    /// Overrides `JOB_MAX_ATTEMPTS`
+    pub max_attempts: Option<i32>,
+    run: JobHandler,
+}
+
+impl JobKind {
+    pub fn new<F, Fut>(name: &'static str, ordering: ShopOrdering, run: F) -> Self
+    where
+        F: Fn(AppState, QueuedJob) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = JobHandlerResult> + Send + 'static,
+    {
+        Self {
+            name,
+            ordering,
+            max_attempts: None,
+            run: Arc::new(move |state, job| Box::pin(run(state, job))),
+        }
+    }
+
+    pub fn max_attempts(mut self, attempts: i32) -> Self {
+        self.max_attempts = Some(attempts.max(1));
+        self
+    }
+}
+
+// =============================================================================
+// Queue
+// =============================================================================
+
+/// Durable work queue in Postgres, worked by a pool of tasks on every
+/// instance. Without Postgres the queue is disabled and `enqueue` fails, so
+/// callers fall back to doing the work directly.
+#[derive(Clone)]
+pub struct JobQueue {
+    inner: Arc<JobQueueInner>,
+}
+
+struct JobQueueInner {
+    instance: String,
+    store: Option<JobQueueStore>,
+    config: JobQueueConfig,
+    kinds: Mutex<HashMap<&'static str, Arc<JobKind>>>,
+    wake: Notify,
+    closing: watch::Sender<bool>,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl JobQueue {
+    pub fn new(config: &JobQueueConfig, store: Option<JobQueueStore>) -> Self {
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "app".to_string());
+        let instance = format!("{}-{}", host, &Uuid::new_v4().simple().to_string()[..8]);
+
+        Self {
+            inner: Arc::new(JobQueueInner {
+                instance,
+                store,
+                config: config.clone(),
+                kinds: Mutex::new(HashMap::new()),
+                wake: Notify::new(),
+                closing: watch::channel(false).0,
+                workers: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.store.is_some()
+    }
+
+    pub fn store(&self) -> AppResult<&JobQueueStore> {
+        self.inner
+            .store
+            .as_ref()
+            .ok_or_else(|| AppError::BadRequest("The job queue needs a Postgres DATABASE_URL".to_string()))
+    }
+
+    pub fn register(&self, kind: JobKind) {
+        self.inner.kinds.lock().unwrap().insert(kind.name, Arc::new(kind));
+    }
+
+    fn kind(&self, name: &str) -> Option<Arc<JobKind>> {
+        self.inner.kinds.lock().unwrap().get(name).cloned()
+    }
+
+    /// Queues a job of a registered kind. Returns `None` for a `Single` kind
+    /// that already has a job waiting or running for the shop.
+    pub async fn enqueue(
+        &self,
+        kind: &str,
+        shop_domain: Option<&str>,
+        payload: serde_json::Value,
+    ) -> AppResult<Option<QueuedJob>> {
+        let store = self.store()?;
+        let registered = self
+            .kind(kind)
+            .ok_or_else(|| AppError::Config(format!("Unknown job kind {}", kind)))?;
+
+        let queue_key = match (registered.ordering, shop_domain) {
+            (ShopOrdering::Unordered, _) | (_, None) => None,
+            (_, Some(shop)) => Some(format!("{}:{}", kind, shop)),
+        };
+        let job = store
+            .enqueue(&NewJob {
+                kind: kind.to_string(),
+                shop_domain: shop_domain.map(str::to_string),
+                unique: registered.ordering == ShopOrdering::Single && queue_key.is_some(),
+                queue_key,
+                payload,
+                max_attempts: registered.max_attempts.unwrap_or(self.inner.config.max_attempts),
+            })
+            .await?;
+
+        if let Some(ref job) = job {
+            debug!("Queued {} job {}", kind, job.id);
+            self.inner.wake.notify_one();
+        }
+        Ok(job)
+    }
+
+    /// Starts the workers. Does nothing without Postgres.
+    pub fn start(&self, state: AppState) {
+        if self.inner.store.is_none() {
+            return;
+        }
+        let workers: Vec<JoinHandle<()>> = (0..self.inner.config.workers)
+            .map(|index| tokio::spawn(self.clone().run_worker(index, state.clone())))
+            .collect();
+
+        info!("📬 Started {} job queue workers", workers.len());
+        self.inner.workers.lock().unwrap().extend(workers);
+    }
+
+    /// Stops claiming jobs and waits up to `timeout` for the running ones.
+    /// Returns false if some were still busy, in which case they are aborted
+    /// and claimed again by another worker once their lease runs out.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.inner.closing.send_replace(true);
+
+        let workers: Vec<JoinHandle<()>> = std::mem::take(&mut *self.inner.workers.lock().unwrap());
+        let aborts: Vec<_> = workers.iter().map(JoinHandle::abort_handle).collect();
+        match tokio::time::timeout(timeout, futures::future::join_all(workers)).await {
+            Ok(_) => true,
+            Err(_) => {
+                warn!("Job queue workers still busy after {:?}, leaving their jobs to be reclaimed", timeout);
+                aborts.iter().for_each(|handle| handle.abort());
+                false
+            }
+        }
+    }
+
+    async fn run_worker(self, index: usize, state: AppState) {
+        let Some(store) = self.inner.store.clone() else { return };
+        let worker = format!("{}/{}", self.inner.instance, index);
+        let kinds: Vec<String> = self.inner.kinds.lock().unwrap().keys().map(|kind| kind.to_string()).collect();
+        let mut closing = self.inner.closing.subscribe();
+
+        while !*closing.borrow() {
+            let idle = match store.claim(&kinds, &worker, JOB_LEASE).await {
+                Ok(Some(job)) => {
+                    self.run_job(&store, &worker, job, &state).await;
+                    continue;
+                }
+                Ok(None) => self.inner.config.poll_interval,
+                Err(e) => {
+                    error!("Job queue worker {} failed to claim a job: {}", worker, e);
+                    self.inner.config.poll_interval * 5
+                }
+            };
+
+            tokio::select! {
+                _ = self.inner.wake.notified() => {}
+                _ = tokio::time::sleep(idle) => {}
+                _ = closing.changed() => {}
+            }
+        }
+        debug!("Job queue worker {} stopped", worker);
+    }
+
+    async fn run_job(&self, store: &JobQueueStore, worker: &str, job: QueuedJob, state: &AppState) {
+        let clock = Instant::now();
+        let result = match self.kind(&job.kind) {
+            // A worker died mid-attempt on its last try; don't start another
+            _ if job.attempts > job.max_attempts => Err("The worker running the last attempt stopped".to_string()),
+            None => Err(format!("No handler registered for {} jobs", job.kind)),
+            Some(kind) => {
+                let heartbeat = {
+                    let (store, worker, id) = (store.clone(), worker.to_string(), job.id);
+                    tokio::spawn(async move {
+                        let mut interval = tokio::time::interval(JOB_LEASE / 3);
+                        interval.tick().await;
+                        loop {
+                            interval.tick().await;
+                            if let Err(e) = store.extend_lease(id, &worker, JOB_LEASE).await {
+                                warn!("Failed to extend the lease on job {}: {}", id, e);
+                            }
+                        }
+                    })
+                };
+
+                // A panicking job fails its attempt without taking the worker down
+                let result = match tokio::spawn(with_actor(kind.name, (kind.run)(state.clone(), job.clone()))).await {
+                    Ok(result) => result,
+                    Err(e) => Err(format!("Job panicked: {}", e)),
+                };
+                heartbeat.abort();
+                result
+            }
+        };
+        let duration_ms = clock.elapsed().as_millis();
+
+        match result {
+            Ok(()) => {
+                debug!("Job {} ({}) finished in {}ms", job.id, job.kind, duration_ms);
+                if let Err(e) = store.complete(job.id, worker).await {
+                    error!("Failed to mark job {} complete: {}", job.id, e);
+                }
+            }
+            Err(message) => {
+                let retry_in = self.inner.config.retry_delay(job.attempts);
+                match store.fail(job.id, worker, &message, retry_in).await {
+                    Ok(Some(status)) if status == "dead" => error!(
+                        "💀 Job {} ({}) failed for good after {} attempts: {}",
+                        job.id, job.kind, job.attempts, message
+                    ),
+                    Ok(_) => warn!(
+                        "Job {} ({}) failed attempt {}/{}, retrying in {:?}: {}",
+                        job.id, job.kind, job.attempts, job.max_attempts, retry_in, message
+                    ),
+                    Err(e) => error!("Failed to record failure of job {}: {}", job.id, e),
+                }
+            }
+        }
+    }
+}
+
+/// Deletes succeeded jobs after a week and dead ones after a month.
+pub fn job_queue_purge_job() -> Job {
+    Job::new("job-queue-purge", Schedule::parse("@hourly").expect("valid schedule"), JobScope::Cluster, |state: AppState| async move {
+        let now = chrono::Utc::now();
+        let store = state.job_queue.store().map_err(|e| e.to_string())?;
+        let purged = store
+            .purge_finished(now - SUCCEEDED_RETENTION, now - DEAD_RETENTION)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(format!("{} finished jobs purged", purged))
+    })
+}
+
+// =============================================================================
+// Admin Handlers
+// =============================================================================
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct QueuedJobParams {
+    pub status: Option<String>,
+    pub kind: Option<String>,
+    pub shop: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// `GET /admin/queue` — job counts by kind and status.
+#[utoipa::path(
+    get,
+    path = "/admin/queue",
+    tag = "jobs",
+    responses(
+        (status = 200, description = "Registered job kinds and job counts", body = crate::openapi::JobQueueOverview),
+    ),
+)]
+pub async fn job_queue_handler(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    let counts = state.job_queue.store()?.counts().await?;
+    let kinds: Vec<serde_json::Value> = {
+        let kinds = state.job_queue.inner.kinds.lock().unwrap();
+        let mut kinds: Vec<_> = kinds
+            .values()
+            .map(|kind| serde_json::json!({
+                "kind": kind.name,
+                "ordering": kind.ordering,
+                "max_attempts": kind.max_attempts.unwrap_or(state.config.job_queue.max_attempts),
+            }))
+            .collect();
+        kinds.sort_by(|a, b| a["kind"].as_str().cmp(&b["kind"].as_str()));
+        kinds
+    };
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "instance": state.job_queue.inner.instance,
+        "workers": state.config.job_queue.workers,
+        "kinds": kinds,
+        "counts": counts
+    }))))
+}
+
+/// `GET /admin/queue/jobs` — most recent jobs first, filtered by `status`,
+/// `kind` and `shop`.
+#[utoipa::path(
+    get,
+    path = "/admin/queue/jobs",
+    tag = "jobs",
+    params(QueuedJobParams),
+    responses(
+        (status = 200, description = "Matching jobs", body = crate::openapi::QueuedJobList),
+        (status = 400, description = "Unknown status", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn list_queued_jobs_handler(
+    Query(params): Query<QueuedJobParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    if let Some(ref status) = params.status {
+        if !JOB_STATUSES.contains(&status.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "status must be one of {}",
+                JOB_STATUSES.join(", ")
+            )));
+        }
+    }
+    let filter = JobQueueFilter {
+        status: params.status,
+        kind: params.kind,
+        shop_domain: params.shop,
+        limit: params.limit.unwrap_or(JOB_LIST_DEFAULT_LIMIT).clamp(1, JOB_LIST_MAX_LIMIT),
+    };
+    let jobs = state.job_queue.store()?.list_jobs(&filter).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "jobs_count": jobs.len(),
+        "jobs": jobs
+    }))))
+}
+
+/// `GET /admin/queue/jobs/{id}` — a job with its payload and last error.
+#[utoipa::path(
+    get,
+    path = "/admin/queue/jobs/{id}",
+    tag = "jobs",
+    params(("id" = Uuid, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "The job", body = crate::openapi::QueuedJobDetail),
+        (status = 404, description = "No such job", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn queued_job_handler(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let job = state.job_queue.store()?.get_job(id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Job {} not found", id)))?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({ "job": job }))))
+}
+
+/// `POST /admin/queue/jobs/{id}/retry` — runs a dead job again with a fresh
+/// set of attempts, or a job waiting out its backoff right away.
+#[utoipa::path(
+    post,
+    path = "/admin/queue/jobs/{id}/retry",
+    tag = "jobs",
+    params(("id" = Uuid, Path, description = "Job ID")),
+    responses(
+        (status = 202, description = "The job, queued to run now", body = crate::openapi::QueuedJobDetail),
+        (status = 404, description = "No such job", body = crate::openapi::ErrorResponse),
+        (status = 409, description = "The job is running or already succeeded", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn retry_queued_job_handler(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let store = state.job_queue.store()?;
+    let job = match store.retry(id).await? {
+        Some(job) => job,
+        None => {
+            return Err(match store.get_job(id).await? {
+                Some(job) => AppError::Conflict(format!("Job {} is {} and can't be retried", id, job.status)),
+                None => AppError::NotFound(format!("Job {} not found", id)),
+            });
+        }
+    };
+    info!("🔁 Job {} ({}) queued for retry", job.id, job.kind);
+    state.job_queue.inner.wake.notify_one();
+
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({ "job": job }))))
+}
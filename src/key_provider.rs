@@ -0,0 +1,388 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::error::{AppError, AppResult};
+
+// =============================================================================
+// Key Providers
+// =============================================================================
+
+/// Unwraps the master encryption key from an external key service, so only
+/// its ciphertext has to live in the environment.
+#[async_trait]
+pub trait KeyProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Decrypts `ciphertext` (as issued by the service) to the raw key bytes.
+    async fn unwrap_key(&self, http: &reqwest::Client, ciphertext: &str) -> AppResult<Vec<u8>>;
+}
+
+/// The wrapped `ENCRYPTION_KEY` and the service that can unwrap it.
+#[derive(Clone)]
+pub struct WrappedKey {
+    pub provider: Arc<dyn KeyProvider>,
+    pub ciphertext: String,
+}
+
+impl WrappedKey {
+    /// Reads `ENCRYPTION_KEY_PROVIDER` (`aws-kms`, `gcp-kms` or `vault`) and
+    /// `ENCRYPTION_KEY_CIPHERTEXT`; `None` when no provider is configured.
+    pub fn from_env() -> AppResult<Option<Self>> {
+        let Some(provider) = env("ENCRYPTION_KEY_PROVIDER") else {
+            return Ok(None);
+        };
+        let provider: Arc<dyn KeyProvider> = match provider.to_lowercase().as_str() {
+            "aws-kms" => Arc::new(AwsKms::from_env()?),
+            "gcp-kms" => Arc::new(GcpKms::from_env()?),
+            "vault" => Arc::new(VaultTransit::from_env()?),
+            other => {
+                return Err(AppError::Config(format!(
+                    "Unknown ENCRYPTION_KEY_PROVIDER: {} (expected aws-kms, gcp-kms or vault)",
+                    other
+                )))
+            }
+        };
+        Ok(Some(Self { provider, ciphertext: required("ENCRYPTION_KEY_CIPHERTEXT")? }))
+    }
+
+    /// Unwraps the key. It must decrypt to the same 32-byte value
+    /// `ENCRYPTION_KEY` would hold, so existing ciphertext stays readable.
+    pub async fn fetch(&self, http: &reqwest::Client) -> AppResult<Secret<String>> {
+        info!("🔐 Unwrapping the encryption key with {}", self.provider.name());
+        let key = self.provider.unwrap_key(http, &self.ciphertext).await?;
+        let key = String::from_utf8(key)
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| {
+                AppError::Encryption(format!("{} did not return a 32-byte encryption key", self.provider.name()))
+            })?;
+        Ok(Secret::new(key))
+    }
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn required(name: &str) -> AppResult<String> {
+    env(name).ok_or_else(|| AppError::Config(format!("{} is required by ENCRYPTION_KEY_PROVIDER", name)))
+}
+
+fn provider_error(provider: &str, detail: impl std::fmt::Display) -> AppError {
+    AppError::Encryption(format!("{}: {}", provider, detail))
+}
+
+/// Sends a provider request and returns the JSON body of a 2xx response.
+async fn send_json(provider: &str, request: reqwest::RequestBuilder) -> AppResult<Value> {
+    let response = request.send().await.map_err(|e| provider_error(provider, e))?;
+    let status = response.status();
+    let body = response.text().await.map_err(|e| provider_error(provider, e))?;
+    if !status.is_success() {
+        return Err(provider_error(provider, format!("{} {}", status, body)));
+    }
+    serde_json::from_str(&body).map_err(|e| provider_error(provider, e))
+}
+
+fn decode_plaintext(provider: &str, plaintext: Option<&Value>) -> AppResult<Vec<u8>> {
+    let plaintext = plaintext
+        .and_then(Value::as_str)
+        .ok_or_else(|| provider_error(provider, "response has no plaintext"))?;
+    general_purpose::STANDARD.decode(plaintext).map_err(|e| provider_error(provider, e))
+}
+
+// =============================================================================
+// Purpose Keys
+// =============================================================================
+
+/// Fixed HKDF salt; changing it would invalidate every link already issued.
+const PURPOSE_KEY_SALT: &[u8] = b"shopify-oauth-rust/purpose-key/v1";
+
+/// Derives a signing key for one feature from a shared secret (usually
+/// `API_SECRET`) with HKDF-SHA256 and `purpose` as context, so a MAC issued
+/// for one feature is never valid for another, or as a Shopify signature.
+pub fn derive_purpose_key(secret: &str, purpose: &str) -> Secret<String> {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(PURPOSE_KEY_SALT), secret.as_bytes())
+        .expand(purpose.as_bytes(), &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    let derived = Secret::new(hex::encode(key));
+    key.fill(0);
+    derived
+}
+
+// =============================================================================
+// AWS KMS
+// =============================================================================
+
+/// AWS KMS `Decrypt`, signed with Signature Version 4 from the standard
+/// `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY` / `AWS_SESSION_TOKEN`.
+pub struct AwsKms {
+    pub region: String,
+    pub endpoint: String,
+    pub access_key_id: String,
+    pub secret_access_key: Secret<String>,
+    pub session_token: Option<String>,
+}
+
+impl AwsKms {
+    fn from_env() -> AppResult<Self> {
+        let region = env("AWS_REGION")
+            .or_else(|| env("AWS_DEFAULT_REGION"))
+            .ok_or_else(|| AppError::Config("AWS_REGION is required by ENCRYPTION_KEY_PROVIDER".to_string()))?;
+        Ok(Self {
+            endpoint: env("AWS_KMS_ENDPOINT").unwrap_or_else(|| format!("https://kms.{}.amazonaws.com", region)),
+            region,
+            access_key_id: required("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: Secret::new(required("AWS_SECRET_ACCESS_KEY")?),
+            session_token: env("AWS_SESSION_TOKEN"),
+        })
+    }
+}
+
+#[async_trait]
+impl KeyProvider for AwsKms {
+    fn name(&self) -> &'static str {
+        "AWS KMS"
+    }
+
+    async fn unwrap_key(&self, http: &reqwest::Client, ciphertext: &str) -> AppResult<Vec<u8>> {
+        let url = url::Url::parse(&self.endpoint).map_err(|e| provider_error(self.name(), e))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(provider_error(self.name(), "endpoint has no host")),
+        };
+        let body = json!({ "CiphertextBlob": ciphertext }).to_string();
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("host", host),
+            ("x-amz-date", amz_date),
+            ("x-amz-target", "TrentService.Decrypt".to_string()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        headers.sort_by_key(|(name, _)| *name);
+        let authorization = sigv4_authorization(
+            &self.access_key_id,
+            &self.secret_access_key,
+            &self.region,
+            "kms",
+            now,
+            &headers,
+            body.as_bytes(),
+        );
+
+        let mut request = http.post(url).header("authorization", authorization).body(body);
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        let response = send_json(self.name(), request).await?;
+        decode_plaintext(self.name(), response.get("Plaintext"))
+    }
+}
+
+/// `Authorization` header for a SigV4-signed `POST /` with the given
+/// (lowercase, sorted) headers.
+pub fn sigv4_authorization(
+    access_key_id: &str,
+    secret_access_key: &Secret<String>,
+    region: &str,
+    service: &str,
+    now: DateTime<Utc>,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> String {
+    let request = SignedRequest { method: "POST", path: "/", headers, body };
+    sigv4_request_authorization(access_key_id, secret_access_key, region, service, now, &request)
+}
+
+/// The parts of a request SigV4 signs. It has no query string.
+pub struct SignedRequest<'a> {
+    pub method: &'a str,
+    /// Already URI-encoded, as sent
+    pub path: &'a str,
+    /// Lowercase names, sorted
+    pub headers: &'a [(&'a str, String)],
+    pub body: &'a [u8],
+}
+
+/// `Authorization` header for `request`, signed with SigV4.
+pub fn sigv4_request_authorization(
+    access_key_id: &str,
+    secret_access_key: &Secret<String>,
+    region: &str,
+    service: &str,
+    now: DateTime<Utc>,
+    request: &SignedRequest,
+) -> String {
+    let date = now.format("%Y%m%d").to_string();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+
+    let headers = request.headers;
+    let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+    let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        request.method,
+        request.path,
+        canonical_headers,
+        signed_headers,
+        hex::encode(Sha256::digest(request.body))
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let signing_key = sigv4_signing_key(secret_access_key, &date, region, service);
+    let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key_id, scope, signed_headers, signature
+    )
+}
+
+pub fn sigv4_signing_key(secret_access_key: &Secret<String>, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(format!("AWS4{}", secret_access_key.expose_secret()).as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    hmac_sha256(&key, b"aws4_request")
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+// =============================================================================
+// GCP Cloud KMS
+// =============================================================================
+
+const GCP_METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// Cloud KMS `decrypt` on `GCP_KMS_KEY_NAME`, authenticated with
+/// `GCP_ACCESS_TOKEN` or, when unset, the instance's service account.
+pub struct GcpKms {
+    /// `projects/<p>/locations/<l>/keyRings/<r>/cryptoKeys/<k>`
+    pub key_name: String,
+    pub endpoint: String,
+    pub access_token: Option<Secret<String>>,
+}
+
+impl GcpKms {
+    fn from_env() -> AppResult<Self> {
+        Ok(Self {
+            key_name: required("GCP_KMS_KEY_NAME")?,
+            endpoint: env("GCP_KMS_ENDPOINT").unwrap_or_else(|| "https://cloudkms.googleapis.com".to_string()),
+            access_token: env("GCP_ACCESS_TOKEN").map(Secret::new),
+        })
+    }
+
+    async fn access_token(&self, http: &reqwest::Client) -> AppResult<String> {
+        gcp_access_token(http, self.access_token.as_ref())
+            .await
+            .map_err(|e| provider_error(self.name(), e))
+    }
+}
+
+/// `token` when one is configured, otherwise one for the instance's service
+/// account from the metadata server.
+pub async fn gcp_access_token(http: &reqwest::Client, token: Option<&Secret<String>>) -> Result<String, String> {
+    if let Some(token) = token {
+        return Ok(token.expose_secret().clone());
+    }
+    let response = http
+        .get(GCP_METADATA_TOKEN_URL)
+        .header("Metadata-Flavor", "Google")
+        .send()
+        .await
+        .map_err(|e| format!("metadata server: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("metadata server answered {}", response.status()));
+    }
+    let body: Value = response.json().await.map_err(|e| format!("metadata server: {}", e))?;
+    body["access_token"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "metadata server returned no access token".to_string())
+}
+
+#[async_trait]
+impl KeyProvider for GcpKms {
+    fn name(&self) -> &'static str {
+        "GCP KMS"
+    }
+
+    async fn unwrap_key(&self, http: &reqwest::Client, ciphertext: &str) -> AppResult<Vec<u8>> {
+        let url = format!("{}/v1/{}:decrypt", self.endpoint.trim_end_matches('/'), self.key_name);
+        let request = http
+            .post(url)
+            .bearer_auth(self.access_token(http).await?)
+            .json(&json!({ "ciphertext": ciphertext }));
+        let response = send_json(self.name(), request).await?;
+        decode_plaintext(self.name(), response.get("plaintext"))
+    }
+}
+
+// =============================================================================
+// HashiCorp Vault Transit
+// =============================================================================
+
+/// Vault's transit engine: `POST /v1/<mount>/decrypt/<key>` with a
+/// `vault:v1:...` ciphertext.
+pub struct VaultTransit {
+    pub addr: String,
+    pub token: Secret<String>,
+    pub mount: String,
+    pub key: String,
+    pub namespace: Option<String>,
+}
+
+impl VaultTransit {
+    fn from_env() -> AppResult<Self> {
+        Ok(Self {
+            addr: required("VAULT_ADDR")?,
+            token: Secret::new(required("VAULT_TOKEN")?),
+            mount: env("VAULT_TRANSIT_MOUNT").unwrap_or_else(|| "transit".to_string()),
+            key: required("VAULT_TRANSIT_KEY")?,
+            namespace: env("VAULT_NAMESPACE"),
+        })
+    }
+}
+
+#[async_trait]
+impl KeyProvider for VaultTransit {
+    fn name(&self) -> &'static str {
+        "Vault"
+    }
+
+    async fn unwrap_key(&self, http: &reqwest::Client, ciphertext: &str) -> AppResult<Vec<u8>> {
+            let url = format!("{}/v1/{}/decrypt/{}", self.addr.trim_end_matches('/'), self.mount, self.key);
+        let mut request = http
+            .post(url)
+            .header("X-Vault-Token", self.token.expose_secret())
+            .json(&json!({ "ciphertext": ciphertext }));
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response = send_json(self.name(), request).await?;
+        decode_plaintext(self.name(), response["data"].get("plaintext"))
+    }
+}
@@ -0,0 +1,793 @@
+//! Shopify OAuth2, Admin API, and webhook handling for Axum apps.
+//!
+//! The server binary is a thin wrapper around [`router`]. The pieces it's built
+//! from can also be used on their own:
+//!
+//! - [`ShopifyOAuth`] builds consent URLs and exchanges callback codes for tokens
+//! - [`ShopifyClient`] calls the Admin API with retries, pagination, and rate limit handling
+//! - [`TokenStore`] keeps access tokens, in Postgres, MySQL, SQLite, or memory
+//! - [`WebhookVerifier`] checks webhook HMACs, base64 or hex, in constant time
+//!   before a payload is trusted, and [`WebhookTopic`] reads what a delivery is about
+//! - [`router_with_app_proxy`] mounts your own handlers under `/proxy`, behind
+//!   app proxy signature verification, to receive storefront traffic
+//!
+//! ```no_run
+//! use axum::{body::Bytes, http::{HeaderMap, StatusCode}, routing::post, Router};
+//! use shopify_oauth_rust::WebhookVerifier;
+//!
+//! let verifier = WebhookVerifier::new("your_api_secret");
+//! let app: Router = Router::new().route(
+//!     "/webhooks/orders/created",
+//!     post(move |headers: HeaderMap, body: Bytes| async move {
+//!         match verifier.verify(&headers, &body) {
+//!             Ok(()) => StatusCode::OK,
+//!             Err(_) => StatusCode::UNAUTHORIZED,
+//!         }
+//!     }),
+//! );
+//! ```
+
+use axum::{
+    routing::get,
+    Router,
+    middleware as axum_middleware,
+};
+use std::sync::Arc;
+use tower::ServiceBuilder;
+use tower_http::cors::CorsLayer;
+use tracing::warn;
+
+pub mod error;
+pub mod database;
+pub mod middleware;
+pub mod http_client;
+pub mod shopify_api;
+pub mod webhooks;
+pub mod abandoned_checkouts;
+pub mod downloads;
+pub mod order_timeline;
+pub mod webhook_queue;
+pub mod csv_response;
+pub mod customer_merge;
+pub mod api_usage;
+pub mod shop_secrets;
+pub mod webhook_sampling;
+pub mod sales_report;
+pub mod sales_metrics;
+pub mod order_documents;
+pub mod checkout_settings;
+pub mod fulfillment_routing;
+pub mod data_residency;
+pub mod metafields;
+pub mod schemas;
+pub mod gift_cards;
+pub mod payouts;
+pub mod market_prices;
+pub mod product_affinity;
+pub mod shop_info;
+pub mod storefront;
+pub mod app_proxy;
+pub mod script_tags;
+pub mod recovery_tracking;
+pub mod scopes;
+pub mod oauth;
+pub mod home;
+pub mod pages;
+pub mod token_store;
+pub mod key_provider;
+pub mod token_audit;
+pub mod admin_shops;
+pub mod api_auth;
+pub mod embedded;
+pub mod logging;
+pub mod tls;
+pub mod config_file;
+pub mod order_sync;
+pub mod order_risks;
+pub mod catalog;
+pub mod customer_index;
+pub mod data_retention;
+pub mod domain_events;
+pub mod low_stock;
+pub mod notifications;
+pub mod mailer;
+pub mod warehouse_export;
+pub mod event_stream;
+pub mod idempotency;
+pub mod response_cache;
+pub mod response_shaping;
+pub mod scheduler;
+pub mod job_queue;
+pub mod openapi;
+
+#[cfg(test)]
+mod tests;
+#[cfg(test)]
+mod test_support;
+
+use database::{
+    DatabaseConfig, DatabaseRouter, WebhookEventStore, CatalogStore,
+    CustomerMirrorStore, ApiUsageStore, ShopSecretStore, DocumentTemplateStore,
+    FulfillmentRoutingStore, IdempotencyStore, LowStockStore, OrderMirrorStore, OrderStore, PersonalDataStore,
+    RecoveryMessageStore, TokenAuditStore, WarehouseExportStore,
+};
+use middleware::{
+    RateLimitConfig, RateLimiter, create_oauth_rate_limiter, create_api_rate_limiter, 
+    create_general_rate_limiter, security_headers_middleware, rate_limit_middleware,
+    request_logging_middleware, soft_rate_limit_middleware, request_id_middleware, client_ip_middleware,
+};
+use error::{AppError, AppResult};
+use http_client::HttpClientConfig;
+use tls::TlsConfig;
+use config_file::{required_var, ConfigErrors};
+use shopify_api::{
+    create_fulfillment_handler, customer_handler, customers_handler, fulfillment_orders_handler,
+    fulfillments_handler, inventory_adjust_handler, inventory_connect_handler, inventory_handler,
+    inventory_set_handler, location_handler, locations_count_handler, locations_handler,
+    order_handler, orders_count_handler, orders_handler, product_handler, products_count_handler,
+    products_handler, customers_count_handler,
+};
+use abandoned_checkouts::{abandoned_checkouts_handler, abandoned_checkouts_count_handler};
+use downloads::{download_handler, DownloadConfig};
+use order_timeline::order_timeline_handler;
+use order_risks::order_risks_handler;
+use customer_merge::{customer_duplicates_handler, customer_merge_handler};
+use sales_report::sales_report_handler;
+use sales_metrics::sales_metrics_handler;
+use product_affinity::product_affinity_handler;
+use shop_info::{access_scopes_handler, shop_handler};
+use scopes::{parse_scopes, scope_guard_middleware, scope_list, AccessScope, ScopeGuard};
+use recovery_tracking::{
+    record_recovery_bounce_handler, record_recovery_send_handler, recovery_click_handler,
+    recovery_deliverability_handler, recovery_open_handler, send_recovery_email_handler,
+    RecoveryTrackingConfig,
+};
+use checkout_settings::{get_checkout_settings_handler, put_checkout_settings_handler};
+use metafields::{
+    create_metafield_handler, delete_metafield_handler, list_metafields_handler, update_metafield_handler,
+};
+use gift_cards::{
+    create_gift_card_handler, disable_gift_card_handler, gift_card_handler, gift_cards_handler,
+};
+use market_prices::product_prices_handler;
+use payouts::{balance_handler, payout_transactions_handler, payouts_handler};
+use schemas::{list_schemas_handler, schema_handler};
+use openapi::{openapi_handler, swagger_ui_handler};
+use token_store::{TokenStoreBackend, TokenStoreConfig};
+use token_audit::{audit_context_middleware, token_audit_handler};
+use admin_shops::{list_installed_shops_handler, revoke_shop_handler, shop_health_handler};
+use order_sync::{order_summary_handler, order_sync_status_handler, start_order_sync_handler, OrderSyncConfig};
+use scheduler::{jobs_handler, Scheduler, SchedulerConfig};
+use job_queue::{
+    job_queue_handler, list_queued_jobs_handler, queued_job_handler, retry_queued_job_handler, JobQueue,
+    JobQueueConfig,
+};
+use catalog::{catalog_handler, catalog_sync_status_handler, start_catalog_sync_handler, CatalogConfig};
+use idempotency::{idempotency_middleware, IdempotencyConfig};
+use data_retention::DataRetentionConfig;
+use event_stream::{event_socket_handler, event_socket_ticket_handler, event_stream_handler, EventBroadcaster};
+use response_cache::{cache_invalidation_middleware, response_cache_middleware, CacheGroup, ResponseCache, ResponseCacheConfig, RouteCache};
+use response_shaping::response_shaping_middleware;
+use customer_index::{
+    customer_index_sync_status_handler, customer_search_handler, start_customer_index_sync_handler,
+    CustomerIndexConfig,
+};
+use api_auth::{api_auth_middleware, ApiArea, ApiAuthConfig, ApiGuard};
+use embedded::{embedded_session_handler, frame_ancestors_middleware};
+use oauth::{auth_handler, oauth_callback, post_install_redirect_setting};
+use home::home_handler;
+use pages::BrandingConfig;
+use data_residency::{get_shop_region_handler, put_shop_region_handler};
+use fulfillment_routing::{
+    get_fulfillment_routing_handler, preview_fulfillment_route_handler, put_fulfillment_routing_handler,
+    route_fulfillment_handler,
+};
+use order_documents::{
+    get_document_template_handler, order_document_handler, put_document_template_handler,
+};
+use api_usage::api_usage_handler;
+use low_stock::{get_low_stock_rules_handler, low_stock_alerts_handler, put_low_stock_rules_handler, LowStockConfig};
+use notifications::NotificationConfig;
+use mailer::{EmailSender, MailConfig};
+use warehouse_export::{start_warehouse_export_handler, warehouse_export_status_handler, WarehouseExportConfig};
+use storefront::{revoke_storefront_token_handler, storefront_token_handler};
+use app_proxy::{app_proxy_middleware, app_proxy_session_handler};
+use script_tags::{
+    create_script_tag_handler, delete_script_tag_handler, put_theme_asset_handler, script_tags_handler,
+    ScriptTagConfig,
+};
+use shop_secrets::{delete_webhook_secret_handler, list_shop_secrets_handler, put_webhook_secret_handler};
+use webhook_queue::{WebhookDispatcher, WebhookQueueConfig};
+use webhook_sampling::{replay_webhook_event_handler, WebhookSampler, WebhookSamplingConfig};
+use webhooks::{
+    orders_created_webhook, orders_updated_webhook, orders_cancelled_webhook,
+    orders_paid_webhook, orders_fulfilled_webhook, orders_risk_assessment_changed_webhook, refunds_created_webhook,
+    products_created_webhook, products_updated_webhook, products_deleted_webhook, inventory_levels_updated_webhook,
+    customers_created_webhook, 
+    customers_updated_webhook, customers_deleted_webhook, customers_redact_webhook,
+    checkouts_created_webhook, checkouts_updated_webhook, app_uninstalled_webhook, list_webhooks_handler,
+    gateway_secret_setting, skip_verification_setting, webhook_event_handler,
+};
+
+pub use http_client::ShopifyClient;
+pub use oauth::ShopifyOAuth;
+pub use token_store::{StateStore, TokenStore};
+pub use webhooks::{WebhookTopic, WebhookVerificationError, WebhookVerifier};
+
+// =============================================================================
+// Configuration and Types
+// =============================================================================
+
+#[derive(Clone)]
+pub struct AppConfig {
+    pub shop: String,
+    pub api_key: String,
+    pub api_secret: String,
+    /// Comma-separated access scopes requested during OAuth
+    pub scopes: String,
+    pub redirect_uri: String,
+    pub port: u16,
+    pub host: String,
+    /// Serve HTTPS directly; `None` expects a proxy to terminate TLS
+    pub tls: Option<TlsConfig>,
+    pub environment: String,
+    /// Dev-only: accept webhooks without checking their HMAC
+    pub skip_webhook_verification: bool,
+    /// Shared secret for attestations from a gateway that already checks
+    /// Shopify HMACs; `None` keeps full verification for every webhook
+    pub webhook_gateway_secret: Option<String>,
+    pub webhook_event_retention_days: i64,
+    /// Public origin Shopify delivers webhooks to, e.g.
+    /// `https://app.example.com`; `None` leaves subscriptions to the operator
+    pub webhook_base_url: Option<String>,
+    /// Serve pages framed inside the Shopify admin (App Bridge)
+    pub embedded_app: bool,
+    /// Where browsers land after `/callback`, unless the flow's `return_to` says
+    /// otherwise; `None` shows the installed page
+    pub post_install_redirect_url: Option<String>,
+    /// Script tags added to every shop on install
+    pub script_tags: ScriptTagConfig,
+    /// Name, colour and logo on the HTML pages
+    pub branding: BrandingConfig,
+    /// Credentials accepted on `/api` and `/admin`
+    pub api_auth: ApiAuthConfig,
+    pub database: DatabaseConfig,
+    pub token_store: TokenStoreConfig,
+    pub rate_limit: RateLimitConfig,
+    pub downloads: DownloadConfig,
+    pub webhook_queue: WebhookQueueConfig,
+    pub order_sync: OrderSyncConfig,
+    pub catalog: CatalogConfig,
+    pub low_stock: LowStockConfig,
+    /// Slack, Teams, HTTP and email notifications for domain events
+    pub notifications: NotificationConfig,
+    /// SMTP, SendGrid or SES for recovery and notification emails; `None`
+    /// sends no email
+    pub mail: Option<MailConfig>,
+    /// Scheduled exports of the local copies to S3, BigQuery or Snowflake
+    pub warehouse_export: WarehouseExportConfig,
+    pub customer_index: CustomerIndexConfig,
+    pub idempotency: IdempotencyConfig,
+    pub data_retention: DataRetentionConfig,
+    pub response_cache: ResponseCacheConfig,
+    pub scheduler: SchedulerConfig,
+    pub job_queue: JobQueueConfig,
+    pub webhook_sampling: WebhookSamplingConfig,
+    pub recovery_tracking: RecoveryTrackingConfig,
+    pub http: HttpClientConfig,
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    pub config: AppConfig,
+    pub token_store: Arc<dyn TokenStore>,
+    pub state_store: Arc<dyn StateStore>,
+    pub webhook_events: WebhookEventStore,
+    pub webhook_queue: WebhookDispatcher,
+    pub webhook_sampler: Option<WebhookSampler>,
+    /// Verified webhooks for `/events/stream` subscribers
+    pub events: EventBroadcaster,
+    pub customer_mirror: CustomerMirrorStore,
+    pub order_mirror: OrderMirrorStore,
+    pub orders: OrderStore,
+    pub catalog: CatalogStore,
+    pub scheduler: Scheduler,
+    pub job_queue: JobQueue,
+    pub recovery_messages: RecoveryMessageStore,
+    /// Sends and logs emails when a mail provider is configured
+    pub email: Option<EmailSender>,
+    pub shopify: ShopifyClient,
+    pub api_usage: ApiUsageStore,
+    pub shop_secrets: ShopSecretStore,
+    pub document_templates: DocumentTemplateStore,
+    pub fulfillment_routing: FulfillmentRoutingStore,
+    pub low_stock: LowStockStore,
+    pub warehouse_exports: WarehouseExportStore,
+    pub token_audit: TokenAuditStore,
+    pub idempotency_keys: IdempotencyStore,
+    pub personal_data: PersonalDataStore,
+    pub response_cache: ResponseCache,
+    pub db: DatabaseRouter,
+}
+
+impl AppConfig {
+    /// Reads the configuration from the environment, which `.env` and the
+    /// config file (see `config_file::load_config_file`) fill in underneath.
+    /// Every missing or invalid setting is reported in one error.
+    pub fn from_env() -> AppResult<Self> {
+        dotenv::dotenv().ok();
+        let mut errors = ConfigErrors::default();
+        
+        let shop = errors.check(required_var("SHOP"));
+        let api_key = errors.check(required_var("API_KEY"));
+        let api_secret = errors.check(required_var("API_SECRET"));
+        let redirect_uri = errors.check(required_var("REDIRECT_URI"));
+        let environment = std::env::var("ENVIRONMENT")
+            .unwrap_or_else(|_| "development".to_string());
+        let skip_webhook_verification = errors.check(skip_verification_setting(
+            std::env::var("DANGEROUSLY_SKIP_WEBHOOK_VERIFICATION").ok().as_deref(),
+            &environment,
+        ));
+        let webhook_gateway_secret = errors.check(
+            gateway_secret_setting(std::env::var("WEBHOOK_GATEWAY_SECRET").ok().as_deref()),
+        );
+        let scopes = errors.check(
+            parse_scopes(
+                &std::env::var("SHOPIFY_SCOPES").unwrap_or_else(|_| "read_orders,read_checkouts".to_string()),
+            )
+            .map(|scopes| scope_list(&scopes))
+            .map_err(|e| AppError::Config(format!("SHOPIFY_SCOPES: {}", e))),
+        );
+        let port = errors.check(
+            std::env::var("PORT")
+                .unwrap_or_else(|_| "3000".to_string())
+                .parse::<u16>()
+                .map_err(|e| AppError::Config(format!("PORT: {}", e))),
+        );
+        let webhook_event_retention_days = errors.check(
+            std::env::var("WEBHOOK_EVENT_RETENTION_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse::<i64>()
+                .map_err(|e| AppError::Config(format!("WEBHOOK_EVENT_RETENTION_DAYS: {}", e))),
+        );
+        let tls = errors.check(TlsConfig::from_env());
+        let scheduler = errors.check(SchedulerConfig::from_env());
+        let data_retention = errors.check(DataRetentionConfig::from_env());
+        let api_auth = errors.check(ApiAuthConfig::from_env(&environment));
+        let post_install_redirect_url = errors.check(post_install_redirect_setting(
+            std::env::var("POST_INSTALL_REDIRECT_URL").ok().as_deref(),
+        ));
+        let script_tags = errors.check(ScriptTagConfig::from_env());
+        let notifications = errors.check(NotificationConfig::from_env());
+        let mail = errors.check(MailConfig::from_env());
+        let warehouse_export = errors.check(WarehouseExportConfig::from_env());
+        let branding = errors.check(BrandingConfig::from_env());
+        let database = errors.check(DatabaseConfig::from_env());
+        let token_store = errors.check(TokenStoreConfig::from_env());
+        let customer_index = CustomerIndexConfig::from_env();
+        
+        if let (Ok(database), Ok(token_store)) = (&database, &token_store) {
+            // A MySQL DATABASE_URL only backs token storage, so it doesn't
+            // count as the Postgres database the other backends need
+            let postgres_url = database.database_url.is_some() && token_store.mysql_url.is_none();
+            match token_store.backend {
+                TokenStoreBackend::Postgres if !postgres_url => {
+                    errors.add("A postgres:// DATABASE_URL is required unless TOKEN_STORE_BACKEND is mysql, sqlite or memory");
+                }
+                TokenStoreBackend::MySql if token_store.mysql_url.is_none() => {
+                    errors.add("TOKEN_STORE_BACKEND=mysql needs a mysql:// or mariadb:// DATABASE_URL");
+                }
+                _ => {}
+            }
+        }
+        // MySQL only stores tokens and OAuth states, so refuse settings that
+        // would otherwise do nothing for want of Postgres
+        if let (Ok(database), Ok(token_store), Ok(data_retention), Ok(warehouse_export)) =
+            (&database, &token_store, &data_retention, &warehouse_export)
+        {
+            let postgres_only: Vec<&str> = [
+                (!database.regional_database_urls.is_empty(), "DATABASE_URL_<REGION>"),
+                (data_retention.pii_retention_days.is_some(), "PII_RETENTION_DAYS"),
+                (warehouse_export.destination.is_some(), "WAREHOUSE_EXPORT"),
+                (customer_index.enabled, "CUSTOMER_INDEX_RECONCILE_INTERVAL_SECS"),
+            ]
+            .into_iter()
+            .filter_map(|(set, name)| set.then_some(name))
+            .collect();
+            if token_store.mysql_url.is_some() && !postgres_only.is_empty() {
+                errors.add(format!(
+                    "A MySQL DATABASE_URL only stores tokens and OAuth states; {} need a postgres:// DATABASE_URL",
+                    postgres_only.join(", ")
+                ));
+            }
+        }
+        if let (Ok(notifications), Ok(None)) = (&notifications, &mail) {
+            if notifications.uses_email() {
+                errors.add("Email notification channels need a MAIL_PROVIDER");
+            }
+        }
+        
+        errors.finish()?;
+        let api_secret = api_secret?;
+        let redirect_uri = redirect_uri?;
+        let token_store = token_store?;
+        let mut database = database?;
+        // The remaining features are Postgres-only and run as if a MySQL
+        // DATABASE_URL were unset
+        if token_store.mysql_url.is_some() {
+            database.database_url = None;
+        }
+        
+        Ok(AppConfig {
+            shop: shop?,
+            api_key: api_key?,
+            downloads: DownloadConfig::from_env(&api_secret),
+            webhook_sampling: WebhookSamplingConfig::from_env(&api_secret),
+            recovery_tracking: RecoveryTrackingConfig::from_env(&api_secret, &redirect_uri),
+            api_secret,
+            scopes: scopes?,
+            redirect_uri,
+            port: port?,
+            host: std::env::var("HOST")
+                .unwrap_or_else(|_| "0.0.0.0".to_string()),
+            tls: tls?,
+            environment,
+            skip_webhook_verification: skip_webhook_verification?,
+            webhook_gateway_secret: webhook_gateway_secret?,
+            webhook_event_retention_days: webhook_event_retention_days?,
+            webhook_base_url: std::env::var("WEBHOOK_BASE_URL")
+                .ok()
+                .map(|url| url.trim().trim_end_matches('/').to_string())
+                .filter(|url| !url.is_empty()),
+            embedded_app: std::env::var("SHOPIFY_EMBEDDED_APP")
+                .map(|v| v.trim().eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            post_install_redirect_url: post_install_redirect_url?,
+            script_tags: script_tags?,
+            branding: branding?,
+            api_auth: api_auth?,
+            database,
+            token_store,
+            rate_limit: RateLimitConfig::from_env(),
+            webhook_queue: WebhookQueueConfig::from_env(),
+            order_sync: OrderSyncConfig::from_env(),
+            catalog: CatalogConfig::from_env(),
+            low_stock: LowStockConfig::from_env(),
+            notifications: notifications?,
+            mail: mail?,
+            warehouse_export: warehouse_export?,
+            customer_index,
+            idempotency: IdempotencyConfig::from_env(),
+            data_retention: data_retention?,
+            response_cache: ResponseCacheConfig::from_env(),
+            scheduler: scheduler?,
+            job_queue: JobQueueConfig::from_env(),
+            http: HttpClientConfig::from_env(),
+        })
+    }
+}
+
+// =============================================================================
+// Helper Functions
+// =============================================================================
+
+/// Loads the shop's access token, distinguishing a missing token from a database failure.
+pub async fn require_token(token_store: &Arc<dyn TokenStore>, shop: &str) -> AppResult<String> {
+    match token_store.get_token(shop).await? {
+        Some(token) => Ok(token),
+        None => {
+            warn!("No access token found for shop: {}", shop);
+            Err(AppError::MissingToken)
+        }
+    }
+}
+
+// =============================================================================
+// Application Router
+// =============================================================================
+
+/// Every route the server exposes, with rate limiting, scope guards, and the
+/// global middleware stack applied.
+pub fn router(state: AppState) -> Router {
+    router_with_app_proxy(state, Router::new())
+}
+
+/// [`router`], with `proxy`'s routes mounted under `/proxy` next to the
+/// built-in ones. Point the app proxy in the Partner Dashboard at
+/// `{HOST}/proxy`; every request there must carry a valid Shopify signature,
+/// and handlers can take an [`app_proxy::AppProxyRequest`] to see which shop
+/// and customer it came from.
+pub fn router_with_app_proxy(state: AppState, proxy: Router<AppState>) -> Router {
+    // Create rate limiting layers, one limit per route group
+    let limiter = RateLimiter::new(state.config.rate_limit.clone()).unwrap_or_else(|e| {
+        warn!("Invalid REDIS_URL for rate limiting, counting in memory: {}", e);
+        RateLimiter::new(RateLimitConfig { use_redis: false, ..state.config.rate_limit.clone() })
+            .expect("in-memory rate limiter")
+    });
+    let oauth_rate_limiter = create_oauth_rate_limiter(&limiter);
+    let api_rate_limiter = create_api_rate_limiter(&limiter);
+    let general_rate_limiter = create_general_rate_limiter(&limiter);
+    let oauth_limited = || axum_middleware::from_fn_with_state(oauth_rate_limiter.clone(), rate_limit_middleware);
+    let api_limited = || axum_middleware::from_fn_with_state(api_rate_limiter.clone(), soft_rate_limit_middleware);
+    let general_limited = || axum_middleware::from_fn_with_state(general_rate_limiter.clone(), rate_limit_middleware);
+    
+    // Per-route scope requirements, checked against the stored grant
+    let guard_state = state.clone();
+    let scoped = |required: &'static [AccessScope]| {
+        axum_middleware::from_fn_with_state(ScopeGuard::new(&guard_state, required), scope_guard_middleware)
+    };
+    // API keys or JWTs with the area's scope, checked before anything else on the route
+    let api_auth = state.config.api_auth.clone();
+    let guarded = |area: ApiArea| axum_middleware::from_fn_with_state(ApiGuard::new(&api_auth, area), api_auth_middleware);
+    
+    // Writes that create Shopify resources honor an Idempotency-Key header
+    let idempotent = || axum_middleware::from_fn_with_state(state.clone(), idempotency_middleware);
+    // Shopify reads, shaped by ?select= and ?redact_pii=, then served from the
+    // response cache until their group changes
+    let cached = |group: CacheGroup| {
+        ServiceBuilder::new()
+            .layer(axum_middleware::from_fn_with_state(
+                RouteCache::new(&state.response_cache, &state.config.shop, group),
+                response_cache_middleware,
+            ))
+            .layer(axum_middleware::from_fn(response_shaping_middleware))
+    };
+    // Shopify writes, which empty their group's cache once they succeed
+    let invalidates = |group: CacheGroup| {
+        axum_middleware::from_fn_with_state(
+            RouteCache::new(&state.response_cache, &state.config.shop, group),
+            cache_invalidation_middleware,
+        )
+    };
+    
+    // Build application router with all endpoints and middleware
+    Router::new()
+        .route("/", get(home_handler).route_layer(general_limited()))
+        // OAuth routes with specific rate limiting
+        .route("/auth", get(auth_handler).route_layer(oauth_limited()))
+        .route("/callback", get(oauth_callback).route_layer(oauth_limited()))
+        // API routes with API-specific rate limiting
+        .nest("/api", Router::new()
+            .route("/shop", get(shop_handler).route_layer(cached(CacheGroup::Shop)))
+            .route("/access-scopes", get(access_scopes_handler))
+            .route(
+                "/storefront-token",
+                get(storefront_token_handler)
+                    .delete(revoke_storefront_token_handler)
+                    .route_layer(scoped(&[AccessScope::UnauthenticatedReadProductListings])),
+            )
+            .route("/orders", get(orders_handler).route_layer(cached(CacheGroup::Orders)).route_layer(scoped(&[AccessScope::ReadOrders])))
+            .route("/orders/count", get(orders_count_handler).route_layer(cached(CacheGroup::Orders)).route_layer(scoped(&[AccessScope::ReadOrders])))
+            .route("/orders/summary", get(order_summary_handler).route_layer(scoped(&[AccessScope::ReadOrders])))
+            .route("/orders/:id", get(order_handler).route_layer(cached(CacheGroup::Orders)).route_layer(scoped(&[AccessScope::ReadOrders])))
+            .route("/orders/:id/timeline", get(order_timeline_handler).route_layer(scoped(&[AccessScope::ReadOrders])))
+            .route("/orders/:id/risks", get(order_risks_handler).route_layer(scoped(&[AccessScope::ReadOrders])))
+            .route(
+                "/orders/:id/fulfillments",
+                get(fulfillments_handler)
+                    .route_layer(cached(CacheGroup::Orders))
+                    .route_layer(scoped(&[AccessScope::ReadOrders]))
+                    .merge(
+                        axum::routing::post(create_fulfillment_handler)
+                            .route_layer(invalidates(CacheGroup::Orders))
+                            .route_layer(idempotent())
+                            .route_layer(scoped(&[AccessScope::WriteFulfillments, AccessScope::WriteMerchantManagedFulfillmentOrders])),
+                    ),
+            )
+            .route("/orders/:id/fulfillment-orders", get(fulfillment_orders_handler).route_layer(cached(CacheGroup::Orders)).route_layer(scoped(&[AccessScope::ReadMerchantManagedFulfillmentOrders])))
+            .route("/orders/:id/documents/:document", get(order_document_handler).route_layer(scoped(&[AccessScope::ReadOrders])))
+            .route(
+                "/orders/:id/fulfillment-route",
+                get(preview_fulfillment_route_handler)
+                    .route_layer(scoped(&[AccessScope::ReadMerchantManagedFulfillmentOrders, AccessScope::ReadInventory]))
+                    .merge(
+                        axum::routing::post(route_fulfillment_handler)
+                            .route_layer(idempotent())
+                            .route_layer(scoped(&[AccessScope::WriteMerchantManagedFulfillmentOrders, AccessScope::ReadInventory])),
+                    ),
+            )
+            .route("/abandoned-checkouts", get(abandoned_checkouts_handler).route_layer(cached(CacheGroup::Checkouts)).route_layer(scoped(&[AccessScope::ReadCheckouts])))
+            .route("/abandoned-checkouts/count", get(abandoned_checkouts_count_handler).route_layer(cached(CacheGroup::Checkouts)).route_layer(scoped(&[AccessScope::ReadCheckouts])))
+            .route("/products", get(products_handler).route_layer(cached(CacheGroup::Products)).route_layer(scoped(&[AccessScope::ReadProducts])))
+            .route("/products/count", get(products_count_handler).route_layer(cached(CacheGroup::Products)).route_layer(scoped(&[AccessScope::ReadProducts])))
+            .route("/products/:id", get(product_handler).route_layer(cached(CacheGroup::Products)).route_layer(scoped(&[AccessScope::ReadProducts])))
+            .route("/products/:id/prices", get(product_prices_handler).route_layer(scoped(&[AccessScope::ReadProducts])))
+            .route("/catalog", get(catalog_handler).route_layer(scoped(&[AccessScope::ReadProducts])))
+            .route("/customers", get(customers_handler).route_layer(cached(CacheGroup::Customers)).route_layer(scoped(&[AccessScope::ReadCustomers])))
+            .route("/customers/count", get(customers_count_handler).route_layer(cached(CacheGroup::Customers)).route_layer(scoped(&[AccessScope::ReadCustomers])))
+            .route("/customers/search", get(customer_search_handler).route_layer(scoped(&[AccessScope::ReadCustomers])))
+            .route("/customers/:id", get(customer_handler).route_layer(cached(CacheGroup::Customers)).route_layer(scoped(&[AccessScope::ReadCustomers])))
+            .route("/customers/duplicates", get(customer_duplicates_handler).route_layer(scoped(&[AccessScope::ReadCustomers])))
+            .route("/customers/merge", axum::routing::post(customer_merge_handler).route_layer(invalidates(CacheGroup::Customers)).route_layer(scoped(&[AccessScope::WriteCustomers])))
+            .route("/shops/:shop/api-usage", get(api_usage_handler))
+            .route("/inventory", get(inventory_handler).route_layer(cached(CacheGroup::Inventory)).route_layer(scoped(&[AccessScope::ReadInventory])))
+            .route(
+                "/inventory/adjust",
+                axum::routing::post(inventory_adjust_handler)
+                    .route_layer(invalidates(CacheGroup::Inventory))
+                    .route_layer(idempotent())
+                    .route_layer(scoped(&[AccessScope::WriteInventory])),
+            )
+            .route("/inventory/set", axum::routing::post(inventory_set_handler).route_layer(invalidates(CacheGroup::Inventory)).route_layer(scoped(&[AccessScope::WriteInventory])))
+            .route("/inventory/connect", axum::routing::post(inventory_connect_handler).route_layer(invalidates(CacheGroup::Inventory)).route_layer(scoped(&[AccessScope::WriteInventory])))
+            .route("/inventory/alerts", get(low_stock_alerts_handler).route_layer(scoped(&[AccessScope::ReadInventory])))
+            .route("/locations", get(locations_handler).route_layer(cached(CacheGroup::Locations)).route_layer(scoped(&[AccessScope::ReadLocations])))
+            .route("/locations/count", get(locations_count_handler).route_layer(cached(CacheGroup::Locations)).route_layer(scoped(&[AccessScope::ReadLocations])))
+            .route("/locations/:id", get(location_handler).route_layer(cached(CacheGroup::Locations)).route_layer(scoped(&[AccessScope::ReadLocations])))
+            .route("/reports/sales", get(sales_report_handler).route_layer(cached(CacheGroup::Orders)).route_layer(scoped(&[AccessScope::ReadOrders])))
+            .route("/metrics/sales", get(sales_metrics_handler).route_layer(cached(CacheGroup::Orders)).route_layer(scoped(&[AccessScope::ReadOrders])))
+            .route("/reports/product-affinity", get(product_affinity_handler).route_layer(cached(CacheGroup::Orders)).route_layer(scoped(&[AccessScope::ReadOrders])))
+            .route("/recovery/messages", axum::routing::post(record_recovery_send_handler))
+            .route("/recovery/emails", axum::routing::post(send_recovery_email_handler))
+            .route("/recovery/messages/:id/bounce", axum::routing::post(record_recovery_bounce_handler))
+            .route("/recovery/deliverability", get(recovery_deliverability_handler))
+            .route(
+                "/gift-cards",
+                get(gift_cards_handler)
+                    .route_layer(scoped(&[AccessScope::ReadGiftCards]))
+                    .merge(
+                        axum::routing::post(create_gift_card_handler)
+                            .route_layer(idempotent())
+                            .route_layer(scoped(&[AccessScope::WriteGiftCards])),
+                    ),
+            )
+            .route(
+                "/script-tags",
+                get(script_tags_handler)
+                    .route_layer(scoped(&[AccessScope::ReadScriptTags]))
+                    .merge(
+                        axum::routing::post(create_script_tag_handler)
+                            .route_layer(idempotent())
+                            .route_layer(scoped(&[AccessScope::WriteScriptTags])),
+                    ),
+            )
+            .route("/script-tags/:id", axum::routing::delete(delete_script_tag_handler).route_layer(scoped(&[AccessScope::WriteScriptTags])))
+            .route("/themes/:theme/assets", axum::routing::put(put_theme_asset_handler).route_layer(scoped(&[AccessScope::WriteThemes])))
+            .route("/gift-cards/:id", get(gift_card_handler).route_layer(scoped(&[AccessScope::ReadGiftCards])))
+            .route("/gift-cards/:id/disable", axum::routing::post(disable_gift_card_handler).route_layer(scoped(&[AccessScope::WriteGiftCards])))
+            .route("/payouts", get(payouts_handler).route_layer(scoped(&[AccessScope::ReadShopifyPaymentsPayouts])))
+            .route("/payouts/:id/transactions", get(payout_transactions_handler).route_layer(scoped(&[AccessScope::ReadShopifyPaymentsPayouts])))
+            .route("/balance", get(balance_handler).route_layer(scoped(&[AccessScope::ReadShopifyPaymentsPayouts])))
+            // Scopes depend on the owning resource, so the handlers check them
+            .route(
+                "/:resource/:id/metafields",
+                get(list_metafields_handler)
+                    .post(create_metafield_handler)
+                    .put(update_metafield_handler)
+                    .delete(delete_metafield_handler)
+                    .route_layer(idempotent()),
+            )
+            .route(
+                "/checkout-settings",
+                get(get_checkout_settings_handler)
+                    .route_layer(scoped(&[AccessScope::ReadCheckouts]))
+                    .merge(axum::routing::put(put_checkout_settings_handler).route_layer(scoped(&[AccessScope::WriteCheckouts]))),
+            )
+            .route_layer(api_limited())
+            .route_layer(guarded(ApiArea::Api))
+            // Per IP ahead of authentication, so failed keys are limited too
+            .route_layer(general_limited())
+        )
+        // Webhook routes, unlimited: Shopify's bursts are HMAC-verified and must not be dropped
+        .nest("/webhooks", Router::new()
+            .route("/", get(list_webhooks_handler))
+            .route("/orders/created", axum::routing::post(orders_created_webhook))
+            .route("/orders/updated", axum::routing::post(orders_updated_webhook))
+            .route("/orders/cancelled", axum::routing::post(orders_cancelled_webhook))
+            .route("/orders/paid", axum::routing::post(orders_paid_webhook))
+            .route("/orders/fulfilled", axum::routing::post(orders_fulfilled_webhook))
+            .route("/orders/risk_assessment_changed", axum::routing::post(orders_risk_assessment_changed_webhook))
+            .route("/refunds/created", axum::routing::post(refunds_created_webhook))
+            .route("/products/created", axum::routing::post(products_created_webhook))
+            .route("/products/updated", axum::routing::post(products_updated_webhook))
+            .route("/products/deleted", axum::routing::post(products_deleted_webhook))
+            .route("/inventory_levels/updated", axum::routing::post(inventory_levels_updated_webhook))
+            .route("/customers/created", axum::routing::post(customers_created_webhook))
+            .route("/customers/updated", axum::routing::post(customers_updated_webhook))
+            .route("/customers/deleted", axum::routing::post(customers_deleted_webhook))
+            .route("/customers/redact", axum::routing::post(customers_redact_webhook))
+            .route("/checkouts/created", axum::routing::post(checkouts_created_webhook))
+            .route("/checkouts/updated", axum::routing::post(checkouts_updated_webhook))
+            .route("/app/uninstalled", axum::routing::post(app_uninstalled_webhook))
+        )
+        // Live webhook events for internal dashboards, unlimited since each
+        // subscriber holds one long-lived request
+        .merge(Router::new()
+            .route("/events/stream", get(event_stream_handler))
+            .route("/ws/token", get(event_socket_ticket_handler))
+            .route_layer(guarded(ApiArea::Api))
+        )
+        // Checks credentials or a /ws/token ticket itself, as browsers can't send headers
+        .route("/ws", get(event_socket_handler))
+        // Admin routes
+        .nest("/admin", Router::new()
+            .route("/webhooks/events/:id", get(webhook_event_handler))
+            .route("/webhooks/events/:id/replay", axum::routing::post(replay_webhook_event_handler))
+            .route("/shops/:shop/secrets", get(list_shop_secrets_handler))
+            .route(
+                "/shops/:shop/secrets/webhook",
+                axum::routing::put(put_webhook_secret_handler).delete(delete_webhook_secret_handler),
+            )
+            .route(
+                "/shops/:shop/document-templates/:document",
+                get(get_document_template_handler).put(put_document_template_handler),
+            )
+            .route(
+                "/shops/:shop/fulfillment-routing",
+                get(get_fulfillment_routing_handler).put(put_fulfillment_routing_handler),
+            )
+            .route(
+                "/shops/:shop/low-stock-rules",
+                get(get_low_stock_rules_handler).put(put_low_stock_rules_handler),
+            )
+            .route("/shops/:shop/region", get(get_shop_region_handler).put(put_shop_region_handler))
+            .route("/audit", get(token_audit_handler))
+            .route("/jobs", get(jobs_handler))
+            .route("/queue", get(job_queue_handler))
+            .route("/queue/jobs", get(list_queued_jobs_handler))
+            .route("/queue/jobs/:id", get(queued_job_handler))
+            .route("/queue/jobs/:id/retry", axum::routing::post(retry_queued_job_handler))
+            .route("/shops", get(list_installed_shops_handler))
+            .route("/shops/:shop", axum::routing::delete(revoke_shop_handler))
+            .route("/shops/:shop/health", get(shop_health_handler))
+            .route(
+                "/shops/:shop/orders/sync",
+                get(order_sync_status_handler).post(start_order_sync_handler),
+            )
+            .route(
+                "/shops/:shop/catalog/sync",
+                get(catalog_sync_status_handler).post(start_catalog_sync_handler),
+            )
+            .route(
+                "/shops/:shop/customers/sync",
+                get(customer_index_sync_status_handler).post(start_customer_index_sync_handler),
+            )
+            .route(
+                "/shops/:shop/warehouse-export",
+                get(warehouse_export_status_handler).post(start_warehouse_export_handler),
+            )
+            .route_layer(general_limited())
+            .route_layer(guarded(ApiArea::Admin))
+            .route_layer(general_limited())
+        )
+        .merge(Router::new()
+            // Embedded admin, authenticated by App Bridge session tokens
+            .route("/embedded/session", get(embedded_session_handler))
+            // Signed export downloads
+            .route("/downloads/:token", get(download_handler))
+            // JSON Schemas for events this app emits
+            .route("/schemas", get(list_schemas_handler))
+            .route("/schemas/*name", get(schema_handler))
+            .route("/openapi.json", get(openapi_handler))
+            .route("/docs", get(swagger_ui_handler))
+            .route_layer(general_limited())
+        )
+        // Storefront requests forwarded by the app proxy, unlimited since they
+        // all arrive from Shopify's servers
+        .nest("/proxy", proxy
+            .route("/session", get(app_proxy_session_handler))
+            .route_layer(axum_middleware::from_fn_with_state(state.clone(), app_proxy_middleware))
+        )
+        // Recovery email open pixel and click redirect, unlimited since mail
+        // providers fetch them through shared proxies
+        .route("/recovery/open/:token", get(recovery_open_handler))
+        .route("/recovery/click/:token", get(recovery_click_handler))
+        // Legacy routes for backward compatibility
+        .merge(Router::new()
+            .route("/orders", get(orders_handler).route_layer(cached(CacheGroup::Orders)).route_layer(scoped(&[AccessScope::ReadOrders])))
+            .route("/abandoned-checkouts", get(abandoned_checkouts_handler).route_layer(cached(CacheGroup::Checkouts)).route_layer(scoped(&[AccessScope::ReadCheckouts])))
+            .route("/abandoned-checkouts/count", get(abandoned_checkouts_count_handler).route_layer(cached(CacheGroup::Checkouts)).route_layer(scoped(&[AccessScope::ReadCheckouts])))
+            .route_layer(api_limited())
+            .route_layer(guarded(ApiArea::Api))
+            .route_layer(general_limited())
+        )
+        // Global middleware layers (applied in reverse order)
+        .layer(axum_middleware::from_fn(security_headers_middleware))
+        .layer(axum_middleware::from_fn_with_state(state.config.embedded_app, frame_ancestors_middleware))
+        .layer(axum_middleware::from_fn(request_logging_middleware))
+        .layer(axum_middleware::from_fn(request_id_middleware))
+        .layer(axum_middleware::from_fn(audit_context_middleware))
+        .layer(axum_middleware::from_fn_with_state(state.config.rate_limit.trusted_proxy_hops, client_ip_middleware))
+        .layer(CorsLayer::permissive()) // Enable CORS for development
+        .with_state(state)
+}
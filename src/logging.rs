@@ -0,0 +1,54 @@
+use std::str::FromStr;
+use tracing_subscriber::EnvFilter;
+
+use crate::error::{AppError, AppResult};
+
+// =============================================================================
+// Log Output
+// =============================================================================
+
+/// How log lines are written, from `LOG_FORMAT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// One JSON object per line, for log aggregation
+    Json,
+    /// Human-readable lines for a terminal
+    #[default]
+    Pretty,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "pretty" => Ok(Self::Pretty),
+            other => Err(format!("LOG_FORMAT must be json or pretty, got {}", other)),
+        }
+    }
+}
+
+impl LogFormat {
+    pub fn from_env() -> AppResult<Self> {
+        match std::env::var("LOG_FORMAT") {
+            Ok(value) if !value.trim().is_empty() => value.parse().map_err(AppError::Config),
+            _ => Ok(Self::default()),
+        }
+    }
+}
+
+/// Installs the global subscriber. `RUST_LOG` picks levels, defaulting to `info`.
+pub fn init_tracing() -> AppResult<()> {
+    dotenv::dotenv().ok();
+    let format = LogFormat::from_env()?;
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    // JSON lines keep span fields such as request_id next to the event's own
+    let result = match format {
+        LogFormat::Json => builder.json().flatten_event(true).with_current_span(true).with_span_list(false).try_init(),
+        LogFormat::Pretty => builder.try_init(),
+    };
+    result.map_err(|e| AppError::Config(format!("Failed to initialise logging: {}", e)))
+}
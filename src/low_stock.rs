@@ -0,0 +1,543 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    require_token,
+    database::{LowStockChange, LowStockStore},
+    domain_events::{DomainEvent, DomainEventSubscriber},
+    error::{AppError, AppResult, ShopifyError},
+    http_client::ShopifyClient,
+    market_prices::legacy_id,
+    scheduler::{Job, JobResult, JobScope, Schedule},
+    token_store::TokenStore,
+};
+
+/// Topic of Shopify's webhook for a changed stock level.
+pub const INVENTORY_LEVEL_TOPIC: &str = "inventory_levels/update";
+
+const ALERTS_DEFAULT_LIMIT: u32 = 50;
+const ALERTS_MAX_LIMIT: u32 = 250;
+
+/// SKUs looked up per sweep query, to keep the search string short.
+const SKUS_PER_QUERY: usize = 50;
+
+/// Stock at every location of the inventory items matching a SKU search.
+const STOCK_LEVELS_QUERY: &str = r#"
+query LowStockLevels($query: String!) {
+  inventoryItems(first: 250, query: $query) {
+    nodes {
+      id
+      sku
+      inventoryLevels(first: 50) {
+        nodes {
+          location { id }
+          quantities(names: ["available"]) { name quantity }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// The SKU of one inventory item, which `inventory_levels/update` doesn't carry.
+const INVENTORY_ITEM_SKU_QUERY: &str = r#"
+query InventoryItemSku($id: ID!) {
+  inventoryItem(id: $id) { sku }
+}
+"#;
+
+// =============================================================================
+// Low-Stock Configuration
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct LowStockConfig {
+    /// Re-check every shop's rules against Shopify periodically, catching
+    /// levels whose webhooks were missed and rules changed since
+    pub sweep_enabled: bool,
+    pub sweep_interval: Duration,
+    /// Slack incoming webhook new alerts are posted to; alerts are only
+    /// listed at `/api/inventory/alerts` when unset
+    pub slack_webhook_url: Option<String>,
+}
+
+impl Default for LowStockConfig {
+    fn default() -> Self {
+        Self {
+            sweep_enabled: true,
+            sweep_interval: Duration::from_secs(60 * 60),
+            slack_webhook_url: None,
+        }
+    }
+}
+
+impl LowStockConfig {
+    /// `LOW_STOCK_SWEEP_INTERVAL_SECS` (0 turns the sweep off) and
+    /// `LOW_STOCK_SLACK_WEBHOOK_URL`.
+    pub fn from_env() -> Self {
+        let slack_webhook_url = std::env::var("LOW_STOCK_SLACK_WEBHOOK_URL")
+            .ok()
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty());
+        match std::env::var("LOW_STOCK_SWEEP_INTERVAL_SECS").ok().and_then(|v| v.parse::<u64>().ok()) {
+            Some(0) => Self { sweep_enabled: false, slack_webhook_url, ..Self::default() },
+            Some(secs) => Self { sweep_enabled: true, sweep_interval: Duration::from_secs(secs), slack_webhook_url },
+            None => Self { slack_webhook_url, ..Self::default() },
+        }
+    }
+}
+
+// =============================================================================
+// Low-Stock Rules
+// =============================================================================
+
+/// Alert when a SKU's available stock drops to `threshold` or below.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LowStockRule {
+    pub sku: String,
+    /// Only at this location; at every location when unset
+    #[serde(default)]
+    pub location_id: Option<u64>,
+    pub threshold: i32,
+}
+
+/// A shop's low-stock rules.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct LowStockRules {
+    pub rules: Vec<LowStockRule>,
+}
+
+impl LowStockRules {
+    /// The threshold for `sku` at `location_id`. A rule for that location
+    /// wins over one for every location.
+    pub fn threshold_for(&self, sku: &str, location_id: u64) -> Option<i32> {
+        let matching = || self.rules.iter().filter(|rule| rule.sku == sku);
+        matching()
+            .find(|rule| rule.location_id == Some(location_id))
+            .or_else(|| matching().find(|rule| rule.location_id.is_none()))
+            .map(|rule| rule.threshold)
+    }
+
+    /// Every SKU with a rule, once each.
+    pub fn skus(&self) -> Vec<&str> {
+        let skus: BTreeSet<&str> = self.rules.iter().map(|rule| rule.sku.as_str()).collect();
+        skus.into_iter().collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seen = BTreeSet::new();
+        for rule in &self.rules {
+            if rule.sku.trim().is_empty() || rule.sku.trim() != rule.sku {
+                return Err(format!("Rule SKU {:?} must be non-empty without surrounding spaces", rule.sku));
+            }
+            if rule.threshold < 0 {
+                return Err(format!("Rule for {} needs a threshold of 0 or more", rule.sku));
+            }
+            if !seen.insert((rule.sku.as_str(), rule.location_id)) {
+                return Err(format!("{} has more than one rule for the same location", rule.sku));
+            }
+        }
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Stock Levels
+// =============================================================================
+
+/// `inventory_levels/update` payload.
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema, ToSchema)]
+pub struct InventoryLevelWebhook {
+    pub inventory_item_id: u64,
+    pub location_id: u64,
+    /// Absent for items whose stock isn't tracked
+    pub available: Option<i32>,
+    pub updated_at: Option<String>,
+    pub admin_graphql_api_id: Option<String>,
+}
+
+/// Available stock of a SKU at one location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StockLevel {
+    pub inventory_item_id: u64,
+    pub location_id: u64,
+    pub sku: String,
+    pub available: i32,
+}
+
+#[derive(Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StockLevelsData {
+    inventory_items: Nodes<StockItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StockItem {
+    id: String,
+    sku: Option<String>,
+    inventory_levels: Nodes<StockItemLevel>,
+}
+
+#[derive(Deserialize)]
+struct StockItemLevel {
+    location: LocationRef,
+    quantities: Vec<Quantity>,
+}
+
+#[derive(Deserialize)]
+struct LocationRef {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct Quantity {
+    name: String,
+    quantity: i32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InventoryItemSkuData {
+    inventory_item: Option<InventoryItemSku>,
+}
+
+#[derive(Deserialize)]
+struct InventoryItemSku {
+    sku: Option<String>,
+}
+
+/// Search string matching any of `skus` exactly, for `inventoryItems(query:)`.
+pub fn sku_search_query(skus: &[&str]) -> String {
+    skus.iter()
+        .map(|sku| format!("sku:\"{}\"", sku.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
+/// Available stock of `skus` at every location they're stocked at.
+pub async fn fetch_stock_levels(client: &ShopifyClient, token: &str, skus: &[&str]) -> Result<Vec<StockLevel>, ShopifyError> {
+    let mut levels = Vec::new();
+    for chunk in skus.chunks(SKUS_PER_QUERY) {
+        let data: StockLevelsData = client
+            .graphql_with_auth(token, STOCK_LEVELS_QUERY, serde_json::json!({ "query": sku_search_query(chunk) }))
+            .await?;
+
+        for item in data.inventory_items.nodes {
+            // Shopify's search is looser than an exact match
+            let (Some(sku), Some(inventory_item_id)) = (item.sku, legacy_id(&item.id)) else { continue };
+            if !chunk.contains(&sku.as_str()) {
+                continue;
+            }
+            for level in item.inventory_levels.nodes {
+                let available = level.quantities.iter().find(|q| q.name == "available").map(|q| q.quantity);
+                if let (Some(location_id), Some(available)) = (legacy_id(&level.location.id), available) {
+                    levels.push(StockLevel { inventory_item_id, location_id, sku: sku.clone(), available });
+                }
+            }
+        }
+    }
+    Ok(levels)
+}
+
+async fn fetch_sku(client: &ShopifyClient, token: &str, inventory_item_id: u64) -> Result<Option<String>, ShopifyError> {
+    let data: InventoryItemSkuData = client
+        .graphql_with_auth(
+            token,
+            INVENTORY_ITEM_SKU_QUERY,
+            serde_json::json!({ "id": format!("gid://shopify/InventoryItem/{}", inventory_item_id) }),
+        )
+        .await?;
+    Ok(data.inventory_item.and_then(|item| item.sku).filter(|sku| !sku.is_empty()))
+}
+
+// =============================================================================
+// Alert Evaluation
+// =============================================================================
+
+/// Posts new alerts to Slack, when a webhook URL is configured.
+#[derive(Clone)]
+pub struct LowStockNotifier {
+    client: reqwest::Client,
+    slack_webhook_url: Option<String>,
+}
+
+impl LowStockNotifier {
+    pub fn new(config: &LowStockConfig) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            slack_webhook_url: config.slack_webhook_url.clone(),
+        }
+    }
+
+    pub fn message(shop: &str, level: &StockLevel, threshold: i32) -> String {
+        format!(
+            ":warning: Low stock at {}: {} has {} available at location {} (alert at {} or fewer)",
+            shop, level.sku, level.available, level.location_id, threshold
+        )
+    }
+
+    /// Whether a notification went out; `Ok(false)` when there's nowhere to send it.
+    pub async fn notify(&self, shop: &str, level: &StockLevel, threshold: i32) -> Result<bool, String> {
+        let Some(ref url) = self.slack_webhook_url else {
+            return Ok(false);
+        };
+        let response = self.client
+            .post(url)
+            .json(&serde_json::json!({ "text": Self::message(shop, level, threshold) }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("Slack answered {}", response.status()));
+        }
+        Ok(true)
+    }
+}
+
+/// Checks a stock level against the shop's rules, opening or resolving its
+/// alert, and notifies when an alert opens. A failed notification is retried
+/// the next time the level is checked.
+pub async fn evaluate_stock_level(
+    store: &LowStockStore,
+    notifier: &LowStockNotifier,
+    shop: &str,
+    rules: &LowStockRules,
+    level: &StockLevel,
+) -> AppResult<LowStockChange> {
+    let threshold = rules.threshold_for(&level.sku, level.location_id);
+    let (inventory_item_id, location_id) = (level.inventory_item_id as i64, level.location_id as i64);
+    let change = store
+        .record_level(shop, inventory_item_id, location_id, &level.sku, level.available, threshold)
+        .await?;
+
+    if let (LowStockChange::Triggered, Some(threshold)) = (change, threshold) {
+        info!("📉 {} is low at location {} for {}: {} available", level.sku, level.location_id, shop, level.available);
+        match notifier.notify(shop, level, threshold).await {
+            Ok(true) => store.mark_notified(shop, inventory_item_id, location_id).await?,
+            Ok(false) => {}
+            Err(e) => warn!("Failed to send low-stock alert for {} at {}: {}", level.sku, shop, e),
+        }
+    }
+    Ok(change)
+}
+
+/// Evaluates the levels `inventory_levels/update` webhooks report.
+pub struct LowStockAlerts {
+    store: LowStockStore,
+    notifier: LowStockNotifier,
+    shopify: ShopifyClient,
+    token_store: Arc<dyn TokenStore>,
+}
+
+impl LowStockAlerts {
+    pub fn new(store: LowStockStore, notifier: LowStockNotifier, shopify: ShopifyClient, token_store: Arc<dyn TokenStore>) -> Self {
+        Self { store, notifier, shopify, token_store }
+    }
+}
+
+#[async_trait]
+impl DomainEventSubscriber for LowStockAlerts {
+    fn name(&self) -> &str {
+        "low-stock alerts"
+    }
+
+    async fn handle(&self, shop_domain: &str, event: &DomainEvent) -> AppResult<()> {
+        let DomainEvent::InventoryLevelUpdated { inventory_item_id, location_id, available: Some(available) } = *event else {
+            return Ok(());
+        };
+        // Shops without rules cost nothing
+        let Some(rules) = self.store.get_rules(shop_domain).await?.filter(|rules| !rules.rules.is_empty()) else {
+            return Ok(());
+        };
+
+        let token = require_token(&self.token_store, shop_domain).await?;
+        let Some(sku) = fetch_sku(&self.shopify, &token, inventory_item_id).await? else {
+            return Ok(());
+        };
+        let level = StockLevel { inventory_item_id, location_id, sku, available };
+        evaluate_stock_level(&self.store, &self.notifier, shop_domain, &rules, &level).await.map(|_| ())
+    }
+}
+
+/// Re-checks every shop's rules against Shopify's stock levels.
+pub fn low_stock_sweep_job(config: &LowStockConfig) -> Job {
+    Job::new("low-stock-sweep", Schedule::Every(config.sweep_interval), JobScope::Cluster, |state: AppState| async move {
+        sweep_shops(&state).await
+    })
+}
+
+async fn sweep_shops(state: &AppState) -> JobResult {
+    let shops = state.token_store.list_shops().await.map_err(|e| e.to_string())?;
+    let notifier = LowStockNotifier::new(&state.config.low_stock);
+
+    let (mut checked, mut low, mut failed) = (0, 0, Vec::new());
+    for shop in shops {
+        match sweep_shop(state, &notifier, &shop.shop_domain).await {
+            Ok((levels, low_levels)) => {
+                checked += levels;
+                low += low_levels;
+            }
+            Err(e) => {
+                error!("Failed to sweep low-stock rules for {}: {}", shop.shop_domain, e);
+                failed.push(shop.shop_domain);
+            }
+        }
+    }
+
+    let summary = format!("{} stock levels checked, {} low", checked, low);
+    if failed.is_empty() {
+        Ok(summary)
+    } else {
+        Err(format!("{}, failed for {}", summary, failed.join(", ")))
+    }
+}
+
+async fn sweep_shop(state: &AppState, notifier: &LowStockNotifier, shop: &str) -> AppResult<(usize, usize)> {
+    let Some(rules) = state.low_stock.get_rules(shop).await?.filter(|rules| !rules.rules.is_empty()) else {
+        return Ok((0, 0));
+    };
+    let token = require_token(&state.token_store, shop).await?;
+    let levels = fetch_stock_levels(&state.shopify, &token, &rules.skus()).await?;
+
+    let mut low = 0;
+    for level in &levels {
+        match evaluate_stock_level(&state.low_stock, notifier, shop, &rules, level).await? {
+            LowStockChange::Triggered | LowStockChange::StillLow => low += 1,
+            LowStockChange::Resolved | LowStockChange::Unchanged => {}
+        }
+    }
+    Ok((levels.len(), low))
+}
+
+// =============================================================================
+// Low-Stock Handlers
+// =============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LowStockAlertParams {
+    /// `open` (default) or `all`, which includes resolved alerts
+    pub status: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// `GET /api/inventory/alerts` — stock levels at or below the shop's
+/// low-stock thresholds, most recent first.
+#[utoipa::path(
+    get,
+    path = "/api/inventory/alerts",
+    tag = "inventory",
+    params(LowStockAlertParams),
+    responses(
+        (status = 200, description = "Low-stock alerts", body = crate::openapi::LowStockAlertList),
+        (status = 400, description = "Unknown status", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn low_stock_alerts_handler(
+    Query(params): Query<LowStockAlertParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let status = params.status.as_deref().unwrap_or("open");
+    let include_resolved = match status {
+        "open" => false,
+        "all" => true,
+        other => return Err(AppError::BadRequest(format!("status must be open or all, got {}", other))),
+    };
+    let limit = params.limit.unwrap_or(ALERTS_DEFAULT_LIMIT).clamp(1, ALERTS_MAX_LIMIT);
+
+    let alerts = state.low_stock.list_alerts(shop, include_resolved, limit as i64).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "status": status,
+        "alerts_count": alerts.len(),
+        "alerts": alerts
+    }))))
+}
+
+/// The shop's low-stock rules, or none when none are stored.
+#[utoipa::path(
+    get,
+    path = "/admin/shops/{shop}/low-stock-rules",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 200, description = "The shop's low-stock rules", body = crate::openapi::LowStockRuleSet),
+    ),
+)]
+pub async fn get_low_stock_rules_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let rules = state.low_stock.get_rules(&shop).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "configured": rules.is_some(),
+        "low_stock": rules.unwrap_or_default()
+    }))))
+}
+
+/// Replaces the shop's low-stock rules, resolving open alerts no rule covers
+/// any more. New thresholds apply as levels are next checked.
+#[utoipa::path(
+    put,
+    path = "/admin/shops/{shop}/low-stock-rules",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    request_body = LowStockRules,
+    responses(
+        (status = 200, description = "The stored rules", body = crate::openapi::LowStockRulesUpdated),
+        (status = 400, description = "A rule without a SKU, with a negative threshold, or repeated", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn put_low_stock_rules_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+    Json(rules): Json<LowStockRules>,
+) -> AppResult<impl IntoResponse> {
+    rules.validate().map_err(AppError::BadRequest)?;
+
+    state.low_stock.put_rules(&shop, &rules).await?;
+
+    // Neither webhooks nor sweeps look at SKUs without rules again
+    let mut resolved = 0;
+    for alert in state.low_stock.list_alerts(&shop, false, i64::MAX).await? {
+        if rules.threshold_for(&alert.sku, alert.location_id as u64).is_none() {
+            state.low_stock
+                .record_level(&shop, alert.inventory_item_id, alert.location_id, &alert.sku, alert.available, None)
+                .await?;
+            resolved += 1;
+        }
+    }
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "alerts_resolved": resolved,
+        "shop": shop,
+        "low_stock": rules
+    }))))
+}
@@ -0,0 +1,267 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    require_token,
+    error::{AppError, AppResult, ShopifyError},
+};
+
+/// Contextual prices of a product's variants for one country. The REST
+/// Admin API only knows the base price.
+const PRODUCT_PRICES_QUERY: &str = r#"
+query ProductPrices($id: ID!, $country: CountryCode!) {
+  product(id: $id) {
+    title
+    variants(first: 250) {
+      nodes {
+        id
+        title
+        sku
+        price
+        compareAtPrice
+        contextualPricing(context: {country: $country}) {
+          price { amount currencyCode }
+          compareAtPrice { amount currencyCode }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// A market and the first country it sells to, which stands in for it in
+/// contextual pricing.
+const MARKET_QUERY: &str = r#"
+query MarketCountry($id: ID!) {
+  market(id: $id) {
+    id
+    name
+    handle
+    regions(first: 1) {
+      nodes {
+        ... on MarketRegionCountry { code }
+      }
+    }
+  }
+}
+"#;
+
+// =============================================================================
+// Market Price Structures
+// =============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MarketPriceParams {
+    /// Two-letter country code, e.g. `CA`, or a market ID
+    pub market: Option<String>,
+}
+
+/// What `?market=` names: a country to price for directly, or a market
+/// (by GraphQL ID) whose country is looked up first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarketSelector {
+    Country(String),
+    Market(String),
+}
+
+impl MarketSelector {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value.len() == 2 && value.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Ok(Self::Country(value.to_ascii_uppercase()));
+        }
+        if value.starts_with("gid://shopify/Market/") {
+            return Ok(Self::Market(value.to_string()));
+        }
+        match value.parse::<u64>() {
+            Ok(id) => Ok(Self::Market(format!("gid://shopify/Market/{}", id))),
+            Err(_) => Err("market must be a two-letter country code or a market ID".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Money {
+    pub amount: String,
+    #[serde(rename(deserialize = "currencyCode"))]
+    pub currency_code: String,
+}
+
+/// A market `?market=` resolved to.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct PricedMarket {
+    pub id: String,
+    pub name: String,
+    pub handle: String,
+}
+
+/// A variant's base price next to what buyers in the market pay.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VariantPrice {
+    pub variant_id: Option<u64>,
+    pub title: String,
+    pub sku: Option<String>,
+    /// In the shop's currency
+    pub price: String,
+    pub compare_at_price: Option<String>,
+    pub market_price: Money,
+    pub market_compare_at_price: Option<Money>,
+}
+
+#[derive(Deserialize)]
+struct ProductPricesData {
+    product: Option<PricedProduct>,
+}
+
+#[derive(Deserialize)]
+struct PricedProduct {
+    title: String,
+    variants: Nodes<PricedVariant>,
+}
+
+#[derive(Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PricedVariant {
+    id: String,
+    title: String,
+    sku: Option<String>,
+    price: String,
+    compare_at_price: Option<String>,
+    contextual_pricing: ContextualPricing,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContextualPricing {
+    price: Money,
+    compare_at_price: Option<Money>,
+}
+
+#[derive(Deserialize)]
+struct MarketData {
+    market: Option<MarketNode>,
+}
+
+#[derive(Deserialize)]
+struct MarketNode {
+    #[serde(flatten)]
+    market: PricedMarket,
+    regions: Nodes<MarketRegion>,
+}
+
+#[derive(Deserialize)]
+struct MarketRegion {
+    /// Absent for regions that aren't countries
+    code: Option<String>,
+}
+
+/// The numeric ID at the end of a GraphQL ID like `gid://shopify/ProductVariant/1`.
+pub fn legacy_id(gid: &str) -> Option<u64> {
+    gid.rsplit('/').next()?.parse().ok()
+}
+
+// =============================================================================
+// Market Price Handlers
+// =============================================================================
+
+/// `GET /api/products/{id}/prices?market=` — each variant's price in a
+/// market, as set by its price list and currency conversion, through the
+/// GraphQL Admin API.
+#[utoipa::path(
+    get,
+    path = "/api/products/{id}/prices",
+    tag = "products",
+    params(("id" = u64, Path, description = "Product ID"), MarketPriceParams),
+    responses(
+        (status = 200, description = "Base and market prices of every variant", body = crate::openapi::ProductPrices),
+        (status = 400, description = "Missing or unreadable market", body = crate::openapi::ErrorResponse),
+        (status = 404, description = "No such product or market", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn product_prices_handler(
+    Path(product_id): Path<u64>,
+    Query(params): Query<MarketPriceParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let selector = params
+        .market
+        .as_deref()
+        .ok_or_else(|| "market is required, e.g. ?market=CA".to_string())
+        .and_then(MarketSelector::parse)
+        .map_err(AppError::BadRequest)?;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let (country, market) = match selector {
+        MarketSelector::Country(country) => (country, None),
+        MarketSelector::Market(id) => {
+            let data: MarketData = state.shopify
+                .graphql_with_auth(&token, MARKET_QUERY, serde_json::json!({ "id": id }))
+                .await
+                .map_err(|e| log_failure(e, "market", &id))?;
+            let market = data.market.ok_or_else(|| AppError::NotFound(format!("Market {} not found", id)))?;
+            let country = market
+                .regions
+                .nodes
+                .into_iter()
+                .find_map(|region| region.code)
+                .ok_or_else(|| AppError::BadRequest(format!("Market {} has no countries to price for", id)))?;
+            (country, Some(market.market))
+        }
+    };
+
+    let data: ProductPricesData = state.shopify
+        .graphql_with_auth(
+            &token,
+            PRODUCT_PRICES_QUERY,
+            serde_json::json!({ "id": format!("gid://shopify/Product/{}", product_id), "country": country }),
+        )
+        .await
+        .map_err(|e| log_failure(e, "product", &product_id.to_string()))?;
+    let product = data.product.ok_or_else(|| AppError::NotFound(format!("Product {} not found", product_id)))?;
+
+    let variants: Vec<VariantPrice> = product
+        .variants
+        .nodes
+        .into_iter()
+        .map(|variant| VariantPrice {
+            variant_id: legacy_id(&variant.id),
+            title: variant.title,
+            sku: variant.sku.filter(|sku| !sku.is_empty()),
+            price: variant.price,
+            compare_at_price: variant.compare_at_price,
+            market_price: variant.contextual_pricing.price,
+            market_compare_at_price: variant.contextual_pricing.compare_at_price,
+        })
+        .collect();
+    info!("Successfully fetched {} variant prices for product {} in {}", variants.len(), product_id, country);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "product_id": product_id,
+        "title": product.title,
+        "country": country,
+        "market": market,
+        "variants_count": variants.len(),
+        "variants": variants
+    }))))
+}
+
+fn log_failure(error: ShopifyError, kind: &str, id: &str) -> AppError {
+    error!("Failed to fetch prices ({} {}): {}", kind, id, error);
+    error.into()
+}
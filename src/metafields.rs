@@ -0,0 +1,366 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use tracing::{info, error};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    require_token,
+    error::{AppError, AppResult, ShopifyError},
+    http_client::ShopifyClient,
+    scopes::{AccessScope, ScopeGuard},
+};
+
+/// Resources whose metafields can be reached through `/api/{resource}/{id}/metafields`
+pub const METAFIELD_RESOURCES: &[&str] = &[
+    "products",
+    "variants",
+    "customers",
+    "orders",
+    "draft_orders",
+    "collections",
+    "locations",
+    "pages",
+    "blogs",
+];
+
+// =============================================================================
+// Metafield Structures
+// =============================================================================
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct Metafield {
+    pub id: Option<u64>,
+    pub namespace: String,
+    pub key: String,
+    /// Always a string here; Shopify sends numbers and booleans unquoted for
+    /// some types
+    #[serde(deserialize_with = "scalar_as_string")]
+    pub value: String,
+    #[serde(rename = "type")]
+    pub metafield_type: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub owner_id: Option<u64>,
+    #[serde(default)]
+    pub owner_resource: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+fn scalar_as_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => s,
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct MetafieldsResponse {
+    pub metafields: Vec<Metafield>,
+}
+
+#[derive(Deserialize)]
+pub struct MetafieldResponse {
+    pub metafield: Metafield,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MetafieldParams {
+    pub namespace: Option<String>,
+    pub key: Option<String>,
+}
+
+/// Body for creating or updating a metafield. Updates find the metafield by
+/// `id`, or by `namespace` and `key` when no ID is given.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct MetafieldInput {
+    #[serde(default, skip_serializing)]
+    pub id: Option<u64>,
+    pub namespace: String,
+    pub key: String,
+    pub value: serde_json::Value,
+    #[serde(rename = "type")]
+    pub metafield_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl MetafieldInput {
+    /// Shopify takes every value as a string; JSON values are sent serialized.
+    pub fn to_shopify_body(&self) -> AppResult<serde_json::Value> {
+        if self.namespace.trim().is_empty() || self.key.trim().is_empty() {
+            return Err(AppError::BadRequest("Metafield namespace and key are required".to_string()));
+        }
+
+        let value = match self.value {
+            serde_json::Value::String(ref s) => s.clone(),
+            ref other => other.to_string(),
+        };
+
+        let mut body = serde_json::to_value(self).map_err(ShopifyError::from)?;
+        body["value"] = value.into();
+        Ok(serde_json::json!({ "metafield": body }))
+    }
+}
+
+pub fn check_resource(resource: &str) -> AppResult<()> {
+    if METAFIELD_RESOURCES.contains(&resource) {
+        Ok(())
+    } else {
+        Err(AppError::NotFound(format!("Metafields are not supported for {}", resource)))
+    }
+}
+
+/// Scopes for reading or writing metafields on `resource`, which are those
+/// of the owner itself. Pages and blogs need `content` scopes, which this app
+/// doesn't request, so they're left to Shopify.
+pub fn metafield_scopes(resource: &str, write: bool) -> &'static [AccessScope] {
+    match (resource, write) {
+        ("products" | "variants" | "collections", false) => &[AccessScope::ReadProducts],
+        ("products" | "variants" | "collections", true) => &[AccessScope::WriteProducts],
+        ("customers", false) => &[AccessScope::ReadCustomers],
+        ("customers", true) => &[AccessScope::WriteCustomers],
+        ("orders" | "draft_orders", false) => &[AccessScope::ReadOrders],
+        ("orders" | "draft_orders", true) => &[AccessScope::WriteOrders],
+        ("locations", _) => &[AccessScope::ReadLocations],
+        _ => &[],
+    }
+}
+
+// The resource must be supported and its scopes granted
+async fn check_access(state: &AppState, resource: &str, write: bool) -> AppResult<()> {
+    check_resource(resource)?;
+    ScopeGuard::new(state, &[]).check_scopes(metafield_scopes(resource, write)).await
+}
+
+// =============================================================================
+// Metafield Handlers
+// =============================================================================
+
+/// Lists a resource's metafields, optionally narrowed to a namespace and key.
+#[utoipa::path(
+    get,
+    path = "/api/{resource}/{id}/metafields",
+    tag = "metafields",
+    params(
+        ("resource" = String, Path, description = "products, variants, customers, orders, draft_orders, collections, locations, pages or blogs"),
+        ("id" = u64, Path, description = "ID of the resource that owns the metafields"),
+        MetafieldParams,
+    ),
+    responses(
+        (status = 200, description = "The resource's metafields", body = crate::openapi::MetafieldList),
+        (status = 404, description = "Unsupported resource", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn list_metafields_handler(
+    Path((resource, id)): Path<(String, u64)>,
+    Query(params): Query<MetafieldParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    check_access(&state, &resource, false).await?;
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let metafields = fetch_metafields(&state.shopify, &token, &resource, id, &params).await?;
+    info!("Successfully fetched {} metafields for {} {}", metafields.len(), resource, id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "resource": resource,
+        "id": id,
+        "metafields_count": metafields.len(),
+        "metafields": metafields
+    }))))
+}
+
+/// Adds a metafield to a resource.
+#[utoipa::path(
+    post,
+    path = "/api/{resource}/{id}/metafields",
+    tag = "metafields",
+    params(
+        ("resource" = String, Path, description = "products, variants, customers, orders, draft_orders, collections, locations, pages or blogs"),
+        ("id" = u64, Path, description = "ID of the resource that owns the metafields"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries with the same key and body return the first response"),
+    ),
+    request_body = MetafieldInput,
+    responses(
+        (status = 201, description = "The new metafield", body = crate::openapi::MetafieldChange),
+        (status = 404, description = "Unsupported resource, or no such owner", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn create_metafield_handler(
+    Path((resource, id)): Path<(String, u64)>,
+    State(state): State<AppState>,
+    Json(input): Json<MetafieldInput>,
+) -> AppResult<impl IntoResponse> {
+    check_access(&state, &resource, true).await?;
+    let body = input.to_shopify_body()?;
+    let token = require_token(&state.token_store, &state.config.shop).await?;
+
+    let response: MetafieldResponse = state.shopify
+        .post_with_auth(&format!("{}/{}/metafields.json", resource, id), &token, &body)
+        .await
+        .map_err(|e| owner_not_found(e, &resource, id))?;
+    info!("🏷️ Created metafield {}.{} on {} {}", input.namespace, input.key, resource, id);
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({
+        "success": true,
+        "metafield": response.metafield
+    }))))
+}
+
+/// Updates a metafield found by `id`, or by `namespace` and `key`.
+#[utoipa::path(
+    put,
+    path = "/api/{resource}/{id}/metafields",
+    tag = "metafields",
+    params(
+        ("resource" = String, Path, description = "products, variants, customers, orders, draft_orders, collections, locations, pages or blogs"),
+        ("id" = u64, Path, description = "ID of the resource that owns the metafields"),
+    ),
+    request_body = MetafieldInput,
+    responses(
+        (status = 200, description = "The updated metafield", body = crate::openapi::MetafieldChange),
+        (status = 404, description = "Unsupported resource, or no such metafield", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn update_metafield_handler(
+    Path((resource, id)): Path<(String, u64)>,
+    State(state): State<AppState>,
+    Json(input): Json<MetafieldInput>,
+) -> AppResult<impl IntoResponse> {
+    check_access(&state, &resource, true).await?;
+    let body = input.to_shopify_body()?;
+    let token = require_token(&state.token_store, &state.config.shop).await?;
+
+    let metafield_id = match input.id {
+        Some(metafield_id) => metafield_id,
+        None => find_metafield(&state.shopify, &token, &resource, id, &input.namespace, &input.key).await?,
+    };
+
+    let response: MetafieldResponse = state.shopify
+        .put_with_auth(&format!("{}/{}/metafields/{}.json", resource, id, metafield_id), &token, &body)
+        .await
+        .map_err(|e| match e {
+            ShopifyError::NotFound => AppError::NotFound(format!("Metafield {} not found", metafield_id)),
+            e => e.into(),
+        })?;
+    info!("🏷️ Updated metafield {}.{} on {} {}", input.namespace, input.key, resource, id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "metafield": response.metafield
+    }))))
+}
+
+/// Deletes the metafield named by the `namespace` and `key` query parameters.
+#[utoipa::path(
+    delete,
+    path = "/api/{resource}/{id}/metafields",
+    tag = "metafields",
+    params(
+        ("resource" = String, Path, description = "products, variants, customers, orders, draft_orders, collections, locations, pages or blogs"),
+        ("id" = u64, Path, description = "ID of the resource that owns the metafields"),
+        MetafieldParams,
+    ),
+    responses(
+        (status = 200, description = "The metafield was deleted", body = crate::openapi::MetafieldDeleted),
+        (status = 400, description = "`namespace` or `key` missing", body = crate::openapi::ErrorResponse),
+        (status = 404, description = "Unsupported resource, or no such metafield", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn delete_metafield_handler(
+    Path((resource, id)): Path<(String, u64)>,
+    Query(params): Query<MetafieldParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    check_access(&state, &resource, true).await?;
+    let (Some(namespace), Some(key)) = (params.namespace.as_deref(), params.key.as_deref()) else {
+        return Err(AppError::BadRequest("namespace and key query parameters are required".to_string()));
+    };
+    let token = require_token(&state.token_store, &state.config.shop).await?;
+
+    let metafield_id = find_metafield(&state.shopify, &token, &resource, id, namespace, key).await?;
+    state.shopify
+        .delete_with_auth(&format!("{}/{}/metafields/{}.json", resource, id, metafield_id), &token)
+        .await?;
+    info!("🏷️ Deleted metafield {}.{} from {} {}", namespace, key, resource, id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "deleted_id": metafield_id
+    }))))
+}
+
+// =============================================================================
+// Metafield Fetch Functions
+// =============================================================================
+
+async fn fetch_metafields(
+    client: &ShopifyClient,
+    token: &str,
+    resource: &str,
+    id: u64,
+    params: &MetafieldParams,
+) -> AppResult<Vec<Metafield>> {
+    let mut query_params = vec![("limit", "250")];
+    if let Some(ref namespace) = params.namespace {
+        query_params.push(("namespace", namespace));
+    }
+    if let Some(ref key) = params.key {
+        query_params.push(("key", key));
+    }
+
+    let response = client
+        .get_with_auth::<MetafieldsResponse>(&format!("{}/{}/metafields.json", resource, id), token, Some(&query_params))
+        .await
+        .map_err(|e| owner_not_found(e, resource, id))?;
+
+    // Filter locally as well, in case Shopify ignores either parameter
+    Ok(response.data.metafields
+        .into_iter()
+        .filter(|m| params.namespace.as_deref().is_none_or(|ns| m.namespace == ns))
+        .filter(|m| params.key.as_deref().is_none_or(|key| m.key == key))
+        .collect())
+}
+
+async fn find_metafield(
+    client: &ShopifyClient,
+    token: &str,
+    resource: &str,
+    id: u64,
+    namespace: &str,
+    key: &str,
+) -> AppResult<u64> {
+    let params = MetafieldParams {
+        namespace: Some(namespace.to_string()),
+        key: Some(key.to_string()),
+    };
+
+    fetch_metafields(client, token, resource, id, &params)
+        .await?
+        .into_iter()
+        .find_map(|m| m.id)
+        .ok_or_else(|| AppError::NotFound(format!("Metafield {}.{} not found on {} {}", namespace, key, resource, id)))
+}
+
+fn owner_not_found(error: ShopifyError, resource: &str, id: u64) -> AppError {
+    match error {
+        ShopifyError::NotFound => AppError::NotFound(format!("{} {} not found", resource, id)),
+        e => {
+            error!("Metafield request for {} {} failed: {}", resource, id, e);
+            e.into()
+        }
+    }
+}
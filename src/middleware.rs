@@ -1,15 +1,22 @@
 use axum::{
-    extract::Request,
-    http::HeaderValue,
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
+    Json,
 };
-use std::time::Instant;
-use tracing::{info, warn};
-use redis::{AsyncCommands};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tracing::{info, warn, debug, Instrument};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::sync::RwLock;
 
+use crate::api_auth::ApiPrincipal;
+
 // =============================================================================
 // Rate Limiting Configuration
 // =============================================================================
@@ -22,6 +29,12 @@ pub struct RateLimitConfig {
     pub burst_size: u32,
     pub redis_url: Option<String>,
     pub use_redis: bool,
+    /// How long an over-limit API request may wait for capacity before it is
+    /// rejected; zero rejects immediately
+    pub queue_budget: Duration,
+    /// Proxies in front of the app that append to `X-Forwarded-For`; zero
+    /// ignores forwarding headers and uses the connection's peer address
+    pub trusted_proxy_hops: usize,
 }
 
 impl Default for RateLimitConfig {
@@ -33,34 +46,42 @@ impl Default for RateLimitConfig {
             burst_size: 5,
             redis_url: None,
             use_redis: false,
+            queue_budget: Duration::ZERO,
+            trusted_proxy_hops: 1,
         }
     }
 }
 
+// The first of `names` that is set and parses, so documented and older
+// variable names both work
+fn env_u32(names: &[&str], default: u32) -> u32 {
+    names
+        .iter()
+        .find_map(|name| std::env::var(name).ok().and_then(|v| v.parse().ok()))
+        .unwrap_or(default)
+}
+
 impl RateLimitConfig {
     pub fn from_env() -> Self {
         Self {
-            oauth_requests_per_minute: std::env::var("OAUTH_RATE_LIMIT")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(10),
-            api_requests_per_minute: std::env::var("API_RATE_LIMIT")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(60),
-            general_requests_per_minute: std::env::var("GENERAL_RATE_LIMIT")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(30),
-            burst_size: std::env::var("RATE_LIMIT_BURST")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(5),
+            oauth_requests_per_minute: env_u32(&["RATE_LIMIT_OAUTH_PER_MINUTE", "OAUTH_RATE_LIMIT"], 10),
+            api_requests_per_minute: env_u32(&["RATE_LIMIT_API_PER_MINUTE", "API_RATE_LIMIT"], 60),
+            general_requests_per_minute: env_u32(&["RATE_LIMIT_GENERAL_PER_MINUTE", "GENERAL_RATE_LIMIT"], 30),
+            burst_size: env_u32(&["RATE_LIMIT_BURST_SIZE", "RATE_LIMIT_BURST"], 5),
             redis_url: std::env::var("REDIS_URL").ok(),
             use_redis: std::env::var("USE_REDIS_RATE_LIMIT")
                 .unwrap_or_default()
                 .parse()
                 .unwrap_or(false),
+            queue_budget: std::env::var("RATE_LIMIT_QUEUE_BUDGET_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(Duration::ZERO),
+            trusted_proxy_hops: std::env::var("TRUSTED_PROXY_HOPS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1),
         }
     }
 }
@@ -69,17 +90,132 @@ impl RateLimitConfig {
 // Rate Limiting Implementation
 // =============================================================================
 
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Milliseconds between requests at `limit` per window. Times are whole
+/// milliseconds, so limits above 60000/min are held to one request per ms.
+fn emission_interval(limit: u32) -> u64 {
+    (RATE_LIMIT_WINDOW.as_millis() as u64 / u64::from(limit.max(1))).max(1)
+}
+
+/// How long a rate limit check waits on Redis before using the memory store.
+const REDIS_CHECK_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// After Redis fails, checks stay in memory this long before trying it again.
+const REDIS_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// How often the memory store drops callers back to a full burst. A TAT is
+/// at most a window ahead, so nothing lingers much longer than that.
+const MEMORY_SWEEP_INTERVAL: Duration = RATE_LIMIT_WINDOW;
+
+/// Which limit a route is held to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitGroup {
+    OAuth,
+    Api,
+    General,
+}
+
+impl RateLimitGroup {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OAuth => "oauth",
+            Self::Api => "api",
+            Self::General => "general",
+        }
+    }
+}
+
+/// Outcome of counting one request against a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Until the window frees up capacity again
+    pub reset_after: Duration,
+}
+
+/// Generic cell rate algorithm: each request pushes the caller's theoretical
+/// arrival time (TAT) one emission interval (`60s / limit`) further out, and a
+/// request is allowed while the TAT is no more than `burst - 1` intervals
+/// ahead of now. Bursts up to `burst` go through at once, after which
+/// requests are spaced evenly, with no window edge to double up on.
+///
+/// Takes and returns times in milliseconds; `tat` is `None` for a new caller.
+/// The Redis script below is the same computation.
+pub fn gcra(now: u64, tat: Option<u64>, limit: u32, burst: u32) -> (RateLimitDecision, Option<u64>) {
+    let limit = limit.max(1);
+    let burst = burst.clamp(1, limit);
+    let interval = emission_interval(limit);
+    let tolerance = interval * u64::from(burst - 1);
+    let tat = tat.unwrap_or(now).max(now);
+
+    let allow_at = tat.saturating_sub(tolerance);
+    if now < allow_at {
+        let decision = RateLimitDecision {
+            allowed: false,
+            limit,
+            remaining: 0,
+            reset_after: Duration::from_millis(allow_at - now),
+        };
+        return (decision, None);
+    }
+
+    let new_tat = tat + interval;
+    let used = (new_tat - now).div_ceil(interval);
+    let decision = RateLimitDecision {
+        allowed: true,
+        limit,
+        remaining: (u64::from(burst).saturating_sub(used)) as u32,
+        reset_after: Duration::from_millis(new_tat - now),
+    };
+    (decision, Some(new_tat))
+}
+
+// KEYS[1] = caller; ARGV = interval ms, tolerance ms. Uses the server clock so
+// every instance agrees on "now". Returns {allowed, wait or reset ms, used}
+const GCRA_SCRIPT: &str = r#"
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local interval = tonumber(ARGV[1])
+local tolerance = tonumber(ARGV[2])
+local tat = tonumber(redis.call('GET', KEYS[1]) or now)
+if tat < now then tat = now end
+local allow_at = tat - tolerance
+if now < allow_at then
+    return {0, allow_at - now, 0}
+end
+local new_tat = tat + interval
+redis.call('SET', KEYS[1], new_tat, 'PX', new_tat - now)
+return {1, new_tat - now, math.ceil((new_tat - now) / interval)}
+"#;
+
+/// Per-caller limits with bursts of `burst_size`, enforced with [`gcra`] in
+/// Redis when `USE_REDIS_RATE_LIMIT` is on so every instance shares the
+/// state, in memory otherwise.
+///
+/// Redis is reached through one `ConnectionManager`, shared by every clone,
+/// that reconnects on its own after a dropped connection. While Redis is
+/// down or slow, checks fall back to the memory store and Redis is retried
+/// every [`REDIS_RETRY_AFTER`].
 #[derive(Clone)]
-#[allow(dead_code)]
 pub struct RateLimiter {
     config: RateLimitConfig,
     redis_client: Option<redis::Client>,
-    // In-memory fallback for when Redis is not available
-    memory_store: Arc<RwLock<std::collections::HashMap<String, (u32, std::time::Instant)>>>,
+    redis_conn: Arc<tokio::sync::OnceCell<redis::aio::ConnectionManager>>,
+    gcra_script: Arc<redis::Script>,
+    // Milliseconds since `epoch` until which Redis is skipped; 0 while healthy
+    redis_down_until: Arc<AtomicU64>,
+    // In-memory fallback for when Redis is not available: each caller's TAT
+    // in milliseconds since `epoch`
+    memory_store: Arc<RwLock<HashMap<String, u64>>>,
+    // Milliseconds since `epoch` of the last sweep of `memory_store`
+    last_sweep: Arc<AtomicU64>,
+    epoch: Instant,
 }
 
 impl RateLimiter {
-    #[allow(dead_code)]
     pub fn new(config: RateLimitConfig) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let redis_client = if config.use_redis {
             if let Some(ref redis_url) = config.redis_url {
@@ -95,126 +231,345 @@ impl RateLimiter {
         Ok(Self {
             config,
             redis_client,
-            memory_store: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            redis_conn: Arc::new(tokio::sync::OnceCell::new()),
+            gcra_script: Arc::new(redis::Script::new(GCRA_SCRIPT)),
+            redis_down_until: Arc::new(AtomicU64::new(0)),
+            memory_store: Arc::new(RwLock::new(HashMap::new())),
+            last_sweep: Arc::new(AtomicU64::new(0)),
+            epoch: Instant::now(),
         })
     }
 
-    #[allow(dead_code)]
+    /// Requests per minute allowed for a route group.
+    pub fn limit_for(&self, group: RateLimitGroup) -> u32 {
+        match group {
+            RateLimitGroup::OAuth => self.config.oauth_requests_per_minute,
+            RateLimitGroup::Api => self.config.api_requests_per_minute,
+            RateLimitGroup::General => self.config.general_requests_per_minute,
+        }
+    }
+
     pub async fn check_rate_limit(
         &self,
         identifier: &str,
         limit: u32,
     ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
-        if let Some(ref client) = self.redis_client {
-            self.check_redis_rate_limit(client, identifier, limit).await
-        } else {
-            self.check_memory_rate_limit(identifier, limit).await
-        }
+        Ok(self.check(identifier, limit).await?.allowed)
     }
 
-    #[allow(dead_code)]
-    async fn check_redis_rate_limit(
+    /// Counts a request for `identifier` and reports what's left of its limit.
+    pub async fn check(
         &self,
-        client: &redis::Client,
         identifier: &str,
         limit: u32,
-    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
-        let mut conn = client.get_async_connection().await?;
-        let key = format!("rate_limit:{}", identifier);
-        
-        // Use Redis sliding window approach
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)?
-            .as_secs();
-        
-        let window_start = now - 60; // 1 minute window
-        
-        // Remove old entries
-        let _: () = conn.zrembyscore(&key, "-inf", window_start as f64).await?;
-        
-        // Count current requests
-        let current_count: u32 = conn.zcard(&key).await?;
-        
-        if current_count >= limit {
-            return Ok(false);
+    ) -> Result<RateLimitDecision, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(ref client) = self.redis_client {
+            if self.now_ms() >= self.redis_down_until.load(Ordering::Relaxed) {
+                let check = self.check_redis_rate_limit(client, identifier, limit);
+                match tokio::time::timeout(REDIS_CHECK_TIMEOUT, check).await {
+                    Ok(Ok(decision)) => {
+                        if self.redis_down_until.swap(0, Ordering::Relaxed) != 0 {
+                            info!("✅ Redis rate limiting recovered");
+                        }
+                        return Ok(decision);
+                    }
+                    Ok(Err(e)) => self.mark_redis_down(&e.to_string()),
+                    Err(_) => self.mark_redis_down("timed out"),
+                }
+            }
+        }
+
+        Ok(self.check_memory_rate_limit(identifier, limit).await)
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
+
+    fn mark_redis_down(&self, reason: &str) {
+        let retry_at = self.now_ms() + REDIS_RETRY_AFTER.as_millis() as u64;
+        // Only the first failure of an outage is worth a warning
+        if self.redis_down_until.swap(retry_at, Ordering::Relaxed) == 0 {
+            warn!("⚠️ Redis rate limiting unavailable ({}), using in-memory limits", reason);
         }
-        
-        // Add current request
-        let _: () = conn.zadd(&key, now, now).await?;
-        let _: () = conn.expire(&key, 61).await?; // Expire after 61 seconds
-        
-        Ok(true)
     }
 
-    #[allow(dead_code)]
-    async fn check_memory_rate_limit(
+    async fn check_redis_rate_limit(
         &self,
+        client: &redis::Client,
         identifier: &str,
         limit: u32,
-    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<RateLimitDecision, Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.redis_conn
+            .get_or_try_init(|| redis::aio::ConnectionManager::new(client.clone()))
+            .await?
+            .clone();
+
+        let limit = limit.max(1);
+        let burst = self.config.burst_size.clamp(1, limit);
+        let interval = emission_interval(limit);
+        let (allowed, wait_ms, used): (u8, u64, u64) = self.gcra_script
+            .key(format!("rate_limit:{}", identifier))
+            .arg(interval)
+            .arg(interval * u64::from(burst - 1))
+            .invoke_async(&mut conn)
+            .await?;
+
+        Ok(RateLimitDecision {
+            allowed: allowed == 1,
+            limit,
+            remaining: u64::from(burst).saturating_sub(used) as u32,
+            reset_after: Duration::from_millis(wait_ms),
+        })
+    }
+
+    async fn check_memory_rate_limit(&self, identifier: &str, limit: u32) -> RateLimitDecision {
         let mut store = self.memory_store.write().await;
-        let now = std::time::Instant::now();
+        let now = self.now_ms();
         
-        // Clean up old entries (older than 1 minute)
-        store.retain(|_, (_, timestamp)| now.duration_since(*timestamp).as_secs() < 60);
+        // Callers whose TAT has passed are back to a full burst; forget them,
+        // on a timer so a store full of active callers isn't scanned per request
+        if now.saturating_sub(self.last_sweep.load(Ordering::Relaxed)) >= MEMORY_SWEEP_INTERVAL.as_millis() as u64 {
+            self.last_sweep.store(now, Ordering::Relaxed);
+            store.retain(|_, tat| *tat > now);
+        }
         
-        match store.get_mut(identifier) {
-            Some((count, timestamp)) => {
-                if now.duration_since(*timestamp).as_secs() >= 60 {
-                    // Reset counter for new window
-                    *count = 1;
-                    *timestamp = now;
-                    Ok(true)
-                } else if *count >= limit {
-                    Ok(false)
-                } else {
-                    *count += 1;
-                    Ok(true)
-                }
-            }
-            None => {
-                store.insert(identifier.to_string(), (1, now));
-                Ok(true)
-            }
+        let (decision, new_tat) = gcra(now, store.get(identifier).copied(), limit, self.config.burst_size);
+        if let Some(tat) = new_tat {
+            store.insert(identifier.to_string(), tat);
         }
+        decision
     }
 }
 
-pub async fn rate_limit_handler(
+/// A route group's limit, attached with `route_layer` and
+/// `rate_limit_middleware`.
+#[derive(Clone)]
+pub struct RouteRateLimit {
+    limiter: RateLimiter,
+    group: RateLimitGroup,
+}
+
+impl RouteRateLimit {
+    pub fn new(limiter: &RateLimiter, group: RateLimitGroup) -> Self {
+        Self { limiter: limiter.clone(), group }
+    }
+}
+
+/// Holds each caller to the group's per-minute limit: per API key or JWT
+/// subject once authenticated, per client IP otherwise. Every response gets
+/// `X-RateLimit-*` headers; over-limit requests get a 429 with `Retry-After`.
+/// If Redis can't be reached the request is let through.
+pub async fn rate_limit_middleware(
+    State(route): State<RouteRateLimit>,
     request: Request,
     next: Next,
 ) -> Response {
-    // For now, just pass through - in production you'd implement actual rate limiting
-    next.run(request).await
+    let client = client_key(&request);
+    let limit = route.limiter.limit_for(route.group);
+    let identifier = format!("{}:{}", route.group.as_str(), client);
+
+    let decision = match route.limiter.check(&identifier, limit).await {
+        Ok(decision) => decision,
+        Err(e) => {
+            warn!("Rate limit check failed for {}, allowing request: {}", identifier, e);
+            return next.run(request).await;
+        }
+    };
+
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        warn!("Rate limit exceeded for {} on {}", client, request.uri());
+        too_many_requests(decision.reset_after)
+    };
+    set_rate_limit_headers(response.headers_mut(), decision.limit, decision.remaining, decision.reset_after);
+    response
 }
 
-// Advanced rate limiting middleware
-#[allow(dead_code)]
-pub async fn advanced_rate_limit_middleware(
-    request: Request,
+fn too_many_requests(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, seconds.to_string())],
+        Json(serde_json::json!({
+            "error": "Rate limit exceeded",
+            "retry_after": seconds
+        })),
+    )
+        .into_response()
+}
+
+fn set_rate_limit_headers(headers: &mut HeaderMap, limit: u32, remaining: u32, reset_after: Duration) {
+    let reset = reset_after.as_secs_f64().ceil() as u64;
+    headers.insert("x-ratelimit-limit", HeaderValue::from(limit));
+    headers.insert("x-ratelimit-remaining", HeaderValue::from(remaining));
+    headers.insert("x-ratelimit-reset", HeaderValue::from(reset));
+}
+
+// Helper functions to create rate limiters for different endpoint types
+pub fn create_oauth_rate_limiter(limiter: &RateLimiter) -> RouteRateLimit {
+    info!("Creating OAuth rate limiter with {} requests/minute", limiter.limit_for(RateLimitGroup::OAuth));
+    RouteRateLimit::new(limiter, RateLimitGroup::OAuth)
+}
+
+pub fn create_api_rate_limiter(limiter: &RateLimiter) -> SoftRateLimiter {
+    info!(
+        "Creating API rate limiter with {} requests/minute (queue budget {:?})",
+        limiter.limit_for(RateLimitGroup::Api), limiter.config.queue_budget
+    );
+    SoftRateLimiter::new(limiter, RateLimitGroup::Api, limiter.config.queue_budget)
+}
+
+pub fn create_general_rate_limiter(limiter: &RateLimiter) -> RouteRateLimit {
+    info!("Creating general rate limiter with {} requests/minute", limiter.limit_for(RateLimitGroup::General));
+    RouteRateLimit::new(limiter, RateLimitGroup::General)
+}
+
+// =============================================================================
+// Soft Rate Limiting
+// =============================================================================
+
+/// A route group's limit that queues slightly-over-limit requests instead of
+/// rejecting them. Counting goes through the shared [`RateLimiter`], so the
+/// limit holds across instances when Redis is on. A request whose slot frees
+/// up within the queue budget waits for it and is counted again; one that
+/// would wait longer gets a 429.
+#[derive(Clone)]
+pub struct SoftRateLimiter {
+    limiter: RateLimiter,
+    group: RateLimitGroup,
+    queue_budget: Duration,
+}
+
+impl SoftRateLimiter {
+    pub fn new(limiter: &RateLimiter, group: RateLimitGroup, queue_budget: Duration) -> Self {
+        Self { limiter: limiter.clone(), group, queue_budget }
+    }
+
+    pub fn requests_per_minute(&self) -> u32 {
+        self.limiter.limit_for(self.group)
+    }
+
+    /// Counts a request for `client`, waiting up to the queue budget for
+    /// capacity. The decision is `allowed` once the request may proceed;
+    /// otherwise `reset_after` is how long until it would have fit.
+    pub async fn acquire(&self, client: &str) -> Result<RateLimitDecision, Box<dyn std::error::Error + Send + Sync>> {
+        let identifier = format!("{}:{}", self.group.as_str(), client);
+        let limit = self.requests_per_minute();
+        let deadline = Instant::now() + self.queue_budget;
+        loop {
+            let decision = self.limiter.check(&identifier, limit).await?;
+            if decision.allowed || Instant::now() + decision.reset_after > deadline {
+                return Ok(decision);
+            }
+            debug!("Queueing a request from {} for {:?}", client, decision.reset_after);
+            tokio::time::sleep(decision.reset_after).await;
+        }
+    }
+}
+
+// Authenticated callers are limited per credential, so clients sharing an IP
+// don't starve each other; everyone else per IP
+fn client_key(request: &Request) -> String {
+    if let Some(principal) = request.extensions().get::<ApiPrincipal>() {
+        return format!("key:{}", principal.name);
+    }
+    format!("ip:{}", client_ip(request.headers(), request.extensions().get()).unwrap_or_else(|| "unknown".to_string()))
+}
+
+// =============================================================================
+// Client Address
+// =============================================================================
+
+/// The caller's IP, resolved once per request by `client_ip_middleware`.
+/// Extracting it never fails; it's `None` when the address is unknown.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientIp(pub Option<String>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ClientIp(client_ip(&parts.headers, parts.extensions.get())))
+    }
+}
+
+/// The address `trusted_hops` proxies saw the request come from. Each proxy
+/// appends the address it was connected from to `X-Forwarded-For`, so only
+/// the rightmost `trusted_hops` entries are theirs; anything further left
+/// was sent by the client and could be anything. Without enough forwarded
+/// hops (or with none trusted) this is the connection's peer address.
+pub fn resolve_client_ip(headers: &HeaderMap, peer: Option<IpAddr>, trusted_hops: usize) -> Option<IpAddr> {
+    if trusted_hops > 0 {
+        let hops: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .collect();
+        let forwarded = match hops.len().checked_sub(trusted_hops) {
+            Some(client) => hops[client].parse().ok(),
+            None if hops.is_empty() => headers.get("x-real-ip").and_then(|v| v.to_str().ok()).and_then(|v| v.trim().parse().ok()),
+            None => None,
+        };
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+    peer
+}
+
+/// Resolves the caller's address for everything after it, using
+/// `RateLimitConfig::trusted_proxy_hops` and the peer address from
+/// `ConnectInfo` when the server provides it.
+pub async fn client_ip_middleware(
+    State(trusted_hops): State<usize>,
+    mut request: Request,
     next: Next,
 ) -> Response {
-    // In a real implementation, you'd get the rate limiter from app state
-    // and check limits based on the endpoint
-    info!("Rate limiting check for request: {}", request.uri());
-    
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip());
+    let ip = resolve_client_ip(request.headers(), peer, trusted_hops);
+    request.extensions_mut().insert(ClientIp(ip.map(|ip| ip.to_string())));
     next.run(request).await
 }
 
-// Helper functions to create rate limiters for different endpoint types
-pub fn create_oauth_rate_limiter(config: &RateLimitConfig) -> tower::layer::util::Identity {
-    info!("Creating OAuth rate limiter with {} requests/minute", config.oauth_requests_per_minute);
-    tower::layer::util::Identity::new()
+/// The caller's IP as `client_ip_middleware` resolved it. Outside that
+/// middleware (tests, bare routers) the rightmost forwarded hop is used.
+pub fn client_ip(headers: &HeaderMap, resolved: Option<&ClientIp>) -> Option<String> {
+    match resolved {
+        Some(ClientIp(ip)) => ip.clone(),
+        None => resolve_client_ip(headers, None, 1).map(|ip| ip.to_string()),
+    }
 }
 
-pub fn create_api_rate_limiter(config: &RateLimitConfig) -> tower::layer::util::Identity {
-    info!("Creating API rate limiter with {} requests/minute", config.api_requests_per_minute);
-    tower::layer::util::Identity::new()
-}
+/// As `rate_limit_middleware`, queueing over-limit requests for up to the
+/// limiter's queue budget before answering with a 429.
+pub async fn soft_rate_limit_middleware(
+    State(limiter): State<SoftRateLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = client_key(&request);
 
-pub fn create_general_rate_limiter(config: &RateLimitConfig) -> tower::layer::util::Identity {
-    info!("Creating general rate limiter with {} requests/minute", config.general_requests_per_minute);
-    tower::layer::util::Identity::new()
+    let decision = match limiter.acquire(&client).await {
+        Ok(decision) => decision,
+        Err(e) => {
+            warn!("Rate limit check failed for {}, allowing request: {}", client, e);
+            return next.run(request).await;
+        }
+    };
+
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        warn!("Rate limit exceeded for {} on {}", client, request.uri());
+        too_many_requests(decision.reset_after)
+    };
+    set_rate_limit_headers(response.headers_mut(), decision.limit, decision.remaining, decision.reset_after);
+    response
 }
 
 // =============================================================================
@@ -234,7 +589,10 @@ pub async fn security_headers_middleware(
     headers.insert("X-Frame-Options", HeaderValue::from_static("DENY"));
     headers.insert("X-XSS-Protection", HeaderValue::from_static("1; mode=block"));
     headers.insert("Referrer-Policy", HeaderValue::from_static("strict-origin-when-cross-origin"));
-    headers.insert("Content-Security-Policy", HeaderValue::from_static("default-src 'self'"));
+    // Pages that load assets from elsewhere (Swagger UI) set their own policy
+    headers
+        .entry("Content-Security-Policy")
+        .or_insert(HeaderValue::from_static("default-src 'self'"));
     
     // HTTPS enforcement in production
     if std::env::var("ENVIRONMENT").unwrap_or_default() == "production" {
@@ -247,58 +605,105 @@ pub async fn security_headers_middleware(
     response
 }
 
+// =============================================================================
+// Request IDs
+// =============================================================================
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest caller-supplied request ID kept; anything longer is replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// The request's ID, added to request extensions by `request_id_middleware`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+// Upstream IDs are kept when they're printable and short enough to log safely
+fn incoming_request_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .filter(|id| id.bytes().all(|b| b.is_ascii_graphic()))
+        .map(str::to_string)
+}
+
+/// Propagates the caller's `X-Request-Id` or generates one, echoes it on the
+/// response, and runs the request inside a span carrying it so every log
+/// line for the request can be correlated.
+pub async fn request_id_middleware(
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let request_id = incoming_request_id(request.headers())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    request.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let span = tracing::info_span!("request", request_id = %request_id);
+    let mut response = next.run(request).instrument(span).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
 // =============================================================================
 // Request Logging Middleware
 // =============================================================================
 
+/// The shop a request concerns, from the `shop` query parameter or the
+/// domain header Shopify sends with webhooks.
+fn request_shop(request: &Request) -> Option<String> {
+    request
+        .uri()
+        .query()
+        .and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == "shop")
+                .map(|(_, shop)| shop.into_owned())
+        })
+        .or_else(|| {
+            request
+                .headers()
+                .get("x-shopify-shop-domain")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        })
+}
+
 pub async fn request_logging_middleware(
     request: Request,
     next: Next,
 ) -> Response {
     let start = Instant::now();
     let method = request.method().clone();
-    let uri = request.uri().clone();
+    let path = request.uri().path().to_string();
+    let shop = request_shop(&request).unwrap_or_default();
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.clone())
+        .unwrap_or_default();
     let user_agent = request
         .headers()
         .get("user-agent")
         .and_then(|h| h.to_str().ok())
-        .unwrap_or("unknown");
+        .unwrap_or("unknown")
+        .to_string();
     
-    info!(
-        "→ {} {} - User-Agent: {}",
-        method,
-        uri,
-        user_agent
-    );
+    debug!(%method, %path, %shop, %request_id, %user_agent, "→ request started");
     
     let response = next.run(request).await;
-    let duration = start.elapsed();
-    let status = response.status();
+    let latency_ms = start.elapsed().as_millis() as u64;
+    let status = response.status().as_u16();
     
-    if status.is_server_error() {
-        warn!(
-            "← {} {} {} - {:?}",
-            method,
-            uri,
-            status,
-            duration
-        );
+    if response.status().is_server_error() {
+        warn!(%method, %path, status, latency_ms, %shop, %request_id, "← request failed");
     } else {
-        info!(
-            "← {} {} {} - {:?}",
-            method,
-            uri,
-            status,
-            duration
-        );
+        info!(%method, %path, status, latency_ms, %shop, %request_id, "← request completed");
     }
     
     response
 }
-
-// =============================================================================
-// Placeholder Rate Limiting Functions (for compilation)
-// =============================================================================
-
-// These functions now provide better rate limiting infrastructure
-// In production, integrate with the RateLimiter struct above
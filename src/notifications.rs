@@ -0,0 +1,464 @@
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::{
+    domain_events::{DomainEvent, DomainEventSubscriber, EVENT_NAMES},
+    error::{AppError, AppResult},
+    mailer::{render_email, EmailSender, NotificationEmail},
+};
+
+// =============================================================================
+// Notification Configuration
+// =============================================================================
+
+/// Where a channel delivers its messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    /// Slack incoming webhook
+    Slack,
+    /// Microsoft Teams incoming webhook
+    Teams,
+    /// Any endpoint taking the event as JSON
+    Http,
+    /// An email address, sent through the configured mail provider
+    Email,
+}
+
+impl ChannelKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "slack" => Some(Self::Slack),
+            "teams" => Some(Self::Teams),
+            "http" => Some(Self::Http),
+            "email" => Some(Self::Email),
+            _ => None,
+        }
+    }
+}
+
+/// A named destination rules send notifications to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelConfig {
+    pub name: String,
+    pub kind: ChannelKind,
+    /// Webhook URL, or the address for email channels
+    pub target: String,
+}
+
+/// Notify `channels` when `event` happens, e.g. an `order_created` with a
+/// `min_total` of 500.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct NotificationRule {
+    /// Domain event name, e.g. `order_created` or `checkout_abandoned`
+    pub event: String,
+    /// Only events whose `total_price` is at least this; events without a
+    /// total never match
+    #[serde(default)]
+    pub min_total: Option<Decimal>,
+    /// Only events in this currency
+    #[serde(default)]
+    pub currency: Option<String>,
+    pub channels: Vec<String>,
+    /// Message text, with `{field}` replaced by the event's fields, `{shop}`
+    /// and `{event}`; a default for the event when unset
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NotificationConfig {
+    pub channels: Vec<ChannelConfig>,
+    pub rules: Vec<NotificationRule>,
+}
+
+impl NotificationConfig {
+    /// `NOTIFICATION_CHANNELS`, e.g. `ops=slack:https://hooks.slack.com/...;owner=email:owner@example.com`,
+    /// and `NOTIFICATION_RULES`, a JSON list of rules.
+    pub fn from_env() -> AppResult<Self> {
+        Self::parse(
+            std::env::var("NOTIFICATION_CHANNELS").ok().as_deref(),
+            std::env::var("NOTIFICATION_RULES").ok().as_deref(),
+        )
+    }
+
+    pub fn parse(channels: Option<&str>, rules: Option<&str>) -> AppResult<Self> {
+        let mut parsed = Vec::new();
+        for entry in channels.unwrap_or_default().split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let channel = entry
+                .split_once('=')
+                .and_then(|(name, target)| {
+                    let (kind, target) = target.split_once(':')?;
+                    Some(ChannelConfig { name: name.trim().to_string(), kind: ChannelKind::parse(kind)?, target: target.trim().to_string() })
+                })
+                .filter(|channel| !channel.name.is_empty())
+                .ok_or_else(|| AppError::Config(format!("NOTIFICATION_CHANNELS: expected name=slack|teams|http:url or name=email:address, got {}", entry)))?;
+            if channel.kind == ChannelKind::Email {
+                if !channel.target.contains('@') {
+                    return Err(AppError::Config(format!("NOTIFICATION_CHANNELS: {} needs an email address", channel.name)));
+                }
+            } else if !channel.target.starts_with("https://") && !channel.target.starts_with("http://") {
+                return Err(AppError::Config(format!("NOTIFICATION_CHANNELS: {} needs an http(s) URL", channel.name)));
+            }
+            if parsed.iter().any(|other: &ChannelConfig| other.name == channel.name) {
+                return Err(AppError::Config(format!("NOTIFICATION_CHANNELS: {} is defined twice", channel.name)));
+            }
+            parsed.push(channel);
+        }
+
+        let rules: Vec<NotificationRule> = match rules.map(str::trim).filter(|rules| !rules.is_empty()) {
+            Some(rules) => serde_json::from_str(rules).map_err(|e| AppError::Config(format!("NOTIFICATION_RULES: {}", e)))?,
+            None => Vec::new(),
+        };
+        for rule in &rules {
+            if !EVENT_NAMES.contains(&rule.event.as_str()) {
+                return Err(AppError::Config(format!("NOTIFICATION_RULES: unknown event {}", rule.event)));
+            }
+            if rule.channels.is_empty() {
+                return Err(AppError::Config(format!("NOTIFICATION_RULES: the {} rule has no channels", rule.event)));
+            }
+            if let Some(channel) = rule.channels.iter().find(|name| !parsed.iter().any(|c| &c.name == *name)) {
+                return Err(AppError::Config(format!("NOTIFICATION_RULES: no channel named {}", channel)));
+            }
+        }
+
+        Ok(Self { channels: parsed, rules })
+    }
+
+    /// Whether any channel sends email, which needs a mail provider.
+    pub fn uses_email(&self) -> bool {
+        self.channels.iter().any(|channel| channel.kind == ChannelKind::Email)
+    }
+}
+
+// =============================================================================
+// Rules and Templates
+// =============================================================================
+
+impl NotificationRule {
+    /// Whether `event` (serialized, as from [`event_fields`]) is one this rule
+    /// notifies about.
+    pub fn matches(&self, event: &DomainEvent, fields: &Value) -> bool {
+        if event.name() != self.event {
+            return false;
+        }
+        if let Some(ref currency) = self.currency {
+            if !fields["currency"].as_str().is_some_and(|c| c.eq_ignore_ascii_case(currency)) {
+                return false;
+            }
+        }
+        match self.min_total {
+            Some(min_total) => fields["total_price"]
+                .as_str()
+                .and_then(|total| total.parse::<Decimal>().ok())
+                .is_some_and(|total| total >= min_total),
+            None => true,
+        }
+    }
+
+    pub fn template(&self) -> &str {
+        self.template.as_deref().unwrap_or_else(|| default_template(&self.event))
+    }
+}
+
+fn default_template(event: &str) -> &'static str {
+    match event {
+        "order_created" => "New order {order_id} for {total_price} {currency} at {shop}",
+        "order_paid" => "Order {order_id} paid: {total_price} {currency} at {shop}",
+        "order_risk_changed" => "Order {order_id} at {shop} is now {risk_level} risk",
+        "checkout_abandoned" => "Checkout {checkout_id} abandoned at {shop} by {email}: {total_price} {currency}",
+        "app_uninstalled" => "{shop} uninstalled the app",
+        _ => "{event} at {shop}",
+    }
+}
+
+/// The event's fields by name, plus `shop` and `event`, for templates and
+/// rule filters.
+pub fn event_fields(shop: &str, event: &DomainEvent) -> Value {
+    let mut fields = serde_json::to_value(event).unwrap_or_default();
+    if let Value::Object(ref mut map) = fields {
+        map.remove("type");
+        map.insert("shop".to_string(), Value::String(shop.to_string()));
+        map.insert("event".to_string(), Value::String(event.name().to_string()));
+    }
+    fields
+}
+
+/// Replaces each `{field}` in `template` with that field of `fields`. Unset
+/// fields render empty; unknown ones are left as written.
+pub fn render_template(template: &str, fields: &Value) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &after[..end];
+        match fields.get(name) {
+            Some(Value::String(value)) => rendered.push_str(value),
+            Some(Value::Null) => {}
+            Some(value) => rendered.push_str(&value.to_string()),
+            None => rendered.push_str(&rest[start..start + end + 2]),
+        }
+        rest = &after[end + 1..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+// =============================================================================
+// Channels
+// =============================================================================
+
+/// A rendered notification, as handed to channels.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub shop: String,
+    /// The domain event, serialized with its `type`
+    pub event: Value,
+    pub text: String,
+}
+
+/// Somewhere notifications are delivered. Implement it to plug in a channel
+/// the built-in Slack, Teams, HTTP and email ones don't cover.
+#[async_trait]
+pub trait NotificationChannel: Send + Sync {
+    /// What rules call the channel.
+    fn name(&self) -> &str;
+
+    async fn send(&self, notification: &Notification) -> Result<(), String>;
+}
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default()
+}
+
+async fn post_json(client: &reqwest::Client, url: &str, body: &Value) -> Result<(), String> {
+    let response = client.post(url).json(body).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("answered {}", response.status()));
+    }
+    Ok(())
+}
+
+/// Posts the text to a Slack incoming webhook.
+pub struct SlackChannel {
+    name: String,
+    url: String,
+    client: reqwest::Client,
+}
+
+impl SlackChannel {
+    pub fn new(name: &str, url: &str) -> Self {
+        Self { name: name.to_string(), url: url.to_string(), client: http_client() }
+    }
+
+    pub fn payload(notification: &Notification) -> Value {
+        serde_json::json!({ "text": notification.text })
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for SlackChannel {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), String> {
+        post_json(&self.client, &self.url, &Self::payload(notification)).await
+    }
+}
+
+/// Posts the text as a message card to a Microsoft Teams incoming webhook.
+pub struct TeamsChannel {
+    name: String,
+    url: String,
+    client: reqwest::Client,
+}
+
+impl TeamsChannel {
+    pub fn new(name: &str, url: &str) -> Self {
+        Self { name: name.to_string(), url: url.to_string(), client: http_client() }
+    }
+
+    pub fn payload(notification: &Notification) -> Value {
+        serde_json::json!({
+            "@type": "MessageCard",
+            "@context": "https://schema.org/extensions",
+            "summary": notification.text,
+            "text": notification.text
+        })
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for TeamsChannel {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), String> {
+        post_json(&self.client, &self.url, &Self::payload(notification)).await
+    }
+}
+
+/// Posts the whole [`Notification`] as JSON, for endpoints that want the
+/// event's fields as well as the text.
+pub struct HttpChannel {
+    name: String,
+    url: String,
+    client: reqwest::Client,
+}
+
+impl HttpChannel {
+    pub fn new(name: &str, url: &str) -> Self {
+        Self { name: name.to_string(), url: url.to_string(), client: http_client() }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for HttpChannel {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), String> {
+        let body = serde_json::to_value(notification).map_err(|e| e.to_string())?;
+        post_json(&self.client, &self.url, &body).await
+    }
+}
+
+/// Emails the text, with the shop and event underneath, from the
+/// `emails/notification.html` template.
+pub struct EmailChannel {
+    name: String,
+    to: String,
+    sender: EmailSender,
+}
+
+impl EmailChannel {
+    pub fn new(name: &str, to: &str, sender: EmailSender) -> Self {
+        Self { name: name.to_string(), to: to.to_string(), sender }
+    }
+
+    /// The text's first line, cut short enough for an inbox.
+    pub fn subject(notification: &Notification) -> String {
+        let line = notification.text.lines().next().unwrap_or_default();
+        match line.char_indices().nth(100) {
+            Some((end, _)) => format!("{}…", &line[..end]),
+            None => line.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for EmailChannel {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), String> {
+        let subject = Self::subject(notification);
+        let html = render_email(&NotificationEmail {
+            brand: self.sender.brand(),
+            subject: &subject,
+            shop: &notification.shop,
+            event: notification.event["type"].as_str().unwrap_or_default(),
+            text: &notification.text,
+        })
+        .map_err(|e| e.to_string())?;
+        self.sender
+            .send(&notification.shop, "notification", &self.to, &subject, html, notification.text.clone())
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+// =============================================================================
+// Notification Subscriber
+// =============================================================================
+
+/// Sends the notifications rules ask for as domain events come in.
+pub struct Notifications {
+    channels: HashMap<String, Arc<dyn NotificationChannel>>,
+    rules: Vec<NotificationRule>,
+}
+
+impl Notifications {
+    pub fn new(channels: Vec<Arc<dyn NotificationChannel>>, rules: Vec<NotificationRule>) -> Self {
+        let channels = channels.into_iter().map(|channel| (channel.name().to_string(), channel)).collect();
+        Self { channels, rules }
+    }
+
+    /// The built-in channels `config` names, with its rules. Email channels
+    /// are left out without an `email` sender.
+    pub fn from_config(config: &NotificationConfig, email: Option<&EmailSender>) -> Self {
+        let channels = config
+            .channels
+            .iter()
+            .filter_map(|channel| -> Option<Arc<dyn NotificationChannel>> {
+                Some(match channel.kind {
+                    ChannelKind::Slack => Arc::new(SlackChannel::new(&channel.name, &channel.target)),
+                    ChannelKind::Teams => Arc::new(TeamsChannel::new(&channel.name, &channel.target)),
+                    ChannelKind::Http => Arc::new(HttpChannel::new(&channel.name, &channel.target)),
+                    ChannelKind::Email => Arc::new(EmailChannel::new(&channel.name, &channel.target, email?.clone())),
+                })
+            })
+            .collect();
+        Self::new(channels, config.rules.clone())
+    }
+
+    /// Notifications `event` calls for, with the channels each goes to.
+    pub fn notifications_for(&self, shop: &str, event: &DomainEvent) -> Vec<(Notification, &[String])> {
+        let fields = event_fields(shop, event);
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(event, &fields))
+            .map(|rule| {
+                let notification = Notification {
+                    shop: shop.to_string(),
+                    event: serde_json::to_value(event).unwrap_or_default(),
+                    text: render_template(rule.template(), &fields),
+                };
+                (notification, rule.channels.as_slice())
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl DomainEventSubscriber for Notifications {
+    fn name(&self) -> &str {
+        "notifications"
+    }
+
+    /// Failed deliveries are logged rather than failing the webhook, whose
+    /// retry would notify the channels that did get it a second time.
+    async fn handle(&self, shop_domain: &str, event: &DomainEvent) -> AppResult<()> {
+        for (notification, channels) in self.notifications_for(shop_domain, event) {
+            for name in channels {
+                let Some(channel) = self.channels.get(name) else {
+                    warn!("No notification channel named {}", name);
+                    continue;
+                };
+                match channel.send(&notification).await {
+                    Ok(()) => info!("🔔 Sent {} notification for {} to {}", event.name(), shop_domain, name),
+                    Err(e) => warn!("Failed to send {} notification for {} to {}: {}", event.name(), shop_domain, name, e),
+                }
+            }
+        }
+        Ok(())
+    }
+}
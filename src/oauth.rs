@@ -0,0 +1,705 @@
+//! Shopify's authorization code grant: the consent redirect, the callback,
+//! and the code-for-token exchange.
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::{info, warn, error};
+use utoipa::IntoParams;
+
+use crate::{
+    AppConfig,
+    AppState,
+    csv_response::prefers_json,
+    middleware::ClientIp,
+    error::{AppError, AppResult, ShopifyError},
+    pages::{render, BrandingConfig, ErrorPage, InstalledPage},
+    scopes::{parse_scopes, scope_list, ungranted_scopes},
+    script_tags::queue_install_script_tags,
+    token_store::{CompletedExchange, OAuthStateRecord, StateClaim},
+};
+
+// =============================================================================
+// OAuth2 Types
+// =============================================================================
+
+// Optional parameters for starting the OAuth flow
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuthParams {
+    /// Comma-separated scopes to request instead of `SHOPIFY_SCOPES`
+    pub scopes: Option<String>,
+    /// Where to send the merchant after installing: a path on this server, or
+    /// a URL on the same origin as `POST_INSTALL_REDIRECT_URL`
+    pub return_to: Option<String>,
+}
+
+// OAuth2 callback parameters
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CallbackParams {
+    pub code: Option<String>,
+    pub shop: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    /// `html` (default) or `json`; overrides `Accept`
+    pub format: Option<String>,
+}
+
+// Shopify access token response
+#[derive(Deserialize, Serialize)]
+pub struct AccessTokenResponse {
+    pub access_token: String,
+    pub scope: String,
+    /// Seconds until an online token expires; offline tokens don't
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_in: Option<i64>,
+}
+
+impl AccessTokenResponse {
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_in.map(|seconds| Utc::now() + chrono::Duration::seconds(seconds))
+    }
+}
+
+// =============================================================================
+// OAuth2 Client
+// =============================================================================
+
+/// The app's OAuth credentials. Builds consent URLs and exchanges the codes
+/// Shopify sends back to the redirect URI for offline access tokens.
+#[derive(Clone)]
+pub struct ShopifyOAuth {
+    api_key: String,
+    api_secret: String,
+    redirect_uri: String,
+}
+
+impl ShopifyOAuth {
+    pub fn new(api_key: impl Into<String>, api_secret: impl Into<String>, redirect_uri: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            api_secret: api_secret.into(),
+            redirect_uri: redirect_uri.into(),
+        }
+    }
+
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self::new(&config.api_key, &config.api_secret, &config.redirect_uri)
+    }
+
+    /// Consent screen URL for `shop`. `state` must be stored and checked on
+    /// the callback to protect against CSRF.
+    pub fn authorize_url(&self, shop: &str, scopes: &str, state: &str) -> String {
+        format!(
+            "https://{}/admin/oauth/authorize?client_id={}&scope={}&redirect_uri={}&state={}",
+            shop,
+            self.api_key,
+            urlencoding::encode(scopes),
+            urlencoding::encode(&self.redirect_uri),
+            urlencoding::encode(state)
+        )
+    }
+
+    /// Exchanges the authorization code from the callback for an access
+    /// token, at `shop_url` (see `ShopifyClient::shop_url`).
+    pub async fn exchange_code(
+        &self,
+        client: &reqwest::Client,
+        shop_url: &str,
+        code: &str,
+    ) -> Result<AccessTokenResponse, ShopifyError> {
+        // Prepare token exchange request
+        let token_url = format!("{}/admin/oauth/access_token", shop_url);
+
+        let token_request = serde_json::json!({
+            "client_id": self.api_key,
+            "client_secret": self.api_secret,
+            "code": code
+        });
+
+        info!("🔄 Exchanging authorization code for access token...");
+        info!("Token URL: {}", token_url);
+
+        let response = client
+            .post(&token_url)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json")
+            .json(&token_request)
+            .send()
+            .await?;
+
+        let status = response.status();
+
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            error!("Token exchange failed with status {}: {}", status, error_text);
+            return Err(ShopifyError::Api { status, body: error_text });
+        }
+
+        let token_response: AccessTokenResponse = serde_json::from_slice(&response.bytes().await?)?;
+
+        info!("✅ Token exchange successful! Granted scopes: {}", token_response.scope);
+
+        Ok(token_response)
+    }
+}
+
+// =============================================================================
+// OAuth2 Flow Implementation
+// =============================================================================
+
+/// Starts the OAuth flow by redirecting to Shopify with a fresh CSRF state.
+#[utoipa::path(
+    get,
+    path = "/auth",
+    tag = "oauth",
+    params(AuthParams),
+    responses(
+        (status = 308, description = "Redirect to Shopify's authorization page"),
+        (status = 400, description = "Unknown scopes, or a `return_to` this app won't redirect to", content_type = "text/html"),
+    ),
+)]
+pub async fn auth_handler(
+    Query(params): Query<AuthParams>,
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let brand = &state.config.branding;
+    let scopes = match params.scopes.as_deref().map(parse_scopes) {
+        None => state.config.scopes.clone(),
+        Some(Ok(scopes)) => scope_list(&scopes),
+        Some(Err(e)) => {
+            warn!("Rejected OAuth scope override: {}", e);
+            let page = ErrorPage::new(brand, "Invalid Scopes", "❌ Invalid Scopes")
+                .with_message(e.to_string())
+                .with_retry("Use the configured scopes");
+            return (StatusCode::BAD_REQUEST, render(&page)).into_response();
+        }
+    };
+    let return_to = match params.return_to.as_deref().map(str::trim).filter(|r| !r.is_empty()) {
+        None => None,
+        Some(return_to) => match validate_return_to(return_to, state.config.post_install_redirect_url.as_deref()) {
+            Ok(return_to) => Some(return_to),
+            Err(e) => {
+                warn!("Rejected OAuth return_to {:?}: {}", return_to, e);
+                let page = ErrorPage::new(brand, "Invalid Return URL", "❌ Invalid Return URL")
+                    .with_message(e.to_string())
+                    .with_retry("Install without return_to");
+                return (StatusCode::BAD_REQUEST, render(&page)).into_response();
+            }
+        },
+    };
+    let csrf_state = uuid::Uuid::new_v4().to_string(); // CSRF protection
+    
+    // Store CSRF state for validation (10 minutes TTL), bound to this shop and
+    // browser, with where to land afterwards
+    let record = OAuthStateRecord {
+        shop: state.config.shop.clone(),
+        scopes: scopes.clone(),
+        return_to,
+        client_ip,
+        user_agent: user_agent(&headers),
+    };
+    if let Err(e) = state.state_store.store_state(&csrf_state, 600, &record).await {
+        error!("Failed to store CSRF state: {}", e);
+        let page = ErrorPage::new(brand, "Internal Error", "❌ Internal Error")
+            .with_message("Unable to initiate OAuth flow. Please try again.");
+        return render(&page).into_response();
+    }
+    
+    let auth_url = ShopifyOAuth::from_config(&state.config)
+        .authorize_url(&state.config.shop, &scopes, &csrf_state);
+    
+    info!("Redirecting to Shopify OAuth with scopes {}: {}", scopes, auth_url);
+    Redirect::permanent(&auth_url).into_response()
+}
+
+/// Shopify's redirect back: checks the state was issued to this shop and
+/// browser, exchanges the code and stores the token.
+/// Answers with a page, or JSON for `?format=json` or `Accept: application/json`.
+/// Browsers are redirected to the flow's `return_to` or `POST_INSTALL_REDIRECT_URL`
+/// instead when either is set, with `shop` and `status` appended.
+#[utoipa::path(
+    get,
+    path = "/callback",
+    tag = "oauth",
+    params(CallbackParams),
+    responses(
+        (status = 200, description = "Installed; HTML pages also report failures with 200", content(
+            (crate::openapi::OAuthInstalled = "application/json"),
+            (String = "text/html"),
+        )),
+        (status = 303, description = "HTML only: back to `return_to` or `POST_INSTALL_REDIRECT_URL` with `shop`, `status=installed|error` and, on failure, `error`"),
+        (status = 400, description = "Shopify denied the install, or `code` or `state` is missing", body = crate::openapi::OAuthCallbackError),
+        (status = 403, description = "Invalid or expired `state`, or one issued for another shop or browser", body = crate::openapi::OAuthCallbackError),
+        (status = 409, description = "A repeat of a callback whose code exchange is still running", body = crate::openapi::OAuthCallbackError),
+        (status = 500, description = "The state or token couldn't be checked or stored", body = crate::openapi::OAuthCallbackError),
+        (status = 502, description = "Shopify refused the code exchange", body = crate::openapi::OAuthCallbackError),
+    ),
+)]
+pub async fn oauth_callback(
+    Query(params): Query<CallbackParams>,
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+) -> Response {
+    let format = match callback_format(params.format.as_deref(), &headers) {
+        Ok(format) => format,
+        Err(e) => return e.into_response(),
+    };
+    let shop = params.shop.clone().unwrap_or_else(|| state.config.shop.clone());
+    let (record, result) = match check_callback(params, &shop, client_ip, &headers, &state).await {
+        Ok((record, CallbackExchange::Pending { state_token, code })) => {
+            let result = complete_install(&state_token, &code, &shop, &record.scopes, &state).await;
+            (Some(record), result)
+        }
+        Ok((record, CallbackExchange::Done(exchange))) => {
+            info!("♻️ Repeated callback for {}, answering with the earlier install", shop);
+            (Some(record), Ok(Installed { exchange, access_token: None }))
+        }
+        Err(e) => (None, Err(e)),
+    };
+    // A return_to only counts once its state has been consumed
+    let return_to = record
+        .and_then(|record| record.return_to)
+        .or_else(|| state.config.post_install_redirect_url.clone());
+    let brand = &state.config.branding;
+
+    match (result, format) {
+        (Ok(installed), CallbackFormat::Json) => (
+            StatusCode::OK,
+            Json(json!({
+                "installed": true,
+                "shop": shop,
+                "scopes": installed.exchange.scope.split(',').map(str::trim).filter(|s| !s.is_empty()).collect::<Vec<_>>(),
+                "expires_at": installed.exchange.expires_at,
+                "redirect_url": return_to.map(|url| post_install_url(&url, &shop, None))
+            })),
+        )
+            .into_response(),
+        (Ok(_), CallbackFormat::Html) if return_to.is_some() => {
+            let url = post_install_url(return_to.as_deref().unwrap_or_default(), &shop, None);
+            info!("↪️ Redirecting to the host app after install: {}", url);
+            Redirect::to(&url).into_response()
+        }
+        (Ok(installed), CallbackFormat::Html) => render(&InstalledPage {
+            brand,
+            shop: &shop,
+            token_preview: installed.access_token.as_deref().map(|token| preview(token, 12)),
+            scopes: &installed.exchange.scope,
+        })
+        .into_response(),
+        (Err(e), CallbackFormat::Json) => (e.status(), Json(e.to_json())).into_response(),
+        (Err(e), CallbackFormat::Html) => match return_to {
+            Some(url) => Redirect::to(&post_install_url(&url, &shop, Some(&e))).into_response(),
+            None => render(&e.page(brand)).into_response(),
+        },
+    }
+}
+
+// The code exchange a callback should run, or how the one an earlier callback
+// for the same state ran
+enum CallbackExchange {
+    Pending { state_token: String, code: String },
+    Done(CompletedExchange),
+}
+
+// A finished install; only the callback that ran the exchange knows the token
+struct Installed {
+    exchange: CompletedExchange,
+    access_token: Option<String>,
+}
+
+/// How long a repeated callback waits for the first one's exchange to finish.
+const EXCHANGE_WAIT: Duration = Duration::from_secs(10);
+const EXCHANGE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Checks the callback's parameters and claims its CSRF state, returning what
+// `/auth` stored with the state and what's left to do
+async fn check_callback(
+    params: CallbackParams,
+    shop: &str,
+    client_ip: Option<String>,
+    headers: &HeaderMap,
+    state: &AppState,
+) -> Result<(OAuthStateRecord, CallbackExchange), CallbackError> {
+    // Handle OAuth errors
+    if let Some(error) = params.error {
+        error!("OAuth error: {}", error);
+        return Err(CallbackError::Denied(error));
+    }
+    
+    // Validate required parameters
+    let code = params.code.ok_or_else(|| {
+        error!("Missing authorization code");
+        CallbackError::MissingCode
+    })?;
+    
+    // Validate CSRF state parameter for security
+    let Some(received_state) = params.state else {
+        warn!("⚠️ No CSRF state received in callback");
+        return Err(CallbackError::MissingState);
+    };
+    let user_agent = user_agent(headers);
+    let binding = |record: &OAuthStateRecord| {
+        check_state_binding(record, shop, client_ip.as_deref(), user_agent.as_deref())
+    };
+    
+    // Repeats of this callback (a double-click, a retried redirect) carry the
+    // same code and share its single exchange
+    let code_hash = hex::encode(Sha256::digest(code.as_bytes()));
+    let mut waited = Duration::ZERO;
+    loop {
+        let claim = state.state_store.claim_state(&received_state, &code_hash).await.map_err(|e| {
+            error!("Database error during CSRF validation: {}", e);
+            CallbackError::StateCheckFailed
+        })?;
+        match claim {
+            StateClaim::Claimed(record) => {
+                info!("✅ CSRF state validation passed");
+                if let Err(e) = binding(&record) {
+                    release_claim(state, &received_state).await;
+                    return Err(e);
+                }
+                return Ok((record, CallbackExchange::Pending { state_token: received_state, code }));
+            }
+            StateClaim::Completed(record, exchange) => {
+                binding(&record)?;
+                return Ok((record, CallbackExchange::Done(exchange)));
+            }
+            StateClaim::Exchanging(record) => {
+                binding(&record)?;
+                if waited >= EXCHANGE_WAIT {
+                    warn!("⚠️ Gave up waiting for another callback's code exchange for {}", shop);
+                    return Err(CallbackError::ExchangeInProgress);
+                }
+                tokio::time::sleep(EXCHANGE_POLL_INTERVAL).await;
+                waited += EXCHANGE_POLL_INTERVAL;
+            }
+            StateClaim::Invalid => {
+                error!("CSRF state validation failed for state: {}", preview(&received_state, 8));
+                return Err(CallbackError::InvalidState);
+            }
+        }
+    }
+}
+
+/// Checks that a consumed state is being used by the flow it was issued for:
+/// a code for another shop would otherwise be stored under the wrong one.
+/// The browser must match too; a changed IP is only logged, since mobile
+/// networks and dual-stack hosts switch addresses mid-flow.
+pub fn check_state_binding(
+    record: &OAuthStateRecord,
+    shop: &str,
+    client_ip: Option<&str>,
+    user_agent: Option<&str>,
+) -> Result<(), CallbackError> {
+    if !record.shop.eq_ignore_ascii_case(shop) {
+        error!("🚨 CSRF state issued for {:?} was returned for {}", record.shop, shop);
+        return Err(CallbackError::ShopMismatch);
+    }
+    if record.user_agent.is_some() && record.user_agent.as_deref() != user_agent {
+        error!("🚨 CSRF state for {} was returned by a different browser", shop);
+        return Err(CallbackError::ClientMismatch);
+    }
+    if record.client_ip.is_some() && record.client_ip.as_deref() != client_ip {
+        warn!(
+            "⚠️ OAuth flow for {} started from {:?} but returned from {:?}",
+            shop, record.client_ip, client_ip
+        );
+    }
+    Ok(())
+}
+
+fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+// Exchanges the callback's code for a stored access token, then records the
+// outcome on the claimed state for any repeats of the callback
+async fn complete_install(
+    state_token: &str,
+    code: &str,
+    shop: &str,
+    requested_scopes: &str,
+    state: &AppState,
+) -> Result<Installed, CallbackError> {
+    info!("✅ OAuth callback received for shop: {} with code: {}", shop, preview(code, 8));
+    
+    // Exchange authorization code for access token
+    let exchanged = ShopifyOAuth::from_config(&state.config)
+        .exchange_code(state.shopify.http(), &state.shopify.shop_url(shop), code)
+        .await;
+    let token_response = match exchanged {
+        Ok(token_response) => token_response,
+        Err(e) => {
+            error!("Failed to exchange code for token: {}", e);
+            release_claim(state, state_token).await;
+            return Err(CallbackError::TokenExchange(e.to_string()));
+        }
+    };
+    info!("✅ Successfully exchanged code for access token");
+    let missing = ungranted_scopes(&token_response.scope, &parse_scopes(requested_scopes).unwrap_or_default());
+    if !missing.is_empty() {
+        warn!("⚠️ {} granted fewer scopes than requested, missing {}", shop, scope_list(&missing));
+    }
+    
+    // Store the access token
+    let exchange = CompletedExchange {
+        scope: token_response.scope.clone(),
+        expires_at: token_response.expires_at(),
+    };
+    if let Err(e) = state.token_store
+        .store_token(shop, &token_response.access_token, &exchange.scope, exchange.expires_at)
+        .await
+    {
+        error!("Failed to store access token: {}", e);
+        release_claim(state, state_token).await;
+        return Err(CallbackError::TokenStorage(e.to_string()));
+    }
+    
+    // The install stands even if repeats can't be told about it
+    if let Err(e) = state.state_store.complete_state(state_token, &exchange).await {
+        warn!("⚠️ Couldn't record the finished exchange on its state: {}", e);
+    }
+    queue_install_script_tags(state, shop).await;
+    
+    Ok(Installed { exchange, access_token: Some(token_response.access_token) })
+}
+
+// Drops the state after a failed exchange; it expires on its own if this fails
+async fn release_claim(state: &AppState, state_token: &str) {
+    if let Err(e) = state.state_store.release_state(state_token).await {
+        warn!("⚠️ Couldn't release CSRF state {}: {}", preview(state_token, 8), e);
+    }
+}
+
+// =============================================================================
+// Post-Install Redirects
+// =============================================================================
+
+/// Reads `POST_INSTALL_REDIRECT_URL`: an http(s) URL, or a path on this server.
+pub fn post_install_redirect_setting(value: Option<&str>) -> AppResult<Option<String>> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        None => Ok(None),
+        Some(url) if is_local_path(url) || absolute_http_url(url).is_some() => Ok(Some(url.to_string())),
+        Some(_) => Err(AppError::Config(
+            "POST_INSTALL_REDIRECT_URL must be an http(s) URL or a path on this server".to_string(),
+        )),
+    }
+}
+
+/// Checks a `return_to` given to `/auth`. Paths on this server are always
+/// allowed; absolute URLs only on `POST_INSTALL_REDIRECT_URL`'s origin, so the
+/// install can't be turned into an open redirect.
+pub fn validate_return_to(return_to: &str, post_install_url: Option<&str>) -> AppResult<String> {
+    if is_local_path(return_to) {
+        return Ok(return_to.to_string());
+    }
+    let Some(url) = absolute_http_url(return_to) else {
+        return Err(AppError::BadRequest(
+            "return_to must be a path on this server or an http(s) URL".to_string(),
+        ));
+    };
+    match post_install_url.and_then(absolute_http_url) {
+        Some(allowed) if allowed.origin() == url.origin() => Ok(return_to.to_string()),
+        Some(allowed) => Err(AppError::BadRequest(format!(
+            "return_to must be on {}",
+            allowed.origin().ascii_serialization()
+        ))),
+        None => Err(AppError::BadRequest(
+            "return_to must be a path on this server unless POST_INSTALL_REDIRECT_URL is an absolute URL".to_string(),
+        )),
+    }
+}
+
+/// `target` with `shop` and `status=installed`, or `status=error` and the
+/// error's code, added to any query it already has.
+pub fn post_install_url(target: &str, shop: &str, error: Option<&CallbackError>) -> String {
+    // Paths are resolved against a placeholder origin and written back as paths
+    let base = url::Url::parse("http://localhost").expect("valid base URL");
+    let Ok(mut url) = base.join(target) else {
+        return target.to_string();
+    };
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("shop", shop);
+        match error {
+            None => query.append_pair("status", "installed"),
+            Some(error) => query.append_pair("status", "error").append_pair("error", error.code()),
+        };
+    }
+    if is_local_path(target) {
+        url[url::Position::BeforePath..].to_string()
+    } else {
+        url.to_string()
+    }
+}
+
+// `/path`, but not `//host` or `/\host`, which browsers treat as another
+// origin; they also drop tabs and newlines, so `/\t/host` is refused too
+fn is_local_path(value: &str) -> bool {
+    value.starts_with('/')
+        && !value.starts_with("//")
+        && !value.starts_with("/\\")
+        && !value.chars().any(char::is_control)
+}
+
+fn absolute_http_url(value: &str) -> Option<url::Url> {
+    url::Url::parse(value)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+}
+
+// First `len` characters of a secret, for logs and pages; nothing if it's shorter
+fn preview(secret: &str, len: usize) -> &str {
+    secret.get(..len).unwrap_or_default()
+}
+
+// =============================================================================
+// Callback Responses
+// =============================================================================
+
+/// How `/callback` answers: a page for people, JSON for CLIs, tests and SPAs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallbackFormat {
+    Html,
+    Json,
+}
+
+/// `?format=html|json` when given, otherwise JSON only if `Accept` prefers it
+/// to HTML, so browsers (and `Accept: */*`) still get pages.
+pub fn callback_format(format: Option<&str>, headers: &HeaderMap) -> AppResult<CallbackFormat> {
+    match format.map(|f| f.trim().to_ascii_lowercase()).as_deref() {
+        Some("html") => Ok(CallbackFormat::Html),
+        Some("json") => Ok(CallbackFormat::Json),
+        Some(other) => Err(AppError::BadRequest(format!("format must be html or json, got {}", other))),
+        None if prefers_json(headers) => Ok(CallbackFormat::Json),
+        None => Ok(CallbackFormat::Html),
+    }
+}
+
+/// Why an install didn't complete. `code()` is stable for clients to match on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallbackError {
+    /// Shopify redirected back with `?error=`, e.g. the merchant declined
+    Denied(String),
+    MissingCode,
+    MissingState,
+    /// Unknown, reused or expired CSRF state
+    InvalidState,
+    /// The state was issued for a different shop
+    ShopMismatch,
+    /// The state was issued to a different browser
+    ClientMismatch,
+    /// A repeat of a callback whose exchange is still running after the wait
+    ExchangeInProgress,
+    StateCheckFailed,
+    TokenExchange(String),
+    TokenStorage(String),
+}
+
+impl CallbackError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Denied(_) => "oauth_denied",
+            Self::MissingCode => "missing_code",
+            Self::MissingState => "missing_state",
+            Self::InvalidState => "invalid_state",
+            Self::ShopMismatch => "shop_mismatch",
+            Self::ClientMismatch => "client_mismatch",
+            Self::ExchangeInProgress => "exchange_in_progress",
+            Self::StateCheckFailed => "state_check_failed",
+            Self::TokenExchange(_) => "token_exchange_failed",
+            Self::TokenStorage(_) => "token_storage_failed",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Denied(_) | Self::MissingCode | Self::MissingState => StatusCode::BAD_REQUEST,
+            Self::InvalidState | Self::ShopMismatch | Self::ClientMismatch => StatusCode::FORBIDDEN,
+            Self::StateCheckFailed | Self::TokenStorage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::TokenExchange(_) => StatusCode::BAD_GATEWAY,
+            Self::ExchangeInProgress => StatusCode::CONFLICT,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::Denied(_) => "Shopify returned an OAuth error",
+            Self::MissingCode => "Missing authorization code",
+            Self::MissingState => "Missing security token. This could indicate a potential security issue.",
+            Self::InvalidState => "Invalid or expired security token. This could indicate a potential security issue.",
+            Self::ShopMismatch => "This install was started for a different shop. This could indicate a potential security issue.",
+            Self::ClientMismatch => "This install was started in a different browser. This could indicate a potential security issue.",
+            Self::ExchangeInProgress => "This install is still being completed. Refresh the page in a moment.",
+            Self::StateCheckFailed => "Unable to validate security token. Please try again.",
+            Self::TokenExchange(_) => "Failed to exchange authorization code for access token.",
+            Self::TokenStorage(_) => "OAuth was successful but failed to store the access token.",
+        }
+    }
+
+    fn details(&self) -> Option<&str> {
+        match self {
+            Self::Denied(details) | Self::TokenExchange(details) | Self::TokenStorage(details) => Some(details),
+            _ => None,
+        }
+    }
+
+    /// `{"error", "code", "details"?, "auth_url"}`, like other error bodies
+    /// plus the code.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut body = json!({
+            "error": self.message(),
+            "code": self.code(),
+            "auth_url": "/auth"
+        });
+        if let Some(details) = self.details() {
+            body["details"] = json!(details);
+        }
+        body
+    }
+
+    /// The page a browser gets for this failure.
+    pub fn page<'a>(&self, brand: &'a BrandingConfig) -> ErrorPage<'a> {
+        let page = match self {
+            Self::Denied(_) => ErrorPage::new(brand, "OAuth Error", "❌ OAuth Error"),
+            Self::MissingCode => ErrorPage::new(brand, "Error", "❌ Error")
+                .with_message(self.message())
+                .with_retry("Try OAuth again"),
+            Self::MissingState | Self::InvalidState | Self::ShopMismatch | Self::ClientMismatch => ErrorPage::new(brand, "Security Error", "🚨 Security Error")
+                .with_message(self.message())
+                .with_message("Please try the OAuth flow again.")
+                .with_retry("Start OAuth Again"),
+            Self::StateCheckFailed => ErrorPage::new(brand, "System Error", "🚨 System Error")
+                .with_message(self.message())
+                .with_retry("Start OAuth Again"),
+            Self::ExchangeInProgress => ErrorPage::new(brand, "Install In Progress", "⏳ Install In Progress")
+                .with_message(self.message()),
+            Self::TokenExchange(_) => ErrorPage::new(brand, "OAuth Error", "❌ Token Exchange Failed")
+                .with_message(self.message())
+                .with_retry("Try OAuth Again"),
+            Self::TokenStorage(_) => ErrorPage::new(brand, "Storage Error", "❌ Storage Error")
+                .with_message(self.message())
+                .with_retry("Try OAuth Again"),
+        };
+        match self.details() {
+            Some(details) => page.with_detail(details),
+            None => page,
+        }
+    }
+}
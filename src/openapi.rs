@@ -0,0 +1,1178 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use utoipa::{
+    openapi::{
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme},
+        ContentBuilder, Ref, ResponseBuilder,
+    },
+    Modify, OpenApi, ToSchema,
+};
+use uuid::Uuid;
+
+use crate::{
+    abandoned_checkouts::AbandonedCheckout,
+    api_usage::{FeatureUsage, HourlyUsage, UsageSummary},
+    customer_merge::DuplicateGroup,
+    database::{
+        CatalogSyncState, CustomerIndexSyncState, CustomerMerge, FinancialStatusTotal, JobQueueCount, LowStockAlert,
+        MirroredCustomer, MirrorSyncStats, OrderSyncState, QueuedJob, RecoveryMessage, ShopSecretMetadata, TokenAuditEvent,
+        WarehouseExportState,
+    },
+    fulfillment_routing::{FulfillmentRoutingConfig, RoutedFulfillmentOrder},
+    gift_cards::GiftCard,
+    market_prices::{PricedMarket, VariantPrice},
+    payouts::{BalanceAmount, BalanceTransaction, Payout},
+    http_client::PageInfo,
+    job_queue::ShopOrdering,
+    low_stock::LowStockRules,
+    metafields::Metafield,
+    order_documents::DocumentTemplate,
+    order_risks::{OrderRisk, RiskLevel},
+    order_timeline::TimelineEntry,
+    product_affinity::{ProductPair, ProductSales},
+    recovery_tracking::RecoveryDeliverability,
+    sales_metrics::{CurrencyMetrics, Granularity},
+    sales_report::SalesSummary,
+    scheduler::JobScope,
+    shop_info::Shop,
+    script_tags::{ScriptTag, ThemeAsset},
+    shop_secrets::StorefrontToken,
+    shopify_api::{Customer, Fulfillment, FulfillmentOrder, InventoryLevel, Location, Order, Product},
+    token_store::InstalledShop,
+    warehouse_export::ExportTable,
+};
+
+/// Where Swagger UI's assets are loaded from; pinned so the page can't change under us.
+const SWAGGER_UI_CDN: &str = "https://cdn.jsdelivr.net/npm/swagger-ui-dist@5.17.14";
+
+// =============================================================================
+// API Document
+// =============================================================================
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Shopify OAuth2 proxy",
+        description = "Shopify Admin API proxy with OAuth installation, webhooks, local mirrors and reports. \
+            `/api` and `/admin` take an API key or JWT when `API_KEYS` or `JWT_SECRET` is set."
+    ),
+    paths(
+        crate::home::home_handler,
+        crate::oauth::auth_handler,
+        crate::oauth::oauth_callback,
+        crate::shop_info::shop_handler,
+        crate::shop_info::access_scopes_handler,
+        crate::storefront::storefront_token_handler,
+        crate::storefront::revoke_storefront_token_handler,
+        crate::shopify_api::orders_handler,
+        crate::shopify_api::orders_count_handler,
+        crate::order_sync::order_summary_handler,
+        crate::shopify_api::order_handler,
+        crate::order_timeline::order_timeline_handler,
+        crate::order_risks::order_risks_handler,
+        crate::shopify_api::fulfillments_handler,
+        crate::shopify_api::create_fulfillment_handler,
+        crate::shopify_api::fulfillment_orders_handler,
+        crate::order_documents::order_document_handler,
+        crate::fulfillment_routing::preview_fulfillment_route_handler,
+        crate::fulfillment_routing::route_fulfillment_handler,
+        crate::abandoned_checkouts::abandoned_checkouts_handler,
+        crate::abandoned_checkouts::abandoned_checkouts_count_handler,
+        crate::shopify_api::products_handler,
+        crate::shopify_api::products_count_handler,
+        crate::shopify_api::product_handler,
+        crate::market_prices::product_prices_handler,
+        crate::catalog::catalog_handler,
+        crate::shopify_api::customers_handler,
+        crate::shopify_api::customers_count_handler,
+        crate::customer_index::customer_search_handler,
+        crate::shopify_api::customer_handler,
+        crate::customer_merge::customer_duplicates_handler,
+        crate::customer_merge::customer_merge_handler,
+        crate::api_usage::api_usage_handler,
+        crate::shopify_api::inventory_handler,
+        crate::shopify_api::inventory_adjust_handler,
+        crate::shopify_api::inventory_set_handler,
+        crate::shopify_api::inventory_connect_handler,
+        crate::low_stock::low_stock_alerts_handler,
+        crate::shopify_api::locations_handler,
+        crate::shopify_api::locations_count_handler,
+        crate::shopify_api::location_handler,
+        crate::sales_report::sales_report_handler,
+        crate::sales_metrics::sales_metrics_handler,
+        crate::product_affinity::product_affinity_handler,
+        crate::recovery_tracking::record_recovery_send_handler,
+        crate::recovery_tracking::send_recovery_email_handler,
+        crate::recovery_tracking::record_recovery_bounce_handler,
+        crate::recovery_tracking::recovery_deliverability_handler,
+        crate::gift_cards::gift_cards_handler,
+        crate::gift_cards::create_gift_card_handler,
+        crate::gift_cards::gift_card_handler,
+        crate::gift_cards::disable_gift_card_handler,
+        crate::payouts::payouts_handler,
+        crate::payouts::payout_transactions_handler,
+        crate::payouts::balance_handler,
+        crate::script_tags::script_tags_handler,
+        crate::script_tags::create_script_tag_handler,
+        crate::script_tags::delete_script_tag_handler,
+        crate::script_tags::put_theme_asset_handler,
+        crate::metafields::list_metafields_handler,
+        crate::metafields::create_metafield_handler,
+        crate::metafields::update_metafield_handler,
+        crate::metafields::delete_metafield_handler,
+        crate::checkout_settings::get_checkout_settings_handler,
+        crate::checkout_settings::put_checkout_settings_handler,
+        crate::webhooks::list_webhooks_handler,
+        crate::webhooks::orders_created_webhook,
+        crate::webhooks::orders_updated_webhook,
+        crate::webhooks::orders_cancelled_webhook,
+        crate::webhooks::orders_paid_webhook,
+        crate::webhooks::orders_fulfilled_webhook,
+        crate::webhooks::orders_risk_assessment_changed_webhook,
+        crate::webhooks::refunds_created_webhook,
+        crate::webhooks::products_created_webhook,
+        crate::webhooks::products_updated_webhook,
+        crate::webhooks::products_deleted_webhook,
+        crate::webhooks::inventory_levels_updated_webhook,
+        crate::webhooks::customers_created_webhook,
+        crate::webhooks::customers_updated_webhook,
+        crate::webhooks::customers_deleted_webhook,
+        crate::webhooks::customers_redact_webhook,
+        crate::webhooks::checkouts_created_webhook,
+        crate::webhooks::checkouts_updated_webhook,
+        crate::webhooks::app_uninstalled_webhook,
+        crate::webhooks::webhook_event_handler,
+        crate::event_stream::event_stream_handler,
+        crate::event_stream::event_socket_handler,
+        crate::event_stream::event_socket_ticket_handler,
+        crate::webhook_sampling::replay_webhook_event_handler,
+        crate::shop_secrets::list_shop_secrets_handler,
+        crate::shop_secrets::put_webhook_secret_handler,
+        crate::shop_secrets::delete_webhook_secret_handler,
+        crate::order_documents::get_document_template_handler,
+        crate::order_documents::put_document_template_handler,
+        crate::fulfillment_routing::get_fulfillment_routing_handler,
+        crate::fulfillment_routing::put_fulfillment_routing_handler,
+        crate::low_stock::get_low_stock_rules_handler,
+        crate::low_stock::put_low_stock_rules_handler,
+        crate::data_residency::get_shop_region_handler,
+        crate::data_residency::put_shop_region_handler,
+        crate::token_audit::token_audit_handler,
+        crate::scheduler::jobs_handler,
+        crate::job_queue::job_queue_handler,
+        crate::job_queue::list_queued_jobs_handler,
+        crate::job_queue::queued_job_handler,
+        crate::job_queue::retry_queued_job_handler,
+        crate::admin_shops::list_installed_shops_handler,
+        crate::admin_shops::revoke_shop_handler,
+        crate::admin_shops::shop_health_handler,
+        crate::order_sync::order_sync_status_handler,
+        crate::order_sync::start_order_sync_handler,
+        crate::catalog::catalog_sync_status_handler,
+        crate::catalog::start_catalog_sync_handler,
+        crate::customer_index::customer_index_sync_status_handler,
+        crate::customer_index::start_customer_index_sync_handler,
+        crate::warehouse_export::warehouse_export_status_handler,
+        crate::warehouse_export::start_warehouse_export_handler,
+        crate::embedded::embedded_session_handler,
+        crate::app_proxy::app_proxy_session_handler,
+        crate::downloads::download_handler,
+        crate::schemas::list_schemas_handler,
+        crate::schemas::schema_handler,
+        crate::recovery_tracking::recovery_open_handler,
+        crate::recovery_tracking::recovery_click_handler,
+        openapi_handler,
+        swagger_ui_handler,
+    ),
+    components(schemas(ErrorResponse)),
+    modifiers(&ApiSecurity),
+    tags(
+        (name = "orders"),
+        (name = "fulfillments", description = "Fulfillments, fulfillment orders and routing between locations"),
+        (name = "abandoned checkouts"),
+        (name = "products", description = "Products from Shopify and the local catalog"),
+        (name = "customers"),
+        (name = "inventory"),
+        (name = "locations"),
+        (name = "reports"),
+        (name = "recovery", description = "Abandoned checkout recovery emails and their tracking"),
+        (name = "gift cards"),
+        (name = "payouts", description = "Shopify Payments payouts and balance, for finance reconciliation"),
+        (name = "metafields"),
+        (name = "storefront", description = "Script tags and theme assets on the online store"),
+        (name = "checkout settings"),
+        (name = "shops", description = "Installed shops and their per-shop settings"),
+        (name = "sync", description = "Local order and catalog mirrors"),
+        (name = "jobs", description = "Scheduled jobs and the job queue"),
+        (name = "audit"),
+        (name = "webhooks", description = "Shopify webhook deliveries, signed with `X-Shopify-Hmac-Sha256`"),
+        (name = "embedded"),
+        (name = "app proxy", description = "Storefront requests forwarded by Shopify's app proxy, signed with `signature`"),
+        (name = "downloads"),
+        (name = "schemas", description = "JSON Schemas for events this app sends elsewhere"),
+        (name = "oauth"),
+        (name = "docs"),
+    ),
+)]
+pub struct ApiDoc;
+
+/// Declares the security schemes and requires API credentials on `/api` and
+/// `/admin`, matching the router's `ApiGuard` layers. Every guarded operation
+/// also gets a `default` error response for failures any route can hit:
+/// missing credentials or scopes, rate limits, and Shopify errors.
+struct ApiSecurity;
+
+impl Modify for ApiSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                crate::api_auth::API_KEY_HEADER,
+                "A key from API_KEYS",
+            ))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("A key from API_KEYS, or a JWT signed with JWT_SECRET"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "session_token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("An App Bridge session token"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "shopify_hmac",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "X-Shopify-Hmac-Sha256",
+                "Base64 HMAC-SHA256 of the body, keyed with the app's API secret",
+            ))),
+        );
+
+        let guarded = [
+            SecurityRequirement::new("api_key", Vec::<String>::new()),
+            SecurityRequirement::new("bearer", Vec::<String>::new()),
+        ];
+        let error = ResponseBuilder::new()
+            .description("Error, with the message in `error`")
+            .content(
+                "application/json",
+                ContentBuilder::new().schema(Some(Ref::from_schema_name("ErrorResponse"))).build(),
+            )
+            .build();
+
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            if !(path.starts_with("/api/") || path.starts_with("/admin/")) {
+                continue;
+            }
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for operation in operations.into_iter().flatten() {
+                operation.security = Some(guarded.to_vec());
+                operation
+                    .responses
+                    .responses
+                    .entry("default".to_string())
+                    .or_insert_with(|| error.clone().into());
+            }
+        }
+    }
+}
+
+/// The document served at `/openapi.json`, built once.
+pub fn api_doc() -> &'static utoipa::openapi::OpenApi {
+    static DOC: OnceLock<utoipa::openapi::OpenApi> = OnceLock::new();
+    DOC.get_or_init(ApiDoc::openapi)
+}
+
+// =============================================================================
+// Handlers
+// =============================================================================
+
+/// The OpenAPI 3.1 document for every route this server exposes.
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "docs",
+    responses(
+        (status = 200, description = "This document", body = Object),
+    ),
+)]
+pub async fn openapi_handler() -> impl IntoResponse {
+    (StatusCode::OK, axum::Json(api_doc()))
+}
+
+// Inline so the page needs no extra route; allowed by hash in the page's CSP
+const SWAGGER_UI_INIT: &str = r##"window.onload = () => {
+  window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui", deepLinking: true });
+};"##;
+
+/// Swagger UI for `/openapi.json`. Assets come from a pinned CDN build, so the
+/// page gets its own Content-Security-Policy allowing that origin.
+#[utoipa::path(
+    get,
+    path = "/docs",
+    tag = "docs",
+    responses(
+        (status = 200, description = "Swagger UI", content_type = "text/html"),
+    ),
+)]
+pub async fn swagger_ui_handler() -> Response {
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <title>API Reference - Shopify OAuth2 proxy</title>
+    <link rel="stylesheet" href="{cdn}/swagger-ui.css">
+</head>
+<body>
+    <div id="swagger-ui"></div>
+    <script src="{cdn}/swagger-ui-bundle.js"></script>
+    <script>{init}</script>
+</body>
+</html>"#,
+        cdn = SWAGGER_UI_CDN,
+        init = SWAGGER_UI_INIT,
+    );
+
+    let mut response = Html(page).into_response();
+    if let Ok(policy) = HeaderValue::from_str(&swagger_ui_content_security_policy()) {
+        response.headers_mut().insert(header::CONTENT_SECURITY_POLICY, policy);
+    }
+    response
+}
+
+/// Content-Security-Policy for the Swagger UI page: its CDN assets, the
+/// inline init script by hash, and API calls back to this server.
+pub fn swagger_ui_content_security_policy() -> String {
+    let init_hash = general_purpose::STANDARD.encode(Sha256::digest(SWAGGER_UI_INIT.as_bytes()));
+    format!(
+        "default-src 'self'; script-src {cdn} 'sha256-{hash}'; style-src {cdn}; img-src 'self' data:; connect-src 'self'",
+        cdn = "https://cdn.jsdelivr.net",
+        hash = init_hash,
+    )
+}
+
+// =============================================================================
+// Response Bodies
+// =============================================================================
+//
+// Handlers build these bodies with `json!`; the types here describe them for
+// the document and aren't constructed.
+
+/// Body of every error response.
+#[derive(ToSchema)]
+pub struct ErrorResponse {
+    pub error: String,
+    /// Where to (re)install the app, when the token is missing, revoked or lacks scopes
+    pub auth_url: Option<String>,
+    pub missing_scopes: Option<Vec<String>>,
+    /// Shopify's error body, when Shopify rejected the call
+    pub details: Option<String>,
+    /// Seconds to wait, also sent as `Retry-After`
+    pub retry_after: Option<u64>,
+}
+
+/// A list export written to the export directory, for `link=true`.
+#[derive(ToSchema)]
+pub struct ExportLink {
+    /// Signed `/downloads/{token}` URL, relative to this server
+    pub download_url: String,
+    pub file_name: String,
+    pub bytes: u64,
+    /// Seconds until the link stops working
+    pub expires_in: i64,
+}
+
+/// `/callback` with `format=json` once the token is stored.
+#[derive(ToSchema)]
+pub struct OAuthInstalled {
+    pub installed: bool,
+    pub shop: String,
+    pub scopes: Vec<String>,
+    /// Only online tokens expire
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Where a browser would have been sent: the flow's `return_to` or
+    /// `POST_INSTALL_REDIRECT_URL`, with `shop` and `status` appended
+    pub redirect_url: Option<String>,
+}
+
+/// `/callback` with `format=json` when the install failed.
+#[derive(ToSchema)]
+pub struct OAuthCallbackError {
+    pub error: String,
+    /// One of `oauth_denied`, `missing_code`, `missing_state`, `invalid_state`,
+    /// `shop_mismatch`, `client_mismatch`, `exchange_in_progress`,
+    /// `state_check_failed`, `token_exchange_failed`, `token_storage_failed`
+    pub code: String,
+    pub details: Option<String>,
+    /// Where to start the install again
+    pub auth_url: String,
+}
+
+#[derive(ToSchema)]
+pub struct ResourceCount {
+    pub shop: String,
+    pub count: u64,
+}
+
+#[derive(ToSchema)]
+pub struct OrderList {
+    pub shop: String,
+    /// `local` when served from the order mirror, otherwise `shopify`
+    pub source: String,
+    pub orders_count: usize,
+    /// Orders on this page per channel: online, pos, draft, other
+    pub channel_breakdown: BTreeMap<String, usize>,
+    pub orders: Vec<Order>,
+    pub page_info: Option<PageInfo>,
+    /// Path for the next page, with `page_info` and `limit` filled in
+    pub next_page: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct OrderDetail {
+    pub shop: String,
+    pub order: Order,
+}
+
+#[derive(ToSchema)]
+pub struct OrderTimeline {
+    pub shop: String,
+    pub order_id: u64,
+    pub entries_count: usize,
+    pub timeline: Vec<TimelineEntry>,
+    /// Why the order couldn't be fetched, when only webhooks were used
+    pub api_error: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct OrderRiskList {
+    pub shop: String,
+    pub order_id: u64,
+    /// The riskiest assessment's level
+    pub risk_level: RiskLevel,
+    pub risks_count: usize,
+    pub risks: Vec<OrderRisk>,
+}
+
+#[derive(ToSchema)]
+pub struct ProductPrices {
+    pub shop: String,
+    pub product_id: u64,
+    pub title: String,
+    /// Country the prices are for
+    pub country: String,
+    /// When `?market=` named a market rather than a country
+    pub market: Option<PricedMarket>,
+    pub variants_count: usize,
+    pub variants: Vec<VariantPrice>,
+}
+
+#[derive(ToSchema)]
+pub struct ProductList {
+    pub shop: String,
+    pub products_count: usize,
+    pub products: Vec<Product>,
+    pub page_info: Option<PageInfo>,
+    pub next_page: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct ProductDetail {
+    pub shop: String,
+    pub product: Product,
+}
+
+#[derive(ToSchema)]
+pub struct CatalogSearch {
+    pub shop: String,
+    /// Matches across every page
+    pub total: i64,
+    pub products_count: usize,
+    pub products: Vec<Product>,
+    pub page_info: PageInfo,
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+#[derive(ToSchema)]
+pub struct CustomerSearch {
+    pub shop: String,
+    /// Matches across every page
+    pub total: i64,
+    pub customers_count: usize,
+    pub customers: Vec<MirroredCustomer>,
+    pub page_info: PageInfo,
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+#[derive(ToSchema)]
+pub struct CustomerList {
+    pub shop: String,
+    pub customers_count: usize,
+    pub customers: Vec<Customer>,
+    pub page_info: Option<PageInfo>,
+    pub next_page: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct CustomerDetail {
+    pub shop: String,
+    pub customer: Customer,
+}
+
+#[derive(ToSchema)]
+pub struct DuplicateGroupList {
+    pub shop: String,
+    pub customers_scanned: usize,
+    /// Present when `refresh=true` re-synced the mirror first
+    pub sync: Option<MirrorSyncStats>,
+    pub groups_count: usize,
+    pub groups: Vec<DuplicateGroup>,
+}
+
+#[derive(ToSchema)]
+pub struct CustomerMerged {
+    pub success: bool,
+    pub merge: CustomerMerge,
+}
+
+#[derive(ToSchema)]
+pub struct AbandonedCheckoutList {
+    pub shop: String,
+    pub checkouts_count: usize,
+    pub abandoned_checkouts: Vec<AbandonedCheckout>,
+}
+
+#[derive(ToSchema)]
+pub struct InventoryLevelList {
+    pub shop: String,
+    pub inventory_levels_count: usize,
+    pub inventory_levels: Vec<InventoryLevel>,
+}
+
+#[derive(ToSchema)]
+pub struct InventoryLevelChange {
+    pub success: bool,
+    pub inventory_level: InventoryLevel,
+}
+
+#[derive(ToSchema)]
+pub struct LocationList {
+    pub shop: String,
+    pub locations_count: usize,
+    pub locations: Vec<Location>,
+}
+
+#[derive(ToSchema)]
+pub struct LocationDetail {
+    pub shop: String,
+    pub location: Location,
+}
+
+#[derive(ToSchema)]
+pub struct FulfillmentList {
+    pub shop: String,
+    pub order_id: u64,
+    pub fulfillments_count: usize,
+    pub fulfillments: Vec<Fulfillment>,
+}
+
+#[derive(ToSchema)]
+pub struct FulfillmentCreated {
+    pub success: bool,
+    pub fulfillment: Fulfillment,
+}
+
+#[derive(ToSchema)]
+pub struct FulfillmentOrderList {
+    pub shop: String,
+    pub order_id: u64,
+    pub fulfillment_orders_count: usize,
+    pub fulfillment_orders: Vec<FulfillmentOrder>,
+}
+
+#[derive(ToSchema)]
+pub struct FulfillmentRoutes {
+    pub order_id: u64,
+    /// True for the preview, which changes nothing
+    pub dry_run: bool,
+    pub fulfillment_orders: Vec<RoutedFulfillmentOrder>,
+}
+
+#[derive(ToSchema)]
+pub struct FulfillmentRouting {
+    pub shop: String,
+    pub configured: bool,
+    pub routing: FulfillmentRoutingConfig,
+}
+
+#[derive(ToSchema)]
+pub struct FulfillmentRoutingUpdated {
+    pub success: bool,
+    pub shop: String,
+    pub routing: FulfillmentRoutingConfig,
+}
+
+#[derive(ToSchema)]
+pub struct LowStockRuleSet {
+    pub shop: String,
+    pub configured: bool,
+    pub low_stock: LowStockRules,
+}
+
+#[derive(ToSchema)]
+pub struct LowStockRulesUpdated {
+    pub success: bool,
+    /// Open alerts no rule covers any more
+    pub alerts_resolved: usize,
+    pub shop: String,
+    pub low_stock: LowStockRules,
+}
+
+#[derive(ToSchema)]
+pub struct LowStockAlertList {
+    pub shop: String,
+    /// `open` or `all`
+    pub status: String,
+    pub alerts_count: usize,
+    pub alerts: Vec<LowStockAlert>,
+}
+
+#[derive(ToSchema)]
+pub struct SalesReportResponse {
+    pub shop: String,
+    pub created_at_min: String,
+    pub created_at_max: Option<String>,
+    pub currency: Option<String>,
+    pub totals: SalesSummary,
+    /// Keyed by channel: online, pos, draft, other
+    pub by_channel: BTreeMap<String, SalesSummary>,
+    pub by_source_name: BTreeMap<String, SalesSummary>,
+    /// Cancelled and test orders, left out of the sums
+    pub excluded_orders: usize,
+}
+
+#[derive(ToSchema)]
+pub struct SalesMetricsResponse {
+    pub shop: String,
+    pub granularity: Granularity,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub currencies: Vec<CurrencyMetrics>,
+    /// When the local order copy last caught up with Shopify
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+#[derive(ToSchema)]
+pub struct ProductAffinity {
+    pub shop: String,
+    pub created_at_min: String,
+    pub created_at_max: Option<String>,
+    /// Orders and line items written, when `refresh=true` re-synced the mirror first
+    pub sync: Option<BTreeMap<String, u64>>,
+    pub orders_analyzed: usize,
+    pub products: Vec<ProductSales>,
+    pub pairs_count: usize,
+    pub pairs: Vec<ProductPair>,
+}
+
+#[derive(ToSchema)]
+pub struct RecoveryMessageSent {
+    pub success: bool,
+    pub message: RecoveryMessage,
+    /// Put this in the email as a 1x1 image
+    pub open_pixel_url: String,
+    /// Use this in place of the recovery URL
+    pub click_url: String,
+}
+
+#[derive(ToSchema)]
+pub struct RecoveryEmailSent {
+    pub success: bool,
+    pub message: RecoveryMessage,
+    /// The provider's ID for the email, where it gives one (not SMTP)
+    pub provider_message_id: Option<String>,
+    pub open_pixel_url: String,
+    pub click_url: String,
+}
+
+#[derive(ToSchema)]
+pub struct RecoveryMessageChange {
+    pub success: bool,
+    pub message: RecoveryMessage,
+}
+
+#[derive(ToSchema)]
+pub struct RecoveryDeliverabilityReport {
+    pub shop: String,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub deliverability: RecoveryDeliverability,
+}
+
+#[derive(ToSchema)]
+pub struct GiftCardList {
+    pub shop: String,
+    pub gift_cards_count: usize,
+    pub gift_cards: Vec<GiftCard>,
+    pub page_info: Option<PageInfo>,
+    pub next_page: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct PayoutList {
+    pub shop: String,
+    /// False when the shop doesn't use Shopify Payments
+    pub shopify_payments: bool,
+    pub payouts_count: usize,
+    pub payouts: Vec<Payout>,
+    pub page_info: Option<PageInfo>,
+    pub next_page: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct PayoutTransactionList {
+    pub shop: String,
+    /// False when the shop doesn't use Shopify Payments
+    pub shopify_payments: bool,
+    pub payout_id: u64,
+    pub transactions_count: usize,
+    pub transactions: Vec<BalanceTransaction>,
+    pub page_info: Option<PageInfo>,
+    pub next_page: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct PaymentsBalance {
+    pub shop: String,
+    /// False when the shop doesn't use Shopify Payments
+    pub shopify_payments: bool,
+    pub balance: Vec<BalanceAmount>,
+}
+
+#[derive(ToSchema)]
+pub struct GiftCardDetail {
+    pub shop: String,
+    pub gift_card: GiftCard,
+}
+
+#[derive(ToSchema)]
+pub struct GiftCardChange {
+    pub success: bool,
+    pub gift_card: GiftCard,
+}
+
+#[derive(ToSchema)]
+pub struct ScriptTagList {
+    pub shop: String,
+    pub script_tags_count: usize,
+    pub script_tags: Vec<ScriptTag>,
+}
+
+#[derive(ToSchema)]
+pub struct ScriptTagChange {
+    pub success: bool,
+    pub script_tag: ScriptTag,
+}
+
+#[derive(ToSchema)]
+pub struct ScriptTagDeleted {
+    pub success: bool,
+    pub deleted_id: u64,
+}
+
+#[derive(ToSchema)]
+pub struct ThemeAssetChange {
+    pub success: bool,
+    pub theme_id: u64,
+    pub asset: ThemeAsset,
+}
+
+#[derive(ToSchema)]
+pub struct MetafieldList {
+    pub shop: String,
+    pub resource: String,
+    pub id: u64,
+    pub metafields_count: usize,
+    pub metafields: Vec<Metafield>,
+}
+
+#[derive(ToSchema)]
+pub struct MetafieldChange {
+    pub success: bool,
+    pub metafield: Metafield,
+}
+
+#[derive(ToSchema)]
+pub struct MetafieldDeleted {
+    pub success: bool,
+    pub deleted_id: u64,
+}
+
+#[derive(ToSchema)]
+pub struct CheckoutSettings {
+    pub shop: String,
+    /// False until settings are first saved; `settings` is then `{}`
+    pub configured: bool,
+    pub settings: serde_json::Value,
+    pub updated_at: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct CheckoutSettingsUpdated {
+    pub success: bool,
+    pub shop: String,
+    pub settings: serde_json::Value,
+    pub updated_at: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct ShopDetail {
+    pub shop: Shop,
+}
+
+#[derive(ToSchema)]
+pub struct AccessScopes {
+    pub shop: String,
+    /// What Shopify says the stored token can do
+    pub access_scopes: Vec<String>,
+    /// Scope recorded when the token was stored
+    pub stored_scope: Option<String>,
+    /// `SHOPIFY_SCOPES`
+    pub requested_scope: String,
+    pub missing_scopes: Vec<String>,
+    /// `/auth`, when scopes are missing
+    pub reauthorize_url: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct StorefrontTokenDetail {
+    pub shop: String,
+    /// Whether this request created the token
+    pub created: bool,
+    /// GraphQL endpoint the token is sent to, as `X-Shopify-Storefront-Access-Token`
+    pub storefront_api_url: String,
+    pub storefront_token: StorefrontToken,
+}
+
+#[derive(ToSchema)]
+pub struct StorefrontTokenRevoked {
+    pub success: bool,
+    pub shop: String,
+    pub id: u64,
+}
+
+#[derive(ToSchema)]
+pub struct ApiUsage {
+    pub shop: String,
+    pub hours: i64,
+    pub totals: UsageSummary,
+    /// Busiest feature first
+    pub by_feature: Vec<FeatureUsage>,
+    pub timeline: Vec<HourlyUsage>,
+}
+
+#[derive(ToSchema)]
+pub struct InstalledShopList {
+    pub count: usize,
+    pub shops: Vec<InstalledShop>,
+}
+
+#[derive(ToSchema)]
+pub struct ShopRevoked {
+    pub shop: String,
+    pub revoked: bool,
+}
+
+#[derive(ToSchema)]
+pub struct ShopHealth {
+    pub shop: String,
+    pub healthy: bool,
+    /// Status of the `/shop.json` check, or the status this server maps its failure to
+    pub status: u16,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct ShopSecretList {
+    pub shop: String,
+    pub secrets_count: usize,
+    pub secrets: Vec<ShopSecretMetadata>,
+}
+
+#[derive(ToSchema)]
+pub struct WebhookSecretUpdated {
+    pub success: bool,
+    pub shop: String,
+    /// Always `webhook`
+    pub integration: String,
+}
+
+#[derive(ToSchema)]
+pub struct WebhookSecretDeleted {
+    pub success: bool,
+    pub shop: String,
+    pub deleted: bool,
+}
+
+#[derive(ToSchema)]
+pub struct ShopRegion {
+    pub shop: String,
+    /// Null when the shop lives in the home database
+    pub region: Option<String>,
+    pub home: bool,
+    pub available_regions: Vec<String>,
+}
+
+#[derive(ToSchema)]
+pub struct ShopRegionAssigned {
+    pub success: bool,
+    pub shop: String,
+    pub region: String,
+}
+
+#[derive(ToSchema)]
+pub struct DocumentTemplateDetail {
+    pub shop: String,
+    /// `packing-slip` or `invoice`
+    pub document: String,
+    pub customized: bool,
+    pub template: DocumentTemplate,
+}
+
+#[derive(ToSchema)]
+pub struct DocumentTemplateUpdated {
+    pub success: bool,
+    pub shop: String,
+    pub document: String,
+    pub template: DocumentTemplate,
+}
+
+#[derive(ToSchema)]
+pub struct OrderSummaryResponse {
+    pub shop: String,
+    /// Open orders, leaving out test orders
+    pub open_orders: i64,
+    pub unfulfilled_orders: i64,
+    pub partially_fulfilled_orders: i64,
+    pub oldest_unfulfilled_at: Option<DateTime<Utc>>,
+    pub oldest_unfulfilled_age_secs: Option<i64>,
+    /// Open orders per financial status and currency, most orders first
+    pub by_financial_status: Vec<FinancialStatusTotal>,
+    /// When the local copy last synced with Shopify
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+#[derive(ToSchema)]
+pub struct OrderSyncStatus {
+    pub shop: String,
+    pub enabled: bool,
+    pub running: bool,
+    /// Whether `/api/orders` answers from the local copy
+    pub serving_locally: bool,
+    pub state: Option<OrderSyncState>,
+}
+
+#[derive(ToSchema)]
+pub struct CatalogSyncStatus {
+    pub shop: String,
+    pub enabled: bool,
+    pub running: bool,
+    pub state: Option<CatalogSyncState>,
+}
+
+#[derive(ToSchema)]
+pub struct CustomerIndexSyncStatus {
+    pub shop: String,
+    pub enabled: bool,
+    pub running: bool,
+    pub state: Option<CustomerIndexSyncState>,
+}
+
+#[derive(ToSchema)]
+pub struct WarehouseExportStatus {
+    pub shop: String,
+    /// `s3`, `bigquery` or `snowflake`; `null` when export is off
+    pub destination: Option<String>,
+    pub exported_tables: Vec<ExportTable>,
+    /// Tables exported or tried so far
+    pub tables: Vec<WarehouseExportState>,
+}
+
+#[derive(ToSchema)]
+pub struct SyncQueued {
+    pub shop: String,
+    pub queued: bool,
+    pub job_id: Uuid,
+}
+
+#[derive(ToSchema)]
+pub struct ScheduledJobList {
+    /// The instance answering
+    pub instance: String,
+    pub jobs_count: usize,
+    pub jobs: Vec<ScheduledJobStatus>,
+}
+
+#[derive(ToSchema)]
+pub struct ScheduledJobStatus {
+    pub name: String,
+    pub schedule: String,
+    pub scope: JobScope,
+    pub running: bool,
+    /// Instance holding the cluster lock, if any
+    pub running_on: Option<String>,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run: ScheduledJobRun,
+}
+
+#[derive(ToSchema)]
+pub struct ScheduledJobRun {
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: Option<String>,
+    pub summary: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: Option<i64>,
+    pub instance: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct JobQueueOverview {
+    pub instance: String,
+    pub workers: usize,
+    pub kinds: Vec<JobKindSummary>,
+    pub counts: Vec<JobQueueCount>,
+}
+
+#[derive(ToSchema)]
+pub struct JobKindSummary {
+    pub kind: String,
+    pub ordering: ShopOrdering,
+    pub max_attempts: i32,
+}
+
+#[derive(ToSchema)]
+pub struct QueuedJobList {
+    pub jobs_count: usize,
+    pub jobs: Vec<QueuedJob>,
+}
+
+#[derive(ToSchema)]
+pub struct QueuedJobDetail {
+    pub job: QueuedJob,
+}
+
+#[derive(ToSchema)]
+pub struct TokenAuditList {
+    pub count: usize,
+    pub events: Vec<TokenAuditEvent>,
+}
+
+#[derive(ToSchema)]
+pub struct SupportedWebhookList {
+    pub supported_webhooks: Vec<SupportedWebhookEntry>,
+    pub registration_base_url: Option<String>,
+    pub webhook_verification: String,
+    pub format: String,
+}
+
+#[derive(ToSchema)]
+pub struct SupportedWebhookEntry {
+    pub topic: String,
+    pub endpoint: String,
+    pub description: String,
+}
+
+#[derive(ToSchema)]
+pub struct WebhookEventDetail {
+    pub id: Uuid,
+    pub shop_domain: String,
+    pub topic: String,
+    pub resource_id: Option<i64>,
+    pub webhook_id: Option<String>,
+    pub received_at: DateTime<Utc>,
+    pub verified: bool,
+    pub headers: serde_json::Value,
+    /// The body as text, lossily decoded
+    pub raw_body: String,
+    /// The exact bytes Shopify sent
+    pub raw_body_base64: String,
+    pub processing: WebhookEventProcessing,
+}
+
+#[derive(ToSchema)]
+pub struct WebhookEventProcessing {
+    pub attempts: i32,
+    pub processed_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct WebhookReplay {
+    pub replayed: bool,
+    pub event_id: Uuid,
+    pub topic: Option<String>,
+    /// Status staging answered with
+    pub staging_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(ToSchema)]
+pub struct EmbeddedSession {
+    pub shop: String,
+    pub user_id: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub installed: bool,
+}
+
+#[derive(ToSchema)]
+pub struct AppProxySession {
+    pub shop: String,
+    /// Storefront path the proxy is mounted at, e.g. `/apps/rewards`
+    pub path_prefix: Option<String>,
+    /// Absent when nobody is logged in to the storefront
+    pub logged_in_customer_id: Option<String>,
+    pub installed: bool,
+}
+
+#[derive(ToSchema)]
+pub struct EventSchemaList {
+    pub schemas_count: usize,
+    pub schemas: Vec<EventSchemaEntry>,
+}
+
+#[derive(ToSchema)]
+pub struct EventSchemaEntry {
+    pub name: String,
+    /// `forwarded_webhook` or `queue_message`
+    pub kind: String,
+    pub description: String,
+    pub topics: Vec<String>,
+    pub url: String,
+}
@@ -0,0 +1,387 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use printpdf::{BuiltinFont, Line, Mm, PdfDocument, Point};
+use serde::{Deserialize, Serialize};
+use tracing::{info, error};
+use utoipa::ToSchema;
+
+use crate::{
+    AppState,
+    require_token,
+    error::{AppError, AppResult, ShopifyError},
+    http_client::ShopifyClient,
+    shopify_api::{CustomerAddress, Order},
+};
+
+// =============================================================================
+// Document Templates
+// =============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    PackingSlip,
+    Invoice,
+}
+
+impl DocumentKind {
+    /// Accepts the URL form with or without the extension, e.g. `packing-slip.pdf`.
+    pub fn from_slug(slug: &str) -> Option<Self> {
+        match slug.strip_suffix(".pdf").unwrap_or(slug) {
+            "packing-slip" => Some(Self::PackingSlip),
+            "invoice" => Some(Self::Invoice),
+            _ => None,
+        }
+    }
+
+    pub fn slug(&self) -> &'static str {
+        match self {
+            Self::PackingSlip => "packing-slip",
+            Self::Invoice => "invoice",
+        }
+    }
+
+    fn default_title(&self) -> &'static str {
+        match self {
+            Self::PackingSlip => "Packing Slip",
+            Self::Invoice => "Invoice",
+        }
+    }
+}
+
+/// Per-shop customization for a document kind. Everything is optional, so an
+/// empty template renders a plain document headed with the shop domain.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct DocumentTemplate {
+    pub title: Option<String>,
+    pub company_name: Option<String>,
+    pub company_address: Vec<String>,
+    pub contact_email: Option<String>,
+    /// Shown under the company details on invoices
+    pub tax_id: Option<String>,
+    /// Packing slips leave prices out unless this is set
+    pub show_prices: bool,
+    pub footer: Option<String>,
+}
+
+// =============================================================================
+// Document Layout
+// =============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Title(String),
+    Heading(String),
+    Text(String),
+    Spacer,
+    Rule,
+    TableHeader(Vec<String>),
+    TableRow(Vec<String>),
+    /// Label and amount, aligned with the last table column
+    Total(String, String),
+}
+
+fn address_lines(address: &CustomerAddress) -> Vec<String> {
+    let name = address.name.clone().or_else(|| {
+        let full = format!(
+            "{} {}",
+            address.first_name.as_deref().unwrap_or_default(),
+            address.last_name.as_deref().unwrap_or_default()
+        );
+        Some(full.trim().to_string())
+    });
+    let city_line = [address.city.as_deref(), address.province_code.as_deref(), address.zip.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    [name, address.company.clone(), address.address1.clone(), address.address2.clone(), Some(city_line), address.country.clone()]
+        .into_iter()
+        .flatten()
+        .filter(|line| !line.trim().is_empty())
+        .collect()
+}
+
+fn money(amount: &str, currency: &str) -> String {
+    format!("{} {}", amount, currency).trim().to_string()
+}
+
+/// Lays out a document for an order. Kept separate from PDF rendering so the
+/// content can be checked without parsing PDFs.
+pub fn document_blocks(kind: DocumentKind, order: &Order, template: &DocumentTemplate, shop: &str) -> Vec<Block> {
+    let currency = order.currency.as_deref().unwrap_or_default();
+    let show_prices = kind == DocumentKind::Invoice || template.show_prices;
+    let mut blocks = Vec::new();
+
+    blocks.push(Block::Title(template.title.clone().unwrap_or_else(|| kind.default_title().to_string())));
+    blocks.push(Block::Heading(template.company_name.clone().unwrap_or_else(|| shop.to_string())));
+    blocks.extend(template.company_address.iter().cloned().map(Block::Text));
+    if let Some(ref email) = template.contact_email {
+        blocks.push(Block::Text(email.clone()));
+    }
+    if let (DocumentKind::Invoice, Some(tax_id)) = (kind, template.tax_id.as_ref()) {
+        blocks.push(Block::Text(format!("Tax ID: {}", tax_id)));
+    }
+
+    blocks.push(Block::Spacer);
+    blocks.push(Block::Text(format!("Order {}", order.name)));
+    blocks.push(Block::Text(format!("Date: {}", order.created_at.get(..10).unwrap_or(&order.created_at))));
+    if kind == DocumentKind::Invoice {
+        if let Some(ref status) = order.financial_status {
+            blocks.push(Block::Text(format!("Payment status: {}", status)));
+        }
+    }
+
+    let (address_heading, address) = match kind {
+        DocumentKind::PackingSlip => ("Ship to", order.shipping_address.as_ref().or(order.billing_address.as_ref())),
+        DocumentKind::Invoice => ("Bill to", order.billing_address.as_ref().or(order.shipping_address.as_ref())),
+    };
+    if let Some(address) = address {
+        blocks.push(Block::Spacer);
+        blocks.push(Block::Heading(address_heading.to_string()));
+        blocks.extend(address_lines(address).into_iter().map(Block::Text));
+    }
+
+    blocks.push(Block::Spacer);
+    let mut header = vec!["Qty".to_string(), "SKU".to_string(), "Item".to_string()];
+    if show_prices {
+        header.push("Price".to_string());
+    }
+    blocks.push(Block::TableHeader(header));
+    blocks.push(Block::Rule);
+
+    for item in &order.line_items {
+        let title = match item.variant_title.as_deref() {
+            Some(variant) if !variant.is_empty() => format!("{} - {}", item.title, variant),
+            _ => item.title.clone(),
+        };
+        let mut row = vec![item.quantity.to_string(), item.sku.clone().unwrap_or_default(), title];
+        if show_prices {
+            row.push(money(&item.price, currency));
+        }
+        blocks.push(Block::TableRow(row));
+    }
+    blocks.push(Block::Rule);
+
+    if show_prices {
+        if let Some(ref subtotal) = order.subtotal_price {
+            blocks.push(Block::Total("Subtotal".to_string(), money(subtotal, currency)));
+        }
+        for shipping in &order.shipping_lines {
+            blocks.push(Block::Total(format!("Shipping ({})", shipping.title), money(&shipping.price, currency)));
+        }
+        if let Some(discounts) = order.total_discounts.as_deref().filter(|d| !matches!(*d, "0" | "0.00")) {
+            blocks.push(Block::Total("Discounts".to_string(), format!("-{}", money(discounts, currency))));
+        }
+        if let Some(ref tax) = order.total_tax {
+            blocks.push(Block::Total("Tax".to_string(), money(tax, currency)));
+        }
+        blocks.push(Block::Total("Total".to_string(), money(&order.total_price, currency)));
+    }
+
+    if let Some(ref footer) = template.footer {
+        blocks.push(Block::Spacer);
+        blocks.push(Block::Text(footer.clone()));
+    }
+
+    blocks
+}
+
+// =============================================================================
+// PDF Rendering
+// =============================================================================
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+// x positions for Qty, SKU, Item, Price
+const COLUMNS: [f32; 4] = [MARGIN, 35.0, 75.0, 160.0];
+
+// The built-in PDF fonts only cover Latin-1
+fn pdf_text(text: &str) -> String {
+    text.chars().map(|c| if (c as u32) < 0x100 { c } else { '?' }).collect()
+}
+
+pub fn render_pdf(title: &str, blocks: &[Block]) -> Result<Vec<u8>, AppError> {
+    let document_error = |e: printpdf::Error| AppError::Document(e.to_string());
+
+    let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Content");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(document_error)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(document_error)?;
+
+    let mut layer = doc.get_page(page).get_layer(layer);
+    let mut y = PAGE_HEIGHT - MARGIN;
+
+    for block in blocks {
+        let height = match block {
+            Block::Title(_) => 12.0,
+            Block::Heading(_) => 7.0,
+            Block::Spacer => 5.0,
+            Block::Rule => 3.0,
+            _ => 6.0,
+        };
+        if y - height < MARGIN {
+            let (page, page_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Content");
+            layer = doc.get_page(page).get_layer(page_layer);
+            y = PAGE_HEIGHT - MARGIN;
+        }
+        y -= height;
+
+        match block {
+            Block::Title(text) => layer.use_text(pdf_text(text), 20.0, Mm(MARGIN), Mm(y), &bold),
+            Block::Heading(text) => layer.use_text(pdf_text(text), 12.0, Mm(MARGIN), Mm(y), &bold),
+            Block::Text(text) => layer.use_text(pdf_text(text), 10.0, Mm(MARGIN), Mm(y), &regular),
+            Block::Spacer => {}
+            Block::Rule => layer.add_line(Line {
+                points: vec![
+                    (Point::new(Mm(MARGIN), Mm(y + 1.5)), false),
+                    (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(y + 1.5)), false),
+                ],
+                is_closed: false,
+            }),
+            Block::TableHeader(cells) | Block::TableRow(cells) => {
+                let font = if matches!(block, Block::TableHeader(_)) { &bold } else { &regular };
+                for (cell, x) in cells.iter().zip(COLUMNS) {
+                    // Item titles are cut short rather than running into the price column
+                    let cell: String = cell.chars().take(48).collect();
+                    layer.use_text(pdf_text(&cell), 10.0, Mm(x), Mm(y), font);
+                }
+            }
+            Block::Total(label, amount) => {
+                let font = if label == "Total" { &bold } else { &regular };
+                layer.use_text(pdf_text(label), 10.0, Mm(COLUMNS[2]), Mm(y), font);
+                layer.use_text(pdf_text(amount), 10.0, Mm(COLUMNS[3]), Mm(y), font);
+            }
+        }
+    }
+
+    doc.save_to_bytes().map_err(document_error)
+}
+
+// =============================================================================
+// Document Handlers
+// =============================================================================
+
+#[derive(Deserialize)]
+struct OrderResponse {
+    order: Order,
+}
+
+async fn fetch_order(client: &ShopifyClient, token: &str, order_id: u64) -> AppResult<Order> {
+    let response = client
+        .get_with_auth::<OrderResponse>(&format!("orders/{}.json", order_id), token, None)
+        .await
+        .map_err(|e| match e {
+            ShopifyError::NotFound => AppError::NotFound(format!("Order {} not found", order_id)),
+            e => AppError::from(e),
+        })?;
+
+    Ok(response.data.order)
+}
+
+fn parse_kind(slug: &str) -> AppResult<DocumentKind> {
+    DocumentKind::from_slug(slug).ok_or_else(|| {
+        AppError::NotFound(format!("Unknown document {}; use packing-slip.pdf or invoice.pdf", slug))
+    })
+}
+
+/// `GET /api/orders/:id/documents/:document`, e.g. `packing-slip.pdf` or `invoice.pdf`
+#[utoipa::path(
+    get,
+    path = "/api/orders/{id}/documents/{document}",
+    tag = "orders",
+    params(("id" = u64, Path, description = "Order ID"), ("document" = String, Path, description = "packing-slip.pdf or invoice.pdf")),
+    responses(
+        (status = 200, description = "The rendered document", content_type = "application/pdf"),
+        (status = 404, description = "No such order or document", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn order_document_handler(
+    Path((order_id, document)): Path<(u64, String)>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let kind = parse_kind(&document)?;
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let order = fetch_order(&state.shopify, &token, order_id).await?;
+    let template = state.document_templates.get(shop, kind).await?.unwrap_or_default();
+
+    let blocks = document_blocks(kind, &order, &template, shop);
+    let title = format!("{} {}", kind.default_title(), order.name);
+    let pdf = render_pdf(&title, &blocks).map_err(|e| {
+        error!("Failed to render {} for order {}: {}", kind.slug(), order_id, e);
+        e
+    })?;
+    info!("🧾 Generated {} for order {} ({} bytes)", kind.slug(), order.name, pdf.len());
+
+    let file_name = format!("{}-{}.pdf", kind.slug(), order.name.trim_start_matches('#'));
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (header::CONTENT_DISPOSITION, format!("inline; filename=\"{}\"", file_name)),
+        ],
+        pdf,
+    ))
+}
+
+/// The shop's customization for a document, or the default when none is stored.
+#[utoipa::path(
+    get,
+    path = "/admin/shops/{shop}/document-templates/{document}",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com"), ("document" = String, Path, description = "packing-slip.pdf or invoice.pdf")),
+    responses(
+        (status = 200, description = "The shop's template for the document", body = crate::openapi::DocumentTemplateDetail),
+        (status = 404, description = "Unknown document", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn get_document_template_handler(
+    Path((shop, document)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let kind = parse_kind(&document)?;
+    let template = state.document_templates.get(&shop, kind).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "document": kind.slug(),
+        "customized": template.is_some(),
+        "template": template.unwrap_or_default()
+    }))))
+}
+
+/// Replaces the shop's customization for a document.
+#[utoipa::path(
+    put,
+    path = "/admin/shops/{shop}/document-templates/{document}",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com"), ("document" = String, Path, description = "packing-slip.pdf or invoice.pdf")),
+    request_body = DocumentTemplate,
+    responses(
+        (status = 200, description = "The stored template", body = crate::openapi::DocumentTemplateUpdated),
+        (status = 404, description = "Unknown document", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn put_document_template_handler(
+    Path((shop, document)): Path<(String, String)>,
+    State(state): State<AppState>,
+    Json(template): Json<DocumentTemplate>,
+) -> AppResult<impl IntoResponse> {
+    let kind = parse_kind(&document)?;
+    state.document_templates.put(&shop, kind, &template).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "shop": shop,
+        "document": kind.slug(),
+        "template": template
+    }))))
+}
@@ -0,0 +1,205 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    AppState,
+    require_token,
+    database::OrderStore,
+    error::{AppError, AppResult},
+    response_cache::CacheGroup,
+};
+
+/// Topic of Shopify's webhook for a changed order risk assessment.
+pub const RISK_ASSESSMENT_TOPIC: &str = "orders/risk_assessment_changed";
+
+// =============================================================================
+// Risk Structures
+// =============================================================================
+
+/// One fraud analysis of an order, by Shopify or a fraud protection app.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct OrderRisk {
+    pub id: u64,
+    pub order_id: u64,
+    pub checkout_id: Option<u64>,
+    /// Who assessed the order, e.g. `External`
+    pub source: String,
+    /// 0.0 (no risk) to 1.0
+    pub score: String,
+    /// accept, investigate or cancel
+    pub recommendation: String,
+    /// Whether it's shown on the order page in the admin
+    pub display: bool,
+    pub cause_cancel: Option<bool>,
+    pub message: String,
+    pub merchant_message: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct OrderRisksResponse {
+    pub risks: Vec<OrderRisk>,
+}
+
+/// An order's overall fraud risk, as `/api/orders?risk=` filters on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+}
+
+impl RiskLevel {
+    /// `low`, `medium` or `high`, in any case.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "low" => Some(Self::Low),
+            "medium" => Some(Self::Medium),
+            "high" => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+
+    /// The level a risk's `recommendation` stands for: `cancel` is high and
+    /// `investigate` medium.
+    pub fn from_recommendation(recommendation: &str) -> Self {
+        match recommendation {
+            "cancel" => Self::High,
+            "investigate" => Self::Medium,
+            _ => Self::Low,
+        }
+    }
+
+    /// An order is as risky as its riskiest assessment; one nobody has
+    /// assessed is low risk.
+    pub fn of(risks: &[OrderRisk]) -> Self {
+        risks
+            .iter()
+            .map(|risk| Self::from_recommendation(&risk.recommendation))
+            .max()
+            .unwrap_or(Self::Low)
+    }
+}
+
+/// `orders/risk_assessment_changed` payload.
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema, ToSchema)]
+pub struct RiskAssessmentWebhook {
+    pub order_id: Option<u64>,
+    pub admin_graphql_api_order_id: Option<String>,
+    /// HIGH, MEDIUM, LOW, NONE or PENDING
+    pub risk_level: String,
+    pub provider_id: Option<u64>,
+    pub provider_title: Option<String>,
+    pub created_at: Option<String>,
+}
+
+impl RiskAssessmentWebhook {
+    /// The assessed order, from `order_id` or the end of its GraphQL ID.
+    pub fn order_id(&self) -> Option<u64> {
+        self.order_id.or_else(|| {
+            self.admin_graphql_api_order_id
+                .as_deref()
+                .and_then(|gid| gid.rsplit('/').next())
+                .and_then(|id| id.parse().ok())
+        })
+    }
+
+    /// The order's new level, or `None` while the assessment is pending.
+    pub fn level(&self) -> Option<RiskLevel> {
+        match self.risk_level.to_ascii_uppercase().as_str() {
+            "NONE" => Some(RiskLevel::Low),
+            _ => RiskLevel::parse(&self.risk_level),
+        }
+    }
+}
+
+// =============================================================================
+// Webhook Processing
+// =============================================================================
+
+/// Records the level from an `orders/risk_assessment_changed` webhook for
+/// `/api/orders?risk=`.
+pub async fn apply_risk_assessment_webhook(
+    store: &OrderStore,
+    shop: &str,
+    payload: &serde_json::Value,
+) -> AppResult<()> {
+    let assessment: RiskAssessmentWebhook = serde_json::from_value(payload.clone())
+        .map_err(|e| AppError::BadRequest(format!("Unreadable {} payload: {}", RISK_ASSESSMENT_TOPIC, e)))?;
+    let Some(order_id) = assessment.order_id().and_then(|id| i64::try_from(id).ok()) else {
+        warn!("Skipping {} webhook for {} without an order ID", RISK_ASSESSMENT_TOPIC, shop);
+        return Ok(());
+    };
+    match assessment.level() {
+        Some(level) => store.record_risk_level(shop, order_id, level.as_str()).await,
+        None => Ok(()),
+    }
+}
+
+// =============================================================================
+// Risk Handlers
+// =============================================================================
+
+/// `GET /api/orders/{id}/risks` — Shopify's fraud analyses of an order and
+/// the overall level they add up to, which is also recorded for
+/// `/api/orders?risk=` when the local order copy is on.
+#[utoipa::path(
+    get,
+    path = "/api/orders/{id}/risks",
+    tag = "orders",
+    params(("id" = u64, Path, description = "Order ID")),
+    responses(
+        (status = 200, description = "The order's risk assessments", body = crate::openapi::OrderRiskList),
+        (status = 404, description = "No such order", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn order_risks_handler(
+    Path(order_id): Path<u64>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let risks = state
+        .shopify
+        .get_with_auth::<OrderRisksResponse>(&format!("orders/{}/risks.json", order_id), &token, None)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch risks for order {}: {}", order_id, e);
+            e
+        })?
+        .data
+        .risks;
+    let risk_level = RiskLevel::of(&risks);
+    info!("Successfully fetched {} risks for order {} ({})", risks.len(), order_id, risk_level.as_str());
+
+    if state.config.database.database_url.is_some() {
+        match state.orders.record_risk_level(shop, order_id as i64, risk_level.as_str()).await {
+            Ok(()) => state.response_cache.invalidate(shop, CacheGroup::Orders).await,
+            Err(e) => warn!("Failed to record the risk level of order {}: {}", order_id, e),
+        }
+    }
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "order_id": order_id,
+        "risk_level": risk_level,
+        "risks_count": risks.len(),
+        "risks": risks
+    }))))
+}
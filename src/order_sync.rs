@@ -0,0 +1,532 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::{pin_mut, TryStreamExt};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::{
+    AppState,
+    database::{OrderStore, QueuedJob, StoredOrder, StoredOrderFilter},
+    error::{AppError, AppResult},
+    http_client::{PageInfo, PaginatedResponse, ShopifyClient},
+    job_queue::{JobHandlerResult, JobKind, ShopOrdering},
+    order_risks::{apply_risk_assessment_webhook, RiskLevel, RISK_ASSESSMENT_TOPIC},
+    scheduler::{Job, JobResult, JobScope, Schedule},
+    shopify_api::{Order, OrderParams, OrdersResponse, ORDERS_DEFAULT_LIMIT},
+};
+
+/// Prefix of `page_info` cursors for pages served from the local copy, so
+/// they can't be confused with Shopify's cursors.
+const LOCAL_CURSOR_PREFIX: &str = "local_";
+
+/// How far before the previous run an incremental sync starts looking, to
+/// cover clock skew between this server and Shopify.
+const SYNC_OVERLAP: chrono::Duration = chrono::Duration::minutes(5);
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct OrderSyncConfig {
+    /// Keep a local copy of orders and serve `/api/orders` from it
+    pub enabled: bool,
+    /// Time between incremental syncs of every installed shop
+    pub interval: Duration,
+}
+
+impl Default for OrderSyncConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: Duration::from_secs(300),
+        }
+    }
+}
+
+impl OrderSyncConfig {
+    /// `ORDER_SYNC_INTERVAL_SECS`; 0 turns the local copy off.
+    pub fn from_env() -> Self {
+        match std::env::var("ORDER_SYNC_INTERVAL_SECS").ok().and_then(|v| v.parse::<u64>().ok()) {
+            Some(0) => Self { enabled: false, ..Self::default() },
+            Some(secs) => Self { enabled: true, interval: Duration::from_secs(secs) },
+            None => Self::default(),
+        }
+    }
+}
+
+// =============================================================================
+// Conversion
+// =============================================================================
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value).ok().map(|t| t.with_timezone(&Utc))
+}
+
+/// The row kept for `order`, or `None` if it lacks an ID or a readable
+/// `created_at`.
+pub fn stored_order(order: &Order) -> Option<StoredOrder> {
+    let created_at = parse_timestamp(&order.created_at)?;
+    let optional = |value: &Option<String>| value.as_deref().and_then(parse_timestamp);
+
+    Some(StoredOrder {
+        order_id: i64::try_from(order.id).ok().filter(|id| *id > 0)?,
+        order_number: i64::try_from(order.order_number).unwrap_or_default(),
+        name: order.name.clone(),
+        email: order.email.clone(),
+        financial_status: order.financial_status.clone(),
+        fulfillment_status: order.fulfillment_status.clone(),
+        source_name: order.source_name.clone(),
+        total_price: order.total_price.parse().unwrap_or(Decimal::ZERO),
+        currency: order.currency.clone(),
+        test: order.test,
+        created_at,
+        updated_at: optional(&order.updated_at).unwrap_or(created_at),
+        processed_at: optional(&order.processed_at),
+        closed_at: optional(&order.closed_at),
+        cancelled_at: optional(&order.cancelled_at),
+        data: serde_json::to_value(order).ok()?,
+    })
+}
+
+// =============================================================================
+// Sync
+// =============================================================================
+
+/// What one sync run did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct OrderSyncReport {
+    /// Whether this run was the initial backfill
+    pub backfill: bool,
+    pub fetched: usize,
+    pub written: u64,
+}
+
+// Shops with a sync in progress in this process
+fn syncing() -> &'static Mutex<HashSet<String>> {
+    static SYNCING: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    SYNCING.get_or_init(Default::default)
+}
+
+struct SyncGuard(String);
+
+impl SyncGuard {
+    fn acquire(shop: &str) -> Option<Self> {
+        syncing().lock().unwrap().insert(shop.to_string()).then(|| Self(shop.to_string()))
+    }
+}
+
+impl Drop for SyncGuard {
+    fn drop(&mut self) {
+        syncing().lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Brings the shop's local orders up to date: every order on the first run,
+/// then those updated since the previous run started. One run per shop at a
+/// time; a second caller gets `Conflict`.
+pub async fn sync_orders(
+    shopify: &ShopifyClient,
+    store: &OrderStore,
+    shop: &str,
+    token: &str,
+) -> AppResult<OrderSyncReport> {
+    let _guard = SyncGuard::acquire(shop)
+        .ok_or_else(|| AppError::Conflict(format!("An order sync is already running for {}", shop)))?;
+
+    let state = store.sync_state(shop).await?;
+    let cursor = state.as_ref().filter(|s| s.backfilled_at.is_some()).and_then(|s| s.updated_at_cursor);
+    let started_at = Utc::now();
+
+    let mut query_params = vec![
+        ("limit".to_string(), "250".to_string()),
+        ("status".to_string(), "any".to_string()),
+    ];
+    if let Some(cursor) = cursor {
+        query_params.push(("updated_at_min".to_string(), (cursor - SYNC_OVERLAP).to_rfc3339()));
+    }
+
+    let mut report = OrderSyncReport { backfill: cursor.is_none(), ..Default::default() };
+    let client = shopify.for_shop(shop);
+    let result: AppResult<()> = async {
+        let pages = client.get_all_pages::<OrdersResponse>("orders.json", token, query_params);
+        pin_mut!(pages);
+
+        while let Some(page) = pages.try_next().await? {
+            let orders: Vec<StoredOrder> = page.orders.iter().filter_map(stored_order).collect();
+            report.fetched += page.orders.len();
+            report.written += store.upsert_orders(shop, &orders).await?;
+        }
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            store.record_sync(shop, report.backfill, Some(started_at), None).await?;
+            info!(
+                "📦 Synced orders for {}: {} fetched, {} written{}",
+                shop, report.fetched, report.written, if report.backfill { " (backfill)" } else { "" }
+            );
+            Ok(report)
+        }
+        Err(e) => {
+            if let Err(record_error) = store.record_sync(shop, false, None, Some(&e.to_string())).await {
+                error!("Failed to record order sync failure for {}: {}", shop, record_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Queue job kind syncing one shop's orders, retried with backoff.
+pub const SHOP_ORDER_SYNC_JOB: &str = "shop-order-sync";
+
+pub fn shop_order_sync_job_kind() -> JobKind {
+    JobKind::new(SHOP_ORDER_SYNC_JOB, ShopOrdering::Single, |state: AppState, job: QueuedJob| async move {
+        run_shop_order_sync(&state, job.shop_domain.as_deref()).await
+    })
+}
+
+async fn run_shop_order_sync(state: &AppState, shop: Option<&str>) -> JobHandlerResult {
+    let shop = shop.ok_or("Order sync job has no shop")?;
+    let token = state.token_store.get_token(shop).await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Shop {} is not installed", shop))?;
+
+    sync_orders(&state.shopify, &state.orders, shop, &token)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Queues an order sync of every installed shop each
+/// `ORDER_SYNC_INTERVAL_SECS`, backfilling shops that haven't been yet.
+pub fn order_sync_job(config: &OrderSyncConfig) -> Job {
+    Job::new("order-sync", Schedule::Every(config.interval), JobScope::Cluster, |state: AppState| async move {
+        queue_installed_shops(&state).await
+    })
+    .run_on_start()
+}
+
+async fn queue_installed_shops(state: &AppState) -> JobResult {
+    let shops = state.token_store.list_shops().await.map_err(|e| e.to_string())?;
+
+    let (mut queued, mut pending, mut failed) = (0, 0, Vec::new());
+    for shop in shops {
+        match state.job_queue.enqueue(SHOP_ORDER_SYNC_JOB, Some(&shop.shop_domain), serde_json::json!({})).await {
+            Ok(Some(_)) => queued += 1,
+            Ok(None) => pending += 1,
+            Err(e) => {
+                error!("Failed to queue order sync for {}: {}", shop.shop_domain, e);
+                failed.push(shop.shop_domain);
+            }
+        }
+    }
+
+    let summary = format!("{} shops queued, {} already pending", queued, pending);
+    if failed.is_empty() {
+        Ok(summary)
+    } else {
+        Err(format!("{}, failed for {}", summary, failed.join(", ")))
+    }
+}
+
+/// Applies an `orders/*` webhook to the local copy between syncs.
+pub async fn apply_order_webhook(
+    store: &OrderStore,
+    shop: &str,
+    topic: &str,
+    payload: &serde_json::Value,
+) -> AppResult<()> {
+    if topic == RISK_ASSESSMENT_TOPIC {
+        return apply_risk_assessment_webhook(store, shop, payload).await;
+    }
+    if topic == "orders/delete" {
+        if let Some(order_id) = payload["id"].as_i64() {
+            store.delete_order(shop, order_id).await?;
+        }
+        return Ok(());
+    }
+
+    let order: Order = serde_json::from_value(payload.clone())
+        .map_err(|e| AppError::BadRequest(format!("Unreadable {} payload: {}", topic, e)))?;
+    match stored_order(&order) {
+        Some(order) => store.upsert_orders(shop, &[order]).await.map(|_| ()),
+        None => {
+            warn!("Skipping {} webhook for {} without an order ID or created_at", topic, shop);
+            Ok(())
+        }
+    }
+}
+
+// =============================================================================
+// Local Queries
+// =============================================================================
+
+fn parse_filter_timestamp(name: &str, value: Option<&str>) -> Result<Option<DateTime<Utc>>, String> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    parse_timestamp(value)
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|midnight| midnight.and_utc())
+        })
+        .map(Some)
+        .ok_or_else(|| format!("{} must be an ISO 8601 date or timestamp", name))
+}
+
+/// The `/api/orders` query as a filter on the local copy. `before_id` is
+/// taken from a local `page_info` cursor.
+pub fn stored_order_filter(params: &OrderParams, before_id: Option<i64>) -> Result<StoredOrderFilter, String> {
+    let ids = params
+        .ids
+        .as_deref()
+        .map(|ids| {
+            ids.split(',')
+                .map(|id| id.trim().parse::<i64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| "ids must be comma-separated order IDs".to_string())
+        })
+        .transpose()?;
+
+    let status = match params.status.as_deref().map(str::trim) {
+        None | Some("any") | Some("") => None,
+        Some(status @ ("open" | "closed" | "cancelled")) => Some(status.to_string()),
+        Some(other) => return Err(format!("Unknown status: {}", other)),
+    };
+
+    let financial_statuses = match params.financial_status.as_deref().map(str::trim) {
+        None | Some("any") | Some("") => None,
+        Some("unpaid") => Some(vec!["authorized".to_string(), "partially_paid".to_string()]),
+        Some(status) => Some(vec![status.to_string()]),
+    };
+
+    // Shopify filters on shipping state; stored orders keep the order's own status
+    let fulfillment_statuses = match params.fulfillment_status.as_deref().map(str::trim) {
+        None | Some("any") | Some("") => None,
+        Some("shipped") => Some(vec!["fulfilled"]),
+        Some("partial") => Some(vec!["partial"]),
+        Some("unshipped") => Some(vec!["unfulfilled"]),
+        Some("unfulfilled") => Some(vec!["unfulfilled", "partial"]),
+        Some(other) => return Err(format!("Unknown fulfillment_status: {}", other)),
+    }
+    .map(|statuses| statuses.into_iter().map(String::from).collect());
+
+    let risk_level = match params.risk.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(risk) => Some(
+            RiskLevel::parse(risk)
+                .ok_or_else(|| format!("Unknown risk: {} (expected low, medium or high)", risk))?
+                .as_str()
+                .to_string(),
+        ),
+    };
+
+    let limit = if params.all.unwrap_or(false) {
+        None
+    } else {
+        Some(i64::from(params.limit.unwrap_or(ORDERS_DEFAULT_LIMIT).clamp(1, 250)))
+    };
+
+    Ok(StoredOrderFilter {
+        ids,
+        since_id: params.since_id.and_then(|id| i64::try_from(id).ok()),
+        before_id,
+        status,
+        financial_statuses,
+        fulfillment_statuses,
+        created_at_min: parse_filter_timestamp("created_at_min", params.created_at_min.as_deref())?,
+        created_at_max: parse_filter_timestamp("created_at_max", params.created_at_max.as_deref())?,
+        updated_at_min: parse_filter_timestamp("updated_at_min", params.updated_at_min.as_deref())?,
+        updated_at_max: parse_filter_timestamp("updated_at_max", params.updated_at_max.as_deref())?,
+        processed_at_min: parse_filter_timestamp("processed_at_min", params.processed_at_min.as_deref())?,
+        processed_at_max: parse_filter_timestamp("processed_at_max", params.processed_at_max.as_deref())?,
+        risk_level,
+        limit,
+    })
+}
+
+/// The order ID a local `page_info` cursor continues after.
+pub fn parse_local_cursor(page_info: &str) -> Option<i64> {
+    page_info.strip_prefix(LOCAL_CURSOR_PREFIX)?.parse().ok()
+}
+
+pub fn local_cursor(order_id: u64) -> String {
+    format!("{}{}", LOCAL_CURSOR_PREFIX, order_id)
+}
+
+/// A page of `/api/orders` from the local copy, or `None` when the request
+/// has to go to Shopify: the copy is off or not backfilled yet, the caller
+/// asked for `live=true`, or `page_info` is one of Shopify's cursors.
+pub async fn local_orders(
+    state: &AppState,
+    shop: &str,
+    params: &OrderParams,
+) -> AppResult<Option<PaginatedResponse<Vec<Order>>>> {
+    if !state.config.order_sync.enabled
+        || state.config.database.database_url.is_none()
+        || params.live.unwrap_or(false)
+    {
+        return Ok(None);
+    }
+    let before_id = match params.page_info.as_deref() {
+        None => None,
+        Some(page_info) => match parse_local_cursor(page_info) {
+            Some(id) => Some(id),
+            None => return Ok(None),
+        },
+    };
+
+    match state.orders.sync_state(shop).await {
+        Ok(Some(sync)) if sync.backfilled_at.is_some() => {}
+        Ok(_) => return Ok(None),
+        Err(e) => {
+            warn!("Serving orders from Shopify, local copy unavailable: {}", e);
+            return Ok(None);
+        }
+    }
+
+    let filter = stored_order_filter(params, before_id).map_err(AppError::BadRequest)?;
+    let orders: Vec<Order> = state
+        .orders
+        .list_orders(shop, &filter)
+        .await?
+        .into_iter()
+        .filter_map(|data| serde_json::from_value(data).ok())
+        .collect();
+
+    let page_info = match (filter.limit, orders.last()) {
+        (Some(limit), Some(last)) if orders.len() as i64 == limit => Some(PageInfo {
+            has_next_page: true,
+            has_previous_page: before_id.is_some(),
+            next_page_info: Some(local_cursor(last.id)),
+            previous_page_info: None,
+        }),
+        _ => None,
+    };
+
+    Ok(Some(PaginatedResponse { data: orders, page_info }))
+}
+
+// =============================================================================
+// Order Summary
+// =============================================================================
+
+/// `GET /api/orders/summary` — open orders by fulfillment and financial
+/// status, from the local copy only, so dashboards can poll it without
+/// spending Shopify API calls. Test, closed and cancelled orders don't count.
+#[utoipa::path(
+    get,
+    path = "/api/orders/summary",
+    tag = "orders",
+    responses(
+        (status = 200, description = "Open order counts and totals", body = crate::openapi::OrderSummaryResponse),
+        (status = 400, description = "The local order copy is off", body = crate::openapi::ErrorResponse),
+        (status = 409, description = "The local order copy is still being backfilled", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn order_summary_handler(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    if !state.config.order_sync.enabled || state.config.database.database_url.is_none() {
+        return Err(AppError::BadRequest(
+            "The order summary needs a Postgres DATABASE_URL and ORDER_SYNC_INTERVAL_SECS above 0".to_string(),
+        ));
+    }
+    let sync = state.orders.sync_state(shop).await?;
+    if sync.as_ref().is_none_or(|s| s.backfilled_at.is_none()) {
+        return Err(AppError::Conflict(format!("Orders for {} are still being backfilled", shop)));
+    }
+
+    let summary = state.orders.summary(shop).await?;
+    let oldest_unfulfilled_age_secs = summary
+        .oldest_unfulfilled_at
+        .map(|placed_at| (Utc::now() - placed_at).num_seconds().max(0));
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "open_orders": summary.open_orders,
+        "unfulfilled_orders": summary.unfulfilled_orders,
+        "partially_fulfilled_orders": summary.partially_fulfilled_orders,
+        "oldest_unfulfilled_at": summary.oldest_unfulfilled_at,
+        "oldest_unfulfilled_age_secs": oldest_unfulfilled_age_secs,
+        "by_financial_status": summary.by_financial_status,
+        "synced_at": sync.and_then(|s| s.last_synced_at)
+    }))))
+}
+
+// =============================================================================
+// Admin Handlers
+// =============================================================================
+
+/// `GET /admin/shops/{shop}/orders/sync` — how far the shop's order sync got.
+#[utoipa::path(
+    get,
+    path = "/admin/shops/{shop}/orders/sync",
+    tag = "sync",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 200, description = "Order sync progress", body = crate::openapi::OrderSyncStatus),
+    ),
+)]
+pub async fn order_sync_status_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let sync = state.orders.sync_state(&shop).await?;
+    let running = syncing().lock().unwrap().contains(&shop);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "enabled": state.config.order_sync.enabled,
+        "running": running,
+        "serving_locally": sync.as_ref().is_some_and(|s| s.backfilled_at.is_some()),
+        "state": sync
+    }))))
+}
+
+/// `POST /admin/shops/{shop}/orders/sync` — queues a sync instead of waiting
+/// for the next interval.
+#[utoipa::path(
+    post,
+    path = "/admin/shops/{shop}/orders/sync",
+    tag = "sync",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 202, description = "Sync queued", body = crate::openapi::SyncQueued),
+        (status = 404, description = "Shop not installed", body = crate::openapi::ErrorResponse),
+        (status = 409, description = "A sync is already queued or running", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn start_order_sync_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    if !state.config.order_sync.enabled || state.config.database.database_url.is_none() {
+        return Err(AppError::BadRequest(
+            "Order sync needs a Postgres DATABASE_URL and ORDER_SYNC_INTERVAL_SECS above 0".to_string(),
+        ));
+    }
+    state.token_store.get_token(&shop).await?
+        .ok_or_else(|| AppError::NotFound(format!("Shop {} is not installed", shop)))?;
+    let job = state.job_queue.enqueue(SHOP_ORDER_SYNC_JOB, Some(&shop), serde_json::json!({})).await?
+        .ok_or_else(|| AppError::Conflict(format!("An order sync is already queued or running for {}", shop)))?;
+
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({
+        "shop": shop,
+        "queued": true,
+        "job_id": job.id
+    }))))
+}
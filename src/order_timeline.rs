@@ -0,0 +1,228 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    AppState,
+    require_token,
+    database::WebhookEvent,
+    error::{AppResult, ShopifyError},
+    http_client::ShopifyClient,
+};
+
+// API entries this close to a webhook entry of the same kind describe the same event
+const DEDUP_WINDOW_SECONDS: i64 = 600;
+
+// =============================================================================
+// Timeline Structures
+// =============================================================================
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TimelineEntry {
+    pub occurred_at: DateTime<Utc>,
+    pub event: String,
+    pub source: String,
+    pub details: serde_json::Value,
+}
+
+impl TimelineEntry {
+    fn new(occurred_at: DateTime<Utc>, event: &str, source: &str, details: serde_json::Value) -> Self {
+        Self {
+            occurred_at,
+            event: event.to_string(),
+            source: source.to_string(),
+            details,
+        }
+    }
+}
+
+fn parse_timestamp(value: &serde_json::Value) -> Option<DateTime<Utc>> {
+    value
+        .as_str()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+// =============================================================================
+// Timeline Construction
+// =============================================================================
+
+pub fn entries_from_webhooks(events: &[WebhookEvent]) -> Vec<TimelineEntry> {
+    events
+        .iter()
+        .filter_map(|event| {
+            let payload = event.payload.as_ref().unwrap_or(&serde_json::Value::Null);
+            let (kind, timestamp_field) = match event.topic.as_str() {
+                "orders/create" => ("created", "created_at"),
+                "orders/paid" => ("paid", "updated_at"),
+                "orders/fulfilled" => ("fulfilled", "updated_at"),
+                "orders/cancelled" => ("cancelled", "cancelled_at"),
+                "orders/updated" => ("updated", "updated_at"),
+                "refunds/create" => ("refunded", "created_at"),
+                _ => return None,
+            };
+
+            let occurred_at = parse_timestamp(&payload[timestamp_field]).unwrap_or(event.received_at);
+            let details = if kind == "refunded" {
+                serde_json::json!({
+                    "refund_id": payload["id"],
+                    "note": payload["note"],
+                    "webhook_id": event.webhook_id,
+                })
+            } else {
+                serde_json::json!({
+                    "financial_status": payload["financial_status"],
+                    "fulfillment_status": payload["fulfillment_status"],
+                    "total_price": payload["total_price"],
+                    "cancel_reason": payload["cancel_reason"],
+                    "webhook_id": event.webhook_id,
+                })
+            };
+
+            Some(TimelineEntry::new(occurred_at, kind, "webhook", details))
+        })
+        .collect()
+}
+
+pub fn entries_from_order(order: &serde_json::Value) -> Vec<TimelineEntry> {
+    let mut entries = Vec::new();
+
+    if let Some(created_at) = parse_timestamp(&order["created_at"]) {
+        entries.push(TimelineEntry::new(created_at, "created", "api", serde_json::json!({
+            "total_price": order["total_price"],
+            "source_name": order["source_name"],
+        })));
+    }
+
+    let financial_status = order["financial_status"].as_str().unwrap_or_default();
+    if matches!(financial_status, "paid" | "partially_paid" | "partially_refunded" | "refunded") {
+        if let Some(processed_at) = parse_timestamp(&order["processed_at"]) {
+            entries.push(TimelineEntry::new(processed_at, "paid", "api", serde_json::json!({
+                "gateway": order["gateway"],
+            })));
+        }
+    }
+
+    for fulfillment in order["fulfillments"].as_array().into_iter().flatten() {
+        if let Some(created_at) = parse_timestamp(&fulfillment["created_at"]) {
+            entries.push(TimelineEntry::new(created_at, "fulfilled", "api", serde_json::json!({
+                "fulfillment_id": fulfillment["id"],
+                "status": fulfillment["status"],
+                "tracking_number": fulfillment["tracking_number"],
+            })));
+        }
+    }
+
+    for refund in order["refunds"].as_array().into_iter().flatten() {
+        if let Some(created_at) = parse_timestamp(&refund["created_at"]) {
+            entries.push(TimelineEntry::new(created_at, "refunded", "api", serde_json::json!({
+                "refund_id": refund["id"],
+                "note": refund["note"],
+            })));
+        }
+    }
+
+    if let Some(cancelled_at) = parse_timestamp(&order["cancelled_at"]) {
+        entries.push(TimelineEntry::new(cancelled_at, "cancelled", "api", serde_json::json!({
+            "cancel_reason": order["cancel_reason"],
+        })));
+    }
+
+    if let Some(closed_at) = parse_timestamp(&order["closed_at"]) {
+        entries.push(TimelineEntry::new(closed_at, "closed", "api", serde_json::json!({})));
+    }
+
+    entries
+}
+
+/// Merges webhook and API entries chronologically. Webhook entries win when both
+/// sources describe the same event, since they carry the payload Shopify sent.
+pub fn build_timeline(
+    webhook_entries: Vec<TimelineEntry>,
+    api_entries: Vec<TimelineEntry>,
+) -> Vec<TimelineEntry> {
+    let mut timeline = webhook_entries;
+
+    for entry in api_entries {
+        let duplicate = timeline.iter().any(|existing| {
+            existing.source == "webhook"
+                && existing.event == entry.event
+                && (existing.occurred_at - entry.occurred_at).num_seconds().abs() <= DEDUP_WINDOW_SECONDS
+        });
+
+        if !duplicate {
+            timeline.push(entry);
+        }
+    }
+
+    timeline.sort_by_key(|entry| entry.occurred_at);
+    timeline
+}
+
+// =============================================================================
+// Timeline Handler
+// =============================================================================
+
+/// An order's history, merged from captured webhooks and the order itself.
+#[utoipa::path(
+    get,
+    path = "/api/orders/{id}/timeline",
+    tag = "orders",
+    params(("id" = u64, Path, description = "Order ID")),
+    responses(
+        (status = 200, description = "The order's events, oldest first", body = crate::openapi::OrderTimeline),
+    ),
+)]
+pub async fn order_timeline_handler(
+    Path(order_id): Path<u64>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+
+    let token = require_token(&state.token_store, shop).await?;
+
+    let events = state.webhook_events.events_for_resource(shop, order_id as i64).await?;
+    let webhook_entries = entries_from_webhooks(&events);
+
+    let (api_entries, api_error) = match fetch_order(&state.shopify, &token, order_id).await {
+        Ok(order) => (entries_from_order(&order), None),
+        Err(e) => {
+            warn!("Failed to fetch order {} for timeline: {}", order_id, e);
+            // Without captured webhooks there is nothing to fall back on
+            if webhook_entries.is_empty() {
+                return Err(e.into());
+            }
+            (Vec::new(), Some(e.to_string()))
+        }
+    };
+
+    let timeline = build_timeline(webhook_entries, api_entries);
+    info!("Built timeline with {} entries for order {}", timeline.len(), order_id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "order_id": order_id,
+        "entries_count": timeline.len(),
+        "timeline": timeline,
+        "api_error": api_error
+    }))))
+}
+
+async fn fetch_order(
+    client: &ShopifyClient,
+    token: &str,
+    order_id: u64,
+) -> Result<serde_json::Value, ShopifyError> {
+    let response = client
+        .get_with_auth::<serde_json::Value>(&format!("orders/{}.json", order_id), token, None)
+        .await?;
+
+    Ok(response.data["order"].clone())
+}
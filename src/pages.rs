@@ -0,0 +1,166 @@
+//! Server-rendered HTML pages. Templates live in `templates/` and share
+//! `layout.html`; askama HTML-escapes everything interpolated into them, so
+//! shop names and error text can't inject markup.
+
+use askama::Template;
+use axum::{extract::FromRef, response::Html};
+use tracing::error;
+
+use crate::{
+    error::{AppError, AppResult},
+    AppState,
+};
+
+// =============================================================================
+// Branding
+// =============================================================================
+
+/// Name, colour and logo shown on every page.
+#[derive(Debug, Clone)]
+pub struct BrandingConfig {
+    pub app_name: String,
+    /// CSS hex colour for buttons and links, e.g. `#5865f2`
+    pub primary_color: String,
+    pub logo_url: Option<String>,
+}
+
+impl Default for BrandingConfig {
+    fn default() -> Self {
+        Self {
+            app_name: "Shopify OAuth2 Integration Demo".to_string(),
+            primary_color: "#5865f2".to_string(),
+            logo_url: None,
+        }
+    }
+}
+
+impl BrandingConfig {
+    /// Reads `BRAND_NAME`, `BRAND_PRIMARY_COLOR` and `BRAND_LOGO_URL`.
+    pub fn from_env() -> AppResult<Self> {
+        let defaults = Self::default();
+        Ok(Self {
+            app_name: std::env::var("BRAND_NAME")
+                .ok()
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .unwrap_or(defaults.app_name),
+            primary_color: brand_color_setting(std::env::var("BRAND_PRIMARY_COLOR").ok().as_deref())?
+                .unwrap_or(defaults.primary_color),
+            logo_url: brand_logo_setting(std::env::var("BRAND_LOGO_URL").ok().as_deref())?,
+        })
+    }
+}
+
+/// Reads `BRAND_PRIMARY_COLOR`. It lands in a stylesheet, where HTML escaping
+/// doesn't help, so only hex colours are accepted.
+pub fn brand_color_setting(value: Option<&str>) -> AppResult<Option<String>> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        None => Ok(None),
+        Some(color) if is_hex_color(color) => Ok(Some(color.to_string())),
+        Some(color) => Err(AppError::Config(format!(
+            "BRAND_PRIMARY_COLOR must be a hex colour like #5865f2, got {:?}",
+            color
+        ))),
+    }
+}
+
+/// Reads `BRAND_LOGO_URL`: an http(s) URL, or a path on this server.
+pub fn brand_logo_setting(value: Option<&str>) -> AppResult<Option<String>> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        None => Ok(None),
+        Some(url) if url.starts_with("https://") || url.starts_with("http://") || url.starts_with('/') => {
+            Ok(Some(url.to_string()))
+        }
+        Some(_) => Err(AppError::Config(
+            "BRAND_LOGO_URL must be an http(s) URL or a path on this server".to_string(),
+        )),
+    }
+}
+
+fn is_hex_color(value: &str) -> bool {
+    value
+        .strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+impl FromRef<AppState> for BrandingConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.branding.clone()
+    }
+}
+
+// =============================================================================
+// Pages
+// =============================================================================
+
+#[derive(Template)]
+#[template(path = "home.html")]
+pub struct HomePage<'a> {
+    pub brand: &'a BrandingConfig,
+}
+
+/// Shown when the app has been installed on a shop.
+#[derive(Template)]
+#[template(path = "installed.html")]
+pub struct InstalledPage<'a> {
+    pub brand: &'a BrandingConfig,
+    pub shop: &'a str,
+    /// The first few characters of the access token; `None` when a repeated
+    /// callback is answered from the earlier install
+    pub token_preview: Option<&'a str>,
+    pub scopes: &'a str,
+}
+
+/// A failure during installation, with an optional button to start over.
+#[derive(Template)]
+#[template(path = "error.html")]
+pub struct ErrorPage<'a> {
+    pub brand: &'a BrandingConfig,
+    pub title: &'a str,
+    pub heading: &'a str,
+    pub messages: Vec<String>,
+    /// The underlying error, shown after the messages
+    pub detail: Option<String>,
+    /// Label for a button back to `/auth`
+    pub retry: Option<&'a str>,
+}
+
+impl<'a> ErrorPage<'a> {
+    pub fn new(brand: &'a BrandingConfig, title: &'a str, heading: &'a str) -> Self {
+        Self {
+            brand,
+            title,
+            heading,
+            messages: Vec::new(),
+            detail: None,
+            retry: None,
+        }
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.messages.push(message.into());
+        self
+    }
+
+    pub fn with_detail(mut self, detail: impl ToString) -> Self {
+        self.detail = Some(detail.to_string());
+        self
+    }
+
+    pub fn with_retry(mut self, label: &'a str) -> Self {
+        self.retry = Some(label);
+        self
+    }
+}
+
+/// Renders `page`. Templates are checked at compile time, so this only fails
+/// if a value's `Display` does.
+pub fn render(page: &impl Template) -> Html<String> {
+    match page.render() {
+        Ok(html) => Html(html),
+        Err(e) => {
+            error!("Failed to render page: {}", e);
+            Html("<h1>Internal Error</h1>".to_string())
+        }
+    }
+}
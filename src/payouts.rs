@@ -0,0 +1,324 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    require_token,
+    error::{AppResult, ShopifyError},
+    http_client::{next_page_url, PaginatedResponse, ShopifyClient},
+    shopify_api::apply_page_info,
+};
+
+const PAYOUTS_DEFAULT_LIMIT: u32 = 50;
+
+// =============================================================================
+// Shopify Payments Structures
+// =============================================================================
+
+/// Money moved from Shopify Payments to the merchant's bank account.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct Payout {
+    pub id: u64,
+    /// scheduled, in_transit, paid, failed or canceled
+    pub status: String,
+    /// `YYYY-MM-DD` the payout was (or will be) sent
+    pub date: String,
+    pub currency: String,
+    pub amount: Decimal,
+    pub summary: Option<PayoutSummary>,
+}
+
+/// Gross amounts and fees of each kind of transaction in a payout.
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema)]
+#[serde(default)]
+pub struct PayoutSummary {
+    pub adjustments_fee_amount: Decimal,
+    pub adjustments_gross_amount: Decimal,
+    pub charges_fee_amount: Decimal,
+    pub charges_gross_amount: Decimal,
+    pub refunds_fee_amount: Decimal,
+    pub refunds_gross_amount: Decimal,
+    pub reserved_funds_fee_amount: Decimal,
+    pub reserved_funds_gross_amount: Decimal,
+    pub retried_payouts_fee_amount: Decimal,
+    pub retried_payouts_gross_amount: Decimal,
+}
+
+/// One movement of money in the Shopify Payments balance.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct BalanceTransaction {
+    pub id: u64,
+    /// charge, refund, dispute, reserve, adjustment, payout, ...
+    #[serde(rename = "type")]
+    pub transaction_type: String,
+    pub test: bool,
+    pub payout_id: Option<u64>,
+    pub payout_status: Option<String>,
+    pub currency: String,
+    pub amount: Decimal,
+    pub fee: Decimal,
+    pub net: Decimal,
+    pub source_id: Option<u64>,
+    pub source_type: Option<String>,
+    pub source_order_id: Option<u64>,
+    pub source_order_transaction_id: Option<u64>,
+    pub processed_at: String,
+}
+
+/// Funds waiting in Shopify Payments in one currency.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct BalanceAmount {
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+#[derive(Deserialize)]
+pub struct PayoutsResponse {
+    pub payouts: Vec<Payout>,
+}
+
+#[derive(Deserialize)]
+pub struct BalanceTransactionsResponse {
+    pub transactions: Vec<BalanceTransaction>,
+}
+
+#[derive(Deserialize)]
+pub struct BalanceResponse {
+    pub balance: Vec<BalanceAmount>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PayoutParams {
+    pub limit: Option<u32>,
+    pub since_id: Option<u64>,
+    pub last_id: Option<u64>,
+    /// scheduled, in_transit, paid, failed or canceled
+    pub status: Option<String>,
+    /// `YYYY-MM-DD`
+    pub date_min: Option<String>,
+    /// `YYYY-MM-DD`
+    pub date_max: Option<String>,
+    pub page_info: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PayoutTransactionParams {
+    pub limit: Option<u32>,
+    pub page_info: Option<String>,
+}
+
+/// Shopify answers the Shopify Payments endpoints with a 404 for shops that
+/// don't use it, which the handlers report as `shopify_payments: false`
+/// rather than an error.
+fn without_payments<T>(result: Result<T, ShopifyError>) -> Result<Option<T>, ShopifyError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ShopifyError::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// =============================================================================
+// Payout Handlers
+// =============================================================================
+
+/// Lists Shopify Payments payouts, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/payouts",
+    tag = "payouts",
+    params(PayoutParams),
+    responses(
+        (status = 200, description = "A page of payouts, empty for shops without Shopify Payments", body = crate::openapi::PayoutList),
+    ),
+)]
+pub async fn payouts_handler(
+    Query(params): Query<PayoutParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let page = without_payments(fetch_payouts(&state.shopify, &token, &params).await).map_err(|e| {
+        error!("Failed to fetch payouts: {}", e);
+        e
+    })?;
+    let shopify_payments = page.is_some();
+    let page = page.unwrap_or(PaginatedResponse { data: Vec::new(), page_info: None });
+    info!("Successfully fetched {} payouts", page.data.len());
+    let next_page = next_page_url("/api/payouts", page.page_info.as_ref(), params.limit.unwrap_or(PAYOUTS_DEFAULT_LIMIT));
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "shopify_payments": shopify_payments,
+        "payouts_count": page.data.len(),
+        "payouts": page.data,
+        "page_info": page.page_info,
+        "next_page": next_page
+    }))))
+}
+
+/// Lists the balance transactions paid out in one payout, for matching a
+/// bank deposit to the orders, refunds and fees behind it.
+#[utoipa::path(
+    get,
+    path = "/api/payouts/{id}/transactions",
+    tag = "payouts",
+    params(("id" = u64, Path, description = "Payout ID"), PayoutTransactionParams),
+    responses(
+        (status = 200, description = "A page of the payout's transactions", body = crate::openapi::PayoutTransactionList),
+    ),
+)]
+pub async fn payout_transactions_handler(
+    Path(payout_id): Path<u64>,
+    Query(params): Query<PayoutTransactionParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let page = without_payments(fetch_payout_transactions(&state.shopify, &token, payout_id, &params).await)
+        .map_err(|e| {
+            error!("Failed to fetch transactions for payout {}: {}", payout_id, e);
+            e
+        })?;
+    let shopify_payments = page.is_some();
+    let page = page.unwrap_or(PaginatedResponse { data: Vec::new(), page_info: None });
+    info!("Successfully fetched {} transactions for payout {}", page.data.len(), payout_id);
+    let next_page = next_page_url(
+        &format!("/api/payouts/{}/transactions", payout_id),
+        page.page_info.as_ref(),
+        params.limit.unwrap_or(PAYOUTS_DEFAULT_LIMIT),
+    );
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "shopify_payments": shopify_payments,
+        "payout_id": payout_id,
+        "transactions_count": page.data.len(),
+        "transactions": page.data,
+        "page_info": page.page_info,
+        "next_page": next_page
+    }))))
+}
+
+/// The Shopify Payments balance not yet paid out, per currency.
+#[utoipa::path(
+    get,
+    path = "/api/balance",
+    tag = "payouts",
+    responses(
+        (status = 200, description = "The current balance, empty for shops without Shopify Payments", body = crate::openapi::PaymentsBalance),
+    ),
+)]
+pub async fn balance_handler(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let balance = without_payments(
+        state.shopify
+            .get_with_auth::<BalanceResponse>("shopify_payments/balance.json", &token, None)
+            .await
+            .map(|response| response.data.balance),
+    )
+    .map_err(|e| {
+        error!("Failed to fetch the Shopify Payments balance: {}", e);
+        e
+    })?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "shopify_payments": balance.is_some(),
+        "balance": balance.unwrap_or_default()
+    }))))
+}
+
+// =============================================================================
+// Payout Fetch Functions
+// =============================================================================
+
+async fn fetch_payouts(
+    client: &ShopifyClient,
+    token: &str,
+    params: &PayoutParams,
+) -> Result<PaginatedResponse<Vec<Payout>>, ShopifyError> {
+    let mut query_params = vec![("limit", params.limit.unwrap_or(PAYOUTS_DEFAULT_LIMIT).to_string())];
+
+    if let Some(since_id) = params.since_id {
+        query_params.push(("since_id", since_id.to_string()));
+    }
+
+    if let Some(last_id) = params.last_id {
+        query_params.push(("last_id", last_id.to_string()));
+    }
+
+    if let Some(ref status) = params.status {
+        query_params.push(("status", status.clone()));
+    }
+
+    if let Some(ref date_min) = params.date_min {
+        query_params.push(("date_min", date_min.clone()));
+    }
+
+    if let Some(ref date_max) = params.date_max {
+        query_params.push(("date_max", date_max.clone()));
+    }
+
+    apply_page_info(&mut query_params, params.page_info.as_deref());
+
+    let query_params_ref: Vec<(&str, &str)> = query_params.iter()
+        .map(|(k, v)| (k as &str, v as &str))
+        .collect();
+
+    let page = client
+        .get_with_auth::<PayoutsResponse>("shopify_payments/payouts.json", token, Some(&query_params_ref))
+        .await?
+        .into_paginated();
+
+    Ok(PaginatedResponse {
+        data: page.data.payouts,
+        page_info: page.page_info,
+    })
+}
+
+async fn fetch_payout_transactions(
+    client: &ShopifyClient,
+    token: &str,
+    payout_id: u64,
+    params: &PayoutTransactionParams,
+) -> Result<PaginatedResponse<Vec<BalanceTransaction>>, ShopifyError> {
+    let mut query_params = vec![
+        ("limit", params.limit.unwrap_or(PAYOUTS_DEFAULT_LIMIT).to_string()),
+        ("payout_id", payout_id.to_string()),
+    ];
+
+    apply_page_info(&mut query_params, params.page_info.as_deref());
+
+    let query_params_ref: Vec<(&str, &str)> = query_params.iter()
+        .map(|(k, v)| (k as &str, v as &str))
+        .collect();
+
+    let page = client
+        .get_with_auth::<BalanceTransactionsResponse>(
+            "shopify_payments/balance/transactions.json",
+            token,
+            Some(&query_params_ref),
+        )
+        .await?
+        .into_paginated();
+
+    Ok(PaginatedResponse {
+        data: page.data.transactions,
+        page_info: page.page_info,
+    })
+}
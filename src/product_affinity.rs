@@ -0,0 +1,345 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::{pin_mut, TryStreamExt};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::str::FromStr;
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    require_token,
+    csv_response::{csv_response, export_file_name, wants_csv, CsvRecord},
+    database::MirroredLineItem,
+    error::{AppError, AppResult},
+    shopify_api::{Order, OrdersResponse},
+};
+
+// =============================================================================
+// Product Affinity Structures
+// =============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProductAffinityParams {
+    /// Defaults to 90 days ago
+    pub created_at_min: Option<String>,
+    pub created_at_max: Option<String>,
+    /// Re-sync the order line item mirror from Shopify for the window first
+    pub refresh: Option<bool>,
+    /// Orders a pair must appear in together to be reported (default 2)
+    pub min_orders: Option<usize>,
+    /// Maximum pairs returned (default 50)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct ProductSales {
+    pub product_id: i64,
+    pub title: String,
+    pub orders: usize,
+    pub units: i64,
+    pub revenue: Decimal,
+}
+
+/// Two products bought in the same order. `confidence` is the share of
+/// orders containing the first product that also contain the second, and
+/// the other way round.
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct ProductPair {
+    pub product_ids: [i64; 2],
+    pub titles: [String; 2],
+    pub orders: usize,
+    /// Share of all analyzed orders containing both products
+    pub support: f64,
+    pub confidence: [f64; 2],
+    /// How much more often the pair occurs than if the products were
+    /// bought independently; above 1 means they go together
+    pub lift: f64,
+}
+
+impl CsvRecord for ProductPair {
+    fn csv_headers() -> Vec<&'static str> {
+        vec![
+            "product_id", "title", "other_product_id", "other_title", "orders", "support", "confidence",
+            "other_confidence", "lift",
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.product_ids[0].to_string(),
+            self.titles[0].clone(),
+            self.product_ids[1].to_string(),
+            self.titles[1].clone(),
+            self.orders.to_string(),
+            self.support.to_string(),
+            self.confidence[0].to_string(),
+            self.confidence[1].to_string(),
+            self.lift.to_string(),
+        ]
+    }
+}
+
+// =============================================================================
+// Affinity Calculations
+// =============================================================================
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        return 0.0;
+    }
+    (numerator as f64 / denominator as f64 * 10_000.0).round() / 10_000.0
+}
+
+/// Distinct products in each order. Custom line items without a product are ignored.
+fn products_by_order(line_items: &[MirroredLineItem]) -> BTreeMap<i64, BTreeSet<i64>> {
+    let mut orders: BTreeMap<i64, BTreeSet<i64>> = BTreeMap::new();
+    for item in line_items {
+        if let Some(product_id) = item.product_id {
+            orders.entry(item.order_id).or_default().insert(product_id);
+        }
+    }
+    orders
+}
+
+fn product_titles(line_items: &[MirroredLineItem]) -> HashMap<i64, String> {
+    line_items
+        .iter()
+        .filter_map(|item| item.product_id.map(|id| (id, item.title.clone())))
+        .collect()
+}
+
+/// Revenue, units, and order count per product, highest revenue first.
+pub fn product_sales(line_items: &[MirroredLineItem]) -> Vec<ProductSales> {
+    let titles = product_titles(line_items);
+    let mut sales: BTreeMap<i64, ProductSales> = BTreeMap::new();
+    let mut orders: BTreeMap<i64, BTreeSet<i64>> = BTreeMap::new();
+
+    for item in line_items {
+        let Some(product_id) = item.product_id else { continue };
+        let entry = sales.entry(product_id).or_insert_with(|| ProductSales {
+            product_id,
+            title: titles.get(&product_id).cloned().unwrap_or_default(),
+            orders: 0,
+            units: 0,
+            revenue: Decimal::ZERO,
+        });
+        entry.units += i64::from(item.quantity);
+        entry.revenue += item.revenue();
+        orders.entry(product_id).or_default().insert(item.order_id);
+    }
+
+    let mut sales: Vec<ProductSales> = sales
+        .into_values()
+        .map(|mut product| {
+            product.orders = orders.get(&product.product_id).map_or(0, BTreeSet::len);
+            product
+        })
+        .collect();
+    sales.sort_by(|a, b| b.revenue.cmp(&a.revenue).then(a.product_id.cmp(&b.product_id)));
+    sales
+}
+
+/// Products frequently bought together, most common pairs first.
+pub fn product_pairs(line_items: &[MirroredLineItem], min_orders: usize) -> Vec<ProductPair> {
+    let titles = product_titles(line_items);
+    let orders = products_by_order(line_items);
+
+    let mut product_orders: HashMap<i64, usize> = HashMap::new();
+    let mut pair_orders: BTreeMap<(i64, i64), usize> = BTreeMap::new();
+    for products in orders.values() {
+        let products: Vec<i64> = products.iter().copied().collect();
+        for (i, &a) in products.iter().enumerate() {
+            *product_orders.entry(a).or_default() += 1;
+            for &b in &products[i + 1..] {
+                *pair_orders.entry((a, b)).or_default() += 1;
+            }
+        }
+    }
+
+    let total = orders.len();
+    let mut pairs: Vec<ProductPair> = pair_orders
+        .into_iter()
+        .filter(|&(_, together)| together >= min_orders.max(1))
+        .map(|((a, b), together)| {
+            let (orders_a, orders_b) = (product_orders[&a], product_orders[&b]);
+            let lift = if orders_a == 0 || orders_b == 0 {
+                0.0
+            } else {
+                let lift = (together * total) as f64 / (orders_a * orders_b) as f64;
+                (lift * 10_000.0).round() / 10_000.0
+            };
+
+            ProductPair {
+                product_ids: [a, b],
+                titles: [
+                    titles.get(&a).cloned().unwrap_or_default(),
+                    titles.get(&b).cloned().unwrap_or_default(),
+                ],
+                orders: together,
+                support: ratio(together, total),
+                confidence: [ratio(together, orders_a), ratio(together, orders_b)],
+                lift,
+            }
+        })
+        .collect();
+
+    pairs.sort_by(|a, b| {
+        b.orders
+            .cmp(&a.orders)
+            .then(b.lift.total_cmp(&a.lift))
+            .then(a.product_ids.cmp(&b.product_ids))
+    });
+    pairs
+}
+
+// =============================================================================
+// Order Line Item Mirror Sync
+// =============================================================================
+
+fn parse_timestamp(name: &str, value: &str) -> AppResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| AppError::BadRequest(format!("{} must be an RFC 3339 timestamp", name)))
+}
+
+/// Line items worth mirroring from an order; cancelled and test orders have none.
+pub fn mirrored_line_items(order: &Order) -> Option<Vec<MirroredLineItem>> {
+    let order_created_at = DateTime::parse_from_rfc3339(&order.created_at).ok()?.with_timezone(&Utc);
+    if order.cancelled_at.is_some() || order.test {
+        return Some(Vec::new());
+    }
+
+    let amount = |value: &str| Decimal::from_str(value.trim()).unwrap_or_default();
+    Some(
+        order.line_items
+            .iter()
+            .map(|item| MirroredLineItem {
+                order_id: order.id as i64,
+                line_item_id: item.id as i64,
+                order_created_at,
+                product_id: item.product_id.map(|id| id as i64),
+                variant_id: item.variant_id.map(|id| id as i64),
+                title: item.title.clone(),
+                sku: item.sku.clone(),
+                quantity: item.quantity,
+                price: amount(&item.price),
+                total_discount: amount(item.total_discount.as_deref().unwrap_or_default()),
+            })
+            .collect(),
+    )
+}
+
+async fn sync_order_mirror(
+    state: &AppState,
+    shop: &str,
+    created_at_min: &str,
+    created_at_max: Option<&str>,
+) -> AppResult<serde_json::Value> {
+    let token = require_token(&state.token_store, shop).await?;
+
+    let mut query_params = vec![
+        ("limit".to_string(), "250".to_string()),
+        ("status".to_string(), "any".to_string()),
+        ("created_at_min".to_string(), created_at_min.to_string()),
+        ("fields".to_string(), "id,created_at,cancelled_at,test,line_items".to_string()),
+    ];
+    if let Some(max) = created_at_max {
+        query_params.push(("created_at_max".to_string(), max.to_string()));
+    }
+
+    let pages = state.shopify.get_all_pages::<OrdersResponse>("orders.json", &token, query_params);
+    pin_mut!(pages);
+
+    let (mut orders, mut line_items_written) = (0, 0);
+    while let Some(page) = pages.try_next().await? {
+        let mut order_ids = Vec::new();
+        let mut line_items = Vec::new();
+        for order in &page.orders {
+            match mirrored_line_items(order) {
+                Some(items) => {
+                    order_ids.push(order.id as i64);
+                    line_items.extend(items);
+                }
+                None => warn!("Skipping order {} with unreadable created_at {}", order.id, order.created_at),
+            }
+        }
+
+        orders += order_ids.len();
+        line_items_written += state.order_mirror.replace_orders(shop, &order_ids, &line_items).await?;
+    }
+
+    Ok(serde_json::json!({
+        "orders": orders,
+        "line_items": line_items_written
+    }))
+}
+
+// =============================================================================
+// Product Affinity Handler
+// =============================================================================
+
+/// Frequently-bought-together pairs and per-product revenue, computed from
+/// the order line item mirror. `Accept: text/csv` streams just the pairs.
+#[utoipa::path(
+    get,
+    path = "/api/reports/product-affinity",
+    tag = "reports",
+    params(ProductAffinityParams),
+    responses(
+        (status = 200, description = "Product pairs and per-product sales for the window", content(
+            (crate::openapi::ProductAffinity = "application/json"),
+            (String = "text/csv"),
+        )),
+        (status = 400, description = "Unreadable timestamp", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn product_affinity_handler(
+    Query(params): Query<ProductAffinityParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    let shop = &state.config.shop;
+
+    let created_at_min = params.created_at_min.clone()
+        .unwrap_or_else(|| (Utc::now() - chrono::Duration::days(90)).to_rfc3339());
+    let window_min = parse_timestamp("created_at_min", &created_at_min)?;
+    let window_max = params.created_at_max.as_deref()
+        .map(|max| parse_timestamp("created_at_max", max))
+        .transpose()?;
+
+    let sync = if params.refresh.unwrap_or(false) {
+        Some(sync_order_mirror(&state, shop, &created_at_min, params.created_at_max.as_deref()).await?)
+    } else {
+        None
+    };
+
+    let line_items = state.order_mirror.line_items_between(shop, window_min, window_max).await?;
+    let orders_analyzed = products_by_order(&line_items).len();
+    let products = product_sales(&line_items);
+    let mut pairs = product_pairs(&line_items, params.min_orders.unwrap_or(2));
+    pairs.truncate(params.limit.unwrap_or(50));
+    info!("🛍️ Product affinity for {}: {} pairs across {} orders", shop, pairs.len(), orders_analyzed);
+
+    if wants_csv(&headers) {
+        return Ok(csv_response(&export_file_name("product-affinity"), pairs));
+    }
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "created_at_min": created_at_min,
+        "created_at_max": params.created_at_max,
+        "sync": sync,
+        "orders_analyzed": orders_analyzed,
+        "products": products,
+        "pairs_count": pairs.len(),
+        "pairs": pairs
+    }))).into_response())
+}
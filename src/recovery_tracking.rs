@@ -0,0 +1,558 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    csv_response::{csv_response, export_file_name, wants_csv, CsvRecord},
+    database::{RecoveryCounts, RecoveryMessage, RecoveryMessageStore},
+    domain_events::{DomainEvent, DomainEventSubscriber},
+    error::{AppError, AppResult},
+    key_provider::derive_purpose_key,
+    mailer::{render_email, RecoveryEmail},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// 1x1 transparent GIF served by the open pixel
+const PIXEL_GIF: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+// =============================================================================
+// Recovery Tracking Configuration
+// =============================================================================
+
+#[derive(Clone)]
+pub struct RecoveryTrackingConfig {
+    /// Public origin tracking links point at, e.g. `https://app.example.com`
+    pub base_url: String,
+    pub signing_secret: Secret<String>,
+}
+
+impl RecoveryTrackingConfig {
+    /// `TRACKING_BASE_URL` defaults to the origin of `redirect_uri` and
+    /// `RECOVERY_TRACKING_SECRET` to a key derived from `fallback_secret`.
+    pub fn from_env(fallback_secret: &str, redirect_uri: &str) -> Self {
+        let base_url = std::env::var("TRACKING_BASE_URL").unwrap_or_else(|_| {
+            reqwest::Url::parse(redirect_uri)
+                .map(|url| url.origin().ascii_serialization())
+                .unwrap_or_default()
+        });
+
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            signing_secret: std::env::var("RECOVERY_TRACKING_SECRET")
+                .map(Secret::new)
+                .unwrap_or_else(|_| derive_purpose_key(fallback_secret, "recovery-tracking")),
+        }
+    }
+}
+
+// =============================================================================
+// Tracking Links
+// =============================================================================
+
+/// What a tracking token stands for. Tokens carry the shop so events can be
+/// routed to the shop's database without a lookup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackingLink {
+    Open { shop: String, message_id: Uuid },
+    Click { shop: String, message_id: Uuid, url: String },
+}
+
+impl TrackingLink {
+    fn encode(&self) -> String {
+        match self {
+            Self::Open { shop, message_id } => format!("open\n{}\n{}", shop, message_id),
+            Self::Click { shop, message_id, url } => format!("click\n{}\n{}\n{}", shop, message_id, url),
+        }
+    }
+
+    fn decode(payload: &str) -> Option<Self> {
+        let mut parts = payload.splitn(4, '\n');
+        let (kind, shop, message_id) = (parts.next()?, parts.next()?, parts.next()?);
+        let (shop, message_id) = (shop.to_string(), message_id.parse().ok()?);
+
+        match (kind, parts.next()) {
+            ("open", None) => Some(Self::Open { shop, message_id }),
+            ("click", Some(url)) => Some(Self::Click { shop, message_id, url: url.to_string() }),
+            _ => None,
+        }
+    }
+}
+
+/// Signs tracking tokens as `base64url(payload).hex(hmac(payload))`, so click
+/// links can't be turned into open redirects and events can't be forged.
+/// Tokens don't expire; recovery emails get opened weeks later.
+#[derive(Clone)]
+pub struct TrackingSigner {
+    secret: Secret<String>,
+}
+
+impl TrackingSigner {
+    pub fn new(config: &RecoveryTrackingConfig) -> Self {
+        Self { secret: config.signing_secret.clone() }
+    }
+
+    fn mac(&self, payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    pub fn sign(&self, link: &TrackingLink) -> String {
+        let payload = link.encode();
+        let signature = hex::encode(self.mac(&payload).finalize().into_bytes());
+        format!("{}.{}", general_purpose::URL_SAFE_NO_PAD.encode(&payload), signature)
+    }
+
+    /// Returns the link if the token is authentic.
+    pub fn verify(&self, token: &str) -> Option<TrackingLink> {
+        let (encoded, signature) = token.split_once('.')?;
+        let payload = String::from_utf8(general_purpose::URL_SAFE_NO_PAD.decode(encoded).ok()?).ok()?;
+
+        // Constant-time comparison
+        self.mac(&payload).verify_slice(&hex::decode(signature).ok()?).ok()?;
+        TrackingLink::decode(&payload)
+    }
+}
+
+// =============================================================================
+// Deliverability Metrics
+// =============================================================================
+
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct RecoveryDeliverability {
+    pub sent: i64,
+    pub delivered: i64,
+    pub opened: i64,
+    pub clicked: i64,
+    pub bounced: i64,
+    pub converted: i64,
+    /// Opens per delivered message
+    pub open_rate: f64,
+    /// Clicks per delivered message
+    pub click_rate: f64,
+    /// Clicks per opened message
+    pub click_to_open_rate: f64,
+    /// Bounces per sent message
+    pub bounce_rate: f64,
+    /// Recovered checkouts per sent message
+    pub conversion_rate: f64,
+}
+
+impl CsvRecord for RecoveryDeliverability {
+    fn csv_headers() -> Vec<&'static str> {
+        vec![
+            "sent", "delivered", "opened", "clicked", "bounced", "converted", "open_rate", "click_rate",
+            "click_to_open_rate", "bounce_rate", "conversion_rate",
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.sent.to_string(),
+            self.delivered.to_string(),
+            self.opened.to_string(),
+            self.clicked.to_string(),
+            self.bounced.to_string(),
+            self.converted.to_string(),
+            self.open_rate.to_string(),
+            self.click_rate.to_string(),
+            self.click_to_open_rate.to_string(),
+            self.bounce_rate.to_string(),
+            self.conversion_rate.to_string(),
+        ]
+    }
+}
+
+fn rate(numerator: i64, denominator: i64) -> f64 {
+    if denominator <= 0 {
+        return 0.0;
+    }
+    (numerator as f64 / denominator as f64 * 10_000.0).round() / 10_000.0
+}
+
+impl From<RecoveryCounts> for RecoveryDeliverability {
+    fn from(counts: RecoveryCounts) -> Self {
+        let delivered = counts.sent - counts.bounced;
+        Self {
+            sent: counts.sent,
+            delivered,
+            opened: counts.opened,
+            clicked: counts.clicked,
+            bounced: counts.bounced,
+            converted: counts.converted,
+            open_rate: rate(counts.opened, delivered),
+            click_rate: rate(counts.clicked, delivered),
+            click_to_open_rate: rate(counts.clicked, counts.opened),
+            bounce_rate: rate(counts.bounced, counts.sent),
+            conversion_rate: rate(counts.converted, counts.sent),
+        }
+    }
+}
+
+// =============================================================================
+// Recovery Message Structures
+// =============================================================================
+
+/// Body for `POST /api/recovery/messages`, sent by whatever delivers the email
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RecordSendRequest {
+    pub checkout_id: u64,
+    pub recipient: String,
+    /// Where the click link should land, usually the checkout's `abandoned_checkout_url`
+    pub recovery_url: String,
+    pub subject: Option<String>,
+}
+
+impl RecordSendRequest {
+    pub fn validate(&self) -> AppResult<()> {
+        if !self.recipient.contains('@') {
+            return Err(AppError::BadRequest("recipient must be an email address".to_string()));
+        }
+
+        validate_send(&self.recipient, &self.recovery_url)
+    }
+}
+
+/// Body for `POST /api/recovery/emails`, which renders and sends the email itself
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SendRecoveryEmailRequest {
+    pub checkout_id: u64,
+    pub recipient: String,
+    /// Where the button should land, usually the checkout's `abandoned_checkout_url`
+    pub recovery_url: String,
+    /// Defaults to "You left something in your cart"
+    pub subject: Option<String>,
+    /// Greets the customer by name when set
+    pub first_name: Option<String>,
+    /// Defaults to the shop's domain
+    pub shop_name: Option<String>,
+}
+
+impl SendRecoveryEmailRequest {
+    pub fn validate(&self) -> AppResult<()> {
+        validate_send(&self.recipient, &self.recovery_url)
+    }
+}
+
+fn validate_send(recipient: &str, recovery_url: &str) -> AppResult<()> {
+    if !recipient.contains('@') {
+        return Err(AppError::BadRequest("recipient must be an email address".to_string()));
+    }
+
+    match reqwest::Url::parse(recovery_url) {
+        Ok(url) if matches!(url.scheme(), "https" | "http") => Ok(()),
+        _ => Err(AppError::BadRequest("recovery_url must be an http(s) URL".to_string())),
+    }
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct BounceRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliverabilityParams {
+    /// Defaults to 30 days ago
+    pub since: Option<DateTime<Utc>>,
+    /// Defaults to now
+    pub until: Option<DateTime<Utc>>,
+}
+
+// =============================================================================
+// Recovery Tracking Handlers
+// =============================================================================
+
+/// Records that a recovery email went out and returns the tracking pixel and
+/// click URLs to put in it.
+#[utoipa::path(
+    post,
+    path = "/api/recovery/messages",
+    tag = "recovery",
+    request_body = RecordSendRequest,
+    responses(
+        (status = 201, description = "The recorded message with its tracking URLs", body = crate::openapi::RecoveryMessageSent),
+        (status = 400, description = "Bad recipient or recovery URL", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn record_recovery_send_handler(
+    State(state): State<AppState>,
+    Json(request): Json<RecordSendRequest>,
+) -> AppResult<impl IntoResponse> {
+    request.validate()?;
+    let shop = &state.config.shop;
+
+    let message = state.recovery_messages
+        .record_send(shop, request.checkout_id as i64, &request.recipient, request.subject.as_deref())
+        .await?;
+    let (open_pixel_url, click_url) = tracking_urls(&state, &message, request.recovery_url);
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({
+        "success": true,
+        "message": message,
+        "open_pixel_url": open_pixel_url,
+        "click_url": click_url
+    }))))
+}
+
+/// The open pixel and click URLs for a message.
+fn tracking_urls(state: &AppState, message: &RecoveryMessage, recovery_url: String) -> (String, String) {
+    let shop = &message.shop_domain;
+    let signer = TrackingSigner::new(&state.config.recovery_tracking);
+    let base_url = &state.config.recovery_tracking.base_url;
+    let open_token = signer.sign(&TrackingLink::Open { shop: shop.clone(), message_id: message.id });
+    let click_token = signer.sign(&TrackingLink::Click {
+        shop: shop.clone(),
+        message_id: message.id,
+        url: recovery_url,
+    });
+
+    (
+        format!("{}/recovery/open/{}.gif", base_url, open_token),
+        format!("{}/recovery/click/{}", base_url, click_token),
+    )
+}
+
+/// Sends a recovery email through the configured mail provider, with the
+/// tracking pixel and click link already in it.
+#[utoipa::path(
+    post,
+    path = "/api/recovery/emails",
+    tag = "recovery",
+    request_body = SendRecoveryEmailRequest,
+    responses(
+        (status = 201, description = "The sent message with its tracking URLs", body = crate::openapi::RecoveryEmailSent),
+        (status = 400, description = "Bad recipient or recovery URL, or no mail provider configured", body = crate::openapi::ErrorResponse),
+        (status = 502, description = "The mail provider refused the email", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn send_recovery_email_handler(
+    State(state): State<AppState>,
+    Json(request): Json<SendRecoveryEmailRequest>,
+) -> AppResult<impl IntoResponse> {
+    request.validate()?;
+    let Some(ref email) = state.email else {
+        return Err(AppError::BadRequest("No MAIL_PROVIDER is configured".to_string()));
+    };
+    let shop = &state.config.shop;
+    let subject = request.subject.as_deref().unwrap_or("You left something in your cart");
+
+    let message = state.recovery_messages
+        .record_send(shop, request.checkout_id as i64, &request.recipient, Some(subject))
+        .await?;
+    let (open_pixel_url, click_url) = tracking_urls(&state, &message, request.recovery_url);
+
+    let template = RecoveryEmail {
+        brand: email.brand(),
+        subject,
+        shop_name: request.shop_name.as_deref().unwrap_or(shop),
+        first_name: request.first_name.as_deref(),
+        click_url: &click_url,
+        open_pixel_url: &open_pixel_url,
+    };
+    let sent = match render_email(&template) {
+        Ok(html) => email.send(shop, "recovery", &request.recipient, subject, html, template.text()).await,
+        Err(e) => Err(e),
+    };
+    let provider_message_id = match sent {
+        Ok(id) => id,
+        Err(e) => {
+            // It never went out, so it mustn't count towards deliverability
+            if let Err(delete_error) = state.recovery_messages.delete_message(shop, message.id).await {
+                warn!("Failed to remove unsent recovery message {}: {}", message.id, delete_error);
+            }
+            return Err(e);
+        }
+    };
+    info!("📧 Sent recovery email {} for checkout {}", message.id, request.checkout_id);
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({
+        "success": true,
+        "message": message,
+        "provider_message_id": provider_message_id,
+        "open_pixel_url": open_pixel_url,
+        "click_url": click_url
+    }))))
+}
+
+/// Marks a message as bounced, as reported by the email provider.
+#[utoipa::path(
+    post,
+    path = "/api/recovery/messages/{id}/bounce",
+    tag = "recovery",
+    params(("id" = Uuid, Path, description = "Recovery message ID")),
+    request_body = BounceRequest,
+    responses(
+        (status = 200, description = "The bounced message", body = crate::openapi::RecoveryMessageChange),
+        (status = 404, description = "No such message", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn record_recovery_bounce_handler(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    Json(request): Json<BounceRequest>,
+) -> AppResult<impl IntoResponse> {
+    let message = state.recovery_messages
+        .record_bounce(&state.config.shop, id, request.reason.as_deref())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Recovery message {} not found", id)))?;
+    info!("📭 Recovery message {} bounced: {}", id, message.bounce_reason.as_deref().unwrap_or("no reason given"));
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "message": message
+    }))))
+}
+
+/// How recovery emails sent in a window performed, as one CSV row with
+/// `Accept: text/csv`.
+#[utoipa::path(
+    get,
+    path = "/api/recovery/deliverability",
+    tag = "recovery",
+    params(DeliverabilityParams),
+    responses(
+        (status = 200, description = "Open, click, bounce and conversion rates for the window", content(
+            (crate::openapi::RecoveryDeliverabilityReport = "application/json"),
+            (String = "text/csv"),
+        )),
+        (status = 400, description = "`since` not before `until`", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn recovery_deliverability_handler(
+    Query(params): Query<DeliverabilityParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    let shop = &state.config.shop;
+    let until = params.until.unwrap_or_else(Utc::now);
+    let since = params.since.unwrap_or(until - chrono::Duration::days(30));
+    if since >= until {
+        return Err(AppError::BadRequest("since must be before until".to_string()));
+    }
+
+    let deliverability = RecoveryDeliverability::from(state.recovery_messages.counts(shop, since, until).await?);
+
+    if wants_csv(&headers) {
+        return Ok(csv_response(&export_file_name("recovery-deliverability"), vec![deliverability]));
+    }
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "since": since,
+        "until": until,
+        "deliverability": deliverability
+    }))).into_response())
+}
+
+/// Tracking pixel. Always returns the image so a bad token never shows a
+/// broken image in someone's inbox.
+#[utoipa::path(
+    get,
+    path = "/recovery/open/{token}",
+    tag = "recovery",
+    params(("token" = String, Path, description = "Signed tracking token")),
+    responses(
+        (status = 200, description = "A 1x1 transparent GIF", content_type = "image/gif"),
+    ),
+)]
+pub async fn recovery_open_handler(
+    Path(token): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    let token = token.trim_end_matches(".gif");
+    match TrackingSigner::new(&state.config.recovery_tracking).verify(token) {
+        Some(TrackingLink::Open { shop, message_id }) => {
+            if let Err(e) = state.recovery_messages.record_open(&shop, message_id).await {
+                warn!("Failed to record open for recovery message {}: {}", message_id, e);
+            }
+        }
+        _ => warn!("Ignoring open with an invalid tracking token"),
+    }
+
+    (
+        [
+            (header::CONTENT_TYPE, "image/gif"),
+            (header::CACHE_CONTROL, "no-store, no-cache, must-revalidate, max-age=0"),
+        ],
+        PIXEL_GIF,
+    ).into_response()
+}
+
+/// Click redirect. The destination comes only from the signed token.
+#[utoipa::path(
+    get,
+    path = "/recovery/click/{token}",
+    tag = "recovery",
+    params(("token" = String, Path, description = "Signed tracking token")),
+    responses(
+        (status = 303, description = "Redirect to the recovery URL"),
+        (status = 400, description = "Invalid tracking link", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn recovery_click_handler(
+    Path(token): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<Response> {
+    let Some(TrackingLink::Click { shop, message_id, url }) =
+        TrackingSigner::new(&state.config.recovery_tracking).verify(&token)
+    else {
+        return Err(AppError::BadRequest("Invalid tracking link".to_string()));
+    };
+
+    // A failed write shouldn't stop the customer getting to their checkout
+    if let Err(e) = state.recovery_messages.record_click(&shop, message_id).await {
+        warn!("Failed to record click for recovery message {}: {}", message_id, e);
+    }
+
+    Ok(Redirect::to(&url).into_response())
+}
+
+// =============================================================================
+// Conversions
+// =============================================================================
+
+/// Credits the recovery emails sent for a checkout with the order later
+/// placed from it.
+pub struct RecoveryConversions {
+    messages: RecoveryMessageStore,
+}
+
+impl RecoveryConversions {
+    pub fn new(messages: RecoveryMessageStore) -> Self {
+        Self { messages }
+    }
+}
+
+#[async_trait]
+impl DomainEventSubscriber for RecoveryConversions {
+    fn name(&self) -> &str {
+        "recovery conversions"
+    }
+
+    async fn handle(&self, shop_domain: &str, event: &DomainEvent) -> AppResult<()> {
+        if let DomainEvent::OrderCreated { order_id, checkout_id: Some(checkout_id), .. } = event {
+            self.messages.record_conversion(shop_domain, *checkout_id as i64, *order_id as i64).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,481 @@
+use axum::{
+    body::{to_bytes, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SubsecRound, Utc};
+use redis::AsyncCommands;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+
+use crate::csv_response::{negotiated_format, ListFormat};
+
+/// `hit` on responses served from the cache, `miss` on ones that were just stored.
+pub const CACHE_STATUS_HEADER: &str = "x-cache";
+
+/// Larger responses (a full page of products with variants) are passed through uncached.
+const MAX_CACHED_BODY_BYTES: usize = 1024 * 1024;
+
+/// How long a cache lookup waits on Redis before answering from Shopify.
+const REDIS_TIMEOUT: Duration = Duration::from_millis(250);
+
+const ENTRY_KEY_PREFIX: &str = "response_cache:";
+const GENERATION_KEY_PREFIX: &str = "response_cache_generation:";
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct ResponseCacheConfig {
+    /// How long a response is served from the cache; zero turns the cache off
+    pub ttl: Duration,
+    /// Entries kept in memory before the ones closest to expiring are dropped
+    pub max_entries: usize,
+    pub redis_url: Option<String>,
+    /// Share entries and invalidations across instances through Redis
+    pub use_redis: bool,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::ZERO,
+            max_entries: 1000,
+            redis_url: None,
+            use_redis: false,
+        }
+    }
+}
+
+impl ResponseCacheConfig {
+    /// `RESPONSE_CACHE_TTL_SECONDS` (off by default), `RESPONSE_CACHE_MAX_ENTRIES`
+    /// and `RESPONSE_CACHE_REDIS`, which uses `REDIS_URL`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            ttl: std::env::var("RESPONSE_CACHE_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.ttl),
+            max_entries: std::env::var("RESPONSE_CACHE_MAX_ENTRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.max_entries),
+            redis_url: std::env::var("REDIS_URL").ok().filter(|url| !url.is_empty()),
+            use_redis: std::env::var("RESPONSE_CACHE_REDIS")
+                .map(|v| v.trim().eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+}
+
+// =============================================================================
+// Cache Groups
+// =============================================================================
+
+/// The resource a cached route reads. Entries are invalidated a group at a
+/// time, by the group's webhooks or a successful write on one of its routes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheGroup {
+    Shop,
+    Orders,
+    Products,
+    Customers,
+    Checkouts,
+    Inventory,
+    Locations,
+}
+
+impl CacheGroup {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Shop => "shop",
+            Self::Orders => "orders",
+            Self::Products => "products",
+            Self::Customers => "customers",
+            Self::Checkouts => "checkouts",
+            Self::Inventory => "inventory",
+            Self::Locations => "locations",
+        }
+    }
+
+    /// The group a webhook topic changes. Shop, inventory and location reads
+    /// have no webhooks here and only expire.
+    pub fn for_topic(topic: &str) -> Option<Self> {
+        match topic.split('/').next()? {
+            "orders" | "refunds" => Some(Self::Orders),
+            "products" => Some(Self::Products),
+            "customers" => Some(Self::Customers),
+            "checkouts" => Some(Self::Checkouts),
+            "inventory_levels" => Some(Self::Inventory),
+            _ => None,
+        }
+    }
+}
+
+/// Path and query an entry is filed under, with query parameters sorted so
+/// `?a=1&b=2` and `?b=2&a=1` share it.
+pub fn cache_path(uri: &Uri) -> String {
+    match uri.query() {
+        Some(query) if !query.is_empty() => {
+            let mut params: Vec<&str> = query.split('&').filter(|p| !p.is_empty()).collect();
+            params.sort_unstable();
+            format!("{}?{}", uri.path(), params.join("&"))
+        }
+        _ => uri.path().to_string(),
+    }
+}
+
+// =============================================================================
+// Cache Store
+// =============================================================================
+
+/// A JSON response body with the validators clients revalidate it with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedResponse {
+    pub content_type: String,
+    pub body: Bytes,
+    /// Strong `ETag`, from a hash of the body
+    pub etag: String,
+    /// When the body was fetched, sent as `Last-Modified` once it's cached
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl CachedResponse {
+    pub fn new(content_type: &str, body: Bytes) -> Self {
+        Self {
+            content_type: content_type.to_string(),
+            etag: etag_for(&body),
+            body,
+            fetched_at: Utc::now().trunc_subsecs(0),
+        }
+    }
+}
+
+/// `"<first 32 hex digits of the body's SHA-256>"`
+pub fn etag_for(body: &[u8]) -> String {
+    format!("\"{}\"", &hex::encode(Sha256::digest(body))[..32])
+}
+
+#[derive(Default)]
+struct MemoryCache {
+    generations: HashMap<(String, CacheGroup), u64>,
+    entries: HashMap<String, (Instant, CachedResponse)>,
+}
+
+/// Successful JSON reads, by shop, group and path, for `RESPONSE_CACHE_TTL_SECONDS`.
+///
+/// Each shop and group has a generation that invalidation bumps; entries are
+/// filed under the generation current when their request started, so a
+/// response that raced an invalidation is never served. Entries live in this
+/// instance's memory, or in Redis with `RESPONSE_CACHE_REDIS` so every
+/// instance sees the same entries and invalidations. Redis failures are
+/// logged and the request goes to Shopify.
+#[derive(Clone)]
+pub struct ResponseCache {
+    config: ResponseCacheConfig,
+    redis_client: Option<redis::Client>,
+    redis_conn: Arc<tokio::sync::OnceCell<redis::aio::ConnectionManager>>,
+    memory: Arc<Mutex<MemoryCache>>,
+}
+
+fn entry_key(shop: &str, group: CacheGroup, generation: u64, path: &str) -> String {
+    format!("{}{}:{}:{}:{}", ENTRY_KEY_PREFIX, shop, group.as_str(), generation, path)
+}
+
+fn generation_key(shop: &str, group: CacheGroup) -> String {
+    format!("{}{}:{}", GENERATION_KEY_PREFIX, shop, group.as_str())
+}
+
+async fn with_timeout<T>(operation: impl std::future::Future<Output = redis::RedisResult<T>>) -> Result<T, String> {
+    match tokio::time::timeout(REDIS_TIMEOUT, operation).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err("timed out".to_string()),
+    }
+}
+
+impl ResponseCache {
+    pub fn new(config: &ResponseCacheConfig) -> redis::RedisResult<Self> {
+        let redis_client = match (&config.redis_url, config.use_redis && config.enabled()) {
+            (Some(url), true) => Some(redis::Client::open(url.as_str())?),
+            (None, true) => {
+                warn!("RESPONSE_CACHE_REDIS is on but no REDIS_URL is set, caching in memory");
+                None
+            }
+            _ => None,
+        };
+        Ok(Self {
+            config: config.clone(),
+            redis_client,
+            redis_conn: Arc::new(tokio::sync::OnceCell::new()),
+            memory: Arc::new(Mutex::new(MemoryCache::default())),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled()
+    }
+
+    async fn redis(&self) -> Option<Result<redis::aio::ConnectionManager, String>> {
+        let client = self.redis_client.as_ref()?;
+        Some(
+            with_timeout(self.redis_conn.get_or_try_init(|| redis::aio::ConnectionManager::new(client.clone())))
+                .await
+                .cloned(),
+        )
+    }
+
+    /// The group's current generation and the entry for `path` under it.
+    pub async fn lookup(&self, shop: &str, group: CacheGroup, path: &str) -> Option<(u64, Option<CachedResponse>)> {
+        match self.redis().await {
+            Some(Ok(mut conn)) => {
+                let lookup = async {
+                    let generation: Option<u64> = conn.get(generation_key(shop, group)).await?;
+                    let generation = generation.unwrap_or(0);
+                    let (content_type, body, etag, fetched_at): (Option<String>, Option<Vec<u8>>, Option<String>, Option<i64>) =
+                        conn.hget(entry_key(shop, group, generation, path), &["content_type", "body", "etag", "fetched_at"]).await?;
+                    let entry = match (content_type, body, etag, fetched_at.and_then(|t| DateTime::from_timestamp(t, 0))) {
+                        (Some(content_type), Some(body), Some(etag), Some(fetched_at)) => {
+                            Some(CachedResponse { content_type, body: Bytes::from(body), etag, fetched_at })
+                        }
+                        _ => None,
+                    };
+                    Ok((generation, entry))
+                };
+                match with_timeout(lookup).await {
+                    Ok(found) => Some(found),
+                    Err(e) => {
+                        warn!("Response cache unavailable for {} {}: {}", group.as_str(), path, e);
+                        None
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                warn!("Response cache unavailable for {} {}: {}", group.as_str(), path, e);
+                None
+            }
+            None => {
+                let memory = self.memory.lock().expect("response cache lock");
+                let generation = memory.generations.get(&(shop.to_string(), group)).copied().unwrap_or(0);
+                let entry = memory
+                    .entries
+                    .get(&entry_key(shop, group, generation, path))
+                    .filter(|(expires_at, _)| *expires_at > Instant::now())
+                    .map(|(_, entry)| entry.clone());
+                Some((generation, entry))
+            }
+        }
+    }
+
+    /// Files a response under the generation [`lookup`](Self::lookup) returned.
+    pub async fn store(&self, shop: &str, group: CacheGroup, generation: u64, path: &str, response: CachedResponse) {
+        let key = entry_key(shop, group, generation, path);
+        match self.redis().await {
+            Some(Ok(mut conn)) => {
+                let fetched_at = response.fetched_at.timestamp().to_string();
+                let fields: [(&str, &[u8]); 4] = [
+                    ("content_type", response.content_type.as_bytes()),
+                    ("body", response.body.as_ref()),
+                    ("etag", response.etag.as_bytes()),
+                    ("fetched_at", fetched_at.as_bytes()),
+                ];
+                let mut pipe = redis::pipe();
+                pipe.hset_multiple(&key, &fields)
+                    .ignore()
+                    .expire(&key, self.config.ttl.as_secs().max(1) as usize)
+                    .ignore();
+                if let Err(e) = with_timeout(pipe.query_async::<_, ()>(&mut conn)).await {
+                    warn!("Failed to cache {} {}: {}", group.as_str(), path, e);
+                }
+            }
+            Some(Err(e)) => warn!("Failed to cache {} {}: {}", group.as_str(), path, e),
+            None => {
+                let mut memory = self.memory.lock().expect("response cache lock");
+                // Invalidated while the request ran
+                if memory.generations.get(&(shop.to_string(), group)).copied().unwrap_or(0) != generation {
+                    return;
+                }
+                let now = Instant::now();
+                if memory.entries.len() >= self.config.max_entries {
+                    memory.entries.retain(|_, (expires_at, _)| *expires_at > now);
+                }
+                if memory.entries.len() >= self.config.max_entries {
+                    let soonest = memory.entries.iter().min_by_key(|(_, (expires_at, _))| *expires_at).map(|(k, _)| k.clone());
+                    if let Some(soonest) = soonest {
+                        memory.entries.remove(&soonest);
+                    }
+                }
+                if self.config.max_entries > 0 {
+                    memory.entries.insert(key, (now + self.config.ttl, response));
+                }
+            }
+        }
+    }
+
+    /// Drops every cached response in the shop's group.
+    pub async fn invalidate(&self, shop: &str, group: CacheGroup) {
+        if !self.is_enabled() {
+            return;
+        }
+        match self.redis().await {
+            Some(Ok(mut conn)) => {
+                if let Err(e) = with_timeout(conn.incr::<_, _, u64>(generation_key(shop, group), 1)).await {
+                    error!("Failed to invalidate cached {} for {}, they expire in {}s: {}",
+                        group.as_str(), shop, self.config.ttl.as_secs(), e);
+                }
+            }
+            Some(Err(e)) => error!("Failed to invalidate cached {} for {}, they expire in {}s: {}",
+                group.as_str(), shop, self.config.ttl.as_secs(), e),
+            None => {
+                let mut memory = self.memory.lock().expect("response cache lock");
+                *memory.generations.entry((shop.to_string(), group)).or_insert(0) += 1;
+                let prefix = format!("{}{}:{}:", ENTRY_KEY_PREFIX, shop, group.as_str());
+                memory.entries.retain(|key, _| !key.starts_with(&prefix));
+            }
+        }
+    }
+}
+
+// =============================================================================
+// Middleware
+// =============================================================================
+
+/// A route's cache group, attached with `route_layer` and `response_cache_middleware`.
+#[derive(Clone)]
+pub struct RouteCache {
+    cache: ResponseCache,
+    shop: String,
+    group: CacheGroup,
+}
+
+impl RouteCache {
+    pub fn new(cache: &ResponseCache, shop: &str, group: CacheGroup) -> Self {
+        Self { cache: cache.clone(), shop: shop.to_string(), group }
+    }
+}
+
+fn is_json(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
+/// Whether the client's copy is still current: `If-None-Match` lists the
+/// entity's ETag (compared weakly, as `GET` allows), or, without one,
+/// `If-Modified-Since` is no earlier than `last_modified`.
+pub fn not_modified(headers: &HeaderMap, etag: &str, last_modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        return if_none_match.split(',').any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag));
+    }
+    let if_modified_since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| DateTime::parse_from_rfc2822(v).ok());
+    matches!((if_modified_since, last_modified), (Some(since), Some(modified)) if modified <= since)
+}
+
+/// The entry as a response, or a bodiless 304 when the client already has
+/// it. `Last-Modified` is only sent for cached entries, the only ones whose
+/// fetch time means anything to a later request.
+fn conditional_response(request: &HeaderMap, entry: CachedResponse, cached: bool, cache_status: Option<&'static str>) -> Response {
+    let last_modified = cached.then_some(entry.fetched_at);
+    let mut response = if not_modified(request, &entry.etag, last_modified) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let mut response = entry.body.into_response();
+        if let Ok(content_type) = HeaderValue::from_str(&entry.content_type) {
+            response.headers_mut().insert(header::CONTENT_TYPE, content_type);
+        }
+        response
+    };
+    let headers = response.headers_mut();
+    if let Ok(etag) = HeaderValue::from_str(&entry.etag) {
+        headers.insert(header::ETAG, etag);
+    }
+    if let Some(last_modified) = last_modified {
+        let http_date = last_modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        if let Ok(value) = HeaderValue::from_str(&http_date) {
+            headers.insert(header::LAST_MODIFIED, value);
+        }
+    }
+    if let Some(status) = cache_status {
+        headers.insert(CACHE_STATUS_HEADER, HeaderValue::from_static(status));
+    }
+    response
+}
+
+/// Drops the route's group after a successful request, for writes whose
+/// responses are never cached or shaped themselves.
+pub async fn cache_invalidation_middleware(State(route): State<RouteCache>, request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if response.status().is_success() {
+        route.cache.invalidate(&route.shop, route.group).await;
+    }
+    response
+}
+
+/// Serves repeated JSON `GET`s from the cache when it's on, and drops the
+/// route's group after a successful write through it. Every JSON `GET`
+/// answer carries an `ETag`, and a matching `If-None-Match` gets a 304 so
+/// polling clients don't download an unchanged body again. CSV and NDJSON
+/// exports and errors always go to the handler untouched; responses over
+/// 1 MB get an `ETag` but aren't cached.
+pub async fn response_cache_middleware(State(route): State<RouteCache>, request: Request, next: Next) -> Response {
+    if !request.method().is_safe() {
+        return cache_invalidation_middleware(State(route), request, next).await;
+    }
+    let cache = &route.cache;
+    if request.method() != Method::GET || negotiated_format(request.headers()) != ListFormat::Json {
+        return next.run(request).await;
+    }
+
+    let path = cache_path(request.uri());
+    let conditions = request.headers().clone();
+    let lookup = match cache.is_enabled() {
+        true => cache.lookup(&route.shop, route.group, &path).await,
+        false => None,
+    };
+    let generation = match lookup {
+        Some((_, Some(entry))) => return conditional_response(&conditions, entry, true, Some("hit")),
+        Some((generation, None)) => Some(generation),
+        None => None,
+    };
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK || !is_json(&response) {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Failed to read the {} response for caching: {}", path, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let content_type = parts.headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    let entry = CachedResponse::new(content_type, body);
+
+    let cached = match generation {
+        Some(generation) if entry.body.len() <= MAX_CACHED_BODY_BYTES => {
+            cache.store(&route.shop, route.group, generation, &path, entry.clone()).await;
+            true
+        }
+        _ => false,
+    };
+    conditional_response(&conditions, entry, cached, generation.map(|_| "miss"))
+}
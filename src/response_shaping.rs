@@ -0,0 +1,198 @@
+use axum::{
+    body::to_bytes,
+    extract::{Query, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tracing::error;
+
+use crate::csv_response::{list_format, ListFormat};
+use crate::error::AppError;
+
+/// Keys dropped at any depth by `redact_pii=true`. Addresses go whole,
+/// names and coordinates included.
+pub const PII_FIELDS: &[&str] = &[
+    "email",
+    "contact_email",
+    "phone",
+    "first_name",
+    "last_name",
+    "billing_address",
+    "shipping_address",
+    "default_address",
+    "addresses",
+    "browser_ip",
+    "client_details",
+];
+
+// =============================================================================
+// Projection
+// =============================================================================
+
+/// Fields kept by `?select=`, as a tree: `variants.price` keeps only the
+/// price of each variant, `variants` keeps them whole.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Selection {
+    whole: bool,
+    fields: BTreeMap<String, Selection>,
+}
+
+impl Selection {
+    /// Parses `id,title,variants.price`. Segments are field names: letters,
+    /// digits and underscores.
+    pub fn parse(select: &str) -> Result<Self, String> {
+        let mut selection = Selection::default();
+        for path in select.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let mut node = &mut selection;
+            for segment in path.split('.') {
+                if segment.is_empty() || !segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return Err(format!("select has an invalid field path: {}", path));
+                }
+                node = node.fields.entry(segment.to_string()).or_default();
+            }
+            node.whole = true;
+        }
+        if selection.fields.is_empty() {
+            return Err("select needs at least one field".to_string());
+        }
+        Ok(selection)
+    }
+
+    /// Keeps only the selected fields of `value`, or of each element when
+    /// it's an array.
+    pub fn project(&self, value: &mut Value) {
+        if self.whole {
+            return;
+        }
+        match value {
+            Value::Object(map) => {
+                map.retain(|key, _| self.fields.contains_key(key));
+                for (key, field) in map.iter_mut() {
+                    self.fields[key].project(field);
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.project(item)),
+            _ => {}
+        }
+    }
+}
+
+fn is_record(value: &Value) -> bool {
+    value.as_object().is_some_and(|object| object.contains_key("id"))
+}
+
+/// Projects the records in a response, leaving its envelope (`shop`,
+/// counts, `page_info`, `next_page`) alone: top-level arrays of objects,
+/// and top-level objects with an `id`, like `{"products": [...]}` or
+/// `{"order": {...}}`.
+pub fn project_records(body: &mut Value, selection: &Selection) {
+    match body {
+        Value::Array(_) => selection.project(body),
+        Value::Object(map) => {
+            for value in map.values_mut() {
+                let records = match &*value {
+                    Value::Array(items) => !items.is_empty() && items.iter().all(Value::is_object),
+                    other => is_record(other),
+                };
+                if records {
+                    selection.project(value);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Drops every [`PII_FIELDS`] key, at any depth.
+pub fn redact_pii(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|key, _| !PII_FIELDS.contains(&key.as_str()));
+            map.values_mut().for_each(redact_pii);
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_pii),
+        _ => {}
+    }
+}
+
+// =============================================================================
+// Middleware
+// =============================================================================
+
+#[derive(Debug, Default, Deserialize)]
+struct ShapeParams {
+    select: Option<String>,
+    redact_pii: Option<bool>,
+    format: Option<String>,
+}
+
+/// Shapes JSON `GET` answers for clients that only need part of them:
+/// `?select=id,title,variants.price` keeps the listed fields of each record,
+/// and `?redact_pii=true` drops emails, phones, names and addresses.
+/// Either one on a CSV or NDJSON export is refused, since those aren't
+/// shaped.
+pub async fn response_shaping_middleware(request: Request, next: Next) -> Response {
+    if !request.method().is_safe() {
+        return next.run(request).await;
+    }
+    let params = match Query::<ShapeParams>::try_from_uri(request.uri()) {
+        Ok(Query(params)) => params,
+        Err(e) => return AppError::BadRequest(e.body_text()).into_response(),
+    };
+    let selection = match params.select.as_deref().map(Selection::parse).transpose() {
+        Ok(selection) => selection,
+        Err(e) => return AppError::BadRequest(e).into_response(),
+    };
+    let redact = params.redact_pii.unwrap_or(false);
+    if selection.is_none() && !redact {
+        return next.run(request).await;
+    }
+    if !matches!(list_format(params.format.as_deref(), request.headers()), Ok(ListFormat::Json)) {
+        return AppError::BadRequest("select and redact_pii only apply to JSON responses".to_string()).into_response();
+    }
+
+    let response = next.run(request).await;
+    let json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !response.status().is_success() || !json {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Failed to read the response for shaping: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let mut value: Value = match serde_json::from_slice(&body) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Failed to parse the response for shaping: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    if let Some(selection) = selection {
+        project_records(&mut value, &selection);
+    }
+    if redact {
+        redact_pii(&mut value);
+    }
+    let mut response = Json(value).into_response();
+    *response.status_mut() = parts.status;
+    for (name, value) in parts.headers.iter() {
+        if name != header::CONTENT_LENGTH {
+            response.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    response
+}
@@ -0,0 +1,304 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::info;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    csv_response::{csv_response, export_file_name, optional, wants_csv, CsvRecord},
+    database::{PeriodRefunds, PeriodSales},
+    error::{AppError, AppResult},
+};
+
+/// Most periods one request can ask for, e.g. a year of days.
+const MAX_PERIODS: i64 = 366;
+
+// =============================================================================
+// Sales Metrics Structures
+// =============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SalesMetricsParams {
+    /// `day` (default) or `week`, starting on Monday; periods are in UTC
+    pub granularity: Option<String>,
+    /// Defaults to 30 days before `until`
+    pub since: Option<DateTime<Utc>>,
+    /// Defaults to now
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Day,
+    Week,
+}
+
+impl Granularity {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") | Some("day") => Ok(Self::Day),
+            Some("week") => Ok(Self::Week),
+            Some(other) => Err(format!("granularity must be day or week, got {}", other)),
+        }
+    }
+
+    /// The Postgres `date_trunc` field.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+        }
+    }
+
+    fn length(&self) -> Duration {
+        match self {
+            Self::Day => Duration::days(1),
+            Self::Week => Duration::weeks(1),
+        }
+    }
+
+    /// Start of the period `at` falls in, as `date_trunc` has it.
+    pub fn truncate(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let day = at.date_naive();
+        let day = match self {
+            Self::Day => day,
+            Self::Week => day - Duration::days(day.weekday().num_days_from_monday() as i64),
+        };
+        day.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+    }
+
+    /// Start of every period overlapping `[since, until)`.
+    pub fn periods(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let mut periods = Vec::new();
+        let mut start = self.truncate(since);
+        while start < until {
+            periods.push(start);
+            start += self.length();
+        }
+        periods
+    }
+}
+
+/// KPIs for one period or a whole window, in one currency.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, ToSchema)]
+pub struct SalesMetrics {
+    /// Orders placed, leaving out test and cancelled orders
+    pub orders: i64,
+    /// Sum of those orders' `total_price`
+    pub revenue: Decimal,
+    pub average_order_value: Decimal,
+    /// Refunds issued in the period, whenever their order was placed
+    pub refunds: i64,
+    pub refunded: Decimal,
+    /// `revenue` less `refunded`
+    pub net_revenue: Decimal,
+}
+
+impl SalesMetrics {
+    fn add_sales(&mut self, orders: i64, revenue: Decimal) {
+        self.orders += orders;
+        self.revenue += revenue;
+        self.finish();
+    }
+
+    fn add_refunds(&mut self, refunds: i64, refunded: Decimal) {
+        self.refunds += refunds;
+        self.refunded += refunded;
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        self.average_order_value = if self.orders > 0 {
+            (self.revenue / Decimal::from(self.orders)).round_dp(2)
+        } else {
+            Decimal::ZERO
+        };
+        self.net_revenue = self.revenue - self.refunded;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct PeriodMetrics {
+    pub period_start: DateTime<Utc>,
+    #[serde(flatten)]
+    pub metrics: SalesMetrics,
+}
+
+/// One currency's metrics. Amounts in different currencies are never added
+/// together.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct CurrencyMetrics {
+    /// `null` for orders stored without a currency
+    pub currency: Option<String>,
+    pub totals: SalesMetrics,
+    /// Every period in the window, including those without sales
+    pub periods: Vec<PeriodMetrics>,
+}
+
+impl SalesMetrics {
+    fn csv_fields(&self) -> [String; 6] {
+        [
+            self.orders.to_string(),
+            self.revenue.to_string(),
+            self.average_order_value.to_string(),
+            self.refunds.to_string(),
+            self.refunded.to_string(),
+            self.net_revenue.to_string(),
+        ]
+    }
+}
+
+impl CsvRecord for CurrencyMetrics {
+    fn csv_headers() -> Vec<&'static str> {
+        vec!["currency", "period_start", "orders", "revenue", "average_order_value", "refunds", "refunded", "net_revenue"]
+    }
+
+    /// The currency's totals, with `period_start` left empty.
+    fn csv_row(&self) -> Vec<String> {
+        let mut row = vec![optional(&self.currency), String::new()];
+        row.extend(self.totals.csv_fields());
+        row
+    }
+
+    /// One row per period.
+    fn csv_rows(&self) -> Vec<Vec<String>> {
+        if self.periods.is_empty() {
+            return vec![self.csv_row()];
+        }
+        self.periods
+            .iter()
+            .map(|period| {
+                let mut row = vec![optional(&self.currency), period.period_start.to_rfc3339()];
+                row.extend(period.metrics.csv_fields());
+                row
+            })
+            .collect()
+    }
+}
+
+type Series = BTreeMap<DateTime<Utc>, SalesMetrics>;
+
+fn period_metrics<'a>(
+    by_currency: &'a mut BTreeMap<Option<String>, Series>,
+    periods: &[DateTime<Utc>],
+    currency: &Option<String>,
+    start: DateTime<Utc>,
+) -> &'a mut SalesMetrics {
+    by_currency
+        .entry(currency.clone())
+        .or_insert_with(|| periods.iter().map(|start| (*start, SalesMetrics::default())).collect())
+        .entry(start)
+        .or_default()
+}
+
+/// Groups the rows by currency into `periods`, filling in the periods a
+/// currency had no orders or refunds in.
+pub fn sales_metrics(periods: &[DateTime<Utc>], sales: &[PeriodSales], refunds: &[PeriodRefunds]) -> Vec<CurrencyMetrics> {
+    let mut by_currency = BTreeMap::new();
+    for row in sales {
+        period_metrics(&mut by_currency, periods, &row.currency, row.period_start).add_sales(row.orders, row.revenue);
+    }
+    for row in refunds {
+        period_metrics(&mut by_currency, periods, &row.currency, row.period_start).add_refunds(row.refunds, row.refunded);
+    }
+
+    by_currency
+        .into_iter()
+        .map(|(currency, series)| {
+            let mut totals = SalesMetrics::default();
+            for metrics in series.values() {
+                totals.add_sales(metrics.orders, metrics.revenue);
+                totals.add_refunds(metrics.refunds, metrics.refunded);
+            }
+            CurrencyMetrics {
+                currency,
+                totals,
+                periods: series
+                    .into_iter()
+                    .map(|(period_start, metrics)| PeriodMetrics { period_start, metrics })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+// =============================================================================
+// Sales Metrics Handler
+// =============================================================================
+
+/// `GET /api/metrics/sales?granularity=day|week` — revenue, order count,
+/// average order value and refunds per period, per currency, from the local
+/// order copy, so dashboards get basic KPIs without spending Shopify API
+/// calls or exporting to a warehouse. `Accept: text/csv` streams one row
+/// per currency and period.
+#[utoipa::path(
+    get,
+    path = "/api/metrics/sales",
+    tag = "reports",
+    params(SalesMetricsParams),
+    responses(
+        (status = 200, description = "Sales KPIs per period and currency", content(
+            (crate::openapi::SalesMetricsResponse = "application/json"),
+            (String = "text/csv"),
+        )),
+        (status = 400, description = "Bad granularity or window, or the local order copy is off", body = crate::openapi::ErrorResponse),
+        (status = 409, description = "The local order copy is still being backfilled", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn sales_metrics_handler(
+    Query(params): Query<SalesMetricsParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    let shop = &state.config.shop;
+    if !state.config.order_sync.enabled || state.config.database.database_url.is_none() {
+        return Err(AppError::BadRequest(
+            "Sales metrics need a Postgres DATABASE_URL and ORDER_SYNC_INTERVAL_SECS above 0".to_string(),
+        ));
+    }
+    let granularity = Granularity::parse(params.granularity.as_deref()).map_err(AppError::BadRequest)?;
+    let until = params.until.unwrap_or_else(Utc::now);
+    let since = params.since.unwrap_or(until - Duration::days(30));
+    if since >= until {
+        return Err(AppError::BadRequest("since must be before until".to_string()));
+    }
+    let periods = granularity.periods(since, until);
+    if periods.len() as i64 > MAX_PERIODS {
+        return Err(AppError::BadRequest(format!(
+            "The window spans {} {}s; ask for at most {}",
+            periods.len(), granularity.as_str(), MAX_PERIODS
+        )));
+    }
+
+    let sync = state.orders.sync_state(shop).await?;
+    if sync.as_ref().is_none_or(|s| s.backfilled_at.is_none()) {
+        return Err(AppError::Conflict(format!("Orders for {} are still being backfilled", shop)));
+    }
+
+    let sales = state.orders.sales_by_period(shop, granularity.as_str(), since, until).await?;
+    let refunds = state.orders.refunds_by_period(shop, granularity.as_str(), since, until).await?;
+    let currencies = sales_metrics(&periods, &sales, &refunds);
+    info!("📊 Sales metrics for {}: {} {}s in {} currencies", shop, periods.len(), granularity.as_str(), currencies.len());
+
+    if wants_csv(&headers) {
+        return Ok(csv_response(&export_file_name("sales-metrics"), currencies));
+    }
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "granularity": granularity,
+        "since": since,
+        "until": until,
+        "currencies": currencies,
+        "synced_at": sync.and_then(|s| s.last_synced_at)
+    }))).into_response())
+}
@@ -0,0 +1,218 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use futures::{pin_mut, TryStreamExt};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use tracing::info;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    require_token,
+    csv_response::{csv_response, export_file_name, wants_csv, CsvRecord},
+    error::{AppError, AppResult, ShopifyError},
+    http_client::ShopifyClient,
+    shopify_api::{Order, OrdersResponse, SalesChannel, SourceFilter},
+};
+
+// =============================================================================
+// Sales Report Structures
+// =============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SalesReportParams {
+    /// Defaults to 30 days ago
+    pub created_at_min: Option<String>,
+    pub created_at_max: Option<String>,
+    /// Comma-separated raw source names, e.g. `pos`
+    pub source_name: Option<String>,
+    /// Comma-separated channels: online, pos, draft, other
+    pub channel: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq, ToSchema)]
+pub struct SalesSummary {
+    pub orders: usize,
+    pub total_sales: Decimal,
+    pub total_discounts: Decimal,
+    pub total_tax: Decimal,
+    pub average_order_value: Decimal,
+}
+
+impl SalesSummary {
+    fn add(&mut self, order: &Order) {
+        self.orders += 1;
+        self.total_sales += parse_amount(&order.total_price);
+        self.total_discounts += parse_amount(order.total_discounts.as_deref().unwrap_or_default());
+        self.total_tax += parse_amount(order.total_tax.as_deref().unwrap_or_default());
+        self.average_order_value = (self.total_sales / Decimal::from(self.orders)).round_dp(2);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SalesReport {
+    pub totals: SalesSummary,
+    pub by_channel: BTreeMap<SalesChannel, SalesSummary>,
+    /// Raw `source_name` values, so individual sales channel apps are visible
+    pub by_source_name: BTreeMap<String, SalesSummary>,
+    /// Cancelled and test orders are left out of the sums
+    pub excluded_orders: usize,
+}
+
+impl SalesReport {
+    /// The report as CSV rows: the totals, then each channel and source name.
+    pub fn into_rows(self) -> Vec<SalesReportRow> {
+        let row = |group: &'static str, name: String, summary: SalesSummary| SalesReportRow { group, name, summary };
+        std::iter::once(row("total", "all".to_string(), self.totals))
+            .chain(self.by_channel.into_iter().map(|(channel, summary)| row("channel", channel.as_str().to_string(), summary)))
+            .chain(self.by_source_name.into_iter().map(|(source, summary)| row("source_name", source, summary)))
+            .collect()
+    }
+}
+
+/// One line of the CSV sales report.
+#[derive(Debug, PartialEq)]
+pub struct SalesReportRow {
+    /// `total`, `channel` or `source_name`
+    pub group: &'static str,
+    pub name: String,
+    pub summary: SalesSummary,
+}
+
+impl CsvRecord for SalesReportRow {
+    fn csv_headers() -> Vec<&'static str> {
+        vec!["group", "name", "orders", "total_sales", "total_discounts", "total_tax", "average_order_value"]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.group.to_string(),
+            self.name.clone(),
+            self.summary.orders.to_string(),
+            self.summary.total_sales.to_string(),
+            self.summary.total_discounts.to_string(),
+            self.summary.total_tax.to_string(),
+            self.summary.average_order_value.to_string(),
+        ]
+    }
+}
+
+fn parse_amount(amount: &str) -> Decimal {
+    Decimal::from_str(amount.trim()).unwrap_or_default()
+}
+
+/// Splits sales by channel and source. Cancelled and test orders are counted
+/// as excluded rather than summed.
+pub fn summarize_sales(orders: &[Order]) -> SalesReport {
+    let mut report = SalesReport {
+        totals: SalesSummary::default(),
+        by_channel: BTreeMap::new(),
+        by_source_name: BTreeMap::new(),
+        excluded_orders: 0,
+    };
+
+    for order in orders {
+        if order.cancelled_at.is_some() || order.test {
+            report.excluded_orders += 1;
+            continue;
+        }
+
+        let source = order.source_name.clone().unwrap_or_else(|| "unknown".to_string());
+        report.totals.add(order);
+        report.by_channel.entry(order.channel()).or_default().add(order);
+        report.by_source_name.entry(source).or_default().add(order);
+    }
+
+    report
+}
+
+// =============================================================================
+// Sales Report Handler
+// =============================================================================
+
+/// Sums sales over a window, split by channel and source name.
+/// `Accept: text/csv` streams the same sums as CSV rows instead.
+#[utoipa::path(
+    get,
+    path = "/api/reports/sales",
+    tag = "reports",
+    params(SalesReportParams),
+    responses(
+        (status = 200, description = "Sales totals by channel and source", content(
+            (crate::openapi::SalesReportResponse = "application/json"),
+            (String = "text/csv"),
+        )),
+        (status = 400, description = "Unknown channel", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn sales_report_handler(
+    Query(params): Query<SalesReportParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+    let source_filter = SourceFilter::parse(params.source_name.as_deref(), params.channel.as_deref())
+        .map_err(AppError::BadRequest)?;
+
+    let created_at_min = params.created_at_min.clone()
+        .unwrap_or_else(|| (Utc::now() - chrono::Duration::days(30)).to_rfc3339());
+
+    let mut orders = fetch_report_orders(&state.shopify, &token, &created_at_min, params.created_at_max.as_deref()).await?;
+    orders.retain(|order| source_filter.matches(order));
+
+    let report = summarize_sales(&orders);
+    info!("📈 Sales report for {}: {} orders across {} channels", shop, report.totals.orders, report.by_channel.len());
+
+    if wants_csv(&headers) {
+        return Ok(csv_response(&export_file_name("sales-report"), report.into_rows()));
+    }
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "created_at_min": created_at_min,
+        "created_at_max": params.created_at_max,
+        "currency": orders.first().and_then(|o| o.currency.clone()),
+        "totals": report.totals,
+        "by_channel": report.by_channel,
+        "by_source_name": report.by_source_name,
+        "excluded_orders": report.excluded_orders
+    }))).into_response())
+}
+
+async fn fetch_report_orders(
+    client: &ShopifyClient,
+    token: &str,
+    created_at_min: &str,
+    created_at_max: Option<&str>,
+) -> Result<Vec<Order>, ShopifyError> {
+    let mut query_params = vec![
+        ("limit".to_string(), "250".to_string()),
+        ("status".to_string(), "any".to_string()),
+        ("created_at_min".to_string(), created_at_min.to_string()),
+        (
+            "fields".to_string(),
+            "id,source_name,currency,total_price,total_discounts,total_tax,cancelled_at,test".to_string(),
+        ),
+    ];
+    if let Some(max) = created_at_max {
+        query_params.push(("created_at_max".to_string(), max.to_string()));
+    }
+
+    let pages = client.get_all_pages::<OrdersResponse>("orders.json", token, query_params);
+    pin_mut!(pages);
+
+    let mut orders = Vec::new();
+    while let Some(page) = pages.try_next().await? {
+        orders.extend(page.orders);
+    }
+
+    Ok(orders)
+}
@@ -0,0 +1,545 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, TimeZone, Timelike, Utc};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    AppState,
+    database::{JobStore, ScheduledJobRecord},
+    error::{AppError, AppResult},
+    token_audit::with_actor,
+};
+
+/// How long a claimed cluster job stays locked without a heartbeat. A crashed
+/// instance's job is picked up again once its lease runs out, and a run that
+/// finds its lease taken over is stopped, so it never overlaps the new one.
+const JOB_LEASE: Duration = Duration::from_secs(300);
+
+// =============================================================================
+// Schedules
+// =============================================================================
+
+/// When a job runs: a five-field cron expression (minute hour day-of-month
+/// month day-of-week, in UTC) or a fixed interval. Interval slots are aligned
+/// to the Unix epoch so every instance agrees on them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(CronExpr),
+}
+
+impl Schedule {
+    /// Parses `@every 30s` (or `m`, `h`), `@hourly`, `@daily`, `@weekly`, or
+    /// a cron expression such as `*/15 * * * *` or `0 3 * * 1-5`.
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let expr = expr.trim();
+        match expr {
+            "@hourly" => return CronExpr::parse("0 * * * *").map(Self::Cron),
+            "@daily" | "@midnight" => return CronExpr::parse("0 0 * * *").map(Self::Cron),
+            "@weekly" => return CronExpr::parse("0 0 * * 0").map(Self::Cron),
+            _ => {}
+        }
+
+        if let Some(every) = expr.strip_prefix("@every") {
+            let every = every.trim();
+            let (amount, unit) = every.split_at(every.find(|c: char| !c.is_ascii_digit()).unwrap_or(every.len()));
+            let amount: u64 = amount.parse().map_err(|_| format!("Invalid interval: {}", every))?;
+            let secs = match unit {
+                "s" => amount,
+                "m" => amount * 60,
+                "h" => amount * 3600,
+                _ => return Err(format!("Interval must end in s, m or h: {}", every)),
+            };
+            if secs == 0 {
+                return Err("Interval must be above zero".to_string());
+            }
+            return Ok(Self::Every(Duration::from_secs(secs)));
+        }
+
+        CronExpr::parse(expr).map(Self::Cron)
+    }
+
+    /// The first slot strictly after `after`, or `None` if the expression
+    /// never matches (e.g. February 30th).
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Every(interval) => {
+                let secs = interval.as_secs().max(1) as i64;
+                let next = (after.timestamp().div_euclid(secs) + 1) * secs;
+                Utc.timestamp_opt(next, 0).single()
+            }
+            Self::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Every(interval) => write!(f, "@every {}s", interval.as_secs()),
+            Self::Cron(cron) => f.write_str(&cron.source),
+        }
+    }
+}
+
+/// A parsed cron expression. Fields accept `*`, numbers, ranges (`1-5`),
+/// steps (`*/15`, `0-30/10`) and comma-separated lists of those.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronExpr {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Cron matches either day field when both are restricted
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronExpr {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("Expected 5 cron fields, got {}: {}", fields.len(), expr));
+        };
+
+        // Sunday is both 0 and 7
+        let mut weekdays = parse_cron_field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+
+        Ok(Self {
+            source: fields.join(" "),
+            minutes: parse_cron_field(minute, 0, 59)?,
+            hours: parse_cron_field(hour, 0, 23)?,
+            days: parse_cron_field(day, 1, 31)?,
+            months: parse_cron_field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    fn day_matches(&self, t: DateTime<Utc>) -> bool {
+        let day = self.days & (1 << t.day()) != 0;
+        let weekday = self.weekdays & (1 << t.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+
+        // Skipping whole months, days and hours, five years is a few thousand steps
+        let give_up = t + ChronoDuration::days(5 * 366);
+        while t < give_up {
+            if self.months & (1 << t.month()) == 0 {
+                let (year, month) = if t.month() == 12 { (t.year() + 1, 1) } else { (t.year(), t.month() + 1) };
+                t = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.day_matches(t) {
+                t = t.date_naive().succ_opt()?.and_hms_opt(0, 0, 0)?.and_utc();
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + ChronoDuration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += ChronoDuration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>().ok().filter(|s| *s > 0).ok_or_else(|| format!("Invalid step in {}", item))?,
+            ),
+            None => (item, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => {
+                let number = |n: &str| {
+                    n.parse::<u32>()
+                        .ok()
+                        .filter(|n| (min..=max).contains(n))
+                        .ok_or_else(|| format!("{} is outside {}-{}", n, min, max))
+                };
+                match range.split_once('-') {
+                    Some((start, end)) => (number(start)?, number(end)?),
+                    // `5/15` means every 15 starting at 5
+                    None if step > 1 => (number(range)?, max),
+                    None => (number(range)?, number(range)?),
+                }
+            }
+        };
+        if start > end {
+            return Err(format!("Range {} runs backwards", range));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+#[derive(Clone, Debug, Default)]
+pub struct SchedulerConfig {
+    /// Per-job schedule overrides; `None` turns the job off
+    pub overrides: HashMap<String, Option<Schedule>>,
+}
+
+impl SchedulerConfig {
+    /// `JOB_SCHEDULES`, e.g. `catalog-reconcile=0 3 * * *;webhook-registration=off`.
+    pub fn from_env() -> AppResult<Self> {
+        Self::parse(&std::env::var("JOB_SCHEDULES").unwrap_or_default())
+    }
+
+    pub fn parse(spec: &str) -> AppResult<Self> {
+        let mut overrides = HashMap::new();
+        for entry in spec.split(';') {
+            if entry.trim().is_empty() {
+                continue;
+            }
+            let (name, expr) = entry
+                .split_once('=')
+                .ok_or_else(|| AppError::Config(format!("JOB_SCHEDULES: expected name=schedule, got {}", entry)))?;
+            let schedule = match expr.trim() {
+                "off" => None,
+                expr => Some(
+                    Schedule::parse(expr)
+                        .map_err(|e| AppError::Config(format!("JOB_SCHEDULES: {}: {}", name.trim(), e)))?,
+                ),
+            };
+            overrides.insert(name.trim().to_string(), schedule);
+        }
+        Ok(Self { overrides })
+    }
+}
+
+// =============================================================================
+// Jobs
+// =============================================================================
+
+/// Where a job runs when several instances share a database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobScope {
+    /// Once per slot across all instances, claimed through `scheduled_jobs`
+    Cluster,
+    /// On every instance, e.g. flushing in-memory counters
+    Instance,
+}
+
+/// A run's outcome: a short summary, or what went wrong.
+pub type JobResult = Result<String, String>;
+
+type JobFn = Arc<dyn Fn(AppState) -> BoxFuture<'static, JobResult> + Send + Sync>;
+
+pub struct Job {
+    pub name: &'static str,
+    pub schedule: Schedule,
+    pub scope: JobScope,
+    /// Also run as soon as the scheduler starts, unless another instance ran
+    /// the job more recently than its schedule allows
+    pub run_on_start: bool,
+    run: JobFn,
+}
+
+impl Job {
+    pub fn new<F, Fut>(name: &'static str, schedule: Schedule, scope: JobScope, run: F) -> Self
+    where
+        F: Fn(AppState) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = JobResult> + Send + 'static,
+    {
+        Self {
+            name,
+            schedule,
+            scope,
+            run_on_start: false,
+            run: Arc::new(move |state| Box::pin(run(state))),
+        }
+    }
+
+    pub fn run_on_start(mut self) -> Self {
+        self.run_on_start = true;
+        self
+    }
+}
+
+/// How this instance last ran a job.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JobStatus {
+    pub running: bool,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_status: Option<&'static str>,
+    pub last_summary: Option<String>,
+    pub last_error: Option<String>,
+    pub last_duration_ms: Option<i64>,
+}
+
+// =============================================================================
+// Scheduler
+// =============================================================================
+
+/// Runs registered jobs on their schedules. Cluster jobs are coordinated
+/// through Postgres when there is one; without it every job runs locally.
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Arc<SchedulerInner>,
+}
+
+struct SchedulerInner {
+    instance: String,
+    store: Option<JobStore>,
+    config: SchedulerConfig,
+    jobs: Mutex<Vec<Arc<Job>>>,
+    status: Mutex<HashMap<&'static str, JobStatus>>,
+}
+
+impl Scheduler {
+    pub fn new(config: &SchedulerConfig, store: Option<JobStore>) -> Self {
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "app".to_string());
+        let instance = format!("{}-{}", host, &uuid::Uuid::new_v4().simple().to_string()[..8]);
+
+        Self {
+            inner: Arc::new(SchedulerInner {
+                instance,
+                store,
+                config: config.clone(),
+                jobs: Mutex::new(Vec::new()),
+                status: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Identifies this process in `scheduled_jobs.locked_by`.
+    pub fn instance(&self) -> &str {
+        &self.inner.instance
+    }
+
+    /// Adds a job, applying any `JOB_SCHEDULES` override for it.
+    pub fn register(&self, mut job: Job) {
+        match self.inner.config.overrides.get(job.name) {
+            Some(None) => {
+                info!("⏸️ Job {} is turned off by JOB_SCHEDULES", job.name);
+                return;
+            }
+            Some(Some(schedule)) => job.schedule = schedule.clone(),
+            None => {}
+        }
+        self.inner.status.lock().unwrap().insert(job.name, JobStatus::default());
+        self.inner.jobs.lock().unwrap().push(Arc::new(job));
+    }
+
+    pub fn jobs(&self) -> Vec<Arc<Job>> {
+        self.inner.jobs.lock().unwrap().clone()
+    }
+
+    pub fn status(&self, name: &str) -> Option<JobStatus> {
+        self.inner.status.lock().unwrap().get(name).cloned()
+    }
+
+    fn update_status(&self, name: &'static str, update: impl FnOnce(&mut JobStatus)) {
+        update(self.inner.status.lock().unwrap().entry(name).or_default());
+    }
+
+    /// Starts a task per registered job.
+    pub fn start(&self, state: AppState) -> Vec<JoinHandle<()>> {
+        self.jobs()
+            .into_iter()
+            .map(|job| {
+                info!("⏰ Scheduled job {} ({}, {:?})", job.name, job.schedule, job.scope);
+                tokio::spawn(self.clone().run_job(job, state.clone()))
+            })
+            .collect()
+    }
+
+    async fn run_job(self, job: Arc<Job>, state: AppState) {
+        let mut due = if job.run_on_start { Some(Utc::now()) } else { job.schedule.next_after(Utc::now()) };
+
+        while let Some(slot) = due {
+            self.update_status(job.name, |status| status.next_run_at = Some(slot));
+            tokio::time::sleep((slot - Utc::now()).to_std().unwrap_or_default()).await;
+
+            let next = job.schedule.next_after(slot.max(Utc::now()));
+            self.run_slot(&job, &state, slot, next).await;
+            due = next;
+        }
+        warn!("Job {} has no further runs for schedule {}", job.name, job.schedule);
+    }
+
+    /// Runs the job for `slot` if this instance gets it.
+    pub async fn run_slot(&self, job: &Job, state: &AppState, slot: DateTime<Utc>, next: Option<DateTime<Utc>>) {
+        let store = match (job.scope, &self.inner.store) {
+            (JobScope::Cluster, Some(store)) => Some(store),
+            _ => None,
+        };
+        if let Some(store) = store {
+            match store.claim(job.name, self.instance(), slot, next, JOB_LEASE).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    debug!("Job {} for {} is running or ran on another instance", job.name, slot);
+                    return;
+                }
+                Err(e) => {
+                    error!("Skipping job {}, could not claim it: {}", job.name, e);
+                    return;
+                }
+            }
+        }
+
+        let started_at = Utc::now();
+        self.update_status(job.name, |status| {
+            status.running = true;
+            status.last_started_at = Some(started_at);
+        });
+
+        // Renews the lease while the job runs; resolves if another instance
+        // took the job over, having found the lease expired
+        let heartbeat = async {
+            let Some(store) = store else {
+                return std::future::pending().await;
+            };
+            let mut interval = tokio::time::interval(JOB_LEASE / 3);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match store.extend_lease(job.name, self.instance(), JOB_LEASE).await {
+                    Ok(true) => {}
+                    Ok(false) => return,
+                    Err(e) => warn!("Failed to extend the lease on job {}: {}", job.name, e),
+                }
+            }
+        };
+
+        // A panicking job fails its run without taking the scheduler down
+        let clock = Instant::now();
+        let mut run = tokio::spawn(with_actor(job.name, (job.run)(state.clone())));
+        let result = tokio::select! {
+            result = &mut run => match result {
+                Ok(result) => result,
+                Err(e) => Err(format!("Job panicked: {}", e)),
+            },
+            _ = heartbeat => {
+                run.abort();
+                Err("Stopped: the lease ran out and another instance took the job over".to_string())
+            }
+        };
+        let duration_ms = clock.elapsed().as_millis() as i64;
+
+        match &result {
+            Ok(summary) => info!("⏰ Job {} finished in {}ms: {}", job.name, duration_ms, summary),
+            Err(e) => error!("Job {} failed after {}ms: {}", job.name, duration_ms, e),
+        }
+        self.update_status(job.name, |status| {
+            status.running = false;
+            status.last_finished_at = Some(Utc::now());
+            status.last_status = Some(if result.is_ok() { "ok" } else { "failed" });
+            status.last_summary = result.as_ref().ok().cloned();
+            status.last_error = result.as_ref().err().cloned();
+            status.last_duration_ms = Some(duration_ms);
+        });
+
+        if let Some(store) = store {
+            if let Err(e) = store
+                .finish(job.name, self.instance(), result.as_ref().ok().map(String::as_str), result.as_ref().err().map(String::as_str), duration_ms)
+                .await
+            {
+                error!("Failed to record job {} result: {}", job.name, e);
+            }
+        }
+    }
+}
+
+// =============================================================================
+// Admin Handler
+// =============================================================================
+
+/// `GET /admin/jobs` — every registered job with its schedule and last run.
+/// Cluster jobs report the last run on any instance, instance jobs the last
+/// run on the one answering.
+#[utoipa::path(
+    get,
+    path = "/admin/jobs",
+    tag = "jobs",
+    responses(
+        (status = 200, description = "Scheduled jobs and their last runs", body = crate::openapi::ScheduledJobList),
+    ),
+)]
+pub async fn jobs_handler(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    let shared: HashMap<String, ScheduledJobRecord> = match &state.scheduler.inner.store {
+        Some(store) => store.list_jobs().await?.into_iter().map(|job| (job.name.clone(), job)).collect(),
+        None => HashMap::new(),
+    };
+
+    let jobs: Vec<serde_json::Value> = state
+        .scheduler
+        .jobs()
+        .iter()
+        .map(|job| {
+            let local = state.scheduler.status(job.name).unwrap_or_default();
+            let last_run = match shared.get(job.name).filter(|_| job.scope == JobScope::Cluster) {
+                Some(record) => serde_json::json!({
+                    "started_at": record.last_started_at,
+                    "finished_at": record.last_finished_at,
+                    "status": record.last_status,
+                    "summary": record.last_summary,
+                    "error": record.last_error,
+                    "duration_ms": record.last_duration_ms,
+                    "instance": record.last_instance,
+                }),
+                None => serde_json::json!({
+                    "started_at": local.last_started_at,
+                    "finished_at": local.last_finished_at,
+                    "status": local.last_status,
+                    "summary": local.last_summary,
+                    "error": local.last_error,
+                    "duration_ms": local.last_duration_ms,
+                    "instance": local.last_started_at.map(|_| state.scheduler.instance()),
+                }),
+            };
+            let running_on = shared
+                .get(job.name)
+                .filter(|record| record.locked_until.is_some_and(|until| until > Utc::now()))
+                .and_then(|record| record.locked_by.clone());
+
+            serde_json::json!({
+                "name": job.name,
+                "schedule": job.schedule.to_string(),
+                "scope": job.scope,
+                "running": local.running || running_on.is_some(),
+                "running_on": running_on,
+                "next_run_at": local.next_run_at,
+                "last_run": last_run,
+            })
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "instance": state.scheduler.instance(),
+        "jobs_count": jobs.len(),
+        "jobs": jobs
+    }))))
+}
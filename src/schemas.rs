@@ -0,0 +1,173 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use schemars::{schema::RootSchema, schema_for};
+use serde::Serialize;
+
+use crate::{
+    error::{AppError, AppResult},
+    low_stock::InventoryLevelWebhook,
+    order_risks::RiskAssessmentWebhook,
+    webhook_queue::QueuedWebhook,
+    webhooks::{
+        AppUninstalledWebhook, CheckoutWebhook, CustomerWebhook, OrderWebhook, ProductDeletedWebhook, ProductWebhook,
+        RefundWebhook,
+    },
+};
+
+// =============================================================================
+// Event Schema Registry
+// =============================================================================
+
+/// An event this app sends somewhere else, published at `/schemas/{name}`.
+#[derive(Debug, Serialize)]
+pub struct EventSchema {
+    pub name: &'static str,
+    /// `forwarded_webhook` or `queue_message`
+    pub kind: &'static str,
+    pub description: &'static str,
+    /// Shopify topics whose payloads follow this schema
+    pub topics: &'static [&'static str],
+    #[serde(skip)]
+    generate: fn() -> RootSchema,
+}
+
+impl EventSchema {
+    /// The JSON Schema, with `$id` set to where it is published.
+    pub fn schema(&self) -> RootSchema {
+        let mut schema = (self.generate)();
+        schema.schema.metadata().id = Some(format!("/schemas/{}", self.name));
+        schema
+    }
+}
+
+const FORWARDED_DESCRIPTION: &str = "Webhook body copied to the staging deployment, with PII scrubbed and \
+    re-signed in X-Shopify-Hmac-Sha256. Sent with X-Shopify-Topic, X-Shopify-Shop-Domain, and X-Webhook-Sampled headers.";
+
+pub const EVENT_SCHEMAS: &[EventSchema] = &[
+    EventSchema {
+        name: "webhooks/orders",
+        kind: "forwarded_webhook",
+        description: FORWARDED_DESCRIPTION,
+        topics: &["orders/create", "orders/updated", "orders/cancelled", "orders/paid", "orders/fulfilled"],
+        generate: || schema_for!(OrderWebhook),
+    },
+    EventSchema {
+        name: "webhooks/order-risks",
+        kind: "forwarded_webhook",
+        description: FORWARDED_DESCRIPTION,
+        topics: &["orders/risk_assessment_changed"],
+        generate: || schema_for!(RiskAssessmentWebhook),
+    },
+    EventSchema {
+        name: "webhooks/refunds",
+        kind: "forwarded_webhook",
+        description: FORWARDED_DESCRIPTION,
+        topics: &["refunds/create"],
+        generate: || schema_for!(RefundWebhook),
+    },
+    EventSchema {
+        name: "webhooks/products",
+        kind: "forwarded_webhook",
+        description: FORWARDED_DESCRIPTION,
+        topics: &["products/create", "products/update"],
+        generate: || schema_for!(ProductWebhook),
+    },
+    EventSchema {
+        name: "webhooks/product-deletions",
+        kind: "forwarded_webhook",
+        description: FORWARDED_DESCRIPTION,
+        topics: &["products/delete"],
+        generate: || schema_for!(ProductDeletedWebhook),
+    },
+    EventSchema {
+        name: "webhooks/inventory-levels",
+        kind: "forwarded_webhook",
+        description: FORWARDED_DESCRIPTION,
+        topics: &["inventory_levels/update"],
+        generate: || schema_for!(InventoryLevelWebhook),
+    },
+    EventSchema {
+        name: "webhooks/customers",
+        kind: "forwarded_webhook",
+        description: FORWARDED_DESCRIPTION,
+        topics: &["customers/create"],
+        generate: || schema_for!(CustomerWebhook),
+    },
+    EventSchema {
+        name: "webhooks/checkouts",
+        kind: "forwarded_webhook",
+        description: FORWARDED_DESCRIPTION,
+        topics: &["checkouts/create", "checkouts/update"],
+        generate: || schema_for!(CheckoutWebhook),
+    },
+    EventSchema {
+        name: "webhooks/app-uninstalled",
+        kind: "forwarded_webhook",
+        description: FORWARDED_DESCRIPTION,
+        topics: &["app/uninstalled"],
+        generate: || schema_for!(AppUninstalledWebhook),
+    },
+    EventSchema {
+        name: "queue/webhook",
+        kind: "queue_message",
+        description: "Verified delivery handed to the webhook workers. `payload` is the Shopify body for `topic`.",
+        topics: &[],
+        generate: || schema_for!(QueuedWebhook),
+    },
+];
+
+pub fn find_event_schema(name: &str) -> Option<&'static EventSchema> {
+    let name = name.trim_start_matches('/').trim_end_matches(".json");
+    EVENT_SCHEMAS.iter().find(|schema| schema.name == name)
+}
+
+// =============================================================================
+// Schema Handlers
+// =============================================================================
+
+/// Lists the JSON Schemas for events this app emits.
+#[utoipa::path(
+    get,
+    path = "/schemas",
+    tag = "schemas",
+    responses(
+        (status = 200, description = "Schemas for the events this app emits", body = crate::openapi::EventSchemaList),
+    ),
+)]
+pub async fn list_schemas_handler() -> AppResult<impl IntoResponse> {
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "schemas_count": EVENT_SCHEMAS.len(),
+        "schemas": EVENT_SCHEMAS
+            .iter()
+            .map(|schema| serde_json::json!({
+                "name": schema.name,
+                "kind": schema.kind,
+                "description": schema.description,
+                "topics": schema.topics,
+                "url": format!("/schemas/{}", schema.name)
+            }))
+            .collect::<Vec<_>>()
+    }))))
+}
+
+/// One event's JSON Schema.
+#[utoipa::path(
+    get,
+    path = "/schemas/{name}",
+    tag = "schemas",
+    params(("name" = String, Path, description = "Schema name, with or without `.json`")),
+    responses(
+        (status = 200, description = "A JSON Schema document", body = Object),
+        (status = 404, description = "No such schema", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn schema_handler(Path(name): Path<String>) -> AppResult<impl IntoResponse> {
+    let schema = find_event_schema(&name)
+        .ok_or_else(|| AppError::NotFound(format!("No schema named {}", name)))?;
+
+    Ok((StatusCode::OK, Json(schema.schema())))
+}
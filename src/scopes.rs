@@ -0,0 +1,252 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::{
+    AppState,
+    token_store::TokenStore,
+    error::{AppError, AppResult},
+};
+
+// =============================================================================
+// Access Scope Registry
+// =============================================================================
+
+/// Shopify access scopes this app knows how to use. `SHOPIFY_SCOPES` and the
+/// `/auth?scopes=` override are checked against this list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccessScope {
+    ReadOrders,
+    WriteOrders,
+    ReadCheckouts,
+    WriteCheckouts,
+    ReadProducts,
+    WriteProducts,
+    ReadCustomers,
+    WriteCustomers,
+    ReadInventory,
+    WriteInventory,
+    ReadLocations,
+    ReadFulfillments,
+    WriteFulfillments,
+    ReadMerchantManagedFulfillmentOrders,
+    WriteMerchantManagedFulfillmentOrders,
+    ReadGiftCards,
+    WriteGiftCards,
+    ReadScriptTags,
+    WriteScriptTags,
+    ReadThemes,
+    WriteThemes,
+    ReadShopifyPaymentsPayouts,
+    ReadMarkets,
+    UnauthenticatedReadProductListings,
+    UnauthenticatedReadProductInventory,
+    UnauthenticatedReadCheckouts,
+    UnauthenticatedWriteCheckouts,
+    UnauthenticatedReadCustomers,
+    UnauthenticatedWriteCustomers,
+}
+
+impl AccessScope {
+    pub const ALL: &'static [AccessScope] = &[
+        Self::ReadOrders,
+        Self::WriteOrders,
+        Self::ReadCheckouts,
+        Self::WriteCheckouts,
+        Self::ReadProducts,
+        Self::WriteProducts,
+        Self::ReadCustomers,
+        Self::WriteCustomers,
+        Self::ReadInventory,
+        Self::WriteInventory,
+        Self::ReadLocations,
+        Self::ReadFulfillments,
+        Self::WriteFulfillments,
+        Self::ReadMerchantManagedFulfillmentOrders,
+        Self::WriteMerchantManagedFulfillmentOrders,
+        Self::ReadGiftCards,
+        Self::WriteGiftCards,
+        Self::ReadScriptTags,
+        Self::WriteScriptTags,
+        Self::ReadThemes,
+        Self::WriteThemes,
+        Self::ReadShopifyPaymentsPayouts,
+        Self::ReadMarkets,
+        Self::UnauthenticatedReadProductListings,
+        Self::UnauthenticatedReadProductInventory,
+        Self::UnauthenticatedReadCheckouts,
+        Self::UnauthenticatedWriteCheckouts,
+        Self::UnauthenticatedReadCustomers,
+        Self::UnauthenticatedWriteCustomers,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ReadOrders => "read_orders",
+            Self::WriteOrders => "write_orders",
+            Self::ReadCheckouts => "read_checkouts",
+            Self::WriteCheckouts => "write_checkouts",
+            Self::ReadProducts => "read_products",
+            Self::WriteProducts => "write_products",
+            Self::ReadCustomers => "read_customers",
+            Self::WriteCustomers => "write_customers",
+            Self::ReadInventory => "read_inventory",
+            Self::WriteInventory => "write_inventory",
+            Self::ReadLocations => "read_locations",
+            Self::ReadFulfillments => "read_fulfillments",
+            Self::WriteFulfillments => "write_fulfillments",
+            Self::ReadMerchantManagedFulfillmentOrders => "read_merchant_managed_fulfillment_orders",
+            Self::WriteMerchantManagedFulfillmentOrders => "write_merchant_managed_fulfillment_orders",
+            // Only granted to Shopify Plus stores
+            Self::ReadGiftCards => "read_gift_cards",
+            Self::WriteGiftCards => "write_gift_cards",
+            Self::ReadScriptTags => "read_script_tags",
+            Self::WriteScriptTags => "write_script_tags",
+            Self::ReadThemes => "read_themes",
+            Self::WriteThemes => "write_themes",
+            Self::ReadShopifyPaymentsPayouts => "read_shopify_payments_payouts",
+            Self::ReadMarkets => "read_markets",
+            // Carried over to the Storefront API tokens the app creates
+            Self::UnauthenticatedReadProductListings => "unauthenticated_read_product_listings",
+            Self::UnauthenticatedReadProductInventory => "unauthenticated_read_product_inventory",
+            Self::UnauthenticatedReadCheckouts => "unauthenticated_read_checkouts",
+            Self::UnauthenticatedWriteCheckouts => "unauthenticated_write_checkouts",
+            Self::UnauthenticatedReadCustomers => "unauthenticated_read_customers",
+            Self::UnauthenticatedWriteCustomers => "unauthenticated_write_customers",
+        }
+    }
+}
+
+impl fmt::Display for AccessScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AccessScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| format!("Unknown access scope: {}", s))
+    }
+}
+
+/// Parses a comma-separated scope list, dropping duplicates and blanks.
+pub fn parse_scopes(list: &str) -> Result<Vec<AccessScope>, String> {
+    let mut scopes = Vec::new();
+    for scope in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let scope: AccessScope = scope.parse()?;
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    if scopes.is_empty() {
+        return Err("At least one access scope is required".to_string());
+    }
+    Ok(scopes)
+}
+
+/// Joins scopes into the comma-separated form Shopify expects.
+pub fn scope_list(scopes: &[AccessScope]) -> String {
+    scopes.iter().map(|scope| scope.as_str()).collect::<Vec<_>>().join(",")
+}
+
+/// Whether `required` is among the comma-separated `granted` scopes. A
+/// `write_` scope implies the matching `read_` scope.
+pub fn has_scope(granted: &str, required: &str) -> bool {
+    let implied = required.strip_prefix("read_").map(|resource| format!("write_{}", resource));
+
+    granted
+        .split(',')
+        .map(str::trim)
+        .any(|scope| scope == required || Some(scope) == implied.as_deref())
+}
+
+/// Required scopes the comma-separated `granted` list doesn't cover.
+pub fn ungranted_scopes(granted: &str, required: &[AccessScope]) -> Vec<AccessScope> {
+    required
+        .iter()
+        .copied()
+        .filter(|scope| !has_scope(granted, scope.as_str()))
+        .collect()
+}
+
+/// `/auth` link that asks for the configured scopes plus the missing ones,
+/// so reauthorizing actually fixes the 403.
+pub fn reauthorize_url(configured: &str, missing: &[AccessScope]) -> String {
+    let mut scopes = parse_scopes(configured).unwrap_or_default();
+    for scope in missing {
+        if !scopes.contains(scope) {
+            scopes.push(*scope);
+        }
+    }
+    format!("/auth?scopes={}", urlencoding::encode(&scope_list(&scopes)))
+}
+
+// =============================================================================
+// Scope Guard Middleware
+// =============================================================================
+
+/// Scopes a route needs, checked against the stored token's grant before the
+/// handler runs. Attach with `route_layer` on the route's method router.
+#[derive(Clone)]
+pub struct ScopeGuard {
+    token_store: Arc<dyn TokenStore>,
+    shop: String,
+    configured_scopes: String,
+    required: &'static [AccessScope],
+}
+
+impl ScopeGuard {
+    pub fn new(state: &AppState, required: &'static [AccessScope]) -> Self {
+        Self {
+            token_store: state.token_store.clone(),
+            shop: state.config.shop.clone(),
+            configured_scopes: state.config.scopes.clone(),
+            required,
+        }
+    }
+
+    async fn check(&self) -> AppResult<()> {
+        self.check_scopes(self.required).await
+    }
+
+    /// Checks `required` instead of the guard's own scopes, for handlers
+    /// whose scopes depend on the request.
+    pub async fn check_scopes(&self, required: &[AccessScope]) -> AppResult<()> {
+        let granted = self.token_store.get_scope(&self.shop).await?.ok_or(AppError::MissingToken)?;
+
+        let missing = ungranted_scopes(&granted, required);
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        warn!("🔐 {} is missing access scopes {}", self.shop, scope_list(&missing));
+        Err(AppError::MissingScopes {
+            missing: missing.iter().map(ToString::to_string).collect(),
+            reauthorize_url: reauthorize_url(&self.configured_scopes, &missing),
+        })
+    }
+}
+
+pub async fn scope_guard_middleware(
+    State(guard): State<ScopeGuard>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match guard.check().await {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
@@ -0,0 +1,501 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    require_token,
+    database::QueuedJob,
+    error::{AppError, AppResult, ShopifyError},
+    http_client::ShopifyClient,
+    job_queue::{JobHandlerResult, JobKind, ShopOrdering},
+};
+
+/// Where Shopify may show a script tag.
+pub const DISPLAY_SCOPES: [&str; 3] = ["online_store", "order_status", "all"];
+
+/// Theme directories assets can be written to.
+const ASSET_DIRECTORIES: [&str; 8] = ["assets/", "blocks/", "config/", "layout/", "locales/", "sections/", "snippets/", "templates/"];
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptTagConfig {
+    /// Script URLs every shop gets as script tags once it installs the app
+    pub install_srcs: Vec<String>,
+    /// Where those scripts are shown
+    pub display_scope: String,
+}
+
+impl Default for ScriptTagConfig {
+    fn default() -> Self {
+        Self { install_srcs: Vec::new(), display_scope: "online_store".to_string() }
+    }
+}
+
+impl ScriptTagConfig {
+    /// `INSTALL_SCRIPT_TAGS` (comma-separated https URLs) and
+    /// `INSTALL_SCRIPT_TAG_DISPLAY_SCOPE`.
+    pub fn from_env() -> AppResult<Self> {
+        Self::parse(
+            std::env::var("INSTALL_SCRIPT_TAGS").ok().as_deref(),
+            std::env::var("INSTALL_SCRIPT_TAG_DISPLAY_SCOPE").ok().as_deref(),
+        )
+    }
+
+    pub fn parse(srcs: Option<&str>, display_scope: Option<&str>) -> AppResult<Self> {
+        let install_srcs: Vec<String> = srcs
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|src| !src.is_empty())
+            .map(str::to_string)
+            .collect();
+        if let Some(src) = install_srcs.iter().find(|src| check_script_src(src).is_err()) {
+            return Err(AppError::Config(format!("INSTALL_SCRIPT_TAGS must be https URLs, got {}", src)));
+        }
+
+        let display_scope = display_scope.map(str::trim).filter(|v| !v.is_empty()).unwrap_or("online_store");
+        check_display_scope(display_scope)
+            .map_err(|_| AppError::Config(format!("INSTALL_SCRIPT_TAG_DISPLAY_SCOPE must be one of {}", DISPLAY_SCOPES.join(", "))))?;
+
+        Ok(Self { install_srcs, display_scope: display_scope.to_string() })
+    }
+}
+
+// =============================================================================
+// Script Tag Structures
+// =============================================================================
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ScriptTag {
+    pub id: u64,
+    pub src: String,
+    pub event: String,
+    pub display_scope: String,
+    #[serde(default)]
+    pub cache: bool,
+    pub created_at: String,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ScriptTagsResponse {
+    pub script_tags: Vec<ScriptTag>,
+}
+
+#[derive(Deserialize)]
+pub struct ScriptTagResponse {
+    pub script_tag: ScriptTag,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ScriptTagParams {
+    /// Only script tags loading this URL
+    pub src: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// Body for `POST /api/script-tags`
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateScriptTagRequest {
+    /// https URL of the script
+    pub src: String,
+    /// online_store (the default), order_status or all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_scope: Option<String>,
+    /// Let Shopify's CDN cache the script
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<bool>,
+}
+
+impl CreateScriptTagRequest {
+    pub fn validate(&self) -> AppResult<()> {
+        check_script_src(&self.src)?;
+        if let Some(ref display_scope) = self.display_scope {
+            check_display_scope(display_scope)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_script_src(src: &str) -> AppResult<()> {
+    match url::Url::parse(src) {
+        Ok(url) if url.scheme() == "https" && url.host_str().is_some() => Ok(()),
+        _ => Err(AppError::BadRequest("src must be an https URL".to_string())),
+    }
+}
+
+fn check_display_scope(display_scope: &str) -> AppResult<()> {
+    if DISPLAY_SCOPES.contains(&display_scope) {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!("display_scope must be one of {}", DISPLAY_SCOPES.join(", "))))
+    }
+}
+
+// =============================================================================
+// Theme Asset Structures
+// =============================================================================
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ThemeAsset {
+    pub key: String,
+    #[serde(default)]
+    pub theme_id: Option<u64>,
+    #[serde(default)]
+    pub public_url: Option<String>,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub checksum: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ThemeAssetResponse {
+    pub asset: ThemeAsset,
+}
+
+#[derive(Deserialize)]
+struct ThemeId {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct ThemesResponse {
+    themes: Vec<ThemeId>,
+}
+
+/// Body for `PUT /api/themes/{theme}/assets`: the asset's key and exactly one
+/// of its contents, a base64 attachment, a URL to fetch it from, or another
+/// asset to copy.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ThemeAssetUpload {
+    /// e.g. `assets/tracking.js` or `snippets/tracking.liquid`
+    pub key: String,
+    /// Text contents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// Base64-encoded binary contents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<String>,
+    /// URL Shopify downloads the contents from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub src: Option<String>,
+    /// Key of an existing asset to copy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_key: Option<String>,
+}
+
+impl ThemeAssetUpload {
+    pub fn validate(&self) -> AppResult<()> {
+        check_asset_key(&self.key)?;
+        let sources = [&self.value, &self.attachment, &self.src, &self.source_key].iter().filter(|s| s.is_some()).count();
+        if sources != 1 {
+            return Err(AppError::BadRequest(
+                "Exactly one of value, attachment, src or source_key is required".to_string(),
+            ));
+        }
+        if let Some(ref source_key) = self.source_key {
+            check_asset_key(source_key)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_asset_key(key: &str) -> AppResult<()> {
+    let in_directory = ASSET_DIRECTORIES.iter().any(|dir| key.strip_prefix(dir).is_some_and(|name| !name.is_empty()));
+    if !in_directory || key.split('/').any(|part| part.is_empty() || part == "..") {
+        return Err(AppError::BadRequest(format!(
+            "Asset keys must name a file under {}",
+            ASSET_DIRECTORIES.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+// =============================================================================
+// Script Tag Handlers
+// =============================================================================
+
+/// Lists the app's script tags.
+#[utoipa::path(
+    get,
+    path = "/api/script-tags",
+    tag = "storefront",
+    params(ScriptTagParams),
+    responses(
+        (status = 200, description = "The app's script tags", body = crate::openapi::ScriptTagList),
+    ),
+)]
+pub async fn script_tags_handler(
+    Query(params): Query<ScriptTagParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let script_tags = fetch_script_tags(&state.shopify, &token, params.src.as_deref(), params.limit).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "script_tags_count": script_tags.len(),
+        "script_tags": script_tags
+    }))))
+}
+
+/// Adds a script tag, loading `src` on the shop's storefront pages.
+#[utoipa::path(
+    post,
+    path = "/api/script-tags",
+    tag = "storefront",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Makes retries with the same key and body return the first response")),
+    request_body = CreateScriptTagRequest,
+    responses(
+        (status = 201, description = "The new script tag", body = crate::openapi::ScriptTagChange),
+        (status = 400, description = "Not an https URL, or an unknown display scope", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn create_script_tag_handler(
+    State(state): State<AppState>,
+    Json(request): Json<CreateScriptTagRequest>,
+) -> AppResult<impl IntoResponse> {
+    request.validate()?;
+    let token = require_token(&state.token_store, &state.config.shop).await?;
+
+    let script_tag = create_script_tag(&state.shopify, &token, &request).await?;
+    info!("📜 Added script tag {} loading {}", script_tag.id, script_tag.src);
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({
+        "success": true,
+        "script_tag": script_tag
+    }))))
+}
+
+/// Removes a script tag.
+#[utoipa::path(
+    delete,
+    path = "/api/script-tags/{id}",
+    tag = "storefront",
+    params(("id" = u64, Path, description = "Script tag ID")),
+    responses(
+        (status = 200, description = "The script tag was removed", body = crate::openapi::ScriptTagDeleted),
+        (status = 404, description = "No such script tag", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn delete_script_tag_handler(
+    Path(script_tag_id): Path<u64>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let token = require_token(&state.token_store, &state.config.shop).await?;
+
+    state.shopify
+        .delete_with_auth(&format!("script_tags/{}.json", script_tag_id), &token)
+        .await
+        .map_err(|e| match e {
+            ShopifyError::NotFound => AppError::NotFound(format!("Script tag {} not found", script_tag_id)),
+            e => {
+                error!("Failed to delete script tag {}: {}", script_tag_id, e);
+                e.into()
+            }
+        })?;
+    info!("📜 Removed script tag {}", script_tag_id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "deleted_id": script_tag_id
+    }))))
+}
+
+// =============================================================================
+// Theme Asset Handlers
+// =============================================================================
+
+/// Creates or replaces a theme asset. `theme` is a theme ID, or `main` for
+/// the published theme.
+#[utoipa::path(
+    put,
+    path = "/api/themes/{theme}/assets",
+    tag = "storefront",
+    params(("theme" = String, Path, description = "Theme ID, or `main` for the published theme")),
+    request_body = ThemeAssetUpload,
+    responses(
+        (status = 200, description = "The stored asset", body = crate::openapi::ThemeAssetChange),
+        (status = 400, description = "Bad key, or not exactly one source for the contents", body = crate::openapi::ErrorResponse),
+        (status = 404, description = "No such theme", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn put_theme_asset_handler(
+    Path(theme): Path<String>,
+    State(state): State<AppState>,
+    Json(upload): Json<ThemeAssetUpload>,
+) -> AppResult<impl IntoResponse> {
+    upload.validate()?;
+    let token = require_token(&state.token_store, &state.config.shop).await?;
+
+    let theme_id = resolve_theme(&state.shopify, &token, &theme).await?;
+    let body = serde_json::json!({ "asset": upload });
+    let response: ThemeAssetResponse = state.shopify
+        .put_with_auth(&format!("themes/{}/assets.json", theme_id), &token, &body)
+        .await
+        .map_err(|e| match e {
+            ShopifyError::NotFound => AppError::NotFound(format!("Theme {} not found", theme_id)),
+            e => {
+                error!("Failed to upload {} to theme {}: {}", upload.key, theme_id, e);
+                e.into()
+            }
+        })?;
+    info!("🎨 Uploaded {} to theme {}", response.asset.key, theme_id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "theme_id": theme_id,
+        "asset": response.asset
+    }))))
+}
+
+async fn resolve_theme(client: &ShopifyClient, token: &str, theme: &str) -> AppResult<u64> {
+    if theme != "main" {
+        return theme
+            .parse()
+            .map_err(|_| AppError::BadRequest("theme must be a theme ID or main".to_string()));
+    }
+    let response = client
+        .get_with_auth::<ThemesResponse>("themes.json", token, Some(&[("role", "main"), ("fields", "id")]))
+        .await?;
+    response.data.themes.first()
+        .map(|theme| theme.id)
+        .ok_or_else(|| AppError::NotFound("The shop has no published theme".to_string()))
+}
+
+// =============================================================================
+// Script Tag Functions
+// =============================================================================
+
+async fn fetch_script_tags(
+    client: &ShopifyClient,
+    token: &str,
+    src: Option<&str>,
+    limit: Option<u32>,
+) -> Result<Vec<ScriptTag>, ShopifyError> {
+    let limit = limit.unwrap_or(250).min(250).to_string();
+    let mut query_params = vec![("limit", limit.as_str())];
+    if let Some(src) = src {
+        query_params.push(("src", src));
+    }
+    let response = client
+        .get_with_auth::<ScriptTagsResponse>("script_tags.json", token, Some(query_params.as_slice()))
+        .await?;
+    Ok(response.data.script_tags)
+}
+
+async fn create_script_tag(
+    client: &ShopifyClient,
+    token: &str,
+    request: &CreateScriptTagRequest,
+) -> Result<ScriptTag, ShopifyError> {
+    let body = serde_json::json!({
+        "script_tag": {
+            "event": "onload",
+            "src": request.src,
+            "display_scope": request.display_scope.as_deref().unwrap_or("online_store"),
+            "cache": request.cache.unwrap_or(false),
+        }
+    });
+    let response: ScriptTagResponse = client
+        .post_with_auth("script_tags.json", token, &body)
+        .await
+        .map_err(|e| {
+            error!("Failed to create script tag for {}: {}", request.src, e);
+            e
+        })?;
+    Ok(response.script_tag)
+}
+
+/// Adds the `INSTALL_SCRIPT_TAGS` `shop` doesn't load yet, leaving other
+/// script tags alone. Returns the number created.
+pub async fn ensure_install_script_tags(
+    shopify: &ShopifyClient,
+    shop: &str,
+    token: &str,
+    config: &ScriptTagConfig,
+) -> AppResult<usize> {
+    let client = shopify.for_shop(shop);
+    let existing = fetch_script_tags(&client, token, None, None).await?;
+
+    let mut created = 0;
+    for src in config.install_srcs.iter().filter(|src| !existing.iter().any(|tag| &tag.src == *src)) {
+        let request = CreateScriptTagRequest {
+            src: src.clone(),
+            display_scope: Some(config.display_scope.clone()),
+            cache: None,
+        };
+        let script_tag = create_script_tag(&client, token, &request).await?;
+        info!("📜 Added script tag {} loading {} on {}", script_tag.id, src, shop);
+        created += 1;
+    }
+    Ok(created)
+}
+
+// =============================================================================
+// Install Jobs
+// =============================================================================
+
+/// Job kind adding `INSTALL_SCRIPT_TAGS` to a newly installed shop.
+pub const SHOP_SCRIPT_TAGS_JOB: &str = "shop-script-tags";
+
+pub fn shop_script_tags_job_kind() -> JobKind {
+    JobKind::new(SHOP_SCRIPT_TAGS_JOB, ShopOrdering::Single, |state: AppState, job: QueuedJob| async move {
+        run_install_script_tags(&state, job.shop_domain.as_deref()).await
+    })
+}
+
+async fn run_install_script_tags(state: &AppState, shop: Option<&str>) -> JobHandlerResult {
+    let shop = shop.ok_or("Script tag job has no shop")?;
+    let token = state.token_store.get_token(shop).await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Shop {} is not installed", shop))?;
+
+    ensure_install_script_tags(&state.shopify, shop, &token, &state.config.script_tags)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Adds `INSTALL_SCRIPT_TAGS` to a shop that just installed the app: through
+/// the job queue, which retries, when Postgres is available, otherwise once
+/// in the background.
+pub async fn queue_install_script_tags(state: &AppState, shop: &str) {
+    if state.config.script_tags.install_srcs.is_empty() {
+        return;
+    }
+    if state.job_queue.is_enabled() {
+        match state.job_queue.enqueue(SHOP_SCRIPT_TAGS_JOB, Some(shop), serde_json::json!({})).await {
+            Ok(_) => return,
+            Err(e) => error!("Failed to queue script tags for {}, adding them directly: {}", shop, e),
+        }
+    }
+
+    let (state, shop) = (state.clone(), shop.to_string());
+    tokio::spawn(async move {
+        if let Err(e) = run_install_script_tags(&state, Some(&shop)).await {
+            warn!("Couldn't add script tags to {}: {}", shop, e);
+        }
+    });
+}
@@ -0,0 +1,130 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, error};
+use utoipa::ToSchema;
+
+use crate::{
+    AppState,
+    require_token,
+    error::AppResult,
+    scopes::has_scope,
+};
+
+// =============================================================================
+// Shop Structures
+// =============================================================================
+
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema)]
+#[serde(default)]
+pub struct Shop {
+    pub id: u64,
+    pub name: String,
+    pub email: Option<String>,
+    pub shop_owner: Option<String>,
+    pub domain: Option<String>,
+    pub myshopify_domain: String,
+    pub plan_name: Option<String>,
+    pub plan_display_name: Option<String>,
+    pub currency: Option<String>,
+    pub enabled_presentment_currencies: Vec<String>,
+    pub country_code: Option<String>,
+    pub province_code: Option<String>,
+    pub iana_timezone: Option<String>,
+    pub primary_locale: Option<String>,
+    pub weight_unit: Option<String>,
+    pub taxes_included: Option<bool>,
+    pub password_enabled: Option<bool>,
+    pub checkout_api_supported: Option<bool>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ShopResponse {
+    pub shop: Shop,
+}
+
+/// Requested scopes that the granted list doesn't cover, honouring
+/// `write_` scopes implying their `read_` counterpart.
+pub fn missing_scopes(requested: &str, granted: &[String]) -> Vec<String> {
+    let granted = granted.join(",");
+
+    requested
+        .split(',')
+        .map(str::trim)
+        .filter(|scope| !scope.is_empty() && !has_scope(&granted, scope))
+        .map(str::to_string)
+        .collect()
+}
+
+// =============================================================================
+// Shop Handlers
+// =============================================================================
+
+/// The configured shop's details from Shopify.
+#[utoipa::path(
+    get,
+    path = "/api/shop",
+    tag = "shops",
+    responses(
+        (status = 200, description = "The shop's settings", body = crate::openapi::ShopDetail),
+    ),
+)]
+pub async fn shop_handler(
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let response = state.shopify
+        .get_with_auth::<ShopResponse>("shop.json", &token, None)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch shop: {}", e);
+            e
+        })?;
+    info!("Successfully fetched shop details for {}", shop);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": response.data.shop
+    }))))
+}
+
+/// Scopes Shopify reports for the stored token, compared with what the app
+/// requests, for diagnosing 403s from missing scopes.
+#[utoipa::path(
+    get,
+    path = "/api/access-scopes",
+    tag = "shops",
+    responses(
+        (status = 200, description = "Granted, stored and requested scopes", body = crate::openapi::AccessScopes),
+    ),
+)]
+pub async fn access_scopes_handler(
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let granted = state.shopify.fetch_access_scopes(&token).await.map_err(|e| {
+        error!("Failed to fetch access scopes: {}", e);
+        e
+    })?;
+    let stored = state.token_store.get_scope(shop).await?;
+    let missing = missing_scopes(&state.config.scopes, &granted);
+    info!("🔐 {} has {} access scopes, {} requested scopes missing", shop, granted.len(), missing.len());
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "access_scopes": granted,
+        "stored_scope": stored,
+        "requested_scope": state.config.scopes,
+        "missing_scopes": missing,
+        "reauthorize_url": if missing.is_empty() { None } else { Some("/auth") }
+    }))))
+}
@@ -0,0 +1,185 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{AppState, error::{AppError, AppResult}};
+
+// =============================================================================
+// Integration Secrets
+// =============================================================================
+
+/// Credentials an integration keeps per shop in `ShopSecretStore`.
+///
+/// Each type is stored as encrypted JSON under its own `INTEGRATION` name, so
+/// integrations read and write their own struct rather than raw strings.
+pub trait IntegrationSecret: Serialize + DeserializeOwned {
+    const INTEGRATION: &'static str;
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub struct KlaviyoCredentials {
+    pub private_api_key: String,
+}
+
+impl IntegrationSecret for KlaviyoCredentials {
+    const INTEGRATION: &'static str = "klaviyo";
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub struct ThirdPartyLogisticsCredentials {
+    pub base_url: String,
+    pub account_id: String,
+    pub api_key: String,
+}
+
+impl IntegrationSecret for ThirdPartyLogisticsCredentials {
+    const INTEGRATION: &'static str = "3pl";
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub struct SmtpCredentials {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub from_address: Option<String>,
+}
+
+impl IntegrationSecret for SmtpCredentials {
+    const INTEGRATION: &'static str = "smtp";
+}
+
+/// Secret a shop's webhooks are signed with, for custom apps created per shop;
+/// shops without one are verified with `API_SECRET`.
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookSecret {
+    pub api_secret: String,
+}
+
+impl IntegrationSecret for WebhookSecret {
+    const INTEGRATION: &'static str = "webhook";
+}
+
+/// A shop's Storefront API access token, created through the Admin API for
+/// headless storefronts.
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct StorefrontToken {
+    pub id: u64,
+    pub access_token: String,
+    /// Comma-separated `unauthenticated_*` scopes the token carries
+    pub access_scope: String,
+    pub title: String,
+    pub created_at: String,
+}
+
+impl IntegrationSecret for StorefrontToken {
+    const INTEGRATION: &'static str = "storefront";
+}
+
+// Credentials never end up in logs through Debug
+macro_rules! redacted_debug {
+    ($($ty:ty),*) => {
+        $(impl std::fmt::Debug for $ty {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{} {{ [REDACTED] }}", stringify!($ty))
+            }
+        })*
+    };
+}
+
+redacted_debug!(KlaviyoCredentials, ThirdPartyLogisticsCredentials, SmtpCredentials, WebhookSecret, StorefrontToken);
+
+// =============================================================================
+// Shop Secret Handlers
+// =============================================================================
+
+/// Lists which integrations have credentials for a shop, without the values.
+#[utoipa::path(
+    get,
+    path = "/admin/shops/{shop}/secrets",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 200, description = "Stored integrations, without their values", body = crate::openapi::ShopSecretList),
+    ),
+)]
+pub async fn list_shop_secrets_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let secrets = state.shop_secrets.list(&shop).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "secrets_count": secrets.len(),
+        "secrets": secrets
+    }))))
+}
+
+/// Stores or rotates the secret a shop's webhooks are verified with.
+#[utoipa::path(
+    put,
+    path = "/admin/shops/{shop}/secrets/webhook",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    request_body = WebhookSecret,
+    responses(
+        (status = 200, description = "Secret stored", body = crate::openapi::WebhookSecretUpdated),
+        (status = 400, description = "Empty secret", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn put_webhook_secret_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+    Json(secret): Json<WebhookSecret>,
+) -> AppResult<impl IntoResponse> {
+    if secret.api_secret.trim().is_empty() {
+        return Err(AppError::BadRequest("api_secret must not be empty".to_string()));
+    }
+    state.shop_secrets.put(&shop, &secret).await?;
+    info!("🪝 Webhooks from {} are now verified with their own secret", shop);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "shop": shop,
+        "integration": WebhookSecret::INTEGRATION
+    }))))
+}
+
+/// Removes a shop's webhook secret, so its webhooks are verified with
+/// `API_SECRET` again.
+#[utoipa::path(
+    delete,
+    path = "/admin/shops/{shop}/secrets/webhook",
+    tag = "shops",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 200, description = "Secret removed", body = crate::openapi::WebhookSecretDeleted),
+        (status = 404, description = "The shop has no webhook secret", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn delete_webhook_secret_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    if !state.shop_secrets.delete::<WebhookSecret>(&shop).await? {
+        return Err(AppError::NotFound(format!("Shop {} has no webhook secret", shop)));
+    }
+    info!("🪝 Webhooks from {} are verified with the app secret again", shop);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "shop": shop,
+        "deleted": true
+    }))))
+}
@@ -1,19 +1,29 @@
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
-use serde::{Deserialize, Serialize};
-use tracing::{info, warn, error};
+use futures::{pin_mut, Stream, TryStreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{info, error};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::{AppState, http_client::ShopifyClient};
+use crate::{
+    AppState,
+    require_token,
+    csv_response::{full_name, list_format, optional, CsvRecord, ListFormat},
+    downloads::list_export,
+    error::{AppError, AppResult, ShopifyError},
+    http_client::{next_page_url, PaginatedResponse, ShopifyClient},
+    order_sync::local_orders,
+};
 
 // =============================================================================
 // Product Structures
 // =============================================================================
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct Product {
     pub id: u64,
     pub title: String,
@@ -31,7 +41,7 @@ pub struct Product {
     pub options: Vec<ProductOption>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct ProductVariant {
     pub id: u64,
     pub product_id: u64,
@@ -60,7 +70,7 @@ pub struct ProductVariant {
     pub requires_shipping: bool,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct ProductImage {
     pub id: u64,
     pub product_id: u64,
@@ -74,7 +84,7 @@ pub struct ProductImage {
     pub variant_ids: Vec<u64>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct ProductOption {
     pub id: u64,
     pub product_id: u64,
@@ -88,7 +98,8 @@ pub struct ProductsResponse {
     pub products: Vec<Product>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ProductParams {
     pub limit: Option<u32>,
     pub since_id: Option<u64>,
@@ -103,13 +114,288 @@ pub struct ProductParams {
     pub published_at_max: Option<String>,
     pub published_status: Option<String>,
     pub fields: Option<String>,
+    pub page_info: Option<String>,
+    pub all: Option<bool>,
+    /// `csv` (one row per variant) or `ndjson` streams every matching product
+    pub format: Option<String>,
+    /// With an export format, write the export to a file and return a signed
+    /// `/downloads` link to it instead
+    pub link: Option<bool>,
 }
 
 // =============================================================================
-// Customer Structures
+// Order Structures
 // =============================================================================
 
+// Every field has a default so responses narrowed with `fields` still deserialize
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct Order {
+    pub id: u64,
+    pub name: String,
+    pub order_number: u64,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub created_at: String,
+    pub updated_at: Option<String>,
+    pub processed_at: Option<String>,
+    pub closed_at: Option<String>,
+    pub cancelled_at: Option<String>,
+    pub cancel_reason: Option<String>,
+    pub financial_status: Option<String>,
+    pub fulfillment_status: Option<String>,
+    pub currency: Option<String>,
+    pub total_price: String,
+    pub subtotal_price: Option<String>,
+    pub total_tax: Option<String>,
+    pub total_discounts: Option<String>,
+    pub total_weight: Option<u64>,
+    pub taxes_included: bool,
+    pub confirmed: bool,
+    pub test: bool,
+    pub tags: String,
+    pub note: Option<String>,
+    pub source_name: Option<String>,
+    pub payment_gateway_names: Vec<String>,
+    pub customer: Option<OrderCustomer>,
+    pub billing_address: Option<CustomerAddress>,
+    pub shipping_address: Option<CustomerAddress>,
+    pub line_items: Vec<OrderLineItem>,
+    pub shipping_lines: Vec<ShippingLine>,
+    pub discount_codes: Vec<DiscountCode>,
+    pub fulfillments: Vec<OrderFulfillment>,
+    pub refunds: Vec<OrderRefund>,
+}
+
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct OrderCustomer {
+    pub id: u64,
+    pub email: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub phone: Option<String>,
+    pub orders_count: Option<i32>,
+    pub total_spent: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct OrderLineItem {
+    pub id: u64,
+    pub product_id: Option<u64>,
+    pub variant_id: Option<u64>,
+    pub title: String,
+    pub variant_title: Option<String>,
+    pub sku: Option<String>,
+    pub vendor: Option<String>,
+    pub quantity: i32,
+    pub price: String,
+    pub total_discount: Option<String>,
+    pub fulfillable_quantity: Option<i32>,
+    pub fulfillment_status: Option<String>,
+    pub requires_shipping: bool,
+    pub taxable: bool,
+    pub gift_card: bool,
+}
+
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct ShippingLine {
+    pub id: u64,
+    pub title: String,
+    pub code: Option<String>,
+    pub price: String,
+    pub source: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct DiscountCode {
+    pub code: String,
+    pub amount: String,
+    #[serde(rename = "type")]
+    pub discount_type: String,
+}
+
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct OrderFulfillment {
+    pub id: u64,
+    pub status: Option<String>,
+    pub created_at: Option<String>,
+    pub tracking_company: Option<String>,
+    pub tracking_number: Option<String>,
+    pub tracking_numbers: Vec<String>,
+    pub tracking_urls: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct OrderRefund {
+    pub id: u64,
+    pub created_at: Option<String>,
+    pub note: Option<String>,
+    /// Money returned; `kind` is `refund` for the amounts that count
+    pub transactions: Vec<RefundTransaction>,
+}
+
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct RefundTransaction {
+    pub id: u64,
+    pub kind: Option<String>,
+    /// `success` once the money has moved
+    pub status: Option<String>,
+    pub amount: Option<String>,
+    pub currency: Option<String>,
+}
+
+/// Where an order was placed, grouped from Shopify's `source_name`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SalesChannel {
+    /// Online store and the Shopify mobile apps
+    Online,
+    Pos,
+    Draft,
+    /// Sales channel apps and custom sources
+    Other,
+}
+
+impl SalesChannel {
+    pub fn from_source_name(source_name: Option<&str>) -> Self {
+        match source_name.map(|s| s.trim().to_ascii_lowercase()).as_deref() {
+            Some("web") | Some("iphone") | Some("android") | None | Some("") => Self::Online,
+            Some("pos") => Self::Pos,
+            Some("shopify_draft_order") => Self::Draft,
+            Some(_) => Self::Other,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "online" => Some(Self::Online),
+            "pos" => Some(Self::Pos),
+            "draft" => Some(Self::Draft),
+            "other" => Some(Self::Other),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Online => "online",
+            Self::Pos => "pos",
+            Self::Draft => "draft",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl Order {
+    pub fn channel(&self) -> SalesChannel {
+        SalesChannel::from_source_name(self.source_name.as_deref())
+    }
+}
+
+/// Source filter for order listings. Shopify has no server-side `source_name`
+/// filter on the REST orders endpoint, so matching happens on each page.
+#[derive(Debug, Default, Clone)]
+pub struct SourceFilter {
+    source_names: Vec<String>,
+    channels: Vec<SalesChannel>,
+}
+
+impl SourceFilter {
+    /// `source_name` is a comma-separated list of raw values (e.g. `pos,web`);
+    /// `channel` a comma-separated list of online, pos, draft, or other.
+    pub fn parse(source_name: Option<&str>, channel: Option<&str>) -> Result<Self, String> {
+        let split = |value: Option<&str>| -> Vec<String> {
+            value
+                .unwrap_or_default()
+                .split(',')
+                .map(|v| v.trim().to_ascii_lowercase())
+                .filter(|v| !v.is_empty())
+                .collect()
+        };
+
+        let channels = split(channel)
+            .iter()
+            .map(|c| SalesChannel::parse(c).ok_or_else(|| format!("Unknown channel: {}", c)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            source_names: split(source_name),
+            channels,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.source_names.is_empty() && self.channels.is_empty()
+    }
+
+    pub fn matches(&self, order: &Order) -> bool {
+        let source = order.source_name.as_deref().unwrap_or_default().to_ascii_lowercase();
+        (self.source_names.is_empty() || self.source_names.contains(&source))
+            && (self.channels.is_empty() || self.channels.contains(&order.channel()))
+    }
+}
+
+/// Number of orders per channel, in channel order.
+pub fn channel_breakdown(orders: &[Order]) -> std::collections::BTreeMap<SalesChannel, usize> {
+    let mut counts = std::collections::BTreeMap::new();
+    for order in orders {
+        *counts.entry(order.channel()).or_insert(0) += 1;
+    }
+    counts
+}
+
 #[derive(Deserialize, Serialize)]
+pub struct OrdersResponse {
+    pub orders: Vec<Order>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OrderParams {
+    pub limit: Option<u32>,
+    pub since_id: Option<u64>,
+    /// Comma-separated order IDs
+    pub ids: Option<String>,
+    /// open, closed, cancelled, or any (default: any)
+    pub status: Option<String>,
+    pub financial_status: Option<String>,
+    pub fulfillment_status: Option<String>,
+    pub created_at_min: Option<String>,
+    pub created_at_max: Option<String>,
+    pub updated_at_min: Option<String>,
+    pub updated_at_max: Option<String>,
+    pub processed_at_min: Option<String>,
+    pub processed_at_max: Option<String>,
+    pub fields: Option<String>,
+    /// Comma-separated raw source names, e.g. `pos` or `web,iphone`
+    pub source_name: Option<String>,
+    /// Comma-separated channels: online, pos, draft, other
+    pub channel: Option<String>,
+    pub page_info: Option<String>,
+    pub all: Option<bool>,
+    /// Ask Shopify even when the local order copy could answer
+    pub live: Option<bool>,
+    /// low, medium or high, from risk assessment webhooks; needs the local order copy
+    pub risk: Option<String>,
+    /// `csv` (one row per line item) or `ndjson` streams every matching order
+    pub format: Option<String>,
+    /// With an export format, write the export to a file and return a signed
+    /// `/downloads` link to it instead
+    pub link: Option<bool>,
+}
+
+// =============================================================================
+// Customer Structures
+// =============================================================================
+
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct Customer {
     pub id: u64,
     pub email: Option<String>,
@@ -140,7 +426,7 @@ pub struct Customer {
     pub default_address: Option<CustomerAddress>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct CustomerAddress {
     pub id: Option<u64>,
     pub customer_id: Option<u64>,
@@ -162,14 +448,14 @@ pub struct CustomerAddress {
     pub is_default: Option<bool>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct EmailMarketingConsent {
     pub state: String,
     pub opt_in_level: String,
     pub consent_updated_at: Option<String>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct SmsMarketingConsent {
     pub state: String,
     pub opt_in_level: String,
@@ -182,7 +468,8 @@ pub struct CustomersResponse {
     pub customers: Vec<Customer>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct CustomerParams {
     pub limit: Option<u32>,
     pub since_id: Option<u64>,
@@ -191,13 +478,20 @@ pub struct CustomerParams {
     pub updated_at_min: Option<String>,
     pub updated_at_max: Option<String>,
     pub fields: Option<String>,
+    pub page_info: Option<String>,
+    pub all: Option<bool>,
+    /// `csv` (with the default address) or `ndjson` streams every matching customer
+    pub format: Option<String>,
+    /// With an export format, write the export to a file and return a signed
+    /// `/downloads` link to it instead
+    pub link: Option<bool>,
 }
 
 // =============================================================================
 // Inventory Structures
 // =============================================================================
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct InventoryLevel {
     pub inventory_item_id: u64,
     pub location_id: u64,
@@ -210,7 +504,8 @@ pub struct InventoryLevelsResponse {
     pub inventory_levels: Vec<InventoryLevel>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct InventoryParams {
     pub limit: Option<u32>,
     pub inventory_item_ids: Option<String>,
@@ -218,169 +513,1309 @@ pub struct InventoryParams {
     pub updated_at_min: Option<String>,
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct InventoryLevelResponse {
+    pub inventory_level: InventoryLevel,
+}
+
+/// Body for `POST /api/inventory/adjust`
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct InventoryAdjustRequest {
+    pub location_id: u64,
+    pub inventory_item_id: u64,
+    /// Positive to add stock, negative to remove it
+    pub available_adjustment: i32,
+}
+
+/// Body for `POST /api/inventory/set`
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct InventorySetRequest {
+    pub location_id: u64,
+    pub inventory_item_id: u64,
+    pub available: i32,
+    /// Disconnect the item from locations that can't stock it alongside this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disconnect_if_necessary: Option<bool>,
+}
+
+/// Body for `POST /api/inventory/connect`
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct InventoryConnectRequest {
+    pub location_id: u64,
+    pub inventory_item_id: u64,
+    /// Move the item off its current fulfillment service location if needed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relocate_if_necessary: Option<bool>,
+}
+
 // =============================================================================
-// API Handlers
+// Location Structures
 // =============================================================================
 
-pub async fn products_handler(
-    Query(params): Query<ProductParams>,
-    State(state): State<AppState>,
-) -> impl IntoResponse {
-    let shop = &state.config.shop;
-    
-    // Get stored access token
-    let token = match get_token(&state.token_store, shop).await {
-        Some(token) => token,
-        None => {
-            warn!("No access token found for shop: {}", shop);
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({
-                    "error": "No access token found. Please complete OAuth flow first.",
-                    "auth_url": "/auth"
-                })),
-            );
-        }
-    };
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct Location {
+    pub id: u64,
+    pub name: String,
+    pub address1: Option<String>,
+    pub address2: Option<String>,
+    pub city: Option<String>,
+    pub zip: Option<String>,
+    pub province: Option<String>,
+    pub province_code: Option<String>,
+    pub country: Option<String>,
+    pub country_code: Option<String>,
+    pub country_name: Option<String>,
+    pub phone: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Locations managed by a fulfillment service app
+    #[serde(default)]
+    pub legacy: bool,
+    #[serde(default)]
+    pub active: bool,
+    pub localized_country_name: Option<String>,
+    pub localized_province_name: Option<String>,
+}
 
-    // Fetch products from Shopify
-    match fetch_products(&token, shop, &params).await {
-        Ok(products) => {
-            info!("Successfully fetched {} products", products.len());
-            (StatusCode::OK, Json(serde_json::json!({
-                "shop": shop,
-                "products_count": products.len(),
-                "products": products
-            })))
-        }
-        Err(e) => {
-            error!("Failed to fetch products: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": "Failed to fetch products",
-                    "details": e.to_string()
-                })),
-            )
-        }
-    }
+#[derive(Deserialize, Serialize)]
+pub struct LocationsResponse {
+    pub locations: Vec<Location>,
 }
 
-pub async fn customers_handler(
-    Query(params): Query<CustomerParams>,
-    State(state): State<AppState>,
-) -> impl IntoResponse {
-    let shop = &state.config.shop;
-    
-    // Get stored access token
-    let token = match get_token(&state.token_store, shop).await {
-        Some(token) => token,
-        None => {
-            warn!("No access token found for shop: {}", shop);
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({
-                    "error": "No access token found. Please complete OAuth flow first.",
-                    "auth_url": "/auth"
-                })),
-            );
-        }
-    };
+#[derive(Deserialize, Serialize)]
+pub struct LocationResponse {
+    pub location: Location,
+}
 
-    // Fetch customers from Shopify
-    match fetch_customers(&token, shop, &params).await {
-        Ok(customers) => {
-            info!("Successfully fetched {} customers", customers.len());
-            (StatusCode::OK, Json(serde_json::json!({
-                "shop": shop,
-                "customers_count": customers.len(),
-                "customers": customers
-            })))
-        }
-        Err(e) => {
-            error!("Failed to fetch customers: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": "Failed to fetch customers",
-                    "details": e.to_string()
-                })),
-            )
-        }
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LocationParams {
+    /// Only return active locations
+    pub active: Option<bool>,
+}
+
+// =============================================================================
+// Fulfillment Structures
+// =============================================================================
+
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct Fulfillment {
+    pub id: u64,
+    pub order_id: u64,
+    /// pending, open, success, cancelled, error or failure
+    pub status: Option<String>,
+    /// Carrier-reported delivery status, e.g. in_transit or delivered
+    pub shipment_status: Option<String>,
+    pub name: Option<String>,
+    pub service: Option<String>,
+    pub location_id: Option<u64>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub tracking_company: Option<String>,
+    pub tracking_number: Option<String>,
+    pub tracking_numbers: Vec<String>,
+    pub tracking_url: Option<String>,
+    pub tracking_urls: Vec<String>,
+    pub line_items: Vec<FulfillmentLineItem>,
+}
+
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct FulfillmentLineItem {
+    pub id: u64,
+    pub variant_id: Option<u64>,
+    pub product_id: Option<u64>,
+    pub title: String,
+    pub sku: Option<String>,
+    pub quantity: i32,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct FulfillmentsResponse {
+    pub fulfillments: Vec<Fulfillment>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct FulfillmentResponse {
+    pub fulfillment: Fulfillment,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FulfillmentParams {
+    pub limit: Option<u32>,
+    pub since_id: Option<u64>,
+}
+
+/// A group of an order's line items that ship from one location. Shopify
+/// creates these when the order is placed; fulfillments are created against them.
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct FulfillmentOrder {
+    pub id: u64,
+    pub order_id: u64,
+    pub assigned_location_id: u64,
+    /// open, in_progress, scheduled, on_hold, incomplete, cancelled or closed
+    pub status: String,
+    pub request_status: Option<String>,
+    pub supported_actions: Vec<String>,
+    pub destination: Option<FulfillmentOrderDestination>,
+    pub line_items: Vec<FulfillmentOrderLineItem>,
+    pub fulfill_at: Option<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+impl FulfillmentOrder {
+    /// Whether a fulfillment can still be created against this order
+    pub fn is_fulfillable(&self) -> bool {
+        matches!(self.status.as_str(), "open" | "in_progress")
     }
 }
 
-pub async fn inventory_handler(
-    Query(params): Query<InventoryParams>,
-    State(state): State<AppState>,
-) -> impl IntoResponse {
-    let shop = &state.config.shop;
-    
-    // Get stored access token
-    let token = match get_token(&state.token_store, shop).await {
-        Some(token) => token,
-        None => {
-            warn!("No access token found for shop: {}", shop);
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({
-                    "error": "No access token found. Please complete OAuth flow first.",
-                    "auth_url": "/auth"
-                })),
-            );
-        }
-    };
+#[derive(Deserialize, Serialize, Default, Clone, ToSchema)]
+#[serde(default)]
+pub struct FulfillmentOrderDestination {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub company: Option<String>,
+    pub address1: Option<String>,
+    pub address2: Option<String>,
+    pub city: Option<String>,
+    pub province: Option<String>,
+    pub province_code: Option<String>,
+    pub zip: Option<String>,
+    pub country: Option<String>,
+    pub country_code: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
 
-    // Fetch inventory levels from Shopify
-    match fetch_inventory_levels(&token, shop, &params).await {
-        Ok(inventory_levels) => {
-            info!("Successfully fetched {} inventory levels", inventory_levels.len());
-            (StatusCode::OK, Json(serde_json::json!({
-                "shop": shop,
-                "inventory_levels_count": inventory_levels.len(),
-                "inventory_levels": inventory_levels
-            })))
+#[derive(Deserialize, Serialize, Default, ToSchema)]
+#[serde(default)]
+pub struct FulfillmentOrderLineItem {
+    pub id: u64,
+    pub line_item_id: u64,
+    pub inventory_item_id: u64,
+    pub variant_id: Option<u64>,
+    pub quantity: i32,
+    pub fulfillable_quantity: i32,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct FulfillmentOrdersResponse {
+    pub fulfillment_orders: Vec<FulfillmentOrder>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FulfillmentOrderParams {
+    /// Include closed and cancelled fulfillment orders
+    pub include_closed: Option<bool>,
+}
+
+/// Body for `POST /api/orders/{id}/fulfillments`
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema)]
+#[serde(default)]
+pub struct CreateFulfillmentRequest {
+    pub tracking_number: Option<String>,
+    pub tracking_company: Option<String>,
+    pub tracking_url: Option<String>,
+    /// Send Shopify's shipping confirmation email
+    pub notify_customer: bool,
+    /// What to fulfill; everything still fulfillable when empty
+    pub line_items_by_fulfillment_order: Vec<FulfillmentOrderLineItems>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct FulfillmentOrderLineItems {
+    pub fulfillment_order_id: u64,
+    /// Quantities per fulfillment order line item; the whole fulfillment order when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fulfillment_order_line_items: Vec<FulfillmentOrderLineItemQuantity>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct FulfillmentOrderLineItemQuantity {
+    pub id: u64,
+    pub quantity: i32,
+}
+
+impl CreateFulfillmentRequest {
+    /// Builds Shopify's `fulfillment` body against the order's fulfillment
+    /// orders, checking that any requested ones belong to the order and can
+    /// still be fulfilled.
+    pub fn to_shopify_body(&self, fulfillment_orders: &[FulfillmentOrder]) -> Result<serde_json::Value, AppError> {
+        let fulfillable = |id: u64| fulfillment_orders.iter().any(|fo| fo.id == id && fo.is_fulfillable());
+
+        let line_items_by_fulfillment_order: Vec<serde_json::Value> = if self.line_items_by_fulfillment_order.is_empty() {
+            fulfillment_orders
+                .iter()
+                .filter(|fo| fo.is_fulfillable())
+                .map(|fo| serde_json::json!({ "fulfillment_order_id": fo.id }))
+                .collect()
+        } else {
+            if let Some(requested) = self.line_items_by_fulfillment_order.iter().find(|r| !fulfillable(r.fulfillment_order_id)) {
+                return Err(AppError::BadRequest(format!(
+                    "Fulfillment order {} is not an open fulfillment order of this order",
+                    requested.fulfillment_order_id
+                )));
+            }
+            self.line_items_by_fulfillment_order
+                .iter()
+                .map(|r| serde_json::to_value(r).unwrap_or_default())
+                .collect()
+        };
+
+        if line_items_by_fulfillment_order.is_empty() {
+            return Err(AppError::Conflict("Order has nothing left to fulfill".to_string()));
         }
-        Err(e) => {
-            error!("Failed to fetch inventory levels: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": "Failed to fetch inventory levels",
-                    "details": e.to_string()
-                })),
-            )
+
+        let mut tracking_info = serde_json::Map::new();
+        for (key, value) in [
+            ("number", &self.tracking_number),
+            ("company", &self.tracking_company),
+            ("url", &self.tracking_url),
+        ] {
+            if let Some(value) = value {
+                tracking_info.insert(key.to_string(), value.clone().into());
+            }
         }
+
+        Ok(serde_json::json!({
+            "fulfillment": {
+                "line_items_by_fulfillment_order": line_items_by_fulfillment_order,
+                "tracking_info": tracking_info,
+                "notify_customer": self.notify_customer
+            }
+        }))
     }
 }
 
 // =============================================================================
-// API Fetch Functions
+// CSV Layouts
 // =============================================================================
 
-async fn fetch_products(
-    token: &str,
-    shop: &str,
-    params: &ProductParams,
-) -> Result<Vec<Product>, Box<dyn std::error::Error + Send + Sync>> {
-    let client = ShopifyClient::new(shop, None)?;
-    
-    let mut query_params = Vec::new();
-    
-    // Set default limit if not provided
-    let limit = params.limit.unwrap_or(50);
-    query_params.push(("limit", limit.to_string()));
-    
-    if let Some(since_id) = params.since_id {
-        query_params.push(("since_id", since_id.to_string()));
+const ADDRESS_CSV_FIELDS: [&str; 8] = ["name", "company", "address1", "address2", "city", "province", "zip", "country"];
+
+pub(crate) const BILLING_ADDRESS_CSV_HEADERS: [&str; 8] = [
+    "billing_name", "billing_company", "billing_address1", "billing_address2", "billing_city",
+    "billing_province", "billing_zip", "billing_country",
+];
+
+pub(crate) const SHIPPING_ADDRESS_CSV_HEADERS: [&str; 8] = [
+    "shipping_name", "shipping_company", "shipping_address1", "shipping_address2", "shipping_city",
+    "shipping_province", "shipping_zip", "shipping_country",
+];
+
+/// An address as `ADDRESS_CSV_FIELDS`, preferring codes (`ON`, `CA`) over names.
+fn address_csv_fields(address: Option<&CustomerAddress>) -> Vec<String> {
+    let Some(address) = address else {
+        return vec![String::new(); ADDRESS_CSV_FIELDS.len()];
+    };
+    let name = address.name.clone().unwrap_or_else(|| full_name(&address.first_name, &address.last_name));
+
+    vec![
+        name,
+        optional(&address.company),
+        optional(&address.address1),
+        optional(&address.address2),
+        optional(&address.city),
+        optional(&address.province_code.clone().or_else(|| address.province.clone())),
+        optional(&address.zip),
+        optional(&address.country_code.clone().or_else(|| address.country.clone())),
+    ]
+}
+
+const ORDER_LINE_ITEM_CSV_HEADERS: [&str; 9] = [
+    "line_item_id", "line_item_sku", "line_item_title", "line_item_variant_title", "line_item_vendor",
+    "line_item_quantity", "line_item_price", "line_item_discount", "line_item_fulfillment_status",
+];
+
+impl CsvRecord for Order {
+    fn csv_headers() -> Vec<&'static str> {
+        let mut headers = vec![
+            "order_id", "name", "created_at", "processed_at", "cancelled_at", "financial_status",
+            "fulfillment_status", "currency", "subtotal_price", "total_discounts", "total_tax", "total_price",
+            "discount_codes", "shipping_method", "email", "phone", "customer_id", "customer_name", "tags",
+            "source_name", "test",
+        ];
+        headers.extend(BILLING_ADDRESS_CSV_HEADERS);
+        headers.extend(SHIPPING_ADDRESS_CSV_HEADERS);
+        headers.extend(ORDER_LINE_ITEM_CSV_HEADERS);
+        headers
     }
-    
-    if let Some(ref vendor) = params.vendor {
-        query_params.push(("vendor", vendor.clone()));
+
+    /// The order's own columns, with the line item columns left empty.
+    fn csv_row(&self) -> Vec<String> {
+        let customer_name = self.customer.as_ref().map(|customer| full_name(&customer.first_name, &customer.last_name));
+
+        let mut row = vec![
+            self.id.to_string(),
+            self.name.clone(),
+            self.created_at.clone(),
+            optional(&self.processed_at),
+            optional(&self.cancelled_at),
+            optional(&self.financial_status),
+            optional(&self.fulfillment_status),
+            optional(&self.currency),
+            optional(&self.subtotal_price),
+            optional(&self.total_discounts),
+            optional(&self.total_tax),
+            self.total_price.clone(),
+            self.discount_codes.iter().map(|d| d.code.as_str()).collect::<Vec<_>>().join("; "),
+            self.shipping_lines.iter().map(|s| s.title.as_str()).collect::<Vec<_>>().join("; "),
+            optional(&self.email),
+            optional(&self.phone),
+            optional(&self.customer.as_ref().map(|customer| customer.id)),
+            optional(&customer_name),
+            self.tags.clone(),
+            optional(&self.source_name),
+            self.test.to_string(),
+        ];
+        row.extend(address_csv_fields(self.billing_address.as_ref()));
+        row.extend(address_csv_fields(self.shipping_address.as_ref()));
+        row.extend(vec![String::new(); ORDER_LINE_ITEM_CSV_HEADERS.len()]);
+        row
     }
-    
-    if let Some(ref product_type) = params.product_type {
-        query_params.push(("product_type", product_type.clone()));
+
+    /// One row per line item, repeating the order's columns.
+    fn csv_rows(&self) -> Vec<Vec<String>> {
+        let order = self.csv_row();
+        if self.line_items.is_empty() {
+            return vec![order];
+        }
+        let order = &order[..order.len() - ORDER_LINE_ITEM_CSV_HEADERS.len()];
+
+        self.line_items
+            .iter()
+            .map(|item| {
+                let mut row = order.to_vec();
+                row.extend([
+                    item.id.to_string(),
+                    optional(&item.sku),
+                    item.title.clone(),
+                    optional(&item.variant_title),
+                    optional(&item.vendor),
+                    item.quantity.to_string(),
+                    item.price.clone(),
+                    optional(&item.total_discount),
+                    optional(&item.fulfillment_status),
+                ]);
+                row
+            })
+            .collect()
+    }
+}
+
+const PRODUCT_VARIANT_CSV_HEADERS: [&str; 18] = [
+    "variant_id", "variant_title", "sku", "barcode", "option1_name", "option1_value", "option2_name",
+    "option2_value", "option3_name", "option3_value", "price", "compare_at_price", "inventory_quantity",
+    "inventory_policy", "weight", "weight_unit", "requires_shipping", "taxable",
+];
+
+impl CsvRecord for Product {
+    fn csv_headers() -> Vec<&'static str> {
+        let mut headers = vec![
+            "product_id", "handle", "title", "vendor", "product_type", "status", "tags", "published_at",
+            "created_at", "updated_at", "image_src",
+        ];
+        headers.extend(PRODUCT_VARIANT_CSV_HEADERS);
+        headers
+    }
+
+    /// The product's own columns, with the variant columns left empty.
+    fn csv_row(&self) -> Vec<String> {
+        let mut row = vec![
+            self.id.to_string(),
+            self.handle.clone(),
+            self.title.clone(),
+            self.vendor.clone(),
+            self.product_type.clone(),
+            self.status.clone(),
+            self.tags.clone(),
+            optional(&self.published_at),
+            self.created_at.clone(),
+            self.updated_at.clone(),
+            self.images.iter().min_by_key(|image| image.position).map(|image| image.src.clone()).unwrap_or_default(),
+        ];
+        row.extend(vec![String::new(); PRODUCT_VARIANT_CSV_HEADERS.len()]);
+        row
+    }
+
+    /// One row per variant, repeating the product's columns.
+    fn csv_rows(&self) -> Vec<Vec<String>> {
+        let product = self.csv_row();
+        if self.variants.is_empty() {
+            return vec![product];
+        }
+        let product = &product[..product.len() - PRODUCT_VARIANT_CSV_HEADERS.len()];
+        let option_name = |position: i32| {
+            self.options.iter().find(|option| option.position == position).map(|option| option.name.clone()).unwrap_or_default()
+        };
+
+        self.variants
+            .iter()
+            .map(|variant| {
+                let mut row = product.to_vec();
+                row.extend([
+                    variant.id.to_string(),
+                    variant.title.clone(),
+                    optional(&variant.sku),
+                    optional(&variant.barcode),
+                    option_name(1),
+                    optional(&variant.option1),
+                    option_name(2),
+                    optional(&variant.option2),
+                    option_name(3),
+                    optional(&variant.option3),
+                    variant.price.clone(),
+                    optional(&variant.compare_at_price),
+                    variant.inventory_quantity.to_string(),
+                    variant.inventory_policy.clone(),
+                    variant.weight.to_string(),
+                    variant.weight_unit.clone(),
+                    variant.requires_shipping.to_string(),
+                    variant.taxable.to_string(),
+                ]);
+                row
+            })
+            .collect()
+    }
+}
+
+impl CsvRecord for Customer {
+    fn csv_headers() -> Vec<&'static str> {
+        let mut headers = vec![
+            "customer_id", "first_name", "last_name", "email", "phone", "state", "email_marketing",
+            "orders_count", "total_spent", "currency", "tags", "note", "verified_email", "tax_exempt",
+            "created_at", "updated_at",
+        ];
+        headers.extend(ADDRESS_CSV_FIELDS);
+        headers
+    }
+
+    /// One row per customer, with their default address.
+    fn csv_row(&self) -> Vec<String> {
+        let mut row = vec![
+            self.id.to_string(),
+            optional(&self.first_name),
+            optional(&self.last_name),
+            optional(&self.email),
+            optional(&self.phone),
+            self.state.clone(),
+            self.email_marketing_consent
+                .as_ref()
+                .map(|consent| consent.state.clone())
+                .unwrap_or_else(|| if self.accepts_marketing { "subscribed" } else { "not_subscribed" }.to_string()),
+            self.orders_count.to_string(),
+            self.total_spent.clone(),
+            self.currency.clone(),
+            self.tags.clone(),
+            optional(&self.note),
+            self.verified_email.to_string(),
+            self.tax_exempt.to_string(),
+            self.created_at.clone(),
+            self.updated_at.clone(),
+        ];
+        row.extend(address_csv_fields(self.default_address.as_ref()));
+        row
+    }
+}
+
+/// Every page of a list endpoint as owned records, for CSV downloads that
+/// keep streaming after the handler returns.
+pub(crate) fn export_pages<R, T>(
+    client: ShopifyClient,
+    token: String,
+    endpoint: &'static str,
+    query_params: Vec<(String, String)>,
+    records: fn(R) -> Vec<T>,
+) -> impl Stream<Item = Result<Vec<T>, ShopifyError>> + Send + 'static
+where
+    R: DeserializeOwned + Send + 'static,
+    T: Send + 'static,
+{
+    async_stream::try_stream! {
+        let pages = client.get_all_pages::<R>(endpoint, &token, query_params);
+        pin_mut!(pages);
+        while let Some(page) = pages.try_next().await? {
+            yield records(page);
+        }
+    }
+}
+
+// Filters plus Shopify's largest page size; `fields` is ignored since the
+// CSV layout needs whole records
+pub(crate) fn export_params(filters: Vec<(&'static str, String)>, page_info: Option<&str>) -> Vec<(String, String)> {
+    let mut query_params = vec![("limit", "250".to_string())];
+    query_params.extend(filters);
+    apply_page_info(&mut query_params, page_info);
+    query_params.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+// =============================================================================
+// API Handlers
+// =============================================================================
+
+/// Lists orders, from the local copy once backfilled. `format=csv|ndjson`
+/// or a matching `Accept` header streams every matching order instead, or
+/// with `link=true` writes them to a file behind a signed download link.
+#[utoipa::path(
+    get,
+    path = "/api/orders",
+    tag = "orders",
+    params(OrderParams),
+    responses(
+        (status = 200, description = "A page of orders, or the full export", content(
+            (crate::openapi::OrderList = "application/json"),
+            (String = "text/csv"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 201, description = "With `link`, a signed link to the export file", body = crate::openapi::ExportLink),
+    ),
+)]
+pub async fn orders_handler(
+    Query(params): Query<OrderParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    let shop = &state.config.shop;
+    
+    // Get stored access token
+    let token = require_token(&state.token_store, shop).await?;
+    let source_filter = SourceFilter::parse(params.source_name.as_deref(), params.channel.as_deref())
+        .map_err(AppError::BadRequest)?;
+    
+    // Risk levels are only known locally
+    let risk_filtered = params.risk.as_deref().is_some_and(|risk| !risk.trim().is_empty());
+    let local_only = || AppError::BadRequest(
+        "Filtering by risk needs the backfilled local order copy (a Postgres DATABASE_URL and ORDER_SYNC_INTERVAL_SECS above 0), without live=true or an export format".to_string(),
+    );
+
+    // Exports: every matching order, streamed from Shopify
+    let format = list_format(params.format.as_deref(), &headers)?;
+    if format != ListFormat::Json {
+        if risk_filtered {
+            return Err(local_only());
+        }
+        let query_params = export_params(order_filter_params(&params), params.page_info.as_deref());
+        let pages = export_pages(state.shopify.clone(), token, "orders.json", query_params, |page: OrdersResponse| page.orders)
+            .map_ok(move |mut orders| {
+                orders.retain(|order| source_filter.matches(order));
+                orders
+            });
+        return list_export(&state.config.downloads, params.link.unwrap_or(false), format, "orders", pages).await;
+    }
+
+    // Serve from the local copy once it's backfilled, otherwise fetch from Shopify
+    let (page, source) = match local_orders(&state, shop, &params).await? {
+        Some(page) => (page, "local"),
+        None if risk_filtered => return Err(local_only()),
+        None => {
+            let page = fetch_orders(&state.shopify, &token, &params).await.map_err(|e| {
+                error!("Failed to fetch orders: {}", e);
+                e
+            })?;
+            (page, "shopify")
+        }
+    };
+    let mut orders = page.data;
+    if !source_filter.is_empty() {
+        orders.retain(|order| source_filter.matches(order));
+    }
+    info!("Successfully fetched {} orders from {}", orders.len(), source);
+    let next_page = next_page_url("/api/orders", page.page_info.as_ref(), params.limit.unwrap_or(ORDERS_DEFAULT_LIMIT));
+    
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "source": source,
+        "orders_count": orders.len(),
+        "channel_breakdown": channel_breakdown(&orders),
+        "orders": orders,
+        "page_info": page.page_info,
+        "next_page": next_page
+    }))).into_response())
+}
+
+/// `fields` for the single-resource endpoints
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResourceParams {
+    pub fields: Option<String>,
+}
+
+/// Fetches one order.
+#[utoipa::path(
+    get,
+    path = "/api/orders/{id}",
+    tag = "orders",
+    params(("id" = u64, Path, description = "Order ID"), ResourceParams),
+    responses(
+        (status = 200, description = "The order", body = crate::openapi::OrderDetail),
+        (status = 404, description = "No such order", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn order_handler(
+    Path(order_id): Path<u64>,
+    Query(params): Query<ResourceParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let order = fetch_resource(&state.shopify, &token, "order", order_id, &params).await?;
+    info!("Successfully fetched order {}", order_id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "order": order
+    }))))
+}
+
+/// Fetches one product.
+#[utoipa::path(
+    get,
+    path = "/api/products/{id}",
+    tag = "products",
+    params(("id" = u64, Path, description = "Product ID"), ResourceParams),
+    responses(
+        (status = 200, description = "The product", body = crate::openapi::ProductDetail),
+        (status = 404, description = "No such product", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn product_handler(
+    Path(product_id): Path<u64>,
+    Query(params): Query<ResourceParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let product = fetch_resource(&state.shopify, &token, "product", product_id, &params).await?;
+    info!("Successfully fetched product {}", product_id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "product": product
+    }))))
+}
+
+/// Fetches one customer.
+#[utoipa::path(
+    get,
+    path = "/api/customers/{id}",
+    tag = "customers",
+    params(("id" = u64, Path, description = "Customer ID"), ResourceParams),
+    responses(
+        (status = 200, description = "The customer", body = crate::openapi::CustomerDetail),
+        (status = 404, description = "No such customer", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn customer_handler(
+    Path(customer_id): Path<u64>,
+    Query(params): Query<ResourceParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let customer = fetch_resource(&state.shopify, &token, "customer", customer_id, &params).await?;
+    info!("Successfully fetched customer {}", customer_id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "customer": customer
+    }))))
+}
+
+/// Lists products. `format=csv|ndjson` or a matching `Accept` header
+/// streams every matching product instead, or with `link=true` writes them
+/// to a file behind a signed download link.
+#[utoipa::path(
+    get,
+    path = "/api/products",
+    tag = "products",
+    params(ProductParams),
+    responses(
+        (status = 200, description = "A page of products, or the full export", content(
+            (crate::openapi::ProductList = "application/json"),
+            (String = "text/csv"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 201, description = "With `link`, a signed link to the export file", body = crate::openapi::ExportLink),
+    ),
+)]
+pub async fn products_handler(
+    Query(params): Query<ProductParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    let shop = &state.config.shop;
+    
+    // Get stored access token
+    let token = require_token(&state.token_store, shop).await?;
+    
+    // Exports: every matching product, streamed from Shopify
+    let format = list_format(params.format.as_deref(), &headers)?;
+    if format != ListFormat::Json {
+        let query_params = export_params(product_filter_params(&params), params.page_info.as_deref());
+        let pages = export_pages(state.shopify.clone(), token, "products.json", query_params, |page: ProductsResponse| page.products);
+        return list_export(&state.config.downloads, params.link.unwrap_or(false), format, "products", pages).await;
+    }
+
+    // Fetch products from Shopify
+    let page = fetch_products(&state.shopify, &token, &params).await.map_err(|e| {
+        error!("Failed to fetch products: {}", e);
+        e
+    })?;
+    let products = page.data;
+    info!("Successfully fetched {} products", products.len());
+    let next_page = next_page_url("/api/products", page.page_info.as_ref(), params.limit.unwrap_or(50));
+    
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "products_count": products.len(),
+        "products": products,
+        "page_info": page.page_info,
+        "next_page": next_page
+    }))).into_response())
+}
+
+/// Lists customers. `format=csv|ndjson` or a matching `Accept` header
+/// streams every matching customer instead, or with `link=true` writes them
+/// to a file behind a signed download link.
+#[utoipa::path(
+    get,
+    path = "/api/customers",
+    tag = "customers",
+    params(CustomerParams),
+    responses(
+        (status = 200, description = "A page of customers, or the full export", content(
+            (crate::openapi::CustomerList = "application/json"),
+            (String = "text/csv"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 201, description = "With `link`, a signed link to the export file", body = crate::openapi::ExportLink),
+    ),
+)]
+pub async fn customers_handler(
+    Query(params): Query<CustomerParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    let shop = &state.config.shop;
+    
+    // Get stored access token
+    let token = require_token(&state.token_store, shop).await?;
+    
+    // Exports: every matching customer, streamed from Shopify
+    let format = list_format(params.format.as_deref(), &headers)?;
+    if format != ListFormat::Json {
+        let query_params = export_params(customer_filter_params(&params), params.page_info.as_deref());
+        let pages = export_pages(state.shopify.clone(), token, "customers.json", query_params, |page: CustomersResponse| page.customers);
+        return list_export(&state.config.downloads, params.link.unwrap_or(false), format, "customers", pages).await;
+    }
+
+    // Fetch customers from Shopify
+    let page = fetch_customers(&state.shopify, &token, &params).await.map_err(|e| {
+        error!("Failed to fetch customers: {}", e);
+        e
+    })?;
+    let customers = page.data;
+    info!("Successfully fetched {} customers", customers.len());
+    let next_page = next_page_url("/api/customers", page.page_info.as_ref(), params.limit.unwrap_or(50));
+    
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "customers_count": customers.len(),
+        "customers": customers,
+        "page_info": page.page_info,
+        "next_page": next_page
+    }))).into_response())
+}
+
+/// Lists inventory levels by item and location.
+#[utoipa::path(
+    get,
+    path = "/api/inventory",
+    tag = "inventory",
+    params(InventoryParams),
+    responses(
+        (status = 200, description = "Matching inventory levels", body = crate::openapi::InventoryLevelList),
+    ),
+)]
+pub async fn inventory_handler(
+    Query(params): Query<InventoryParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    
+    // Get stored access token
+    let token = require_token(&state.token_store, shop).await?;
+
+    // Fetch inventory levels from Shopify
+    let inventory_levels = fetch_inventory_levels(&state.shopify, &token, &params).await.map_err(|e| {
+        error!("Failed to fetch inventory levels: {}", e);
+        e
+    })?;
+    info!("Successfully fetched {} inventory levels", inventory_levels.len());
+    
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "inventory_levels_count": inventory_levels.len(),
+        "inventory_levels": inventory_levels
+    }))))
+}
+
+/// Adds or removes available stock of an item at a location.
+#[utoipa::path(
+    post,
+    path = "/api/inventory/adjust",
+    tag = "inventory",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Makes retries with the same key and body return the first response")),
+    request_body = InventoryAdjustRequest,
+    responses(
+        (status = 200, description = "The adjusted level", body = crate::openapi::InventoryLevelChange),
+        (status = 400, description = "Zero adjustment", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn inventory_adjust_handler(
+    State(state): State<AppState>,
+    Json(request): Json<InventoryAdjustRequest>,
+) -> AppResult<impl IntoResponse> {
+    if request.available_adjustment == 0 {
+        return Err(AppError::BadRequest("available_adjustment must not be zero".to_string()));
+    }
+
+    let token = require_token(&state.token_store, &state.config.shop).await?;
+    let level = update_inventory_level(&state.shopify, &token, "inventory_levels/adjust.json", &request).await?;
+    info!(
+        "📦 Adjusted inventory item {} at location {} by {}",
+        request.inventory_item_id, request.location_id, request.available_adjustment
+    );
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "inventory_level": level
+    }))))
+}
+
+/// Sets the available stock of an item at a location.
+#[utoipa::path(
+    post,
+    path = "/api/inventory/set",
+    tag = "inventory",
+    request_body = InventorySetRequest,
+    responses(
+        (status = 200, description = "The new level", body = crate::openapi::InventoryLevelChange),
+    ),
+)]
+pub async fn inventory_set_handler(
+    State(state): State<AppState>,
+    Json(request): Json<InventorySetRequest>,
+) -> AppResult<impl IntoResponse> {
+    let token = require_token(&state.token_store, &state.config.shop).await?;
+    let level = update_inventory_level(&state.shopify, &token, "inventory_levels/set.json", &request).await?;
+    info!(
+        "📦 Set inventory item {} at location {} to {}",
+        request.inventory_item_id, request.location_id, request.available
+    );
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "inventory_level": level
+    }))))
+}
+
+/// Stocks an item at a location.
+#[utoipa::path(
+    post,
+    path = "/api/inventory/connect",
+    tag = "inventory",
+    request_body = InventoryConnectRequest,
+    responses(
+        (status = 201, description = "The new level", body = crate::openapi::InventoryLevelChange),
+    ),
+)]
+pub async fn inventory_connect_handler(
+    State(state): State<AppState>,
+    Json(request): Json<InventoryConnectRequest>,
+) -> AppResult<impl IntoResponse> {
+    let token = require_token(&state.token_store, &state.config.shop).await?;
+    let level = update_inventory_level(&state.shopify, &token, "inventory_levels/connect.json", &request).await?;
+    info!(
+        "📦 Connected inventory item {} to location {}",
+        request.inventory_item_id, request.location_id
+    );
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({
+        "success": true,
+        "inventory_level": level
+    }))))
+}
+
+/// Lists the shop's locations.
+#[utoipa::path(
+    get,
+    path = "/api/locations",
+    tag = "locations",
+    params(LocationParams),
+    responses(
+        (status = 200, description = "The shop's locations", body = crate::openapi::LocationList),
+    ),
+)]
+pub async fn locations_handler(
+    Query(params): Query<LocationParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    // Shopify returns every location in one response
+    let mut locations = fetch_locations(&state.shopify, &token).await.map_err(|e| {
+        error!("Failed to fetch locations: {}", e);
+        e
+    })?;
+    if params.active.unwrap_or(false) {
+        locations.retain(|location| location.active);
+    }
+    info!("Successfully fetched {} locations", locations.len());
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "locations_count": locations.len(),
+        "locations": locations
+    }))))
+}
+
+/// Fetches one location.
+#[utoipa::path(
+    get,
+    path = "/api/locations/{id}",
+    tag = "locations",
+    params(("id" = u64, Path, description = "Location ID")),
+    responses(
+        (status = 200, description = "The location", body = crate::openapi::LocationDetail),
+        (status = 404, description = "No such location", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn location_handler(
+    Path(location_id): Path<u64>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let response = state.shopify
+        .get_with_auth::<LocationResponse>(&format!("locations/{}.json", location_id), &token, None)
+        .await
+        .map_err(|e| match e {
+            ShopifyError::NotFound => AppError::NotFound(format!("Location {} not found", location_id)),
+            e => e.into(),
+        })?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "location": response.data.location
+    }))))
+}
+
+/// Counts the shop's locations.
+#[utoipa::path(
+    get,
+    path = "/api/locations/count",
+    tag = "locations",
+    responses(
+        (status = 200, description = "Number of locations", body = crate::openapi::ResourceCount),
+    ),
+)]
+pub async fn locations_count_handler(
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let count = state.shopify.get_count("locations/count.json", &token, &[]).await?;
+    info!("Successfully fetched locations count: {}", count);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "count": count
+    }))))
+}
+
+/// Same filters as `/api/orders`. `source_name` and `channel` are applied
+/// locally to listed orders, so Shopify can't count with them.
+#[utoipa::path(
+    get,
+    path = "/api/orders/count",
+    tag = "orders",
+    params(OrderParams),
+    responses(
+        (status = 200, description = "Number of matching orders", body = crate::openapi::ResourceCount),
+        (status = 400, description = "`source_name` or `channel` given", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn orders_count_handler(
+    Query(params): Query<OrderParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    if params.source_name.is_some() || params.channel.is_some() {
+        return Err(AppError::BadRequest(
+            "source_name and channel filters are not supported when counting orders".to_string(),
+        ));
+    }
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let filters = order_filter_params(&params);
+    let count = count_resource(&state.shopify, &token, "orders", &filters).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "count": count
+    }))))
+}
+
+/// Counts products with the same filters as `/api/products`.
+#[utoipa::path(
+    get,
+    path = "/api/products/count",
+    tag = "products",
+    params(ProductParams),
+    responses(
+        (status = 200, description = "Number of matching products", body = crate::openapi::ResourceCount),
+    ),
+)]
+pub async fn products_count_handler(
+    Query(params): Query<ProductParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let filters = product_filter_params(&params);
+    let count = count_resource(&state.shopify, &token, "products", &filters).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "count": count
+    }))))
+}
+
+/// Counts customers with the same filters as `/api/customers`.
+#[utoipa::path(
+    get,
+    path = "/api/customers/count",
+    tag = "customers",
+    params(CustomerParams),
+    responses(
+        (status = 200, description = "Number of matching customers", body = crate::openapi::ResourceCount),
+    ),
+)]
+pub async fn customers_count_handler(
+    Query(params): Query<CustomerParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let filters = customer_filter_params(&params);
+    let count = count_resource(&state.shopify, &token, "customers", &filters).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "count": count
+    }))))
+}
+
+/// Lists an order's fulfillments.
+#[utoipa::path(
+    get,
+    path = "/api/orders/{id}/fulfillments",
+    tag = "fulfillments",
+    params(("id" = u64, Path, description = "Order ID"), FulfillmentParams),
+    responses(
+        (status = 200, description = "The order's fulfillments", body = crate::openapi::FulfillmentList),
+        (status = 404, description = "No such order", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn fulfillments_handler(
+    Path(order_id): Path<u64>,
+    Query(params): Query<FulfillmentParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let limit = params.limit.unwrap_or(50).min(250).to_string();
+    let since_id = params.since_id.map(|id| id.to_string());
+    let mut query_params = vec![("limit", limit.as_str())];
+    if let Some(ref since_id) = since_id {
+        query_params.push(("since_id", since_id));
+    }
+
+    let response = state.shopify
+        .get_with_auth::<FulfillmentsResponse>(&format!("orders/{}/fulfillments.json", order_id), &token, Some(&query_params))
+        .await
+        .map_err(|e| order_not_found(e, order_id))?;
+    let fulfillments = response.data.fulfillments;
+    info!("Successfully fetched {} fulfillments for order {}", fulfillments.len(), order_id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "order_id": order_id,
+        "fulfillments_count": fulfillments.len(),
+        "fulfillments": fulfillments
+    }))))
+}
+
+/// Creates a fulfillment with tracking details against the order's
+/// fulfillment orders.
+#[utoipa::path(
+    post,
+    path = "/api/orders/{id}/fulfillments",
+    tag = "fulfillments",
+    params(("id" = u64, Path, description = "Order ID"), ("Idempotency-Key" = Option<String>, Header, description = "Makes retries with the same key and body return the first response")),
+    request_body = CreateFulfillmentRequest,
+    responses(
+        (status = 201, description = "The new fulfillment", body = crate::openapi::FulfillmentCreated),
+        (status = 400, description = "A requested fulfillment order isn't open", body = crate::openapi::ErrorResponse),
+        (status = 409, description = "Nothing left to fulfill", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn create_fulfillment_handler(
+    Path(order_id): Path<u64>,
+    State(state): State<AppState>,
+    Json(request): Json<CreateFulfillmentRequest>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let fulfillment_orders = fetch_fulfillment_orders(&state.shopify, &token, order_id, false).await?;
+    let body = request.to_shopify_body(&fulfillment_orders)?;
+
+    let response: FulfillmentResponse = state.shopify
+        .post_with_auth("fulfillments.json", &token, &body)
+        .await
+        .map_err(|e| {
+            error!("Failed to create fulfillment for order {}: {}", order_id, e);
+            e
+        })?;
+    info!(
+        "🚚 Created fulfillment {} for order {} (tracking: {})",
+        response.fulfillment.id,
+        order_id,
+        response.fulfillment.tracking_number.as_deref().unwrap_or("none")
+    );
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({
+        "success": true,
+        "fulfillment": response.fulfillment
+    }))))
+}
+
+/// Lists an order's fulfillment orders.
+#[utoipa::path(
+    get,
+    path = "/api/orders/{id}/fulfillment-orders",
+    tag = "fulfillments",
+    params(("id" = u64, Path, description = "Order ID"), FulfillmentOrderParams),
+    responses(
+        (status = 200, description = "The order's fulfillment orders", body = crate::openapi::FulfillmentOrderList),
+        (status = 404, description = "No such order", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn fulfillment_orders_handler(
+    Path(order_id): Path<u64>,
+    Query(params): Query<FulfillmentOrderParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let token = require_token(&state.token_store, shop).await?;
+
+    let include_closed = params.include_closed.unwrap_or(false);
+    let fulfillment_orders = fetch_fulfillment_orders(&state.shopify, &token, order_id, include_closed).await?;
+    info!("Successfully fetched {} fulfillment orders for order {}", fulfillment_orders.len(), order_id);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "order_id": order_id,
+        "fulfillment_orders_count": fulfillment_orders.len(),
+        "fulfillment_orders": fulfillment_orders
+    }))))
+}
+
+// =============================================================================
+// API Fetch Functions
+// =============================================================================
+
+// Filters shared by the list and count endpoints
+pub(crate) fn order_filter_params(params: &OrderParams) -> Vec<(&'static str, String)> {
+    let mut query_params = Vec::new();
+    
+    // Shopify only returns open orders unless asked otherwise
+    query_params.push(("status", params.status.clone().unwrap_or_else(|| "any".to_string())));
+    
+    if let Some(since_id) = params.since_id {
+        query_params.push(("since_id", since_id.to_string()));
+    }
+    
+    if let Some(ref ids) = params.ids {
+        query_params.push(("ids", ids.clone()));
+    }
+    
+    if let Some(ref financial_status) = params.financial_status {
+        query_params.push(("financial_status", financial_status.clone()));
+    }
+    
+    if let Some(ref fulfillment_status) = params.fulfillment_status {
+        query_params.push(("fulfillment_status", fulfillment_status.clone()));
+    }
+    
+    if let Some(ref created_at_min) = params.created_at_min {
+        query_params.push(("created_at_min", created_at_min.clone()));
+    }
+    
+    if let Some(ref created_at_max) = params.created_at_max {
+        query_params.push(("created_at_max", created_at_max.clone()));
+    }
+    
+    if let Some(ref updated_at_min) = params.updated_at_min {
+        query_params.push(("updated_at_min", updated_at_min.clone()));
+    }
+    
+    if let Some(ref updated_at_max) = params.updated_at_max {
+        query_params.push(("updated_at_max", updated_at_max.clone()));
+    }
+    
+    if let Some(ref processed_at_min) = params.processed_at_min {
+        query_params.push(("processed_at_min", processed_at_min.clone()));
+    }
+    
+    if let Some(ref processed_at_max) = params.processed_at_max {
+        query_params.push(("processed_at_max", processed_at_max.clone()));
+    }
+    
+    query_params
+}
+
+// Filters shared by the list and count endpoints
+pub(crate) fn product_filter_params(params: &ProductParams) -> Vec<(&'static str, String)> {
+    let mut query_params = Vec::new();
+    
+    if let Some(since_id) = params.since_id {
+        query_params.push(("since_id", since_id.to_string()));
+    }
+    
+    if let Some(ref vendor) = params.vendor {
+        query_params.push(("vendor", vendor.clone()));
+    }
+    
+    if let Some(ref product_type) = params.product_type {
+        query_params.push(("product_type", product_type.clone()));
     }
     
     if let Some(collection_id) = params.collection_id {
@@ -415,34 +1850,13 @@ async fn fetch_products(
         query_params.push(("published_status", published_status.clone()));
     }
     
-    if let Some(ref fields) = params.fields {
-        query_params.push(("fields", fields.clone()));
-    }
-
-    let query_params_ref: Vec<(&str, &str)> = query_params.iter()
-        .map(|(k, v)| (k as &str, v as &str))
-        .collect();
-
-    let products_response: ProductsResponse = client
-        .get_with_auth("products.json", token, Some(&query_params_ref))
-        .await?;
-    
-    Ok(products_response.products)
+    query_params
 }
 
-async fn fetch_customers(
-    token: &str,
-    shop: &str,
-    params: &CustomerParams,
-) -> Result<Vec<Customer>, Box<dyn std::error::Error + Send + Sync>> {
-    let client = ShopifyClient::new(shop, None)?;
-    
+// Filters shared by the list and count endpoints
+pub(crate) fn customer_filter_params(params: &CustomerParams) -> Vec<(&'static str, String)> {
     let mut query_params = Vec::new();
     
-    // Set default limit if not provided
-    let limit = params.limit.unwrap_or(50);
-    query_params.push(("limit", limit.to_string()));
-    
     if let Some(since_id) = params.since_id {
         query_params.push(("since_id", since_id.to_string()));
     }
@@ -463,28 +1877,161 @@ async fn fetch_customers(
         query_params.push(("updated_at_max", updated_at_max.clone()));
     }
     
+    query_params
+}
+
+// The orders endpoint has always returned the latest 5 orders by default
+pub(crate) const ORDERS_DEFAULT_LIMIT: u32 = 5;
+
+async fn fetch_orders(
+    client: &ShopifyClient,
+    token: &str,
+    params: &OrderParams,
+) -> Result<PaginatedResponse<Vec<Order>>, ShopifyError> {
+    let mut query_params = Vec::new();
+    
+    // Set default limit if not provided (full exports use the maximum page size)
+    let limit = params.limit.unwrap_or(if params.all.unwrap_or(false) { 250 } else { ORDERS_DEFAULT_LIMIT });
+    query_params.push(("limit", limit.to_string()));
+    
+    query_params.extend(order_filter_params(params));
+    
+    if let Some(ref fields) = params.fields {
+        // Source filtering and the channel breakdown need source_name
+        let fields = if fields.split(',').any(|f| f.trim() == "source_name") {
+            fields.clone()
+        } else {
+            format!("{},source_name", fields)
+        };
+        query_params.push(("fields", fields));
+    }
+
+    apply_page_info(&mut query_params, params.page_info.as_deref());
+
+    let query_params_ref: Vec<(&str, &str)> = query_params.iter()
+        .map(|(k, v)| (k as &str, v as &str))
+        .collect();
+
+    if params.all.unwrap_or(false) {
+        let pages = client.get_all_pages::<OrdersResponse>("orders.json", token, owned_params(&query_params_ref));
+        pin_mut!(pages);
+        
+        let mut orders = Vec::new();
+        while let Some(page) = pages.try_next().await? {
+            orders.extend(page.orders);
+        }
+        
+        return Ok(PaginatedResponse { data: orders, page_info: None });
+    }
+
+    let page = client
+        .get_with_auth::<OrdersResponse>("orders.json", token, Some(&query_params_ref))
+        .await?
+        .into_paginated();
+    
+    Ok(PaginatedResponse {
+        data: page.data.orders,
+        page_info: page.page_info,
+    })
+}
+
+async fn fetch_products(
+    client: &ShopifyClient,
+    token: &str,
+    params: &ProductParams,
+) -> Result<PaginatedResponse<Vec<Product>>, ShopifyError> {
+    let mut query_params = Vec::new();
+    
+    // Set default limit if not provided (full exports use the maximum page size)
+    let limit = params.limit.unwrap_or(if params.all.unwrap_or(false) { 250 } else { 50 });
+    query_params.push(("limit", limit.to_string()));
+    
+    query_params.extend(product_filter_params(params));
+    
     if let Some(ref fields) = params.fields {
         query_params.push(("fields", fields.clone()));
     }
 
+    apply_page_info(&mut query_params, params.page_info.as_deref());
+
     let query_params_ref: Vec<(&str, &str)> = query_params.iter()
         .map(|(k, v)| (k as &str, v as &str))
         .collect();
 
-    let customers_response: CustomersResponse = client
-        .get_with_auth("customers.json", token, Some(&query_params_ref))
-        .await?;
+    if params.all.unwrap_or(false) {
+        let pages = client.get_all_pages::<ProductsResponse>("products.json", token, owned_params(&query_params_ref));
+        pin_mut!(pages);
+        
+        let mut products = Vec::new();
+        while let Some(page) = pages.try_next().await? {
+            products.extend(page.products);
+        }
+        
+        return Ok(PaginatedResponse { data: products, page_info: None });
+    }
+
+    let page = client
+        .get_with_auth::<ProductsResponse>("products.json", token, Some(&query_params_ref))
+        .await?
+        .into_paginated();
     
-    Ok(customers_response.customers)
+    Ok(PaginatedResponse {
+        data: page.data.products,
+        page_info: page.page_info,
+    })
 }
 
-async fn fetch_inventory_levels(
+async fn fetch_customers(
+    client: &ShopifyClient,
     token: &str,
-    shop: &str,
-    params: &InventoryParams,
-) -> Result<Vec<InventoryLevel>, Box<dyn std::error::Error + Send + Sync>> {
-    let client = ShopifyClient::new(shop, None)?;
+    params: &CustomerParams,
+) -> Result<PaginatedResponse<Vec<Customer>>, ShopifyError> {
+    let mut query_params = Vec::new();
+    
+    // Set default limit if not provided (full exports use the maximum page size)
+    let limit = params.limit.unwrap_or(if params.all.unwrap_or(false) { 250 } else { 50 });
+    query_params.push(("limit", limit.to_string()));
+    
+    query_params.extend(customer_filter_params(params));
+    
+    if let Some(ref fields) = params.fields {
+        query_params.push(("fields", fields.clone()));
+    }
+
+    apply_page_info(&mut query_params, params.page_info.as_deref());
+
+    let query_params_ref: Vec<(&str, &str)> = query_params.iter()
+        .map(|(k, v)| (k as &str, v as &str))
+        .collect();
+
+    if params.all.unwrap_or(false) {
+        let pages = client.get_all_pages::<CustomersResponse>("customers.json", token, owned_params(&query_params_ref));
+        pin_mut!(pages);
+        
+        let mut customers = Vec::new();
+        while let Some(page) = pages.try_next().await? {
+            customers.extend(page.customers);
+        }
+        
+        return Ok(PaginatedResponse { data: customers, page_info: None });
+    }
+
+    let page = client
+        .get_with_auth::<CustomersResponse>("customers.json", token, Some(&query_params_ref))
+        .await?
+        .into_paginated();
     
+    Ok(PaginatedResponse {
+        data: page.data.customers,
+        page_info: page.page_info,
+    })
+}
+
+pub async fn fetch_inventory_levels(
+    client: &ShopifyClient,
+    token: &str,
+    params: &InventoryParams,
+) -> Result<Vec<InventoryLevel>, ShopifyError> {
     let mut query_params = Vec::new();
     
     // Set default limit if not provided
@@ -509,15 +2056,135 @@ async fn fetch_inventory_levels(
 
     let inventory_response: InventoryLevelsResponse = client
         .get_with_auth("inventory_levels.json", token, Some(&query_params_ref))
-        .await?;
+        .await?
+        .data;
     
     Ok(inventory_response.inventory_levels)
 }
 
-// Helper function to get token (to be implemented in main.rs)
-async fn get_token(token_store: &crate::database::DbTokenStore, shop: &str) -> Option<String> {
-    match token_store.get_token(shop).await {
-        Ok(Some(token)) => Some(token),
-        _ => None,
+// Inventory writes all take a flat JSON body and return the resulting level
+async fn update_inventory_level<T: Serialize>(
+    client: &ShopifyClient,
+    token: &str,
+    endpoint: &str,
+    body: &T,
+) -> Result<InventoryLevel, ShopifyError> {
+    let response: InventoryLevelResponse = client.post_with_auth(endpoint, token, body).await.map_err(|e| {
+        error!("Failed to update inventory via {}: {}", endpoint, e);
+        e
+    })?;
+
+    Ok(response.inventory_level)
+}
+
+pub async fn fetch_locations(client: &ShopifyClient, token: &str) -> Result<Vec<Location>, ShopifyError> {
+    let response: LocationsResponse = client
+        .get_with_auth("locations.json", token, None)
+        .await?
+        .data;
+
+    Ok(response.locations)
+}
+
+pub async fn fetch_fulfillment_orders(
+    client: &ShopifyClient,
+    token: &str,
+    order_id: u64,
+    include_closed: bool,
+) -> AppResult<Vec<FulfillmentOrder>> {
+    let include_closed = include_closed.to_string();
+    let query_params = [("include_closed", include_closed.as_str())];
+
+    let response = client
+        .get_with_auth::<FulfillmentOrdersResponse>(
+            &format!("orders/{}/fulfillment_orders.json", order_id),
+            token,
+            Some(&query_params),
+        )
+        .await
+        .map_err(|e| order_not_found(e, order_id))?;
+
+    Ok(response.data.fulfillment_orders)
+}
+
+fn order_not_found(error: ShopifyError, order_id: u64) -> AppError {
+    match error {
+        ShopifyError::NotFound => AppError::NotFound(format!("Order {} not found", order_id)),
+        e => {
+            error!("Failed to fetch fulfillment data for order {}: {}", order_id, e);
+            e.into()
+        }
+    }
+}
+
+/// Fetches `{resource}s/{id}.json`. The body is passed through untyped since
+/// `fields` can leave out anything the typed models require.
+async fn fetch_resource(
+    client: &ShopifyClient,
+    token: &str,
+    resource: &str,
+    id: u64,
+    params: &ResourceParams,
+) -> AppResult<serde_json::Value> {
+    let query_params: Vec<(&str, &str)> = params.fields.as_deref()
+        .map(|fields| vec![("fields", fields)])
+        .unwrap_or_default();
+
+    let response = client
+        .get_with_auth::<serde_json::Value>(&format!("{}s/{}.json", resource, id), token, Some(&query_params))
+        .await
+        .map_err(|e| match e {
+            ShopifyError::NotFound => AppError::NotFound(format!("{} {} not found", capitalize(resource), id)),
+            e => {
+                error!("Failed to fetch {} {}: {}", resource, id, e);
+                e.into()
+            }
+        })?;
+
+    let mut body = response.data;
+    Ok(body[resource].take())
+}
+
+async fn count_resource(
+    client: &ShopifyClient,
+    token: &str,
+    resource: &str,
+    filters: &[(&str, String)],
+) -> Result<u64, ShopifyError> {
+    let query_params: Vec<(&str, &str)> = filters.iter()
+        .map(|(k, v)| (*k, v.as_str()))
+        .collect();
+
+    let count = client
+        .get_count(&format!("{}/count.json", resource), token, &query_params)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch {} count: {}", resource, e);
+            e
+        })?;
+    info!("Successfully fetched {} count: {}", resource, count);
+    Ok(count)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn owned_params(query_params: &[(&str, &str)]) -> Vec<(String, String)> {
+    query_params.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+// Shopify rejects filter parameters on cursor requests; only `limit` and
+// `fields` may accompany `page_info`.
+pub(crate) fn apply_page_info(query_params: &mut Vec<(&str, String)>, page_info: Option<&str>) {
+    if let Some(page_info) = page_info {
+        query_params.retain(|(k, _)| matches!(*k, "limit" | "fields"));
+        query_params.push(("page_info", page_info.to_string()));
     }
 }
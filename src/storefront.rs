@@ -0,0 +1,116 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use crate::{
+    AppState,
+    require_token,
+    error::{AppError, AppResult, ShopifyError},
+    http_client::ShopifyClient,
+    shop_secrets::StorefrontToken,
+};
+
+/// Title storefront tokens are created with, shown in the shop's admin.
+const TOKEN_TITLE: &str = "Headless storefront";
+
+#[derive(Deserialize)]
+pub struct StorefrontTokenResponse {
+    pub storefront_access_token: StorefrontToken,
+}
+
+// =============================================================================
+// Storefront Token Handlers
+// =============================================================================
+
+/// Returns the shop's Storefront API access token, creating one through the
+/// Admin API on first use. The token carries the app's `unauthenticated_*`
+/// scopes and is stored encrypted with the shop's other secrets.
+#[utoipa::path(
+    get,
+    path = "/api/storefront-token",
+    tag = "shops",
+    responses(
+        (status = 200, description = "The Storefront API token and endpoint", body = crate::openapi::StorefrontTokenDetail),
+        (status = 403, description = "The app has no unauthenticated scopes", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn storefront_token_handler(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let (storefront_token, created) = match state.shop_secrets.get::<StorefrontToken>(shop).await? {
+        Some(storefront_token) => (storefront_token, false),
+        None => {
+            let token = require_token(&state.token_store, shop).await?;
+            let storefront_token = create_storefront_token(&state.shopify, &token).await?;
+            state.shop_secrets.put(shop, &storefront_token).await?;
+            info!("🛍️ Created Storefront API token {} for {} ({})", storefront_token.id, shop, storefront_token.access_scope);
+            (storefront_token, true)
+        }
+    };
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "shop": shop,
+        "created": created,
+        "storefront_api_url": format!("https://{}/api/{}/graphql.json", shop, state.shopify.api_version()),
+        "storefront_token": storefront_token
+    }))))
+}
+
+/// Revokes the shop's Storefront API token at Shopify and forgets it, so the
+/// next `GET` creates a fresh one.
+#[utoipa::path(
+    delete,
+    path = "/api/storefront-token",
+    tag = "shops",
+    responses(
+        (status = 200, description = "Token revoked", body = crate::openapi::StorefrontTokenRevoked),
+        (status = 404, description = "No token stored for the shop", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn revoke_storefront_token_handler(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    let shop = &state.config.shop;
+    let storefront_token = state.shop_secrets.get::<StorefrontToken>(shop).await?
+        .ok_or_else(|| AppError::NotFound(format!("No Storefront API token stored for {}", shop)))?;
+    let token = require_token(&state.token_store, shop).await?;
+
+    match state.shopify
+        .delete_with_auth(&format!("storefront_access_tokens/{}.json", storefront_token.id), &token)
+        .await
+    {
+        Ok(()) => {}
+        // Deleted in the admin already
+        Err(ShopifyError::NotFound) => warn!("Storefront API token {} for {} was already gone", storefront_token.id, shop),
+        Err(e) => {
+            error!("Failed to revoke Storefront API token {}: {}", storefront_token.id, e);
+            return Err(e.into());
+        }
+    }
+    state.shop_secrets.delete::<StorefrontToken>(shop).await?;
+    info!("🛍️ Revoked Storefront API token {} for {}", storefront_token.id, shop);
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "shop": shop,
+        "id": storefront_token.id
+    }))))
+}
+
+// =============================================================================
+// Storefront Token Functions
+// =============================================================================
+
+async fn create_storefront_token(client: &ShopifyClient, token: &str) -> Result<StorefrontToken, ShopifyError> {
+    let body = serde_json::json!({ "storefront_access_token": { "title": TOKEN_TITLE } });
+    let response: StorefrontTokenResponse = client
+        .post_with_auth("storefront_access_tokens.json", token, &body)
+        .await
+        .map_err(|e| {
+            error!("Failed to create Storefront API token: {}", e);
+            e
+        })?;
+    Ok(response.storefront_access_token)
+}
@@ -0,0 +1,586 @@
+//! A mock Shopify for tests. `MockShopify` serves the OAuth token endpoint
+//! and paginated REST resources on a local port, can answer with 429s, and
+//! signs webhooks the way the app verifies them. `app_state` wires an
+//! `AppState` to it on in-memory stores, so whole flows run without Postgres
+//! or the network; `TestDatabase` gives a test a migrated Postgres of its own
+//! and the `AppState` on top of it.
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, Request, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use sqlx::{Connection, PgConnection};
+use std::time::Duration;
+use testcontainers_modules::{
+    postgres::Postgres,
+    testcontainers::{runners::AsyncRunner, ContainerAsync},
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    database::{
+        ApiUsageStore, CatalogStore, CustomerMirrorStore, DatabaseConfig, DatabaseRouter, DocumentTemplateStore, EmailLogStore,
+        FulfillmentRoutingStore, IdempotencyStore, LowStockStore, OrderMirrorStore, OrderStore, PersonalDataStore, RecoveryMessageStore,
+        ShopSecretStore, TokenAuditStore, WarehouseExportStore, WebhookEventStore,
+    },
+    event_stream::EventBroadcaster,
+    mailer::EmailSender,
+    job_queue::JobQueue,
+    response_cache::ResponseCache,
+    scheduler::Scheduler,
+    token_audit::AuditedTokenStore,
+    token_store::{
+        connect_stores, MemoryStateStore, MemoryTokenStore, StateStore, TokenStore, TokenStoreBackend, TokenStoreConfig,
+    },
+    webhook_queue::{QueuedWebhook, WebhookDispatcher},
+    webhooks::sign_webhook_base64,
+    AppConfig, AppState, ShopifyClient,
+};
+
+// =============================================================================
+// Mock Shopify
+// =============================================================================
+
+/// A Shopify stand-in on `127.0.0.1`. Every shop's requests go to it once a
+/// client is pointed at `origin()`.
+pub struct MockShopify {
+    origin: String,
+    api_secret: String,
+    state: Arc<Mutex<MockState>>,
+}
+
+struct MockState {
+    origin: String,
+    api_key: String,
+    api_secret: String,
+    /// Authorization codes the token endpoint accepts, each once
+    grants: HashMap<String, MockGrant>,
+    /// Items of each REST resource, e.g. `orders`, served `page_size` at a time
+    resources: HashMap<String, Vec<Value>>,
+    page_size: usize,
+    /// Available stock by location and inventory item
+    inventory: HashMap<(u64, u64), i64>,
+    /// GraphQL response bodies by operation name
+    graphql: HashMap<String, Value>,
+    /// Admin API requests still to be answered with a 429
+    throttled: u32,
+    /// Last ID given to a created item; IDs aren't reused after deletes
+    last_id: u64,
+    /// `METHOD /path` of every request, in order
+    requests: Vec<String>,
+}
+
+#[derive(Clone)]
+struct MockGrant {
+    access_token: String,
+    scope: String,
+}
+
+impl MockShopify {
+    /// Starts the mock for an app with these credentials; the token endpoint
+    /// rejects any others.
+    pub async fn start(api_key: &str, api_secret: &str) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("bind mock Shopify");
+        let origin = format!("http://{}", listener.local_addr().expect("mock Shopify address"));
+        let state = Arc::new(Mutex::new(MockState {
+            origin: origin.clone(),
+            api_key: api_key.to_string(),
+            api_secret: api_secret.to_string(),
+            grants: HashMap::new(),
+            resources: HashMap::new(),
+            page_size: 2,
+            inventory: HashMap::new(),
+            graphql: HashMap::new(),
+            throttled: 0,
+            last_id: 0,
+            requests: Vec::new(),
+        }));
+        let app = Router::new()
+            .route("/admin/oauth/access_token", post(access_token))
+            .route("/admin/api/:version/:resource", get(list_resource).post(create_resource))
+            .route("/admin/api/:version/:resource/:id", get(list_child_resource).delete(delete_resource))
+            .route("/admin/api/:version/:parent/:id/:resource", get(list_nested_resource))
+            .route("/admin/api/:version/themes/:theme/assets.json", put(put_theme_asset))
+            .route("/admin/api/:version/inventory_levels/adjust.json", post(adjust_inventory))
+            .route("/admin/api/:version/graphql.json", post(graphql))
+            .with_state(state.clone());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.expect("mock Shopify stopped");
+        });
+
+        Self { origin, api_secret: api_secret.to_string(), state }
+    }
+
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// Lets the token endpoint exchange `code`, once, for `access_token`.
+    pub fn grant(&self, code: &str, access_token: &str, scope: &str) {
+        let grant = MockGrant { access_token: access_token.to_string(), scope: scope.to_string() };
+        self.state.lock().unwrap().grants.insert(code.to_string(), grant);
+    }
+
+    /// Serves `items` at `/admin/api/{version}/{resource}.json`, with Link
+    /// headers between pages of `page_size`, and each item by ID at
+    /// `{resource}/{id}.json`. `resource` can be nested, e.g.
+    /// `orders/1/risks` or `shopify_payments/payouts`.
+    pub fn resource(&self, resource: &str, items: Vec<Value>) {
+        self.state.lock().unwrap().resources.insert(resource.to_string(), items);
+    }
+
+    /// Answers GraphQL queries named `operation` (`query {operation}(...)`)
+    /// with `response`, the whole body including `data` or `errors`.
+    pub fn graphql(&self, operation: &str, response: Value) {
+        self.state.lock().unwrap().graphql.insert(operation.to_string(), response);
+    }
+
+    pub fn page_size(&self, page_size: usize) {
+        self.state.lock().unwrap().page_size = page_size.max(1);
+    }
+
+    /// Answers the next `count` Admin API requests with a 429.
+    pub fn throttle(&self, count: u32) {
+        self.state.lock().unwrap().throttled = count;
+    }
+
+    /// `METHOD /path` of every request so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// A webhook delivery as Shopify would send it to `path`, signed with the
+    /// app's secret.
+    pub fn webhook(&self, path: &str, shop: &str, topic: &str, body: &Value) -> Request<Body> {
+        self.webhook_signed_with(&self.api_secret, path, shop, topic, body)
+    }
+
+    /// As `webhook`, signed with `secret`, e.g. a custom app's own.
+    pub fn webhook_signed_with(&self, secret: &str, path: &str, shop: &str, topic: &str, body: &Value) -> Request<Body> {
+        let body = body.to_string();
+        Request::builder()
+            .method("POST")
+            .uri(path)
+            .header("Content-Type", "application/json")
+            .header("X-Shopify-Topic", topic)
+            .header("X-Shopify-Shop-Domain", shop)
+            .header("X-Shopify-Webhook-Id", uuid::Uuid::new_v4().to_string())
+            .header("X-Shopify-Hmac-Sha256", sign_webhook_base64(body.as_bytes(), secret))
+            .body(Body::from(body))
+            .unwrap()
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    client_id: String,
+    client_secret: String,
+    code: String,
+}
+
+async fn access_token(State(state): State<Arc<Mutex<MockState>>>, Json(request): Json<TokenRequest>) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push("POST /admin/oauth/access_token".to_string());
+
+    if request.client_id != state.api_key || request.client_secret != state.api_secret {
+        return (StatusCode::UNAUTHORIZED, Json(json!({"error": "invalid_client"}))).into_response();
+    }
+    // Codes are single use, as on Shopify
+    match state.grants.remove(&request.code) {
+        Some(grant) => Json(json!({"access_token": grant.access_token, "scope": grant.scope})).into_response(),
+        None => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_request", "error_description": "The authorization code was not found or was already used"})),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct GraphQlRequest {
+    query: String,
+    #[serde(default)]
+    variables: Value,
+}
+
+/// Answers with the response registered for the query's operation name, and
+/// logs it with the variables as `POST /.../graphql.json {operation} {variables}`.
+async fn graphql(
+    State(state): State<Arc<Mutex<MockState>>>,
+    Path(version): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<GraphQlRequest>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    let operation = request
+        .query
+        .trim_start()
+        .strip_prefix("query ")
+        .and_then(|rest| rest.split(|c: char| c == '(' || c == '{' || c.is_whitespace()).next())
+        .unwrap_or_default()
+        .to_string();
+    state.requests.push(format!("POST /admin/api/{}/graphql.json {} {}", version, operation, request.variables));
+
+    if headers.get("X-Shopify-Access-Token").is_none() {
+        return (StatusCode::UNAUTHORIZED, Json(json!({"errors": "[API] Invalid API key or access token"}))).into_response();
+    }
+    match state.graphql.get(&operation) {
+        Some(response) => Json(response.clone()).into_response(),
+        None => Json(json!({"errors": [{"message": format!("No mock response for {}", operation)}]})).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct InventoryAdjustment {
+    location_id: u64,
+    inventory_item_id: u64,
+    available_adjustment: i64,
+}
+
+async fn adjust_inventory(
+    State(state): State<Arc<Mutex<MockState>>>,
+    Path(version): Path<String>,
+    Json(adjustment): Json<InventoryAdjustment>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push(format!("POST /admin/api/{}/inventory_levels/adjust.json", version));
+
+    let available = state.inventory.entry((adjustment.location_id, adjustment.inventory_item_id)).or_default();
+    *available += adjustment.available_adjustment;
+    Json(json!({
+        "inventory_level": {
+            "inventory_item_id": adjustment.inventory_item_id,
+            "location_id": adjustment.location_id,
+            "available": *available,
+            "updated_at": "2025-01-01T00:00:00Z"
+        }
+    }))
+    .into_response()
+}
+
+async fn list_resource(
+    State(state): State<Arc<Mutex<MockState>>>,
+    Path((version, resource)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    list(&state, &version, &resource, &query, &headers)
+}
+
+async fn list_child_resource(
+    State(state): State<Arc<Mutex<MockState>>>,
+    Path((version, parent, resource)): Path<(String, String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    list(&state, &version, &format!("{}/{}", parent, resource), &query, &headers)
+}
+
+async fn list_nested_resource(
+    State(state): State<Arc<Mutex<MockState>>>,
+    Path((version, parent, id, resource)): Path<(String, String, String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    list(&state, &version, &format!("{}/{}/{}", parent, id, resource), &query, &headers)
+}
+
+fn list(
+    state: &Mutex<MockState>,
+    version: &str,
+    resource: &str,
+    query: &HashMap<String, String>,
+    headers: &HeaderMap,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push(format!("GET /admin/api/{}/{}", version, resource));
+
+    if headers.get("X-Shopify-Access-Token").is_none() {
+        return (StatusCode::UNAUTHORIZED, Json(json!({"errors": "[API] Invalid API key or access token"}))).into_response();
+    }
+    if state.throttled > 0 {
+        state.throttled -= 1;
+        let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(json!({"errors": "Exceeded 2 calls per second for api client. Reduce request rates to resume uninterrupted service."}))).into_response();
+        response.headers_mut().insert("Retry-After", HeaderValue::from_static("0.1"));
+        return response;
+    }
+    let Some(name) = resource.strip_suffix(".json") else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(items) = state.resources.get(name) else {
+        return show(&state, name, query);
+    };
+
+    // Cursors are just page numbers here; Shopify's are opaque
+    let limit = query.get("limit").and_then(|l| l.parse().ok()).unwrap_or(state.page_size).min(state.page_size);
+    let page: usize = query.get("page_info").and_then(|p| p.strip_prefix("p")).and_then(|p| p.parse().ok()).unwrap_or(0);
+    let pages = items.len().div_ceil(limit).max(1);
+    let slice: Vec<Value> = items.iter().skip(page * limit).take(limit).cloned().collect();
+
+    let link = |page: usize, rel: &str| {
+        format!("<{}/admin/api/{}/{}?limit={}&page_info=p{}>; rel=\"{}\"", state.origin, version, resource, limit, page, rel)
+    };
+    let links: Vec<String> = [
+        (page > 0).then(|| link(page - 1, "previous")),
+        (page + 1 < pages).then(|| link(page + 1, "next")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let key = name.rsplit('/').next().unwrap_or(name);
+    let mut response = Json(json!({ key: slice })).into_response();
+    response.headers_mut().insert("X-Shopify-Shop-Api-Call-Limit", HeaderValue::from_static("1/40"));
+    if !links.is_empty() {
+        response.headers_mut().insert("Link", HeaderValue::from_str(&links.join(", ")).unwrap());
+    }
+    response
+}
+
+/// One item of a resource, e.g. `orders/1`, as `{"order": {...}}`. Like
+/// Shopify, `fields` trims it to the listed top-level keys.
+fn show(state: &MockState, name: &str, query: &HashMap<String, String>) -> Response {
+    let not_found = || (StatusCode::NOT_FOUND, Json(json!({"errors": "Not Found"}))).into_response();
+    let Some((resource, Ok(id))) = name.rsplit_once('/').map(|(resource, id)| (resource, id.parse::<u64>())) else {
+        return not_found();
+    };
+    let Some(item) = state.resources.get(resource)
+        .and_then(|items| items.iter().find(|item| item["id"] == id))
+    else {
+        return not_found();
+    };
+
+    let item = match query.get("fields") {
+        Some(fields) => {
+            let fields: Vec<&str> = fields.split(',').map(str::trim).collect();
+            item.as_object()
+                .map(|item| item.iter().filter(|(key, _)| fields.contains(&key.as_str())).map(|(k, v)| (k.clone(), v.clone())).collect())
+                .map(Value::Object)
+                .unwrap_or_else(|| item.clone())
+        }
+        None => item.clone(),
+    };
+    let key = resource.rsplit('/').next().unwrap_or(resource);
+    Json(json!({ key.strip_suffix('s').unwrap_or(key): item })).into_response()
+}
+
+/// Adds the item posted as `{"<singular>": {...}}` to a REST resource, with
+/// the fields Shopify fills in, and answers with it.
+async fn create_resource(
+    State(state): State<Arc<Mutex<MockState>>>,
+    Path((version, resource)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push(format!("POST /admin/api/{}/{}", version, resource));
+
+    if headers.get("X-Shopify-Access-Token").is_none() {
+        return (StatusCode::UNAUTHORIZED, Json(json!({"errors": "[API] Invalid API key or access token"}))).into_response();
+    }
+    let Some(name) = resource.strip_suffix(".json") else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let singular = name.strip_suffix('s').unwrap_or(name).to_string();
+    let Some(Value::Object(mut item)) = body.get(&singular).cloned() else {
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({"errors": {singular: ["is required"]}}))).into_response();
+    };
+
+    let last_id = state.last_id;
+    let items = state.resources.entry(name.to_string()).or_default();
+    let id = items.iter().filter_map(|item| item["id"].as_u64()).max().unwrap_or(0).max(last_id) + 1;
+    item.insert("id".to_string(), json!(id));
+    item.insert("created_at".to_string(), json!("2025-01-01T00:00:00Z"));
+    if name == "storefront_access_tokens" {
+        item.insert("access_token".to_string(), json!(format!("storefront-token-{}", id)));
+        item.insert("access_scope".to_string(), json!("unauthenticated_read_product_listings"));
+    }
+    items.push(Value::Object(item.clone()));
+    state.last_id = id;
+    (StatusCode::CREATED, Json(json!({ singular: item }))).into_response()
+}
+
+/// Removes an item created with `create_resource` or served with `resource`.
+async fn delete_resource(
+    State(state): State<Arc<Mutex<MockState>>>,
+    Path((version, resource, id)): Path<(String, String, String)>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push(format!("DELETE /admin/api/{}/{}/{}", version, resource, id));
+
+    let id: Option<u64> = id.strip_suffix(".json").and_then(|id| id.parse().ok());
+    let Some(items) = state.resources.get_mut(&resource) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match items.iter().position(|item| item["id"].as_u64() == id) {
+        Some(index) => {
+            items.remove(index);
+            Json(json!({})).into_response()
+        }
+        None => (StatusCode::NOT_FOUND, Json(json!({"errors": "Not Found"}))).into_response(),
+    }
+}
+
+/// Stores nothing, answering with the asset as Shopify describes one.
+async fn put_theme_asset(
+    State(state): State<Arc<Mutex<MockState>>>,
+    Path((version, theme)): Path<(String, u64)>,
+    Json(body): Json<Value>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push(format!("PUT /admin/api/{}/themes/{}/assets.json", version, theme));
+
+    let asset = &body["asset"];
+    Json(json!({
+        "asset": {
+            "key": asset["key"],
+            "theme_id": theme,
+            "content_type": "application/javascript",
+            "size": asset["value"].as_str().map(str::len),
+            "created_at": "2025-01-01T00:00:00Z",
+            "updated_at": "2025-01-01T00:00:00Z"
+        }
+    }))
+    .into_response()
+}
+
+// =============================================================================
+// App State
+// =============================================================================
+
+/// An `AppState` for `config` on in-memory token and state stores, sending
+/// Shopify calls to `shopify`. Postgres-backed features are unavailable, as
+/// when `DATABASE_URL` is unset. Webhooks the app processes arrive on the
+/// returned receiver.
+pub fn app_state(config: AppConfig, shopify: &MockShopify) -> (AppState, mpsc::UnboundedReceiver<QueuedWebhook>) {
+    // As `DatabaseRouter::unavailable`, but failing fast rather than after the
+    // pool's 30s acquire timeout
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(100))
+        .connect_lazy_with(sqlx::postgres::PgConnectOptions::new());
+    let db = DatabaseRouter::from_pools(pool, BTreeMap::new());
+    build_state(config, shopify, db, Arc::new(MemoryTokenStore::default()), Arc::new(MemoryStateStore::default()))
+}
+
+fn build_state(
+    config: AppConfig,
+    shopify: &MockShopify,
+    db: DatabaseRouter,
+    token_store: Arc<dyn TokenStore>,
+    state_store: Arc<dyn StateStore>,
+) -> (AppState, mpsc::UnboundedReceiver<QueuedWebhook>) {
+    let (processed, webhooks) = mpsc::unbounded_channel();
+    let webhook_queue = WebhookDispatcher::start(
+        &config.webhook_queue,
+        Arc::new(move |webhook: QueuedWebhook| {
+            let _ = processed.send(webhook);
+            Box::pin(async { Ok(()) })
+        }),
+    );
+    let shopify = ShopifyClient::new(&config.shop, &config.http)
+        .expect("Shopify client")
+        .with_origin(shopify.origin());
+
+    let state = AppState {
+        token_store,
+        state_store,
+        webhook_events: WebhookEventStore::new(db.clone()),
+        webhook_queue,
+        webhook_sampler: None,
+        events: EventBroadcaster::new(),
+        customer_mirror: CustomerMirrorStore::new(db.clone()),
+        order_mirror: OrderMirrorStore::new(db.clone()),
+        orders: OrderStore::new(db.clone()),
+        catalog: CatalogStore::new(db.clone()),
+        scheduler: Scheduler::new(&config.scheduler, None),
+        job_queue: JobQueue::new(&config.job_queue, None),
+        recovery_messages: RecoveryMessageStore::new(db.clone()),
+        email: config.mail.clone().map(|mail| EmailSender::new(mail, config.branding.clone(), Some(EmailLogStore::new(db.clone())))),
+        shopify,
+        api_usage: ApiUsageStore::new(db.clone()),
+        shop_secrets: ShopSecretStore::new(db.clone(), &config.database).expect("shop secret store"),
+        document_templates: DocumentTemplateStore::new(db.clone()),
+        fulfillment_routing: FulfillmentRoutingStore::new(db.clone()),
+        low_stock: LowStockStore::new(db.clone()),
+        warehouse_exports: WarehouseExportStore::new(db.clone()),
+        token_audit: TokenAuditStore::new(db.clone()),
+        idempotency_keys: IdempotencyStore::new(db.clone()),
+        personal_data: PersonalDataStore::new(db.clone()).with_export_dir(config.downloads.export_dir.clone()),
+        response_cache: ResponseCache::new(&config.response_cache).expect("response cache"),
+        db,
+        config,
+    };
+    (state, webhooks)
+}
+
+// =============================================================================
+// Test Database
+// =============================================================================
+
+/// An empty, migrated Postgres database of its own for one test. With
+/// `TEST_DATABASE_URL` set it's a new database on that server (the role needs
+/// `CREATEDB`; databases are left behind for inspection); otherwise a
+/// throwaway container, which needs Docker.
+pub struct TestDatabase {
+    url: String,
+    _container: Option<ContainerAsync<Postgres>>,
+}
+
+impl TestDatabase {
+    pub async fn start() -> Self {
+        if let Ok(server) = std::env::var("TEST_DATABASE_URL") {
+            let name = format!("shopify_test_{}", uuid::Uuid::new_v4().simple());
+            let mut admin = PgConnection::connect(&server).await.expect("connect to TEST_DATABASE_URL");
+            sqlx::query(&format!("CREATE DATABASE {}", name))
+                .execute(&mut admin)
+                .await
+                .expect("create test database");
+            let mut url = url::Url::parse(&server).expect("TEST_DATABASE_URL");
+            url.set_path(&name);
+            return Self { url: url.to_string(), _container: None };
+        }
+
+        let container = Postgres::default()
+            .start()
+            .await
+            .expect("start a Postgres container (is Docker running? or set TEST_DATABASE_URL)");
+        let host = container.get_host().await.expect("container host");
+        let port = container.get_host_port_ipv4(5432).await.expect("container port");
+        Self { url: format!("postgres://postgres:postgres@{}:{}/postgres", host, port), _container: Some(container) }
+    }
+
+    /// Connects with `config`'s pool settings, running the migrations.
+    pub async fn connect(&self, config: &DatabaseConfig) -> DatabaseRouter {
+        let config = DatabaseConfig {
+            database_url: Some(self.url.clone()),
+            regional_database_urls: Default::default(),
+            ..config.clone()
+        };
+        DatabaseRouter::connect(&config).await.expect("connect to test database")
+    }
+
+    /// As `app_state`, but with tokens, OAuth states and every Postgres-backed
+    /// feature in this database, as when `DATABASE_URL` is set. Token changes
+    /// are audited, as in production.
+    pub async fn app_state(
+        &self,
+        config: AppConfig,
+        shopify: &MockShopify,
+    ) -> (AppState, mpsc::UnboundedReceiver<QueuedWebhook>) {
+        let db = self.connect(&config.database).await;
+        let stores = TokenStoreConfig { backend: TokenStoreBackend::Postgres, redis_url: None, ..config.token_store.clone() };
+        let (token_store, state_store) = connect_stores(&stores, &db, &config.database.encryption_key)
+            .await
+            .expect("Postgres token and state stores");
+        let token_store = Arc::new(AuditedTokenStore::new(token_store, Arc::new(TokenAuditStore::new(db.clone()))));
+        build_state(config, shopify, db, token_store, state_store)
+    }
+}
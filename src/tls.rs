@@ -0,0 +1,95 @@
+use axum_server::tls_rustls::RustlsConfig;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+use crate::error::{AppError, AppResult};
+
+// =============================================================================
+// TLS Configuration
+// =============================================================================
+
+/// Native HTTPS, for deployments without a TLS-terminating proxy in front.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    /// PEM certificate chain, leaf first
+    pub cert_path: PathBuf,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_path: PathBuf,
+    /// How often the files are checked for a renewed certificate
+    pub reload_interval: Duration,
+}
+
+impl TlsConfig {
+    /// `None` unless `TLS_CERT_PATH` and `TLS_KEY_PATH` are set; setting only
+    /// one of them is a configuration error.
+    pub fn from_env() -> AppResult<Option<Self>> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+
+        match (var("TLS_CERT_PATH"), var("TLS_KEY_PATH")) {
+            (None, None) => Ok(None),
+            (Some(cert_path), Some(key_path)) => Ok(Some(Self {
+                cert_path: cert_path.into(),
+                key_path: key_path.into(),
+                reload_interval: var("TLS_RELOAD_INTERVAL_SECS")
+                    .and_then(|v| v.parse().ok())
+                    .filter(|secs| *secs > 0)
+                    .map(Duration::from_secs)
+                    .unwrap_or(Duration::from_secs(60)),
+            })),
+            _ => Err(AppError::Config(
+                "TLS_CERT_PATH and TLS_KEY_PATH must be set together".to_string(),
+            )),
+        }
+    }
+
+    /// Reads the certificate and key, so a bad path fails startup rather than
+    /// the first handshake.
+    pub async fn load(&self) -> AppResult<RustlsConfig> {
+        // Several rustls versions are linked in; pick the provider for this one
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        RustlsConfig::from_pem_file(&self.cert_path, &self.key_path)
+            .await
+            .map_err(|e| AppError::Config(format!(
+                "TLS certificate {} / key {}: {}",
+                self.cert_path.display(),
+                self.key_path.display(),
+                e
+            )))
+    }
+}
+
+// =============================================================================
+// Certificate Reloading
+// =============================================================================
+
+async fn modified(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.and_then(|m| m.modified()).ok()
+}
+
+/// Swaps in the certificate and key whenever either file changes, so renewals
+/// (certbot, cert-manager) take effect without a restart. Existing
+/// connections keep the certificate they were opened with, and a renewal
+/// that doesn't parse leaves the current one in place until the next check.
+pub async fn watch_certificates(tls: TlsConfig, config: RustlsConfig) {
+    let mut loaded = (modified(&tls.cert_path).await, modified(&tls.key_path).await);
+    let mut interval = tokio::time::interval(tls.reload_interval);
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let current = (modified(&tls.cert_path).await, modified(&tls.key_path).await);
+        if current == loaded {
+            continue;
+        }
+
+        match config.reload_from_pem_file(&tls.cert_path, &tls.key_path).await {
+            Ok(()) => {
+                info!("🔒 Reloaded TLS certificate from {}", tls.cert_path.display());
+                loaded = current;
+            }
+            Err(e) => warn!("Keeping the current TLS certificate, reload failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{MatchedPath, Query, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::{
+    AppState,
+    database::TokenAuditFilter,
+    error::{AppError, AppResult},
+    middleware::client_ip,
+    token_store::{InstalledShop, TokenStore},
+};
+
+const DEFAULT_AUDIT_LIMIT: i64 = 100;
+const MAX_AUDIT_LIMIT: i64 = 1000;
+
+// =============================================================================
+// Audit Entries
+// =============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    /// First token for a shop
+    Store,
+    /// A token replacing an existing one
+    Rotate,
+    Read,
+    Delete,
+    /// Expired tokens swept by the cleanup job
+    Expire,
+    /// Tokens re-encrypted onto the current scheme
+    Rekey,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Store => "store",
+            Self::Rotate => "rotate",
+            Self::Read => "read",
+            Self::Delete => "delete",
+            Self::Expire => "expire",
+            Self::Rekey => "rekey",
+        }
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "store" => Ok(Self::Store),
+            "rotate" => Ok(Self::Rotate),
+            "read" => Ok(Self::Read),
+            "delete" => Ok(Self::Delete),
+            "expire" => Ok(Self::Expire),
+            "rekey" => Ok(Self::Rekey),
+            other => Err(format!("Unknown audit action: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOutcome {
+    Success,
+    /// Nothing stored (or only an expired token) for the shop
+    NotFound,
+    Failure,
+}
+
+impl AuditOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::NotFound => "not_found",
+            Self::Failure => "failure",
+        }
+    }
+}
+
+impl FromStr for AuditOutcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "success" => Ok(Self::Success),
+            "not_found" => Ok(Self::NotFound),
+            "failure" => Ok(Self::Failure),
+            other => Err(format!("Unknown audit outcome: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenAuditEntry {
+    pub shop_domain: Option<String>,
+    pub action: AuditAction,
+    pub outcome: AuditOutcome,
+    pub actor: String,
+    pub source_ip: Option<String>,
+    pub detail: Option<String>,
+}
+
+/// Where audit entries are written.
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    async fn record(&self, entry: TokenAuditEntry) -> AppResult<()>;
+}
+
+// =============================================================================
+// Request Context
+// =============================================================================
+
+/// Who is touching tokens: the route for requests, the job name for
+/// background work.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditContext {
+    pub actor: String,
+    pub source_ip: Option<String>,
+}
+
+tokio::task_local! {
+    static AUDIT_CONTEXT: AuditContext;
+}
+
+impl AuditContext {
+    /// The context of the running task; work spawned outside a request or
+    /// `with_actor` is attributed to `system`.
+    pub fn current() -> Self {
+        AUDIT_CONTEXT.try_with(Clone::clone).unwrap_or_else(|_| Self {
+            actor: "system".to_string(),
+            source_ip: None,
+        })
+    }
+}
+
+/// Runs `future` with token access attributed to `actor`.
+pub async fn with_actor<F: Future>(actor: &str, future: F) -> F::Output {
+    let context = AuditContext { actor: actor.to_string(), source_ip: None };
+    AUDIT_CONTEXT.scope(context, future).await
+}
+
+/// Attributes token access during the request to its route and client IP.
+pub async fn audit_context_middleware(request: Request, next: Next) -> Response {
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let context = AuditContext {
+        actor: format!("{} {}", request.method(), path),
+        source_ip: client_ip(request.headers(), request.extensions().get()),
+    };
+    AUDIT_CONTEXT.scope(context, next.run(request)).await
+}
+
+// =============================================================================
+// Audited Token Store
+// =============================================================================
+
+/// Records every store, rotation, read and deletion going through the
+/// wrapped token store. A failed audit write is logged, not surfaced, so
+/// auditing can't take token access down with it.
+pub struct AuditedTokenStore {
+    inner: Arc<dyn TokenStore>,
+    sink: Arc<dyn AuditSink>,
+}
+
+impl AuditedTokenStore {
+    pub fn new(inner: Arc<dyn TokenStore>, sink: Arc<dyn AuditSink>) -> Self {
+        Self { inner, sink }
+    }
+
+    async fn record(&self, shop_domain: Option<&str>, action: AuditAction, outcome: AuditOutcome, detail: Option<String>) {
+        let context = AuditContext::current();
+        let entry = TokenAuditEntry {
+            shop_domain: shop_domain.map(str::to_string),
+            action,
+            outcome,
+            actor: context.actor,
+            source_ip: context.source_ip,
+            detail,
+        };
+        if let Err(e) = self.sink.record(entry).await {
+            warn!("Failed to write token audit entry ({} {:?}): {}", action.as_str(), shop_domain, e);
+        }
+    }
+
+    async fn record_result<T>(
+        &self,
+        shop_domain: Option<&str>,
+        action: AuditAction,
+        result: &AppResult<T>,
+        found: impl FnOnce(&T) -> bool,
+    ) {
+        let (outcome, detail) = match result {
+            Ok(value) if found(value) => (AuditOutcome::Success, None),
+            Ok(_) => (AuditOutcome::NotFound, None),
+            Err(e) => (AuditOutcome::Failure, Some(e.to_string())),
+        };
+        self.record(shop_domain, action, outcome, detail).await;
+    }
+
+    /// One entry for a sweep across shops, skipped when it touched nothing.
+    async fn record_sweep(&self, action: AuditAction, result: &AppResult<u64>) {
+        let (outcome, detail) = match result {
+            Ok(0) => return,
+            Ok(count) => (AuditOutcome::Success, format!("{} tokens", count)),
+            Err(e) => (AuditOutcome::Failure, e.to_string()),
+        };
+        self.record(None, action, outcome, Some(detail)).await;
+    }
+}
+
+#[async_trait]
+impl TokenStore for AuditedTokenStore {
+    async fn store_token(
+        &self,
+        shop_domain: &str,
+        access_token: &str,
+        scope: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        let action = match self.inner.get_scope(shop_domain).await {
+            Ok(Some(_)) => AuditAction::Rotate,
+            _ => AuditAction::Store,
+        };
+        let result = self.inner.store_token(shop_domain, access_token, scope, expires_at).await;
+        self.record_result(Some(shop_domain), action, &result, |_| true).await;
+        result
+    }
+
+    async fn get_token(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        let result = self.inner.get_token(shop_domain).await;
+        self.record_result(Some(shop_domain), AuditAction::Read, &result, Option::is_some).await;
+        result
+    }
+
+    // Scopes and expiry don't expose the credential, so they go unaudited
+    async fn get_scope(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        self.inner.get_scope(shop_domain).await
+    }
+
+    async fn get_expiry(&self, shop_domain: &str) -> AppResult<Option<DateTime<Utc>>> {
+        self.inner.get_expiry(shop_domain).await
+    }
+
+    async fn delete_token(&self, shop_domain: &str) -> AppResult<bool> {
+        let result = self.inner.delete_token(shop_domain).await;
+        self.record_result(Some(shop_domain), AuditAction::Delete, &result, |deleted| *deleted).await;
+        result
+    }
+
+    async fn delete_expired_tokens(&self) -> AppResult<u64> {
+        let result = self.inner.delete_expired_tokens().await;
+        self.record_sweep(AuditAction::Expire, &result).await;
+        result
+    }
+
+    async fn list_shops(&self) -> AppResult<Vec<InstalledShop>> {
+        self.inner.list_shops().await
+    }
+
+    async fn rekey_legacy_tokens(&self) -> AppResult<u64> {
+        let result = self.inner.rekey_legacy_tokens().await;
+        self.record_sweep(AuditAction::Rekey, &result).await;
+        result
+    }
+}
+
+// =============================================================================
+// Audit Log Handler
+// =============================================================================
+
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    pub shop: Option<String>,
+    pub action: Option<String>,
+    pub outcome: Option<String>,
+    pub actor: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+impl AuditQuery {
+    pub fn into_filter(self) -> AppResult<TokenAuditFilter> {
+        let action = self.action.map(|action| action.parse::<AuditAction>()).transpose().map_err(AppError::BadRequest)?;
+        let outcome = self.outcome.map(|outcome| outcome.parse::<AuditOutcome>()).transpose().map_err(AppError::BadRequest)?;
+        let limit = self.limit.unwrap_or(DEFAULT_AUDIT_LIMIT);
+        if !(1..=MAX_AUDIT_LIMIT).contains(&limit) {
+            return Err(AppError::BadRequest(format!("limit must be between 1 and {}", MAX_AUDIT_LIMIT)));
+        }
+
+        Ok(TokenAuditFilter {
+            shop_domain: self.shop,
+            action: action.map(|action| action.as_str().to_string()),
+            outcome: outcome.map(|outcome| outcome.as_str().to_string()),
+            actor: self.actor,
+            since: self.since,
+            until: self.until,
+            limit,
+        })
+    }
+}
+
+/// `GET /admin/audit` — token audit entries, newest first, filtered by
+/// `shop`, `action`, `outcome`, `actor` and a `since`/`until` window.
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    tag = "audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Matching audit entries", body = crate::openapi::TokenAuditList),
+        (status = 400, description = "Unknown action or outcome, or limit out of range", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn token_audit_handler(
+    Query(query): Query<AuditQuery>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let filter = query.into_filter()?;
+    let events = state.token_audit.query(&filter).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "count": events.len(),
+        "events": events
+    }))))
+}
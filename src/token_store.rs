@@ -0,0 +1,1031 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::{
+    AppState,
+    database::{DatabaseRouter, PgStateStore, PgTokenStore, TokenEncryption},
+    error::{AppError, AppResult},
+    scheduler::{Job, JobScope, Schedule},
+};
+
+#[cfg(feature = "mysql")]
+mod mysql;
+#[cfg(feature = "mysql")]
+pub use mysql::{connect_mysql, MySqlStateStore, MySqlTokenStore};
+
+// =============================================================================
+// Storage Traits
+// =============================================================================
+
+/// A shop with a stored, unexpired access token.
+#[derive(Debug, Clone, PartialEq, Serialize, utoipa::ToSchema)]
+pub struct InstalledShop {
+    pub shop_domain: String,
+    pub scope: String,
+    /// When the first token was stored; re-authorizing keeps it
+    pub installed_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Where shop access tokens and their granted scopes are kept.
+#[async_trait]
+pub trait TokenStore: Send + Sync {
+    /// `expires_at` is set for online tokens; offline tokens never expire.
+    async fn store_token(
+        &self,
+        shop_domain: &str,
+        access_token: &str,
+        scope: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()>;
+
+    /// The stored token, treating an expired one as absent.
+    async fn get_token(&self, shop_domain: &str) -> AppResult<Option<String>>;
+
+    /// Comma-separated scopes granted with the stored, unexpired token.
+    async fn get_scope(&self, shop_domain: &str) -> AppResult<Option<String>>;
+
+    /// When the stored token expires; `None` for offline tokens.
+    async fn get_expiry(&self, shop_domain: &str) -> AppResult<Option<DateTime<Utc>>>;
+
+    async fn delete_token(&self, shop_domain: &str) -> AppResult<bool>;
+
+    /// Removes tokens past their expiry, returning how many went.
+    async fn delete_expired_tokens(&self) -> AppResult<u64>;
+
+    /// Shops with an unexpired token, most recently authorized first.
+    async fn list_shops(&self) -> AppResult<Vec<InstalledShop>>;
+
+    /// Moves tokens encrypted under an older scheme onto the current one.
+    async fn rekey_legacy_tokens(&self) -> AppResult<u64> {
+        Ok(0)
+    }
+}
+
+/// What `/auth` stores with a CSRF state, handed back to the callback. The
+/// state is only good for the shop and browser that started the flow.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuthStateRecord {
+    /// Shop the flow was started for; empty for states issued before this was
+    /// recorded, which no callback matches
+    #[serde(default)]
+    pub shop: String,
+    /// Comma-separated scopes requested from Shopify
+    #[serde(default)]
+    pub scopes: String,
+    /// Where to send the merchant once the install finishes, already validated
+    #[serde(default)]
+    pub return_to: Option<String>,
+    #[serde(default)]
+    pub client_ip: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+}
+
+/// What a finished code exchange left behind, for repeats of its callback.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletedExchange {
+    /// Comma-separated scopes Shopify granted
+    pub scope: String,
+    /// When an online token expires; offline tokens don't
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Where a state stands when a callback presents it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateClaim {
+    /// The first callback for the state, which now owns the code exchange
+    Claimed(OAuthStateRecord),
+    /// A repeat of a callback whose exchange hasn't finished yet
+    Exchanging(OAuthStateRecord),
+    /// A repeat of a callback whose exchange already succeeded
+    Completed(OAuthStateRecord, CompletedExchange),
+    /// Unknown, expired, or claimed for a different code
+    Invalid,
+}
+
+impl StateClaim {
+    /// The claim on an already-claimed state: only repeats of the callback
+    /// that claimed it, carrying the same code, may see how it went.
+    pub(crate) fn existing(
+        record: OAuthStateRecord,
+        claimed_code_hash: Option<&str>,
+        exchange: Option<CompletedExchange>,
+        code_hash: &str,
+    ) -> Self {
+        match (claimed_code_hash, exchange) {
+            (Some(claimed), _) if claimed != code_hash => Self::Invalid,
+            (Some(_), None) => Self::Exchanging(record),
+            (Some(_), Some(exchange)) => Self::Completed(record, exchange),
+            (None, _) => Self::Invalid,
+        }
+    }
+}
+
+/// Columns `oauth_states` keeps a record in, in `OAuthStateRow` order.
+pub(crate) const STATE_RECORD_COLUMNS: &str = "shop_domain, scopes, return_to, client_ip, user_agent";
+
+/// The claim columns, selected after `STATE_RECORD_COLUMNS` to see how an
+/// earlier callback is getting on.
+pub(crate) const STATE_CLAIM_COLUMNS: &str = "code_hash, exchange_result";
+
+// Rows from before the record columns existed have NULLs
+#[derive(sqlx::FromRow)]
+pub(crate) struct OAuthStateRow {
+    shop_domain: Option<String>,
+    scopes: Option<String>,
+    return_to: Option<String>,
+    client_ip: Option<String>,
+    user_agent: Option<String>,
+    #[sqlx(default)]
+    code_hash: Option<String>,
+    /// `CompletedExchange` as JSON
+    #[sqlx(default)]
+    exchange_result: Option<String>,
+}
+
+impl OAuthStateRow {
+    #[cfg_attr(not(feature = "mysql"), allow(dead_code))]
+    pub(crate) fn is_unclaimed(&self) -> bool {
+        self.code_hash.is_none()
+    }
+
+    pub(crate) fn into_claim(self, code_hash: &str) -> StateClaim {
+        let claimed_code_hash = self.code_hash.clone();
+        let exchange = self
+            .exchange_result
+            .as_deref()
+            .and_then(|json| serde_json::from_str(json).ok());
+        StateClaim::existing(self.into(), claimed_code_hash.as_deref(), exchange, code_hash)
+    }
+}
+
+impl From<OAuthStateRow> for OAuthStateRecord {
+    fn from(row: OAuthStateRow) -> Self {
+        Self {
+            shop: row.shop_domain.unwrap_or_default(),
+            scopes: row.scopes.unwrap_or_default(),
+            return_to: row.return_to,
+            client_ip: row.client_ip,
+            user_agent: row.user_agent,
+        }
+    }
+}
+
+pub(crate) fn exchange_json(exchange: &CompletedExchange) -> String {
+    serde_json::to_string(exchange).expect("exchange result serializes")
+}
+
+/// One-time CSRF states issued when the OAuth flow starts. A callback claims
+/// its state before exchanging the code, so a double-click or a retried
+/// redirect can't exchange the same code twice, even on another instance.
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    async fn store_state(&self, state_token: &str, ttl_seconds: i64, record: &OAuthStateRecord) -> AppResult<()>;
+
+    /// Claims an unexpired state for exchanging the code hashed as
+    /// `code_hash`. Only the first call gets `Claimed`; repeats with the same
+    /// code learn how that exchange is going.
+    async fn claim_state(&self, state_token: &str, code_hash: &str) -> AppResult<StateClaim>;
+
+    /// Records a claimed state's successful exchange for repeat callbacks,
+    /// which see it until the state expires.
+    async fn complete_state(&self, state_token: &str, exchange: &CompletedExchange) -> AppResult<()>;
+
+    /// Drops a claimed state whose exchange failed; it can't be retried, as
+    /// Shopify codes are single use.
+    async fn release_state(&self, state_token: &str) -> AppResult<()>;
+
+    async fn cleanup_expired_states(&self) -> AppResult<u64>;
+}
+
+// =============================================================================
+// Backend Configuration
+// =============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenStoreBackend {
+    Postgres,
+    /// MySQL or MariaDB at `DATABASE_URL`; needs the `mysql` feature
+    MySql,
+    Sqlite,
+    /// Lost on restart; for tests and throwaway deployments
+    Memory,
+}
+
+impl FromStr for TokenStoreBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "postgres" | "postgresql" => Ok(Self::Postgres),
+            "mysql" | "mariadb" => Ok(Self::MySql),
+            "sqlite" => Ok(Self::Sqlite),
+            "memory" => Ok(Self::Memory),
+            other => Err(format!(
+                "Unknown token store backend: {} (expected postgres, mysql, sqlite or memory)",
+                other
+            )),
+        }
+    }
+}
+
+impl TokenStoreBackend {
+    /// The database backend a `DATABASE_URL` points at, from its scheme.
+    pub fn for_database_url(url: &str) -> Self {
+        let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_lowercase());
+        match scheme.as_deref() {
+            Some("mysql") | Some("mariadb") => Self::MySql,
+            _ => Self::Postgres,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenStoreConfig {
+    pub backend: TokenStoreBackend,
+    /// `DATABASE_URL` when it points at MySQL/MariaDB
+    pub mysql_url: Option<String>,
+    pub sqlite_url: String,
+    /// When set, OAuth states live in Redis and token lookups are cached there
+    pub redis_url: Option<String>,
+    /// How long a cached token is served before the backend is asked again;
+    /// zero turns the cache off
+    pub cache_ttl_seconds: u64,
+}
+
+impl Default for TokenStoreConfig {
+    fn default() -> Self {
+        Self {
+            backend: TokenStoreBackend::Postgres,
+            mysql_url: None,
+            sqlite_url: "sqlite://shopify_tokens.db".to_string(),
+            redis_url: None,
+            cache_ttl_seconds: 300,
+        }
+    }
+}
+
+impl TokenStoreConfig {
+    pub fn from_env() -> AppResult<Self> {
+        let defaults = Self::default();
+        let database_url = std::env::var("DATABASE_URL").ok().filter(|url| !url.is_empty());
+        let database_backend = database_url.as_deref().map(TokenStoreBackend::for_database_url);
+        Ok(Self {
+            // Without an explicit backend, follow the DATABASE_URL scheme
+            backend: match std::env::var("TOKEN_STORE_BACKEND") {
+                Ok(value) => value.parse().map_err(AppError::Config)?,
+                Err(_) => database_backend.unwrap_or(defaults.backend),
+            },
+            mysql_url: database_url.filter(|_| database_backend == Some(TokenStoreBackend::MySql)),
+            sqlite_url: std::env::var("TOKEN_STORE_SQLITE_URL").unwrap_or(defaults.sqlite_url),
+            redis_url: std::env::var("REDIS_URL").ok().filter(|url| !url.is_empty()),
+            cache_ttl_seconds: match std::env::var("TOKEN_CACHE_TTL_SECONDS") {
+                Ok(value) => value.parse()
+                    .map_err(|e| AppError::Config(format!("TOKEN_CACHE_TTL_SECONDS: {}", e)))?,
+                Err(_) => defaults.cache_ttl_seconds,
+            },
+        })
+    }
+}
+
+/// Builds the token and state stores for the configured backend, moving
+/// states and a token cache to Redis when `REDIS_URL` is set.
+pub async fn connect_stores(
+    config: &TokenStoreConfig,
+    db: &DatabaseRouter,
+    encryption_key: &Secret<String>,
+) -> AppResult<(Arc<dyn TokenStore>, Arc<dyn StateStore>)> {
+    let (tokens, states) = connect_backend(config, db, encryption_key).await?;
+    let Some(redis_url) = &config.redis_url else {
+        return Ok((tokens, states));
+    };
+
+    info!("🔄 Connecting to Redis for OAuth states and the token cache");
+    let conn = redis::Client::open(redis_url.as_str())?
+        .get_multiplexed_tokio_connection()
+        .await?;
+
+    let tokens: Arc<dyn TokenStore> = if config.cache_ttl_seconds > 0 {
+        Arc::new(CachedTokenStore::new(tokens, conn.clone(), encryption_key, config.cache_ttl_seconds)?)
+    } else {
+        tokens
+    };
+    Ok((tokens, Arc::new(RedisStateStore::new(conn))))
+}
+
+async fn connect_backend(
+    config: &TokenStoreConfig,
+    db: &DatabaseRouter,
+    encryption_key: &Secret<String>,
+) -> AppResult<(Arc<dyn TokenStore>, Arc<dyn StateStore>)> {
+    match config.backend {
+        TokenStoreBackend::Postgres => Ok((
+            Arc::new(PgTokenStore::new(db.clone(), encryption_key)?),
+            Arc::new(PgStateStore::new(db.home().clone())),
+        )),
+        #[cfg(feature = "mysql")]
+        TokenStoreBackend::MySql => {
+            let url = config.mysql_url.as_deref()
+                .ok_or_else(|| AppError::Config("A mysql:// DATABASE_URL is required".to_string()))?;
+            let pool = connect_mysql(url).await?;
+            Ok((
+                Arc::new(MySqlTokenStore::new(pool.clone(), encryption_key)?),
+                Arc::new(MySqlStateStore::new(pool)),
+            ))
+        }
+        #[cfg(not(feature = "mysql"))]
+        TokenStoreBackend::MySql => Err(AppError::Config(
+            "MySQL storage needs a build with the `mysql` feature".to_string(),
+        )),
+        TokenStoreBackend::Sqlite => {
+            let pool = connect_sqlite(&config.sqlite_url).await?;
+            Ok((
+                Arc::new(SqliteTokenStore::new(pool.clone(), encryption_key)?),
+                Arc::new(SqliteStateStore::new(pool)),
+            ))
+        }
+        TokenStoreBackend::Memory => {
+            warn!("🧠 Tokens and OAuth states are kept in memory and will be lost on restart");
+            Ok((Arc::new(MemoryTokenStore::default()), Arc::new(MemoryStateStore::default())))
+        }
+    }
+}
+
+// =============================================================================
+// SQLite Backend
+// =============================================================================
+
+/// Opens (creating if needed) and migrates the SQLite token database.
+pub async fn connect_sqlite(url: &str) -> AppResult<SqlitePool> {
+    info!("🔄 Opening SQLite token store at {}", url);
+    let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+
+    // SQLite serializes writes anyway, and `sqlite::memory:` databases exist
+    // per connection, so a single connection keeps both cases simple
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await?;
+    sqlx::migrate!("./migrations/sqlite").run(&pool).await?;
+
+    Ok(pool)
+}
+
+#[derive(Clone)]
+pub struct SqliteTokenStore {
+    pool: SqlitePool,
+    encryption: TokenEncryption,
+}
+
+impl SqliteTokenStore {
+    pub fn new(pool: SqlitePool, encryption_key: &Secret<String>) -> AppResult<Self> {
+        let encryption = TokenEncryption::new(encryption_key)?;
+        Ok(Self { pool, encryption })
+    }
+}
+
+#[async_trait]
+impl TokenStore for SqliteTokenStore {
+    async fn store_token(
+        &self,
+        shop_domain: &str,
+        access_token: &str,
+        scope: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        let encrypted_token = self.encryption.encrypt_for_shop(shop_domain, access_token)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO shopify_tokens (shop_domain, encrypted_access_token, scope, expires_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (shop_domain)
+            DO UPDATE SET
+                encrypted_access_token = excluded.encrypted_access_token,
+                scope = excluded.scope,
+                expires_at = excluded.expires_at,
+                updated_at = unixepoch()
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(encrypted_token)
+        .bind(scope)
+        .bind(expires_at.map(|expires_at| expires_at.timestamp()))
+        .execute(&self.pool)
+        .await?;
+
+        info!("✅ Token stored for shop: {}", shop_domain);
+        Ok(())
+    }
+
+    async fn get_token(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        let row = sqlx::query_as::<_, (String,)>(
+            "SELECT encrypted_access_token FROM shopify_tokens
+             WHERE shop_domain = ?1 AND (expires_at IS NULL OR expires_at > ?2)"
+        )
+        .bind(shop_domain)
+        .bind(Utc::now().timestamp())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|(encrypted_token,)| self.encryption.decrypt_for_shop(shop_domain, &encrypted_token))
+            .transpose()
+    }
+
+    async fn get_scope(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        let row = sqlx::query_as::<_, (String,)>(
+            "SELECT scope FROM shopify_tokens
+             WHERE shop_domain = ?1 AND (expires_at IS NULL OR expires_at > ?2)"
+        )
+        .bind(shop_domain)
+        .bind(Utc::now().timestamp())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(scope,)| scope))
+    }
+
+    async fn get_expiry(&self, shop_domain: &str) -> AppResult<Option<DateTime<Utc>>> {
+        let row = sqlx::query_as::<_, (Option<i64>,)>(
+            "SELECT expires_at FROM shopify_tokens WHERE shop_domain = ?1"
+        )
+        .bind(shop_domain)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.and_then(|(expires_at,)| expires_at).and_then(|expires_at| DateTime::from_timestamp(expires_at, 0)))
+    }
+
+    async fn delete_token(&self, shop_domain: &str) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM shopify_tokens WHERE shop_domain = ?1")
+            .bind(shop_domain)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_expired_tokens(&self) -> AppResult<u64> {
+        let result = sqlx::query("DELETE FROM shopify_tokens WHERE expires_at <= ?1")
+            .bind(Utc::now().timestamp())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn list_shops(&self) -> AppResult<Vec<InstalledShop>> {
+        let rows = sqlx::query_as::<_, (String, String, i64, i64, Option<i64>)>(
+            "SELECT shop_domain, scope, created_at, updated_at, expires_at FROM shopify_tokens
+             WHERE expires_at IS NULL OR expires_at > ?1
+             ORDER BY updated_at DESC, shop_domain"
+        )
+        .bind(Utc::now().timestamp())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let timestamp = |seconds: i64| DateTime::from_timestamp(seconds, 0).unwrap_or_default();
+        Ok(rows
+            .into_iter()
+            .map(|(shop_domain, scope, created_at, updated_at, expires_at)| InstalledShop {
+                shop_domain,
+                scope,
+                installed_at: timestamp(created_at),
+                updated_at: timestamp(updated_at),
+                expires_at: expires_at.map(timestamp),
+            })
+            .collect())
+    }
+}
+
+#[derive(Clone)]
+pub struct SqliteStateStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStateStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl StateStore for SqliteStateStore {
+    async fn store_state(&self, state_token: &str, ttl_seconds: i64, record: &OAuthStateRecord) -> AppResult<()> {
+        sqlx::query(&format!(
+            "INSERT INTO oauth_states (state_token, expires_at, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            STATE_RECORD_COLUMNS
+        ))
+        .bind(state_token)
+        .bind(Utc::now().timestamp() + ttl_seconds)
+        .bind(&record.shop)
+        .bind(&record.scopes)
+        .bind(&record.return_to)
+        .bind(&record.client_ip)
+        .bind(&record.user_agent)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn claim_state(&self, state_token: &str, code_hash: &str) -> AppResult<StateClaim> {
+        let now = Utc::now().timestamp();
+        let claimed = sqlx::query_as::<_, OAuthStateRow>(&format!(
+            "UPDATE oauth_states SET code_hash = ?2
+             WHERE state_token = ?1 AND expires_at > ?3 AND code_hash IS NULL
+             RETURNING {}",
+            STATE_RECORD_COLUMNS
+        ))
+        .bind(state_token)
+        .bind(code_hash)
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(row) = claimed {
+            return Ok(StateClaim::Claimed(row.into()));
+        }
+
+        let existing = sqlx::query_as::<_, OAuthStateRow>(&format!(
+            "SELECT {}, {} FROM oauth_states WHERE state_token = ?1 AND expires_at > ?2",
+            STATE_RECORD_COLUMNS, STATE_CLAIM_COLUMNS
+        ))
+        .bind(state_token)
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(existing.map_or(StateClaim::Invalid, |row| row.into_claim(code_hash)))
+    }
+
+    async fn complete_state(&self, state_token: &str, exchange: &CompletedExchange) -> AppResult<()> {
+        sqlx::query("UPDATE oauth_states SET exchange_result = ?2 WHERE state_token = ?1")
+            .bind(state_token)
+            .bind(exchange_json(exchange))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn release_state(&self, state_token: &str) -> AppResult<()> {
+        sqlx::query("DELETE FROM oauth_states WHERE state_token = ?1")
+            .bind(state_token)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn cleanup_expired_states(&self) -> AppResult<u64> {
+        let result = sqlx::query("DELETE FROM oauth_states WHERE expires_at <= ?1")
+            .bind(Utc::now().timestamp())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+// =============================================================================
+// Redis State Store and Token Cache
+// =============================================================================
+
+const STATE_KEY_PREFIX: &str = "oauth_state:";
+/// Set once a callback claims the state, with the state's remaining TTL
+const STATE_CLAIM_KEY_PREFIX: &str = "oauth_state_claim:";
+const TOKEN_CACHE_KEY_PREFIX: &str = "token_cache:";
+
+/// OAuth states as Redis keys with the state's TTL, so expiry needs no cleanup.
+/// Claims are separate `SET NX` keys, which makes them atomic across instances.
+#[derive(Clone)]
+pub struct RedisStateStore {
+    conn: MultiplexedConnection,
+}
+
+impl RedisStateStore {
+    pub fn new(conn: MultiplexedConnection) -> Self {
+        Self { conn }
+    }
+
+    fn claim_key(state_token: &str) -> String {
+        format!("{}{}", STATE_CLAIM_KEY_PREFIX, state_token)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RedisStateClaim {
+    code_hash: String,
+    exchange: Option<CompletedExchange>,
+}
+
+#[async_trait]
+impl StateStore for RedisStateStore {
+    async fn store_state(&self, state_token: &str, ttl_seconds: i64, record: &OAuthStateRecord) -> AppResult<()> {
+        // Already expired; Redis rejects non-positive TTLs
+        if ttl_seconds <= 0 {
+            return Ok(());
+        }
+
+        let value = serde_json::to_string(record).expect("state record serializes");
+        let mut conn = self.conn.clone();
+        let _: () = conn
+            .set_ex(format!("{}{}", STATE_KEY_PREFIX, state_token), value, ttl_seconds as usize)
+            .await?;
+        Ok(())
+    }
+
+    async fn claim_state(&self, state_token: &str, code_hash: &str) -> AppResult<StateClaim> {
+        let mut conn = self.conn.clone();
+        let key = format!("{}{}", STATE_KEY_PREFIX, state_token);
+        let (value, ttl): (Option<String>, i64) = redis::pipe().get(&key).ttl(&key).query_async(&mut conn).await?;
+        let Some(value) = value else {
+            return Ok(StateClaim::Invalid);
+        };
+        // States stored before records existed hold a bare `1`, which reads
+        // as a record for no shop
+        let record: OAuthStateRecord = serde_json::from_str(&value).unwrap_or_default();
+
+        let claim = RedisStateClaim { code_hash: code_hash.to_string(), exchange: None };
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(Self::claim_key(state_token))
+            .arg(serde_json::to_string(&claim).expect("state claim serializes"))
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl.max(1))
+            .query_async(&mut conn)
+            .await?;
+        if claimed.is_some() {
+            return Ok(StateClaim::Claimed(record));
+        }
+
+        let existing: Option<String> = conn.get(Self::claim_key(state_token)).await?;
+        Ok(match existing.and_then(|json| serde_json::from_str::<RedisStateClaim>(&json).ok()) {
+            Some(claim) => StateClaim::existing(record, Some(&claim.code_hash), claim.exchange, code_hash),
+            None => StateClaim::Invalid,
+        })
+    }
+
+    async fn complete_state(&self, state_token: &str, exchange: &CompletedExchange) -> AppResult<()> {
+        let mut conn = self.conn.clone();
+        let key = Self::claim_key(state_token);
+        let Some(json) = conn.get::<_, Option<String>>(&key).await? else {
+            return Ok(());
+        };
+        let Ok(mut claim) = serde_json::from_str::<RedisStateClaim>(&json) else {
+            return Ok(());
+        };
+        claim.exchange = Some(exchange.clone());
+        let _: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(serde_json::to_string(&claim).expect("state claim serializes"))
+            .arg("XX")
+            .arg("KEEPTTL")
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn release_state(&self, state_token: &str) -> AppResult<()> {
+        let mut conn = self.conn.clone();
+        let _: () = conn
+            .del(&[format!("{}{}", STATE_KEY_PREFIX, state_token), Self::claim_key(state_token)])
+            .await?;
+        Ok(())
+    }
+
+    async fn cleanup_expired_states(&self) -> AppResult<u64> {
+        Ok(0)
+    }
+}
+
+/// Read-through cache in front of another token store. Tokens are cached
+/// encrypted, entries expire after the TTL (or sooner, when the token itself
+/// expires first), and writes through this store drop the shop's entry. A
+/// Redis failure falls back to the backing store.
+pub struct CachedTokenStore {
+    inner: Arc<dyn TokenStore>,
+    conn: MultiplexedConnection,
+    encryption: TokenEncryption,
+    ttl_seconds: u64,
+}
+
+impl CachedTokenStore {
+    pub fn new(
+        inner: Arc<dyn TokenStore>,
+        conn: MultiplexedConnection,
+        encryption_key: &Secret<String>,
+        ttl_seconds: u64,
+    ) -> AppResult<Self> {
+        let encryption = TokenEncryption::new(encryption_key)?;
+        Ok(Self { inner, conn, encryption, ttl_seconds })
+    }
+
+    fn key(shop_domain: &str) -> String {
+        format!("{}{}", TOKEN_CACHE_KEY_PREFIX, shop_domain)
+    }
+
+    async fn read_cache(&self, shop_domain: &str) -> redis::RedisResult<Option<(String, String)>> {
+        let mut conn = self.conn.clone();
+        let (token, scope): (Option<String>, Option<String>) =
+            conn.hget(Self::key(shop_domain), &["token", "scope"]).await?;
+        Ok(token.zip(scope))
+    }
+
+    async fn write_cache(
+        &self,
+        shop_domain: &str,
+        encrypted_token: &str,
+        scope: &str,
+        ttl_seconds: u64,
+    ) -> redis::RedisResult<()> {
+        let key = Self::key(shop_domain);
+        redis::pipe()
+            .hset_multiple(&key, &[("token", encrypted_token), ("scope", scope)])
+            .ignore()
+            .expire(&key, ttl_seconds as usize)
+            .ignore()
+            .query_async(&mut self.conn.clone())
+            .await
+    }
+
+    async fn invalidate(&self, shop_domain: &str) {
+        let mut conn = self.conn.clone();
+        if let Err(e) = conn.del::<_, ()>(Self::key(shop_domain)).await {
+            warn!("Failed to invalidate cached token for {}: {}", shop_domain, e);
+        }
+    }
+
+    /// Token and scope for the shop, from the cache or the backing store.
+    async fn cached(&self, shop_domain: &str) -> AppResult<Option<(String, String)>> {
+        match self.read_cache(shop_domain).await {
+            Ok(Some((encrypted_token, scope))) => {
+                return Ok(Some((self.encryption.decrypt_for_shop(shop_domain, &encrypted_token)?, scope)));
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Token cache unavailable for {}: {}", shop_domain, e),
+        }
+
+        let Some(token) = self.inner.get_token(shop_domain).await? else {
+            return Ok(None);
+        };
+        let scope = self.inner.get_scope(shop_domain).await?.unwrap_or_default();
+
+        // Never serve an online token from the cache past its expiry
+        let ttl_seconds = match self.inner.get_expiry(shop_domain).await? {
+            Some(expires_at) => (expires_at - Utc::now()).num_seconds().clamp(0, self.ttl_seconds as i64) as u64,
+            None => self.ttl_seconds,
+        };
+        if ttl_seconds > 0 {
+            let encrypted_token = self.encryption.encrypt_for_shop(shop_domain, &token)?;
+            if let Err(e) = self.write_cache(shop_domain, &encrypted_token, &scope, ttl_seconds).await {
+                warn!("Failed to cache token for {}: {}", shop_domain, e);
+            }
+        }
+        Ok(Some((token, scope)))
+    }
+}
+
+#[async_trait]
+impl TokenStore for CachedTokenStore {
+    async fn store_token(
+        &self,
+        shop_domain: &str,
+        access_token: &str,
+        scope: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        self.inner.store_token(shop_domain, access_token, scope, expires_at).await?;
+        self.invalidate(shop_domain).await;
+        Ok(())
+    }
+
+    async fn get_token(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        Ok(self.cached(shop_domain).await?.map(|(token, _)| token))
+    }
+
+    async fn get_scope(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        Ok(self.cached(shop_domain).await?.map(|(_, scope)| scope))
+    }
+
+    async fn get_expiry(&self, shop_domain: &str) -> AppResult<Option<DateTime<Utc>>> {
+        self.inner.get_expiry(shop_domain).await
+    }
+
+    async fn delete_token(&self, shop_domain: &str) -> AppResult<bool> {
+        let deleted = self.inner.delete_token(shop_domain).await?;
+        self.invalidate(shop_domain).await;
+        Ok(deleted)
+    }
+
+    /// Cached entries never outlive their token, so only the backing store
+    /// needs purging.
+    async fn delete_expired_tokens(&self) -> AppResult<u64> {
+        self.inner.delete_expired_tokens().await
+    }
+
+    async fn list_shops(&self) -> AppResult<Vec<InstalledShop>> {
+        self.inner.list_shops().await
+    }
+
+    async fn rekey_legacy_tokens(&self) -> AppResult<u64> {
+        self.inner.rekey_legacy_tokens().await
+    }
+}
+
+// =============================================================================
+// In-Memory Backend
+// =============================================================================
+
+struct MemoryToken {
+    access_token: String,
+    scope: String,
+    expires_at: Option<DateTime<Utc>>,
+    installed_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl MemoryToken {
+    fn is_live(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryTokenStore {
+    tokens: RwLock<HashMap<String, MemoryToken>>,
+}
+
+impl MemoryTokenStore {
+    fn live<T>(&self, shop_domain: &str, field: impl FnOnce(&MemoryToken) -> T) -> Option<T> {
+        let tokens = self.tokens.read().unwrap();
+        tokens.get(shop_domain).filter(|token| token.is_live(Utc::now())).map(field)
+    }
+}
+
+#[async_trait]
+impl TokenStore for MemoryTokenStore {
+    async fn store_token(
+        &self,
+        shop_domain: &str,
+        access_token: &str,
+        scope: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        let now = Utc::now();
+        let mut tokens = self.tokens.write().unwrap();
+        let installed_at = tokens.get(shop_domain).map_or(now, |token| token.installed_at);
+        tokens.insert(shop_domain.to_string(), MemoryToken {
+            access_token: access_token.to_string(),
+            scope: scope.to_string(),
+            expires_at,
+            installed_at,
+            updated_at: now,
+        });
+        Ok(())
+    }
+
+    async fn get_token(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        Ok(self.live(shop_domain, |token| token.access_token.clone()))
+    }
+
+    async fn get_scope(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        Ok(self.live(shop_domain, |token| token.scope.clone()))
+    }
+
+    async fn get_expiry(&self, shop_domain: &str) -> AppResult<Option<DateTime<Utc>>> {
+        Ok(self.tokens.read().unwrap().get(shop_domain).and_then(|token| token.expires_at))
+    }
+
+    async fn delete_token(&self, shop_domain: &str) -> AppResult<bool> {
+        Ok(self.tokens.write().unwrap().remove(shop_domain).is_some())
+    }
+
+    async fn delete_expired_tokens(&self) -> AppResult<u64> {
+        let now = Utc::now();
+        let mut tokens = self.tokens.write().unwrap();
+        let before = tokens.len();
+        tokens.retain(|_, token| token.is_live(now));
+        Ok((before - tokens.len()) as u64)
+    }
+
+    async fn list_shops(&self) -> AppResult<Vec<InstalledShop>> {
+        let now = Utc::now();
+        let tokens = self.tokens.read().unwrap();
+        let mut shops: Vec<InstalledShop> = tokens
+            .iter()
+            .filter(|(_, token)| token.is_live(now))
+            .map(|(shop_domain, token)| InstalledShop {
+                shop_domain: shop_domain.clone(),
+                scope: token.scope.clone(),
+                installed_at: token.installed_at,
+                updated_at: token.updated_at,
+                expires_at: token.expires_at,
+            })
+            .collect();
+        shops.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(a.shop_domain.cmp(&b.shop_domain)));
+        Ok(shops)
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStateStore {
+    states: RwLock<HashMap<String, MemoryState>>,
+}
+
+struct MemoryState {
+    /// Unix seconds
+    expires_at: i64,
+    record: OAuthStateRecord,
+    code_hash: Option<String>,
+    exchange: Option<CompletedExchange>,
+}
+
+#[async_trait]
+impl StateStore for MemoryStateStore {
+    async fn store_state(&self, state_token: &str, ttl_seconds: i64, record: &OAuthStateRecord) -> AppResult<()> {
+        let state = MemoryState {
+            expires_at: Utc::now().timestamp() + ttl_seconds,
+            record: record.clone(),
+            code_hash: None,
+            exchange: None,
+        };
+        self.states.write().unwrap().insert(state_token.to_string(), state);
+        Ok(())
+    }
+
+    async fn claim_state(&self, state_token: &str, code_hash: &str) -> AppResult<StateClaim> {
+        let mut states = self.states.write().unwrap();
+        let Some(state) = states
+            .get_mut(state_token)
+            .filter(|state| state.expires_at > Utc::now().timestamp())
+        else {
+            return Ok(StateClaim::Invalid);
+        };
+        if state.code_hash.is_none() {
+            state.code_hash = Some(code_hash.to_string());
+            return Ok(StateClaim::Claimed(state.record.clone()));
+        }
+        Ok(StateClaim::existing(
+            state.record.clone(),
+            state.code_hash.as_deref(),
+            state.exchange.clone(),
+            code_hash,
+        ))
+    }
+
+    async fn complete_state(&self, state_token: &str, exchange: &CompletedExchange) -> AppResult<()> {
+        if let Some(state) = self.states.write().unwrap().get_mut(state_token) {
+            state.exchange = Some(exchange.clone());
+        }
+        Ok(())
+    }
+
+    async fn release_state(&self, state_token: &str) -> AppResult<()> {
+        self.states.write().unwrap().remove(state_token);
+        Ok(())
+    }
+
+    async fn cleanup_expired_states(&self) -> AppResult<u64> {
+        let now = Utc::now().timestamp();
+        let mut states = self.states.write().unwrap();
+        let before = states.len();
+        states.retain(|_, state| state.expires_at > now);
+        Ok((before - states.len()) as u64)
+    }
+}
+
+// =============================================================================
+// Scheduled Jobs
+// =============================================================================
+
+/// Drops OAuth states whose authorization was never completed.
+pub fn state_cleanup_job() -> Job {
+    Job::new("oauth-state-cleanup", Schedule::Every(Duration::from_secs(300)), JobScope::Cluster, |state: AppState| async move {
+        let removed = state.state_store.cleanup_expired_states().await.map_err(|e| e.to_string())?;
+        Ok(format!("{} expired states removed", removed))
+    })
+}
+
+/// Drops expired online access tokens, so their shops show as needing
+/// re-authorization.
+pub fn token_expiry_job() -> Job {
+    Job::new("token-expiry", Schedule::Every(Duration::from_secs(300)), JobScope::Cluster, |state: AppState| async move {
+        let removed = state.token_store.delete_expired_tokens().await.map_err(|e| e.to_string())?;
+        if removed > 0 {
+            info!("🧹 Removed {} expired access tokens", removed);
+        }
+        Ok(format!("{} expired tokens removed", removed))
+    })
+}
@@ -0,0 +1,239 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use secrecy::Secret;
+use sqlx::mysql::{MySqlConnectOptions, MySqlPool, MySqlPoolOptions};
+use std::str::FromStr;
+use tracing::info;
+
+use super::{
+    exchange_json, CompletedExchange, InstalledShop, OAuthStateRecord, OAuthStateRow, StateClaim, StateStore, TokenStore,
+    STATE_CLAIM_COLUMNS, STATE_RECORD_COLUMNS,
+};
+use crate::{database::TokenEncryption, error::AppResult};
+
+/// Connects to and migrates the MySQL/MariaDB token database.
+pub async fn connect_mysql(url: &str) -> AppResult<MySqlPool> {
+    info!("🔄 Connecting to MySQL token store");
+    let pool = MySqlPoolOptions::new()
+        .max_connections(10)
+        .connect_with(MySqlConnectOptions::from_str(url)?)
+        .await?;
+    sqlx::migrate!("./migrations/mysql").run(&pool).await?;
+
+    Ok(pool)
+}
+
+#[derive(Clone)]
+pub struct MySqlTokenStore {
+    pool: MySqlPool,
+    encryption: TokenEncryption,
+}
+
+impl MySqlTokenStore {
+    pub fn new(pool: MySqlPool, encryption_key: &Secret<String>) -> AppResult<Self> {
+        let encryption = TokenEncryption::new(encryption_key)?;
+        Ok(Self { pool, encryption })
+    }
+}
+
+#[async_trait]
+impl TokenStore for MySqlTokenStore {
+    async fn store_token(
+        &self,
+        shop_domain: &str,
+        access_token: &str,
+        scope: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        let encrypted_token = self.encryption.encrypt_for_shop(shop_domain, access_token)?;
+
+        // VALUES() rather than a row alias so MariaDB accepts it too
+        sqlx::query(
+            r#"
+            INSERT INTO shopify_tokens (shop_domain, encrypted_access_token, scope, expires_at)
+            VALUES (?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                encrypted_access_token = VALUES(encrypted_access_token),
+                scope = VALUES(scope),
+                expires_at = VALUES(expires_at),
+                updated_at = CURRENT_TIMESTAMP(6)
+            "#,
+        )
+        .bind(shop_domain)
+        .bind(encrypted_token)
+        .bind(scope)
+        .bind(expires_at.map(|expires_at| expires_at.timestamp()))
+        .execute(&self.pool)
+        .await?;
+
+        info!("✅ Token stored for shop: {}", shop_domain);
+        Ok(())
+    }
+
+    async fn get_token(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        let row = sqlx::query_as::<_, (String,)>(
+            "SELECT encrypted_access_token FROM shopify_tokens
+             WHERE shop_domain = ? AND (expires_at IS NULL OR expires_at > ?)"
+        )
+        .bind(shop_domain)
+        .bind(Utc::now().timestamp())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|(encrypted_token,)| self.encryption.decrypt_for_shop(shop_domain, &encrypted_token))
+            .transpose()
+    }
+
+    async fn get_scope(&self, shop_domain: &str) -> AppResult<Option<String>> {
+        let row = sqlx::query_as::<_, (String,)>(
+            "SELECT scope FROM shopify_tokens
+             WHERE shop_domain = ? AND (expires_at IS NULL OR expires_at > ?)"
+        )
+        .bind(shop_domain)
+        .bind(Utc::now().timestamp())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(scope,)| scope))
+    }
+
+    async fn get_expiry(&self, shop_domain: &str) -> AppResult<Option<DateTime<Utc>>> {
+        let row = sqlx::query_as::<_, (Option<i64>,)>(
+            "SELECT expires_at FROM shopify_tokens WHERE shop_domain = ?"
+        )
+        .bind(shop_domain)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.and_then(|(expires_at,)| expires_at).and_then(|expires_at| DateTime::from_timestamp(expires_at, 0)))
+    }
+
+    async fn delete_token(&self, shop_domain: &str) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM shopify_tokens WHERE shop_domain = ?")
+            .bind(shop_domain)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_expired_tokens(&self) -> AppResult<u64> {
+        let result = sqlx::query("DELETE FROM shopify_tokens WHERE expires_at <= ?")
+            .bind(Utc::now().timestamp())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn list_shops(&self) -> AppResult<Vec<InstalledShop>> {
+        let rows = sqlx::query_as::<_, (String, String, DateTime<Utc>, DateTime<Utc>, Option<i64>)>(
+            "SELECT shop_domain, scope, created_at, updated_at, expires_at FROM shopify_tokens
+             WHERE expires_at IS NULL OR expires_at > ?
+             ORDER BY updated_at DESC, shop_domain"
+        )
+        .bind(Utc::now().timestamp())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(shop_domain, scope, installed_at, updated_at, expires_at)| InstalledShop {
+                shop_domain,
+                scope,
+                installed_at,
+                updated_at,
+                expires_at: expires_at.and_then(|expires_at| DateTime::from_timestamp(expires_at, 0)),
+            })
+            .collect())
+    }
+}
+
+#[derive(Clone)]
+pub struct MySqlStateStore {
+    pool: MySqlPool,
+}
+
+impl MySqlStateStore {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl StateStore for MySqlStateStore {
+    async fn store_state(&self, state_token: &str, ttl_seconds: i64, record: &OAuthStateRecord) -> AppResult<()> {
+        sqlx::query(&format!(
+            "INSERT INTO oauth_states (state_token, expires_at, {}) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            STATE_RECORD_COLUMNS
+        ))
+        .bind(state_token)
+        .bind(Utc::now().timestamp() + ttl_seconds)
+        .bind(&record.shop)
+        .bind(&record.scopes)
+        .bind(&record.return_to)
+        .bind(&record.client_ip)
+        .bind(&record.user_agent)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn claim_state(&self, state_token: &str, code_hash: &str) -> AppResult<StateClaim> {
+        // MySQL has no UPDATE ... RETURNING; the row lock keeps two callbacks
+        // from both claiming the state
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query_as::<_, OAuthStateRow>(&format!(
+            "SELECT {}, {} FROM oauth_states WHERE state_token = ? AND expires_at > ? FOR UPDATE",
+            STATE_RECORD_COLUMNS, STATE_CLAIM_COLUMNS
+        ))
+        .bind(state_token)
+        .bind(Utc::now().timestamp())
+        .fetch_optional(&mut *tx)
+        .await?;
+        let claim = match row {
+            None => StateClaim::Invalid,
+            Some(row) if row.is_unclaimed() => {
+                sqlx::query("UPDATE oauth_states SET code_hash = ? WHERE state_token = ?")
+                    .bind(code_hash)
+                    .bind(state_token)
+                    .execute(&mut *tx)
+                    .await?;
+                StateClaim::Claimed(row.into())
+            }
+            Some(row) => row.into_claim(code_hash),
+        };
+        tx.commit().await?;
+
+        Ok(claim)
+    }
+
+    async fn complete_state(&self, state_token: &str, exchange: &CompletedExchange) -> AppResult<()> {
+        sqlx::query("UPDATE oauth_states SET exchange_result = ? WHERE state_token = ?")
+            .bind(exchange_json(exchange))
+            .bind(state_token)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn release_state(&self, state_token: &str) -> AppResult<()> {
+        sqlx::query("DELETE FROM oauth_states WHERE state_token = ?")
+            .bind(state_token)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn cleanup_expired_states(&self) -> AppResult<u64> {
+        let result = sqlx::query("DELETE FROM oauth_states WHERE expires_at <= ?")
+            .bind(Utc::now().timestamp())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
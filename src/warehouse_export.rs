@@ -0,0 +1,903 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use parquet::{
+    data_type::{ByteArray, ByteArrayType, DataType, Int64Type},
+    file::{
+        properties::WriterProperties,
+        writer::{SerializedFileWriter, SerializedRowGroupWriter},
+    },
+    schema::parser::parse_message_type,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    AppState,
+    database::{ExportRow, QueuedJob, WarehouseExportStore},
+    error::{AppError, AppResult},
+    job_queue::{JobHandlerResult, JobKind, ShopOrdering},
+    key_provider::{gcp_access_token, sigv4_request_authorization, SignedRequest},
+    scheduler::{Job, JobResult, JobScope, Schedule},
+};
+
+const WRITE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long after being synced rows wait to be exported. `synced_at` is
+/// stamped when a sync's transaction starts, so rows from one still running
+/// can commit with a time before the watermark.
+const SETTLE_TIME: chrono::Duration = chrono::Duration::minutes(5);
+
+// =============================================================================
+// Export Batches
+// =============================================================================
+
+/// A local copy that can be exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportTable {
+    Orders,
+    Customers,
+    Products,
+}
+
+impl ExportTable {
+    pub const ALL: [Self; 3] = [Self::Orders, Self::Customers, Self::Products];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "orders" => Some(Self::Orders),
+            "customers" => Some(Self::Customers),
+            "products" => Some(Self::Products),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Orders => "orders",
+            Self::Customers => "customers",
+            Self::Products => "products",
+        }
+    }
+}
+
+/// Rows of one shop's table, in `(synced_at, id)` order, to write to the
+/// warehouse. A row appears again in a later batch whenever it changes.
+#[derive(Debug, Clone)]
+pub struct ExportBatch {
+    pub shop: String,
+    pub table: ExportTable,
+    pub rows: Vec<ExportRow>,
+}
+
+impl ExportBatch {
+    /// The row as written to NDJSON files and BigQuery, `data` aside.
+    fn record(&self, row: &ExportRow) -> Value {
+        json!({
+            "shop_domain": self.shop,
+            "id": row.id,
+            "updated_at": row.updated_at,
+            "synced_at": row.synced_at,
+            "data": row.data
+        })
+    }
+
+    /// Gzipped JSON lines, one row per line.
+    pub fn ndjson_gz(&self) -> Result<Vec<u8>, String> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        for row in &self.rows {
+            serde_json::to_writer(&mut encoder, &self.record(row)).map_err(|e| e.to_string())?;
+            encoder.write_all(b"\n").map_err(|e| e.to_string())?;
+        }
+        encoder.finish().map_err(|e| e.to_string())
+    }
+
+    /// A Parquet file with one row group, `data` as a JSON string column.
+    pub fn parquet(&self) -> Result<Vec<u8>, String> {
+        let schema = parse_message_type(
+            "message export {
+                REQUIRED BYTE_ARRAY shop_domain (UTF8);
+                REQUIRED INT64 id;
+                OPTIONAL INT64 updated_at (TIMESTAMP(MICROS,true));
+                REQUIRED INT64 synced_at (TIMESTAMP(MICROS,true));
+                REQUIRED BYTE_ARRAY data (JSON);
+            }",
+        )
+        .map_err(|e| e.to_string())?;
+        let properties = WriterProperties::builder()
+            .set_compression(parquet::basic::Compression::SNAPPY)
+            .build();
+
+        let shops = vec![ByteArray::from(self.shop.as_str()); self.rows.len()];
+        let ids: Vec<i64> = self.rows.iter().map(|row| row.id).collect();
+        let updated_at: Vec<i64> = self.rows.iter().filter_map(|row| row.updated_at).map(|at| at.timestamp_micros()).collect();
+        let updated_at_levels: Vec<i16> = self.rows.iter().map(|row| i16::from(row.updated_at.is_some())).collect();
+        let synced_at: Vec<i64> = self.rows.iter().map(|row| row.synced_at.timestamp_micros()).collect();
+        let data: Vec<ByteArray> = self.rows.iter().map(|row| ByteArray::from(row.data.to_string().into_bytes())).collect();
+
+        let mut buffer = Vec::new();
+        let write = |buffer: &mut Vec<u8>| -> parquet::errors::Result<()> {
+            let mut writer = SerializedFileWriter::new(buffer, Arc::new(schema), Arc::new(properties))?;
+            let mut row_group = writer.next_row_group()?;
+            write_column::<ByteArrayType>(&mut row_group, &shops, None)?;
+            write_column::<Int64Type>(&mut row_group, &ids, None)?;
+            write_column::<Int64Type>(&mut row_group, &updated_at, Some(&updated_at_levels))?;
+            write_column::<Int64Type>(&mut row_group, &synced_at, None)?;
+            write_column::<ByteArrayType>(&mut row_group, &data, None)?;
+            row_group.close()?;
+            writer.close()?;
+            Ok(())
+        };
+        write(&mut buffer).map_err(|e| e.to_string())?;
+        Ok(buffer)
+    }
+}
+
+/// Writes the row group's next column; `definition_levels` are for optional
+/// columns, 0 where the value is null.
+fn write_column<T: DataType>(
+    row_group: &mut SerializedRowGroupWriter<'_, &mut Vec<u8>>,
+    values: &[T::T],
+    definition_levels: Option<&[i16]>,
+) -> parquet::errors::Result<()> {
+    let mut column = row_group
+        .next_column()?
+        .ok_or_else(|| parquet::errors::ParquetError::General("schema has fewer columns than written".to_string()))?;
+    column.typed::<T>().write_batch(values, definition_levels, None)?;
+    column.close()
+}
+
+// =============================================================================
+// Destinations
+// =============================================================================
+
+/// Somewhere export batches are written.
+#[async_trait]
+pub trait ExportDestination: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Writes `batch`, returning where it went for the logs.
+    async fn write(&self, batch: &ExportBatch) -> Result<String, String>;
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn required(name: &str) -> AppResult<String> {
+    env(name).ok_or_else(|| AppError::Config(format!("{} is required by WAREHOUSE_EXPORT", name)))
+}
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder().timeout(WRITE_TIMEOUT).build().unwrap_or_default()
+}
+
+/// Body of a non-2xx response, for the error.
+async fn failure(service: &str, response: reqwest::Response) -> String {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    format!("{} answered {}: {}", service, status, body)
+}
+
+// =============================================================================
+// Amazon S3
+// =============================================================================
+
+/// Files written to S3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Parquet,
+    /// Gzipped JSON lines
+    Ndjson,
+}
+
+impl FileFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Parquet => "parquet",
+            Self::Ndjson => "ndjson.gz",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Parquet => "application/vnd.apache.parquet",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+/// The standard `AWS_REGION` / `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY`
+/// / `AWS_SESSION_TOKEN`.
+pub struct AwsCredentials {
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: Secret<String>,
+    pub session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    fn from_env() -> AppResult<Self> {
+        Ok(Self {
+            region: env("AWS_REGION")
+                .or_else(|| env("AWS_DEFAULT_REGION"))
+                .ok_or_else(|| AppError::Config("AWS_REGION is required by WAREHOUSE_EXPORT".to_string()))?,
+            access_key_id: required("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: Secret::new(required("AWS_SECRET_ACCESS_KEY")?),
+            session_token: env("AWS_SESSION_TOKEN"),
+        })
+    }
+}
+
+/// One object per batch, partitioned Hive-style by table, shop and the day
+/// the batch's first row was synced, e.g.
+/// `orders/shop_domain=example.myshopify.com/dt=2026-10-15/20261015T101500123456Z-450789469.parquet`.
+/// A batch retried after a failure starts at the same row, so it overwrites
+/// its earlier object rather than adding a copy.
+pub struct S3Destination {
+    pub bucket: String,
+    /// Prepended to every key, e.g. `shopify/`
+    pub prefix: String,
+    pub format: FileFormat,
+    /// Path-style base URL of an S3-compatible store; `None` for AWS itself
+    pub endpoint: Option<String>,
+    pub credentials: AwsCredentials,
+    http: reqwest::Client,
+}
+
+impl S3Destination {
+    pub fn new(bucket: String, prefix: String, format: FileFormat, endpoint: Option<String>, credentials: AwsCredentials) -> Self {
+        Self { bucket, prefix, format, endpoint, credentials, http: http_client() }
+    }
+
+    fn from_env() -> AppResult<Self> {
+        let format = match env("WAREHOUSE_S3_FORMAT").map(|v| v.to_lowercase()).as_deref() {
+            None | Some("parquet") => FileFormat::Parquet,
+            Some("ndjson") => FileFormat::Ndjson,
+            Some(other) => {
+                return Err(AppError::Config(format!(
+                    "Unknown WAREHOUSE_S3_FORMAT: {} (expected parquet or ndjson)",
+                    other
+                )))
+            }
+        };
+        Ok(Self::new(
+            required("WAREHOUSE_S3_BUCKET")?,
+            env("WAREHOUSE_S3_PREFIX").unwrap_or_default(),
+            format,
+            env("AWS_S3_ENDPOINT").map(|url| url.trim_end_matches('/').to_string()),
+            AwsCredentials::from_env()?,
+        ))
+    }
+
+    /// Where `batch` is written, or `None` for an empty batch.
+    pub fn key(&self, batch: &ExportBatch) -> Option<String> {
+        let first = batch.rows.first()?;
+        Some(format!(
+            "{}{}/shop_domain={}/dt={}/{}-{}.{}",
+            self.prefix,
+            batch.table.as_str(),
+            batch.shop,
+            first.synced_at.format("%Y-%m-%d"),
+            first.synced_at.format("%Y%m%dT%H%M%S%6fZ"),
+            first.id,
+            self.format.extension()
+        ))
+    }
+
+    /// The object's URL, with the path as signed.
+    fn url(&self, key: &str) -> (String, String) {
+        let key = key.split('/').map(uri_encode).collect::<Vec<_>>().join("/");
+        match &self.endpoint {
+            Some(endpoint) => {
+                let path = format!("/{}/{}", uri_encode(&self.bucket), key);
+                (format!("{}{}", endpoint, path), path)
+            }
+            None => {
+                let path = format!("/{}", key);
+                let host = format!("https://{}.s3.{}.amazonaws.com", self.bucket, self.credentials.region);
+                (format!("{}{}", host, path), path)
+            }
+        }
+    }
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters, as SigV4
+/// expects of each path segment.
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[async_trait]
+impl ExportDestination for S3Destination {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    async fn write(&self, batch: &ExportBatch) -> Result<String, String> {
+        let key = self.key(batch).ok_or("Nothing to write")?;
+        let body = match self.format {
+            FileFormat::Parquet => batch.parquet()?,
+            FileFormat::Ndjson => batch.ndjson_gz()?,
+        };
+        let (url, path) = self.url(&key);
+        let parsed = url::Url::parse(&url).map_err(|e| e.to_string())?;
+        let host = match (parsed.host_str(), parsed.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err("endpoint has no host".to_string()),
+        };
+
+        let now = Utc::now();
+        let mut headers = vec![
+            ("content-type", self.format.content_type().to_string()),
+            ("host", host),
+            ("x-amz-content-sha256", hex::encode(Sha256::digest(&body))),
+            ("x-amz-date", now.format("%Y%m%dT%H%M%SZ").to_string()),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        headers.sort_by_key(|(name, _)| *name);
+        let request = SignedRequest { method: "PUT", path: &path, headers: &headers, body: &body };
+        let authorization = sigv4_request_authorization(
+            &self.credentials.access_key_id,
+            &self.credentials.secret_access_key,
+            &self.credentials.region,
+            "s3",
+            now,
+            &request,
+        );
+
+        let mut request = self.http.put(parsed).header("authorization", authorization).body(body);
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(failure("S3", response).await);
+        }
+        Ok(format!("s3://{}/{}", self.bucket, key))
+    }
+}
+
+// =============================================================================
+// BigQuery
+// =============================================================================
+
+/// BigQuery streaming inserts (`tabledata.insertAll`) into
+/// `<dataset>.<prefix><table>`, with columns `shop_domain STRING`,
+/// `id INT64`, `updated_at TIMESTAMP`, `synced_at TIMESTAMP` and `data`
+/// (`JSON` or `STRING`). Each row's insert ID is its shop, table, ID and
+/// `synced_at`, so BigQuery drops most rows sent twice.
+pub struct BigQueryDestination {
+    pub project: String,
+    pub dataset: String,
+    pub table_prefix: String,
+    pub endpoint: String,
+    /// `GCP_ACCESS_TOKEN`; `None` asks the metadata server
+    pub access_token: Option<Secret<String>>,
+    http: reqwest::Client,
+}
+
+impl BigQueryDestination {
+    pub fn new(
+        project: String,
+        dataset: String,
+        table_prefix: String,
+        endpoint: String,
+        access_token: Option<Secret<String>>,
+    ) -> Self {
+        Self { project, dataset, table_prefix, endpoint, access_token, http: http_client() }
+    }
+
+    fn from_env(table_prefix: String) -> AppResult<Self> {
+        Ok(Self::new(
+            required("BIGQUERY_PROJECT")?,
+            required("BIGQUERY_DATASET")?,
+            table_prefix,
+            env("BIGQUERY_ENDPOINT").unwrap_or_else(|| "https://bigquery.googleapis.com".to_string()),
+            env("GCP_ACCESS_TOKEN").map(Secret::new),
+        ))
+    }
+
+    /// The `insertAll` request body.
+    pub fn payload(batch: &ExportBatch) -> Value {
+        let rows: Vec<Value> = batch
+            .rows
+            .iter()
+            .map(|row| {
+                let mut record = batch.record(row);
+                record["data"] = Value::String(row.data.to_string());
+                json!({
+                    "insertId": format!("{}:{}:{}:{}", batch.shop, batch.table.as_str(), row.id, row.synced_at.timestamp_micros()),
+                    "json": record
+                })
+            })
+            .collect();
+        json!({ "rows": rows })
+    }
+}
+
+#[async_trait]
+impl ExportDestination for BigQueryDestination {
+    fn name(&self) -> &'static str {
+        "bigquery"
+    }
+
+    async fn write(&self, batch: &ExportBatch) -> Result<String, String> {
+        let table = format!("{}{}", self.table_prefix, batch.table.as_str());
+        let url = format!(
+            "{}/bigquery/v2/projects/{}/datasets/{}/tables/{}/insertAll",
+            self.endpoint.trim_end_matches('/'),
+            self.project,
+            self.dataset,
+            table
+        );
+        let token = gcp_access_token(&self.http, self.access_token.as_ref()).await?;
+        let response = self
+            .http
+            .post(url)
+            .bearer_auth(token)
+            .json(&Self::payload(batch))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(failure("BigQuery", response).await);
+        }
+
+        // Rejected rows come back in a 200
+        let body: Value = response.json().await.map_err(|e| e.to_string())?;
+        if let Some(errors) = body["insertErrors"].as_array().filter(|errors| !errors.is_empty()) {
+            return Err(format!(
+                "BigQuery rejected {} of {} rows, first: {}",
+                errors.len(),
+                batch.rows.len(),
+                errors[0]["errors"]
+            ));
+        }
+        Ok(format!("{}.{}.{}", self.project, self.dataset, table))
+    }
+}
+
+// =============================================================================
+// Snowflake
+// =============================================================================
+
+/// Where Snowflake statements run, as the SQL API takes it.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnowflakeContext {
+    pub database: String,
+    pub schema: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warehouse: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+}
+
+/// Snowflake's SQL API, inserting each batch into `<prefix><table>` with
+/// columns `SHOP_DOMAIN VARCHAR`, `ID NUMBER`, `UPDATED_AT TIMESTAMP_TZ`,
+/// `SYNCED_AT TIMESTAMP_TZ` and `DATA VARCHAR`; `PARSE_JSON(DATA)` reads
+/// it. Authenticates with an OAuth token or a key-pair JWT.
+pub struct SnowflakeDestination {
+    pub endpoint: String,
+    pub token: Secret<String>,
+    /// `OAUTH` or `KEYPAIR_JWT`
+    pub token_type: String,
+    pub table_prefix: String,
+    pub context: SnowflakeContext,
+    http: reqwest::Client,
+}
+
+impl SnowflakeDestination {
+    pub fn new(endpoint: String, token: Secret<String>, token_type: String, table_prefix: String, context: SnowflakeContext) -> Self {
+        Self { endpoint, token, token_type, table_prefix, context, http: http_client() }
+    }
+
+    fn from_env(table_prefix: String) -> AppResult<Self> {
+        let endpoint = match env("SNOWFLAKE_ENDPOINT") {
+            Some(endpoint) => endpoint,
+            None => format!("https://{}.snowflakecomputing.com", required("SNOWFLAKE_ACCOUNT")?),
+        };
+        let token_type = env("SNOWFLAKE_TOKEN_TYPE").unwrap_or_else(|| "OAUTH".to_string()).to_uppercase();
+        if token_type != "OAUTH" && token_type != "KEYPAIR_JWT" {
+            return Err(AppError::Config(format!(
+                "Unknown SNOWFLAKE_TOKEN_TYPE: {} (expected OAUTH or KEYPAIR_JWT)",
+                token_type
+            )));
+        }
+        Ok(Self::new(
+            endpoint,
+            Secret::new(required("SNOWFLAKE_TOKEN")?),
+            token_type,
+            table_prefix,
+            SnowflakeContext {
+                database: required("SNOWFLAKE_DATABASE")?,
+                schema: required("SNOWFLAKE_SCHEMA")?,
+                warehouse: env("SNOWFLAKE_WAREHOUSE"),
+                role: env("SNOWFLAKE_ROLE"),
+            },
+        ))
+    }
+
+    /// The statement request: one `INSERT` with a column of values bound
+    /// to each parameter.
+    pub fn statement(&self, batch: &ExportBatch) -> Value {
+        let column = |kind: &str, values: Vec<Value>| json!({ "type": kind, "value": values });
+        let mut body = json!({
+            "statement": format!(
+                "INSERT INTO {}{} (SHOP_DOMAIN, ID, UPDATED_AT, SYNCED_AT, DATA) VALUES (?, ?, ?, ?, ?)",
+                self.table_prefix,
+                batch.table.as_str()
+            ),
+            "timeout": WRITE_TIMEOUT.as_secs(),
+            "bindings": {
+                "1": column("TEXT", batch.rows.iter().map(|_| json!(batch.shop)).collect()),
+                "2": column("FIXED", batch.rows.iter().map(|row| json!(row.id.to_string())).collect()),
+                "3": column("TEXT", batch.rows.iter().map(|row| json!(row.updated_at.map(|at| at.to_rfc3339()))).collect()),
+                "4": column("TEXT", batch.rows.iter().map(|row| json!(row.synced_at.to_rfc3339())).collect()),
+                "5": column("TEXT", batch.rows.iter().map(|row| json!(row.data.to_string())).collect())
+            }
+        });
+        if let (Some(body), Ok(Value::Object(context))) = (body.as_object_mut(), serde_json::to_value(&self.context)) {
+            body.extend(context);
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl ExportDestination for SnowflakeDestination {
+    fn name(&self) -> &'static str {
+        "snowflake"
+    }
+
+    async fn write(&self, batch: &ExportBatch) -> Result<String, String> {
+        let response = self
+            .http
+            .post(format!("{}/api/v2/statements", self.endpoint.trim_end_matches('/')))
+            .bearer_auth(self.token.expose_secret())
+            .header("X-Snowflake-Authorization-Token-Type", &self.token_type)
+            .header("Accept", "application/json")
+            .json(&self.statement(batch))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        // 202 means the insert is still running and may yet fail, so it's
+        // sent again next time rather than counted as written
+        if response.status() != reqwest::StatusCode::OK {
+            return Err(failure("Snowflake", response).await);
+        }
+        let body: Value = response.json().await.map_err(|e| e.to_string())?;
+        Ok(format!(
+            "{}.{}.{}{} (statement {})",
+            self.context.database,
+            self.context.schema,
+            self.table_prefix,
+            batch.table.as_str(),
+            body["statementHandle"].as_str().unwrap_or("unknown")
+        ))
+    }
+}
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+#[derive(Clone)]
+pub struct WarehouseExportConfig {
+    /// Where batches are written; `None` exports nothing
+    pub destination: Option<Arc<dyn ExportDestination>>,
+    /// Time between exports of every installed shop
+    pub interval: Duration,
+    pub tables: Vec<ExportTable>,
+    /// Most rows written at once
+    pub batch_size: i64,
+}
+
+impl Default for WarehouseExportConfig {
+    fn default() -> Self {
+        Self {
+            destination: None,
+            interval: Duration::from_secs(3600),
+            tables: ExportTable::ALL.to_vec(),
+            batch_size: 500,
+        }
+    }
+}
+
+impl WarehouseExportConfig {
+    /// `WAREHOUSE_EXPORT` (`s3`, `bigquery` or `snowflake`), with
+    /// `WAREHOUSE_EXPORT_INTERVAL_SECS`, `WAREHOUSE_EXPORT_TABLES`,
+    /// `WAREHOUSE_EXPORT_BATCH_SIZE` and the destination's own settings.
+    pub fn from_env() -> AppResult<Self> {
+        let defaults = Self::default();
+        let Some(kind) = env("WAREHOUSE_EXPORT") else {
+            return Ok(defaults);
+        };
+        let table_prefix = env("WAREHOUSE_TABLE_PREFIX").unwrap_or_else(|| "shopify_".to_string());
+        let destination: Arc<dyn ExportDestination> = match kind.to_lowercase().as_str() {
+            "s3" => Arc::new(S3Destination::from_env()?),
+            "bigquery" => Arc::new(BigQueryDestination::from_env(table_prefix)?),
+            "snowflake" => Arc::new(SnowflakeDestination::from_env(table_prefix)?),
+            other => {
+                return Err(AppError::Config(format!(
+                    "Unknown WAREHOUSE_EXPORT: {} (expected s3, bigquery or snowflake)",
+                    other
+                )))
+            }
+        };
+
+        let tables = match env("WAREHOUSE_EXPORT_TABLES") {
+            None => defaults.tables,
+            Some(list) => list
+                .split(',')
+                .filter(|name| !name.trim().is_empty())
+                .map(|name| {
+                    ExportTable::parse(name).ok_or_else(|| {
+                        AppError::Config(format!(
+                            "Unknown table in WAREHOUSE_EXPORT_TABLES: {} (expected orders, customers or products)",
+                            name.trim()
+                        ))
+                    })
+                })
+                .collect::<AppResult<Vec<_>>>()?,
+        };
+        let interval = match env("WAREHOUSE_EXPORT_INTERVAL_SECS") {
+            None => defaults.interval,
+            Some(secs) => secs
+                .parse::<u64>()
+                .ok()
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs)
+                .ok_or_else(|| AppError::Config("WAREHOUSE_EXPORT_INTERVAL_SECS must be a number of seconds above 0".to_string()))?,
+        };
+        let batch_size = match env("WAREHOUSE_EXPORT_BATCH_SIZE") {
+            None => defaults.batch_size,
+            Some(size) => size
+                .parse::<i64>()
+                .ok()
+                .filter(|size| (1..=10_000).contains(size))
+                .ok_or_else(|| AppError::Config("WAREHOUSE_EXPORT_BATCH_SIZE must be between 1 and 10000".to_string()))?,
+        };
+
+        Ok(Self { destination: Some(destination), interval, tables, batch_size })
+    }
+}
+
+// =============================================================================
+// Exporting
+// =============================================================================
+
+/// Rows one export wrote for a table.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TableExport {
+    pub table: ExportTable,
+    pub rows: i64,
+    pub batches: usize,
+}
+
+/// Writes every configured table's rows synced since its watermark, a batch
+/// at a time, moving the watermark past each batch once it's written.
+/// Delivery is at least once: a batch whose watermark wasn't saved is sent
+/// again. A failing table is recorded and doesn't stop the others.
+pub async fn export_shop(
+    store: &WarehouseExportStore,
+    destination: &dyn ExportDestination,
+    config: &WarehouseExportConfig,
+    shop: &str,
+) -> Result<Vec<TableExport>, String> {
+    let settled_before = Utc::now() - SETTLE_TIME;
+    let (mut exported, mut failed) = (Vec::new(), Vec::new());
+    for table in &config.tables {
+        match export_table(store, destination, config.batch_size, shop, *table, settled_before).await {
+            Ok(export) => exported.push(export),
+            Err(e) => {
+                error!("Failed to export {} for {} to {}: {}", table.as_str(), shop, destination.name(), e);
+                if let Err(e) = store.record_error(shop, *table, &e).await {
+                    error!("Failed to record the export error: {}", e);
+                }
+                failed.push(format!("{}: {}", table.as_str(), e));
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(exported)
+    } else {
+        Err(failed.join("; "))
+    }
+}
+
+async fn export_table(
+    store: &WarehouseExportStore,
+    destination: &dyn ExportDestination,
+    batch_size: i64,
+    shop: &str,
+    table: ExportTable,
+    settled_before: DateTime<Utc>,
+) -> Result<TableExport, String> {
+    let state = store.state(shop, table).await.map_err(|e| e.to_string())?;
+    let mut after = state.and_then(|s| s.synced_until.zip(s.last_id));
+    let mut export = TableExport { table, rows: 0, batches: 0 };
+    loop {
+        let rows = store
+            .rows_after(shop, table, after, settled_before, batch_size)
+            .await
+            .map_err(|e| e.to_string())?;
+        let Some(last) = rows.last().map(|row| (row.synced_at, row.id)) else {
+            break;
+        };
+        let count = rows.len() as i64;
+        let batch = ExportBatch { shop: shop.to_string(), table, rows };
+        let location = destination.write(&batch).await?;
+        store.advance(shop, table, last.0, last.1, count).await.map_err(|e| e.to_string())?;
+        info!("📦 Exported {} {} for {} to {}", count, table.as_str(), shop, location);
+
+        export.rows += count;
+        export.batches += 1;
+        after = Some(last);
+        if count < batch_size {
+            break;
+        }
+    }
+    Ok(export)
+}
+
+/// Queue job kind exporting one shop's tables. `{"full": true}` forgets the
+/// watermarks first, exporting everything again.
+pub const SHOP_WAREHOUSE_EXPORT_JOB: &str = "shop-warehouse-export";
+
+pub fn shop_warehouse_export_job_kind() -> JobKind {
+    JobKind::new(SHOP_WAREHOUSE_EXPORT_JOB, ShopOrdering::Single, |state: AppState, job: QueuedJob| async move {
+        run_shop_warehouse_export(&state, job.shop_domain.as_deref(), job.payload["full"].as_bool().unwrap_or(false)).await
+    })
+}
+
+async fn run_shop_warehouse_export(state: &AppState, shop: Option<&str>, full: bool) -> JobHandlerResult {
+    let shop = shop.ok_or("Warehouse export job has no shop")?;
+    let config = &state.config.warehouse_export;
+    let destination = config.destination.as_ref().ok_or("Warehouse export is not configured")?;
+    if full {
+        state.warehouse_exports.reset(shop).await.map_err(|e| e.to_string())?;
+    }
+    export_shop(&state.warehouse_exports, destination.as_ref(), config, shop).await.map(|_| ())
+}
+
+/// Queues an export of every installed shop each
+/// `WAREHOUSE_EXPORT_INTERVAL_SECS`.
+pub fn warehouse_export_job(config: &WarehouseExportConfig) -> Job {
+    Job::new("warehouse-export", Schedule::Every(config.interval), JobScope::Cluster, |state: AppState| async move {
+        queue_installed_shops(&state).await
+    })
+}
+
+async fn queue_installed_shops(state: &AppState) -> JobResult {
+    let shops = state.token_store.list_shops().await.map_err(|e| e.to_string())?;
+
+    let (mut queued, mut pending, mut failed) = (0, 0, Vec::new());
+    for shop in shops {
+        match state.job_queue.enqueue(SHOP_WAREHOUSE_EXPORT_JOB, Some(&shop.shop_domain), json!({})).await {
+            Ok(Some(_)) => queued += 1,
+            Ok(None) => pending += 1,
+            Err(e) => {
+                error!("Failed to queue warehouse export for {}: {}", shop.shop_domain, e);
+                failed.push(shop.shop_domain);
+            }
+        }
+    }
+
+    let summary = format!("{} shops queued, {} already pending", queued, pending);
+    if failed.is_empty() {
+        Ok(summary)
+    } else {
+        Err(format!("{}, failed for {}", summary, failed.join(", ")))
+    }
+}
+
+// =============================================================================
+// Admin Handlers
+// =============================================================================
+
+fn require_export(state: &AppState) -> AppResult<()> {
+    if state.config.warehouse_export.destination.is_none() || state.config.database.database_url.is_none() {
+        return Err(AppError::BadRequest(
+            "Warehouse export needs a Postgres DATABASE_URL and WAREHOUSE_EXPORT".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// `GET /admin/shops/{shop}/warehouse-export` — each table's watermark and
+/// last error.
+#[utoipa::path(
+    get,
+    path = "/admin/shops/{shop}/warehouse-export",
+    tag = "sync",
+    params(("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com")),
+    responses(
+        (status = 200, description = "Export watermarks", body = crate::openapi::WarehouseExportStatus),
+    ),
+)]
+pub async fn warehouse_export_status_handler(
+    Path(shop): Path<String>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let config = &state.config.warehouse_export;
+    let tables = if state.config.database.database_url.is_some() {
+        state.warehouse_exports.states(&shop).await?
+    } else {
+        Vec::new()
+    };
+
+    Ok((StatusCode::OK, Json(json!({
+        "shop": shop,
+        "destination": config.destination.as_ref().map(|destination| destination.name()),
+        "exported_tables": config.tables,
+        "tables": tables
+    }))))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StartWarehouseExportParams {
+    /// Forget the watermarks and export every row again
+    #[serde(default)]
+    pub full: bool,
+}
+
+/// `POST /admin/shops/{shop}/warehouse-export` — queues an export instead
+/// of waiting for the next interval.
+#[utoipa::path(
+    post,
+    path = "/admin/shops/{shop}/warehouse-export",
+    tag = "sync",
+    params(
+        ("shop" = String, Path, description = "Shop domain, e.g. example.myshopify.com"),
+        StartWarehouseExportParams,
+    ),
+    responses(
+        (status = 202, description = "Export queued", body = crate::openapi::SyncQueued),
+        (status = 400, description = "Warehouse export isn't configured", body = crate::openapi::ErrorResponse),
+        (status = 404, description = "Shop not installed", body = crate::openapi::ErrorResponse),
+        (status = 409, description = "An export is already queued or running", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn start_warehouse_export_handler(
+    Path(shop): Path<String>,
+    Query(params): Query<StartWarehouseExportParams>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    require_export(&state)?;
+    state.token_store.get_token(&shop).await?
+        .ok_or_else(|| AppError::NotFound(format!("Shop {} is not installed", shop)))?;
+    let job = state.job_queue.enqueue(SHOP_WAREHOUSE_EXPORT_JOB, Some(&shop), json!({ "full": params.full })).await?
+        .ok_or_else(|| AppError::Conflict(format!("A warehouse export is already queued or running for {}", shop)))?;
+
+    Ok((StatusCode::ACCEPTED, Json(json!({
+        "shop": shop,
+        "queued": true,
+        "job_id": job.id
+    }))))
+}
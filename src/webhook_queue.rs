@@ -0,0 +1,178 @@
+use futures::future::BoxFuture;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tracing::{info, error, debug, warn};
+
+// =============================================================================
+// Queue Configuration
+// =============================================================================
+
+#[derive(Clone, Debug)]
+pub struct WebhookQueueConfig {
+    pub shards: usize,
+    pub capacity_per_shard: usize,
+    /// How long shutdown waits for queued webhooks to be applied
+    pub drain_timeout: Duration,
+}
+
+impl Default for WebhookQueueConfig {
+    fn default() -> Self {
+        Self {
+            shards: 4,
+            capacity_per_shard: 1000,
+            drain_timeout: Duration::from_secs(25),
+        }
+    }
+}
+
+impl WebhookQueueConfig {
+    pub fn from_env() -> Self {
+        Self {
+            shards: std::env::var("WEBHOOK_WORKER_SHARDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(4),
+            capacity_per_shard: std::env::var("WEBHOOK_QUEUE_CAPACITY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(1000),
+            drain_timeout: std::env::var("WEBHOOK_DRAIN_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(Duration::from_secs(25)),
+        }
+    }
+}
+
+// =============================================================================
+// Queued Webhooks
+// =============================================================================
+
+/// A verified webhook delivery waiting to be applied.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct QueuedWebhook {
+    /// Row in `webhook_events` captured when the delivery arrived
+    pub event_id: Option<uuid::Uuid>,
+    pub shop_domain: String,
+    pub topic: String,
+    pub resource_id: Option<i64>,
+    pub webhook_id: Option<String>,
+    pub payload: serde_json::Value,
+}
+
+/// Applies a webhook; an `Err` (already logged) describes what failed.
+pub type WebhookProcessor = Arc<dyn Fn(QueuedWebhook) -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+// =============================================================================
+// Shard-by-Shop Dispatcher
+// =============================================================================
+
+/// Fans webhook processing out to a fixed set of workers, hashing on the shop
+/// domain. Every event for a shop lands on the same worker and is applied in
+/// arrival order (so `orders/updated` can't overtake `orders/create`), while
+/// different shops proceed in parallel. Bounded queues absorb bursts and apply
+/// backpressure to the HTTP handlers once full.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    senders: Arc<Vec<mpsc::Sender<QueuedWebhook>>>,
+    closing: Arc<watch::Sender<bool>>,
+    workers: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+// Resolves once the dispatcher starts draining (or is dropped)
+async fn closed(mut closing: watch::Receiver<bool>) {
+    let _ = closing.wait_for(|closing| *closing).await;
+}
+
+pub fn shard_for(shop_domain: &str, shards: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    shop_domain.hash(&mut hasher);
+    (hasher.finish() % shards as u64) as usize
+}
+
+impl WebhookDispatcher {
+    pub fn start(config: &WebhookQueueConfig, processor: WebhookProcessor) -> Self {
+        let (closing, closing_rx) = watch::channel(false);
+        let mut workers = Vec::with_capacity(config.shards);
+        let senders = (0..config.shards)
+            .map(|shard| {
+                let (tx, mut rx) = mpsc::channel::<QueuedWebhook>(config.capacity_per_shard);
+                let processor = processor.clone();
+                let closing = closing_rx.clone();
+
+                workers.push(tokio::spawn(async move {
+                    let closing = closed(closing);
+                    tokio::pin!(closing);
+                    loop {
+                        tokio::select! {
+                            webhook = rx.recv() => match webhook {
+                                Some(webhook) => {
+                                    debug!("Worker {} processing {} for {}", shard, webhook.topic, webhook.shop_domain);
+                                    let _ = processor(webhook).await;
+                                }
+                                None => break,
+                            },
+                            // Stop taking new events but finish the ones already queued
+                            _ = &mut closing => {
+                                rx.close();
+                                while let Some(webhook) = rx.recv().await {
+                                    let _ = processor(webhook).await;
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    info!("Webhook worker {} stopped", shard);
+                }));
+
+                tx
+            })
+            .collect();
+
+        info!("🧵 Started {} webhook workers", config.shards);
+        Self {
+            senders: Arc::new(senders),
+            closing: Arc::new(closing),
+            workers: Arc::new(Mutex::new(workers)),
+        }
+    }
+
+    /// Closes every queue and waits up to `timeout` for the workers to apply
+    /// what was already queued. Returns false if some were still busy, in
+    /// which case they are aborted.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        let queued: usize = self.senders.iter().map(|tx| tx.max_capacity() - tx.capacity()).sum();
+        info!("⏳ Draining {} queued webhooks", queued);
+        self.closing.send_replace(true);
+
+        let workers: Vec<JoinHandle<()>> = std::mem::take(&mut *self.workers.lock().unwrap());
+        let aborts: Vec<_> = workers.iter().map(JoinHandle::abort_handle).collect();
+        match tokio::time::timeout(timeout, futures::future::join_all(workers)).await {
+            Ok(_) => true,
+            Err(_) => {
+                warn!("Webhook workers still busy after {:?}, abandoning the rest of the queue", timeout);
+                aborts.iter().for_each(|handle| handle.abort());
+                false
+            }
+        }
+    }
+
+    /// Queues a webhook on its shop's worker, waiting if that queue is full.
+    pub async fn dispatch(&self, webhook: QueuedWebhook) {
+        let shard = shard_for(&webhook.shop_domain, self.senders.len());
+        let topic = webhook.topic.clone();
+
+        if self.senders[shard].send(webhook).await.is_err() {
+            error!("Webhook worker {} is stopped or shutting down, dropping {} event", shard, topic);
+        }
+    }
+}
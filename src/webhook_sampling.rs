@@ -0,0 +1,276 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::{info, warn, debug};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    error::{AppError, AppResult},
+    webhook_queue::QueuedWebhook,
+    webhooks::sign_webhook,
+};
+
+// =============================================================================
+// Sampling Configuration
+// =============================================================================
+
+#[derive(Clone)]
+pub struct WebhookSamplingConfig {
+    /// Staging endpoint that receives sampled deliveries; sampling is off when unset
+    pub staging_url: Option<String>,
+    /// Share of verified deliveries to copy, 0-100
+    pub sample_percent: f64,
+    /// Staging app's webhook secret, used to re-sign the scrubbed body
+    pub signing_secret: Secret<String>,
+}
+
+impl Default for WebhookSamplingConfig {
+    fn default() -> Self {
+        Self {
+            staging_url: None,
+            sample_percent: 0.0,
+            signing_secret: Secret::new(String::new()),
+        }
+    }
+}
+
+impl WebhookSamplingConfig {
+    pub fn from_env(fallback_secret: &str) -> Self {
+        Self {
+            staging_url: std::env::var("WEBHOOK_STAGING_URL")
+                .ok()
+                .filter(|url| !url.is_empty()),
+            sample_percent: std::env::var("WEBHOOK_SAMPLE_PERCENT")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .map(|p| p.clamp(0.0, 100.0))
+                .unwrap_or(0.0),
+            signing_secret: Secret::new(
+                std::env::var("WEBHOOK_STAGING_SECRET")
+                    .unwrap_or_else(|_| fallback_secret.to_string())
+            ),
+        }
+    }
+}
+
+// =============================================================================
+// PII Scrubbing
+// =============================================================================
+
+// Keys whose values identify a person wherever they appear in a payload
+const PII_KEYS: &[&str] = &[
+    "email",
+    "contact_email",
+    "phone",
+    "first_name",
+    "last_name",
+    "company",
+    "address1",
+    "address2",
+    "zip",
+    "latitude",
+    "longitude",
+    "browser_ip",
+    "note",
+];
+
+/// Replaces personal data in a webhook payload in place. Emails become stable
+/// placeholders so records that shared an address still match each other;
+/// coordinates are nulled and other values are replaced outright. `name` is
+/// only scrubbed inside addresses, where it is a person's name rather than an
+/// order or product name.
+pub fn scrub_pii(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let is_address = map.contains_key("address1");
+            for (key, field) in map.iter_mut() {
+                if PII_KEYS.contains(&key.as_str()) || (is_address && key == "name") {
+                    *field = scrubbed_value(key, field);
+                } else {
+                    scrub_pii(field);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(scrub_pii),
+        _ => {}
+    }
+}
+
+fn scrubbed_value(key: &str, value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Null => serde_json::Value::Null,
+        serde_json::Value::String(s) if key.ends_with("email") && !s.is_empty() => {
+            let digest = Sha256::digest(s.trim().to_lowercase().as_bytes());
+            format!("redacted-{}@example.com", hex::encode(&digest[..6])).into()
+        }
+        serde_json::Value::String(_) => "[REDACTED]".into(),
+        _ => serde_json::Value::Null,
+    }
+}
+
+// =============================================================================
+// Staging Forwarder
+// =============================================================================
+
+/// Whether a delivery falls inside the sample. Decided from the delivery's
+/// identity rather than at random, so Shopify's retries of a sampled webhook
+/// are sampled too.
+pub fn is_sampled(key: &str, percent: f64) -> bool {
+    if percent <= 0.0 {
+        return false;
+    }
+    let digest = Sha256::digest(key.as_bytes());
+    let bucket = u64::from_be_bytes(digest[..8].try_into().unwrap()) % 10_000;
+    (bucket as f64) < percent * 100.0
+}
+
+/// Copies scrubbed production webhooks to a staging deployment so new
+/// processing code sees realistic traffic before it ships.
+#[derive(Clone)]
+pub struct WebhookSampler {
+    config: WebhookSamplingConfig,
+    client: reqwest::Client,
+}
+
+impl WebhookSampler {
+    /// `None` when no staging URL is configured.
+    pub fn new(config: &WebhookSamplingConfig) -> Option<Self> {
+        config.staging_url.as_ref()?;
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .ok()?;
+
+        Some(Self {
+            config: config.clone(),
+            client,
+        })
+    }
+
+    /// Forwards a sampled share of verified deliveries in the background.
+    pub fn maybe_forward(&self, webhook: &QueuedWebhook) {
+        let key = webhook
+            .webhook_id
+            .clone()
+            .or_else(|| webhook.event_id.map(|id| id.to_string()))
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        if !is_sampled(&key, self.config.sample_percent) {
+            return;
+        }
+
+        let sampler = self.clone();
+        let webhook = webhook.clone();
+        tokio::spawn(async move {
+            match sampler.forward(&webhook).await {
+                Ok(status) => debug!("Sampled {} webhook to staging ({})", webhook.topic, status),
+                Err(e) => warn!("Failed to forward sampled {} webhook to staging: {}", webhook.topic, e),
+            }
+        });
+    }
+
+    /// Scrubs the payload, re-signs it for staging, and posts it with the
+    /// original Shopify headers. Returns the staging response status.
+    pub async fn forward(&self, webhook: &QueuedWebhook) -> Result<reqwest::StatusCode, reqwest::Error> {
+        let staging_url = self.config.staging_url.as_deref().unwrap_or_default();
+
+        let mut payload = webhook.payload.clone();
+        scrub_pii(&mut payload);
+        let body = serde_json::to_vec(&payload).unwrap_or_default();
+        let signature = sign_webhook(&body, self.config.signing_secret.expose_secret());
+
+        let mut request = self.client
+            .post(staging_url)
+            .header("Content-Type", "application/json")
+            .header("X-Shopify-Topic", &webhook.topic)
+            .header("X-Shopify-Shop-Domain", &webhook.shop_domain)
+            .header("X-Shopify-Hmac-Sha256", signature)
+            .header("X-Webhook-Sampled", "true");
+
+        if let Some(ref webhook_id) = webhook.webhook_id {
+            request = request.header("X-Shopify-Webhook-Id", webhook_id);
+        }
+        if let Some(event_id) = webhook.event_id {
+            request = request.header("X-Webhook-Source-Event-Id", event_id.to_string());
+        }
+
+        let response = request.body(body).send().await?;
+        Ok(response.status())
+    }
+}
+
+// =============================================================================
+// Replay Handler
+// =============================================================================
+
+/// Replays a captured production delivery into staging, scrubbed, regardless
+/// of the sample rate. Useful for reproducing a specific event.
+#[utoipa::path(
+    post,
+    path = "/admin/webhooks/events/{id}/replay",
+    tag = "webhooks",
+    params(("id" = Uuid, Path, description = "Captured webhook event ID")),
+    responses(
+        (status = 200, description = "Replayed to staging", body = crate::openapi::WebhookReplay),
+        (status = 400, description = "No staging URL, or the event can't be replayed", body = crate::openapi::ErrorResponse),
+        (status = 404, description = "No such event", body = crate::openapi::ErrorResponse),
+        (status = 502, description = "Staging couldn't be reached", body = crate::openapi::WebhookReplay),
+    ),
+)]
+pub async fn replay_webhook_event_handler(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> AppResult<impl IntoResponse> {
+    let sampler = state.webhook_sampler.as_ref().ok_or_else(|| {
+        AppError::BadRequest("Webhook replay needs WEBHOOK_STAGING_URL to be configured".to_string())
+    })?;
+
+    let event = state.webhook_events.get_event(id).await?
+        .ok_or_else(|| AppError::NotFound("Webhook event not found".to_string()))?;
+
+    if !event.verified {
+        return Err(AppError::BadRequest("Only verified webhook events can be replayed".to_string()));
+    }
+
+    let payload = event.raw_body.as_deref()
+        .and_then(|body| serde_json::from_slice(body).ok())
+        .or(event.payload)
+        .ok_or_else(|| AppError::BadRequest("Webhook event has no JSON payload to replay".to_string()))?;
+
+    let webhook = QueuedWebhook {
+        event_id: Some(event.id),
+        shop_domain: event.shop_domain,
+        topic: event.topic,
+        resource_id: event.resource_id,
+        webhook_id: event.webhook_id,
+        payload,
+    };
+
+    match sampler.forward(&webhook).await {
+        Ok(status) => {
+            info!("🔁 Replayed {} webhook {} to staging ({})", webhook.topic, id, status);
+            Ok((StatusCode::OK, Json(serde_json::json!({
+                "replayed": true,
+                "event_id": id,
+                "topic": webhook.topic,
+                "staging_status": status.as_u16()
+            }))))
+        }
+        Err(e) => {
+            warn!("Failed to replay webhook {} to staging: {}", id, e);
+            Ok((StatusCode::BAD_GATEWAY, Json(serde_json::json!({
+                "replayed": false,
+                "event_id": id,
+                "error": format!("Failed to reach staging: {}", e)
+            }))))
+        }
+    }
+}
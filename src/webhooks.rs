@@ -1,17 +1,44 @@
 use axum::{
-    extract::State,
+    async_trait,
+    extract::{FromRef, FromRequest, MatchedPath, Path, Request, State},
     http::{StatusCode, HeaderMap},
     response::IntoResponse,
     Json,
     body::Bytes,
 };
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing::{info, warn, error, debug};
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
-use hex;
+use base64::{Engine as _, engine::general_purpose};
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
 
-use crate::AppState;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    AppConfig,
+    AppState,
+    catalog::apply_product_webhook,
+    customer_index::apply_customer_webhook,
+    data_retention::apply_customer_redaction,
+    database::{
+        CatalogStore, CustomerMirrorStore, OrderStore, PersonalDataStore, QueuedJob, WebhookEventStore,
+    },
+    domain_events::{DomainEvent, DomainEventRegistry},
+    error::{AppError, AppResult},
+    http_client::ShopifyClient,
+    job_queue::{JobKind, ShopOrdering},
+    low_stock::InventoryLevelWebhook,
+    order_risks::RiskAssessmentWebhook,
+    order_sync::apply_order_webhook,
+    response_cache::CacheGroup,
+    scheduler::{Job, JobScope, Schedule},
+    shop_secrets::WebhookSecret,
+    webhook_queue::{QueuedWebhook, WebhookProcessor},
+};
 
 // =============================================================================
 // Webhook Verification
@@ -19,28 +46,285 @@ use crate::AppState;
 
 type HmacSha256 = Hmac<Sha256>;
 
+/// Why a webhook delivery failed verification.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WebhookVerificationError {
+    #[error("No webhook secret configured")]
+    MissingSecret,
+    #[error("Missing X-Shopify-Hmac-Sha256 header")]
+    MissingSignature,
+    #[error("Webhook signature is neither base64 nor hex")]
+    MalformedSignature,
+    #[error("Invalid webhook signature")]
+    InvalidSignature,
+}
+
+/// Decodes a webhook signature: base64 as Shopify sends it, or hex (with or
+/// without a `sha256=` prefix) as `sign_webhook` produces it.
+pub fn decode_signature(signature: &str) -> Option<Vec<u8>> {
+    let signature = signature.trim();
+    let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+    let digest = if signature.len() == 64 && signature.bytes().all(|b| b.is_ascii_hexdigit()) {
+        hex::decode(signature).ok()?
+    } else {
+        general_purpose::STANDARD.decode(signature).ok()?
+    };
+    // A SHA-256 MAC is 32 bytes; anything else can't match
+    (digest.len() == 32).then_some(digest)
+}
+
+/// Checks `signature` against the HMAC-SHA256 of `body` under `secret`, in
+/// constant time. Unreadable signatures don't match.
 pub fn verify_webhook(
     body: &[u8],
     signature: &str,
     secret: &str,
 ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
-    // Remove 'sha256=' prefix if present
-    let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
-    
-    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())?;
+    match verify_signature(body, signature, secret) {
+        Ok(()) => Ok(true),
+        Err(WebhookVerificationError::MalformedSignature | WebhookVerificationError::InvalidSignature) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn verify_signature(body: &[u8], signature: &str, secret: &str) -> Result<(), WebhookVerificationError> {
+    // An empty key is one anybody can sign with
+    if secret.is_empty() {
+        return Err(WebhookVerificationError::MissingSecret);
+    }
+    let signature = decode_signature(signature).ok_or(WebhookVerificationError::MalformedSignature)?;
+
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
     mac.update(body);
-    
-    let expected_signature = hex::encode(mac.finalize().into_bytes());
-    
-    // Use constant-time comparison
-    Ok(expected_signature == signature)
+    // Constant-time comparison
+    mac.verify_slice(&signature).map_err(|_| WebhookVerificationError::InvalidSignature)
+}
+
+/// Reads `DANGEROUSLY_SKIP_WEBHOOK_VERIFICATION`. Only `true` turns the bypass
+/// on, and it is refused outright in production.
+pub fn skip_verification_setting(value: Option<&str>, environment: &str) -> AppResult<bool> {
+    let skip = value.map(|v| v.trim().eq_ignore_ascii_case("true")).unwrap_or(false);
+    if skip && environment == "production" {
+        return Err(AppError::Config(
+            "DANGEROUSLY_SKIP_WEBHOOK_VERIFICATION cannot be enabled in production".to_string(),
+        ));
+    }
+    Ok(skip)
+}
+
+/// Hex signature `verify_webhook` accepts for `body`, for deliveries we send
+/// ourselves.
+pub fn sign_webhook(body: &[u8], secret: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC can take key of any size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Header a trusted gateway sets after checking Shopify's HMAC itself, as
+/// `t=<unix seconds>,v1=<hex signature>`.
+pub const GATEWAY_VERIFIED_HEADER: &str = "X-Gateway-Webhook-Verified";
+
+/// Attestations older (or further in the future) than this are ignored.
+const GATEWAY_ATTESTATION_MAX_AGE_SECS: i64 = 300;
+
+/// Reads `WEBHOOK_GATEWAY_SECRET`. Unset means every webhook gets full HMAC
+/// verification.
+pub fn gateway_secret_setting(value: Option<&str>) -> AppResult<Option<String>> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        None => Ok(None),
+        Some(secret) if secret.len() < 32 => Err(AppError::Config(
+            "WEBHOOK_GATEWAY_SECRET must be at least 32 characters".to_string(),
+        )),
+        Some(secret) => Ok(Some(secret.to_string())),
+    }
+}
+
+// The body digest binds the attestation to the exact bytes the gateway checked,
+// so a captured header can't be replayed with a different payload.
+fn gateway_payload(timestamp: i64, webhook_id: &str, shopify_hmac: &str, body: &[u8]) -> String {
+    use sha2::Digest;
+    format!("{}.{}.{}.{}", timestamp, webhook_id, shopify_hmac, hex::encode(Sha256::digest(body)))
+}
+
+/// Header value a gateway sends for a delivery it has verified, in the form
+/// `t=<unix secs>,v1=<hex>`. The signature is HMAC-SHA256 with the shared
+/// secret over `<t>.<X-Shopify-Webhook-Id>.<X-Shopify-Hmac-Sha256>.<hex sha256 of body>`.
+/// The server only verifies; this is the reference for gateway implementations.
+pub fn sign_gateway_attestation(
+    secret: &str,
+    timestamp: i64,
+    webhook_id: &str,
+    shopify_hmac: &str,
+    body: &[u8],
+) -> String {
+    let signature = sign_webhook(gateway_payload(timestamp, webhook_id, shopify_hmac, body).as_bytes(), secret);
+    format!("t={},v1={}", timestamp, signature)
+}
+
+/// Checks a gateway attestation against the delivery's headers and body.
+pub fn verify_gateway_attestation(
+    header: &str,
+    secret: &str,
+    webhook_id: &str,
+    shopify_hmac: &str,
+    body: &[u8],
+    now: i64,
+) -> Result<(), &'static str> {
+    let mut timestamp = None;
+    let mut signature = None;
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signature = hex::decode(value).ok(),
+            _ => {}
+        }
+    }
+    let timestamp = timestamp.ok_or("Gateway attestation has no timestamp")?;
+    let signature = signature.ok_or("Gateway attestation has no signature")?;
+
+    if (now - timestamp).abs() > GATEWAY_ATTESTATION_MAX_AGE_SECS {
+        return Err("Gateway attestation has expired");
+    }
+
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC can take key of any size");
+    mac.update(gateway_payload(timestamp, webhook_id, shopify_hmac, body).as_bytes());
+    // Constant-time comparison
+    mac.verify_slice(&signature).map_err(|_| "Invalid gateway attestation signature")
+}
+
+/// Base64 signature, as Shopify puts in `X-Shopify-Hmac-Sha256`.
+pub fn sign_webhook_base64(body: &[u8], secret: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC can take key of any size");
+    mac.update(body);
+    general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+/// A webhook topic, e.g. `orders/create`, split into the resource and what
+/// happened to it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WebhookTopic {
+    pub resource: String,
+    pub event: String,
+}
+
+impl WebhookTopic {
+    /// `resource/event`, lowercase with underscores as Shopify spells topics.
+    pub fn parse(topic: &str) -> Option<Self> {
+        let (resource, event) = topic.trim().split_once('/')?;
+        let valid = |part: &str| {
+            !part.is_empty() && part.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+        };
+        (valid(resource) && valid(event)).then(|| Self {
+            resource: resource.to_string(),
+            event: event.to_string(),
+        })
+    }
+
+    /// The topic of a delivery, from its `X-Shopify-Topic` header.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        header_value(headers, "X-Shopify-Topic").and_then(Self::parse)
+    }
+}
+
+impl std::fmt::Display for WebhookTopic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.resource, self.event)
+    }
+}
+
+/// Checks that a webhook delivery really came from Shopify before its body
+/// is trusted. Secrets are kept out of `Debug` output and wiped on drop.
+#[derive(Clone, Debug)]
+pub struct WebhookVerifier {
+    api_secret: Secret<String>,
+    gateway_secret: Option<Secret<String>>,
+    skip_verification: bool,
+}
+
+impl WebhookVerifier {
+    /// Verifies `X-Shopify-Hmac-Sha256` against the app's API secret.
+    pub fn new(api_secret: impl Into<String>) -> Self {
+        Self {
+            api_secret: Secret::new(api_secret.into()),
+            gateway_secret: None,
+            skip_verification: false,
+        }
+    }
+
+    /// Also accepts deliveries carrying a valid attestation from a gateway
+    /// that has already checked the HMAC.
+    pub fn with_gateway_secret(mut self, secret: impl Into<String>) -> Self {
+        self.gateway_secret = Some(Secret::new(secret.into()));
+        self
+    }
+
+    /// Verifies the HMAC against `api_secret` instead of the app's, for shops
+    /// whose webhooks are signed with a secret of their own.
+    pub fn with_api_secret(mut self, api_secret: impl Into<String>) -> Self {
+        self.api_secret = Secret::new(api_secret.into());
+        self
+    }
+
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            api_secret: Secret::new(config.api_secret.clone()),
+            gateway_secret: config.webhook_gateway_secret.clone().map(Secret::new),
+            skip_verification: config.skip_webhook_verification,
+        }
+    }
+
+    /// Checks a delivery's `X-Shopify-Hmac-Sha256` header (or a gateway's
+    /// attestation) against its raw body.
+    pub fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), WebhookVerificationError> {
+        if self.skip_verification {
+            warn!(
+                "🚨 DANGEROUSLY_SKIP_WEBHOOK_VERIFICATION is on: accepting {} webhook without checking its HMAC",
+                header_value(headers, "X-Shopify-Topic").unwrap_or("unknown")
+            );
+            return Ok(());
+        }
+
+        let signature = header_value(headers, "X-Shopify-Hmac-Sha256")
+            .ok_or(WebhookVerificationError::MissingSignature)?;
+
+        // Deliveries a trusted gateway has already verified skip the HMAC. A bad
+        // attestation isn't fatal; the delivery just gets verified in full.
+        if let (Some(gateway_secret), Some(attestation)) =
+            (&self.gateway_secret, header_value(headers, GATEWAY_VERIFIED_HEADER))
+        {
+            let webhook_id = header_value(headers, "X-Shopify-Webhook-Id").unwrap_or_default();
+            let now = chrono::Utc::now().timestamp();
+            let gateway_secret = gateway_secret.expose_secret();
+            match verify_gateway_attestation(attestation, gateway_secret, webhook_id, signature, body, now) {
+                Ok(()) => return Ok(()),
+                Err(e) => warn!("{} for webhook {}; verifying HMAC instead", e, webhook_id),
+            }
+        }
+
+        self.verify_signature(body, signature)?;
+
+        if let Some(shop_domain) = header_value(headers, "X-Shopify-Shop-Domain") {
+            debug!("Webhook from shop: {}", shop_domain);
+        }
+
+        Ok(())
+    }
+
+    /// Checks a signature taken from wherever the caller got the delivery,
+    /// base64 or hex, against the API secret alone.
+    pub fn verify_signature(&self, body: &[u8], signature: &str) -> Result<(), WebhookVerificationError> {
+        verify_signature(body, signature, self.api_secret.expose_secret())
+    }
 }
 
 // =============================================================================
 // Webhook Event Structures
 // =============================================================================
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, utoipa::ToSchema)]
 pub struct OrderWebhook {
     pub id: u64,
     pub email: Option<String>,
@@ -105,7 +389,7 @@ pub struct OrderWebhook {
     pub shipping_lines: Vec<serde_json::Value>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, utoipa::ToSchema)]
 pub struct ProductWebhook {
     pub id: u64,
     pub title: String,
@@ -126,7 +410,47 @@ pub struct ProductWebhook {
     pub image: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// `products/delete` carries only the deleted product's ID.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, utoipa::ToSchema)]
+pub struct ProductDeletedWebhook {
+    pub id: u64,
+}
+
+/// `app/uninstalled` carries the shop the app was removed from.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, utoipa::ToSchema)]
+pub struct AppUninstalledWebhook {
+    pub id: u64,
+    pub name: Option<String>,
+    pub domain: Option<String>,
+    pub myshopify_domain: Option<String>,
+}
+
+/// `customers/delete` carries only the deleted customer's ID.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, utoipa::ToSchema)]
+pub struct CustomerDeletedWebhook {
+    pub id: u64,
+}
+
+/// `customers/redact`, a GDPR request Shopify sends when a merchant is
+/// asked to erase a customer's personal data.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, utoipa::ToSchema)]
+pub struct CustomerRedactWebhook {
+    pub shop_id: u64,
+    pub shop_domain: String,
+    pub customer: RedactedCustomer,
+    /// Orders to scrub along with the customer
+    #[serde(default)]
+    pub orders_to_redact: Vec<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, utoipa::ToSchema)]
+pub struct RedactedCustomer {
+    pub id: u64,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, utoipa::ToSchema)]
 pub struct CustomerWebhook {
     pub id: u64,
     pub email: Option<String>,
@@ -152,7 +476,7 @@ pub struct CustomerWebhook {
     pub default_address: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, utoipa::ToSchema)]
 pub struct CheckoutWebhook {
     pub id: u64,
     pub token: String,
@@ -199,11 +523,21 @@ pub struct CheckoutWebhook {
     pub customer: Option<serde_json::Value>,
 }
 
+#[derive(Debug, Deserialize, Serialize, JsonSchema, utoipa::ToSchema)]
+pub struct RefundWebhook {
+    pub id: u64,
+    pub order_id: u64,
+    pub created_at: String,
+    pub note: Option<String>,
+    pub refund_line_items: Vec<serde_json::Value>,
+    pub transactions: Vec<serde_json::Value>,
+}
+
 // =============================================================================
 // Webhook Response Structures
 // =============================================================================
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, utoipa::ToSchema)]
 pub struct WebhookResponse {
     pub status: String,
     pub message: String,
@@ -232,350 +566,1016 @@ impl WebhookResponse {
 }
 
 // =============================================================================
-// Webhook Handlers
+// Verified Webhook Extractor
 // =============================================================================
 
-pub async fn orders_created_webhook(
-    State(state): State<AppState>,
-    headers: HeaderMap,
+/// A webhook delivery that passed `WebhookVerifier`, with its body parsed as
+/// `T`. Extracting it captures the delivery (verified or not) for
+/// `/admin/webhooks/events/:id`, so no handler can skip either step. It reads
+/// the body, so it must be the handler's last argument.
+///
+/// The topic comes from the route, looked up in `SUPPORTED_WEBHOOKS`, rather
+/// than `X-Shopify-Topic`: the HMAC covers only the body, not the headers.
+pub struct VerifiedWebhook<T> {
+    pub payload: T,
+    pub topic: &'static str,
+    /// `X-Shopify-Shop-Domain`, or the configured shop when it's missing
+    pub shop_domain: String,
+    /// `X-Shopify-Webhook-Id`, the same across Shopify's retries
+    pub webhook_id: Option<String>,
+    /// The captured delivery, if storing it worked
+    pub event_id: Option<Uuid>,
     body: Bytes,
-) -> impl IntoResponse {
-    debug!("Received order created webhook");
-    
-    // Verify webhook authenticity
-    if let Err(e) = verify_webhook_request(&headers, &body, &state.config.api_secret).await {
-        warn!("Webhook verification failed: {}", e);
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(WebhookResponse::error("Webhook verification failed")),
-        );
+}
+
+impl<T> VerifiedWebhook<T> {
+    /// Hands the delivery to the shop's worker, filed under `resource_id`, so
+    /// it's processed in arrival order off the request path.
+    pub async fn queue(&self, state: &AppState, resource_id: u64) {
+        let payload = match serde_json::from_slice::<serde_json::Value>(&self.body) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Failed to decode {} webhook payload for processing: {}", self.topic, e);
+                return;
+            }
+        };
+
+        queue_webhook_event(state, QueuedWebhook {
+            event_id: self.event_id,
+            shop_domain: self.shop_domain.clone(),
+            topic: self.topic.to_string(),
+            resource_id: Some(resource_id as i64),
+            webhook_id: self.webhook_id.clone(),
+            payload,
+        })
+        .await;
     }
+}
 
-    // Parse the order data
-    match serde_json::from_slice::<OrderWebhook>(&body) {
-        Ok(order) => {
-            info!("✅ Order created: {} - ${} - {}", order.name, order.total_price, order.email.unwrap_or_default());
-            
-            // Here you would typically:
-            // 1. Store the order in your database
-            // 2. Send notifications
-            // 3. Trigger business logic
-            // 4. Update inventory tracking
-            // 5. Send confirmation emails
-            
-            // For now, just log the event
-            info!("Order {} processed successfully", order.id);
-            
-            (
-                StatusCode::OK,
-                Json(WebhookResponse::success(&format!("Order {} processed", order.id))),
-            )
+/// Topic delivered to a webhook route, e.g. `orders/create` for `/webhooks/orders/created`.
+pub fn webhook_topic(route: &str) -> Option<&'static str> {
+    SUPPORTED_WEBHOOKS
+        .iter()
+        .find(|webhook| webhook.endpoint == route)
+        .map(|webhook| webhook.topic)
+}
+
+fn reject(status: StatusCode, message: &str) -> (StatusCode, Json<WebhookResponse>) {
+    (status, Json(WebhookResponse::error(message)))
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for VerifiedWebhook<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    AppState: FromRef<S>,
+{
+    type Rejection = (StatusCode, Json<WebhookResponse>);
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let state = AppState::from_ref(state);
+        let route = request.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+        let Some(topic) = route.as_deref().and_then(webhook_topic) else {
+            error!("No webhook topic is registered for route {:?}", route);
+            return Err(reject(StatusCode::INTERNAL_SERVER_ERROR, "Unknown webhook route"));
+        };
+        debug!("Received {} webhook", topic);
+
+        let headers = request.headers().clone();
+        let body = Bytes::from_request(request, &state).await.map_err(|e| {
+            warn!("Failed to read {} webhook body: {}", topic, e);
+            reject(StatusCode::BAD_REQUEST, "Failed to read webhook body")
+        })?;
+
+        // Verify webhook authenticity, keeping the raw delivery either way
+        let mut verifier = WebhookVerifier::from_config(&state.config);
+        if let Some(secret) = shop_webhook_secret(&state, &headers).await {
+            verifier = verifier.with_api_secret(secret);
         }
-        Err(e) => {
-            error!("Failed to parse order webhook: {}", e);
-            (
-                StatusCode::BAD_REQUEST,
-                Json(WebhookResponse::error("Failed to parse order data")),
-            )
+        let verification = verifier.verify(&headers, &body);
+        let event_id = capture_delivery(&state, &headers, topic, &body, verification.is_ok()).await;
+        if let Err(e) = verification {
+            warn!("Webhook verification failed: {}", e);
+            return Err(reject(StatusCode::UNAUTHORIZED, "Webhook verification failed"));
         }
+
+        let payload = serde_json::from_slice::<T>(&body).map_err(|e| {
+            error!("Failed to parse {} webhook: {}", topic, e);
+            reject(StatusCode::BAD_REQUEST, &format!("Failed to parse {} payload", topic))
+        })?;
+
+        Ok(Self {
+            payload,
+            topic,
+            shop_domain: header_value(&headers, "X-Shopify-Shop-Domain")
+                .unwrap_or(&state.config.shop)
+                .to_string(),
+            webhook_id: header_value(&headers, "X-Shopify-Webhook-Id").map(str::to_string),
+            event_id,
+            body,
+        })
     }
 }
 
+// =============================================================================
+// Webhook Handlers
+// =============================================================================
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/orders/created",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = OrderWebhook, description = "Shopify's `orders/create` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn orders_created_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<OrderWebhook>,
+) -> impl IntoResponse {
+    let order = &webhook.payload;
+    webhook.queue(&state, order.id).await;
+    info!("✅ Order created: {} - ${} - {}", order.name, order.total_price, order.email.as_deref().unwrap_or_default());
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Order {} processed", order.id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/orders/updated",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = OrderWebhook, description = "Shopify's `orders/updated` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
 pub async fn orders_updated_webhook(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    body: Bytes,
+    webhook: VerifiedWebhook<OrderWebhook>,
 ) -> impl IntoResponse {
-    debug!("Received order updated webhook");
-    
-    if let Err(e) = verify_webhook_request(&headers, &body, &state.config.api_secret).await {
-        warn!("Webhook verification failed: {}", e);
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(WebhookResponse::error("Webhook verification failed")),
-        );
-    }
+    let order = &webhook.payload;
+    webhook.queue(&state, order.id).await;
+    info!("📝 Order updated: {} - Status: {}", order.name, order.financial_status);
 
-    match serde_json::from_slice::<OrderWebhook>(&body) {
-        Ok(order) => {
-            info!("📝 Order updated: {} - Status: {}", order.name, order.financial_status);
-            
-            // Handle order update logic here
-            
-            (
-                StatusCode::OK,
-                Json(WebhookResponse::success(&format!("Order {} update processed", order.id))),
-            )
-        }
-        Err(e) => {
-            error!("Failed to parse order update webhook: {}", e);
-            (
-                StatusCode::BAD_REQUEST,
-                Json(WebhookResponse::error("Failed to parse order update data")),
-            )
-        }
-    }
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Order {} update processed", order.id))),
+    )
 }
 
+#[utoipa::path(
+    post,
+    path = "/webhooks/orders/cancelled",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = OrderWebhook, description = "Shopify's `orders/cancelled` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
 pub async fn orders_cancelled_webhook(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    body: Bytes,
+    webhook: VerifiedWebhook<OrderWebhook>,
 ) -> impl IntoResponse {
-    debug!("Received order cancelled webhook");
-    
-    if let Err(e) = verify_webhook_request(&headers, &body, &state.config.api_secret).await {
-        warn!("Webhook verification failed: {}", e);
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(WebhookResponse::error("Webhook verification failed")),
-        );
-    }
+    let order = &webhook.payload;
+    webhook.queue(&state, order.id).await;
+    info!("❌ Order cancelled: {} - Reason: {}", order.name, order.cancel_reason.as_deref().unwrap_or_default());
 
-    match serde_json::from_slice::<OrderWebhook>(&body) {
-        Ok(order) => {
-            info!("❌ Order cancelled: {} - Reason: {}", order.name, order.cancel_reason.unwrap_or_default());
-            
-            // Handle order cancellation logic here
-            
-            (
-                StatusCode::OK,
-                Json(WebhookResponse::success(&format!("Order {} cancellation processed", order.id))),
-            )
-        }
-        Err(e) => {
-            error!("Failed to parse order cancellation webhook: {}", e);
-            (
-                StatusCode::BAD_REQUEST,
-                Json(WebhookResponse::error("Failed to parse order cancellation data")),
-            )
-        }
-    }
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Order {} cancellation processed", order.id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/orders/paid",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = OrderWebhook, description = "Shopify's `orders/paid` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn orders_paid_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<OrderWebhook>,
+) -> impl IntoResponse {
+    let order = &webhook.payload;
+    webhook.queue(&state, order.id).await;
+    info!("💰 Order paid: {} - ${}", order.name, order.total_price);
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Order {} payment processed", order.id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/orders/fulfilled",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = OrderWebhook, description = "Shopify's `orders/fulfilled` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn orders_fulfilled_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<OrderWebhook>,
+) -> impl IntoResponse {
+    let order = &webhook.payload;
+    webhook.queue(&state, order.id).await;
+    info!("📦 Order fulfilled: {}", order.name);
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Order {} fulfillment processed", order.id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/orders/risk_assessment_changed",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = RiskAssessmentWebhook, description = "Shopify's `orders/risk_assessment_changed` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn orders_risk_assessment_changed_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<RiskAssessmentWebhook>,
+) -> impl IntoResponse {
+    let assessment = &webhook.payload;
+    let Some(order_id) = assessment.order_id() else {
+        warn!("Risk assessment webhook without an order ID");
+        return (StatusCode::OK, Json(WebhookResponse::success("Risk assessment without an order ignored")));
+    };
+    webhook.queue(&state, order_id).await;
+    info!("🚩 Order {} risk assessed: {}", order_id, assessment.risk_level);
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Order {} risk assessment processed", order_id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/refunds/created",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = RefundWebhook, description = "Shopify's `refunds/create` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn refunds_created_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<RefundWebhook>,
+) -> impl IntoResponse {
+    let refund = &webhook.payload;
+    // Refunds are filed under their order so they show up in its timeline
+    webhook.queue(&state, refund.order_id).await;
+    info!("↩️ Refund {} created for order {}", refund.id, refund.order_id);
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Refund {} processed", refund.id))),
+    )
 }
 
+#[utoipa::path(
+    post,
+    path = "/webhooks/inventory_levels/updated",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = InventoryLevelWebhook, description = "Shopify's `inventory_levels/update` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn inventory_levels_updated_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<InventoryLevelWebhook>,
+) -> impl IntoResponse {
+    let level = &webhook.payload;
+    // Filed under the inventory item, which has no other resource to belong to
+    webhook.queue(&state, level.inventory_item_id).await;
+    info!(
+        "📦 Inventory item {} at location {}: {:?} available",
+        level.inventory_item_id, level.location_id, level.available
+    );
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Inventory item {} level processed", level.inventory_item_id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/products/created",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = ProductWebhook, description = "Shopify's `products/create` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
 pub async fn products_created_webhook(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    body: Bytes,
+    webhook: VerifiedWebhook<ProductWebhook>,
 ) -> impl IntoResponse {
-    debug!("Received product created webhook");
-    
-    if let Err(e) = verify_webhook_request(&headers, &body, &state.config.api_secret).await {
-        warn!("Webhook verification failed: {}", e);
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(WebhookResponse::error("Webhook verification failed")),
-        );
-    }
+    let product = &webhook.payload;
+    webhook.queue(&state, product.id).await;
+    info!("🆕 Product created: {} - {}", product.title, product.vendor);
 
-    match serde_json::from_slice::<ProductWebhook>(&body) {
-        Ok(product) => {
-            info!("🆕 Product created: {} - {}", product.title, product.vendor);
-            
-            // Handle product creation logic here
-            
-            (
-                StatusCode::OK,
-                Json(WebhookResponse::success(&format!("Product {} processed", product.id))),
-            )
-        }
-        Err(e) => {
-            error!("Failed to parse product webhook: {}", e);
-            (
-                StatusCode::BAD_REQUEST,
-                Json(WebhookResponse::error("Failed to parse product data")),
-            )
-        }
-    }
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Product {} processed", product.id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/products/updated",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = ProductWebhook, description = "Shopify's `products/update` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn products_updated_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<ProductWebhook>,
+) -> impl IntoResponse {
+    let product = &webhook.payload;
+    webhook.queue(&state, product.id).await;
+    info!("📝 Product updated: {} - Status: {}", product.title, product.status);
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Product {} update processed", product.id))),
+    )
 }
 
+#[utoipa::path(
+    post,
+    path = "/webhooks/products/deleted",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = ProductDeletedWebhook, description = "Shopify's `products/delete` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn products_deleted_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<ProductDeletedWebhook>,
+) -> impl IntoResponse {
+    let product = &webhook.payload;
+    webhook.queue(&state, product.id).await;
+    info!("🗑️ Product deleted: {}", product.id);
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Product {} deletion processed", product.id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/customers/created",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = CustomerWebhook, description = "Shopify's `customers/create` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
 pub async fn customers_created_webhook(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    body: Bytes,
+    webhook: VerifiedWebhook<CustomerWebhook>,
 ) -> impl IntoResponse {
-    debug!("Received customer created webhook");
-    
-    if let Err(e) = verify_webhook_request(&headers, &body, &state.config.api_secret).await {
-        warn!("Webhook verification failed: {}", e);
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(WebhookResponse::error("Webhook verification failed")),
-        );
-    }
+    let customer = &webhook.payload;
+    webhook.queue(&state, customer.id).await;
+    info!("👤 Customer created: {} {} - {}",
+        customer.first_name.as_deref().unwrap_or_default(),
+        customer.last_name.as_deref().unwrap_or_default(),
+        customer.email.as_deref().unwrap_or_default()
+    );
 
-    match serde_json::from_slice::<CustomerWebhook>(&body) {
-        Ok(customer) => {
-            info!("👤 Customer created: {} {} - {}", 
-                customer.first_name.unwrap_or_default(),
-                customer.last_name.unwrap_or_default(),
-                customer.email.unwrap_or_default()
-            );
-            
-            // Handle customer creation logic here
-            
-            (
-                StatusCode::OK,
-                Json(WebhookResponse::success(&format!("Customer {} processed", customer.id))),
-            )
-        }
-        Err(e) => {
-            error!("Failed to parse customer webhook: {}", e);
-            (
-                StatusCode::BAD_REQUEST,
-                Json(WebhookResponse::error("Failed to parse customer data")),
-            )
-        }
-    }
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Customer {} processed", customer.id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/customers/updated",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = CustomerWebhook, description = "Shopify's `customers/update` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn customers_updated_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<CustomerWebhook>,
+) -> impl IntoResponse {
+    let customer = &webhook.payload;
+    webhook.queue(&state, customer.id).await;
+    info!("👤 Customer updated: {}", customer.id);
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Customer {} update processed", customer.id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/customers/deleted",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = CustomerDeletedWebhook, description = "Shopify's `customers/delete` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn customers_deleted_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<CustomerDeletedWebhook>,
+) -> impl IntoResponse {
+    let customer = &webhook.payload;
+    webhook.queue(&state, customer.id).await;
+    info!("🗑️ Customer deleted: {}", customer.id);
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Customer {} deletion processed", customer.id))),
+    )
 }
 
+#[utoipa::path(
+    post,
+    path = "/webhooks/customers/redact",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = CustomerRedactWebhook, description = "Shopify's `customers/redact` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn customers_redact_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<CustomerRedactWebhook>,
+) -> impl IntoResponse {
+    let customer = &webhook.payload.customer;
+    webhook.queue(&state, customer.id).await;
+    info!("🧽 Customer redaction requested: {} ({} orders)", customer.id, webhook.payload.orders_to_redact.len());
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Customer {} redaction queued", customer.id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/checkouts/created",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = CheckoutWebhook, description = "Shopify's `checkouts/create` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
 pub async fn checkouts_created_webhook(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    body: Bytes,
+    webhook: VerifiedWebhook<CheckoutWebhook>,
 ) -> impl IntoResponse {
-    debug!("Received checkout created webhook");
-    
-    if let Err(e) = verify_webhook_request(&headers, &body, &state.config.api_secret).await {
-        warn!("Webhook verification failed: {}", e);
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(WebhookResponse::error("Webhook verification failed")),
-        );
-    }
+    let checkout = &webhook.payload;
+    webhook.queue(&state, checkout.id).await;
+    info!("🛒 Checkout created: {} - ${}", checkout.token, checkout.total_price);
 
-    match serde_json::from_slice::<CheckoutWebhook>(&body) {
-        Ok(checkout) => {
-            info!("🛒 Checkout created: {} - ${}", checkout.token, checkout.total_price);
-            
-            // Handle checkout creation logic here
-            
-            (
-                StatusCode::OK,
-                Json(WebhookResponse::success(&format!("Checkout {} processed", checkout.id))),
-            )
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Checkout {} processed", checkout.id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/checkouts/updated",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = CheckoutWebhook, description = "Shopify's `checkouts/update` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn checkouts_updated_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<CheckoutWebhook>,
+) -> impl IntoResponse {
+    let checkout = &webhook.payload;
+    webhook.queue(&state, checkout.id).await;
+    info!("📝 Checkout updated: {} - ${}", checkout.token, checkout.total_price);
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Checkout {} update processed", checkout.id))),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/app/uninstalled",
+    tag = "webhooks",
+    security(("shopify_hmac" = [])),
+    request_body(content = AppUninstalledWebhook, description = "Shopify's `app/uninstalled` payload"),
+    responses(
+        (status = 200, description = "Accepted", body = WebhookResponse),
+        (status = 400, description = "Unparseable payload", body = WebhookResponse),
+        (status = 401, description = "HMAC verification failed", body = WebhookResponse),
+    ),
+)]
+pub async fn app_uninstalled_webhook(
+    State(state): State<AppState>,
+    webhook: VerifiedWebhook<AppUninstalledWebhook>,
+) -> impl IntoResponse {
+    let shop = &webhook.payload;
+    webhook.queue(&state, shop.id).await;
+    info!("👋 App uninstalled from {}", webhook.shop_domain);
+
+    (
+        StatusCode::OK,
+        Json(WebhookResponse::success(&format!("Shop {} uninstall processed", shop.id))),
+    )
+}
+
+// =============================================================================
+// Helper Functions
+// =============================================================================
+
+// The secret stored for the delivering shop, if it has one. Failed lookups
+// fall back to the app secret, so a shop with its own is refused until
+// Shopify retries.
+async fn shop_webhook_secret(state: &AppState, headers: &HeaderMap) -> Option<String> {
+    // Secrets live in Postgres
+    state.config.database.database_url.as_ref()?;
+    let shop = header_value(headers, "X-Shopify-Shop-Domain")?;
+    match state.shop_secrets.get::<WebhookSecret>(shop).await {
+        Ok(secret) => secret.map(|secret| secret.api_secret),
+        Err(e) => {
+            warn!("Failed to look up the webhook secret for {}, using the app secret: {}", shop, e);
+            None
         }
+    }
+}
+
+// Store the delivery exactly as received so it can be inspected later via
+// /admin/webhooks/events/:id. Storage failures never block the webhook.
+async fn capture_delivery(
+    state: &AppState,
+    headers: &HeaderMap,
+    topic: &str,
+    body: &[u8],
+    verified: bool,
+) -> Option<Uuid> {
+    let shop = header_value(headers, "X-Shopify-Shop-Domain").unwrap_or(&state.config.shop);
+    let webhook_id = header_value(headers, "X-Shopify-Webhook-Id");
+    
+    let header_json: serde_json::Map<String, serde_json::Value> = headers
+        .iter()
+        .filter_map(|(name, value)| {
+            value.to_str().ok().map(|v| (name.to_string(), serde_json::Value::String(v.to_string())))
+        })
+        .collect();
+    
+    match state.webhook_events
+        .capture_delivery(shop, topic, webhook_id, &serde_json::Value::Object(header_json), body, verified)
+        .await
+    {
+        Ok(id) => Some(id),
         Err(e) => {
-            error!("Failed to parse checkout webhook: {}", e);
-            (
-                StatusCode::BAD_REQUEST,
-                Json(WebhookResponse::error("Failed to parse checkout data")),
-            )
+            error!("Failed to capture {} webhook delivery: {}", topic, e);
+            None
         }
     }
 }
 
-pub async fn checkouts_updated_webhook(
-    State(state): State<AppState>,
-    headers: HeaderMap,
-    body: Bytes,
-) -> impl IntoResponse {
-    debug!("Received checkout updated webhook");
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+// Drops cached reads the delivery makes stale, pushes it to live
+// subscribers, samples it to staging, then queues it: as a durable job when
+// Postgres is available, in memory otherwise. Shopify only needs a fast 2xx.
+async fn queue_webhook_event(state: &AppState, webhook: QueuedWebhook) {
+    let topic = webhook.topic.clone();
     
-    if let Err(e) = verify_webhook_request(&headers, &body, &state.config.api_secret).await {
-        warn!("Webhook verification failed: {}", e);
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(WebhookResponse::error("Webhook verification failed")),
-        );
+    // Cached reads of the resource are stale from now on
+    if let Some(group) = CacheGroup::for_topic(&topic) {
+        state.response_cache.invalidate(&webhook.shop_domain, group).await;
     }
+    
+    state.events.publish(&webhook);
+    
+    if let Some(ref sampler) = state.webhook_sampler {
+        sampler.maybe_forward(&webhook);
+    }
+    
+    if state.job_queue.is_enabled() {
+        match serde_json::to_value(&webhook) {
+            Ok(job) => match state.job_queue.enqueue(WEBHOOK_JOB, Some(&webhook.shop_domain), job).await {
+                Ok(_) => return,
+                Err(e) => error!("Failed to queue {} webhook as a job, processing it in memory: {}", topic, e),
+            },
+            Err(e) => error!("Failed to encode {} webhook as a job: {}", topic, e),
+        }
+    }
+    state.webhook_queue.dispatch(webhook).await;
+}
+
+/// Job kind applying queued webhooks, one at a time per shop so
+/// `orders/updated` can't overtake `orders/create`.
+pub const WEBHOOK_JOB: &str = "webhook";
+
+pub fn webhook_job_kind(processor: WebhookProcessor) -> JobKind {
+    JobKind::new(WEBHOOK_JOB, ShopOrdering::Serial, move |_state: AppState, job: QueuedJob| {
+        let processor = processor.clone();
+        async move {
+            let webhook: QueuedWebhook = serde_json::from_value(job.payload)
+                .map_err(|e| format!("Invalid webhook job payload: {}", e))?;
+            processor(webhook).await
+        }
+    })
+}
 
-    match serde_json::from_slice::<CheckoutWebhook>(&body) {
-        Ok(checkout) => {
-            info!("📝 Checkout updated: {} - ${}", checkout.token, checkout.total_price);
+/// Worker-side processing for queued webhooks: files the captured delivery
+/// under its resource (so order history can be reconstructed), keeps the
+/// local order copy, catalog and customer index current, publishes the
+/// domain event it stands for to `domain_events`, records the attempt, and
+/// erases customers on `customers/redact`.
+/// Fails if any step did, so a queued job is retried; every step is safe to
+/// repeat.
+pub fn webhook_processor(
+    events: WebhookEventStore,
+    domain_events: DomainEventRegistry,
+    orders: Option<OrderStore>,
+    catalog: Option<CatalogStore>,
+    customers: Option<CustomerMirrorStore>,
+    personal_data: Option<PersonalDataStore>,
+) -> WebhookProcessor {
+    Arc::new(move |webhook: QueuedWebhook| {
+        let events = events.clone();
+        let domain_events = domain_events.clone();
+        let orders = orders.clone();
+        let catalog = catalog.clone();
+        let customers = customers.clone();
+        let personal_data = personal_data.clone();
+        Box::pin(async move {
+            let mut failure = None;
             
-            // Handle checkout update logic here
+            // Keep the local order copy current between syncs
+            if let Some(ref orders) = orders {
+                if webhook.topic.starts_with("orders/") {
+                    if let Err(e) = apply_order_webhook(orders, &webhook.shop_domain, &webhook.topic, &webhook.payload).await {
+                        error!("Failed to apply {} webhook to local orders: {}", webhook.topic, e);
+                        failure.get_or_insert(format!("Applying to local orders: {}", e));
+                    }
+                }
+            }
             
-            (
-                StatusCode::OK,
-                Json(WebhookResponse::success(&format!("Checkout {} update processed", checkout.id))),
-            )
+            if let Some(ref catalog) = catalog {
+                if webhook.topic.starts_with("products/") {
+                    if let Err(e) = apply_product_webhook(catalog, &webhook.shop_domain, &webhook.topic, &webhook.payload).await {
+                        error!("Failed to apply {} webhook to the catalog: {}", webhook.topic, e);
+                        failure.get_or_insert(format!("Applying to the catalog: {}", e));
+                    }
+                }
+            }
+            
+            if let Some(ref customers) = customers {
+                if webhook.topic.starts_with("customers/") {
+                    if let Err(e) = apply_customer_webhook(customers, &webhook.shop_domain, &webhook.topic, &webhook.payload).await {
+                        error!("Failed to apply {} webhook to the customer index: {}", webhook.topic, e);
+                        failure.get_or_insert(format!("Applying to the customer index: {}", e));
+                    }
+                }
+            }
+            
+            // Business logic, once the local copies are current
+            if let Some(event) = DomainEvent::from_webhook(&webhook.topic, &webhook.payload) {
+                if let Err(e) = domain_events.publish(&webhook.shop_domain, &event).await {
+                    failure.get_or_insert(format!("Handling {}: {}", event.name(), e));
+                }
+            }
+            
+            let result = match webhook.event_id {
+                Some(id) => events.mark_processed(&webhook.shop_domain, id, webhook.resource_id, failure.as_deref()).await,
+                // Capture failed at receipt; store what we have now
+                None => events
+                    .record_event(
+                        &webhook.shop_domain,
+                        &webhook.topic,
+                        webhook.resource_id,
+                        webhook.webhook_id.as_deref(),
+                        &webhook.payload,
+                    )
+                    .await
+                    .map(|_| ()),
+            };
+            
+            if let Err(e) = result {
+                error!("Failed to record {} webhook event: {}", webhook.topic, e);
+                failure.get_or_insert(format!("Recording the event: {}", e));
+            }
+            
+            // Last, so the redaction request's own delivery is scrubbed too
+            if let Some(ref personal_data) = personal_data {
+                if webhook.topic == "customers/redact" {
+                    if let Err(e) = apply_customer_redaction(personal_data, &webhook.shop_domain, &webhook.payload).await {
+                        error!("Failed to redact customer {:?}: {}", webhook.resource_id, e);
+                        failure.get_or_insert(format!("Redacting the customer: {}", e));
+                    }
+                }
+            }
+            
+            failure.map_or(Ok(()), Err)
+        })
+    })
+}
+
+// =============================================================================
+// Webhook Event Debugging
+// =============================================================================
+
+/// Returns a captured delivery with its raw body, headers, verification result,
+/// and processing attempts — exactly what Shopify sent us.
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks/events/{id}",
+    tag = "webhooks",
+    params(("id" = Uuid, Path, description = "Captured webhook event ID")),
+    responses(
+        (status = 200, description = "The delivery as received", body = crate::openapi::WebhookEventDetail),
+        (status = 404, description = "No such event", body = crate::openapi::ErrorResponse),
+    ),
+)]
+pub async fn webhook_event_handler(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match state.webhook_events.get_event(id).await {
+        Ok(Some(event)) => {
+            let raw_body = event.raw_body.unwrap_or_default();
+            (StatusCode::OK, Json(serde_json::json!({
+                "id": event.id,
+                "shop_domain": event.shop_domain,
+                "topic": event.topic,
+                "resource_id": event.resource_id,
+                "webhook_id": event.webhook_id,
+                "received_at": event.received_at,
+                "verified": event.verified,
+                "headers": event.headers,
+                "raw_body": String::from_utf8_lossy(&raw_body),
+                "raw_body_base64": general_purpose::STANDARD.encode(&raw_body),
+                "processing": {
+                    "attempts": event.processing_attempts,
+                    "processed_at": event.processed_at,
+                    "last_error": event.last_error
+                }
+            })))
         }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Webhook event not found" })),
+        ),
         Err(e) => {
-            error!("Failed to parse checkout update webhook: {}", e);
+            error!("Failed to load webhook event {}: {}", id, e);
             (
-                StatusCode::BAD_REQUEST,
-                Json(WebhookResponse::error("Failed to parse checkout update data")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Failed to load webhook event",
+                    "details": e.to_string()
+                })),
             )
         }
     }
 }
 
-// =============================================================================
-// Helper Functions
-// =============================================================================
-
-async fn verify_webhook_request(
-    headers: &HeaderMap,
-    body: &[u8],
-    secret: &str,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let signature = headers
-        .get("X-Shopify-Hmac-Sha256")
-        .and_then(|v| v.to_str().ok())
-        .ok_or("Missing X-Shopify-Hmac-Sha256 header")?;
-
-    if !verify_webhook(body, signature, secret)? {
-        return Err("Invalid webhook signature".into());
-    }
+/// A Shopify topic this app handles and the route it is delivered to.
+pub struct SupportedWebhook {
+    pub topic: &'static str,
+    pub endpoint: &'static str,
+    pub description: &'static str,
+}
 
-    // Additional verification: check shop domain if available
-    if let Some(shop_domain) = headers.get("X-Shopify-Shop-Domain").and_then(|v| v.to_str().ok()) {
-        debug!("Webhook from shop: {}", shop_domain);
-        // You could add additional validation here to ensure the webhook is from the expected shop
-    }
+pub const SUPPORTED_WEBHOOKS: &[SupportedWebhook] = &[
+    SupportedWebhook { topic: "orders/create", endpoint: "/webhooks/orders/created", description: "Triggered when a new order is created" },
+    SupportedWebhook { topic: "orders/updated", endpoint: "/webhooks/orders/updated", description: "Triggered when an order is updated" },
+    SupportedWebhook { topic: "orders/cancelled", endpoint: "/webhooks/orders/cancelled", description: "Triggered when an order is cancelled" },
+    SupportedWebhook { topic: "orders/paid", endpoint: "/webhooks/orders/paid", description: "Triggered when an order is paid" },
+    SupportedWebhook { topic: "orders/fulfilled", endpoint: "/webhooks/orders/fulfilled", description: "Triggered when an order is fulfilled" },
+    SupportedWebhook { topic: "orders/risk_assessment_changed", endpoint: "/webhooks/orders/risk_assessment_changed", description: "Triggered when an order's fraud risk assessment changes" },
+    SupportedWebhook { topic: "refunds/create", endpoint: "/webhooks/refunds/created", description: "Triggered when a refund is created" },
+    SupportedWebhook { topic: "products/create", endpoint: "/webhooks/products/created", description: "Triggered when a new product is created" },
+    SupportedWebhook { topic: "products/update", endpoint: "/webhooks/products/updated", description: "Triggered when a product or its variants change" },
+    SupportedWebhook { topic: "products/delete", endpoint: "/webhooks/products/deleted", description: "Triggered when a product is deleted" },
+    SupportedWebhook { topic: "inventory_levels/update", endpoint: "/webhooks/inventory_levels/updated", description: "Triggered when an item's stock at a location changes" },
+    SupportedWebhook { topic: "customers/create", endpoint: "/webhooks/customers/created", description: "Triggered when a new customer is created" },
+    SupportedWebhook { topic: "customers/update", endpoint: "/webhooks/customers/updated", description: "Triggered when a customer is updated" },
+    SupportedWebhook { topic: "customers/delete", endpoint: "/webhooks/customers/deleted", description: "Triggered when a customer is deleted" },
+    SupportedWebhook { topic: "customers/redact", endpoint: "/webhooks/customers/redact", description: "GDPR request to erase a customer's personal data" },
+    SupportedWebhook { topic: "checkouts/create", endpoint: "/webhooks/checkouts/created", description: "Triggered when a new checkout is created" },
+    SupportedWebhook { topic: "checkouts/update", endpoint: "/webhooks/checkouts/updated", description: "Triggered when a checkout is updated" },
+    SupportedWebhook { topic: "app/uninstalled", endpoint: "/webhooks/app/uninstalled", description: "Triggered when a shop uninstalls the app" },
+];
 
-    Ok(())
-}
+/// Mandatory compliance topics, which are subscribed to in the Partner
+/// Dashboard rather than through the webhooks API.
+pub const COMPLIANCE_TOPICS: &[&str] = &["customers/redact"];
 
 // Webhook management endpoint to list configured webhooks
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Topics this app handles and where they're delivered", body = crate::openapi::SupportedWebhookList),
+    ),
+)]
 pub async fn list_webhooks_handler(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
 ) -> impl IntoResponse {
-    // This would typically fetch webhooks from Shopify API
-    // For now, return the endpoints this app supports
-    
     let supported_webhooks = serde_json::json!({
-        "supported_webhooks": [
-            {
-                "topic": "orders/create",
-                "endpoint": "/webhooks/orders/created",
-                "description": "Triggered when a new order is created"
-            },
-            {
-                "topic": "orders/updated",
-                "endpoint": "/webhooks/orders/updated", 
-                "description": "Triggered when an order is updated"
-            },
-            {
-                "topic": "orders/cancelled",
-                "endpoint": "/webhooks/orders/cancelled",
-                "description": "Triggered when an order is cancelled"
-            },
-            {
-                "topic": "products/create",
-                "endpoint": "/webhooks/products/created",
-                "description": "Triggered when a new product is created"
-            },
-            {
-                "topic": "customers/create",
-                "endpoint": "/webhooks/customers/created",
-                "description": "Triggered when a new customer is created"
-            },
-            {
-                "topic": "checkouts/create",
-                "endpoint": "/webhooks/checkouts/created",
-                "description": "Triggered when a new checkout is created"
-            },
-            {
-                "topic": "checkouts/update",
-                "endpoint": "/webhooks/checkouts/updated",
-                "description": "Triggered when a checkout is updated"
-            }
-        ],
+        "supported_webhooks": SUPPORTED_WEBHOOKS
+            .iter()
+            .map(|webhook| serde_json::json!({
+                "topic": webhook.topic,
+                "endpoint": webhook.endpoint,
+                "description": webhook.description
+            }))
+            .collect::<Vec<_>>(),
+        "registration_base_url": state.config.webhook_base_url,
         "webhook_verification": "HMAC SHA256 with API secret",
         "format": "JSON"
     });
 
     (StatusCode::OK, Json(supported_webhooks))
 }
+
+// =============================================================================
+// Webhook Registration
+// =============================================================================
+
+#[derive(Deserialize)]
+struct WebhookSubscription {
+    topic: String,
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct WebhookSubscriptionsResponse {
+    webhooks: Vec<WebhookSubscription>,
+}
+
+/// Subscribes `shop` to every supported topic it isn't already subscribed to
+/// at `base_url`, compliance topics aside, leaving other subscriptions alone. A topic Shopify refuses
+/// (usually for a missing scope) is logged and skipped. Returns the number
+/// of subscriptions created.
+pub async fn reconcile_webhook_subscriptions(
+    shopify: &ShopifyClient,
+    shop: &str,
+    token: &str,
+    base_url: &str,
+) -> AppResult<usize> {
+    let client = shopify.for_shop(shop);
+    let existing = client
+        .get_with_auth::<WebhookSubscriptionsResponse>("webhooks.json", token, Some(&[("limit", "250")]))
+        .await?
+        .data
+        .webhooks;
+
+    let mut created = 0;
+    for webhook in SUPPORTED_WEBHOOKS.iter().filter(|w| !COMPLIANCE_TOPICS.contains(&w.topic)) {
+        let address = format!("{}{}", base_url, webhook.endpoint);
+        if existing.iter().any(|s| s.topic == webhook.topic && s.address == address) {
+            continue;
+        }
+
+        let body = serde_json::json!({
+            "webhook": { "topic": webhook.topic, "address": address, "format": "json" }
+        });
+        match client.post_with_auth::<_, serde_json::Value>("webhooks.json", token, &body).await {
+            Ok(_) => {
+                info!("🪝 Subscribed {} to {} at {}", shop, webhook.topic, address);
+                created += 1;
+            }
+            Err(e) => warn!("Could not subscribe {} to {}: {}", shop, webhook.topic, e),
+        }
+    }
+    Ok(created)
+}
+
+// =============================================================================
+// Scheduled Jobs
+// =============================================================================
+
+/// Drops captured webhook deliveries past `WEBHOOK_EVENT_RETENTION_DAYS`.
+pub fn webhook_event_purge_job() -> Job {
+    Job::new("webhook-event-purge", Schedule::Every(Duration::from_secs(3600)), JobScope::Cluster, |state: AppState| async move {
+        let purged = state
+            .webhook_events
+            .purge_older_than(state.config.webhook_event_retention_days)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(format!("{} webhook events purged", purged))
+    })
+}
+
+/// Keeps every installed shop subscribed to the supported topics at
+/// `WEBHOOK_BASE_URL`, covering new installs and subscriptions Shopify
+/// dropped after repeated delivery failures.
+pub fn webhook_registration_job() -> Job {
+    Job::new("webhook-registration", Schedule::Every(Duration::from_secs(3600)), JobScope::Cluster, |state: AppState| async move {
+        let Some(base_url) = state.config.webhook_base_url.clone() else {
+            return Ok("WEBHOOK_BASE_URL is not set".to_string());
+        };
+        let shops = state.token_store.list_shops().await.map_err(|e| e.to_string())?;
+
+        let (mut created, mut failed) = (0, Vec::new());
+        for shop in &shops {
+            let result = match state.token_store.get_token(&shop.shop_domain).await {
+                Ok(Some(token)) => reconcile_webhook_subscriptions(&state.shopify, &shop.shop_domain, &token, &base_url).await,
+                Ok(None) => continue,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(count) => created += count,
+                Err(e) => {
+                    error!("Webhook registration failed for {}: {}", shop.shop_domain, e);
+                    failed.push(shop.shop_domain.clone());
+                }
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(format!("{} shops checked, {} subscriptions created", shops.len(), created))
+        } else {
+            Err(format!("Registration failed for {}", failed.join(", ")))
+        }
+    })
+    .run_on_start()
+}